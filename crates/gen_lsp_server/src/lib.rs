@@ -62,7 +62,7 @@
 use failure::{bail, format_err};
 
 mod msg;
-mod stdio;
+mod transport;
 
 use crossbeam_channel::{Receiver, Sender};
 use languageserver_types::{
@@ -70,16 +70,21 @@ use languageserver_types::{
     request::{Initialize, Shutdown},
     InitializeParams, InitializeResult, ServerCapabilities,
 };
+use serde_json::{to_value, Value};
 
 pub type Result<T> = ::std::result::Result<T, failure::Error>;
 pub use crate::{
     msg::{ErrorCode, RawMessage, RawNotification, RawRequest, RawResponse, RawResponseError},
-    stdio::{stdio_transport, Threads},
+    transport::{
+        spawn_transport, stdio_transport, tcp_connect_transport, tcp_listen_transport, Stdio,
+        Threads, Transport,
+    },
 };
 
 /// Main entry point: runs the server from initialization to shutdown.
 /// To attach server to standard input/output streams, use the `stdio_transport`
-/// function to create corresponding `sender` and `receiver` pair.
+/// function to create corresponding `sender` and `receiver` pair. For a socket,
+/// use `tcp_connect_transport` or `tcp_listen_transport`.
 ///
 /// `server` should use the `handle_shutdown` function to handle the `Shutdown`
 /// request.
@@ -88,9 +93,26 @@ pub fn run_server(
     receiver: Receiver<RawMessage>,
     sender: Sender<RawMessage>,
     server: impl FnOnce(InitializeParams, &Receiver<RawMessage>, &Sender<RawMessage>) -> Result<()>,
+) -> Result<()> {
+    run_server_with_init(
+        |_| Ok(to_value(InitializeResult { capabilities: caps })?),
+        receiver,
+        sender,
+        server,
+    )
+}
+
+/// Like `run_server`, but lets the server build the `InitializeResult` from
+/// the raw `initialize` params. This is useful for protocol extensions which
+/// are not (yet) a part of `languageserver_types`.
+pub fn run_server_with_init(
+    init: impl FnOnce(&Value) -> Result<Value>,
+    receiver: Receiver<RawMessage>,
+    sender: Sender<RawMessage>,
+    server: impl FnOnce(InitializeParams, &Receiver<RawMessage>, &Sender<RawMessage>) -> Result<()>,
 ) -> Result<()> {
     log::info!("lsp server initializes");
-    let params = initialize(&receiver, &sender, caps)?;
+    let params = initialize(&receiver, &sender, init)?;
     log::info!("lsp server initialized, serving requests");
     server(params, &receiver, &sender)?;
     log::info!("lsp server waiting for exit notification");
@@ -119,16 +141,24 @@ pub fn handle_shutdown(req: RawRequest, sender: &Sender<RawMessage>) -> Option<R
 fn initialize(
     receiver: &Receiver<RawMessage>,
     sender: &Sender<RawMessage>,
-    caps: ServerCapabilities,
+    init: impl FnOnce(&Value) -> Result<Value>,
 ) -> Result<InitializeParams> {
-    let (id, params) = match receiver.recv() {
-        Ok(RawMessage::Request(req)) => match req.cast::<Initialize>() {
-            Err(req) => bail!("expected initialize request, got {:?}", req),
-            Ok(req) => req,
-        },
+    let (id, raw_params, params) = match receiver.recv() {
+        Ok(RawMessage::Request(req)) => {
+            let raw_params = req.params.clone();
+            match req.cast::<Initialize>() {
+                Err(req) => bail!("expected initialize request, got {:?}", req),
+                Ok((id, params)) => (id, raw_params, params),
+            }
+        }
         msg => bail!("expected initialize request, got {:?}", msg),
     };
-    let resp = RawResponse::ok::<Initialize>(id, &InitializeResult { capabilities: caps });
+    let result = init(&raw_params)?;
+    let resp = RawResponse {
+        id,
+        result: Some(result),
+        error: None,
+    };
     sender.send(RawMessage::Response(resp)).unwrap();
     match receiver.recv() {
         Ok(RawMessage::Notification(n)) => {
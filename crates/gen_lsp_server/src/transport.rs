@@ -0,0 +1,149 @@
+use std::{
+    io::{self, stdin, stdout, BufReader, BufWriter, Read, Stdin, Stdout, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use failure::bail;
+
+use crate::{RawMessage, Result};
+
+/// A bidirectional byte stream between the client and the server, like
+/// standard input/output or a socket.
+pub trait Transport {
+    type Reader: Read + Send + 'static;
+    type Writer: Write + Send + 'static;
+
+    /// Splits the stream into halves, which are used from separate threads.
+    fn split(self) -> io::Result<(Self::Reader, Self::Writer)>;
+}
+
+/// Standard input and output of the process, which is how most editors
+/// communicate with a server they've spawned.
+pub struct Stdio;
+
+impl Transport for Stdio {
+    type Reader = Stdin;
+    type Writer = Stdout;
+
+    fn split(self) -> io::Result<(Stdin, Stdout)> {
+        Ok((stdin(), stdout()))
+    }
+}
+
+impl Transport for TcpStream {
+    type Reader = TcpStream;
+    type Writer = TcpStream;
+
+    fn split(self) -> io::Result<(TcpStream, TcpStream)> {
+        Ok((self.try_clone()?, self))
+    }
+}
+
+pub fn stdio_transport() -> (Receiver<RawMessage>, Sender<RawMessage>, Threads) {
+    spawn_transport(Stdio).expect("stdio can't fail to split")
+}
+
+/// Connects to a client which listens at `addr`.
+pub fn tcp_connect_transport(
+    addr: impl ToSocketAddrs,
+) -> Result<(Receiver<RawMessage>, Sender<RawMessage>, Threads)> {
+    let stream = TcpStream::connect(addr)?;
+    spawn_transport(stream)
+}
+
+/// Listens at `addr` and waits for a single client to connect.
+pub fn tcp_listen_transport(
+    addr: impl ToSocketAddrs,
+) -> Result<(Receiver<RawMessage>, Sender<RawMessage>, Threads)> {
+    let listener = TcpListener::bind(addr)?;
+    log::info!("waiting for a client at {}", listener.local_addr()?);
+    let (stream, client_addr) = listener.accept()?;
+    log::info!("client connected from {}", client_addr);
+    spawn_transport(stream)
+}
+
+/// Starts a reader and a writer thread, which parse and serialize messages
+/// to the transport.
+pub fn spawn_transport(
+    transport: impl Transport,
+) -> Result<(Receiver<RawMessage>, Sender<RawMessage>, Threads)> {
+    let (reader, writer) = transport.split()?;
+    let (writer_sender, writer_receiver) = bounded::<RawMessage>(16);
+    let writer = thread::spawn(move || {
+        let mut writer = BufWriter::new(writer);
+        writer_receiver
+            .into_iter()
+            .try_for_each(|it| it.write(&mut writer))?;
+        Ok(())
+    });
+    let (reader_sender, reader_receiver) = bounded::<RawMessage>(16);
+    let reader = thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        while let Some(msg) = RawMessage::read(&mut reader)? {
+            if let Err(_) = reader_sender.send(msg) {
+                break;
+            }
+        }
+        Ok(())
+    });
+    let threads = Threads { reader, writer };
+    Ok((reader_receiver, writer_sender, threads))
+}
+
+pub struct Threads {
+    reader: thread::JoinHandle<Result<()>>,
+    writer: thread::JoinHandle<Result<()>>,
+}
+
+impl Threads {
+    pub fn join(self) -> Result<()> {
+        match self.reader.join() {
+            Ok(r) => r?,
+            Err(_) => bail!("reader panicked"),
+        }
+        match self.writer.join() {
+            Ok(r) => r,
+            Err(_) => bail!("writer panicked"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use languageserver_types::request::Shutdown;
+
+    use crate::{RawRequest, RawResponse};
+
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (client_receiver, client_sender, _client_threads) =
+            tcp_connect_transport(addr).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (server_receiver, server_sender, _server_threads) = spawn_transport(stream).unwrap();
+
+        client_sender
+            .send(RawMessage::Request(RawRequest::new::<Shutdown>(92, &())))
+            .unwrap();
+        let id = match server_receiver.recv().unwrap() {
+            RawMessage::Request(req) => {
+                assert_eq!(req.method, "shutdown");
+                req.id
+            }
+            msg => panic!("expected a request, got {:?}", msg),
+        };
+
+        server_sender
+            .send(RawMessage::Response(RawResponse::ok::<Shutdown>(id, &())))
+            .unwrap();
+        match client_receiver.recv().unwrap() {
+            RawMessage::Response(resp) => assert_eq!(resp.id, 92),
+            msg => panic!("expected a response, got {:?}", msg),
+        }
+    }
+}
@@ -1,7 +1,14 @@
-use std::{fs, io::Read, path::Path, time::Instant};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
 
 use clap::{App, Arg, SubCommand};
 use join_to_string::join;
+use ra_ide_api::{Analysis, FileId, Severity};
 use ra_ide_api_light::{extend_selection, file_structure, syntax_tree};
 use ra_syntax::{SourceFile, TextRange, TreeArc, AstNode};
 use tools::collect_tests;
@@ -27,7 +34,21 @@ fn main() -> Result<()> {
                 ),
         )
         .subcommand(SubCommand::with_name("parse").arg(Arg::with_name("no-dump").long("--no-dump")))
-        .subcommand(SubCommand::with_name("symbols"))
+        .subcommand(SubCommand::with_name("symbols").arg(Arg::with_name("file")))
+        .subcommand(
+            SubCommand::with_name("analysis-stats").arg(Arg::with_name("path").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("diagnostics")
+                .arg(Arg::with_name("path").required(true))
+                .arg(
+                    Arg::with_name("format")
+                        .long("--format")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("extend-selection")
                 .arg(Arg::with_name("start"))
@@ -45,12 +66,26 @@ fn main() -> Result<()> {
             eprintln!("parsing: {:?}", elapsed);
             ::std::mem::forget(file);
         }
-        ("symbols", _) => {
-            let file = file()?;
+        ("symbols", Some(matches)) => {
+            let file = match matches.value_of("file") {
+                Some(path) => SourceFile::parse(&fs::read_to_string(path)?),
+                None => file()?,
+            };
             for s in file_structure(&file) {
                 println!("{:?}", s);
             }
         }
+        ("analysis-stats", Some(matches)) => {
+            let path = Path::new(matches.value_of("path").unwrap());
+            analysis_stats(path)?;
+        }
+        ("diagnostics", Some(matches)) => {
+            let path = Path::new(matches.value_of("path").unwrap());
+            let json = matches.value_of("format") == Some("json");
+            if diagnostics(path, json)? {
+                process::exit(1);
+            }
+        }
         ("render-test", Some(matches)) => {
             let file = matches.value_of("file").unwrap();
             let file = Path::new(file);
@@ -82,6 +117,90 @@ fn read_stdin() -> Result<String> {
     Ok(buff)
 }
 
+fn analysis_stats(path: &Path) -> Result<()> {
+    let start = Instant::now();
+    let ws = ra_lsp_server::load_cargo(path)?;
+    eprintln!("loading: {:?}", start.elapsed());
+
+    let analysis = ws.host.analysis();
+    let start = Instant::now();
+    let stats = analysis.infer_all_functions()?;
+    eprintln!(
+        "inference of {} functions in {} files: {:?}",
+        stats.functions,
+        stats.files,
+        start.elapsed()
+    );
+    if let Some(rss) = memory_usage() {
+        eprintln!("memory: {}mb", rss / (1024 * 1024));
+    }
+    eprint!("{}", analysis.status());
+    Ok(())
+}
+
+/// Prints the diagnostics for all files of the workspace at `path`, either like
+/// rustc does or as one JSON object per line. Returns whether there were
+/// any errors.
+fn diagnostics(path: &Path, json: bool) -> Result<bool> {
+    let ws = ra_lsp_server::load_cargo(path)?;
+    let analysis = ws.host.analysis();
+    let stdout = io::stdout();
+    write_diagnostics(&analysis, &ws.local_files, json, &mut stdout.lock())
+}
+
+fn write_diagnostics(
+    analysis: &Analysis,
+    files: &[(FileId, PathBuf)],
+    json: bool,
+    out: &mut impl Write,
+) -> Result<bool> {
+    let mut has_errors = false;
+    for (file_id, path) in files.iter() {
+        let line_index = analysis.file_line_index(*file_id);
+        for d in analysis.diagnostics(*file_id)? {
+            let severity = match d.severity {
+                Severity::Error => {
+                    has_errors = true;
+                    "error"
+                }
+                Severity::WeakWarning => "warning",
+            };
+            let start = line_index.line_col(d.range.start());
+            let end = line_index.line_col(d.range.end());
+            if json {
+                let d = serde_json::json!({
+                    "file": path.display().to_string(),
+                    "severity": severity,
+                    "message": d.message,
+                    "start": { "line": start.line + 1, "column": start.col_utf16 + 1 },
+                    "end": { "line": end.line + 1, "column": end.col_utf16 + 1 },
+                });
+                writeln!(out, "{}", d)?;
+            } else {
+                writeln!(out, "{}: {}", severity, d.message)?;
+                writeln!(
+                    out,
+                    "  --> {}:{}:{}\n",
+                    path.display(),
+                    start.line + 1,
+                    start.col_utf16 + 1
+                )?;
+            }
+        }
+    }
+    Ok(has_errors)
+}
+
+/// The size of a memory page, in which `/proc/self/statm` counts memory.
+const PAGE_SIZE: usize = 4096;
+
+/// The resident set size of the process in bytes. Only implemented on Linux.
+fn memory_usage() -> Option<usize> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * PAGE_SIZE)
+}
+
 fn render_test(file: &Path, line: usize) -> Result<(String, String)> {
     let text = fs::read_to_string(file)?;
     let tests = collect_tests(&text);
@@ -113,3 +232,41 @@ fn selections(file: &SourceFile, start: u32, end: u32) -> String {
         .surround_with("(", ")")
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use ra_ide_api::mock_analysis::single_file;
+
+    use super::*;
+
+    #[test]
+    fn diagnostics_are_written_like_rustc_or_as_json() {
+        let (analysis, file_id) = single_file("fn main() {}\nmod foo;\n");
+        let files = vec![(file_id, PathBuf::from("/ws/src/main.rs"))];
+
+        let mut human = Vec::new();
+        assert!(write_diagnostics(&analysis, &files, false, &mut human).unwrap());
+        assert_eq!(
+            String::from_utf8(human).unwrap(),
+            "error: unresolved module\n  --> /ws/src/main.rs:2:5\n\n"
+        );
+
+        let mut json = Vec::new();
+        assert!(write_diagnostics(&analysis, &files, true, &mut json).unwrap());
+        let d: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(d["file"], "/ws/src/main.rs");
+        assert_eq!(d["severity"], "error");
+        assert_eq!(d["message"], "unresolved module");
+        assert_eq!(d["start"], serde_json::json!({ "line": 2, "column": 5 }));
+        assert_eq!(d["end"], serde_json::json!({ "line": 2, "column": 8 }));
+    }
+
+    #[test]
+    fn no_errors_without_diagnostics() {
+        let (analysis, file_id) = single_file("fn main() {}\n");
+        let files = vec![(file_id, PathBuf::from("/ws/src/main.rs"))];
+        let mut out = Vec::new();
+        assert!(!write_diagnostics(&analysis, &files, false, &mut out).unwrap());
+        assert!(out.is_empty());
+    }
+}
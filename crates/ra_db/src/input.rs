@@ -56,16 +56,38 @@ pub struct CrateGraph {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CrateId(pub u32);
 
+/// The edition of the Rust language a crate is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edition {
+    Edition2015,
+    Edition2018,
+}
+
+impl Edition {
+    /// Parses the edition as written in `Cargo.toml`. Unknown editions are
+    /// treated as the latest one.
+    pub fn from_string(s: &str) -> Edition {
+        match s {
+            "2015" => Edition::Edition2015,
+            _ => Edition::Edition2018,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
+    edition: Edition,
+    display_name: Option<SmolStr>,
     dependencies: Vec<Dependency>,
 }
 
 impl CrateData {
-    fn new(file_id: FileId) -> CrateData {
+    fn new(file_id: FileId, edition: Edition) -> CrateData {
         CrateData {
             file_id,
+            edition,
+            display_name: None,
             dependencies: Vec::new(),
         }
     }
@@ -78,6 +100,9 @@ impl CrateData {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Dependency {
     pub crate_id: CrateId,
+    /// The name the dependency is known as in the dependent crate. This is not
+    /// necessarily the name of the dependency itself: Cargo allows renaming
+    /// dependencies.
     pub name: SmolStr,
 }
 
@@ -88,9 +113,9 @@ impl Dependency {
 }
 
 impl CrateGraph {
-    pub fn add_crate_root(&mut self, file_id: FileId) -> CrateId {
+    pub fn add_crate_root(&mut self, file_id: FileId, edition: Edition) -> CrateId {
         let crate_id = CrateId(self.arena.len() as u32);
-        let prev = self.arena.insert(crate_id, CrateData::new(file_id));
+        let prev = self.arena.insert(crate_id, CrateData::new(file_id, edition));
         assert!(prev.is_none());
         crate_id
     }
@@ -107,6 +132,17 @@ impl CrateGraph {
     pub fn crate_root(&self, crate_id: CrateId) -> FileId {
         self.arena[&crate_id].file_id
     }
+    pub fn edition(&self, crate_id: CrateId) -> Edition {
+        self.arena[&crate_id].edition
+    }
+    /// Sets the name of the crate as shown to the user, like the name of the
+    /// Cargo target. It plays no part in name resolution.
+    pub fn set_display_name(&mut self, crate_id: CrateId, name: SmolStr) {
+        self.arena.get_mut(&crate_id).unwrap().display_name = Some(name);
+    }
+    pub fn display_name(&self, crate_id: CrateId) -> Option<&SmolStr> {
+        self.arena[&crate_id].display_name.as_ref()
+    }
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self
             .arena
@@ -120,6 +156,39 @@ impl CrateGraph {
     ) -> impl Iterator<Item = &'a Dependency> + 'a {
         self.arena[&crate_id].dependencies.iter()
     }
+    /// The name under which some other crate depends on `crate_id`. Crates
+    /// don't have names of their own, so this is `None` for crates nobody
+    /// depends on, like the one of the current package.
+    pub fn dependency_name(&self, crate_id: CrateId) -> Option<&SmolStr> {
+        self.arena
+            .values()
+            .flat_map(|data| data.dependencies.iter())
+            .find(|dep| dep.crate_id == crate_id)
+            .map(|dep| &dep.name)
+    }
+    /// Returns `crate_id` together with all the crates which depend on it,
+    /// directly or through other crates. These are the crates affected by a
+    /// change to `crate_id`.
+    pub fn transitive_reverse_dependencies(&self, crate_id: CrateId) -> Vec<CrateId> {
+        let mut res = vec![crate_id];
+        let mut visited = FxHashSet::default();
+        visited.insert(crate_id);
+        let mut i = 0;
+        while i < res.len() {
+            let target = res[i];
+            i += 1;
+            let mut dependents: Vec<CrateId> = self
+                .arena
+                .iter()
+                .filter(|(_, data)| data.dependencies.iter().any(|dep| dep.crate_id == target))
+                .map(|(&id, _)| id)
+                .filter(|id| visited.insert(*id))
+                .collect();
+            dependents.sort();
+            res.extend(dependents);
+        }
+        res
+    }
     fn dfs_find(&self, target: CrateId, from: CrateId, visited: &mut FxHashSet<CrateId>) -> bool {
         if !visited.insert(from) {
             return false;
@@ -141,15 +210,15 @@ impl CrateGraph {
 
 #[cfg(test)]
 mod tests {
-    use super::{CrateGraph, FxHashMap, FileId, SmolStr};
+    use super::{CrateGraph, FxHashMap, FileId, SmolStr, Edition::Edition2018};
 
     #[test]
     #[should_panic]
     fn it_should_painc_because_of_cycle_dependencies() {
         let mut graph = CrateGraph::default();
-        let crate1 = graph.add_crate_root(FileId(1u32));
-        let crate2 = graph.add_crate_root(FileId(2u32));
-        let crate3 = graph.add_crate_root(FileId(3u32));
+        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
+        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
+        let crate3 = graph.add_crate_root(FileId(3u32), Edition2018);
         graph.add_dep(crate1, SmolStr::new("crate2"), crate2);
         graph.add_dep(crate2, SmolStr::new("crate3"), crate3);
         graph.add_dep(crate3, SmolStr::new("crate1"), crate1);
@@ -160,11 +229,28 @@ mod tests {
         let mut graph = CrateGraph {
             arena: FxHashMap::default(),
         };
-        let crate1 = graph.add_crate_root(FileId(1u32));
-        let crate2 = graph.add_crate_root(FileId(2u32));
-        let crate3 = graph.add_crate_root(FileId(3u32));
+        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
+        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
+        let crate3 = graph.add_crate_root(FileId(3u32), Edition2018);
+        graph.add_dep(crate1, SmolStr::new("crate2"), crate2);
+        graph.add_dep(crate2, SmolStr::new("crate3"), crate3);
+    }
+
+    #[test]
+    fn transitive_reverse_dependencies_include_indirect_dependents() {
+        let mut graph = CrateGraph::default();
+        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
+        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
+        let crate3 = graph.add_crate_root(FileId(3u32), Edition2018);
+        let crate4 = graph.add_crate_root(FileId(4u32), Edition2018);
         graph.add_dep(crate1, SmolStr::new("crate2"), crate2);
         graph.add_dep(crate2, SmolStr::new("crate3"), crate3);
+        graph.add_dep(crate4, SmolStr::new("crate2"), crate2);
+        assert_eq!(
+            graph.transitive_reverse_dependencies(crate3),
+            vec![crate3, crate2, crate1, crate4]
+        );
+        assert_eq!(graph.transitive_reverse_dependencies(crate1), vec![crate1]);
     }
 }
 
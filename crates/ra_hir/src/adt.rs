@@ -50,7 +50,7 @@ impl StructData {
     }
 }
 
-fn get_def_id(
+pub(crate) fn get_def_id(
     db: &impl HirDatabase,
     same_file_loc: &DefLoc,
     node: &SyntaxNode,
@@ -160,6 +160,12 @@ pub struct StructField {
     pub(crate) type_ref: TypeRef,
 }
 
+impl StructField {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+}
+
 /// Fields of an enum variant or struct
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VariantData {
@@ -2,17 +2,19 @@ use std::sync::Arc;
 
 use relative_path::RelativePathBuf;
 use ra_db::{CrateId, Cancelable, FileId};
-use ra_syntax::{ast, TreeArc, SyntaxNode};
+use ra_syntax::{ast, AstNode, SmolStr, TreeArc, SyntaxNode, TextRange};
 
 use crate::{
-    Name, DefId, Path, PerNs, ScopesWithSyntaxMapping, Ty, HirFileId,
+    Name, DefId, Path, PerNs, ScopesWithSyntaxMapping, Ty, HirFileId, MissingMatchArms,
     type_ref::TypeRef,
-    nameres::ModuleScope,
+    nameres::{ModuleScope, NamedImport},
     db::HirDatabase,
-    expr::BodySyntaxMapping,
+    expr::{BodySyntaxMapping, ExprId},
     ty::InferenceResult,
     adt::VariantData,
     code_model_impl::def_id_to_ast,
+    impl_block::{ImplId, ImplItem, ModuleImplBlocks},
+    traits::{self, ObjectSafetyViolation},
 };
 
 /// hir::Crate describes a single crate. It's the main interface with which
@@ -36,6 +38,11 @@ impl Crate {
     pub fn dependencies(&self, db: &impl HirDatabase) -> Cancelable<Vec<CrateDependency>> {
         Ok(self.dependencies_impl(db))
     }
+    /// This crate and the crates which depend on it, directly or through
+    /// other crates.
+    pub fn transitive_reverse_dependencies(&self, db: &impl HirDatabase) -> Vec<Crate> {
+        self.transitive_reverse_dependencies_impl(db)
+    }
     pub fn root_module(&self, db: &impl HirDatabase) -> Cancelable<Option<Module>> {
         self.root_module_impl(db)
     }
@@ -67,13 +74,21 @@ pub enum ModuleSource {
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Problem {
+    /// `mod foo;` declaration without a corresponding file. `candidates` lists
+    /// the paths where the file for the module might be created.
     UnresolvedModule {
-        candidate: RelativePathBuf,
+        candidates: Vec<RelativePathBuf>,
     },
     NotDirOwner {
         move_to: RelativePathBuf,
         candidate: RelativePathBuf,
     },
+    /// An item with the same name and namespace as an earlier item of the
+    /// module. `first` is the range of the name of the earlier item, in the
+    /// same file.
+    DuplicateDefinition {
+        first: TextRange,
+    },
 }
 
 impl Module {
@@ -139,6 +154,17 @@ impl Module {
     }
 
     pub fn resolve_path(&self, db: &impl HirDatabase, path: &Path) -> Cancelable<PerNs<DefId>> {
+        self.resolve_path_impl(db, path).map(|(def_id, _)| def_id)
+    }
+
+    /// Like `resolve_path`, but also returns the `use` item through which the
+    /// last segment of the path was imported into its module, if any. For
+    /// `foo::Bar`, this is the `pub use` of `Bar` in `foo`.
+    pub fn resolve_path_with_import(
+        &self,
+        db: &impl HirDatabase,
+        path: &Path,
+    ) -> Cancelable<(PerNs<DefId>, Option<NamedImport>)> {
         self.resolve_path_impl(db, path)
     }
 
@@ -148,6 +174,11 @@ impl Module {
     ) -> Cancelable<Vec<(TreeArc<SyntaxNode>, Problem)>> {
         self.problems_impl(db)
     }
+
+    /// Returns all impl blocks defined directly in this module.
+    pub fn impl_blocks(&self, db: &impl HirDatabase) -> Cancelable<Vec<ImplBlock>> {
+        self.impl_blocks_impl(db)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -160,6 +191,9 @@ impl StructField {
     pub fn name(&self) -> &Name {
         &self.name
     }
+    pub fn parent_struct(&self) -> Struct {
+        self.struct_.clone()
+    }
     pub fn ty(&self, db: &impl HirDatabase) -> Cancelable<Option<Ty>> {
         db.type_for_field(self.struct_.def_id, self.name.clone())
     }
@@ -193,6 +227,10 @@ impl Struct {
         Ok(res)
     }
 
+    pub fn variant_data(&self, db: &impl HirDatabase) -> Cancelable<Arc<VariantData>> {
+        Ok(db.struct_data(self.def_id)?.variant_data.clone())
+    }
+
     pub fn source(
         &self,
         db: &impl HirDatabase,
@@ -274,10 +312,33 @@ pub use crate::code_model_impl::function::ScopeEntryWithSyntax;
 pub struct FnSignature {
     pub(crate) name: Name,
     pub(crate) params: Vec<TypeRef>,
+    pub(crate) param_names: Vec<Option<Name>>,
     pub(crate) ret_type: TypeRef,
     /// True if the first param is `self`. This is relevant to decide whether this
     /// can be called as a method.
     pub(crate) has_self_param: bool,
+    pub(crate) type_params: Vec<Name>,
+    pub(crate) where_predicates: Vec<WherePredicate>,
+    pub(crate) attrs: Vec<SmolStr>,
+    pub(crate) is_unsafe: bool,
+}
+
+/// A single `T: Bound1 + Bound2` predicate from a where clause. Lifetime
+/// predicates are not represented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WherePredicate {
+    pub(crate) type_ref: TypeRef,
+    pub(crate) bounds: Vec<TypeRef>,
+}
+
+impl WherePredicate {
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+
+    pub fn bounds(&self) -> &[TypeRef] {
+        &self.bounds
+    }
 }
 
 impl FnSignature {
@@ -289,6 +350,12 @@ impl FnSignature {
         &self.params
     }
 
+    /// Names of the params, in the same order as `params`. `None` is used for
+    /// params which are not simple bindings, like `(a, b): (i32, i32)`.
+    pub fn param_names(&self) -> &[Option<Name>] {
+        &self.param_names
+    }
+
     pub fn ret_type(&self) -> &TypeRef {
         &self.ret_type
     }
@@ -298,6 +365,37 @@ impl FnSignature {
     pub fn has_self_param(&self) -> bool {
         self.has_self_param
     }
+
+    /// Names of the generic type parameters, `T` in `fn foo<T>()`.
+    pub fn type_params(&self) -> &[Name] {
+        &self.type_params
+    }
+
+    pub fn where_predicates(&self) -> &[WherePredicate] {
+        &self.where_predicates
+    }
+
+    /// Names of the attributes on the function, like `test` or `deprecated`.
+    pub fn attrs(&self) -> &[SmolStr] {
+        &self.attrs
+    }
+
+    /// True for `unsafe fn`s, which can be called only in `unsafe` code.
+    pub fn is_unsafe(&self) -> bool {
+        self.is_unsafe
+    }
+
+    pub fn is_test(&self) -> bool {
+        self.has_attr("test")
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.has_attr("deprecated")
+    }
+
+    fn has_attr(&self, name: &str) -> bool {
+        self.attrs.iter().any(|it| it == name)
+    }
 }
 
 impl Function {
@@ -329,6 +427,52 @@ impl Function {
     pub fn infer(&self, db: &impl HirDatabase) -> Cancelable<Arc<InferenceResult>> {
         db.infer(self.def_id)
     }
+
+    /// The trait which declares this function, if it is a trait method.
+    pub fn containing_trait(&self, db: &impl HirDatabase) -> Cancelable<Option<Trait>> {
+        self.containing_trait_impl(db)
+    }
+
+    /// The trait implemented by the impl block which contains this function,
+    /// if it is a method of a trait impl.
+    pub fn impl_trait(&self, db: &impl HirDatabase) -> Cancelable<Option<Trait>> {
+        match self.impl_block(db)? {
+            Some(impl_block) => impl_block.target_trait_def(db),
+            None => Ok(None),
+        }
+    }
+
+    /// For a trait method, the methods which implement it in the impls of the
+    /// trait in `krate`. Impls which rely on the default body are skipped.
+    pub fn implementations(
+        &self,
+        db: &impl HirDatabase,
+        krate: &Crate,
+    ) -> Cancelable<Vec<Function>> {
+        self.implementations_impl(db, krate)
+    }
+
+    /// The `match` expressions in the body which don't cover all of the
+    /// variants of an enum, or both `bool` values.
+    pub fn missing_match_arms(&self, db: &impl HirDatabase) -> Cancelable<Vec<MissingMatchArms>> {
+        self.missing_match_arms_impl(db)
+    }
+
+    /// The problems in the body which don't need type inference, together with
+    /// the expressions they are found in.
+    pub fn body_problems(&self, db: &impl HirDatabase) -> Cancelable<Vec<(ExprId, BodyProblem)>> {
+        self.body_problems_impl(db)
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum BodyProblem {
+    /// `break` outside of a loop, or in a closure inside of a loop.
+    BreakOutsideOfLoop,
+    ContinueOutsideOfLoop,
+    /// The `?` operator in a function which returns neither `Result` nor
+    /// `Option`.
+    TryInNonTryFn,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -374,6 +518,48 @@ impl Trait {
         Trait { def_id }
     }
 
+    pub fn def_id(&self) -> DefId {
+        self.def_id
+    }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Cancelable<Module> {
+        self.def_id.module(db)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Cancelable<Option<Name>> {
+        Ok(db.trait_data(self.def_id)?.name.clone())
+    }
+
+    /// The functions declared in the trait, with or without a default body.
+    pub fn methods(&self, db: &impl HirDatabase) -> Cancelable<Vec<Function>> {
+        Ok(db.trait_data(self.def_id)?.methods.clone())
+    }
+
+    /// The super-traits which could be resolved, `Clone` in `trait Foo: Clone`.
+    pub fn super_traits(&self, db: &impl HirDatabase) -> Cancelable<Vec<Trait>> {
+        let module = self.module(db)?;
+        let mut res = Vec::new();
+        for path in db.trait_data(self.def_id)?.super_traits.iter() {
+            let def_id = match module.resolve_path(db, path)?.take_types() {
+                Some(it) => it,
+                None => continue,
+            };
+            if let Def::Trait(trait_) = def_id.resolve(db)? {
+                res.push(trait_);
+            }
+        }
+        Ok(res)
+    }
+
+    /// The reasons why the trait can't be used as `dyn Trait`, empty if the
+    /// trait is object safe.
+    pub fn object_safety_violations(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Cancelable<Vec<ObjectSafetyViolation>> {
+        traits::object_safety_violations(db, self)
+    }
+
     pub fn source(&self, db: &impl HirDatabase) -> Cancelable<(HirFileId, TreeArc<ast::TraitDef>)> {
         Ok(def_id_to_ast(db, self.def_id))
     }
@@ -393,3 +579,64 @@ impl Type {
         Ok(def_id_to_ast(db, self.def_id))
     }
 }
+
+/// An `impl` block, either inherent (`impl Foo {}`) or a trait one
+/// (`impl Trait for Foo {}`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImplBlock {
+    pub(crate) module_impl_blocks: Arc<ModuleImplBlocks>,
+    pub(crate) impl_id: ImplId,
+}
+
+impl ImplBlock {
+    pub fn source(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Cancelable<(HirFileId, TreeArc<ast::ImplBlock>)> {
+        let source_item_id = self.impl_data().source_item_id;
+        let syntax = db.file_item(source_item_id);
+        let node = ast::ImplBlock::cast(&syntax)
+            .unwrap_or_else(|| panic!("impl points to wrong source {:?}", syntax))
+            .to_owned();
+        Ok((source_item_id.file_id, node))
+    }
+
+    /// The trait being implemented, `None` for inherent impls.
+    pub fn target_trait(&self) -> Option<&TypeRef> {
+        self.impl_data().target_trait()
+    }
+
+    /// The self type of the impl.
+    pub fn target_type(&self) -> &TypeRef {
+        self.impl_data().target_type()
+    }
+
+    /// The self type of the impl, resolved in the module of the impl. This is
+    /// what `Self` refers to inside the impl block.
+    pub fn target_ty(&self, db: &impl HirDatabase) -> Cancelable<Ty> {
+        Ty::from_hir(db, self.module(), Some(self), self.target_type())
+    }
+
+    /// Resolves the implemented trait. `None` for inherent impls and for
+    /// traits which can't be resolved.
+    pub fn target_trait_def(&self, db: &impl HirDatabase) -> Cancelable<Option<Trait>> {
+        let path = match self.target_trait() {
+            Some(TypeRef::Path(path)) => path,
+            _ => return Ok(None),
+        };
+        let def_id = ctry!(self.module().resolve_path(db, path)?.take_types());
+        match def_id.resolve(db)? {
+            Def::Trait(trait_) => Ok(Some(trait_)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The module in which the impl block is defined.
+    pub fn module(&self) -> &Module {
+        &self.module_impl_blocks.module
+    }
+
+    pub fn items(&self) -> &[ImplItem] {
+        self.impl_data().items()
+    }
+}
@@ -1,19 +1,30 @@
 mod scope;
+mod match_check;
+mod body_check;
 
 use std::sync::Arc;
 
 use ra_db::Cancelable;
-use ra_syntax::{TreeArc, ast::{self, NameOwner}};
+use ra_syntax::{
+    TreeArc, AstNode,
+    ast::{self, NameOwner, TypeParamsOwner, AttrsOwner},
+    SyntaxKind::UNSAFE_KW,
+};
 
 use crate::{
-    DefId, HirDatabase, Name, AsName, Function, FnSignature, Module,
+    DefId, DefKind, HirDatabase, Name, AsName, Function, FnSignature, WherePredicate, Module,
+    ImplBlock, Trait, Crate,
     type_ref::{TypeRef, Mutability},
     expr::Body,
-    impl_block::ImplBlock,
     code_model_impl::def_id_to_ast,
+    impl_block::ImplItem,
+    adt::get_def_id,
 };
 
-pub use self::scope::{FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax};
+pub use self::{
+    scope::{FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax},
+    match_check::MissingMatchArms,
+};
 
 impl Function {
     pub(crate) fn new(def_id: DefId) -> Function {
@@ -32,6 +43,45 @@ impl Function {
     pub(crate) fn impl_block(&self, db: &impl HirDatabase) -> Cancelable<Option<ImplBlock>> {
         self.def_id.impl_block(db)
     }
+
+    pub(crate) fn containing_trait_impl(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Cancelable<Option<Trait>> {
+        let (_, fn_def) = def_id_to_ast::<ast::FnDef>(db, self.def_id);
+        let trait_def = ctry!(fn_def
+            .syntax()
+            .parent()
+            .and_then(|item_list| item_list.parent())
+            .and_then(ast::TraitDef::cast));
+        // The trait is defined in the same file and module as its methods.
+        let def_id = get_def_id(db, &self.def_id.loc(db), trait_def.syntax(), DefKind::Trait);
+        Ok(Some(Trait::new(def_id)))
+    }
+
+    pub(crate) fn implementations_impl(
+        &self,
+        db: &impl HirDatabase,
+        krate: &Crate,
+    ) -> Cancelable<Vec<Function>> {
+        let trait_ = match self.containing_trait(db)? {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let name = self.signature(db).name().clone();
+        let impls = db.impls_in_crate(krate.clone())?;
+        let mut res = Vec::new();
+        for impl_block in impls.lookup_impl_blocks_for_trait(db, &trait_) {
+            for item in impl_block?.items() {
+                if let ImplItem::Method(f) = item {
+                    if f.signature(db).name() == &name {
+                        res.push(f.clone());
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
 }
 
 impl FnSignature {
@@ -43,6 +93,7 @@ impl FnSignature {
             .map(|n| n.as_name())
             .unwrap_or_else(Name::missing);
         let mut params = Vec::new();
+        let mut param_names = Vec::new();
         let mut has_self_param = false;
         if let Some(param_list) = node.param_list() {
             if let Some(self_param) = param_list.self_param() {
@@ -61,11 +112,17 @@ impl FnSignature {
                     }
                 };
                 params.push(self_type);
+                param_names.push(Some(Name::self_param()));
                 has_self_param = true;
             }
             for param in param_list.params() {
                 let type_ref = TypeRef::from_ast_opt(param.type_ref());
                 params.push(type_ref);
+                let name = match param.pat().map(|it| it.kind()) {
+                    Some(ast::PatKind::BindPat(it)) => it.name().map(|n| n.as_name()),
+                    _ => None,
+                };
+                param_names.push(name);
             }
         }
         let ret_type = if let Some(type_ref) = node.ret_type().and_then(|rt| rt.type_ref()) {
@@ -73,11 +130,34 @@ impl FnSignature {
         } else {
             TypeRef::unit()
         };
+        let type_params = node
+            .type_param_list()
+            .into_iter()
+            .flat_map(|it| it.type_params())
+            .map(|it| it.name().map(|n| n.as_name()).unwrap_or_else(Name::missing))
+            .collect();
+        let where_predicates = node
+            .where_clause()
+            .into_iter()
+            .flat_map(|it| it.predicates())
+            .filter_map(|pred| {
+                let type_ref = TypeRef::from_ast(pred.type_ref()?);
+                let bounds = pred.bounds().map(TypeRef::from_ast).collect();
+                Some(WherePredicate { type_ref, bounds })
+            })
+            .collect();
+        let attrs = node.attrs().filter_map(|it| it.name()).collect();
+        let is_unsafe = node.syntax().children().any(|it| it.kind() == UNSAFE_KW);
         let sig = FnSignature {
             name,
             params,
+            param_names,
             ret_type,
             has_self_param,
+            type_params,
+            where_predicates,
+            attrs,
+            is_unsafe,
         };
         Arc::new(sig)
     }
@@ -0,0 +1,89 @@
+use ra_db::Cancelable;
+
+use crate::{
+    BodyProblem, Function, HirDatabase, Ty,
+    expr::{Body, Expr, ExprId},
+};
+
+impl Function {
+    pub(crate) fn body_problems_impl(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Cancelable<Vec<(ExprId, BodyProblem)>> {
+        let body = self.body(db)?;
+        let mut res = Vec::new();
+        check_loops(&body, body.body_expr(), false, &mut res);
+        if !returns_try_type(db, self)? {
+            check_try(&body, body.body_expr(), &mut res);
+        }
+        Ok(res)
+    }
+}
+
+/// Finds the `break`s and `continue`s which are not inside of a loop. Closures
+/// can't break out of the loops around them, so their bodies start outside of
+/// any loop.
+fn check_loops(body: &Body, expr: ExprId, in_loop: bool, acc: &mut Vec<(ExprId, BodyProblem)>) {
+    match &body[expr] {
+        Expr::Break { .. } if !in_loop => acc.push((expr, BodyProblem::BreakOutsideOfLoop)),
+        Expr::Continue if !in_loop => acc.push((expr, BodyProblem::ContinueOutsideOfLoop)),
+        Expr::Loop { body: loop_body } => {
+            check_loops(body, *loop_body, true, acc);
+            return;
+        }
+        Expr::While {
+            condition,
+            body: loop_body,
+        } => {
+            check_loops(body, *condition, in_loop, acc);
+            check_loops(body, *loop_body, true, acc);
+            return;
+        }
+        Expr::For {
+            iterable,
+            body: loop_body,
+            ..
+        } => {
+            check_loops(body, *iterable, in_loop, acc);
+            check_loops(body, *loop_body, true, acc);
+            return;
+        }
+        Expr::Lambda {
+            body: lambda_body, ..
+        } => {
+            check_loops(body, *lambda_body, false, acc);
+            return;
+        }
+        _ => (),
+    }
+    body[expr].walk_child_exprs(|child| check_loops(body, child, in_loop, acc));
+}
+
+/// Finds the uses of `?`. Closures are skipped, as their return type is not
+/// known here.
+fn check_try(body: &Body, expr: ExprId, acc: &mut Vec<(ExprId, BodyProblem)>) {
+    match &body[expr] {
+        Expr::Try { .. } => acc.push((expr, BodyProblem::TryInNonTryFn)),
+        Expr::Lambda { .. } => return,
+        _ => (),
+    }
+    body[expr].walk_child_exprs(|child| check_try(body, child, acc));
+}
+
+/// Whether `?` can be used in the function. If the return type is not known,
+/// this is assumed to be the case.
+fn returns_try_type(db: &impl HirDatabase, function: &Function) -> Cancelable<bool> {
+    let signature = function.signature(db);
+    let module = function.module(db)?;
+    let impl_block = function.impl_block(db)?;
+    let ret_ty = Ty::from_hir(db, &module, impl_block.as_ref(), signature.ret_type())?;
+    let res = match ret_ty {
+        Ty::Unknown => true,
+        Ty::Adt { name, .. } => {
+            let name = name.to_string();
+            name == "Result" || name == "Option"
+        }
+        _ => false,
+    };
+    Ok(res)
+}
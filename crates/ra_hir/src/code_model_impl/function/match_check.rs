@@ -0,0 +1,269 @@
+//! A basic exhaustiveness check for `match` expressions. Only the top-level
+//! constructors of the patterns are considered, and only for enums and
+//! `bool`s. Whenever a pattern is not understood, the match is assumed to be
+//! exhaustive, so that there are no false positives.
+use ra_db::Cancelable;
+use ra_syntax::ast::AttrsOwner;
+
+use crate::{
+    Def, DefId, Enum, Function, HirDatabase, Module, Path, Ty,
+    expr::{Body, Expr, ExprId, Literal, MatchArm, Pat, PatId},
+};
+
+/// A `match` which doesn't cover all of the variants of the matched enum, or
+/// both of the `bool` values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingMatchArms {
+    pub match_expr: ExprId,
+    /// The patterns of the missing arms, like `Foo::Bar(..)` or `false`. The
+    /// enum is named as it resolves at the `match`.
+    pub missing: Vec<String>,
+}
+
+/// A value which a pattern can match at the top level: a variant of an enum
+/// or a `bool` literal.
+struct Constructor {
+    pattern: String,
+    variant: Option<DefId>,
+    bool_value: Option<bool>,
+}
+
+enum Coverage {
+    /// The pattern matches every value.
+    All,
+    /// The pattern matches the values of the given constructor.
+    Constructor(usize),
+    /// The pattern is not understood.
+    Unknown,
+}
+
+impl Function {
+    pub(crate) fn missing_match_arms_impl(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Cancelable<Vec<MissingMatchArms>> {
+        let body = self.body(db)?;
+        let infer = self.infer(db)?;
+        let module = self.module(db)?;
+        let mut res = Vec::new();
+        for (match_expr, expr) in body.exprs() {
+            let (scrutinee, arms) = match expr {
+                Expr::Match { expr, arms } => (*expr, arms),
+                _ => continue,
+            };
+            let constructors = match constructors(db, self, &module, &infer[scrutinee])? {
+                Some(it) => it,
+                None => continue,
+            };
+            let missing = missing_constructors(db, &module, &body, &constructors, arms)?;
+            if !missing.is_empty() {
+                res.push(MissingMatchArms { match_expr, missing });
+            }
+        }
+        Ok(res)
+    }
+}
+
+fn constructors(
+    db: &impl HirDatabase,
+    function: &Function,
+    module: &Module,
+    ty: &Ty,
+) -> Cancelable<Option<Vec<Constructor>>> {
+    let res = match ty {
+        Ty::Bool => [true, false]
+            .iter()
+            .map(|&value| Constructor {
+                pattern: value.to_string(),
+                variant: None,
+                bool_value: Some(value),
+            })
+            .collect(),
+        Ty::Adt { def_id, .. } => {
+            let e = match def_id.resolve(db)? {
+                Def::Enum(e) => e,
+                _ => return Ok(None),
+            };
+            let enum_path = ctry!(enum_path(db, function, module, &e)?);
+            let scope = module.scope(db)?;
+            let mut res = Vec::new();
+            for (name, variant) in e.variants(db)? {
+                // `#[cfg]`s are not evaluated, so it's not known whether such
+                // a variant exists. Arms for it are not understood either,
+                // which keeps the check quiet for such a `match`.
+                let (_, source) = variant.source(db)?;
+                if source.has_attr("cfg") {
+                    continue;
+                }
+                let variant_data = variant.variant_data(db)?;
+                let fields = if variant_data.is_tuple() {
+                    "(..)"
+                } else if variant_data.is_struct() {
+                    " { .. }"
+                } else {
+                    ""
+                };
+                // Variants imported on their own, like `Some`, are used as is.
+                let imported = scope.entries().any(|(scope_name, resolution)| {
+                    *scope_name == name
+                        && (resolution.def_id.types == Some(variant.def_id())
+                            || resolution.def_id.values == Some(variant.def_id()))
+                });
+                let pattern = if imported {
+                    format!("{}{}", name, fields)
+                } else {
+                    format!("{}::{}{}", enum_path, name, fields)
+                };
+                res.push(Constructor {
+                    pattern,
+                    variant: Some(variant.def_id()),
+                    bool_value: None,
+                });
+            }
+            res
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(res))
+}
+
+/// The path of `e` as it resolves in the body of `function`: the name it is
+/// in scope with, or its path from the crate root otherwise.
+fn enum_path(
+    db: &impl HirDatabase,
+    function: &Function,
+    module: &Module,
+    e: &Enum,
+) -> Cancelable<Option<String>> {
+    let def_id = Some(e.def_id());
+    let block_scopes = db.body_block_scopes(function.def_id())?;
+    let in_blocks = block_scopes.scopes().find_map(|scope| {
+        scope
+            .entries()
+            .find(|(_, res)| res.def_id.types == def_id)
+            .map(|(name, _)| name.to_string())
+    });
+    if let Some(name) = in_blocks {
+        return Ok(Some(name));
+    }
+    let in_module = module
+        .scope(db)?
+        .entries()
+        .find(|(_, res)| res.def_id.types == def_id)
+        .map(|(name, _)| name.to_string());
+    if let Some(name) = in_module {
+        return Ok(Some(name));
+    }
+    let enum_module = e.def_id().module(db)?;
+    let enum_krate = ctry!(enum_module.krate(db)?);
+    let krate = ctry!(module.krate(db)?);
+    let mut segments = Vec::new();
+    for module in enum_module.path_to_root(db)?.into_iter().rev() {
+        match module.name(db)? {
+            Some(name) => segments.push(name.to_string()),
+            None if enum_krate == krate => segments.push("crate".to_string()),
+            None => {
+                let dep = ctry!(krate
+                    .dependencies(db)?
+                    .into_iter()
+                    .find(|it| it.krate == enum_krate));
+                segments.push(dep.name.to_string());
+            }
+        }
+    }
+    segments.push(ctry!(e.name(db)?).to_string());
+    Ok(Some(segments.join("::")))
+}
+
+/// The patterns of the constructors which are not covered by `arms`, or
+/// nothing if some pattern is not understood.
+fn missing_constructors(
+    db: &impl HirDatabase,
+    module: &Module,
+    body: &Body,
+    constructors: &[Constructor],
+    arms: &[MatchArm],
+) -> Cancelable<Vec<String>> {
+    let mut covered = vec![false; constructors.len()];
+    for arm in arms {
+        // A guarded arm covers nothing, as the guard might be false.
+        if arm.guard.is_some() {
+            continue;
+        }
+        for &pat in &arm.pats {
+            match coverage(db, module, body, constructors, pat)? {
+                Coverage::All | Coverage::Unknown => return Ok(Vec::new()),
+                Coverage::Constructor(idx) => covered[idx] = true,
+            }
+        }
+    }
+    let res = constructors
+        .iter()
+        .zip(covered)
+        .filter(|(_, covered)| !covered)
+        .map(|(constructor, _)| constructor.pattern.clone())
+        .collect();
+    Ok(res)
+}
+
+fn coverage(
+    db: &impl HirDatabase,
+    module: &Module,
+    body: &Body,
+    constructors: &[Constructor],
+    pat: PatId,
+) -> Cancelable<Coverage> {
+    let variant_coverage = |path: &Path| -> Cancelable<Coverage> {
+        let resolution = module.resolve_path(db, path)?;
+        let def_id = match resolution.types.or(resolution.values) {
+            Some(it) => it,
+            None => return Ok(Coverage::Unknown),
+        };
+        let idx = constructors
+            .iter()
+            .position(|it| it.variant == Some(def_id));
+        Ok(idx.map_or(Coverage::Unknown, Coverage::Constructor))
+    };
+    let res = match &body[pat] {
+        Pat::Wild => Coverage::All,
+        Pat::Bind { name } => {
+            // A lone identifier is a unit variant if one is in scope, and a
+            // binding otherwise.
+            let path = Path::from(name.clone());
+            match variant_coverage(&path)? {
+                Coverage::Unknown => Coverage::All,
+                it => it,
+            }
+        }
+        Pat::Path(path) => variant_coverage(path)?,
+        Pat::TupleStruct {
+            path: Some(path),
+            args,
+        } => {
+            if args.iter().all(|&arg| is_irrefutable(body, arg)) {
+                variant_coverage(path)?
+            } else {
+                Coverage::Unknown
+            }
+        }
+        Pat::Lit(expr) => match &body[*expr] {
+            Expr::Literal(Literal::Bool(value)) => {
+                let idx = constructors
+                    .iter()
+                    .position(|it| it.bool_value == Some(*value));
+                idx.map_or(Coverage::Unknown, Coverage::Constructor)
+            }
+            _ => Coverage::Unknown,
+        },
+        _ => Coverage::Unknown,
+    };
+    Ok(res)
+}
+
+fn is_irrefutable(body: &Body, pat: PatId) -> bool {
+    match &body[pat] {
+        Pat::Wild | Pat::Bind { .. } => true,
+        Pat::Tuple(args) => args.iter().all(|&arg| is_irrefutable(body, arg)),
+        _ => false,
+    }
+}
@@ -5,7 +5,7 @@ use rustc_hash::{FxHashMap, FxHashSet};
 use ra_syntax::{
     AstNode, SyntaxNode, TextUnit, TextRange,
     algo::generate,
-    ast,
+    ast::{self, NameOwner},
 };
 use ra_arena::{Arena, RawId, impl_arena_id};
 use ra_db::LocalSyntaxPtr;
@@ -195,6 +195,70 @@ impl ScopesWithSyntaxMapping {
         })
     }
 
+    /// Resolves `name` as if it was used at `offset`, which doesn't have to be
+    /// inside of an expression, like in the arguments of a macro call. Of the
+    /// visible bindings with the name, the innermost one is the last one.
+    pub fn resolve_local_name_at_offset(
+        &self,
+        offset: TextUnit,
+        name: &Name,
+    ) -> Option<ScopeEntryWithSyntax> {
+        let body = &self.scopes.body;
+        let mut candidates = self
+            .scope_chain_for_offset(offset)
+            .flat_map(|scope| self.scopes.entries(scope).iter())
+            .filter(|entry| entry.name() == name)
+            .map(|entry| entry.pat())
+            .collect::<Vec<_>>();
+        // The scope of a `let` is attached to the statements after it, so the
+        // `let`s right before `offset` are not in the chain.
+        for (expr, data) in body.exprs() {
+            let statements = match data {
+                Expr::Block { statements, .. } => statements,
+                _ => continue,
+            };
+            let contains_offset = self.syntax_mapping.expr_syntax(expr).map_or(false, |ptr| {
+                ptr.range().start() <= offset && offset <= ptr.range().end()
+            });
+            if !contains_offset {
+                continue;
+            }
+            for stmt in statements {
+                if let Statement::Let {
+                    pat, initializer, ..
+                } = stmt
+                {
+                    let end = initializer
+                        .and_then(|it| self.syntax_mapping.expr_syntax(it))
+                        .or_else(|| self.syntax_mapping.pat_syntax(*pat))
+                        .map(|ptr| ptr.range().end());
+                    if end.map_or(false, |end| end <= offset) {
+                        collect_bindings(body, *pat, name, &mut candidates);
+                    }
+                }
+            }
+        }
+        let ptr = candidates
+            .into_iter()
+            .filter_map(|pat| self.syntax_mapping.pat_syntax(pat))
+            .filter(|ptr| ptr.range().end() <= offset)
+            .max_by_key(|ptr| ptr.range().start())?;
+        Some(ScopeEntryWithSyntax {
+            name: name.clone(),
+            ptr,
+        })
+    }
+
+    /// The entry which the binding introduces into the scope.
+    pub fn entry_for_binding(&self, pat: &ast::BindPat) -> Option<ScopeEntryWithSyntax> {
+        let name = pat.name()?.as_name();
+        self.syntax_mapping.node_pat(ast::Pat::cast(pat.syntax())?)?;
+        Some(ScopeEntryWithSyntax {
+            name,
+            ptr: LocalSyntaxPtr::new(pat.syntax()),
+        })
+    }
+
     pub fn find_all_refs(&self, pat: &ast::BindPat) -> Vec<ReferenceDescriptor> {
         let fn_def = pat.syntax().ancestors().find_map(ast::FnDef::cast).unwrap();
         let name_ptr = LocalSyntaxPtr::new(pat.syntax());
@@ -230,6 +294,13 @@ impl ScopeEntry {
     }
 }
 
+fn collect_bindings(body: &Body, pat: PatId, name: &Name, acc: &mut Vec<PatId>) {
+    match &body[pat] {
+        Pat::Bind { name: it } if it == name => acc.push(pat),
+        p => p.walk_child_pats(|pat| collect_bindings(body, pat, name, acc)),
+    }
+}
+
 fn compute_block_scopes(
     statements: &[Statement],
     tail: Option<ExprId>,
@@ -292,6 +363,10 @@ fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut FnScopes, scope:
                 for pat in &arm.pats {
                     scopes.add_bindings(body, scope, *pat);
                 }
+                if let Some(guard) = arm.guard {
+                    scopes.set_scope(guard, scope);
+                    compute_expr_scopes(guard, body, scopes, scope);
+                }
                 scopes.set_scope(arm.expr, scope);
                 compute_expr_scopes(arm.expr, body, scopes, scope);
             }
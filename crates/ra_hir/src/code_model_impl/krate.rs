@@ -20,6 +20,16 @@ impl Crate {
             })
             .collect()
     }
+    pub(crate) fn transitive_reverse_dependencies_impl(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Vec<Crate> {
+        db.crate_graph()
+            .transitive_reverse_dependencies(self.crate_id)
+            .into_iter()
+            .map(Crate::new)
+            .collect()
+    }
     pub(crate) fn root_module_impl(&self, db: &impl HirDatabase) -> Cancelable<Option<Module>> {
         let crate_graph = db.crate_graph();
         let file_id = crate_graph.crate_root(self.crate_id);
@@ -1,11 +1,13 @@
 use ra_db::{Cancelable, SourceRootId, FileId};
 use ra_syntax::{ast, SyntaxNode, AstNode, TreeArc};
 
+use std::sync::Arc;
+
 use crate::{
-    Module, ModuleSource, Problem,
+    Module, ModuleSource, Problem, ImplBlock,
     Crate, DefId, DefLoc, DefKind, Name, Path, PathKind, PerNs, Def,
     module_tree::ModuleId,
-    nameres::ModuleScope,
+    nameres::{ModuleScope, NamedImport},
     db::HirDatabase,
 };
 
@@ -129,28 +131,32 @@ impl Module {
         &self,
         db: &impl HirDatabase,
         path: &Path,
-    ) -> Cancelable<PerNs<DefId>> {
+    ) -> Cancelable<(PerNs<DefId>, Option<NamedImport>)> {
         let mut curr_per_ns = PerNs::types(
             match path.kind {
                 PathKind::Crate => self.crate_root(db)?,
                 PathKind::Self_ | PathKind::Plain => self.clone(),
-                PathKind::Super => {
-                    if let Some(p) = self.parent(db)? {
-                        p
-                    } else {
-                        return Ok(PerNs::none());
+                PathKind::Super(level) => {
+                    let mut module = self.clone();
+                    for _ in 0..level {
+                        module = match module.parent(db)? {
+                            Some(p) => p,
+                            None => return Ok((PerNs::none(), None)),
+                        };
                     }
+                    module
                 }
             }
             .def_id,
         );
 
+        let mut import = None;
         let segments = &path.segments;
         for (idx, name) in segments.iter().enumerate() {
             let curr = if let Some(r) = curr_per_ns.as_ref().take_types() {
                 r
             } else {
-                return Ok(PerNs::none());
+                return Ok((PerNs::none(), None));
             };
             let module = match curr.resolve(db)? {
                 Def::Module(it) => it,
@@ -161,28 +167,29 @@ impl Module {
                             e.variants(db)?.into_iter().find(|(n, _variant)| n == name);
 
                         if let Some((_n, variant)) = matching_variant {
-                            return Ok(PerNs::both(variant.def_id(), e.def_id()));
+                            return Ok((PerNs::both(variant.def_id(), e.def_id()), None));
                         } else {
-                            return Ok(PerNs::none());
+                            return Ok((PerNs::none(), None));
                         }
                     } else if segments.len() == idx {
                         // enum
-                        return Ok(PerNs::types(e.def_id()));
+                        return Ok((PerNs::types(e.def_id()), None));
                     } else {
                         // malformed enum?
-                        return Ok(PerNs::none());
+                        return Ok((PerNs::none(), None));
                     }
                 }
-                _ => return Ok(PerNs::none()),
+                _ => return Ok((PerNs::none(), None)),
             };
             let scope = module.scope(db)?;
-            curr_per_ns = if let Some(r) = scope.get(&name) {
-                r.def_id
-            } else {
-                return Ok(PerNs::none());
+            let resolution = match scope.get(&name) {
+                Some(it) => it,
+                None => return Ok((PerNs::none(), None)),
             };
+            curr_per_ns = resolution.def_id;
+            import = resolution.import;
         }
-        Ok(curr_per_ns)
+        Ok((curr_per_ns, import))
     }
 
     pub fn problems_impl(
@@ -191,6 +198,20 @@ impl Module {
     ) -> Cancelable<Vec<(TreeArc<SyntaxNode>, Problem)>> {
         let loc = self.def_id.loc(db);
         let module_tree = db.module_tree(loc.source_root_id)?;
-        Ok(loc.module_id.problems(&module_tree, db))
+        let mut res = loc.module_id.problems(&module_tree, db);
+        let items = db.input_module_items(loc.source_root_id, loc.module_id)?;
+        res.extend(items.duplicate_definitions(db));
+        Ok(res)
+    }
+
+    pub(crate) fn impl_blocks_impl(&self, db: &impl HirDatabase) -> Cancelable<Vec<ImplBlock>> {
+        let loc = self.def_id.loc(db);
+        let module_impl_blocks = db.impls_in_module(loc.source_root_id, loc.module_id)?;
+        let res = module_impl_blocks
+            .impls
+            .iter()
+            .map(|(impl_id, _)| ImplBlock::from_id(Arc::clone(&module_impl_blocks), impl_id))
+            .collect();
+        Ok(res)
     }
 }
@@ -5,14 +5,15 @@ use ra_db::{SourceRootId, LocationIntener, SyntaxDatabase, Cancelable};
 
 use crate::{
     DefLoc, DefId, MacroCallLoc, MacroCallId, Name, HirFileId,
-    SourceFileItems, SourceItemId, Crate,
+    SourceFileItems, SourceItemId, Crate, TyInterner,
     query_definitions,
     FnSignature, FnScopes,
     macros::MacroExpansion,
     module_tree::{ModuleId, ModuleTree},
-    nameres::{ItemMap, InputModuleItems},
+    nameres::{ItemMap, InputModuleItems, ModuleScope, BodyBlockScopes},
     ty::{InferenceResult, Ty, method_resolution::CrateImplBlocks},
     adt::{StructData, EnumData, EnumVariantData},
+    traits::TraitData,
     impl_block::ModuleImplBlocks,
 };
 
@@ -21,6 +22,7 @@ salsa::query_group! {
 pub trait HirDatabase: SyntaxDatabase
     + AsRef<LocationIntener<DefLoc, DefId>>
     + AsRef<LocationIntener<MacroCallLoc, MacroCallId>>
+    + AsRef<TyInterner>
 {
     fn hir_source_file(file_id: HirFileId) -> TreeArc<SourceFile> {
         type HirSourceFileQuery;
@@ -37,6 +39,11 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::fn_scopes;
     }
 
+    fn body_block_scopes(def_id: DefId) -> Cancelable<Arc<BodyBlockScopes>> {
+        type BodyBlockScopesQuery;
+        use fn crate::nameres::BodyBlockScopes::body_block_scopes_query;
+    }
+
     fn struct_data(def_id: DefId) -> Cancelable<Arc<StructData>> {
         type StructDataQuery;
         use fn crate::adt::StructData::struct_data_query;
@@ -52,6 +59,11 @@ pub trait HirDatabase: SyntaxDatabase
         use fn crate::adt::EnumVariantData::enum_variant_data_query;
     }
 
+    fn trait_data(def_id: DefId) -> Cancelable<Arc<TraitData>> {
+        type TraitDataQuery;
+        use fn crate::traits::TraitData::trait_data_query;
+    }
+
     fn infer(def_id: DefId) -> Cancelable<Arc<InferenceResult>> {
         type InferQuery;
         use fn crate::ty::infer;
@@ -87,6 +99,11 @@ pub trait HirDatabase: SyntaxDatabase
         use fn query_definitions::input_module_items;
     }
 
+    fn module_declarations(source_root_id: SourceRootId, module_id: ModuleId) -> Cancelable<Arc<ModuleScope>> {
+        type ModuleDeclarationsQuery;
+        use fn crate::nameres::ModuleScope::module_declarations_query;
+    }
+
     fn item_map(source_root_id: SourceRootId) -> Cancelable<Arc<ItemMap>> {
         type ItemMapQuery;
         use fn query_definitions::item_map;
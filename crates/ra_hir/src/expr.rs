@@ -5,9 +5,16 @@ use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id, map::ArenaMap};
 use ra_db::{LocalSyntaxPtr, Cancelable};
-use ra_syntax::ast::{self, AstNode, LoopBodyOwner, ArgListOwner, NameOwner};
+use ra_syntax::{
+    TextRange,
+    SyntaxKind::{IF_KW, FAT_ARROW, TRUE_KW},
+    ast::{self, AstNode, LoopBodyOwner, ArgListOwner, NameOwner, LiteralFlavor},
+};
 
-use crate::{Path, type_ref::{Mutability, TypeRef}, Name, HirDatabase, DefId, Def, name::AsName};
+use crate::{
+    Path, type_ref::{Mutability, TypeRef}, Name, HirDatabase, DefId, Def, name::AsName,
+    ty::primitive::{UncertainIntTy, UncertainFloatTy},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ExprId(RawId);
@@ -51,6 +58,10 @@ impl Body {
     pub fn body_expr(&self) -> ExprId {
         self.body_expr
     }
+
+    pub fn exprs(&self) -> impl Iterator<Item = (ExprId, &Expr)> {
+        self.exprs.iter()
+    }
 }
 
 impl Index<ExprId> for Body {
@@ -98,11 +109,34 @@ impl BodySyntaxMapping {
             .cloned()
     }
 
+    /// The smallest expression which contains `range`. An empty range finds
+    /// the expression at an offset.
+    pub fn expr_covering_range(&self, range: TextRange) -> Option<ExprId> {
+        covering(self.expr_syntax_mapping_back.iter(), range)
+    }
+
+    /// The smallest pattern which contains `range`.
+    pub fn pat_covering_range(&self, range: TextRange) -> Option<PatId> {
+        covering(self.pat_syntax_mapping_back.iter(), range)
+    }
+
     pub fn body(&self) -> &Arc<Body> {
         &self.body
     }
 }
 
+/// Children are allocated before their parents, so on ties the innermost node
+/// wins.
+fn covering<'a, ID>(
+    nodes: impl Iterator<Item = (ID, &'a LocalSyntaxPtr)>,
+    range: TextRange,
+) -> Option<ID> {
+    nodes
+        .filter(|(_, ptr)| range.is_subrange(&ptr.range()))
+        .min_by_key(|(_, ptr)| ptr.range().len())
+        .map(|(id, _)| id)
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Expr {
     /// This is produced if syntax tree does not have a required expression piece.
@@ -183,15 +217,41 @@ pub enum Expr {
         arg_types: Vec<Option<TypeRef>>,
         body: ExprId,
     },
+    Tuple {
+        exprs: Vec<ExprId>,
+    },
+    Array(Array),
+    Index {
+        base: ExprId,
+        index: ExprId,
+    },
+    Literal(Literal),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Array {
+    ElementList(Vec<ExprId>),
+    Repeat { initializer: ExprId, repeat: ExprId },
 }
 
 pub use ra_syntax::ast::PrefixOp as UnaryOp;
 pub use ra_syntax::ast::BinOp as BinaryOp;
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Literal {
+    String,
+    ByteString,
+    Char,
+    Byte,
+    Bool(bool),
+    Int(UncertainIntTy),
+    Float(UncertainFloatTy),
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MatchArm {
     pub pats: Vec<PatId>,
-    // guard: Option<ExprId>, // TODO
+    pub guard: Option<ExprId>,
     pub expr: ExprId,
 }
 
@@ -215,7 +275,7 @@ impl Expr {
     pub fn walk_child_exprs(&self, mut f: impl FnMut(ExprId)) {
         match self {
             Expr::Missing => {}
-            Expr::Path(_) => {}
+            Expr::Path(_) | Expr::Literal(_) => {}
             Expr::If {
                 condition,
                 then_branch,
@@ -266,6 +326,9 @@ impl Expr {
             Expr::Match { expr, arms } => {
                 f(*expr);
                 for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        f(guard);
+                    }
                     f(arm.expr);
                 }
             }
@@ -290,6 +353,22 @@ impl Expr {
                 f(*lhs);
                 f(*rhs);
             }
+            Expr::Tuple { exprs } | Expr::Array(Array::ElementList(exprs)) => {
+                for expr in exprs {
+                    f(*expr);
+                }
+            }
+            Expr::Array(Array::Repeat {
+                initializer,
+                repeat,
+            }) => {
+                f(*initializer);
+                f(*repeat);
+            }
+            Expr::Index { base, index } => {
+                f(*base);
+                f(*index);
+            }
             Expr::Field { expr, .. }
             | Expr::Try { expr }
             | Expr::Cast { expr, .. }
@@ -308,6 +387,7 @@ impl_arena_id!(PatId);
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Pat {
     Missing,
+    Wild,
     Bind {
         name: Name,
     },
@@ -315,13 +395,18 @@ pub enum Pat {
         path: Option<Path>,
         args: Vec<PatId>,
     },
+    Tuple(Vec<PatId>),
+    /// A path to a unit struct, a unit variant or a constant, like `None`.
+    Path(Path),
+    /// A literal pattern, like `true` or `92`.
+    Lit(ExprId),
 }
 
 impl Pat {
     pub fn walk_child_pats(&self, f: impl FnMut(PatId)) {
         match self {
-            Pat::Missing | Pat::Bind { .. } => {}
-            Pat::TupleStruct { args, .. } => {
+            Pat::Missing | Pat::Wild | Pat::Bind { .. } | Pat::Path(_) | Pat::Lit(_) => {}
+            Pat::TupleStruct { args, .. } | Pat::Tuple(args) => {
                 args.iter().map(|pat| *pat).for_each(f);
             }
         }
@@ -395,10 +480,12 @@ impl ExprCollector {
                     let arms = vec![
                         MatchArm {
                             pats: vec![pat],
+                            guard: None,
                             expr: then_branch,
                         },
                         MatchArm {
                             pats: vec![placeholder_pat],
+                            guard: None,
                             expr: else_branch,
                         },
                     ];
@@ -489,10 +576,7 @@ impl ExprCollector {
                 let arms = if let Some(match_arm_list) = e.match_arm_list() {
                     match_arm_list
                         .arms()
-                        .map(|arm| MatchArm {
-                            pats: arm.pats().map(|p| self.collect_pat(p)).collect(),
-                            expr: self.collect_expr_opt(arm.expr()),
-                        })
+                        .map(|arm| self.collect_match_arm(arm))
                         .collect()
                 } else {
                     Vec::new()
@@ -564,10 +648,15 @@ impl ExprCollector {
             }
             ast::ExprKind::FieldExpr(e) => {
                 let expr = self.collect_expr_opt(e.expr());
-                let name = e
-                    .name_ref()
-                    .map(|nr| nr.as_name())
-                    .unwrap_or_else(Name::missing);
+                let name = match e.field_access() {
+                    Some(ast::FieldKind::Name(nr)) => nr.as_name(),
+                    Some(ast::FieldKind::Index(idx)) => idx
+                        .leaf_text()
+                        .and_then(|it| it.parse::<usize>().ok())
+                        .map(Name::tuple_field_name)
+                        .unwrap_or_else(Name::missing),
+                    None => Name::missing(),
+                };
                 self.alloc_expr(Expr::Field { expr, name }, syntax_ptr)
             }
             ast::ExprKind::TryExpr(e) => {
@@ -616,14 +705,57 @@ impl ExprCollector {
                 let op = e.op();
                 self.alloc_expr(Expr::BinaryOp { lhs, rhs, op }, syntax_ptr)
             }
+            ast::ExprKind::TupleExpr(e) => {
+                let exprs = e.exprs().map(|expr| self.collect_expr(expr)).collect();
+                self.alloc_expr(Expr::Tuple { exprs }, syntax_ptr)
+            }
+            ast::ExprKind::ArrayExpr(e) => {
+                let array = if e.is_repeat() {
+                    let mut exprs = e.exprs();
+                    let initializer = self.collect_expr_opt(exprs.next());
+                    let repeat = self.collect_expr_opt(exprs.next());
+                    Array::Repeat {
+                        initializer,
+                        repeat,
+                    }
+                } else {
+                    Array::ElementList(e.exprs().map(|expr| self.collect_expr(expr)).collect())
+                };
+                self.alloc_expr(Expr::Array(array), syntax_ptr)
+            }
+            ast::ExprKind::IndexExpr(e) => {
+                let base = self.collect_expr_opt(e.base());
+                let index = self.collect_expr_opt(e.index());
+                self.alloc_expr(Expr::Index { base, index }, syntax_ptr)
+            }
+            ast::ExprKind::Literal(e) => {
+                let flavor = match e.flavor() {
+                    Some(it) => it,
+                    None => return self.alloc_expr(Expr::Missing, syntax_ptr),
+                };
+                let lit = match flavor {
+                    LiteralFlavor::IntNumber { suffix } => Literal::Int(
+                        suffix
+                            .map(|it| UncertainIntTy::from_name(&Name::new(it)))
+                            .unwrap_or(UncertainIntTy::Unknown),
+                    ),
+                    LiteralFlavor::FloatNumber { suffix } => Literal::Float(
+                        suffix
+                            .map(|it| UncertainFloatTy::from_name(&Name::new(it)))
+                            .unwrap_or(UncertainFloatTy::Unknown),
+                    ),
+                    LiteralFlavor::String => Literal::String,
+                    LiteralFlavor::ByteString => Literal::ByteString,
+                    LiteralFlavor::Char => Literal::Char,
+                    LiteralFlavor::Byte => Literal::Byte,
+                    LiteralFlavor::Bool => Literal::Bool(e.token().kind() == TRUE_KW),
+                };
+                self.alloc_expr(Expr::Literal(lit), syntax_ptr)
+            }
 
             // TODO implement HIR for these:
             ast::ExprKind::Label(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::IndexExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::TupleExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::ArrayExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
             ast::ExprKind::RangeExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::Literal(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
         }
     }
 
@@ -669,6 +801,39 @@ impl ExprCollector {
         }
     }
 
+    fn collect_match_arm(&mut self, arm: &ast::MatchArm) -> MatchArm {
+        // Literal patterns are not `Pat`s in the syntax tree, and the guard is
+        // not wrapped in a node of its own, so the children are sorted out by
+        // their position relative to the `if` and the `=>`.
+        let mut pats = Vec::new();
+        let mut guard = None;
+        let mut expr = None;
+        let mut seen_if = false;
+        let mut seen_arrow = false;
+        for child in arm.syntax().children() {
+            match child.kind() {
+                IF_KW => seen_if = true,
+                FAT_ARROW => seen_arrow = true,
+                _ => (),
+            }
+            if let Some(pat) = ast::Pat::cast(child) {
+                pats.push(self.collect_pat(pat));
+            } else if let Some(e) = ast::Expr::cast(child) {
+                if seen_arrow {
+                    expr = Some(self.collect_expr(e));
+                } else if seen_if {
+                    guard = Some(self.collect_expr(e));
+                } else {
+                    let lit = self.collect_expr(e);
+                    let pat = self.alloc_pat(Pat::Lit(lit), LocalSyntaxPtr::new(e.syntax()));
+                    pats.push(pat);
+                }
+            }
+        }
+        let expr = expr.unwrap_or_else(|| self.exprs.alloc(Expr::Missing));
+        MatchArm { pats, guard, expr }
+    }
+
     fn collect_pat(&mut self, pat: &ast::Pat) -> PatId {
         let syntax_ptr = LocalSyntaxPtr::new(pat.syntax());
         match pat.kind() {
@@ -684,6 +849,19 @@ impl ExprCollector {
                 let args = p.args().map(|p| self.collect_pat(p)).collect();
                 self.alloc_pat(Pat::TupleStruct { path, args }, syntax_ptr)
             }
+            ast::PatKind::TuplePat(p) => {
+                let args = p.args().map(|p| self.collect_pat(p)).collect();
+                self.alloc_pat(Pat::Tuple(args), syntax_ptr)
+            }
+            ast::PatKind::PlaceholderPat(_) => self.alloc_pat(Pat::Wild, syntax_ptr),
+            ast::PatKind::PathPat(p) => {
+                let pat = p
+                    .path()
+                    .and_then(Path::from_ast)
+                    .map(Pat::Path)
+                    .unwrap_or(Pat::Missing);
+                self.alloc_pat(pat, syntax_ptr)
+            }
             _ => {
                 // TODO
                 self.alloc_pat(Pat::Missing, syntax_ptr)
@@ -1,5 +1,5 @@
-use ra_db::{SourceRootId, LocationIntener, Cancelable, FileId};
-use ra_syntax::{TreeArc, SyntaxKind, SyntaxNode, SourceFile, AstNode, ast};
+use ra_db::{SourceRootId, LocationIntener, Cancelable, FileId, FileRange};
+use ra_syntax::{TreeArc, SyntaxKind, SyntaxNode, SourceFile, TextRange, AstNode, ast};
 use ra_arena::{Arena, RawId, impl_arena_id};
 
 use crate::{
@@ -43,6 +43,40 @@ impl HirFileId {
         }
     }
 
+    /// Maps a range in this file to the original source file. Ranges inside
+    /// of macro expansions are mapped back to the tokens of the macro call
+    /// they came from or, if there are no such tokens, to the whole call.
+    pub fn original_range(self, db: &impl HirDatabase, range: TextRange) -> FileRange {
+        match self.0 {
+            HirFileIdRepr::File(file_id) => FileRange { file_id, range },
+            HirFileIdRepr::Macro(macro_call_id) => {
+                let loc = macro_call_id.loc(db);
+                let call_range = match map_range_to_call(db, macro_call_id, range) {
+                    Some(it) => it,
+                    None => db.file_item(loc.source_item_id).range(),
+                };
+                loc.source_item_id.file_id.original_range(db, call_range)
+            }
+        }
+    }
+
+    /// Like `original_range`, but `None` for the ranges which don't come from
+    /// the tokens of a macro call, rather than the range of the whole call.
+    pub fn exact_original_range(
+        self,
+        db: &impl HirDatabase,
+        range: TextRange,
+    ) -> Option<FileRange> {
+        match self.0 {
+            HirFileIdRepr::File(file_id) => Some(FileRange { file_id, range }),
+            HirFileIdRepr::Macro(macro_call_id) => {
+                let call_range = map_range_to_call(db, macro_call_id, range)?;
+                let loc = macro_call_id.loc(db);
+                loc.source_item_id.file_id.exact_original_range(db, call_range)
+            }
+        }
+    }
+
     pub(crate) fn as_original_file(self) -> FileId {
         match self.0 {
             HirFileIdRepr::File(file_id) => file_id,
@@ -74,6 +108,20 @@ impl HirFileId {
     }
 }
 
+/// Maps a range in the expansion of a macro call to the tokens of the call.
+fn map_range_to_call(
+    db: &impl HirDatabase,
+    macro_call_id: MacroCallId,
+    range: TextRange,
+) -> Option<TextRange> {
+    let loc = macro_call_id.loc(db);
+    let syntax = db.file_item(loc.source_item_id);
+    let macro_call = ast::MacroCall::cast(&syntax)?;
+    let expansion = db.expand_macro_invocation(macro_call_id)?;
+    let range = expansion.map_range_back(range)?;
+    Some(range + macro_call.token_tree()?.syntax().range().start())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum HirFileIdRepr {
     File(FileId),
@@ -13,13 +13,7 @@ use crate::{
     module_tree::ModuleId,
 };
 
-use crate::code_model_api::{Module, ModuleSource};
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ImplBlock {
-    module_impl_blocks: Arc<ModuleImplBlocks>,
-    impl_id: ImplId,
-}
+use crate::code_model_api::{Module, ModuleSource, ImplBlock};
 
 impl ImplBlock {
     pub(crate) fn containing(
@@ -40,25 +34,14 @@ impl ImplBlock {
         }
     }
 
-    fn impl_data(&self) -> &ImplData {
+    pub(crate) fn impl_data(&self) -> &ImplData {
         &self.module_impl_blocks.impls[self.impl_id]
     }
-
-    pub fn target_trait(&self) -> Option<&TypeRef> {
-        self.impl_data().target_trait()
-    }
-
-    pub fn target_type(&self) -> &TypeRef {
-        self.impl_data().target_type()
-    }
-
-    pub fn items(&self) -> &[ImplItem] {
-        self.impl_data().items()
-    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ImplData {
+    pub(crate) source_item_id: SourceItemId,
     target_trait: Option<TypeRef>,
     target_type: TypeRef,
     items: Vec<ImplItem>,
@@ -74,13 +57,17 @@ impl ImplData {
         let target_trait = node.target_trait().map(TypeRef::from_ast);
         let target_type = TypeRef::from_ast_opt(node.target_type());
         let module_loc = module.def_id.loc(db);
+        let source_item_id = SourceItemId {
+            file_id: module_loc.source_item_id.file_id,
+            item_id: Some(file_items.id_of_unchecked(node.syntax())),
+        };
         let items = if let Some(item_list) = node.item_list() {
             item_list
                 .impl_items()
                 .map(|item_node| {
                     let kind = match item_node.kind() {
                         ast::ImplItemKind::FnDef(..) => DefKind::Function,
-                        ast::ImplItemKind::ConstDef(..) => DefKind::Item,
+                        ast::ImplItemKind::ConstDef(..) => DefKind::Const,
                         ast::ImplItemKind::TypeDef(..) => DefKind::Item,
                     };
                     let item_id = file_items.id_of_unchecked(item_node.syntax());
@@ -105,6 +92,7 @@ impl ImplData {
             Vec::new()
         };
         ImplData {
+            source_item_id,
             target_trait,
             target_type,
             items,
@@ -154,19 +142,22 @@ impl_arena_id!(ImplId);
 /// we don't need to do the second step again.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ModuleImplBlocks {
+    pub(crate) module: Module,
     pub(crate) impls: Arena<ImplId, ImplData>,
     impls_by_def: FxHashMap<DefId, ImplId>,
 }
 
 impl ModuleImplBlocks {
-    fn new() -> Self {
+    fn new(module: Module) -> Self {
         ModuleImplBlocks {
+            module,
             impls: Arena::default(),
             impls_by_def: FxHashMap::default(),
         }
     }
 
-    fn collect(&mut self, db: &impl HirDatabase, module: Module) -> Cancelable<()> {
+    fn collect(&mut self, db: &impl HirDatabase) -> Cancelable<()> {
+        let module = self.module.clone();
         let (file_id, module_source) = module.definition_source(db)?;
         let node = match &module_source {
             ModuleSource::SourceFile(node) => node.syntax(),
@@ -195,8 +186,59 @@ pub(crate) fn impls_in_module(
     source_root_id: SourceRootId,
     module_id: ModuleId,
 ) -> Cancelable<Arc<ModuleImplBlocks>> {
-    let mut result = ModuleImplBlocks::new();
     let module = Module::from_module_id(db, source_root_id, module_id)?;
-    result.collect(db, module)?;
+    let mut result = ModuleImplBlocks::new(module);
+    result.collect(db)?;
     Ok(Arc::new(result))
 }
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::AstNode;
+
+    use crate::{mock::MockDatabase, source_binder, type_ref::TypeRef, ImplItem};
+
+    #[test]
+    fn module_impl_blocks() {
+        let (db, pos) = MockDatabase::with_position(
+            "
+            //- /lib.rs
+            struct Foo;
+            trait Trait { fn frob(&self); }
+            impl Foo {
+                fn new() -> Foo { Foo }
+                const ANSWER: u32 = 92;
+            }
+            impl Trait for Foo {
+                fn frob(&self) {}
+            }
+            <|>
+        ",
+        );
+        let module = source_binder::module_from_position(&db, pos).unwrap().unwrap();
+        let impls = module.impl_blocks(&db).unwrap();
+        assert_eq!(impls.len(), 2);
+
+        let inherent = &impls[0];
+        assert!(inherent.target_trait().is_none());
+        match inherent.target_type() {
+            TypeRef::Path(path) => assert_eq!(path.as_ident().unwrap().to_string(), "Foo"),
+            ty => panic!("unexpected target type: {:?}", ty),
+        }
+        match inherent.items() {
+            [ImplItem::Method(_), ImplItem::Const(_)] => (),
+            items => panic!("unexpected impl items: {:?}", items),
+        }
+        let (_, syntax) = inherent.source(&db).unwrap();
+        assert!(syntax.syntax().text().to_string().starts_with("impl Foo {"));
+
+        let trait_impl = &impls[1];
+        assert!(trait_impl.target_trait().is_some());
+        match trait_impl.items() {
+            [ImplItem::Method(_)] => (),
+            items => panic!("unexpected impl items: {:?}", items),
+        }
+        let (_, syntax) = trait_impl.source(&db).unwrap();
+        assert!(syntax.syntax().text().to_string().starts_with("impl Trait for Foo {"));
+    }
+}
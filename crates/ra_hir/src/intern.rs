@@ -0,0 +1,77 @@
+//! Per-database interner for types.
+//!
+//! The same types (`&str`, `&mut Vec<String>`) are mentioned over and over
+//! across a workspace. `TyInterner` stores each distinct type once, assigns it
+//! a `TyId` and hands out shared `Arc`s to it. Like `LocationIntener`, it lives
+//! next to the salsa storage and is shared between snapshots of the database.
+use std::{panic, sync::Arc};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use ra_arena::{Arena, RawId, impl_arena_id};
+
+use crate::Ty;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TyId(RawId);
+impl_arena_id!(TyId);
+
+#[derive(Debug, Default)]
+pub struct TyInterner {
+    map: Mutex<TyMap>,
+}
+
+#[derive(Debug, Default)]
+struct TyMap {
+    id2ty: Arena<TyId, Arc<Ty>>,
+    ty2id: FxHashMap<Arc<Ty>, TyId>,
+}
+
+impl panic::RefUnwindSafe for TyInterner {}
+
+impl TyInterner {
+    /// The number of distinct types stored in the interner.
+    pub fn len(&self) -> usize {
+        self.map.lock().id2ty.len()
+    }
+
+    pub fn ty2id(&self, ty: Ty) -> TyId {
+        let mut map = self.map.lock();
+        if let Some(&id) = map.ty2id.get(&ty) {
+            return id;
+        }
+        let ty = Arc::new(ty);
+        let id = map.id2ty.alloc(Arc::clone(&ty));
+        map.ty2id.insert(ty, id);
+        id
+    }
+
+    pub fn id2ty(&self, id: TyId) -> Arc<Ty> {
+        Arc::clone(&self.map.lock().id2ty[id])
+    }
+
+    /// Returns the shared copy of `ty`. Only types written in the source
+    /// should be interned: the ones produced during inference may contain type
+    /// variables.
+    pub(crate) fn intern(&self, ty: Ty) -> Arc<Ty> {
+        let id = self.ty2id(ty);
+        self.id2ty(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_types_share_an_id() {
+        let interner = TyInterner::default();
+        let str_id = interner.ty2id(Ty::Str);
+        let array = Ty::Array(interner.intern(Ty::Str));
+        assert_eq!(interner.ty2id(Ty::Str), str_id);
+        assert_ne!(interner.ty2id(array.clone()), str_id);
+        assert!(Arc::ptr_eq(&interner.intern(Ty::Str), &interner.id2ty(str_id)));
+        assert_eq!(*interner.id2ty(interner.ty2id(array.clone())), array);
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -24,11 +24,13 @@ mod path;
 pub mod source_binder;
 
 mod ids;
+mod intern;
 mod macros;
 mod name;
 mod module_tree;
 mod nameres;
 mod adt;
+mod traits;
 mod type_ref;
 mod ty;
 mod impl_block;
@@ -47,19 +49,24 @@ pub use self::{
     path::{Path, PathKind},
     name::Name,
     ids::{HirFileId, DefId, DefLoc, MacroCallId, MacroCallLoc},
+    intern::{TyInterner, TyId},
     macros::{MacroDef, MacroInput, MacroExpansion},
-    nameres::{ItemMap, PerNs, Namespace, Resolution},
-    ty::Ty,
-    impl_block::{ImplBlock, ImplItem},
-    code_model_impl::function::{FnScopes, ScopesWithSyntaxMapping},
+    nameres::{ItemMap, PerNs, Namespace, Resolution, NamedImport, BlockScope},
+    ty::{Ty, FnSig, TraitBound},
+    type_ref::{TypeRef, Mutability},
+    adt::VariantData,
+    traits::ObjectSafetyViolation,
+    impl_block::ImplItem,
+    code_model_impl::function::{FnScopes, ScopesWithSyntaxMapping, MissingMatchArms},
 };
 
 pub use self::code_model_api::{
     Crate, CrateDependency,
     Def,
     Module, ModuleSource, Problem,
-    Struct, Enum, EnumVariant,
-    Function, FnSignature, ScopeEntryWithSyntax,
+    Struct, StructField, Enum, EnumVariant,
+    Function, FnSignature, WherePredicate, ScopeEntryWithSyntax, BodyProblem,
     Static, Const,
     Trait, Type,
+    ImplBlock,
 };
@@ -2,11 +2,14 @@ use std::{sync::Arc, panic};
 
 use parking_lot::Mutex;
 use salsa::{self, Database};
-use ra_db::{LocationIntener, BaseDatabase, FilePosition, FileId, CrateGraph, SourceRoot, SourceRootId};
+use ra_db::{
+    LocationIntener, BaseDatabase, FilePosition, FileId, CrateGraph, SourceRoot, SourceRootId,
+    Edition,
+};
 use relative_path::RelativePathBuf;
 use test_utils::{parse_fixture, CURSOR_MARKER, extract_offset};
 
-use crate::{db, DefId, DefLoc, MacroCallId, MacroCallLoc};
+use crate::{db, DefId, DefLoc, MacroCallId, MacroCallLoc, TyInterner};
 
 pub const WORKSPACE: SourceRootId = SourceRootId(0);
 
@@ -35,7 +38,7 @@ impl MockDatabase {
             .set(WORKSPACE, Arc::new(source_root.clone()));
 
         let mut crate_graph = CrateGraph::default();
-        crate_graph.add_crate_root(file_id);
+        crate_graph.add_crate_root(file_id, Edition::Edition2018);
         db.set_crate_graph(crate_graph);
         (db, source_root, file_id)
     }
@@ -125,6 +128,7 @@ impl MockDatabase {
 struct IdMaps {
     defs: LocationIntener<DefLoc, DefId>,
     macros: LocationIntener<MacroCallLoc, MacroCallId>,
+    types: TyInterner,
 }
 
 impl salsa::Database for MockDatabase {
@@ -181,6 +185,11 @@ impl AsRef<LocationIntener<MacroCallLoc, MacroCallId>> for MockDatabase {
         &self.id_maps.macros
     }
 }
+impl AsRef<TyInterner> for MockDatabase {
+    fn as_ref(&self) -> &TyInterner {
+        &self.id_maps.types
+    }
+}
 
 impl MockDatabase {
     pub(crate) fn log(&self, f: impl FnOnce()) -> Vec<salsa::Event<MockDatabase>> {
@@ -223,9 +232,11 @@ salsa::database_storage! {
             fn expand_macro_invocation() for db::ExpandMacroCallQuery;
             fn module_tree() for db::ModuleTreeQuery;
             fn fn_scopes() for db::FnScopesQuery;
+            fn body_block_scopes() for db::BodyBlockScopesQuery;
             fn file_items() for db::SourceFileItemsQuery;
             fn file_item() for db::FileItemQuery;
             fn input_module_items() for db::InputModuleItemsQuery;
+            fn module_declarations() for db::ModuleDeclarationsQuery;
             fn item_map() for db::ItemMapQuery;
             fn submodules() for db::SubmodulesQuery;
             fn infer() for db::InferQuery;
@@ -234,6 +245,7 @@ salsa::database_storage! {
             fn struct_data() for db::StructDataQuery;
             fn enum_data() for db::EnumDataQuery;
             fn enum_variant_data() for db::EnumVariantDataQuery;
+            fn trait_data() for db::TraitDataQuery;
             fn impls_in_module() for db::ImplsInModuleQuery;
             fn impls_in_crate() for db::ImplsInCrateQuery;
             fn body_hir() for db::BodyHirQuery;
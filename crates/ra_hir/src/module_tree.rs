@@ -2,12 +2,13 @@ use std::sync::Arc;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 use arrayvec::ArrayVec;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use ra_db::{FileId, SourceRootId, Cancelable, SourceRoot};
 use ra_syntax::{
     SyntaxNode, TreeArc,
     algo::generate,
-    ast::{self, AstNode, NameOwner},
+    ast::{self, AstNode, AttrsOwner, NameOwner},
+    SyntaxKind::MODULE,
 };
 use ra_arena::{Arena, RawId, impl_arena_id};
 
@@ -28,16 +29,33 @@ impl ModuleSource {
             panic!("expected file or inline module")
         }
     }
+
+    pub(crate) fn syntax(&self) -> &SyntaxNode {
+        match self {
+            ModuleSource::SourceFile(it) => it.syntax(),
+            ModuleSource::Module(it) => it.syntax(),
+        }
+    }
 }
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub struct Submodule {
     name: Name,
     is_declaration: bool,
+    /// Whether the module is declared in a block, like in a function body.
+    is_in_block: bool,
+    /// Value of the `#[path = "..."]` attribute, if any.
+    attr_path: Option<RelativePathBuf>,
+    /// Path to the inline module which contains the declaration, relative to
+    /// the directory of its file.
+    inline_path: RelativePathBuf,
     source: SourceItemId,
 }
 
 impl Submodule {
+    /// Everything the module tree needs to know about the syntax is computed
+    /// here, so that the tree itself is not recomputed when a file changes,
+    /// unless the submodule declarations of the file change.
     pub(crate) fn submodules_query(
         db: &impl HirDatabase,
         source: SourceItemId,
@@ -46,25 +64,36 @@ impl Submodule {
         let file_id = source.file_id;
         let file_items = db.file_items(file_id);
         let module_source = ModuleSource::from_source_item_id(db, source);
-        let submodules = match module_source {
+        let mut submodules = match &module_source {
             ModuleSource::SourceFile(source_file) => {
-                collect_submodules(file_id, &file_items, &*source_file)
+                collect_submodules(file_id, &file_items, &**source_file, false)
             }
             ModuleSource::Module(module) => {
-                collect_submodules(file_id, &file_items, module.item_list().unwrap())
+                collect_submodules(file_id, &file_items, module.item_list().unwrap(), false)
             }
         };
+        for block in module_blocks(module_source.syntax()) {
+            submodules.extend(collect_submodules(file_id, &file_items, block, true));
+        }
         return Ok(Arc::new(submodules));
 
         fn collect_submodules(
             file_id: HirFileId,
             file_items: &SourceFileItems,
             root: &impl ast::ModuleItemOwner,
+            is_in_block: bool,
         ) -> Vec<Submodule> {
             modules(root)
                 .map(|(name, m)| Submodule {
                     name,
                     is_declaration: m.has_semi(),
+                    is_in_block,
+                    attr_path: m
+                        .attrs()
+                        .filter_map(|it| it.as_key_value())
+                        .find(|(key, _)| key == "path")
+                        .map(|(_, value)| RelativePathBuf::from(value.as_str())),
+                    inline_path: inline_module_path(m),
                     source: SourceItemId {
                         file_id,
                         item_id: Some(file_items.id_of(file_id, m.syntax())),
@@ -108,6 +137,8 @@ struct LinkData {
     source: SourceItemId,
     owner: ModuleId,
     name: Name,
+    /// Whether the module is declared in a block, where only the block sees it.
+    is_in_block: bool,
     points_to: Vec<ModuleId>,
     problem: Option<Problem>,
 }
@@ -127,8 +158,19 @@ impl ModuleTree {
     }
 
     pub(crate) fn find_module_by_source(&self, source: SourceItemId) -> Option<ModuleId> {
-        let (res, _) = self.mods.iter().find(|(_, m)| m.source == source)?;
-        Some(res)
+        self.find_modules_by_source(source).next()
+    }
+
+    /// A single file might be included into the tree from several places, and
+    /// each inclusion gets a separate `ModuleId`.
+    pub(crate) fn find_modules_by_source<'a>(
+        &'a self,
+        source: SourceItemId,
+    ) -> impl Iterator<Item = ModuleId> + 'a {
+        self.mods
+            .iter()
+            .filter(move |(_, m)| m.source == source)
+            .map(|(id, _)| id)
     }
 }
 
@@ -166,6 +208,34 @@ impl ModuleId {
             Some((link.name.clone(), module))
         })
     }
+    /// The children declared in the item list of the module, which are a part
+    /// of its scope, unlike the ones declared in its blocks.
+    pub(crate) fn scope_children<'a>(
+        self,
+        tree: &'a ModuleTree,
+    ) -> impl Iterator<Item = (Name, ModuleId)> + 'a {
+        tree.mods[self].children.iter().filter_map(move |&it| {
+            let link = &tree.links[it];
+            if link.is_in_block {
+                return None;
+            }
+            let module = *link.points_to.first()?;
+            Some((link.name.clone(), module))
+        })
+    }
+    /// The child declared by the `mod` item at `source`.
+    pub(crate) fn child_by_source(
+        self,
+        tree: &ModuleTree,
+        source: SourceItemId,
+    ) -> Option<ModuleId> {
+        tree.mods[self]
+            .children
+            .iter()
+            .map(|&it| &tree.links[it])
+            .find(|link| link.source == source)
+            .and_then(|link| link.points_to.first().cloned())
+    }
     pub(crate) fn problems(
         self,
         tree: &ModuleTree,
@@ -225,6 +295,20 @@ fn modules(root: &impl ast::ModuleItemOwner) -> impl Iterator<Item = (Name, &ast
         })
 }
 
+/// The blocks of the functions (and of other items) of the module. Modules
+/// declared in them, like `mod tests {}` inside of a function body, are
+/// children of the module, but only the block sees them. Blocks of nested
+/// inline modules belong to those modules.
+fn module_blocks(module: &SyntaxNode) -> impl Iterator<Item = &ast::Block> {
+    module
+        .descendants()
+        .filter_map(ast::Block::cast)
+        .filter(move |block| {
+            let owner = block.syntax().ancestors().find(|it| it.kind() == MODULE);
+            owner.map_or(true, |it| it == module)
+        })
+}
+
 fn create_module_tree<'a>(
     db: &impl HirDatabase,
     source_root: SourceRootId,
@@ -235,7 +319,16 @@ fn create_module_tree<'a>(
     let mut visited = FxHashSet::default();
 
     let source_root = db.source_root(source_root);
-    for &file_id in source_root.files.values() {
+    // Start with crate roots, so that the files which are included via `mod`
+    // declarations are reached from their parents, and not treated as roots.
+    let crate_graph = db.crate_graph();
+    let mut files = source_root.files.values().map(|&it| it).collect::<Vec<_>>();
+    files.sort_by_key(|&file_id| {
+        let is_crate_root = crate_graph.crate_id_for_crate_root(file_id).is_some();
+        (!is_crate_root, file_id)
+    });
+    for file_id in files {
+        db.check_canceled()?;
         let source = SourceItemId {
             file_id: file_id.into(),
             item_id: None,
@@ -252,6 +345,7 @@ fn create_module_tree<'a>(
             &mut roots,
             None,
             source,
+            false,
         )?;
         roots.insert(file_id, module_id);
     }
@@ -266,6 +360,7 @@ fn build_subtree(
     roots: &mut FxHashMap<FileId, ModuleId>,
     parent: Option<LinkId>,
     source: SourceItemId,
+    is_attr_path_owner: bool,
 ) -> Cancelable<ModuleId> {
     visited.insert(source);
     let id = tree.push_mod(ModuleData {
@@ -278,12 +373,26 @@ fn build_subtree(
             source: sub.source,
             name: sub.name.clone(),
             owner: id,
+            is_in_block: sub.is_in_block,
             points_to: Vec::new(),
             problem: None,
         });
 
-        let (points_to, problem) = if sub.is_declaration {
-            let (points_to, problem) = resolve_submodule(db, source.file_id, &sub.name);
+        // Rust doesn't allow `mod foo;` in blocks without `#[path]`, so there
+        // is no file to look for.
+        let is_bare_block_declaration =
+            sub.is_declaration && sub.is_in_block && sub.attr_path.is_none();
+        let (points_to, problem) = if is_bare_block_declaration {
+            (Vec::new(), None)
+        } else if sub.is_declaration {
+            let (points_to, problem) = resolve_submodule(
+                db,
+                source,
+                &sub.name,
+                is_attr_path_owner,
+                sub.attr_path.as_ref(),
+                &sub.inline_path,
+            );
             let points_to = points_to
                 .into_iter()
                 .map(|file_id| match roots.remove(&file_id) {
@@ -302,6 +411,7 @@ fn build_subtree(
                             file_id: file_id.into(),
                             item_id: None,
                         },
+                        sub.attr_path.is_some(),
                     ),
                 })
                 .collect::<Cancelable<Vec<_>>>()?;
@@ -315,6 +425,7 @@ fn build_subtree(
                 roots,
                 Some(link),
                 sub.source,
+                is_attr_path_owner,
             )?;
             (vec![points_to], None)
         };
@@ -325,42 +436,88 @@ fn build_subtree(
     Ok(id)
 }
 
+/// Resolves `mod name;` declared in the module defined by `source`.
+///
+/// `is_attr_path_owner` is true if the parent module itself was loaded via a
+/// `#[path]` attribute: such files own their directory, like `mod.rs` does.
 fn resolve_submodule(
     db: &impl HirDatabase,
-    file_id: HirFileId,
+    source: SourceItemId,
     name: &Name,
+    is_attr_path_owner: bool,
+    attr_path: Option<&RelativePathBuf>,
+    inline_path: &RelativePath,
 ) -> (Vec<FileId>, Option<Problem>) {
-    // FIXME: handle submodules of inline modules properly
-    let file_id = file_id.original_file(db);
+    let file_id = source.file_id.original_file(db);
     let source_root_id = db.file_source_root(file_id);
     let path = db.file_relative_path(file_id);
     let root = RelativePathBuf::default();
     let dir_path = path.parent().unwrap_or(&root);
     let mod_name = path.file_stem().unwrap_or("unknown");
-    let is_dir_owner = mod_name == "mod" || mod_name == "lib" || mod_name == "main";
+    let is_dir_owner =
+        is_attr_path_owner || mod_name == "mod" || mod_name == "lib" || mod_name == "main";
 
-    let file_mod = dir_path.join(format!("{}.rs", name));
-    let dir_mod = dir_path.join(format!("{}/mod.rs", name));
-    let file_dir_mod = dir_path.join(format!("{}/{}.rs", mod_name, name));
-    let mut candidates = ArrayVec::<[_; 2]>::new();
-    if is_dir_owner {
-        candidates.push(file_mod.clone());
-        candidates.push(dir_mod);
+    // `mod foo;` inside `mod bar { ... }` refers to `bar/foo.rs`.
+    let mut dir_path = if is_dir_owner {
+        RelativePathBuf::from(dir_path.as_str())
     } else {
-        candidates.push(file_dir_mod.clone());
+        dir_path.join(mod_name)
     };
+    if !inline_path.as_str().is_empty() {
+        dir_path = dir_path.join(inline_path);
+    }
+
+    let mut candidates = ArrayVec::<[_; 2]>::new();
+    match attr_path {
+        Some(attr_path) if inline_path.as_str().is_empty() => {
+            // Outside of inline modules, `#[path]` is relative to the directory
+            // of the current file, regardless of the directory ownership.
+            let dir = path.parent().unwrap_or(&root);
+            candidates.push(dir.join(attr_path));
+        }
+        Some(attr_path) => candidates.push(dir_path.join(attr_path)),
+        None => {
+            candidates.push(dir_path.join(format!("{}.rs", name)));
+            // `bar.rs` keeps its submodules in `bar/`, without nesting `mod.rs`
+            // files below a non-`mod.rs` one.
+            if is_dir_owner {
+                candidates.push(dir_path.join(format!("{}/mod.rs", name)));
+            }
+        }
+    }
+    let candidates = candidates
+        .into_iter()
+        .map(|path| path.normalize())
+        .collect::<Vec<_>>();
     let sr = db.source_root(source_root_id);
     let points_to = candidates
-        .into_iter()
-        .filter_map(|path| sr.files.get(&path))
+        .iter()
+        .filter_map(|path| sr.files.get(path))
         .map(|&it| it)
         .collect::<Vec<_>>();
     let problem = if points_to.is_empty() {
-        Some(Problem::UnresolvedModule {
-            candidate: if is_dir_owner { file_mod } else { file_dir_mod },
-        })
+        Some(Problem::UnresolvedModule { candidates })
     } else {
         None
     };
     (points_to, problem)
 }
+
+/// Path to the inline module which contains the declaration of `module`,
+/// relative to the directory of its file.
+fn inline_module_path(module: &ast::Module) -> RelativePathBuf {
+    let mut res = RelativePathBuf::default();
+    let mut names = module
+        .syntax()
+        .ancestors()
+        .skip(1)
+        .filter_map(ast::Module::cast)
+        .filter_map(|it| it.name())
+        .map(|it| it.text().clone())
+        .collect::<Vec<_>>();
+    names.reverse();
+    for name in names {
+        res.push(name.as_str());
+    }
+    res
+}
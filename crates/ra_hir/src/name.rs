@@ -5,6 +5,9 @@ use ra_syntax::{ast, SmolStr};
 /// `Name` is a wrapper around string, which is used in hir for both references
 /// and declarations. In theory, names should also carry hygiene info, but we are
 /// not there yet!
+///
+/// `SmolStr` stores short names inline and shares long ones, so the copies of
+/// the same name don't allocate.
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Name {
     text: SmolStr,
@@ -23,7 +26,7 @@ impl fmt::Debug for Name {
 }
 
 impl Name {
-    fn new(text: SmolStr) -> Name {
+    pub(crate) fn new(text: SmolStr) -> Name {
         Name { text }
     }
 
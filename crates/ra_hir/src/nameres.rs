@@ -14,26 +14,35 @@
 //! modifications (that is, typing inside a function should not change IMIs),
 //! so that the results of name resolution can be preserved unless the module
 //! structure itself is modified.
+//!
+//! The scope of directly declared items is computed per module as well, by the
+//! `module_declarations` query, so a change to one module's items only redoes
+//! the import fixpoint, and not the lowering of every other module.
+mod block_scope;
+
 use std::sync::Arc;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 use ra_syntax::{
-    TextRange,
+    TextRange, TreeArc, SyntaxNode,
     SyntaxKind::{self, *},
-    ast::{self, AstNode}
+    ast::{self, AstNode, NameOwner}
 };
-use ra_db::{SourceRootId, Cancelable, FileId};
+use ra_db::{SourceRootId, Cancelable};
 
 use crate::{
     HirFileId,
     DefId, DefLoc, DefKind,
     SourceItemId, SourceFileItemId, SourceFileItems,
     Path, PathKind,
-    HirDatabase, Crate,
+    HirDatabase, Crate, Problem,
     Name, AsName,
     module_tree::{ModuleId, ModuleTree},
 };
 
+pub(crate) use self::block_scope::{enclosing_scopes, resolve_path as resolve_path_in_blocks};
+pub use self::block_scope::{BlockScope, BodyBlockScopes};
+
 /// `ItemMap` is the result of name resolution. It contains, for each
 /// module, the set of visible items.
 // FIXME: currenty we compute item map per source-root. We should do it per crate instead.
@@ -54,6 +63,107 @@ impl ModuleScope {
     pub fn get(&self, name: &Name) -> Option<&Resolution> {
         self.items.get(name)
     }
+
+    /// The scope of the module before imports are resolved: the extern crates
+    /// prelude, the items declared in the module, its child modules and
+    /// placeholders for the names of the imports.
+    pub(crate) fn module_declarations_query(
+        db: &impl HirDatabase,
+        source_root: SourceRootId,
+        module_id: ModuleId,
+    ) -> Cancelable<Arc<ModuleScope>> {
+        let module_tree = db.module_tree(source_root)?;
+        let input = db.input_module_items(source_root, module_id)?;
+        let mut module_items = ModuleScope::default();
+
+        // Populate extern crates prelude
+        {
+            let root_id = module_id.crate_root(&module_tree);
+            let file_id = root_id.source(&module_tree).file_id;
+            let crate_graph = db.crate_graph();
+            if let Some(crate_id) = crate_graph.crate_id_for_crate_root(file_id.as_original_file())
+            {
+                let krate = Crate::new(crate_id);
+                let deps = krate.dependencies(db)?;
+                for dep in deps.iter() {
+                    if let Some(module) = dep.krate.root_module(db)? {
+                        let def_id = module.def_id;
+                        module_items.add_item(dep.name.clone(), PerNs::types(def_id));
+                    }
+                }
+                // `extern crate foo as bar;` makes the dependency visible as
+                // `bar` as well: in the module declaring it, and, when declared
+                // in the crate root, in every module of the crate.
+                let root_input = if root_id == module_id {
+                    None
+                } else {
+                    Some(db.input_module_items(source_root, root_id)?)
+                };
+                let extern_crates = root_input
+                    .iter()
+                    .flat_map(|it| it.extern_crates.iter())
+                    .chain(input.extern_crates.iter());
+                for extern_crate in extern_crates {
+                    let alias = match &extern_crate.alias {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    let dep = deps.iter().find(|dep| dep.name == extern_crate.name);
+                    if let Some(dep) = dep {
+                        if let Some(module) = dep.krate.root_module(db)? {
+                            module_items.add_item(alias.clone(), PerNs::types(module.def_id));
+                        }
+                    }
+                }
+            };
+        }
+        for import in input.imports.iter() {
+            if let Some(name) = import.path.segments.iter().last() {
+                if let ImportKind::Named(import) = import.kind {
+                    module_items.items.insert(
+                        name.clone(),
+                        Resolution {
+                            def_id: PerNs::none(),
+                            import: Some(import),
+                        },
+                    );
+                }
+            }
+        }
+        // Populate explicitly declared items, except modules
+        for item in input.items.iter() {
+            if item.kind == MODULE {
+                continue;
+            }
+            let resolution = Resolution {
+                def_id: item.def_id(db, source_root, module_id),
+                import: None,
+            };
+            module_items.items.insert(item.name.clone(), resolution);
+        }
+
+        // Populate modules
+        for (name, module_id) in module_id.scope_children(&module_tree) {
+            let def_loc = DefLoc {
+                kind: DefKind::Module,
+                source_root_id: source_root,
+                module_id,
+                source_item_id: module_id.source(&module_tree),
+            };
+            let def_id = def_loc.id(db);
+            module_items.add_item(name, PerNs::types(def_id));
+        }
+
+        Ok(Arc::new(module_items))
+    }
+
+    fn add_item(&mut self, name: Name, def_id: PerNs<DefId>) {
+        let resolution = Resolution {
+            def_id,
+            import: None,
+        };
+        self.items.insert(name, resolution);
+    }
 }
 
 /// A set of items and imports declared inside a module, without relation to
@@ -66,6 +176,67 @@ impl ModuleScope {
 pub struct InputModuleItems {
     pub(crate) items: Vec<ModuleItem>,
     imports: Vec<Import>,
+    extern_crates: Vec<ExternCrate>,
+}
+
+impl InputModuleItems {
+    /// Items defined in a namespace which already has an item with the same
+    /// name, like two `fn foo`, or `struct Foo` and `mod Foo`, reported on
+    /// the name of the later item. Items with `#[cfg]` attributes are skipped,
+    /// as only one of the definitions is usually enabled.
+    pub(crate) fn duplicate_definitions(
+        &self,
+        db: &impl HirDatabase,
+    ) -> Vec<(TreeArc<SyntaxNode>, Problem)> {
+        let mut first_items: FxHashMap<(Namespace, &Name), &ModuleItem> = FxHashMap::default();
+        let mut res = Vec::new();
+        for item in self.items.iter() {
+            // Items generated by macros have no names to point at.
+            if item.id.file_id.as_macro_call_id().is_some() {
+                continue;
+            }
+            let syntax = db.file_item(item.id);
+            if has_cfg_attr(&syntax) {
+                continue;
+            }
+            let kinds = DefKind::for_syntax_kind(item.kind);
+            let mut first = None;
+            for &(ns, defined) in [
+                (Namespace::Types, kinds.types.is_some()),
+                (Namespace::Values, kinds.values.is_some()),
+            ]
+            .iter()
+            {
+                if !defined {
+                    continue;
+                }
+                let prev = first_items.entry((ns, &item.name)).or_insert(item);
+                if first.is_none() && !std::ptr::eq(*prev, item) {
+                    first = Some(*prev);
+                }
+            }
+            let first = match first {
+                Some(it) => it,
+                None => continue,
+            };
+            let name = syntax.children().find(|it| it.kind() == NAME);
+            let first_name = db
+                .file_item(first.id)
+                .children()
+                .find(|it| it.kind() == NAME)
+                .map(|it| it.range());
+            if let (Some(name), Some(first)) = (name, first_name) {
+                res.push((name.to_owned(), Problem::DuplicateDefinition { first }));
+            }
+        }
+        res
+    }
+}
+
+fn has_cfg_attr(item: &SyntaxNode) -> bool {
+    item.children()
+        .filter_map(ast::Attr::cast)
+        .any(|attr| attr.name().map_or(false, |it| it.as_str() == "cfg"))
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -82,30 +253,52 @@ enum Vis {
     Other,
 }
 
+/// `extern crate name as alias;`
+#[derive(Debug, PartialEq, Eq)]
+struct ExternCrate {
+    name: Name,
+    alias: Option<Name>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Import {
     path: Path,
     kind: ImportKind,
 }
 
+/// The `use` item which imports a name. For re-exports, like `pub use
+/// self::bar::Bar;` in `foo`, the scope of `foo` records where `Bar` comes
+/// from, so that `foo::Bar` can be resolved both to the definition and to the
+/// re-export.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NamedImport {
+    pub file_id: HirFileId,
     pub file_item_id: SourceFileItemId,
     pub relative_range: TextRange,
 }
 
 impl NamedImport {
-    // FIXME: this is only here for one use-case in completion. Seems like a
-    // pretty gross special case.
-    pub fn range(&self, db: &impl HirDatabase, file_id: FileId) -> TextRange {
-        let source_item_id = SourceItemId {
-            file_id: file_id.into(),
-            item_id: Some(self.file_item_id),
-        };
-        let syntax = db.file_item(source_item_id);
+    /// The `use` item of the import.
+    pub fn use_item(&self, db: &impl HirDatabase) -> TreeArc<ast::UseItem> {
+        let syntax = db.file_item(self.source_item_id());
+        ast::UseItem::cast(&syntax)
+            .expect("import should point to UseItem node")
+            .to_owned()
+    }
+
+    /// The range of the imported path, within the `use` item.
+    pub fn range(&self, db: &impl HirDatabase) -> TextRange {
+        let syntax = db.file_item(self.source_item_id());
         let offset = syntax.range().start();
         self.relative_range + offset
     }
+
+    fn source_item_id(&self) -> SourceItemId {
+        SourceItemId {
+            file_id: self.file_id,
+            item_id: Some(self.file_item_id),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -217,6 +410,8 @@ impl InputModuleItems {
         file_items: &SourceFileItems,
         item: &ast::ModuleItem,
     ) -> Option<()> {
+        // `#[cfg]` attributes are not evaluated, so the items behind
+        // `#[cfg(test)]` are always present, as if `test` was enabled.
         match item.kind() {
             ast::ModuleItemKind::StructDef(it) => {
                 self.items.push(ModuleItem::new(file_id, file_items, it)?)
@@ -236,9 +431,11 @@ impl InputModuleItems {
             ast::ModuleItemKind::ImplBlock(_) => {
                 // impls don't define items
             }
-            ast::ModuleItemKind::UseItem(it) => self.add_use_item(file_items, it),
-            ast::ModuleItemKind::ExternCrateItem(_) => {
-                // TODO
+            ast::ModuleItemKind::UseItem(it) => self.add_use_item(file_id, file_items, it),
+            ast::ModuleItemKind::ExternCrateItem(it) => {
+                let name = it.crate_name()?.as_name();
+                let alias = it.alias().and_then(|it| it.name()).map(|it| it.as_name());
+                self.extern_crates.push(ExternCrate { name, alias })
             }
             ast::ModuleItemKind::ConstDef(it) => {
                 self.items.push(ModuleItem::new(file_id, file_items, it)?)
@@ -253,13 +450,19 @@ impl InputModuleItems {
         Some(())
     }
 
-    fn add_use_item(&mut self, file_items: &SourceFileItems, item: &ast::UseItem) {
+    fn add_use_item(
+        &mut self,
+        file_id: HirFileId,
+        file_items: &SourceFileItems,
+        item: &ast::UseItem,
+    ) {
         let file_item_id = file_items.id_of_unchecked(item.syntax());
         let start_offset = item.syntax().range().start();
         Path::expand_use_item(item, |path, range| {
             let kind = match range {
                 None => ImportKind::Glob,
                 Some(range) => ImportKind::Named(NamedImport {
+                    file_id,
                     file_item_id,
                     relative_range: range - start_offset,
                 }),
@@ -288,6 +491,25 @@ impl ModuleItem {
         };
         Some(res)
     }
+
+    /// Depending on the item kind, the location can define something in the
+    /// values namespace, the types namespace, or both.
+    fn def_id(
+        &self,
+        db: &impl HirDatabase,
+        source_root_id: SourceRootId,
+        module_id: ModuleId,
+    ) -> PerNs<DefId> {
+        DefKind::for_syntax_kind(self.kind).map(|kind| {
+            let def_loc = DefLoc {
+                kind,
+                source_root_id,
+                module_id,
+                source_item_id: self.id,
+            };
+            def_loc.id(db)
+        })
+    }
 }
 
 pub(crate) struct Resolver<'a, DB> {
@@ -296,6 +518,8 @@ pub(crate) struct Resolver<'a, DB> {
     source_root: SourceRootId,
     module_tree: Arc<ModuleTree>,
     processed_imports: FxHashSet<(ModuleId, usize)>,
+    /// Whether a glob import added names in the current iteration.
+    glob_imports_changed: bool,
     result: ItemMap,
 }
 
@@ -315,22 +539,27 @@ where
             source_root,
             module_tree,
             processed_imports: FxHashSet::default(),
+            glob_imports_changed: false,
             result: ItemMap::default(),
         }
     }
 
     pub(crate) fn resolve(mut self) -> Cancelable<ItemMap> {
-        for (&module_id, items) in self.input.iter() {
-            self.populate_module(module_id, Arc::clone(items))?;
+        for &module_id in self.input.keys() {
+            self.db.check_canceled()?;
+            self.populate_module(module_id)?;
         }
 
         loop {
             let processed_imports_count = self.processed_imports.len();
+            self.glob_imports_changed = false;
             for &module_id in self.input.keys() {
                 self.db.check_canceled()?;
                 self.resolve_imports(module_id)?;
             }
-            if processed_imports_count == self.processed_imports.len() {
+            if processed_imports_count == self.processed_imports.len()
+                && !self.glob_imports_changed
+            {
                 // no new imports resolved
                 break;
             }
@@ -338,94 +567,14 @@ where
         Ok(self.result)
     }
 
-    fn populate_module(
-        &mut self,
-        module_id: ModuleId,
-        input: Arc<InputModuleItems>,
-    ) -> Cancelable<()> {
-        let mut module_items = ModuleScope::default();
-
-        // Populate extern crates prelude
-        {
-            let root_id = module_id.crate_root(&self.module_tree);
-            let file_id = root_id.source(&self.module_tree).file_id;
-            let crate_graph = self.db.crate_graph();
-            if let Some(crate_id) = crate_graph.crate_id_for_crate_root(file_id.as_original_file())
-            {
-                let krate = Crate::new(crate_id);
-                for dep in krate.dependencies(self.db)? {
-                    if let Some(module) = dep.krate.root_module(self.db)? {
-                        let def_id = module.def_id;
-                        self.add_module_item(
-                            &mut module_items,
-                            dep.name.clone(),
-                            PerNs::types(def_id),
-                        );
-                    }
-                }
-            };
-        }
-        for import in input.imports.iter() {
-            if let Some(name) = import.path.segments.iter().last() {
-                if let ImportKind::Named(import) = import.kind {
-                    module_items.items.insert(
-                        name.clone(),
-                        Resolution {
-                            def_id: PerNs::none(),
-                            import: Some(import),
-                        },
-                    );
-                }
-            }
-        }
-        // Populate explicitly declared items, except modules
-        for item in input.items.iter() {
-            if item.kind == MODULE {
-                continue;
-            }
-            // depending on the item kind, the location can define something in
-            // the values namespace, the types namespace, or both
-            let kind = DefKind::for_syntax_kind(item.kind);
-            let def_id = kind.map(|k| {
-                let def_loc = DefLoc {
-                    kind: k,
-                    source_root_id: self.source_root,
-                    module_id,
-                    source_item_id: item.id,
-                };
-                def_loc.id(self.db)
-            });
-            let resolution = Resolution {
-                def_id,
-                import: None,
-            };
-            module_items.items.insert(item.name.clone(), resolution);
-        }
-
-        // Populate modules
-        for (name, module_id) in module_id.children(&self.module_tree) {
-            let def_loc = DefLoc {
-                kind: DefKind::Module,
-                source_root_id: self.source_root,
-                module_id,
-                source_item_id: module_id.source(&self.module_tree),
-            };
-            let def_id = def_loc.id(self.db);
-            self.add_module_item(&mut module_items, name, PerNs::types(def_id));
-        }
-
-        self.result.per_module.insert(module_id, module_items);
+    fn populate_module(&mut self, module_id: ModuleId) -> Cancelable<()> {
+        let module_items = self.db.module_declarations(self.source_root, module_id)?;
+        self.result
+            .per_module
+            .insert(module_id, ModuleScope::clone(&module_items));
         Ok(())
     }
 
-    fn add_module_item(&self, module_items: &mut ModuleScope, name: Name, def_id: PerNs<DefId>) {
-        let resolution = Resolution {
-            def_id,
-            import: None,
-        };
-        module_items.items.insert(name, resolution);
-    }
-
     fn resolve_imports(&mut self, module_id: ModuleId) -> Cancelable<()> {
         for (i, import) in self.input[&module_id].imports.iter().enumerate() {
             if self.processed_imports.contains(&(module_id, i)) {
@@ -443,23 +592,14 @@ where
     fn resolve_import(&mut self, module_id: ModuleId, import: &Import) -> Cancelable<bool> {
         log::debug!("resolving import: {:?}", import);
         let ptr = match import.kind {
-            ImportKind::Glob => return Ok(false),
+            ImportKind::Glob => return self.resolve_glob_import(module_id, &import.path),
             ImportKind::Named(ptr) => ptr,
         };
 
-        let mut curr: ModuleId = match import.path.kind {
-            PathKind::Plain | PathKind::Self_ => module_id,
-            PathKind::Super => {
-                match module_id.parent(&self.module_tree) {
-                    Some(it) => it,
-                    None => {
-                        // TODO: error
-                        log::debug!("super path in root module");
-                        return Ok(true); // this can't suddenly resolve if we just resolve some other imports
-                    }
-                }
-            }
-            PathKind::Crate => module_id.crate_root(&self.module_tree),
+        let mut curr: ModuleId = match self.path_start(module_id, &import.path) {
+            Some(it) => it,
+            // this can't suddenly resolve if we just resolve some other imports
+            None => return Ok(true),
         };
 
         for (i, name) in import.path.segments.iter().enumerate() {
@@ -550,6 +690,110 @@ where
         Ok(true)
     }
 
+    /// The module which the path starts from, `None` for `super` in the crate
+    /// root.
+    fn path_start(&self, module_id: ModuleId, path: &Path) -> Option<ModuleId> {
+        match path.kind {
+            PathKind::Plain | PathKind::Self_ => Some(module_id),
+            PathKind::Super(level) => {
+                let mut curr = module_id;
+                for _ in 0..level {
+                    curr = match curr.parent(&self.module_tree) {
+                        Some(it) => it,
+                        None => {
+                            // TODO: error
+                            log::debug!("super path in root module");
+                            return None;
+                        }
+                    };
+                }
+                Some(curr)
+            }
+            PathKind::Crate => Some(module_id.crate_root(&self.module_tree)),
+        }
+    }
+
+    /// Glob imports, like `use super::*;` in test modules, add the names of
+    /// the imported module which are not declared or imported explicitly. As
+    /// the imported module may get more names when other imports resolve,
+    /// glob imports are never done, unless the path is broken.
+    fn resolve_glob_import(&mut self, module_id: ModuleId, path: &Path) -> Cancelable<bool> {
+        let mut curr = match self.path_start(module_id, path) {
+            Some(it) => it,
+            None => return Ok(true),
+        };
+        for (i, name) in path.segments.iter().enumerate() {
+            let def_id = match self.result.per_module[&curr].items.get(name) {
+                Some(res) => match res.def_id.take_types() {
+                    Some(it) => it,
+                    None => return Ok(false),
+                },
+                None => return Ok(false),
+            };
+            match def_id.loc(self.db) {
+                DefLoc {
+                    kind: DefKind::Module,
+                    module_id: target_module_id,
+                    source_root_id,
+                    ..
+                } => {
+                    if source_root_id == self.source_root {
+                        curr = target_module_id;
+                        continue;
+                    }
+                    let module = crate::code_model_api::Module::new(def_id);
+                    let rest = Path {
+                        segments: path.segments[i + 1..].iter().cloned().collect(),
+                        kind: PathKind::Crate,
+                    };
+                    let target = if rest.segments.is_empty() {
+                        Some(module)
+                    } else {
+                        module
+                            .resolve_path(self.db, &rest)?
+                            .take_types()
+                            .map(crate::code_model_api::Module::new)
+                    };
+                    let target = match target {
+                        Some(it) => it,
+                        None => return Ok(true),
+                    };
+                    let entries = target
+                        .scope(self.db)?
+                        .entries()
+                        .map(|(name, res)| (name.clone(), res.def_id))
+                        .collect::<Vec<_>>();
+                    self.add_glob_entries(module_id, entries);
+                    // Other source roots are resolved already.
+                    return Ok(true);
+                }
+                // Enum variants are not supported yet.
+                _ => return Ok(true),
+            }
+        }
+        let entries = self.result.per_module[&curr]
+            .items
+            .iter()
+            .map(|(name, res)| (name.clone(), res.def_id))
+            .collect::<Vec<_>>();
+        self.add_glob_entries(module_id, entries);
+        Ok(false)
+    }
+
+    fn add_glob_entries(&mut self, module_id: ModuleId, entries: Vec<(Name, PerNs<DefId>)>) {
+        let mut changed = false;
+        self.update(module_id, |items| {
+            for (name, def_id) in entries {
+                if def_id.is_none() || items.items.contains_key(&name) {
+                    continue;
+                }
+                items.items.insert(name, Resolution { def_id, import: None });
+                changed = true;
+            }
+        });
+        self.glob_imports_changed |= changed;
+    }
+
     fn update(&mut self, module_id: ModuleId, f: impl FnOnce(&mut ModuleScope)) {
         let module_items = self.result.per_module.get_mut(&module_id).unwrap();
         f(module_items)
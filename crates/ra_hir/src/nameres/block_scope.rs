@@ -0,0 +1,251 @@
+//! Items and imports declared in a block, like `struct Foo;` or `use
+//! std::fmt;` in a function body, are visible in the whole block, and only
+//! there. They shadow the items of the module and of the blocks around.
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_syntax::{
+    AstNode, SyntaxNode,
+    ast::{self, ModuleItemOwner},
+    SyntaxKind::MODULE,
+};
+use ra_db::{Cancelable, LocalSyntaxPtr};
+
+use crate::{
+    DefId, DefLoc, DefKind, Def, HirDatabase, HirFileId, Module, Name, Path, PathKind, Function,
+    expr::ExprId,
+};
+
+use super::{ImportKind, InputModuleItems, PerNs, Resolution};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BlockScope {
+    items: FxHashMap<Name, Resolution>,
+}
+
+impl BlockScope {
+    pub fn entries<'a>(&'a self) -> impl Iterator<Item = (&'a Name, &'a Resolution)> + 'a {
+        self.items.iter()
+    }
+
+    pub fn get(&self, name: &Name) -> Option<&Resolution> {
+        self.items.get(name)
+    }
+
+    /// The scope of `block`, or `None` if the block declares nothing. The
+    /// imports are resolved in the `outer` scopes too, innermost first.
+    fn new(
+        db: &impl HirDatabase,
+        module: &Module,
+        file_id: HirFileId,
+        block: &ast::Block,
+        outer: &[Arc<BlockScope>],
+    ) -> Cancelable<Option<BlockScope>> {
+        let file_items = db.file_items(file_id);
+        let mut input = InputModuleItems::default();
+        for item in block.items() {
+            input.add_item(file_id, &file_items, item);
+        }
+        if input.items.is_empty() && input.imports.is_empty() {
+            return Ok(None);
+        }
+        let loc = module.def_id.loc(db);
+        let module_tree = db.module_tree(loc.source_root_id)?;
+        let mut res = BlockScope::default();
+        for item in input.items.iter() {
+            let def_id = if item.kind == MODULE {
+                let child = match loc.module_id.child_by_source(&module_tree, item.id) {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let def_loc = DefLoc {
+                    kind: DefKind::Module,
+                    source_root_id: loc.source_root_id,
+                    module_id: child,
+                    source_item_id: child.source(&module_tree),
+                };
+                PerNs::types(def_loc.id(db))
+            } else {
+                item.def_id(db, loc.source_root_id, loc.module_id)
+            };
+            res.insert(item.name.clone(), def_id);
+        }
+        for import in input.imports.iter() {
+            let scopes = Some(&res).into_iter().chain(outer.iter().map(|it| &**it));
+            let def_id = resolve_path(db, module, scopes, &import.path)?;
+            match import.kind {
+                ImportKind::Named(_) => {
+                    if let Some(name) = import.path.segments.last() {
+                        res.insert(name.clone(), def_id);
+                    }
+                }
+                ImportKind::Glob => {
+                    let def_id = match def_id.take_types() {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    match def_id.resolve(db)? {
+                        Def::Module(module) => {
+                            for (name, resolution) in module.scope(db)?.entries() {
+                                res.insert_glob(name, resolution.def_id);
+                            }
+                        }
+                        Def::Enum(e) => {
+                            for (name, variant) in e.variants(db)? {
+                                res.insert_glob(&name, PerNs::both(variant.def_id(), def_id));
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        Ok(Some(res))
+    }
+
+    fn insert(&mut self, name: Name, def_id: PerNs<DefId>) {
+        let resolution = Resolution {
+            def_id,
+            import: None,
+        };
+        self.items.insert(name, resolution);
+    }
+
+    /// Glob imports don't override the names declared in the block.
+    fn insert_glob(&mut self, name: &Name, def_id: PerNs<DefId>) {
+        if !self.items.contains_key(name) {
+            self.insert(name.clone(), def_id);
+        }
+    }
+}
+
+/// The scopes of the blocks of a function body, and of the blocks around the
+/// function, for functions declared in a block.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BodyBlockScopes {
+    blocks: FxHashMap<ExprId, Arc<BlockScope>>,
+    outer: Vec<Arc<BlockScope>>,
+}
+
+impl BodyBlockScopes {
+    pub(crate) fn body_block_scopes_query(
+        db: &impl HirDatabase,
+        def_id: DefId,
+    ) -> Cancelable<Arc<BodyBlockScopes>> {
+        let function = Function::new(def_id);
+        let module = function.module(db)?;
+        let (file_id, fn_def) = function.source(db)?;
+        let syntax_mapping = function.body_syntax_mapping(db)?;
+        let mut res = BodyBlockScopes::default();
+        if let Some(parent) = fn_def.syntax().parent() {
+            res.outer = enclosing_scopes(db, &module, file_id, parent)?;
+        }
+        for block in fn_def.syntax().descendants().filter_map(ast::Block::cast) {
+            // Blocks of the items in the body belong to those items.
+            let expr = match syntax_mapping.syntax_expr(LocalSyntaxPtr::new(block.syntax())) {
+                Some(it) => it,
+                None => continue,
+            };
+            let outer = match block.syntax().parent() {
+                Some(parent) => enclosing_scopes(db, &module, file_id, parent)?,
+                None => Vec::new(),
+            };
+            if let Some(scope) = BlockScope::new(db, &module, file_id, block, &outer)? {
+                res.blocks.insert(expr, Arc::new(scope));
+            }
+        }
+        Ok(Arc::new(res))
+    }
+
+    /// The scope of the block expression `expr`, if it declares something.
+    pub(crate) fn block(&self, expr: ExprId) -> Option<&BlockScope> {
+        self.blocks.get(&expr).map(|it| &**it)
+    }
+
+    /// The scopes of the blocks around the function, innermost first.
+    pub(crate) fn outer(&self) -> impl Iterator<Item = &BlockScope> {
+        self.outer.iter().map(|it| &**it)
+    }
+
+    /// The scopes of all the blocks of the body and around the function, in
+    /// no particular order.
+    pub(crate) fn scopes(&self) -> impl Iterator<Item = &BlockScope> {
+        self.blocks.values().chain(self.outer.iter()).map(|it| &**it)
+    }
+}
+
+/// The scopes of the blocks around `node`, up to its module, innermost first.
+/// A module is a barrier: the items of the blocks around it are not visible
+/// inside of it.
+pub(crate) fn enclosing_scopes(
+    db: &impl HirDatabase,
+    module: &Module,
+    file_id: HirFileId,
+    node: &SyntaxNode,
+) -> Cancelable<Vec<Arc<BlockScope>>> {
+    let blocks = node
+        .ancestors()
+        .take_while(|it| it.kind() != MODULE)
+        .filter_map(ast::Block::cast)
+        .collect::<Vec<_>>();
+    // The outer blocks come first, as the imports of a block may refer to
+    // their items.
+    let mut res = Vec::new();
+    for block in blocks.into_iter().rev() {
+        if let Some(scope) = BlockScope::new(db, module, file_id, block, &res)? {
+            res.insert(0, Arc::new(scope));
+        }
+    }
+    Ok(res)
+}
+
+/// Resolves `path` in the block `scopes`, innermost first, and then in the
+/// `module`. Only the first segment of the path can come from a block.
+pub(crate) fn resolve_path<'a>(
+    db: &impl HirDatabase,
+    module: &Module,
+    mut scopes: impl Iterator<Item = &'a BlockScope>,
+    path: &Path,
+) -> Cancelable<PerNs<DefId>> {
+    if path.kind == PathKind::Plain {
+        if let Some(first) = path.segments.first() {
+            if let Some(resolution) = scopes.find_map(|scope| scope.get(first)) {
+                return resolve_rest(db, resolution.def_id, &path.segments[1..]);
+            }
+        }
+    }
+    module.resolve_path(db, path)
+}
+
+/// Resolves the segments of a path which follow the one found in a block.
+fn resolve_rest(
+    db: &impl HirDatabase,
+    def_id: PerNs<DefId>,
+    rest: &[Name],
+) -> Cancelable<PerNs<DefId>> {
+    if rest.is_empty() {
+        return Ok(def_id);
+    }
+    let def_id = match def_id.take_types() {
+        Some(it) => it,
+        None => return Ok(PerNs::none()),
+    };
+    let res = match def_id.resolve(db)? {
+        Def::Module(module) => {
+            let path = Path {
+                kind: PathKind::Self_,
+                segments: rest.to_vec(),
+            };
+            module.resolve_path(db, &path)?
+        }
+        Def::Enum(e) if rest.len() == 1 => {
+            let variant = e.variants(db)?.into_iter().find(|(name, _)| *name == rest[0]);
+            match variant {
+                Some((_, variant)) => PerNs::both(variant.def_id(), def_id),
+                None => PerNs::none(),
+            }
+        }
+        _ => PerNs::none(),
+    };
+    Ok(res)
+}
@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
 use salsa::Database;
-use ra_db::{FilesDatabase, CrateGraph, SourceRootId};
+use ra_db::{FilesDatabase, CrateGraph, SourceRootId, Edition};
+use ra_syntax::{AstNode, TextRange};
 use relative_path::RelativePath;
 use test_utils::assert_eq_text;
 
 use crate::{
-    ItemMap, Resolution,
+    ItemMap, Resolution, Path, PathKind, Name, Problem,
     db::HirDatabase,
     mock::MockDatabase,
     module_tree::ModuleId,
@@ -78,6 +79,175 @@ fn item_map_smoke_test() {
     );
 }
 
+#[test]
+fn item_map_using_chained_super() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        pub struct Baz;
+
+        //- /foo/mod.rs
+        pub mod bar;
+
+        //- /foo/bar.rs
+        use super::super::Baz;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+        ",
+    );
+}
+
+#[test]
+fn glob_imports_of_the_parent_module() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod foo;
+        use self::foo::Baz;
+        pub struct Bar;
+        fn helper() {}
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+            fn helper() {}
+            <|>
+        }
+
+        //- /foo.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Baz: t v
+            foo: t
+            helper: v
+            tests: t
+        ",
+    );
+}
+
+#[test]
+fn glob_imports_see_names_imported_later() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        mod a {
+            pub use crate::b::*;
+        }
+        mod b {
+            pub use crate::c::Baz;
+        }
+        mod c {
+            pub struct Baz;
+        }
+        use self::a::*;
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            a: t
+            b: t
+            c: t
+        ",
+    );
+}
+
+#[test]
+fn module_resolution_works_for_path_attr() {
+    let (item_map, module_id) = item_map(
+        r#"
+        //- /lib.rs
+        #[path = "bar/baz/foo.rs"]
+        mod foo;
+
+        use crate::foo::Baz;
+        <|>
+
+        //- /bar/baz/foo.rs
+        pub struct Baz;
+    "#,
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_resolution_works_for_path_attr_in_inline_module() {
+    let (item_map, module_id) = item_map(
+        r#"
+        //- /lib.rs
+        mod foo {
+            #[path = "baz.rs"]
+            pub mod bar;
+        }
+
+        use crate::foo::bar::Baz;
+        <|>
+
+        //- /foo/baz.rs
+        pub struct Baz;
+    "#,
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_included_via_path_attr_owns_its_directory() {
+    let (item_map, module_id) = item_map(
+        r#"
+        //- /lib.rs
+        #[path = "sys/unix.rs"]
+        mod sys;
+
+        use crate::sys::fd::Fd;
+        <|>
+
+        //- /sys/unix.rs
+        pub mod fd;
+
+        //- /sys/fd.rs
+        pub struct Fd;
+    "#,
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Fd: t v
+            sys: t
+        ",
+    );
+}
+
 #[test]
 fn use_trees() {
     let (item_map, module_id) = item_map(
@@ -136,6 +306,38 @@ fn re_exports() {
     );
 }
 
+#[test]
+fn re_exports_record_their_import() {
+    let (db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        mod foo;
+        <|>
+
+        //- /foo.rs
+        pub use self::bar::Baz;
+        mod bar {
+            pub struct Baz;
+        }
+    ",
+    );
+    let module = crate::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap();
+    let path = Path {
+        kind: PathKind::Plain,
+        segments: vec![Name::new("foo".into()), Name::new("Baz".into())],
+    };
+    let (def_id, import) = module.resolve_path_with_import(&db, &path).unwrap();
+    assert!(def_id.types.is_some());
+    let import = import.unwrap();
+    assert_eq!(
+        import.use_item(&db).syntax().text().to_string(),
+        "pub use self::bar::Baz;"
+    );
+    assert_eq!(import.range(&db), TextRange::from_to(19.into(), 22.into()));
+}
+
 #[test]
 fn name_res_works_for_broken_modules() {
     covers!(name_res_works_for_broken_modules);
@@ -194,6 +396,79 @@ fn item_map_contains_items_from_expansions() {
     );
 }
 
+#[test]
+fn item_map_does_not_contain_items_of_function_bodies() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        struct Foo;
+        mod m { pub struct Baz; }
+        fn main() {
+            use self::m::Baz;
+            struct Foo { x: u32 }
+            enum Bar {}
+            mod tests {}
+        }
+        <|>
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Foo: t v
+            m: t
+            main: v
+        ",
+    );
+}
+
+#[test]
+fn item_map_of_module_inside_a_function() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        fn main() {
+            mod tests {
+                use super::Foo;
+                struct Bar;
+                <|>
+            }
+        }
+        struct Foo;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Foo: t v
+        ",
+    );
+}
+
+#[test]
+fn module_declarations_inside_functions_are_not_resolved_to_files() {
+    let (db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        fn main() {
+            mod tests;
+        }
+        <|>
+        //- /tests.rs
+        struct Foo;
+    ",
+    );
+    let module = crate::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap();
+    assert!(module.problems(&db).unwrap().is_empty());
+    let children = module.children(&db).unwrap().collect::<Vec<_>>();
+    assert!(children.is_empty());
+}
+
 #[test]
 fn item_map_using_self() {
     let (item_map, module_id) = item_map(
@@ -233,8 +508,47 @@ fn item_map_across_crates() {
     let lib_id = sr.files[RelativePath::new("/lib.rs")];
 
     let mut crate_graph = CrateGraph::default();
-    let main_crate = crate_graph.add_crate_root(main_id);
-    let lib_crate = crate_graph.add_crate_root(lib_id);
+    let main_crate = crate_graph.add_crate_root(main_id, Edition::Edition2018);
+    let lib_crate = crate_graph.add_crate_root(lib_id, Edition::Edition2018);
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let source_root = db.file_source_root(main_id);
+    let module = crate::source_binder::module_from_file_id(&db, main_id)
+        .unwrap()
+        .unwrap();
+    let module_id = module.def_id.loc(&db).module_id;
+    let item_map = db.item_map(source_root).unwrap();
+
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn extern_crate_alias() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        extern crate test_crate as other;
+        use other::Baz;
+
+        //- /lib.rs
+        pub struct Baz;
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id, Edition::Edition2018);
+    let lib_crate = crate_graph.add_crate_root(lib_id, Edition::Edition2018);
     crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
 
     db.set_crate_graph(crate_graph);
@@ -251,6 +565,56 @@ fn item_map_across_crates() {
         module_id,
         "
             Baz: t v
+            other: t
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn extern_crate_alias_in_submodules() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        extern crate test_crate as other;
+        mod foo;
+
+        //- /foo.rs
+        extern crate test_crate as another;
+        use other::Baz;
+        use another::Qux;
+
+        //- /lib.rs
+        pub struct Baz;
+        pub struct Qux;
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let foo_id = sr.files[RelativePath::new("/foo.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id, Edition::Edition2018);
+    let lib_crate = crate_graph.add_crate_root(lib_id, Edition::Edition2018);
+    crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
+
+    db.set_crate_graph(crate_graph);
+
+    let source_root = db.file_source_root(main_id);
+    let module = crate::source_binder::module_from_file_id(&db, foo_id)
+        .unwrap()
+        .unwrap();
+    let module_id = module.def_id.loc(&db).module_id;
+    let item_map = db.item_map(source_root).unwrap();
+
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            Qux: t v
+            another: t
+            other: t
             test_crate: t
         ",
     );
@@ -286,8 +650,8 @@ fn import_across_source_roots() {
     eprintln!("lib = {:?}, main = {:?}", lib_id, main_id);
 
     let mut crate_graph = CrateGraph::default();
-    let main_crate = crate_graph.add_crate_root(main_id);
-    let lib_crate = crate_graph.add_crate_root(lib_id);
+    let main_crate = crate_graph.add_crate_root(main_id, Edition::Edition2018);
+    let lib_crate = crate_graph.add_crate_root(lib_id, Edition::Edition2018);
     crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
 
     db.set_crate_graph(crate_graph);
@@ -328,8 +692,8 @@ fn reexport_across_crates() {
     let lib_id = sr.files[RelativePath::new("/lib.rs")];
 
     let mut crate_graph = CrateGraph::default();
-    let main_crate = crate_graph.add_crate_root(main_id);
-    let lib_crate = crate_graph.add_crate_root(lib_id);
+    let main_crate = crate_graph.add_crate_root(main_id, Edition::Edition2018);
+    let lib_crate = crate_graph.add_crate_root(lib_id, Edition::Edition2018);
     crate_graph.add_dep(main_crate, "test_crate".into(), lib_crate);
 
     db.set_crate_graph(crate_graph);
@@ -470,3 +834,106 @@ fn typing_inside_a_function_inside_a_macro_should_not_invalidate_item_map() {
         ",
     );
 }
+
+#[test]
+fn adding_an_item_recomputes_declarations_of_its_module_only() {
+    let (mut db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        mod foo;<|>
+
+        use crate::foo::bar::Baz;
+        //- /foo/mod.rs
+        pub mod bar;
+
+        //- /foo/bar.rs
+        pub struct Baz;
+        ",
+    );
+    let source_root = db.file_source_root(pos.file_id);
+    db.item_map(source_root).unwrap();
+    db.query_mut(ra_db::FileTextQuery).set(
+        pos.file_id,
+        Arc::new("mod foo; use crate::foo::bar::Baz; struct Quux;".to_string()),
+    );
+
+    let events = db.log_executed(|| {
+        db.item_map(source_root).unwrap();
+    });
+    let recomputed = events
+        .iter()
+        .filter(|it| it.contains("module_declarations"))
+        .count();
+    assert_eq!(recomputed, 1, "{:#?}", events);
+}
+
+#[test]
+fn typing_inside_a_function_should_not_recompute_module_tree() {
+    let (mut db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        mod foo {
+            mod bar;
+        }
+        fn quux() -> i32 { 1 + 1 }<|>
+        //- /foo/bar.rs
+        pub struct Baz;
+        ",
+    );
+    let source_root = db.file_source_root(pos.file_id);
+    db.module_tree(source_root).unwrap();
+    db.query_mut(ra_db::FileTextQuery).set(
+        pos.file_id,
+        Arc::new("mod foo { mod bar; } fn quux() -> i32 { 92 }".to_string()),
+    );
+
+    let events = db.log_executed(|| {
+        db.module_tree(source_root).unwrap();
+    });
+    assert!(format!("{:?}", events).contains("submodules"), "{:#?}", events);
+    assert!(!format!("{:?}", events).contains("module_tree"), "{:#?}", events);
+}
+
+#[test]
+fn duplicate_definitions_are_reported_on_the_later_item() {
+    let (db, pos) = MockDatabase::with_position(
+        "
+        //- /lib.rs
+        fn foo() {}
+        fn foo() {}
+        struct Bar;
+        mod Bar {}
+        struct Baz;
+        fn Baz() {}
+        enum E {}
+        fn E() {}
+        #[cfg(test)]
+        fn quux() {}
+        #[cfg(not(test))]
+        fn quux() {}
+        <|>
+    ",
+    );
+    let module = crate::source_binder::module_from_position(&db, pos)
+        .unwrap()
+        .unwrap();
+    let text = db.file_text(pos.file_id);
+    let problems = module
+        .problems(&db)
+        .unwrap()
+        .into_iter()
+        .map(|(name, problem)| match problem {
+            Problem::DuplicateDefinition { first } => {
+                assert!(first.start() < name.range().start());
+                let first = u32::from(first.start()) as usize..u32::from(first.end()) as usize;
+                (name.text().to_string(), text[first].to_string())
+            }
+            _ => panic!("unexpected problem: {:?}", problem),
+        })
+        .collect::<Vec<_>>();
+    let expected = [("foo", "foo"), ("Bar", "Bar"), ("Baz", "Baz")]
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(problems, expected);
+}
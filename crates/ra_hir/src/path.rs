@@ -1,6 +1,6 @@
 use ra_syntax::{ast, AstNode, TextRange};
 
-use crate::{Name, AsName};
+use crate::{Name, AsName, KnownName};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path {
@@ -12,7 +12,9 @@ pub struct Path {
 pub enum PathKind {
     Plain,
     Self_,
-    Super,
+    /// `super::`, repeated the given number of times, like in
+    /// `super::super::foo`.
+    Super(u8),
     Crate,
 }
 
@@ -41,7 +43,16 @@ impl Path {
                     break;
                 }
                 ast::PathSegmentKind::SuperKw => {
-                    kind = PathKind::Super;
+                    let mut level = 1;
+                    while let Some(q) = qualifier(path) {
+                        match q.segment()?.kind()? {
+                            ast::PathSegmentKind::SuperKw => level += 1,
+                            // `super` can only be preceded by more `super`s
+                            _ => return None,
+                        }
+                        path = q;
+                    }
+                    kind = PathKind::Super(level);
                     break;
                 }
             }
@@ -80,6 +91,12 @@ impl Path {
         self.kind == PathKind::Self_ && self.segments.len() == 0
     }
 
+    /// `true` if this path is just a standalone `Self`
+    pub fn is_self_type(&self) -> bool {
+        self.as_ident()
+            .map_or(false, |it| it.as_known_name() == Some(KnownName::SelfType))
+    }
+
     /// If this path is a single identifier, like `foo`, return its name.
     pub fn as_ident(&self) -> Option<&Name> {
         if self.kind != PathKind::Plain || self.segments.len() > 1 {
@@ -181,11 +198,17 @@ fn convert_path(prefix: Option<Path>, path: &ast::Path) -> Option<Path> {
             }
         }
         ast::PathSegmentKind::SuperKw => {
-            if prefix.is_some() {
-                return None;
-            }
+            // `super::super` goes one module further up for each `super`.
+            let level = match prefix {
+                None => 1,
+                Some(Path {
+                    kind: PathKind::Super(level),
+                    ref segments,
+                }) if segments.is_empty() => level + 1,
+                Some(_) => return None,
+            };
             Path {
-                kind: PathKind::Super,
+                kind: PathKind::Super(level),
                 segments: Vec::new(),
             }
         }
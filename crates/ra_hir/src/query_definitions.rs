@@ -102,6 +102,7 @@ pub(super) fn item_map(
     let input = module_tree
         .modules()
         .map(|id| {
+            db.check_canceled()?;
             let items = db.input_module_items(source_root, id)?;
             Ok((id, items))
         })
@@ -5,16 +5,21 @@
 ///
 /// So, this modules should not be used during hir construction, it exists
 /// purely for "IDE needs".
-use ra_db::{FileId, FilePosition, Cancelable};
+use std::sync::Arc;
+
+use ra_db::{FileId, FilePosition, Cancelable, LocalSyntaxPtr};
 use ra_syntax::{
-    SmolStr, TextRange, SyntaxNode,
-    ast::{self, AstNode, NameOwner},
+    SmolStr, TextRange, TextUnit, SyntaxNode,
+    ast::{self, AstNode, NameOwner, ModuleItemOwner},
     algo::find_node_at_offset,
+    SyntaxKind::*,
 };
 
 use crate::{
-    HirDatabase, Function, SourceItemId,
-    DefKind, DefLoc, AsName, Module,
+    HirDatabase, Function, SourceItemId, Def, DefId, Struct, StructField, Ty,
+    ScopeEntryWithSyntax, Name, Path,
+    DefKind, DefLoc, AsName, Module, ImplBlock, NamedImport, BlockScope, PerNs, HirFileId,
+    nameres::{enclosing_scopes, resolve_path_in_blocks},
 };
 
 /// Locates the module by `FileId`. Picks topmost module in the file.
@@ -32,7 +37,8 @@ pub fn module_from_declaration(
     file_id: FileId,
     decl: &ast::Module,
 ) -> Cancelable<Option<Module>> {
-    let parent_module = module_from_file_id(db, file_id)?;
+    // The declaration can be inside of an inline module.
+    let parent_module = module_from_child_node(db, file_id, decl.syntax())?;
     let child_name = decl.name();
     match (parent_module, child_name) {
         (Some(parent_module), Some(child_name)) => {
@@ -57,20 +63,51 @@ pub fn module_from_position(
     }
 }
 
+/// Locates all modules by position in the source code. Unlike
+/// `module_from_position`, this accounts for files which are included as
+/// modules from several places.
+pub fn modules_from_position(
+    db: &impl HirDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<Module>> {
+    let file = db.source_file(position.file_id);
+    let source = match find_node_at_offset::<ast::Module>(file.syntax(), position.offset) {
+        Some(m) if !m.has_semi() => inline_module_source(db, position.file_id, m),
+        _ => SourceItemId {
+            file_id: position.file_id.into(),
+            item_id: None,
+        },
+    };
+    let source_root_id = db.file_source_root(position.file_id);
+    let module_tree = db.module_tree(source_root_id)?;
+    module_tree
+        .find_modules_by_source(source)
+        .map(|module_id| Module::from_module_id(db, source_root_id, module_id))
+        .collect()
+}
+
 fn module_from_inline(
     db: &impl HirDatabase,
     file_id: FileId,
     module: &ast::Module,
 ) -> Cancelable<Option<Module>> {
+    let source = inline_module_source(db, file_id, module);
+    module_from_source(db, source)
+}
+
+fn inline_module_source(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    module: &ast::Module,
+) -> SourceItemId {
     assert!(!module.has_semi());
     let file_id = file_id.into();
     let file_items = db.file_items(file_id);
     let item_id = file_items.id_of(file_id, module.syntax());
-    let source = SourceItemId {
+    SourceItemId {
         file_id,
         item_id: Some(item_id),
-    };
-    module_from_source(db, source)
+    }
 }
 
 /// Locates the module by child syntax element within the module
@@ -124,21 +161,30 @@ pub fn function_from_module(
     module: &Module,
     fn_def: &ast::FnDef,
 ) -> Function {
+    Function::new(def_id_from_module(db, module, DefKind::Function, fn_def.syntax()))
+}
+
+fn def_id_from_module(
+    db: &impl HirDatabase,
+    module: &Module,
+    kind: DefKind,
+    item: &SyntaxNode,
+) -> DefId {
     let loc = module.def_id.loc(db);
     let file_id = loc.source_item_id.file_id;
     let file_items = db.file_items(file_id);
-    let item_id = file_items.id_of(file_id, fn_def.syntax());
+    let item_id = file_items.id_of(file_id, item);
     let source_item_id = SourceItemId {
         file_id,
         item_id: Some(item_id),
     };
     let def_loc = DefLoc {
-        kind: DefKind::Function,
+        kind,
         source_root_id: loc.source_root_id,
         module_id: loc.module_id,
         source_item_id,
     };
-    Function::new(def_loc.id(db))
+    def_loc.id(db)
 }
 
 pub fn function_from_child_node(
@@ -150,39 +196,391 @@ pub fn function_from_child_node(
     function_from_source(db, file_id, fn_def)
 }
 
+/// Locates the impl block which contains the given syntax node.
+pub fn impl_block_from_child_node(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<ImplBlock>> {
+    let impl_block = ctry!(node.ancestors().find_map(ast::ImplBlock::cast));
+    let module = ctry!(module_from_child_node(db, file_id, impl_block.syntax())?);
+    let file_id = file_id.into();
+    let file_items = db.file_items(file_id);
+    let source_item_id = SourceItemId {
+        file_id,
+        item_id: Some(file_items.id_of(file_id, impl_block.syntax())),
+    };
+    let res = module
+        .impl_blocks(db)?
+        .into_iter()
+        .find(|it| it.impl_data().source_item_id == source_item_id);
+    Ok(res)
+}
+
+/// What a name-like node refers to, see `def_for_node`.
+#[derive(Debug)]
+pub enum NameDefinition {
+    /// A local binding or a parameter of the function.
+    Local(Function, ScopeEntryWithSyntax),
+    /// A named field of a struct.
+    Field(StructField),
+    /// A method, resolved via the type of the receiver.
+    Method(Function),
+    /// An item, like a module, a struct or a function.
+    Item(Def),
+    /// A lifetime parameter, like `'a` in `fn foo<'a>()`. Lifetimes are not
+    /// lowered to hir, so this points to the syntax of the parameter, which is
+    /// always in the same file as its usages.
+    Lifetime(LocalSyntaxPtr),
+}
+
+/// Classifies a name-like node and resolves it to the definition. The node is
+/// either a `NameRef`, the `Name` of a definition, a `Lifetime` or the field
+/// name in a struct pattern, like `foo` in `S { foo: _ }`.
+///
+/// This is the single place which knows how names are resolved, so that
+/// goto definition, hover and find references agree with each other.
+pub fn def_for_node(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<NameDefinition>> {
+    if let Some(name_ref) = ast::NameRef::cast(node) {
+        return def_for_name_ref(db, file_id, name_ref);
+    }
+    if let Some(name) = ast::Name::cast(node) {
+        return def_for_name(db, file_id, name);
+    }
+    if let Some(lifetime) = ast::Lifetime::cast(node) {
+        let param = ctry!(lifetime_param(lifetime));
+        return Ok(Some(NameDefinition::Lifetime(LocalSyntaxPtr::new(
+            param.syntax(),
+        ))));
+    }
+    let parent = ctry!(node.parent());
+    if node.kind() == IDENT && parent.kind() == FIELD_PAT_LIST {
+        let struct_pat = ctry!(parent.parent().and_then(ast::StructPat::cast));
+        let name = ctry!(node.leaf_text());
+        let field = struct_field(db, file_id, ctry!(struct_pat.path()), name)?;
+        return Ok(field.map(NameDefinition::Field));
+    }
+    Ok(None)
+}
+
+/// Resolves `name` as a value used at `offset`, where there is no `NameRef`
+/// for it: the named interpolations in the format strings of `println!` and
+/// friends, like `{name}`, refer to the locals and constants in scope. `node`
+/// is the macro call.
+pub fn resolve_value_at_offset(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+    offset: TextUnit,
+    name: &str,
+) -> Cancelable<Option<NameDefinition>> {
+    let name = Name::new(name.into());
+    if let Some(function) = function_from_child_node(db, file_id, node)? {
+        let scopes = function.scopes(db)?;
+        if let Some(entry) = scopes.resolve_local_name_at_offset(offset, &name) {
+            return Ok(Some(NameDefinition::Local(function, entry)));
+        }
+    }
+    let module = ctry!(module_from_child_node(db, file_id, node)?);
+    let path = Path::from(name);
+    let def_id = ctry!(resolve_path_at(db, file_id, &module, node, &path)?.take_values());
+    match def_id.resolve(db)? {
+        Def::Item => Ok(None),
+        def => Ok(Some(NameDefinition::Item(def))),
+    }
+}
+
+fn def_for_name_ref(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    name_ref: &ast::NameRef,
+) -> Cancelable<Option<NameDefinition>> {
+    let parent = ctry!(name_ref.syntax().parent());
+    if let Some(field_expr) = ast::FieldExpr::cast(parent) {
+        let struct_ = ctry!(receiver_struct(db, file_id, ctry!(field_expr.expr()))?);
+        let field = find_field(db, &struct_, &name_ref.as_name())?;
+        return Ok(field.map(NameDefinition::Field));
+    }
+    if ast::MethodCallExpr::cast(parent).is_some() {
+        let function = ctry!(function_from_child_node(db, file_id, parent)?);
+        let infer_result = function.infer(db)?;
+        let syntax_mapping = function.body_syntax_mapping(db)?;
+        let expr = ctry!(ast::Expr::cast(parent).and_then(|it| syntax_mapping.node_expr(it)));
+        let def_id = ctry!(infer_result.method_resolution(expr));
+        return match def_id.resolve(db)? {
+            Def::Function(it) => Ok(Some(NameDefinition::Method(it))),
+            _ => Ok(None),
+        };
+    }
+    // In the shorthand `S { foo }`, the name is both the field and the local
+    // variable. The local can be found via the scopes, so this is the field.
+    if let Some(named_field) = ast::NamedField::cast(parent) {
+        let struct_lit = ctry!(named_field
+            .syntax()
+            .ancestors()
+            .find_map(ast::StructLit::cast));
+        let field = struct_field(db, file_id, ctry!(struct_lit.path()), name_ref.text())?;
+        return Ok(field.map(NameDefinition::Field));
+    }
+    if let Some(function) = function_from_child_node(db, file_id, name_ref.syntax())? {
+        let scopes = function.scopes(db)?;
+        if let Some(entry) = scopes.resolve_local_name(name_ref) {
+            return Ok(Some(NameDefinition::Local(function, entry)));
+        }
+    }
+    let module = ctry!(module_from_child_node(db, file_id, name_ref.syntax())?);
+    let path = ctry!(name_ref
+        .syntax()
+        .ancestors()
+        .find_map(ast::Path::cast)
+        .and_then(Path::from_ast));
+    let resolved = resolve_path_at(db, file_id, &module, name_ref.syntax(), &path)?;
+    let def_id = ctry!(resolved.take_types().or(resolved.take_values()));
+    match def_id.resolve(db)? {
+        Def::Item => Ok(None),
+        def => Ok(Some(NameDefinition::Item(def))),
+    }
+}
+
+/// The scopes of the blocks around `node` which declare items, innermost
+/// first. Their items shadow the ones of the module.
+pub fn block_scopes_at(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Vec<Arc<BlockScope>>> {
+    match module_from_child_node(db, file_id, node)? {
+        Some(module) => enclosing_scopes(db, &module, file_id.into(), node),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Resolves `path`, written at `node`, in the blocks around `node` and then
+/// in the `module`.
+fn resolve_path_at(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    module: &Module,
+    node: &SyntaxNode,
+    path: &Path,
+) -> Cancelable<PerNs<DefId>> {
+    let scopes = enclosing_scopes(db, module, file_id.into(), node)?;
+    resolve_path_in_blocks(db, module, scopes.iter().map(|it| &**it), path)
+}
+
+/// Finds the `use` item through which the path of `name_ref` resolves, like
+/// the `pub use self::bar::Bar;` in `foo` for `foo::Bar`. `None` if the last
+/// segment of the path is declared in its module rather than imported.
+pub fn import_for_name_ref(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    name_ref: &ast::NameRef,
+) -> Cancelable<Option<NamedImport>> {
+    let module = ctry!(module_from_child_node(db, file_id, name_ref.syntax())?);
+    let path = ctry!(name_ref
+        .syntax()
+        .ancestors()
+        .find_map(ast::Path::cast)
+        .and_then(Path::from_ast));
+    let (resolved, import) = module.resolve_path_with_import(db, &path)?;
+    if resolved.is_none() {
+        return Ok(None);
+    }
+    Ok(import)
+}
+
+fn def_for_name(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    name: &ast::Name,
+) -> Cancelable<Option<NameDefinition>> {
+    let parent = ctry!(name.syntax().parent());
+    if let Some(bind_pat) = ast::BindPat::cast(parent) {
+        let function = ctry!(function_from_child_node(db, file_id, parent)?);
+        let entry = ctry!(function.scopes(db)?.entry_for_binding(bind_pat));
+        return Ok(Some(NameDefinition::Local(function, entry)));
+    }
+    if let Some(field_def) = ast::NamedFieldDef::cast(parent) {
+        let struct_def = ctry!(field_def.syntax().ancestors().find_map(ast::StructDef::cast));
+        let module = ctry!(module_from_child_node(db, file_id, struct_def.syntax())?);
+        let def_id = def_id_from_module(db, &module, DefKind::Struct, struct_def.syntax());
+        let field = find_field(db, &Struct::new(def_id), &name.as_name())?;
+        return Ok(field.map(NameDefinition::Field));
+    }
+    if let Some(module) = ast::Module::cast(parent) {
+        let module = if module.has_semi() {
+            module_from_declaration(db, file_id, module)?
+        } else {
+            module_from_inline(db, file_id, module)?
+        };
+        return Ok(module.map(|it| NameDefinition::Item(Def::Module(it))));
+    }
+    let kind = match parent.kind() {
+        FN_DEF => DefKind::Function,
+        STRUCT_DEF => DefKind::Struct,
+        ENUM_DEF => DefKind::Enum,
+        TRAIT_DEF => DefKind::Trait,
+        TYPE_DEF => DefKind::Type,
+        CONST_DEF => DefKind::Const,
+        STATIC_DEF => DefKind::Static,
+        _ => return Ok(None),
+    };
+    let module = ctry!(module_from_child_node(db, file_id, parent)?);
+    let def_id = def_id_from_module(db, &module, kind, parent);
+    Ok(Some(NameDefinition::Item(def_id.resolve(db)?)))
+}
+
+/// Finds the field named `name` of the struct at `path`, which is the path of
+/// a struct literal or a struct pattern.
+pub fn struct_field(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    path: &ast::Path,
+    name: &str,
+) -> Cancelable<Option<StructField>> {
+    let hir_path = ctry!(Path::from_ast(path));
+    let struct_ = if hir_path.is_self_type() {
+        let impl_block = ctry!(impl_block_from_child_node(db, file_id, path.syntax())?);
+        ctry!(adt_struct(db, &impl_block.target_ty(db)?)?)
+    } else {
+        let module = ctry!(module_from_child_node(db, file_id, path.syntax())?);
+        let resolved = resolve_path_at(db, file_id, &module, path.syntax(), &hir_path)?;
+        let def_id = ctry!(resolved.take_types());
+        match def_id.resolve(db)? {
+            Def::Struct(it) => it,
+            _ => return Ok(None),
+        }
+    };
+    find_field(db, &struct_, &Name::new(name.into()))
+}
+
+/// The struct of the receiver of `receiver.field`, looking through references.
+fn receiver_struct(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    receiver: &ast::Expr,
+) -> Cancelable<Option<Struct>> {
+    let function = ctry!(function_from_child_node(db, file_id, receiver.syntax())?);
+    let infer_result = function.infer(db)?;
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    let expr = ctry!(syntax_mapping.node_expr(receiver));
+    let mut ty = &infer_result[expr];
+    while let Ty::Ref(inner, _) = ty {
+        ty = &**inner;
+    }
+    adt_struct(db, ty)
+}
+
+fn adt_struct(db: &impl HirDatabase, ty: &Ty) -> Cancelable<Option<Struct>> {
+    let def_id = match ty {
+        Ty::Adt { def_id, .. } => *def_id,
+        _ => return Ok(None),
+    };
+    match def_id.resolve(db)? {
+        Def::Struct(it) => Ok(Some(it)),
+        _ => Ok(None),
+    }
+}
+
+fn find_field(
+    db: &impl HirDatabase,
+    struct_: &Struct,
+    name: &Name,
+) -> Cancelable<Option<StructField>> {
+    let field = struct_
+        .fields(db)?
+        .into_iter()
+        .find(|it| it.name() == name);
+    Ok(field)
+}
+
+/// The declaration of the lifetime in the generic parameters of the enclosing
+/// items. `'static` and elided lifetimes are not declared anywhere.
+fn lifetime_param(lifetime: &ast::Lifetime) -> Option<&ast::LifetimeParam> {
+    let text = lifetime.syntax().leaf_text()?;
+    lifetime
+        .syntax()
+        .ancestors()
+        .filter_map(|node| node.children().find_map(ast::TypeParamList::cast))
+        .flat_map(|it| it.lifetime_params())
+        .find(|param| {
+            param
+                .lifetime()
+                .and_then(|it| it.syntax().leaf_text())
+                .map_or(false, |it| it == text)
+        })
+}
+
 pub fn macro_symbols(
     db: &impl HirDatabase,
     file_id: FileId,
 ) -> Cancelable<Vec<(SmolStr, TextRange)>> {
+    let mut res = Vec::new();
+    for macro_file in macro_files(db, file_id)? {
+        let file = db.hir_source_file(macro_file);
+        for trait_def in file.syntax().descendants().filter_map(ast::TraitDef::cast) {
+            if let Some(name) = trait_def.name() {
+                let dst_range = name.syntax().range();
+                if let Some(src_range) = macro_file.exact_original_range(db, dst_range) {
+                    res.push((name.text().clone(), src_range.range))
+                }
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// The expansions of the macro calls among the items of the root module of
+/// `file_id`.
+pub fn macro_files(db: &impl HirDatabase, file_id: FileId) -> Cancelable<Vec<HirFileId>> {
     let module = match module_from_file_id(db, file_id)? {
         Some(it) => it,
         None => return Ok(Vec::new()),
     };
     let loc = module.def_id.loc(db);
     let items = db.input_module_items(loc.source_root_id, loc.module_id)?;
-    let mut res = Vec::new();
-
-    for macro_call_id in items
+    let mut res = items
         .items
         .iter()
-        .filter_map(|it| it.id.file_id.as_macro_call_id())
-    {
-        if let Some(exp) = db.expand_macro_invocation(macro_call_id) {
-            let loc = macro_call_id.loc(db);
-            let syntax = db.file_item(loc.source_item_id);
-            let macro_call = ast::MacroCall::cast(&syntax).unwrap();
-            let off = macro_call.token_tree().unwrap().syntax().range().start();
-            let file = exp.file();
-            for trait_def in file.syntax().descendants().filter_map(ast::TraitDef::cast) {
-                if let Some(name) = trait_def.name() {
-                    let dst_range = name.syntax().range();
-                    if let Some(src_range) = exp.map_range_back(dst_range) {
-                        res.push((name.text().clone(), src_range + off))
-                    }
-                }
+        .map(|it| it.id.file_id)
+        .filter(|it| it.as_macro_call_id().is_some())
+        .collect::<Vec<_>>();
+    // The items of a macro call come one after another.
+    res.dedup();
+    Ok(res)
+}
+
+/// The functions declared by the macro calls among the items of the root
+/// module of `file_id`.
+pub fn macro_functions(db: &impl HirDatabase, file_id: FileId) -> Cancelable<Vec<Function>> {
+    let module = match module_from_file_id(db, file_id)? {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let loc = module.def_id.loc(db);
+    let mut res = Vec::new();
+    for macro_file in macro_files(db, file_id)? {
+        let file_items = db.file_items(macro_file);
+        for item in db.hir_source_file(macro_file).items() {
+            if item.syntax().kind() != FN_DEF {
+                continue;
             }
+            let def_loc = DefLoc {
+                kind: DefKind::Function,
+                source_root_id: loc.source_root_id,
+                module_id: loc.module_id,
+                source_item_id: SourceItemId {
+                    file_id: macro_file,
+                    item_id: Some(file_items.id_of(macro_file, item.syntax())),
+                },
+            };
+            res.push(Function::new(def_loc.id(db)));
         }
     }
-
     Ok(res)
 }
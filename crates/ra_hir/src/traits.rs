@@ -0,0 +1,185 @@
+//! HIR for trait definitions.
+
+use std::{fmt, sync::Arc};
+
+use ra_db::Cancelable;
+use ra_syntax::ast::{self, NameOwner, FnDefOwner, AstNode};
+use rustc_hash::FxHashSet;
+
+use crate::{
+    DefId, Name, AsName, Function, HirDatabase, DefKind, Path, TypeRef, Trait,
+    adt::get_def_id,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitData {
+    pub(crate) name: Option<Name>,
+    pub(crate) methods: Vec<Function>,
+    /// The unresolved paths of the super-traits.
+    pub(crate) super_traits: Vec<Path>,
+}
+
+impl TraitData {
+    pub(crate) fn trait_data_query(
+        db: &impl HirDatabase,
+        def_id: DefId,
+    ) -> Cancelable<Arc<TraitData>> {
+        let def_loc = def_id.loc(db);
+        assert!(def_loc.kind == DefKind::Trait);
+        let syntax = db.file_item(def_loc.source_item_id);
+        let trait_def =
+            ast::TraitDef::cast(&syntax).expect("trait def should point to TraitDef node");
+        let name = trait_def.name().map(|n| n.as_name());
+        let methods = if let Some(item_list) = trait_def.item_list() {
+            item_list
+                .functions()
+                .map(|fn_def| {
+                    let def_id = get_def_id(db, &def_loc, fn_def.syntax(), DefKind::Function);
+                    Function::new(def_id)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let super_traits = trait_def
+            .super_traits()
+            .filter_map(|it| match TypeRef::from_ast(it) {
+                TypeRef::Path(path) => Some(path),
+                _ => None,
+            })
+            .collect();
+        Ok(Arc::new(TraitData {
+            name,
+            methods,
+            super_traits,
+        }))
+    }
+}
+
+/// A reason why a trait can't be made into a `dyn Trait` object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectSafetyViolation {
+    /// The trait requires `Self: Sized`.
+    SizedSelf,
+    /// The method has no `self` receiver.
+    NoReceiver(Name),
+    /// The method mentions `Self` in a parameter other than the receiver or
+    /// in the return type.
+    SelfInSignature(Name),
+    /// The method has type parameters.
+    GenericMethod(Name),
+    /// The super-trait is not object safe itself.
+    SuperTrait(Name),
+}
+
+impl fmt::Display for ObjectSafetyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectSafetyViolation::SizedSelf => write!(f, "it requires `Self: Sized`"),
+            ObjectSafetyViolation::NoReceiver(name) => {
+                write!(f, "method `{}` has no `self` parameter", name)
+            }
+            ObjectSafetyViolation::SelfInSignature(name) => {
+                write!(f, "method `{}` references the `Self` type", name)
+            }
+            ObjectSafetyViolation::GenericMethod(name) => {
+                write!(f, "method `{}` has generic type parameters", name)
+            }
+            ObjectSafetyViolation::SuperTrait(name) => {
+                write!(f, "super-trait `{}` is not object safe", name)
+            }
+        }
+    }
+}
+
+/// Checks the object safety rules syntactically: only the signatures of the
+/// methods are looked at, and `Self` is not found in generic arguments, like
+/// in `fn foo(&self) -> Option<Self>`, as paths don't record them.
+pub(crate) fn object_safety_violations(
+    db: &impl HirDatabase,
+    trait_: &Trait,
+) -> Cancelable<Vec<ObjectSafetyViolation>> {
+    let mut visited = FxHashSet::default();
+    violations(db, trait_, &mut visited)
+}
+
+fn violations(
+    db: &impl HirDatabase,
+    trait_: &Trait,
+    visited: &mut FxHashSet<DefId>,
+) -> Cancelable<Vec<ObjectSafetyViolation>> {
+    let mut res = Vec::new();
+    if !visited.insert(trait_.def_id) {
+        return Ok(res);
+    }
+    let data = db.trait_data(trait_.def_id)?;
+    if data.super_traits.iter().any(is_sized) {
+        res.push(ObjectSafetyViolation::SizedSelf);
+    }
+    for method in data.methods.iter() {
+        let signature = method.signature(db);
+        let name = signature.name().clone();
+        // Methods with `where Self: Sized` are not callable on trait objects,
+        // so they don't matter.
+        let where_self_sized = signature
+            .where_predicates()
+            .iter()
+            .any(|it| is_self(it.type_ref()) && it.bounds().iter().any(is_sized_type_ref));
+        if where_self_sized {
+            continue;
+        }
+        if !signature.has_self_param() {
+            res.push(ObjectSafetyViolation::NoReceiver(name));
+            continue;
+        }
+        if !signature.type_params().is_empty() {
+            res.push(ObjectSafetyViolation::GenericMethod(name.clone()));
+        }
+        let mentions_self = signature.params()[1..].iter().any(mentions_self)
+            || mentions_self(signature.ret_type());
+        if mentions_self {
+            res.push(ObjectSafetyViolation::SelfInSignature(name));
+        }
+    }
+    for super_trait in trait_.super_traits(db)? {
+        if !violations(db, &super_trait, visited)?.is_empty() {
+            let name = super_trait.name(db)?.unwrap_or_else(Name::missing);
+            res.push(ObjectSafetyViolation::SuperTrait(name));
+        }
+    }
+    Ok(res)
+}
+
+fn is_sized(path: &Path) -> bool {
+    path.segments.last().map_or(false, |it| it.to_string() == "Sized")
+}
+
+fn is_sized_type_ref(type_ref: &TypeRef) -> bool {
+    match type_ref {
+        TypeRef::Path(path) => is_sized(path),
+        _ => false,
+    }
+}
+
+fn is_self(type_ref: &TypeRef) -> bool {
+    match type_ref {
+        TypeRef::Path(path) => path.is_self_type(),
+        _ => false,
+    }
+}
+
+fn mentions_self(type_ref: &TypeRef) -> bool {
+    match type_ref {
+        TypeRef::Path(path) => path.is_self_type(),
+        TypeRef::Tuple(types) | TypeRef::Fn(types) => types.iter().any(mentions_self),
+        TypeRef::RawPtr(inner, _)
+        | TypeRef::Reference(inner, _)
+        | TypeRef::Array(inner)
+        | TypeRef::Slice(inner) => mentions_self(inner),
+        TypeRef::Never
+        | TypeRef::Placeholder
+        | TypeRef::ImplTrait(_)
+        | TypeRef::DynTrait(_)
+        | TypeRef::Error => false,
+    }
+}
@@ -14,7 +14,7 @@
 //! rustc.
 
 mod autoderef;
-mod primitive;
+pub(crate) mod primitive;
 #[cfg(test)]
 mod tests;
 pub(crate) mod method_resolution;
@@ -32,13 +32,15 @@ use join_to_string::join;
 use ra_db::Cancelable;
 
 use crate::{
-    Def, DefId, Module, Function, Struct, Enum, EnumVariant, Path, Name, ImplBlock,
-    FnSignature, FnScopes,
+    Def, DefId, Module, Function, Struct, Enum, EnumVariant, Type, Const, Static, Path, Name,
+    ImplBlock, FnSignature, FnScopes, TyInterner,
     db::HirDatabase,
+    nameres::{BlockScope, BodyBlockScopes, PerNs, resolve_path_in_blocks},
     type_ref::{TypeRef, Mutability},
     name::KnownName,
-    expr::{Body, Expr, ExprId, PatId, UnaryOp, BinaryOp, Statement},
+    expr::{Body, Expr, Literal, Array, ExprId, Pat, PatId, UnaryOp, BinaryOp, Statement},
 };
+use self::primitive::{UncertainIntTy, UncertainFloatTy};
 
 fn transpose<T>(x: Cancelable<Option<T>>) -> Option<Cancelable<T>> {
     match x {
@@ -106,13 +108,32 @@ impl UnifyValue for TypeVarValue {
     }
 }
 
-/// The kinds of placeholders we need during type inference. Currently, we only
-/// have type variables; in the future, we will probably also need int and float
-/// variables, for inference of literal values (e.g. `100` could be one of
-/// several integer types).
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// The kinds of placeholders we need during type inference. There are general
+/// type variables, and special ones for integer and float literals: `100`
+/// could be one of several integer types, but it can't be a `bool`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum InferTy {
     TypeVar(TypeVarId),
+    IntVar(TypeVarId),
+    FloatVar(TypeVarId),
+}
+
+impl InferTy {
+    fn to_inner(self) -> TypeVarId {
+        match self {
+            InferTy::TypeVar(ty) | InferTy::IntVar(ty) | InferTy::FloatVar(ty) => ty,
+        }
+    }
+
+    /// The type of a variable which is still unknown after inference: integer
+    /// and float literals default to `i32` and `f64`, like in rustc.
+    fn fallback_value(self) -> Ty {
+        match self {
+            InferTy::TypeVar(..) => Ty::Unknown,
+            InferTy::IntVar(..) => Ty::Int(primitive::IntTy::I32),
+            InferTy::FloatVar(..) => Ty::Float(primitive::FloatTy::F64),
+        }
+    }
 }
 
 /// When inferring an expression, we propagate downward whatever type hint we
@@ -171,8 +192,10 @@ pub enum Ty {
     /// The pointee of a string slice. Written as `str`.
     Str,
 
-    // An array with the given length. Written as `[T; n]`.
-    // Array(Ty, ty::Const),
+    /// An array with the given length. Written as `[T; n]`. We don't evaluate
+    /// the length, so it's not part of the type.
+    Array(Arc<Ty>),
+
     /// The pointee of an array slice.  Written as `[T]`.
     Slice(Arc<Ty>),
 
@@ -197,8 +220,9 @@ pub enum Ty {
     // above function pointer type. Once we implement generics, we will probably
     // need this as well.
 
-    // A trait, defined with `dyn trait`.
-    // Dynamic(),
+    /// A trait object, written as `dyn Trait + Send`.
+    Dynamic(Arc<[TraitBound]>),
+
     // The anonymous type of a closure. Used to represent the type of
     // `|a| a`.
     // Closure(DefId, ClosureSubsts<'tcx>),
@@ -220,8 +244,9 @@ pub enum Ty {
     // `<T as Trait<..>>::N`.pub
     // Projection(ProjectionTy),
 
-    // Opaque (`impl Trait`) type found in a return type.
-    // Opaque(DefId, Substs),
+    /// An opaque type, written as `impl Trait` in argument or return
+    /// position. We only know the trait bounds of it.
+    Opaque(Arc<[TraitBound]>),
 
     // A type parameter; for example, `T` in `fn f<T>(x: T) {}
     // Param(ParamTy),
@@ -237,6 +262,14 @@ pub enum Ty {
     Unknown,
 }
 
+/// A trait that a `dyn Trait` or `impl Trait` type implements.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraitBound {
+    pub def_id: DefId,
+    /// The name, for displaying.
+    pub name: Name,
+}
+
 /// A function signature.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FnSig {
@@ -244,41 +277,87 @@ pub struct FnSig {
     output: Ty,
 }
 
+impl FnSig {
+    pub fn input(&self) -> &[Ty] {
+        &self.input
+    }
+
+    pub fn output(&self) -> &Ty {
+        &self.output
+    }
+}
+
 impl Ty {
     pub(crate) fn from_hir(
         db: &impl HirDatabase,
         module: &Module,
         impl_block: Option<&ImplBlock>,
         type_ref: &TypeRef,
+    ) -> Cancelable<Self> {
+        Ty::from_hir_in_blocks(db, module, &[], impl_block, type_ref)
+    }
+
+    /// Like `from_hir`, but the paths are resolved in the block `scopes`,
+    /// innermost first, before the module.
+    pub(crate) fn from_hir_in_blocks(
+        db: &impl HirDatabase,
+        module: &Module,
+        scopes: &[&BlockScope],
+        impl_block: Option<&ImplBlock>,
+        type_ref: &TypeRef,
+    ) -> Cancelable<Self> {
+        Ty::from_hir_expanding(db, module, scopes, impl_block, type_ref, &mut Vec::new())
+    }
+
+    /// Lowers `type_ref`, expanding the type aliases it mentions. `aliases` are
+    /// the aliases we are currently expanding: an alias which (directly or
+    /// through other aliases) refers to itself, like `type A = &A;`, becomes
+    /// `Ty::Unknown` at the point of recursion instead of looping forever.
+    fn from_hir_expanding(
+        db: &impl HirDatabase,
+        module: &Module,
+        blocks: &[&BlockScope],
+        impl_block: Option<&ImplBlock>,
+        type_ref: &TypeRef,
+        aliases: &mut Vec<DefId>,
     ) -> Cancelable<Self> {
         Ok(match type_ref {
             TypeRef::Never => Ty::Never,
             TypeRef::Tuple(inner) => {
                 let inner_tys = inner
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, impl_block, tr))
+                    .map(|tr| Ty::from_hir_expanding(db, module, blocks, impl_block, tr, aliases))
                     .collect::<Cancelable<Vec<_>>>()?;
                 Ty::Tuple(inner_tys.into())
             }
-            TypeRef::Path(path) => Ty::from_hir_path(db, module, impl_block, path)?,
+            TypeRef::Path(path) => {
+                Ty::from_hir_path(db, module, blocks, impl_block, path, aliases)?
+            }
             TypeRef::RawPtr(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
-                Ty::RawPtr(Arc::new(inner_ty), *mutability)
+                let inner_ty =
+                    Ty::from_hir_expanding(db, module, blocks, impl_block, inner, aliases)?;
+                Ty::RawPtr(intern_ty(db, inner_ty), *mutability)
+            }
+            TypeRef::Array(inner) => {
+                let inner_ty =
+                    Ty::from_hir_expanding(db, module, blocks, impl_block, inner, aliases)?;
+                Ty::Array(intern_ty(db, inner_ty))
             }
-            TypeRef::Array(_inner) => Ty::Unknown, // TODO
             TypeRef::Slice(inner) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
-                Ty::Slice(Arc::new(inner_ty))
+                let inner_ty =
+                    Ty::from_hir_expanding(db, module, blocks, impl_block, inner, aliases)?;
+                Ty::Slice(intern_ty(db, inner_ty))
             }
             TypeRef::Reference(inner, mutability) => {
-                let inner_ty = Ty::from_hir(db, module, impl_block, inner)?;
-                Ty::Ref(Arc::new(inner_ty), *mutability)
+                let inner_ty =
+                    Ty::from_hir_expanding(db, module, blocks, impl_block, inner, aliases)?;
+                Ty::Ref(intern_ty(db, inner_ty), *mutability)
             }
             TypeRef::Placeholder => Ty::Unknown,
             TypeRef::Fn(params) => {
                 let mut inner_tys = params
                     .iter()
-                    .map(|tr| Ty::from_hir(db, module, impl_block, tr))
+                    .map(|tr| Ty::from_hir_expanding(db, module, blocks, impl_block, tr, aliases))
                     .collect::<Cancelable<Vec<_>>>()?;
                 let return_ty = inner_tys
                     .pop()
@@ -289,10 +368,54 @@ impl Ty {
                 };
                 Ty::FnPtr(Arc::new(sig))
             }
+            TypeRef::ImplTrait(bounds) => match Ty::trait_bounds(db, module, blocks, bounds)? {
+                Some(bounds) => Ty::Opaque(bounds),
+                None => Ty::Unknown,
+            },
+            TypeRef::DynTrait(bounds) => match Ty::trait_bounds(db, module, blocks, bounds)? {
+                Some(bounds) => Ty::Dynamic(bounds),
+                None => Ty::Unknown,
+            },
             TypeRef::Error => Ty::Unknown,
         })
     }
 
+    /// Resolves the bounds of an `impl Trait` or `dyn Trait` type. Bounds
+    /// which don't resolve to a trait are skipped; if none of them do, we
+    /// don't know anything about the type and return `None`.
+    fn trait_bounds(
+        db: &impl HirDatabase,
+        module: &Module,
+        blocks: &[&BlockScope],
+        bounds: &[TypeRef],
+    ) -> Cancelable<Option<Arc<[TraitBound]>>> {
+        let mut res = Vec::new();
+        for bound in bounds {
+            let path = match bound {
+                TypeRef::Path(path) => path,
+                _ => continue,
+            };
+            let resolved = resolve_path_in_blocks(db, module, blocks.iter().cloned(), path)?;
+            let def_id = match resolved.take_types() {
+                Some(def_id) => def_id,
+                None => continue,
+            };
+            if let Def::Trait(_) = def_id.resolve(db)? {
+                if let Some(name) = path.segments.last() {
+                    res.push(TraitBound {
+                        def_id,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+        Ok(if res.is_empty() {
+            None
+        } else {
+            Some(res.into())
+        })
+    }
+
     pub(crate) fn from_hir_opt(
         db: &impl HirDatabase,
         module: &Module,
@@ -304,11 +427,13 @@ impl Ty {
             .unwrap_or(Ok(Ty::Unknown))
     }
 
-    pub(crate) fn from_hir_path(
+    fn from_hir_path(
         db: &impl HirDatabase,
         module: &Module,
+        blocks: &[&BlockScope],
         impl_block: Option<&ImplBlock>,
         path: &Path,
+        aliases: &mut Vec<DefId>,
     ) -> Cancelable<Self> {
         if let Some(name) = path.as_ident() {
             if let Some(KnownName::Bool) = name.as_known_name() {
@@ -328,12 +453,16 @@ impl Ty {
             }
         }
 
-        // Resolve in module (in type namespace)
-        let resolved = if let Some(r) = module.resolve_path(db, path)?.take_types() {
+        // Resolve in the blocks, then in the module (in type namespace)
+        let resolved = resolve_path_in_blocks(db, module, blocks.iter().cloned(), path)?;
+        let resolved = if let Some(r) = resolved.take_types() {
             r
         } else {
             return Ok(Ty::Unknown);
         };
+        if let Def::Type(alias) = resolved.resolve(db)? {
+            return type_for_type_alias(db, alias, aliases);
+        }
         let ty = db.type_for_def(resolved)?;
         Ok(ty)
     }
@@ -345,6 +474,7 @@ impl Ty {
     fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
         f(self);
         match self {
+            Ty::Array(t) => Arc::make_mut(t).walk_mut(f),
             Ty::Slice(t) => Arc::make_mut(t).walk_mut(f),
             Ty::RawPtr(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Ref(t, _) => Arc::make_mut(t).walk_mut(f),
@@ -394,6 +524,7 @@ impl fmt::Display for Ty {
             Ty::Uint(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Float(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Str => write!(f, "str"),
+            Ty::Array(t) => write!(f, "[{}; _]", t),
             Ty::Slice(t) => write!(f, "[{}]", t),
             Ty::RawPtr(t, m) => write!(f, "*{}{}", m.as_keyword_for_ptr(), t),
             Ty::Ref(t, m) => write!(f, "&{}{}", m.as_keyword_for_ref(), t),
@@ -416,6 +547,14 @@ impl fmt::Display for Ty {
                 write!(f, " -> {}", sig.output)
             }
             Ty::Adt { name, .. } => write!(f, "{}", name),
+            Ty::Opaque(bounds) => join(bounds.iter().map(|b| &b.name))
+                .surround_with("impl ", "")
+                .separator(" + ")
+                .to_fmt(f),
+            Ty::Dynamic(bounds) => join(bounds.iter().map(|b| &b.name))
+                .surround_with("dyn ", "")
+                .separator(" + ")
+                .to_fmt(f),
             Ty::Unknown => write!(f, "[unknown]"),
             Ty::Infer(..) => write!(f, "_"),
         }
@@ -461,6 +600,59 @@ pub fn type_for_enum_variant(db: &impl HirDatabase, ev: EnumVariant) -> Cancelab
     type_for_enum(db, enum_parent)
 }
 
+/// Compute the type an alias stands for. We don't go through the
+/// `type_for_def` query for nested aliases: a recursive alias would make salsa
+/// panic on the query cycle, so we track the aliases being expanded ourselves.
+fn type_for_type_alias(
+    db: &impl HirDatabase,
+    alias: Type,
+    aliases: &mut Vec<DefId>,
+) -> Cancelable<Ty> {
+    if aliases.contains(&alias.def_id) {
+        log::debug!("recursive type alias {:?}", alias.def_id);
+        return Ok(Ty::Unknown);
+    }
+    let (_, type_def) = alias.source(db)?;
+    let type_ref = TypeRef::from_ast_opt(type_def.type_ref());
+    let module = alias.def_id.module(db)?;
+    let impl_block = alias.def_id.impl_block(db)?;
+    aliases.push(alias.def_id);
+    let ty = Ty::from_hir_expanding(db, &module, &[], impl_block.as_ref(), &type_ref, aliases);
+    aliases.pop();
+    ty
+}
+
+/// The declared type of a const. The initializer is never looked at, so
+/// consts which refer to each other, like `const A: usize = B;` and
+/// `const B: usize = A;`, can't make the queries go in a cycle.
+fn type_for_const(db: &impl HirDatabase, konst: Const) -> Cancelable<Ty> {
+    let (_, const_def) = konst.source(db)?;
+    let type_ref = TypeRef::from_ast_opt(const_def.type_ref());
+    type_for_item_type_ref(db, konst.def_id, &type_ref)
+}
+
+/// The declared type of a static, see `type_for_const`.
+fn type_for_static(db: &impl HirDatabase, statik: Static) -> Cancelable<Ty> {
+    let (_, static_def) = statik.source(db)?;
+    let type_ref = TypeRef::from_ast_opt(static_def.type_ref());
+    type_for_item_type_ref(db, statik.def_id, &type_ref)
+}
+
+fn type_for_item_type_ref(
+    db: &impl HirDatabase,
+    def_id: DefId,
+    type_ref: &TypeRef,
+) -> Cancelable<Ty> {
+    let module = def_id.module(db)?;
+    let impl_block = def_id.impl_block(db)?;
+    Ty::from_hir(db, &module, impl_block.as_ref(), type_ref)
+}
+
+/// Returns the database's shared copy of `ty`, see `TyInterner::intern`.
+fn intern_ty(db: &impl AsRef<TyInterner>, ty: Ty) -> Arc<Ty> {
+    db.as_ref().intern(ty)
+}
+
 pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Ty> {
     let def = def_id.resolve(db)?;
     match def {
@@ -472,6 +664,9 @@ pub(super) fn type_for_def(db: &impl HirDatabase, def_id: DefId) -> Cancelable<T
         Def::Struct(s) => type_for_struct(db, s),
         Def::Enum(e) => type_for_enum(db, e),
         Def::EnumVariant(ev) => type_for_enum_variant(db, ev),
+        Def::Type(alias) => type_for_type_alias(db, alias, &mut Vec::new()),
+        Def::Const(konst) => type_for_const(db, konst),
+        Def::Static(statik) => type_for_static(db, statik),
         _ => {
             log::debug!(
                 "trying to get type for item of unknown type {:?} {:?}",
@@ -514,6 +709,16 @@ pub(super) fn type_for_field(
 pub struct InferenceResult {
     type_of_expr: ArenaMap<ExprId, Ty>,
     type_of_pat: ArenaMap<PatId, Ty>,
+    /// For each method call expr, the function it resolves to.
+    method_resolutions: ArenaMap<ExprId, DefId>,
+}
+
+impl InferenceResult {
+    /// The method called by the method call expression `expr`, if it could
+    /// be resolved.
+    pub fn method_resolution(&self, expr: ExprId) -> Option<DefId> {
+        self.method_resolutions.get(expr).cloned()
+    }
 }
 
 impl Index<ExprId> for InferenceResult {
@@ -540,9 +745,13 @@ struct InferenceContext<'a, D: HirDatabase> {
     scopes: Arc<FnScopes>,
     module: Module,
     impl_block: Option<ImplBlock>,
+    block_scopes: Arc<BodyBlockScopes>,
+    /// The blocks with items we are currently in, innermost last.
+    block_stack: Vec<ExprId>,
     var_unification_table: InPlaceUnificationTable<TypeVarId>,
     type_of_expr: ArenaMap<ExprId, Ty>,
     type_of_pat: ArenaMap<PatId, Ty>,
+    method_resolutions: ArenaMap<ExprId, DefId>,
     /// The return type of the function being inferred.
     return_ty: Ty,
 }
@@ -577,7 +786,11 @@ fn binary_op_return_ty(op: BinaryOp, rhs_ty: Ty) -> Ty {
         | BinaryOp::BitwiseAnd
         | BinaryOp::BitwiseOr
         | BinaryOp::BitwiseXor => match rhs_ty {
-            Ty::Uint(..) | Ty::Int(..) | Ty::Float(..) => rhs_ty,
+            Ty::Uint(..)
+            | Ty::Int(..)
+            | Ty::Float(..)
+            | Ty::Infer(InferTy::IntVar(..))
+            | Ty::Infer(InferTy::FloatVar(..)) => rhs_ty,
             _ => Ty::Unknown,
         },
         BinaryOp::RangeRightOpen | BinaryOp::RangeRightClosed => Ty::Unknown,
@@ -588,7 +801,14 @@ fn binary_op_rhs_expectation(op: BinaryOp, lhs_ty: Ty) -> Ty {
     match op {
         BinaryOp::BooleanAnd | BinaryOp::BooleanOr => Ty::Bool,
         BinaryOp::Assignment | BinaryOp::EqualityTest => match lhs_ty {
-            Ty::Uint(..) | Ty::Int(..) | Ty::Float(..) | Ty::Str | Ty::Char | Ty::Bool => lhs_ty,
+            Ty::Uint(..)
+            | Ty::Int(..)
+            | Ty::Float(..)
+            | Ty::Str
+            | Ty::Char
+            | Ty::Bool
+            | Ty::Infer(InferTy::IntVar(..))
+            | Ty::Infer(InferTy::FloatVar(..)) => lhs_ty,
             _ => Ty::Unknown,
         },
         BinaryOp::LesserEqualTest
@@ -615,7 +835,11 @@ fn binary_op_rhs_expectation(op: BinaryOp, lhs_ty: Ty) -> Ty {
         | BinaryOp::BitwiseAnd
         | BinaryOp::BitwiseOr
         | BinaryOp::BitwiseXor => match lhs_ty {
-            Ty::Uint(..) | Ty::Int(..) | Ty::Float(..) => lhs_ty,
+            Ty::Uint(..)
+            | Ty::Int(..)
+            | Ty::Float(..)
+            | Ty::Infer(InferTy::IntVar(..))
+            | Ty::Infer(InferTy::FloatVar(..)) => lhs_ty,
             _ => Ty::Unknown,
         },
         _ => Ty::Unknown,
@@ -629,10 +853,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         scopes: Arc<FnScopes>,
         module: Module,
         impl_block: Option<ImplBlock>,
+        block_scopes: Arc<BodyBlockScopes>,
     ) -> Self {
         InferenceContext {
+            block_stack: Vec::new(),
             type_of_expr: ArenaMap::default(),
             type_of_pat: ArenaMap::default(),
+            method_resolutions: ArenaMap::default(),
             var_unification_table: InPlaceUnificationTable::new(),
             return_ty: Ty::Unknown, // set in collect_fn_signature
             db,
@@ -640,6 +867,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             scopes,
             module,
             impl_block,
+            block_scopes,
         }
     }
 
@@ -657,6 +885,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         InferenceResult {
             type_of_expr: expr_types,
             type_of_pat: pat_types,
+            method_resolutions: self.method_resolutions,
         }
     }
 
@@ -668,8 +897,24 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         self.type_of_pat.insert(pat, ty);
     }
 
+    /// The scopes of the blocks around the current expression, innermost
+    /// first.
+    fn enclosing_block_scopes(&self) -> Vec<&BlockScope> {
+        let blocks = self.block_stack.iter().rev();
+        blocks
+            .filter_map(|&block| self.block_scopes.block(block))
+            .chain(self.block_scopes.outer())
+            .collect()
+    }
+
+    fn resolve_path(&self, path: &Path) -> Cancelable<PerNs<DefId>> {
+        let scopes = self.enclosing_block_scopes();
+        resolve_path_in_blocks(self.db, &self.module, scopes.into_iter(), path)
+    }
+
     fn make_ty(&self, type_ref: &TypeRef) -> Cancelable<Ty> {
-        Ty::from_hir(self.db, &self.module, self.impl_block.as_ref(), type_ref)
+        let scopes = self.enclosing_block_scopes();
+        Ty::from_hir_in_blocks(self.db, &self.module, &scopes, self.impl_block.as_ref(), type_ref)
     }
 
     fn unify(&mut self, ty1: &Ty, ty2: &Ty) -> bool {
@@ -679,9 +924,11 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         match (&*ty1, &*ty2) {
             (Ty::Unknown, ..) => true,
             (.., Ty::Unknown) => true,
+            // A diverging expression never produces a value, so it fits
+            // anywhere, without constraining the other type.
+            (Ty::Never, _) | (_, Ty::Never) => true,
             (Ty::Bool, _)
             | (Ty::Str, _)
-            | (Ty::Never, _)
             | (Ty::Char, _)
             | (Ty::Int(..), Ty::Int(..))
             | (Ty::Uint(..), Ty::Uint(..))
@@ -694,20 +941,30 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                     def_id: def_id2, ..
                 },
             ) if def_id1 == def_id2 => true,
-            (Ty::Slice(t1), Ty::Slice(t2)) => self.unify(t1, t2),
+            (Ty::Array(t1), Ty::Array(t2)) | (Ty::Slice(t1), Ty::Slice(t2)) => self.unify(t1, t2),
             (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::FnPtr(sig1), Ty::FnPtr(sig2)) if sig1 == sig2 => true,
+            (Ty::Opaque(b1), Ty::Opaque(b2)) | (Ty::Dynamic(b1), Ty::Dynamic(b2)) => b1 == b2,
             (Ty::Tuple(ts1), Ty::Tuple(ts2)) if ts1.len() == ts2.len() => ts1
                 .iter()
                 .zip(ts2.iter())
                 .all(|(t1, t2)| self.unify(t1, t2)),
-            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2))) => {
+            (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2)))
+            | (Ty::Infer(InferTy::IntVar(tv1)), Ty::Infer(InferTy::IntVar(tv2)))
+            | (Ty::Infer(InferTy::FloatVar(tv1)), Ty::Infer(InferTy::FloatVar(tv2))) => {
                 // both type vars are unknown since we tried to resolve them
                 self.var_unification_table.union(*tv1, *tv2);
                 true
             }
-            (Ty::Infer(InferTy::TypeVar(tv)), other) | (other, Ty::Infer(InferTy::TypeVar(tv))) => {
+            (Ty::Infer(InferTy::TypeVar(tv)), other)
+            | (other, Ty::Infer(InferTy::TypeVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Int(_))
+            | (other @ Ty::Int(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::IntVar(tv)), other @ Ty::Uint(_))
+            | (other @ Ty::Uint(_), Ty::Infer(InferTy::IntVar(tv)))
+            | (Ty::Infer(InferTy::FloatVar(tv)), other @ Ty::Float(_))
+            | (other @ Ty::Float(_), Ty::Infer(InferTy::FloatVar(tv))) => {
                 // the type var is unknown since we tried to resolve it
                 self.var_unification_table
                     .union_value(*tv, TypeVarValue::Known(other.clone()));
@@ -723,6 +980,18 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         ))
     }
 
+    fn new_integer_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::IntVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
+    fn new_float_var(&mut self) -> Ty {
+        Ty::Infer(InferTy::FloatVar(
+            self.var_unification_table.new_key(TypeVarValue::Unknown),
+        ))
+    }
+
     /// Replaces Ty::Unknown by a new type var, so we can maybe still infer it.
     fn insert_type_vars_shallow(&mut self, ty: Ty) -> Ty {
         match ty {
@@ -741,12 +1010,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     /// known type.
     fn resolve_ty_as_possible(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_as_possible(known_ty.clone())
                 } else {
-                    Ty::Infer(InferTy::TypeVar(tv))
+                    ty
                 }
             }
             _ => ty,
@@ -756,30 +1026,33 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     /// If `ty` is a type variable with known type, returns that type;
     /// otherwise, return ty.
     fn resolve_ty_shallow<'b>(&mut self, ty: &'b Ty) -> Cow<'b, Ty> {
-        match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                match self.var_unification_table.probe_value(*tv).known() {
-                    Some(known_ty) => {
-                        // The known_ty can't be a type var itself
-                        Cow::Owned(known_ty.clone())
-                    }
-                    _ => Cow::Borrowed(ty),
-                }
+        let mut ty = Cow::Borrowed(ty);
+        // A type var can be known to be an integer var, which in turn can be
+        // known to be a specific integer type, so we may need several steps.
+        loop {
+            let inner = match &*ty {
+                Ty::Infer(tv) => tv.to_inner(),
+                _ => return ty,
+            };
+            match self.var_unification_table.probe_value(inner).known() {
+                Some(known_ty) => ty = Cow::Owned(known_ty.clone()),
+                None => return ty,
             }
-            _ => Cow::Borrowed(ty),
         }
     }
 
     /// Resolves the type completely; type variables without known type are
-    /// replaced by Ty::Unknown.
+    /// replaced by their fallback: Ty::Unknown, or the default integer or
+    /// float type.
     fn resolve_ty_completely(&mut self, ty: Ty) -> Ty {
         ty.fold(&mut |ty| match ty {
-            Ty::Infer(InferTy::TypeVar(tv)) => {
-                if let Some(known_ty) = self.var_unification_table.probe_value(tv).known() {
+            Ty::Infer(tv) => {
+                let inner = tv.to_inner();
+                if let Some(known_ty) = self.var_unification_table.probe_value(inner).known() {
                     // known_ty may contain other variables that are known by now
                     self.resolve_ty_completely(known_ty.clone())
                 } else {
-                    Ty::Unknown
+                    tv.fallback_value()
                 }
             }
             _ => ty,
@@ -797,8 +1070,8 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             };
         };
 
-        // resolve in module
-        let resolved = ctry!(self.module.resolve_path(self.db, &path)?.take_values());
+        // resolve in the blocks and the module
+        let resolved = ctry!(self.resolve_path(&path)?.take_values());
         let ty = self.db.type_for_def(resolved)?;
         let ty = self.insert_type_vars(ty);
         Ok(Some(ty))
@@ -810,7 +1083,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         } else {
             return Ok((Ty::Unknown, None));
         };
-        let def_id = if let Some(def_id) = self.module.resolve_path(self.db, &path)?.take_types() {
+        let def_id = if let Some(def_id) = self.resolve_path(&path)?.take_types() {
             def_id
         } else {
             return Ok((Ty::Unknown, None));
@@ -828,6 +1101,48 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         })
     }
 
+    /// Infers the types of the bindings in `pat`, which matches a value of the
+    /// type `expected`.
+    fn infer_pat(&mut self, pat: PatId, expected: &Ty) -> Cancelable<Ty> {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let ty = match &body[pat] {
+            Pat::Missing => Ty::Unknown,
+            Pat::Wild | Pat::Bind { .. } => expected.clone(),
+            Pat::Tuple(args) => {
+                let expected_tys = match &*self.resolve_ty_shallow(expected) {
+                    Ty::Tuple(tys) if tys.len() == args.len() => tys.to_vec(),
+                    _ => vec![Ty::Unknown; args.len()],
+                };
+                let mut tys = Vec::with_capacity(args.len());
+                for (arg, expected_ty) in args.iter().zip(expected_tys) {
+                    tys.push(self.infer_pat(*arg, &expected_ty)?);
+                }
+                Ty::Tuple(tys.into())
+            }
+            Pat::TupleStruct { path, args } => {
+                let (ty, def_id) = self.resolve_variant(path.as_ref())?;
+                for (i, arg) in args.iter().enumerate() {
+                    let field_ty = match def_id {
+                        Some(def_id) => self
+                            .db
+                            .type_for_field(def_id, Name::tuple_field_name(i))?
+                            .unwrap_or(Ty::Unknown),
+                        None => Ty::Unknown,
+                    };
+                    self.infer_pat(*arg, &field_ty)?;
+                }
+                ty
+            }
+            Pat::Path(path) => self.resolve_variant(Some(path))?.0,
+            Pat::Lit(expr) => self.infer_expr(*expr, &Expectation::has_type(expected.clone()))?,
+        };
+        let ty = self.insert_type_vars_shallow(ty);
+        self.unify(&ty, expected);
+        let ty = self.resolve_ty_as_possible(ty);
+        self.write_pat_ty(pat, ty.clone());
+        Ok(ty)
+    }
+
     fn infer_expr(&mut self, expr: ExprId, expected: &Expectation) -> Cancelable<Ty> {
         let body = Arc::clone(&self.body); // avoid borrow checker problem
         let ty = match &body[expr] {
@@ -842,16 +1157,32 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let then_ty = self.infer_expr(*then_branch, expected)?;
                 match else_branch {
                     Some(else_branch) => {
-                        self.infer_expr(*else_branch, expected)?;
+                        let else_ty = self.infer_expr(*else_branch, expected)?;
+                        if let Ty::Never = then_ty {
+                            else_ty
+                        } else {
+                            self.unify(&then_ty, &else_ty);
+                            then_ty
+                        }
                     }
                     None => {
                         // no else branch -> unit
                         self.unify(&then_ty, &Ty::unit()); // actually coerce
+                        Ty::unit()
                     }
-                };
-                then_ty
+                }
+            }
+            Expr::Block { statements, tail } => {
+                let has_items = self.block_scopes.block(expr).is_some();
+                if has_items {
+                    self.block_stack.push(expr);
+                }
+                let ty = self.infer_block(statements, *tail, expected);
+                if has_items {
+                    self.block_stack.pop();
+                }
+                ty?
             }
-            Expr::Block { statements, tail } => self.infer_block(statements, *tail, expected)?,
             Expr::Loop { body } => {
                 self.infer_expr(*body, &Expectation::has_type(Ty::unit()))?;
                 // TODO handle break with value
@@ -900,7 +1231,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let receiver_ty = self.infer_expr(*receiver, &Expectation::none())?;
                 let resolved = receiver_ty.clone().lookup_method(self.db, method_name)?;
                 let method_ty = match resolved {
-                    Some(def_id) => self.db.type_for_def(def_id)?,
+                    Some(def_id) => {
+                        self.method_resolutions.insert(expr, def_id);
+                        self.db.type_for_def(def_id)?
+                    }
                     None => Ty::Unknown,
                 };
                 let method_ty = self.insert_type_vars(method_ty);
@@ -926,14 +1260,24 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 ret_ty
             }
             Expr::Match { expr, arms } => {
-                let _ty = self.infer_expr(*expr, &Expectation::none())?;
+                let input_ty = self.infer_expr(*expr, &Expectation::none())?;
+                // Diverging arms don't contribute to the type of the match.
+                let mut result_ty = Ty::Never;
                 for arm in arms {
-                    // TODO type the bindings in pats
-                    // TODO type the guard
-                    let _ty = self.infer_expr(arm.expr, &Expectation::none())?;
+                    for pat in &arm.pats {
+                        self.infer_pat(*pat, &input_ty)?;
+                    }
+                    if let Some(guard) = arm.guard {
+                        self.infer_expr(guard, &Expectation::has_type(Ty::Bool))?;
+                    }
+                    let arm_ty = self.infer_expr(arm.expr, expected)?;
+                    if let Ty::Never = result_ty {
+                        result_ty = arm_ty;
+                    } else {
+                        self.unify(&result_ty, &arm_ty);
+                    }
                 }
-                // TODO unify all the match arm types
-                Ty::Unknown
+                result_ty
             }
             Expr::Path(p) => self.infer_path_expr(expr, p)?.unwrap_or(Ty::Unknown),
             Expr::Continue => Ty::Never,
@@ -994,10 +1338,16 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 Ty::Unknown
             }
             Expr::Cast { expr, type_ref } => {
-                let _inner_ty = self.infer_expr(*expr, &Expectation::none())?;
-                let cast_ty =
-                    Ty::from_hir(self.db, &self.module, self.impl_block.as_ref(), type_ref)?;
+                let cast_ty = self.make_ty(type_ref)?;
                 let cast_ty = self.insert_type_vars(cast_ty);
+                // Like rustc, use the target type as a hint for a literal
+                // operand, so that `1 as u8` is a `u8` and not an `i32`. If
+                // the hint doesn't fit, as in `1 as f64`, it's just ignored.
+                let inner_expectation = match &body[*expr] {
+                    Expr::Literal(_) => Expectation::has_type(cast_ty.clone()),
+                    _ => Expectation::none(),
+                };
+                self.infer_expr(*expr, &inner_expectation)?;
                 // TODO check the cast...
                 cast_ty
             }
@@ -1040,6 +1390,78 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 }
                 _ => Ty::Unknown,
             },
+            Expr::Tuple { exprs } => {
+                let expected_tys = match &*self.resolve_ty_shallow(&expected.ty) {
+                    Ty::Tuple(tys) if tys.len() == exprs.len() => tys.to_vec(),
+                    _ => vec![Ty::Unknown; exprs.len()],
+                };
+                let mut tys = Vec::with_capacity(exprs.len());
+                for (expr, expected_ty) in exprs.iter().zip(expected_tys) {
+                    tys.push(self.infer_expr(*expr, &Expectation::has_type(expected_ty))?);
+                }
+                Ty::Tuple(tys.into())
+            }
+            Expr::Array(array) => {
+                let elem_ty = match &*self.resolve_ty_shallow(&expected.ty) {
+                    Ty::Array(inner) | Ty::Slice(inner) => Ty::clone(inner),
+                    _ => Ty::Unknown,
+                };
+                let elem_ty = self.insert_type_vars_shallow(elem_ty);
+                match array {
+                    Array::ElementList(items) => {
+                        for item in items {
+                            self.infer_expr(*item, &Expectation::has_type(elem_ty.clone()))?;
+                        }
+                    }
+                    Array::Repeat {
+                        initializer,
+                        repeat,
+                    } => {
+                        self.infer_expr(*initializer, &Expectation::has_type(elem_ty.clone()))?;
+                        self.infer_expr(
+                            *repeat,
+                            &Expectation::has_type(Ty::Uint(primitive::UintTy::Usize)),
+                        )?;
+                    }
+                }
+                Ty::Array(Arc::new(elem_ty))
+            }
+            Expr::Index { base, index } => {
+                let base_ty = self.infer_expr(*base, &Expectation::none())?;
+                let index_ty = self.infer_expr(*index, &Expectation::none())?;
+                // TODO the `Index` trait; for now, only builtin indexing of
+                // arrays and slices by an integer is supported
+                let elem_ty = base_ty.autoderef(self.db).find_map(|ty| match ty {
+                    Ty::Array(inner) | Ty::Slice(inner) => Some(Ty::clone(&inner)),
+                    _ => None,
+                });
+                let is_integer = match &*self.resolve_ty_shallow(&index_ty) {
+                    Ty::Uint(..) | Ty::Int(..) | Ty::Infer(InferTy::IntVar(..)) => true,
+                    _ => false,
+                };
+                match elem_ty {
+                    Some(elem_ty) if is_integer => {
+                        self.unify(&index_ty, &Ty::Uint(primitive::UintTy::Usize));
+                        elem_ty
+                    }
+                    _ => Ty::Unknown,
+                }
+            }
+            Expr::Literal(lit) => match lit {
+                Literal::Bool(_) => Ty::Bool,
+                Literal::String => Ty::Ref(intern_ty(self.db, Ty::Str), Mutability::Shared),
+                Literal::ByteString => {
+                    let array = Ty::Array(intern_ty(self.db, Ty::Uint(primitive::UintTy::U8)));
+                    Ty::Ref(intern_ty(self.db, array), Mutability::Shared)
+                }
+                Literal::Char => Ty::Char,
+                Literal::Byte => Ty::Uint(primitive::UintTy::U8),
+                Literal::Int(UncertainIntTy::Signed(ty)) => Ty::Int(*ty),
+                Literal::Int(UncertainIntTy::Unsigned(ty)) => Ty::Uint(*ty),
+                Literal::Int(UncertainIntTy::Unknown) => self.new_integer_var(),
+                Literal::Float(UncertainFloatTy::Known(ty)) => Ty::Float(*ty),
+                Literal::Float(UncertainFloatTy::Unknown) => self.new_float_var(),
+            },
         };
         // use a new type variable if we got Ty::Unknown here
         let ty = self.insert_type_vars_shallow(ty);
@@ -1055,36 +1477,47 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         tail: Option<ExprId>,
         expected: &Expectation,
     ) -> Cancelable<Ty> {
+        // A block without a tail has the type `!` if one of the statements
+        // diverges, like in `{ foo(); return; }`.
+        let mut diverges = false;
         for stmt in statements {
-            match stmt {
+            let stmt_ty = match stmt {
                 Statement::Let {
                     pat,
                     type_ref,
                     initializer,
                 } => {
-                    let decl_ty = Ty::from_hir_opt(
-                        self.db,
-                        &self.module,
-                        self.impl_block.as_ref(),
-                        type_ref.as_ref(),
-                    )?;
+                    let decl_ty = match type_ref {
+                        Some(type_ref) => self.make_ty(type_ref)?,
+                        None => Ty::Unknown,
+                    };
                     let decl_ty = self.insert_type_vars(decl_ty);
                     let ty = if let Some(expr) = initializer {
-                        let expr_ty = self.infer_expr(*expr, &Expectation::has_type(decl_ty))?;
+                        let expr_ty =
+                            self.infer_expr(*expr, &Expectation::has_type(decl_ty.clone()))?;
                         expr_ty
                     } else {
-                        decl_ty
+                        decl_ty.clone()
+                    };
+                    // `let x: u32 = return;` still declares a `u32`.
+                    let pat_ty = match ty {
+                        Ty::Never if type_ref.is_some() => decl_ty,
+                        _ => ty.clone(),
                     };
 
-                    self.write_pat_ty(*pat, ty);
-                }
-                Statement::Expr(expr) => {
-                    self.infer_expr(*expr, &Expectation::none())?;
+                    self.infer_pat(*pat, &pat_ty)?;
+                    ty
                 }
+                Statement::Expr(expr) => self.infer_expr(*expr, &Expectation::none())?,
+            };
+            if let Ty::Never = stmt_ty {
+                diverges = true;
             }
         }
         let ty = if let Some(expr) = tail {
             self.infer_expr(expr, expected)?
+        } else if diverges {
+            Ty::Never
         } else {
             Ty::unit()
         };
@@ -1096,7 +1529,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         for (type_ref, pat) in signature.params().iter().zip(body.params()) {
             let ty = self.make_ty(type_ref)?;
             let ty = self.insert_type_vars(ty);
-            self.write_pat_ty(*pat, ty);
+            self.infer_pat(*pat, &ty)?;
         }
         self.return_ty = {
             let ty = self.make_ty(signature.ret_type())?;
@@ -1122,7 +1555,8 @@ pub fn infer(db: &impl HirDatabase, def_id: DefId) -> Cancelable<Arc<InferenceRe
     let scopes = db.fn_scopes(def_id)?;
     let module = function.module(db)?;
     let impl_block = function.impl_block(db)?;
-    let mut ctx = InferenceContext::new(db, body, scopes, module, impl_block);
+    let block_scopes = db.body_block_scopes(def_id)?;
+    let mut ctx = InferenceContext::new(db, body, scopes, module, impl_block, block_scopes);
 
     let signature = function.signature(db);
     ctx.collect_fn_signature(&signature)?;
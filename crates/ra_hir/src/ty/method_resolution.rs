@@ -8,7 +8,7 @@ use rustc_hash::FxHashMap;
 
 use ra_db::{Cancelable, SourceRootId};
 
-use crate::{HirDatabase, DefId, module_tree::ModuleId, Module, Crate, Name, Function, impl_block::{ImplId, ImplBlock, ImplItem}};
+use crate::{HirDatabase, DefId, module_tree::ModuleId, Module, Crate, Name, Function, ImplBlock, Trait, impl_block::{ImplId, ImplItem}};
 use super::Ty;
 
 /// This is used as a key for indexing impls.
@@ -34,44 +34,85 @@ impl TyFingerprint {
 pub struct CrateImplBlocks {
     /// To make sense of the ModuleIds, we need the source root.
     source_root_id: SourceRootId,
+    /// Inherent impls, by their self type.
     impls: FxHashMap<TyFingerprint, Vec<(ModuleId, ImplId)>>,
+    /// Trait impls, by their self type.
+    trait_impls: FxHashMap<TyFingerprint, Vec<(ModuleId, ImplId)>>,
+    /// Trait impls, by the implemented trait.
+    impls_by_trait: FxHashMap<DefId, Vec<(ModuleId, ImplId)>>,
 }
 
 impl CrateImplBlocks {
+    /// Returns the impls for `ty`, the inherent ones first, as they take
+    /// precedence during method resolution.
     pub fn lookup_impl_blocks<'a>(
         &'a self,
         db: &'a impl HirDatabase,
         ty: &Ty,
     ) -> impl Iterator<Item = Cancelable<ImplBlock>> + 'a {
         let fingerprint = TyFingerprint::for_impl(ty);
-        fingerprint
-            .and_then(|f| self.impls.get(&f))
+        let inherent = fingerprint.and_then(|f| self.impls.get(&f));
+        let traits = fingerprint.and_then(|f| self.trait_impls.get(&f));
+        inherent
             .into_iter()
+            .chain(traits)
             .flat_map(|i| i.iter())
-            .map(move |(module_id, impl_id)| {
-                let module_impl_blocks = db.impls_in_module(self.source_root_id, *module_id)?;
-                Ok(ImplBlock::from_id(module_impl_blocks, *impl_id))
-            })
+            .map(move |&(module_id, impl_id)| self.impl_block(db, module_id, impl_id))
+    }
+
+    /// Returns the impls of `trait_` in this crate.
+    pub fn lookup_impl_blocks_for_trait<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        trait_: &Trait,
+    ) -> impl Iterator<Item = Cancelable<ImplBlock>> + 'a {
+        self.impls_by_trait
+            .get(&trait_.def_id)
+            .into_iter()
+            .flat_map(|i| i.iter())
+            .map(move |&(module_id, impl_id)| self.impl_block(db, module_id, impl_id))
+    }
+
+    fn impl_block(
+        &self,
+        db: &impl HirDatabase,
+        module_id: ModuleId,
+        impl_id: ImplId,
+    ) -> Cancelable<ImplBlock> {
+        let module_impl_blocks = db.impls_in_module(self.source_root_id, module_id)?;
+        Ok(ImplBlock::from_id(module_impl_blocks, impl_id))
     }
 
     fn collect_recursive(&mut self, db: &impl HirDatabase, module: Module) -> Cancelable<()> {
+        db.check_canceled()?;
         let module_id = module.def_id.loc(db).module_id;
         let module_impl_blocks = db.impls_in_module(self.source_root_id, module_id)?;
 
         for (impl_id, impl_data) in module_impl_blocks.impls.iter() {
             let impl_block = ImplBlock::from_id(Arc::clone(&module_impl_blocks), impl_id);
 
-            if let Some(_target_trait) = impl_data.target_trait() {
-                // ignore for now
-            } else {
-                let target_ty =
-                    Ty::from_hir(db, &module, Some(&impl_block), impl_data.target_type())?;
-                if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
-                    self.impls
+            let target_ty = Ty::from_hir(db, &module, Some(&impl_block), impl_data.target_type())?;
+            let target_ty_fp = TyFingerprint::for_impl(&target_ty);
+            if impl_data.target_trait().is_some() {
+                let trait_ = match impl_block.target_trait_def(db)? {
+                    Some(it) => it,
+                    None => continue,
+                };
+                self.impls_by_trait
+                    .entry(trait_.def_id)
+                    .or_insert_with(Vec::new)
+                    .push((module_id, impl_id));
+                if let Some(target_ty_fp) = target_ty_fp {
+                    self.trait_impls
                         .entry(target_ty_fp)
                         .or_insert_with(Vec::new)
                         .push((module_id, impl_id));
                 }
+            } else if let Some(target_ty_fp) = target_ty_fp {
+                self.impls
+                    .entry(target_ty_fp)
+                    .or_insert_with(Vec::new)
+                    .push((module_id, impl_id));
             }
         }
 
@@ -92,6 +133,8 @@ impl CrateImplBlocks {
         let mut crate_impl_blocks = CrateImplBlocks {
             source_root_id,
             impls: FxHashMap::default(),
+            trait_impls: FxHashMap::default(),
+            impls_by_trait: FxHashMap::default(),
         };
         if let Some(module) = krate.root_module(db)? {
             crate_impl_blocks.collect_recursive(db, module)?;
@@ -139,6 +182,21 @@ impl Ty {
         // rustc does an autoderef and then autoref again).
 
         for derefed_ty in self.autoderef(db) {
+            // The methods of `impl Trait` and `dyn Trait` types come from
+            // their bounds.
+            match &derefed_ty {
+                Ty::Opaque(bounds) | Ty::Dynamic(bounds) => {
+                    for bound in bounds.iter() {
+                        for f in Trait::new(bound.def_id).methods(db)? {
+                            if let Some(result) = callback(f)? {
+                                return Ok(Some(result));
+                            }
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
             let krate = match def_crate(db, &derefed_ty)? {
                 Some(krate) => krate,
                 None => continue,
@@ -161,4 +219,27 @@ impl Ty {
         }
         Ok(None)
     }
+
+    /// Calls `callback` for the items of all impl blocks of this type, without
+    /// autoderef. These are the items which can be reached via `Self::`.
+    pub fn iterate_impl_items<T>(
+        self,
+        db: &impl HirDatabase,
+        mut callback: impl FnMut(ImplItem) -> Cancelable<Option<T>>,
+    ) -> Cancelable<Option<T>> {
+        let krate = match def_crate(db, &self)? {
+            Some(krate) => krate,
+            None => return Ok(None),
+        };
+        let impls = db.impls_in_crate(krate)?;
+        for impl_block in impls.lookup_impl_blocks(db, &self) {
+            let impl_block = impl_block?;
+            for item in impl_block.items() {
+                if let Some(result) = callback(item.clone())? {
+                    return Ok(Some(result));
+                }
+            }
+        }
+        Ok(None)
+    }
 }
@@ -130,3 +130,40 @@ impl FloatTy {
         }
     }
 }
+
+/// The type of an integer literal, as far as it is given by its suffix:
+/// `92u8` is a `u8`, while `92` can be of any integer type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UncertainIntTy {
+    Unknown,
+    Signed(IntTy),
+    Unsigned(UintTy),
+}
+
+impl UncertainIntTy {
+    pub fn from_name(name: &Name) -> UncertainIntTy {
+        if let Some(ty) = IntTy::from_name(name) {
+            UncertainIntTy::Signed(ty)
+        } else if let Some(ty) = UintTy::from_name(name) {
+            UncertainIntTy::Unsigned(ty)
+        } else {
+            UncertainIntTy::Unknown
+        }
+    }
+}
+
+/// The type of a float literal, as far as it is given by its suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UncertainFloatTy {
+    Unknown,
+    Known(FloatTy),
+}
+
+impl UncertainFloatTy {
+    pub fn from_name(name: &Name) -> UncertainFloatTy {
+        match FloatTy::from_name(name) {
+            Some(ty) => UncertainFloatTy::Known(ty),
+            None => UncertainFloatTy::Unknown,
+        }
+    }
+}
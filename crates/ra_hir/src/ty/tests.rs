@@ -10,7 +10,7 @@ use ra_syntax::ast::{self, AstNode};
 use test_utils::{project_dir, assert_eq_text, read_text};
 
 use crate::{
-    source_binder,
+    source_binder, Ty, TyInterner,
     mock::MockDatabase,
 };
 
@@ -268,6 +268,205 @@ fn test(a: A) {
     );
 }
 
+#[test]
+fn infer_diverging() {
+    check_inference(
+        r#"
+fn test(a: i32, c: bool) {
+    let x = if c { a } else { return; };
+    let y = if c { return } else { a };
+    let z: u32 = return;
+    loop {}
+}
+"#,
+        "diverging.txt",
+    );
+}
+
+#[test]
+fn infer_literals() {
+    check_inference(
+        r#"
+fn takes_u8(x: u8) {}
+
+fn test() {
+    let a = 1;
+    takes_u8(a);
+    let b = 1.5;
+    let c = 2;
+    let d = 1 as u8;
+    let e = 'c';
+    let f = b'x';
+    let g = "str";
+    let h = true;
+    let i = c + 1u16;
+}
+"#,
+        "literals.txt",
+    );
+}
+
+#[test]
+fn infer_byte_strings() {
+    check_inference(
+        r#"
+fn test() {
+    let a = b"bytes";
+    let b = br"raw";
+}
+"#,
+        "byte_strings.txt",
+    );
+}
+
+#[test]
+fn infer_tuples_and_arrays() {
+    check_inference(
+        r#"
+fn test(x: &[u8], y: [u16; 4], t: (u32, &str)) {
+    let (a, b) = t;
+    let c = t.0;
+    let d = x[0];
+    let e = y[1];
+    let f = [1, 2];
+    let g: [u8; 2] = [3, 4];
+    let h = (a, true);
+    let i = [0u64; 8];
+    match t {
+        (n, _) => n,
+    };
+}
+"#,
+        "tuples_and_arrays.txt",
+    );
+}
+
+#[test]
+fn infer_impl_and_dyn_trait() {
+    check_inference(
+        r#"
+trait Foo {
+    fn foo(&self) -> u32;
+}
+
+trait Bar {
+    fn bar(&self) -> i8;
+}
+
+fn test(x: impl Foo, y: &dyn Foo, z: impl Foo + Bar) {
+    x;
+    y;
+    x.foo();
+    y.foo();
+    z.bar();
+}
+"#,
+        "impl_and_dyn_trait.txt",
+    );
+}
+
+#[test]
+fn infer_recursive_types() {
+    check_inference(
+        r#"
+struct Foo(*const Foo);
+
+type A = &B;
+type B = (A, u32);
+type C = Foo;
+
+fn test(a: A, c: C, f: Foo) {
+    a;
+    c;
+    f.0;
+}
+"#,
+        "recursive_types.txt",
+    );
+}
+
+#[test]
+fn infer_recursive_consts() {
+    check_inference(
+        r#"
+const A: usize = B;
+const B: usize = A;
+static C: &str = D;
+static D: &str = C;
+
+fn test() {
+    A;
+    B;
+    C;
+    D;
+}
+"#,
+        "recursive_consts.txt",
+    );
+}
+
+#[test]
+fn infer_items_of_blocks() {
+    check_inference(
+        r#"
+mod m {
+    pub struct Bar;
+}
+
+fn test() {
+    struct Foo;
+    let a: Foo;
+    {
+        use m::Bar;
+        let b: Bar;
+    }
+    let c: Bar;
+}
+
+fn other() {
+    let d: Foo;
+}
+"#,
+        "items_of_blocks.txt",
+    );
+}
+
+#[test]
+fn source_types_are_interned() {
+    let (db, _, file_id) = MockDatabase::with_single_file(
+        r#"
+fn test(a: &str, b: &mut str) {
+    "c";
+}
+"#,
+    );
+    let fn_def = db
+        .source_file(file_id)
+        .syntax()
+        .descendants()
+        .find_map(ast::FnDef::cast)
+        .unwrap()
+        .to_owned();
+    let func = source_binder::function_from_source(&db, file_id, &fn_def)
+        .unwrap()
+        .unwrap();
+    let inference_result = func.infer(&db).unwrap();
+    let referents = inference_result
+        .type_of_pat
+        .iter()
+        .map(|(_, ty)| ty)
+        .chain(inference_result.type_of_expr.iter().map(|(_, ty)| ty))
+        .filter_map(|ty| match ty {
+            Ty::Ref(inner, _) => Some(Arc::clone(inner)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(referents.len(), 3);
+    assert!(referents.iter().all(|it| Arc::ptr_eq(it, &referents[0])));
+    let types: &TyInterner = db.as_ref();
+    assert_eq!(types.len(), 1);
+}
+
 fn infer(content: &str) -> String {
     let (db, _, file_id) = MockDatabase::with_single_file(content);
     let source_file = db.source_file(file_id);
@@ -49,8 +49,10 @@ pub enum TypeRef {
     /// A fn pointer. Last element of the vector is the return type.
     Fn(Vec<TypeRef>),
     // For
-    // ImplTrait,
-    // DynTrait,
+    /// An `impl Trait` type, with the trait bounds.
+    ImplTrait(Vec<TypeRef>),
+    /// A `dyn Trait` type, with the trait bounds.
+    DynTrait(Vec<TypeRef>),
     Error,
 }
 
@@ -95,8 +97,12 @@ impl TypeRef {
             }
             // for types are close enough for our purposes to the inner type for now...
             ForType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
-            ImplTraitType(_inner) => TypeRef::Error,
-            DynTraitType(_inner) => TypeRef::Error,
+            ImplTraitType(inner) => {
+                TypeRef::ImplTrait(inner.bounds().map(TypeRef::from_ast).collect())
+            }
+            DynTraitType(inner) => {
+                TypeRef::DynTrait(inner.bounds().map(TypeRef::from_ast).collect())
+            }
         }
     }
 
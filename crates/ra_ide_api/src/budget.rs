@@ -0,0 +1,44 @@
+//! Expensive requests, like the workspace symbol search and the search for
+//! references, can be given a deadline. When it passes, the results found so
+//! far are returned, and the rest of the work is done in the background, so
+//! that the next request is faster.
+use std::{panic::AssertUnwindSafe, time::Instant};
+
+use ra_db::{BaseDatabase, Cancelable};
+use salsa::ParallelDatabase;
+
+use crate::db::RootDatabase;
+
+/// The result of a request with a deadline. `is_incomplete` is set if the
+/// deadline has passed before all of the results were found.
+#[derive(Debug)]
+pub struct Partial<T> {
+    pub value: T,
+    pub is_incomplete: bool,
+}
+
+impl<T> Partial<T> {
+    pub(crate) fn new(value: T, is_incomplete: bool) -> Partial<T> {
+        Partial { value, is_incomplete }
+    }
+}
+
+pub(crate) fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.map_or(false, |it| Instant::now() >= it)
+}
+
+/// Runs `f` on a snapshot of the database, on the thread pool. The snapshot
+/// blocks the changes to the database, so `f` should check for cancellation
+/// between the units of work: the work is dropped, together with the
+/// snapshot, as soon as a change is pending.
+pub(crate) fn complete_in_background<F>(db: &RootDatabase, f: F)
+where
+    F: FnOnce(&RootDatabase) -> Cancelable<()> + Send + 'static,
+{
+    let snapshot = db.snapshot();
+    rayon::spawn(move || {
+        // Cancellation unwinds, and a panic must not reach the thread pool.
+        let f = AssertUnwindSafe(f);
+        let _ = snapshot.catch_canceled(move |db| (f.0)(db));
+    });
+}
@@ -0,0 +1,75 @@
+use ra_db::Cancelable;
+use ra_syntax::{
+    AstNode,
+    ast,
+    SyntaxKind::MODULE,
+};
+
+use crate::{NavigationTarget, Query, db::RootDatabase, symbol_index};
+
+/// A primitive type, like `u32` or `str`. They are not defined anywhere, so
+/// the IDE features know about them from this table.
+#[derive(Debug)]
+pub(crate) struct BuiltinType {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+}
+
+const BUILTIN_TYPES: &[BuiltinType] = &[
+    BuiltinType { name: "bool", description: "The boolean type." },
+    BuiltinType { name: "char", description: "A character type." },
+    BuiltinType { name: "str", description: "String slices." },
+    BuiltinType { name: "i8", description: "The 8-bit signed integer type." },
+    BuiltinType { name: "i16", description: "The 16-bit signed integer type." },
+    BuiltinType { name: "i32", description: "The 32-bit signed integer type." },
+    BuiltinType { name: "i64", description: "The 64-bit signed integer type." },
+    BuiltinType { name: "i128", description: "The 128-bit signed integer type." },
+    BuiltinType { name: "isize", description: "The pointer-sized signed integer type." },
+    BuiltinType { name: "u8", description: "The 8-bit unsigned integer type." },
+    BuiltinType { name: "u16", description: "The 16-bit unsigned integer type." },
+    BuiltinType { name: "u32", description: "The 32-bit unsigned integer type." },
+    BuiltinType { name: "u64", description: "The 64-bit unsigned integer type." },
+    BuiltinType { name: "u128", description: "The 128-bit unsigned integer type." },
+    BuiltinType { name: "usize", description: "The pointer-sized unsigned integer type." },
+    BuiltinType { name: "f32", description: "The 32-bit floating point type." },
+    BuiltinType { name: "f64", description: "The 64-bit floating point type." },
+];
+
+impl BuiltinType {
+    /// The builtin type referred to by `name_ref`, if it is the first segment
+    /// of a path, like in `u32` or `u32::max_value()`. The caller should check
+    /// that the name does not resolve to an item first, as primitive types
+    /// can be shadowed.
+    pub(crate) fn from_name_ref(name_ref: &ast::NameRef) -> Option<&'static BuiltinType> {
+        let segment = name_ref.syntax().parent().and_then(ast::PathSegment::cast)?;
+        let path = segment.syntax().parent().and_then(ast::Path::cast)?;
+        if path.qualifier().is_some() {
+            return None;
+        }
+        let name = name_ref.text();
+        BUILTIN_TYPES.iter().find(|it| it.name == name.as_str())
+    }
+
+    /// The markdown for hovers.
+    pub(crate) fn hover_text(&self) -> String {
+        format!("```rust\n{}\n```\n\n{}", self.name, self.description)
+    }
+
+    /// The documentation module of the type in the standard library, like
+    /// `#[doc(primitive = "u32")] mod prim_u32 {}`, if `std` is indexed.
+    pub(crate) fn navigation_target(
+        &self,
+        db: &RootDatabase,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let mut query = Query::new(format!("prim_{}", self.name));
+        query.exact();
+        query.libraries(true);
+        query.limit(4);
+        let res = symbol_index::world_symbols(db, query)?
+            .value
+            .into_iter()
+            .find(|it| it.ptr.kind() == MODULE)
+            .map(NavigationTarget::from_symbol);
+        Ok(res)
+    }
+}
@@ -4,11 +4,19 @@ mod completion_context;
 mod complete_dot;
 mod complete_fn_param;
 mod complete_keyword;
+mod complete_mod;
 mod complete_snippet;
 mod complete_path;
+mod complete_pattern;
 mod complete_scope;
+mod complete_trait_impl;
+mod complete_format;
+mod complete_constructor;
+mod complete_struct_fields;
+mod complete_import;
 
 use ra_db::SyntaxDatabase;
+use ra_ide_api_light::ImportStyle;
 
 use crate::{
     db,
@@ -19,7 +27,20 @@ use crate::{
     },
 };
 
-pub use crate::completion::completion_item::{CompletionItem, InsertText, CompletionItemKind};
+pub use crate::completion::{
+    completion_item::{CompletionItem, InsertText, CompletionItemKind},
+    complete_snippet::{UserSnippet, SnippetScope},
+};
+
+/// User settings for completion.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionConfig {
+    /// Snippets defined by the user, offered next to the built-in ones.
+    pub user_snippets: Vec<UserSnippet>,
+    /// How the completions which need an import, like trait methods or the
+    /// items which are not in scope, add it.
+    pub import_style: ImportStyle,
+}
 
 /// Main entry point for completion. We run completion as a two-phase process.
 ///
@@ -46,6 +67,7 @@ pub use crate::completion::completion_item::{CompletionItem, InsertText, Complet
 pub(crate) fn completions(
     db: &db::RootDatabase,
     position: FilePosition,
+    config: &CompletionConfig,
 ) -> Cancelable<Option<Completions>> {
     let original_file = db.source_file(position.file_id);
     let ctx = ctry!(CompletionContext::new(db, &original_file, position)?);
@@ -53,13 +75,24 @@ pub(crate) fn completions(
     let mut acc = Completions::default();
 
     complete_fn_param::complete_fn_param(&mut acc, &ctx);
+    complete_keyword::complete_item_keyword(&mut acc, &ctx);
+    complete_keyword::complete_type_keyword(&mut acc, &ctx);
+    complete_keyword::complete_pat_keyword(&mut acc, &ctx);
     complete_keyword::complete_expr_keyword(&mut acc, &ctx);
     complete_keyword::complete_use_tree_keyword(&mut acc, &ctx);
     complete_snippet::complete_expr_snippet(&mut acc, &ctx);
     complete_snippet::complete_item_snippet(&mut acc, &ctx);
+    complete_snippet::complete_user_snippet(&mut acc, &ctx, config);
     complete_path::complete_path(&mut acc, &ctx)?;
+    complete_pattern::complete_pattern(&mut acc, &ctx)?;
     complete_scope::complete_scope(&mut acc, &ctx)?;
-    complete_dot::complete_dot(&mut acc, &ctx)?;
+    complete_dot::complete_dot(&mut acc, &ctx, config.import_style)?;
+    complete_trait_impl::complete_trait_impl(&mut acc, &ctx)?;
+    complete_mod::complete_mod(&mut acc, &ctx)?;
+    complete_format::complete_format_arg(&mut acc, &ctx)?;
+    complete_constructor::complete_constructor(&mut acc, &ctx)?;
+    complete_struct_fields::complete_struct_fields(&mut acc, &ctx)?;
+    complete_import::complete_import(&mut acc, &ctx, config.import_style)?;
 
     Ok(Some(acc))
 }
@@ -72,6 +105,8 @@ fn check_completion(code: &str, expected_completions: &str, kind: CompletionKind
     } else {
         single_file_with_position(code)
     };
-    let completions = completions(&analysis.db, position).unwrap().unwrap();
+    let completions = completions(&analysis.db, position, &CompletionConfig::default())
+        .unwrap()
+        .unwrap_or_default();
     completions.assert_match(expected_completions, kind);
 }
@@ -0,0 +1,123 @@
+use hir::{Ty, Def, Name, VariantData};
+
+use crate::Cancelable;
+use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
+
+/// Completes the ways to construct a value of the expected type: the variants
+/// of an enum, like `Some(..)` or `Shape::Circle { .. }`, and `vec![]`.
+pub(super) fn complete_constructor(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+) -> Cancelable<()> {
+    let (def_id, name) = match &ctx.expected_type {
+        Some(Ty::Adt { def_id, name }) => (*def_id, name),
+        _ => return Ok(()),
+    };
+    match def_id.resolve(ctx.db)? {
+        Def::Enum(e) => {
+            for (variant_name, variant) in e.variants(ctx.db)? {
+                let label = if is_prelude_enum(name) {
+                    variant_name.to_string()
+                } else {
+                    format!("{}::{}", name, variant_name)
+                };
+                let snippet = match &*variant.variant_data(ctx.db)? {
+                    _ if ctx.is_call => None,
+                    VariantData::Unit => None,
+                    VariantData::Tuple(_) => Some(format!("{}($0)", label)),
+                    VariantData::Struct(_) => Some(format!("{} {{ $0 }}", label)),
+                };
+                let item = CompletionItem::new(CompletionKind::Magic, label)
+                    .kind(CompletionItemKind::EnumVariant)
+                    .matches_expected_type();
+                let item = match snippet {
+                    Some(snippet) => item.snippet(snippet),
+                    None => item,
+                };
+                item.add_to(acc);
+            }
+        }
+        Def::Struct(_) if name.to_string() == "Vec" => {
+            CompletionItem::new(CompletionKind::Magic, "vec![]")
+                .lookup_by("vec")
+                .snippet("vec![$0]")
+                .kind(CompletionItemKind::Snippet)
+                .matches_expected_type()
+                .add_to(acc);
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// The variants of these are imported by the prelude, so they are written
+/// without the name of the enum.
+fn is_prelude_enum(name: &Name) -> bool {
+    let name = name.to_string();
+    name == "Option" || name == "Result"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_magic_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Magic);
+    }
+
+    #[test]
+    fn completes_prelude_variants_for_let_type() {
+        check_magic_completion(
+            r"
+            enum Option<T> { Some(T), None }
+            fn foo() {
+                let x: Option<u32> = <|>
+            }
+            ",
+            r#"Some "Some($0)" MATCHES_EXPECTED_TYPE
+               None MATCHES_EXPECTED_TYPE"#,
+        );
+    }
+
+    #[test]
+    fn completes_variants_for_return_type() {
+        check_magic_completion(
+            r"
+            enum Shape { Circle { radius: f64 }, Square(f64), Empty }
+            fn make() -> Shape {
+                <|>
+            }
+            ",
+            r#"Shape::Circle "Shape::Circle { $0 }" MATCHES_EXPECTED_TYPE
+               Shape::Square "Shape::Square($0)" MATCHES_EXPECTED_TYPE
+               Shape::Empty MATCHES_EXPECTED_TYPE"#,
+        );
+    }
+
+    #[test]
+    fn completes_vec_for_argument() {
+        check_magic_completion(
+            r"
+            struct Vec<T>;
+            fn consume(items: Vec<u32>) {}
+            fn foo() {
+                consume(<|>)
+            }
+            ",
+            r#"vec "vec![]" "vec![$0]" MATCHES_EXPECTED_TYPE"#,
+        );
+    }
+
+    #[test]
+    fn no_constructors_without_expected_type() {
+        check_magic_completion(
+            r"
+            enum Shape { Square(f64), Empty }
+            fn foo() {
+                let x = <|>
+            }
+            ",
+            r#""#,
+        );
+    }
+}
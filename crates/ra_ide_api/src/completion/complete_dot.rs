@@ -1,10 +1,25 @@
-use hir::{Ty, Def};
+use hir::{Ty, Def, Function};
+use ra_db::SyntaxDatabase;
+use ra_syntax::{AstNode, ast, SyntaxKind::{ATTR, COMMENT, SEMI}};
+use ra_ide_api_light::{insert_use, ImportStyle};
 
-use crate::Cancelable;
+use crate::{Cancelable, imports::path_to_module};
 use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
 
+/// The traits of the std prelude, which are always in scope.
+const STD_PRELUDE_TRAITS: &[&str] = &[
+    "AsMut", "AsRef", "Clone", "Copy", "Default", "DoubleEndedIterator", "Drop", "Eq",
+    "ExactSizeIterator", "Extend", "Fn", "FnMut", "FnOnce", "From", "FromIterator", "Into",
+    "IntoIterator", "Iterator", "Ord", "PartialEq", "PartialOrd", "Send", "Sized", "Sync",
+    "ToOwned", "ToString",
+];
+
 /// Complete dot accesses, i.e. fields or methods (currently only fields).
-pub(super) fn complete_dot(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
+pub(super) fn complete_dot(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    import_style: ImportStyle,
+) -> Cancelable<()> {
     let (function, receiver) = match (&ctx.function, ctx.dot_receiver) {
         (Some(function), Some(receiver)) => (function, receiver),
         _ => return Ok(()),
@@ -19,7 +34,7 @@ pub(super) fn complete_dot(acc: &mut Completions, ctx: &CompletionContext) -> Ca
     if !ctx.is_call {
         complete_fields(acc, ctx, receiver_ty.clone())?;
     }
-    complete_methods(acc, ctx, receiver_ty)?;
+    complete_methods(acc, ctx, receiver_ty, import_style)?;
     Ok(())
 }
 
@@ -44,9 +59,10 @@ fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty)
                 }
             }
             Ty::Tuple(fields) => {
-                for (i, _ty) in fields.iter().enumerate() {
+                for (i, ty) in fields.iter().enumerate() {
                     CompletionItem::new(CompletionKind::Reference, i.to_string())
                         .kind(CompletionItemKind::Field)
+                        .detail(ty.to_string())
                         .add_to(acc);
                 }
             }
@@ -60,23 +76,79 @@ fn complete_methods(
     acc: &mut Completions,
     ctx: &CompletionContext,
     receiver: Ty,
+    import_style: ImportStyle,
 ) -> Cancelable<()> {
     receiver.iterate_methods(ctx.db, |func| {
         let sig = func.signature(ctx.db);
         if sig.has_self_param() {
-            CompletionItem::new(CompletionKind::Reference, sig.name().to_string())
-                .from_function(ctx, func)
-                .kind(CompletionItemKind::Method)
-                .add_to(acc);
+            let (_, fn_def) = func.source(ctx.db)?;
+            let mut builder =
+                CompletionItem::new(CompletionKind::Reference, sig.name().to_string())
+                    .from_function(ctx, func.clone())
+                    .kind(CompletionItemKind::Method)
+                    .detail(function_label(&fn_def));
+            // The method can't be called without its trait in scope, so the
+            // trait is imported together with the call.
+            if let Some(path) = missing_trait_import(ctx, &func)? {
+                let file = ctx.db.source_file(ctx.file_id);
+                let edit = insert_use(&file, ctx.offset, &[path.clone()], import_style);
+                builder = builder
+                    .documentation(format!("Imports `{}`", path))
+                    .set_additional_text_edit(edit);
+            }
+            builder.add_to(acc);
         }
         Ok(None::<()>)
     })?;
     Ok(())
 }
 
+/// The signature of the function, without its attributes, docs and body.
+fn function_label(fn_def: &ast::FnDef) -> String {
+    let body_range = fn_def.body().map(|it| it.syntax().range());
+    let label: String = fn_def
+        .syntax()
+        .children()
+        .filter(|child| match child.kind() {
+            ATTR | COMMENT | SEMI => false,
+            _ => body_range.map_or(true, |range| child.range() != range),
+        })
+        .map(|child| child.text().to_string())
+        .collect();
+    label.trim().to_string()
+}
+
+/// The path of the trait which provides the `method`, if the trait is not in
+/// the scope of the current module.
+fn missing_trait_import(ctx: &CompletionContext, method: &Function) -> Cancelable<Option<String>> {
+    let trait_ = match method.impl_trait(ctx.db)? {
+        Some(it) => it,
+        None => ctry!(method.containing_trait(ctx.db)?),
+    };
+    let module = ctry!(ctx.module.clone());
+    let in_scope = module
+        .scope(ctx.db)?
+        .entries()
+        .any(|(_, res)| res.def_id.types == Some(trait_.def_id()));
+    if in_scope {
+        return Ok(None);
+    }
+    let name = ctry!(trait_.name(ctx.db)?).to_string();
+    let trait_module = trait_.module(ctx.db)?;
+    let trait_krate = ctry!(trait_module.krate(ctx.db)?);
+    let krate = ctry!(module.krate(ctx.db)?);
+    if trait_krate != krate && STD_PRELUDE_TRAITS.contains(&name.as_str()) {
+        return Ok(None);
+    }
+    let module_path = ctry!(path_to_module(ctx.db, &module, &trait_module)?);
+    Ok(Some(format!("{}::{}", module_path, name)))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::completion::*;
+    use test_utils::assert_eq_text;
+
+    use crate::{completion::*, mock_analysis::single_file_with_position};
 
     fn check_ref_completion(code: &str, expected_completions: &str) {
         check_completion(code, expected_completions, CompletionKind::Reference);
@@ -107,7 +179,7 @@ mod tests {
             }
             ",
             r#"the_field "(u32,)"
-               foo "foo($0)""#,
+               foo "fn foo(self)" "foo($0)""#,
         );
     }
 
@@ -123,7 +195,40 @@ mod tests {
             }
             ",
             r#"the_field "(u32, i32)"
-               foo "foo($0)""#,
+               foo "fn foo(&self)" "foo($0)""#,
+        );
+    }
+
+    #[test]
+    fn test_self_completion_in_impl() {
+        check_ref_completion(
+            r"
+            struct A { the_field: u32 }
+            impl A {
+                fn new() -> Self { A { the_field: 0 } }
+                fn the_method(&self) {}
+                fn foo(&self) {
+                    self.<|>
+                }
+            }
+            ",
+            r#"the_field "u32"
+               the_method "fn the_method(&self)" "the_method($0)"
+               foo "fn foo(&self)" "foo($0)""#,
+        );
+    }
+
+    #[test]
+    fn test_tuple_field_completion() {
+        check_ref_completion(
+            r"
+            fn foo(b: (u32, &str)) {
+               let c = (b, 92u8);
+               c.<|>
+            }
+            ",
+            r#"0 "(u32, &str)"
+               1 "u8""#,
         );
     }
 
@@ -152,7 +257,7 @@ mod tests {
                a.<|>
             }
             ",
-            r#"the_method "the_method($0)""#,
+            r#"the_method "fn the_method(&self)" "the_method($0)""#,
         );
     }
 
@@ -171,4 +276,100 @@ mod tests {
             r#""#,
         );
     }
+
+    #[test]
+    fn test_trait_method_completion_imports_trait() {
+        check_ref_completion(
+            r"
+            struct A {}
+            mod shapes {
+                pub trait Shape {
+                    fn area(&self) -> f64;
+                }
+                impl Shape for super::A {
+                    fn area(&self) -> f64 { 0.0 }
+                }
+            }
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"area "fn area(&self) -> f64" "area($0)""#,
+        );
+        check_ref_completion(
+            r"
+            struct A {}
+            mod shapes {
+                pub trait Shape {
+                    fn area(&self) -> f64;
+                }
+                impl Shape for super::A {
+                    fn area(&self) -> f64 { 0.0 }
+                }
+            }
+            use self::shapes::Shape;
+            fn foo(a: A) {
+               a.<|>
+            }
+            ",
+            r#"area "fn area(&self) -> f64" "area($0)""#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_completion_import_edit() {
+        let (analysis, position) = single_file_with_position(
+            "
+mod shapes {
+    pub trait Shape { fn area(&self) -> f64; }
+    impl Shape for u32 { fn area(&self) -> f64 { 0.0 } }
+}
+struct A;
+impl shapes::Shape for A { fn area(&self) -> f64 { 0.0 } }
+fn foo(a: A) { a.<|> }
+",
+        );
+        let items: Vec<CompletionItem> = completions(
+            &analysis.db,
+            position,
+            &CompletionConfig::default(),
+        )
+        .unwrap()
+        .unwrap()
+        .into();
+        let item = items.iter().find(|it| it.label() == "area").unwrap();
+        assert_eq!(item.detail(), Some("fn area(&self) -> f64"));
+        assert_eq!(item.documentation(), Some("Imports `crate::shapes::Shape`"));
+        let text = analysis.file_text(position.file_id);
+        assert_eq_text!(
+            "
+use crate::shapes::Shape;
+
+mod shapes {
+    pub trait Shape { fn area(&self) -> f64; }
+    impl Shape for u32 { fn area(&self) -> f64 { 0.0 } }
+}
+struct A;
+impl shapes::Shape for A { fn area(&self) -> f64 { 0.0 } }
+fn foo(a: A) { a. }
+",
+            &item.additional_text_edit().unwrap().apply(&text)
+        );
+    }
+
+    #[test]
+    fn test_trait_bound_method_completion() {
+        check_ref_completion(
+            r"
+            trait Shape {
+                fn area(&self) -> f64;
+                fn new() -> Self;
+            }
+            fn foo(a: impl Shape, b: &dyn Shape) {
+               a.<|>
+            }
+            ",
+            r#"area "fn area(&self) -> f64" "area($0)""#,
+        );
+    }
 }
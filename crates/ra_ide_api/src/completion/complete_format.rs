@@ -0,0 +1,79 @@
+use crate::{Cancelable, format_string::explicit_names};
+use crate::completion::{
+    CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind,
+    complete_scope::complete_fn,
+};
+
+/// Inside a placeholder of a format string, like `format!("{<|>}", foo = 92)`,
+/// completes the named arguments of the macro call. Local variables are
+/// offered as well, in anticipation of inline format args.
+pub(super) fn complete_format_arg(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
+    let macro_call = match ctx.format_macro_call {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    for name in explicit_names(macro_call) {
+        CompletionItem::new(CompletionKind::Reference, name.to_string())
+            .kind(CompletionItemKind::Binding)
+            .add_to(acc);
+    }
+    if let Some(function) = &ctx.function {
+        let scopes = function.scopes(ctx.db)?;
+        complete_fn(acc, &scopes, ctx.offset);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_reference_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Reference);
+    }
+
+    #[test]
+    fn completes_named_args_and_locals_in_format_string() {
+        check_reference_completion(
+            r#"
+            fn foo(x: i32) {
+                let y = 1;
+                format!("{} {<|>}", x, width = 10, precision = y);
+            }
+            "#,
+            "width;precision;y;x",
+        );
+    }
+
+    #[test]
+    fn completes_format_string_of_write() {
+        check_reference_completion(
+            r#"
+            fn foo(f: i32) {
+                writeln!(f, "{na<|>}", name = 92);
+            }
+            "#,
+            "name;f",
+        );
+    }
+
+    #[test]
+    fn no_completion_outside_of_placeholders() {
+        check_reference_completion(
+            r#"
+            fn foo(x: i32) {
+                format!("{{<|>}}", name = 92);
+            }
+            "#,
+            "",
+        );
+        check_reference_completion(
+            r#"
+            fn foo(x: i32) {
+                println!("x = <|>", name = 92);
+            }
+            "#,
+            "",
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use ra_db::SyntaxDatabase;
+use ra_ide_api_light::{insert_use, ImportStyle};
+use ra_syntax::SyntaxKind::IDENT;
+
+use crate::{
+    Cancelable,
+    completion::{CompletionItem, Completions, CompletionKind, CompletionContext},
+    imports::import_candidates,
+};
+
+/// The number of characters of the name which must be typed before the items
+/// of other modules are offered, otherwise there are too many of them.
+const MIN_PREFIX_LEN: usize = 2;
+
+/// Completes the items of the other modules of the crate which are not in
+/// scope, like `HashMap` of `crate::collections`, together with an import.
+pub(super) fn complete_import(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    import_style: ImportStyle,
+) -> Cancelable<()> {
+    if !ctx.is_trivial_path || ctx.use_item_syntax.is_some() {
+        return Ok(());
+    }
+    let module = match &ctx.module {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    if ctx.leaf.kind() != IDENT {
+        return Ok(());
+    }
+    let prefix = match ctx.leaf.leaf_text() {
+        Some(text) => {
+            let len = (ctx.offset - ctx.leaf.range().start()).to_usize();
+            text.as_str()[..len].to_string()
+        }
+        None => return Ok(()),
+    };
+    if prefix.chars().count() < MIN_PREFIX_LEN {
+        return Ok(());
+    }
+    let candidates = import_candidates(ctx.db, module, |name| {
+        name.to_string().starts_with(prefix.as_str())
+    })?;
+    let file = ctx.db.source_file(ctx.file_id);
+    for candidate in candidates {
+        let edit = insert_use(&file, ctx.offset, &[candidate.path.clone()], import_style);
+        CompletionItem::new(CompletionKind::Import, candidate.name.to_string())
+            .from_resolution(ctx, &candidate.resolution)
+            .documentation(format!("Imports `{}`", candidate.path))
+            .set_additional_text_edit(edit)
+            .add_to(acc);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::{completion::*, mock_analysis::single_file_with_position};
+
+    fn check_import_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Import);
+    }
+
+    #[test]
+    fn completes_public_items_of_other_modules() {
+        check_import_completion(
+            r"
+            mod collections {
+                pub struct HashMap;
+                pub struct HashSet;
+                struct Hidden;
+            }
+            mod hash {
+                pub struct Hasher;
+            }
+            fn foo() { let x = Has<|> }
+            ",
+            "HashMap;HashSet;Hasher",
+        );
+    }
+
+    #[test]
+    fn skips_items_in_scope_and_short_prefixes() {
+        check_import_completion(
+            r"
+            mod collections {
+                pub struct HashMap;
+            }
+            use self::collections::HashMap;
+            fn foo() { let x = Has<|> }
+            ",
+            "",
+        );
+        check_import_completion(
+            r"
+            mod collections {
+                pub struct HashMap;
+            }
+            fn foo() { let x = H<|> }
+            ",
+            "",
+        );
+    }
+
+    #[test]
+    fn completion_imports_the_item() {
+        let (analysis, position) = single_file_with_position(
+            "
+mod collections {
+    pub struct HashMap;
+}
+fn foo() { let x = HashM<|> }
+",
+        );
+        let items: Vec<CompletionItem> = completions(
+            &analysis.db,
+            position,
+            &CompletionConfig::default(),
+        )
+        .unwrap()
+        .unwrap()
+        .into();
+        let item = items.iter().find(|it| it.label() == "HashMap").unwrap();
+        assert_eq!(item.documentation(), Some("Imports `crate::collections::HashMap`"));
+        let text = analysis.file_text(position.file_id);
+        assert_eq_text!(
+            "
+use crate::collections::HashMap;
+
+mod collections {
+    pub struct HashMap;
+}
+fn foo() { let x = HashM }
+",
+            &item.additional_text_edit().unwrap().apply(&text)
+        );
+    }
+}
@@ -46,8 +46,45 @@ fn keyword(kw: &str, snippet: &str) -> CompletionItem {
         .build()
 }
 
+pub(super) fn complete_item_keyword(acc: &mut Completions, ctx: &CompletionContext) {
+    if !ctx.is_new_item {
+        return;
+    }
+    let keywords: &[&str] = if ctx.is_trait_item {
+        &["fn", "const", "type", "unsafe"]
+    } else if ctx.is_impl_item {
+        &["pub", "fn", "const", "type", "unsafe"]
+    } else {
+        &[
+            "pub", "fn", "struct", "enum", "trait", "impl", "mod", "use", "const", "static",
+            "type", "unsafe", "extern",
+        ]
+    };
+    for kw in keywords {
+        acc.add(keyword(kw, &format!("{} $0", kw)));
+    }
+}
+
+pub(super) fn complete_type_keyword(acc: &mut Completions, ctx: &CompletionContext) {
+    if !ctx.is_type_position {
+        return;
+    }
+    if ctx.after_amp {
+        acc.add(keyword("mut", "mut $0"));
+    }
+    acc.add(keyword("dyn", "dyn $0"));
+}
+
+pub(super) fn complete_pat_keyword(acc: &mut Completions, ctx: &CompletionContext) {
+    if !ctx.is_pat_position {
+        return;
+    }
+    acc.add(keyword("mut", "mut $0"));
+    acc.add(keyword("ref", "ref $0"));
+}
+
 pub(super) fn complete_expr_keyword(acc: &mut Completions, ctx: &CompletionContext) {
-    if !ctx.is_trivial_path {
+    if !ctx.is_trivial_path || ctx.is_type_position {
         return;
     }
 
@@ -74,6 +111,9 @@ pub(super) fn complete_expr_keyword(acc: &mut Completions, ctx: &CompletionConte
         }
     }
     acc.add_all(complete_return(fn_def, ctx.can_be_stmt));
+    if ctx.after_amp {
+        acc.add(keyword("mut", "mut $0"));
+    }
 }
 
 fn is_in_loop_body(leaf: &SyntaxNode) -> bool {
@@ -112,6 +152,18 @@ mod tests {
         check_completion(code, expected_completions, CompletionKind::Keyword);
     }
 
+    #[test]
+    fn no_keywords_after_float_literal() {
+        check_keyword_completion(
+            r"
+            fn quux() -> f64 {
+                1.<|>
+            }
+            ",
+            r#""#,
+        );
+    }
+
     #[test]
     fn completes_keywords_in_use_stmt() {
         check_keyword_completion(
@@ -336,4 +388,113 @@ mod tests {
             "#,
         )
     }
+
+    #[test]
+    fn completes_item_keywords() {
+        check_keyword_completion(
+            r"
+            use foo;
+            <|>
+            ",
+            r#"
+            pub "pub $0"
+            fn "fn $0"
+            struct "struct $0"
+            enum "enum $0"
+            trait "trait $0"
+            impl "impl $0"
+            mod "mod $0"
+            use "use $0"
+            const "const $0"
+            static "static $0"
+            type "type $0"
+            unsafe "unsafe $0"
+            extern "extern $0"
+            "#,
+        );
+    }
+
+    #[test]
+    fn completes_trait_and_impl_item_keywords() {
+        check_keyword_completion(
+            r"
+            trait T { <|> }
+            ",
+            r#"
+            fn "fn $0"
+            const "const $0"
+            type "type $0"
+            unsafe "unsafe $0"
+            "#,
+        );
+        check_keyword_completion(
+            r"
+            impl S { <|> }
+            ",
+            r#"
+            pub "pub $0"
+            fn "fn $0"
+            const "const $0"
+            type "type $0"
+            unsafe "unsafe $0"
+            "#,
+        );
+    }
+
+    #[test]
+    fn completes_only_type_keywords_in_type_position() {
+        check_keyword_completion(
+            r"
+            fn quux() {
+                let x: <|>
+            }
+            ",
+            r#"
+            dyn "dyn $0"
+            "#,
+        );
+        check_keyword_completion(
+            r"
+            fn quux(x: &<|>) {}
+            ",
+            r#"
+            mut "mut $0"
+            dyn "dyn $0"
+            "#,
+        );
+    }
+
+    #[test]
+    fn completes_mut_after_amp_in_expr() {
+        check_keyword_completion(
+            r"
+            fn quux() {
+                let x = &<|>;
+            }
+            ",
+            r#"
+            if "if $0 {}"
+            match "match $0 {}"
+            while "while $0 {}"
+            loop "loop {$0}"
+            return "return"
+            mut "mut $0"
+            "#,
+        );
+    }
+
+    #[test]
+    fn completes_binding_modifiers_in_patterns() {
+        check_keyword_completion(
+            r"
+            fn quux() {
+                let <|>
+            }
+            ",
+            r#"
+            mut "mut $0"
+            ref "ref $0"
+            "#,
+        );
+    }
 }
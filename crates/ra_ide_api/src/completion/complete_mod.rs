@@ -0,0 +1,117 @@
+use ra_db::FilesDatabase;
+use relative_path::{RelativePath, RelativePathBuf};
+use hir::source_binder;
+
+use crate::Cancelable;
+use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
+
+/// Completes `mod <|>` with the names of `.rs` files and directories next to
+/// the current module which are not linked into the module tree yet.
+pub(super) fn complete_mod(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
+    if !ctx.is_mod_decl {
+        return Ok(());
+    }
+    let path = ctx.db.file_relative_path(ctx.file_id);
+    let dir = module_dir(&path);
+    let source_root = ctx.db.source_root(ctx.db.file_source_root(ctx.file_id));
+    for (candidate, &file_id) in source_root.files.iter() {
+        if file_id == ctx.file_id {
+            continue;
+        }
+        let name = match child_module_name(&dir, candidate) {
+            Some(it) => it,
+            None => continue,
+        };
+        let module = match source_binder::module_from_file_id(ctx.db, file_id)? {
+            Some(it) => it,
+            None => continue,
+        };
+        // Files which are not reachable via `mod` declarations become roots
+        // of the module tree.
+        if module.parent(ctx.db)?.is_some() {
+            continue;
+        }
+        CompletionItem::new(CompletionKind::Magic, name)
+            .kind(CompletionItemKind::Module)
+            .add_to(acc);
+    }
+    Ok(())
+}
+
+/// The directory where `mod foo;` declared in the file at `path` looks for
+/// `foo.rs` and `foo/mod.rs`.
+fn module_dir(path: &RelativePath) -> RelativePathBuf {
+    let root = RelativePathBuf::default();
+    let parent = path.parent().unwrap_or(&root);
+    match path.file_stem() {
+        Some("mod") | Some("lib") | Some("main") | None => parent.to_relative_path_buf(),
+        Some(stem) => parent.join(stem),
+    }
+}
+
+/// Returns `foo` if `path` is `dir/foo.rs` or `dir/foo/mod.rs`.
+fn child_module_name(dir: &RelativePath, path: &RelativePath) -> Option<String> {
+    if path.extension() != Some("rs") {
+        return None;
+    }
+    let parent = path.parent()?;
+    let name = if parent == dir {
+        path.file_stem()?
+    } else if path.file_stem() == Some("mod") && parent.parent() == Some(dir) {
+        parent.file_name()?
+    } else {
+        return None;
+    };
+    if name == "mod" || name == "lib" || name == "main" || !is_ident(name) {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_alphabetic() => (),
+        _ => return false,
+    }
+    chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_magic_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Magic);
+    }
+
+    #[test]
+    fn completes_unlinked_sibling_files() {
+        check_magic_completion(
+            r"
+            //- /lib.rs
+            mod foo;
+            mod <|>
+            //- /foo.rs
+            //- /bar.rs
+            //- /baz/qux.rs
+            ",
+            r#"bar"#,
+        );
+    }
+
+    #[test]
+    fn completes_unlinked_directories() {
+        check_magic_completion(
+            r"
+            //- /lib.rs
+            mod foo;
+            //- /foo.rs
+            mod <|>
+            //- /foo/bar/mod.rs
+            //- /foo/baz/qux.rs
+            ",
+            r#"bar"#,
+        );
+    }
+}
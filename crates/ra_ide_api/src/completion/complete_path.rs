@@ -1,3 +1,5 @@
+use ra_syntax::ast::NameOwner;
+
 use crate::{
     Cancelable,
     completion::{CompletionItem, CompletionItemKind, Completions, CompletionKind, CompletionContext},
@@ -8,6 +10,12 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) -> C
         (Some(path), Some(module)) => (path.clone(), module),
         _ => return Ok(()),
     };
+    if path.is_self_type() {
+        if let Some(impl_block) = &ctx.impl_block {
+            complete_self_type(acc, ctx, impl_block)?;
+        }
+        return Ok(());
+    }
     let def_id = match module.resolve_path(ctx.db, &path)?.take_types() {
         Some(it) => it,
         None => return Ok(()),
@@ -16,25 +24,74 @@ pub(super) fn complete_path(acc: &mut Completions, ctx: &CompletionContext) -> C
         hir::Def::Module(module) => {
             let module_scope = module.scope(ctx.db)?;
             for (name, res) in module_scope.entries() {
-                CompletionItem::new(CompletionKind::Reference, name.to_string())
+                let name = name.to_string();
+                if ctx.use_tree_siblings.contains(&name) {
+                    continue;
+                }
+                CompletionItem::new(CompletionKind::Reference, name)
                     .from_resolution(ctx, res)
                     .add_to(acc);
             }
         }
-        hir::Def::Enum(e) => {
-            e.variants(ctx.db)?
-                .into_iter()
-                .for_each(|(variant_name, _variant)| {
-                    CompletionItem::new(CompletionKind::Reference, variant_name.to_string())
-                        .kind(CompletionItemKind::EnumVariant)
-                        .add_to(acc)
-                });
-        }
+        hir::Def::Enum(e) => complete_enum_variants(acc, ctx, e)?,
         _ => return Ok(()),
     };
     Ok(())
 }
 
+/// Completes `Self::` inside an impl block: variants of the self type, if it
+/// is an enum, and its associated functions and consts.
+fn complete_self_type(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    impl_block: &hir::ImplBlock,
+) -> Cancelable<()> {
+    let ty = impl_block.target_ty(ctx.db)?;
+    if let hir::Ty::Adt { def_id, .. } = &ty {
+        if let hir::Def::Enum(e) = def_id.resolve(ctx.db)? {
+            complete_enum_variants(acc, ctx, e)?;
+        }
+    }
+    ty.iterate_impl_items(ctx.db, |item| {
+        match item {
+            hir::ImplItem::Method(func) => {
+                let sig = func.signature(ctx.db);
+                CompletionItem::new(CompletionKind::Reference, sig.name().to_string())
+                    .from_function(ctx, func)
+                    .add_to(acc);
+            }
+            hir::ImplItem::Const(def_id) => {
+                if let hir::Def::Const(konst) = def_id.resolve(ctx.db)? {
+                    let (_, node) = konst.source(ctx.db)?;
+                    if let Some(name) = node.name() {
+                        CompletionItem::new(CompletionKind::Reference, name.text().to_string())
+                            .kind(CompletionItemKind::Const)
+                            .add_to(acc);
+                    }
+                }
+            }
+            hir::ImplItem::Type(_) => (),
+        }
+        Ok(None::<()>)
+    })?;
+    Ok(())
+}
+
+fn complete_enum_variants(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    e: hir::Enum,
+) -> Cancelable<()> {
+    e.variants(ctx.db)?
+        .into_iter()
+        .for_each(|(variant_name, _variant)| {
+            CompletionItem::new(CompletionKind::Reference, variant_name.to_string())
+                .kind(CompletionItemKind::EnumVariant)
+                .add_to(acc)
+        });
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::completion::{CompletionKind, check_completion};
@@ -67,7 +124,78 @@ mod tests {
             //- /foo.rs
             use crate::Sp<|>
             ",
-            "Spam;foo",
+            r#"Spam;foo "foo::$0" TRIGGERS_COMPLETION"#,
+        );
+    }
+
+    #[test]
+    fn completes_expr_path_starting_with_crate() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod foo;
+            struct Spam;
+            //- /foo.rs
+            fn bar() { crate::Sp<|> }
+            ",
+            r#"Spam;foo "foo::$0" TRIGGERS_COMPLETION"#,
+        );
+    }
+
+    #[test]
+    fn completes_path_starting_with_super() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod foo;
+            struct Spam;
+            //- /foo.rs
+            fn baz() { super::<|> }
+            ",
+            r#"Spam;foo "foo::$0" TRIGGERS_COMPLETION"#,
+        );
+    }
+
+    #[test]
+    fn completes_path_starting_with_chained_super() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod foo;
+            struct Spam;
+            //- /foo/mod.rs
+            pub mod bar;
+            //- /foo/bar.rs
+            fn baz() { super::super::<|> }
+            ",
+            r#"Spam;foo "foo::$0" TRIGGERS_COMPLETION"#,
+        );
+    }
+
+    #[test]
+    fn completes_path_starting_with_self() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod m {
+                struct Spam;
+                fn foo() { self::<|> }
+            }
+            ",
+            r#"Spam;foo "foo()$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_items_from_dependencies() {
+        check_reference_completion(
+            "
+            //- /main.rs crate:main deps:other
+            use other::<|>
+            //- /other/lib.rs crate:other
+            pub struct Spam;
+            ",
+            "Spam",
         );
     }
 
@@ -81,7 +209,7 @@ mod tests {
             //- /foo.rs
             use crate::{Sp<|>};
             ",
-            "Spam;foo",
+            r#"Spam;foo "foo::$0" TRIGGERS_COMPLETION"#,
         );
     }
 
@@ -103,6 +231,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn completes_items_not_yet_imported_in_use_tree() {
+        check_reference_completion(
+            "
+            //- /lib.rs
+            mod m {
+                pub mod inner {}
+                pub struct Foo;
+            }
+            use crate::m::{Foo, <|>};
+            ",
+            r#"inner "inner::$0" TRIGGERS_COMPLETION"#,
+        );
+    }
+
     #[test]
     fn completes_enum_variant() {
         check_reference_completion(
@@ -140,4 +283,70 @@ mod tests {
             "main;frobnicate",
         )
     }
+
+    #[test]
+    fn marks_deprecated_functions() {
+        check_reference_completion(
+            r"
+            mod m {
+                #[deprecated]
+                pub fn old() {}
+            }
+            fn foo() { m::<|> }
+            ",
+            r#"old "old()$0" DEPRECATED"#,
+        );
+    }
+
+    #[test]
+    fn marks_deprecated_items() {
+        for (item, expected) in &[
+            ("pub struct Old;", "Old DEPRECATED"),
+            ("pub enum Old {}", "Old DEPRECATED"),
+            ("pub trait Old {}", "Old DEPRECATED"),
+            ("pub const OLD: u32 = 0;", "OLD DEPRECATED"),
+            ("pub static OLD: u32 = 0;", "OLD DEPRECATED"),
+            ("pub mod old {}", "old DEPRECATED"),
+        ] {
+            let code = format!(
+                "
+                mod m {{
+                    #[deprecated]
+                    {}
+                }}
+                fn foo() {{ m::<|> }}
+                ",
+                item
+            );
+            check_reference_completion(&code, expected);
+        }
+    }
+
+    #[test]
+    fn completes_self_type_items_in_impl() {
+        check_reference_completion(
+            r"
+            enum E { A, B }
+            impl E {
+                fn new() -> E { E::A }
+                fn foo(&self) { Self::<|> }
+            }
+            ",
+            r#"A;B;new "new()$0";foo "foo($0)""#,
+        );
+    }
+
+    #[test]
+    fn completes_self_type_consts_in_impl() {
+        check_reference_completion(
+            r"
+            struct S;
+            impl S {
+                const ANSWER: u32 = 92;
+                fn foo() -> u32 { Self::<|> }
+            }
+            ",
+            r#"ANSWER;foo "foo()$0""#,
+        );
+    }
 }
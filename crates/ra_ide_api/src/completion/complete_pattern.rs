@@ -0,0 +1,116 @@
+use hir::{Ty, Def, PerNs, VariantData};
+
+use crate::Cancelable;
+use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
+
+/// Completes variants of the matched enum, as well as structs and constants
+/// from the current module, in pattern position.
+pub(super) fn complete_pattern(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
+    if !ctx.is_pat_position {
+        return Ok(());
+    }
+    if let Some(Ty::Adt { def_id, .. }) = scrutinee_ty(ctx)? {
+        if let Def::Enum(e) = def_id.resolve(ctx.db)? {
+            let enum_name = e.name(ctx.db)?.map(|it| it.to_string()).unwrap_or_default();
+            for (variant_name, variant) in e.variants(ctx.db)? {
+                let label = format!("{}::{}", enum_name, variant_name);
+                let variant_data = variant.variant_data(ctx.db)?;
+                add_pattern(acc, label, CompletionItemKind::EnumVariant, &variant_data);
+            }
+        }
+    }
+    let module = match &ctx.module {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    for (name, res) in module.scope(ctx.db)?.entries() {
+        let resolved = res.def_id.and_then(|d| d.resolve(ctx.db).ok());
+        match resolved {
+            PerNs {
+                types: Some(Def::Struct(s)),
+                ..
+            } => {
+                let variant_data = s.variant_data(ctx.db)?;
+                add_pattern(acc, name.to_string(), CompletionItemKind::Struct, &variant_data);
+            }
+            PerNs {
+                values: Some(Def::Const(..)),
+                ..
+            } => {
+                CompletionItem::new(CompletionKind::Reference, name.to_string())
+                    .from_resolution(ctx, res)
+                    .add_to(acc);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn scrutinee_ty(ctx: &CompletionContext) -> Cancelable<Option<Ty>> {
+    let (function, scrutinee) = match (&ctx.function, ctx.pat_scrutinee) {
+        (Some(function), Some(scrutinee)) => (function, scrutinee),
+        _ => return Ok(None),
+    };
+    let infer_result = function.infer(ctx.db)?;
+    let syntax_mapping = function.body_syntax_mapping(ctx.db)?;
+    let expr = ctry!(syntax_mapping.node_expr(scrutinee));
+    Ok(Some(infer_result[expr].clone()))
+}
+
+/// Adds `Foo`, `Foo(..)` or `Foo { .. }`, depending on the shape of the data.
+fn add_pattern(
+    acc: &mut Completions,
+    label: String,
+    kind: CompletionItemKind,
+    variant_data: &VariantData,
+) {
+    let snippet = match variant_data {
+        VariantData::Unit => None,
+        VariantData::Tuple(_) => Some(format!("{}(..)$0", label)),
+        VariantData::Struct(_) => Some(format!("{} {{ .. }}$0", label)),
+    };
+    let item = CompletionItem::new(CompletionKind::Reference, label).kind(kind);
+    let item = match snippet {
+        Some(snippet) => item.snippet(snippet),
+        None => item,
+    };
+    item.add_to(acc);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_reference_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Reference);
+    }
+
+    #[test]
+    fn completes_enum_variants_in_match_arm() {
+        check_reference_completion(
+            r"
+            enum E { A, B(i32), C { x: i32 } }
+            fn foo(e: E) {
+                match e {
+                    <|>
+                }
+            }
+            ",
+            r#"E::A;E::B "E::B(..)$0";E::C "E::C { .. }$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_structs_in_patterns() {
+        check_reference_completion(
+            r"
+            struct S { x: i32 }
+            fn foo(s: S) {
+                let <|> = s;
+            }
+            ",
+            r#"S "S { .. }$0""#,
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use rustc_hash::FxHashSet;
 use ra_syntax::TextUnit;
+use hir::source_binder;
 
 use crate::{
     Cancelable,
@@ -14,15 +15,32 @@ pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) ->
         Some(it) => it,
         None => return Ok(()),
     };
+    let mut locals = FxHashSet::default();
     if let Some(function) = &ctx.function {
         let scopes = function.scopes(ctx.db)?;
-        complete_fn(acc, &scopes, ctx.offset);
+        locals = complete_fn(acc, &scopes, ctx.offset);
     }
 
+    // The items of the blocks around shadow the ones of the module.
+    let mut items = FxHashSet::default();
+    let block_scopes = source_binder::block_scopes_at(ctx.db, ctx.file_id, ctx.leaf)?;
+    block_scopes
+        .iter()
+        .flat_map(|scope| scope.entries())
+        .filter(|(name, _res)| items.insert((*name).clone()))
+        .filter(|(name, _res)| ctx.is_type_position || !locals.contains(*name))
+        .for_each(|(name, res)| {
+            CompletionItem::new(CompletionKind::Reference, name.to_string())
+                .from_resolution(ctx, res)
+                .add_to(acc)
+        });
+
     let module_scope = module.scope(ctx.db)?;
-    let (file_id, _) = module.definition_source(ctx.db)?;
     module_scope
         .entries()
+        .filter(|(name, _res)| !items.contains(*name))
+        // Items shadowed by local bindings would only be duplicates.
+        .filter(|(name, _res)| ctx.is_type_position || !locals.contains(*name))
         .filter(|(_name, res)| {
             // Don't expose this item
             // FIXME: this penetrates through all kinds of abstractions,
@@ -30,7 +48,7 @@ pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) ->
             match res.import {
                 None => true,
                 Some(import) => {
-                    let range = import.range(ctx.db, file_id);
+                    let range = import.range(ctx.db);
                     !range.is_subrange(&ctx.leaf.range())
                 }
             }
@@ -43,27 +61,51 @@ pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) ->
     Ok(())
 }
 
-fn complete_fn(acc: &mut Completions, scopes: &hir::ScopesWithSyntaxMapping, offset: TextUnit) {
+/// Completes the local bindings visible at the `offset`, and returns their
+/// names.
+pub(super) fn complete_fn(
+    acc: &mut Completions,
+    scopes: &hir::ScopesWithSyntaxMapping,
+    offset: TextUnit,
+) -> FxHashSet<hir::Name> {
     let mut shadowed = FxHashSet::default();
     scopes
         .scope_chain_for_offset(offset)
         .flat_map(|scope| scopes.scopes.entries(scope).iter())
-        .filter(|entry| shadowed.insert(entry.name()))
+        .filter(|entry| shadowed.insert(entry.name().clone()))
         .for_each(|entry| {
             CompletionItem::new(CompletionKind::Reference, entry.name().to_string())
                 .kind(CompletionItemKind::Binding)
                 .add_to(acc)
         });
+    shadowed
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::completion::{CompletionKind, check_completion};
+    use crate::mock_analysis::single_file_with_position;
+    use crate::completion::{
+        CompletionKind, CompletionItem, CompletionItemKind, CompletionConfig, check_completion,
+    };
 
     fn check_reference_completion(code: &str, expected_completions: &str) {
         check_completion(code, expected_completions, CompletionKind::Reference);
     }
 
+    fn completion_kind(code: &str, label: &str) -> Option<CompletionItemKind> {
+        let (analysis, position) = single_file_with_position(code);
+        let completions: Vec<CompletionItem> = analysis
+            .completions(position, &CompletionConfig::default())
+            .unwrap()
+            .unwrap();
+        let items = completions
+            .iter()
+            .filter(|it| it.label() == label)
+            .collect::<Vec<_>>();
+        assert_eq!(items.len(), 1, "expected a single {:?}", label);
+        items[0].kind()
+    }
+
     #[test]
     fn completes_bindings_from_let() {
         check_reference_completion(
@@ -78,6 +120,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn no_completions_in_comments_and_strings() {
+        check_reference_completion(
+            r"
+            fn quux(x: i32) {
+                // x<|>
+            }
+            ",
+            r#""#,
+        );
+        check_reference_completion(
+            r#"
+            fn quux(x: i32) {
+                let s = "x<|>";
+            }
+            "#,
+            r#""#,
+        );
+    }
+
     #[test]
     fn completes_bindings_from_if_let() {
         check_reference_completion(
@@ -189,4 +251,83 @@ mod tests {
                with_args "with_args($0)""#,
         );
     }
+
+    #[test]
+    fn locals_shadow_module_items() {
+        check_reference_completion(
+            r"
+            fn foo() {}
+            fn quux() {
+                let foo = 92;
+                <|>
+            }
+            ",
+            r#"foo;quux "quux()$0""#,
+        );
+    }
+
+    #[test]
+    fn completes_items_from_both_namespaces_once() {
+        let code = r"
+            struct Unit;
+            struct Pair(u32, u32);
+            fn quux() {
+                <|>
+            }
+            ";
+        assert_eq!(completion_kind(code, "Unit"), Some(CompletionItemKind::Struct));
+        assert_eq!(completion_kind(code, "Pair"), Some(CompletionItemKind::Struct));
+    }
+
+    #[test]
+    fn completes_items_of_blocks_only_inside_them() {
+        let code = r"
+            fn quux() {
+                struct Foo;
+                {
+                    enum Bar {}
+                    <|>
+                }
+            }
+            ";
+        assert_eq!(completion_kind(code, "Foo"), Some(CompletionItemKind::Struct));
+        assert_eq!(completion_kind(code, "Bar"), Some(CompletionItemKind::Enum));
+        let (analysis, position) = single_file_with_position(
+            r"
+            fn quux() { struct Foo; }
+            fn other() { <|> }
+            ",
+        );
+        let completions = analysis
+            .completions(position, &CompletionConfig::default())
+            .unwrap()
+            .unwrap();
+        assert!(completions.iter().all(|it| it.label() != "Foo"));
+    }
+
+    #[test]
+    fn completes_parent_items_in_test_modules() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct Foo;
+            fn parse() {}
+            #[cfg(test)]
+            mod tests {
+                use super::*;
+                #[test]
+                fn parses() { <|> }
+            }
+            ",
+        );
+        let completions: Vec<CompletionItem> = analysis
+            .completions(position, &CompletionConfig::default())
+            .unwrap()
+            .unwrap();
+        let labels = completions.iter().map(|it| it.label()).collect::<Vec<_>>();
+        for label in &["Foo", "parse", "parses", "tests"] {
+            assert!(labels.contains(label), "{} is not completed", label);
+        }
+        let code = "struct Foo; mod tests { use super::*; fn f() { <|> } }";
+        assert_eq!(completion_kind(code, "Foo"), Some(CompletionItemKind::Struct));
+    }
 }
@@ -1,4 +1,31 @@
-use crate::completion::{CompletionItem, Completions, CompletionKind, CompletionItemKind, CompletionContext, completion_item::Builder};
+use ra_db::SyntaxDatabase;
+use ra_ide_api_light::insert_use;
+
+use crate::completion::{
+    CompletionItem, Completions, CompletionKind, CompletionItemKind, CompletionContext,
+    CompletionConfig,
+    completion_item::Builder,
+};
+
+/// A snippet from the user settings.
+#[derive(Debug, Clone)]
+pub struct UserSnippet {
+    pub label: String,
+    /// The inserted text, with the usual `$0` and `${1:placeholder}` markers.
+    pub body: String,
+    /// Paths which the body needs in scope, like `std::collections::HashMap`.
+    /// The missing `use` items are added when the snippet is accepted.
+    pub requires: Vec<String>,
+    pub scope: SnippetScope,
+}
+
+/// Where a `UserSnippet` is offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnippetScope {
+    Expr,
+    Item,
+    Type,
+}
 
 fn snippet(label: &str, snippet: &str) -> Builder {
     CompletionItem::new(CompletionKind::Snippet, label)
@@ -7,7 +34,7 @@ fn snippet(label: &str, snippet: &str) -> Builder {
 }
 
 pub(super) fn complete_expr_snippet(acc: &mut Completions, ctx: &CompletionContext) {
-    if !(ctx.is_trivial_path && ctx.function_syntax.is_some()) {
+    if !(ctx.is_trivial_path && ctx.function_syntax.is_some()) || ctx.is_type_position {
         return;
     }
     snippet("pd", "eprintln!(\"$0 = {:?}\", $0);").add_to(acc);
@@ -32,13 +59,77 @@ fn ${1:feature}() {
     snippet("pub(crate)", "pub(crate) $0").add_to(acc);
 }
 
+pub(super) fn complete_user_snippet(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    config: &CompletionConfig,
+) {
+    let is_expr = ctx.is_trivial_path && ctx.function_syntax.is_some() && !ctx.is_type_position;
+    let is_type = ctx.is_trivial_path && ctx.is_type_position;
+    let applicable = config.user_snippets.iter().filter(|it| match it.scope {
+        SnippetScope::Expr => is_expr,
+        SnippetScope::Item => ctx.is_new_item,
+        SnippetScope::Type => is_type,
+    });
+    for user_snippet in applicable {
+        let edit = if user_snippet.requires.is_empty() {
+            None
+        } else {
+            let file = ctx.db.source_file(ctx.file_id);
+            insert_use(&file, ctx.offset, &user_snippet.requires, config.import_style)
+        };
+        CompletionItem::new(CompletionKind::UserSnippet, user_snippet.label.as_str())
+            .snippet(user_snippet.body.as_str())
+            .kind(CompletionItemKind::Snippet)
+            .set_additional_text_edit(edit)
+            .add_to(acc);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::completion::{CompletionKind, check_completion};
+    use test_utils::assert_eq_text;
+
+    use crate::{
+        mock_analysis::single_file_with_position,
+        completion::{
+            CompletionKind, CompletionConfig, UserSnippet, SnippetScope, check_completion,
+            completions,
+        },
+    };
+
     fn check_snippet_completion(code: &str, expected_completions: &str) {
         check_completion(code, expected_completions, CompletionKind::Snippet);
     }
 
+    fn user_snippet_labels(code: &str) -> Vec<String> {
+        let (analysis, position) = single_file_with_position(code);
+        let config = CompletionConfig {
+            user_snippets: vec![
+                user_snippet("expr", SnippetScope::Expr, &[]),
+                user_snippet("item", SnippetScope::Item, &[]),
+                user_snippet("type", SnippetScope::Type, &[]),
+            ],
+            ..CompletionConfig::default()
+        };
+        let completions = completions(&analysis.db, position, &config).unwrap().unwrap();
+        let items: Vec<_> = completions.into();
+        items
+            .into_iter()
+            .filter(|it| ["expr", "item", "type"].contains(&it.label()))
+            .map(|it| it.label().to_string())
+            .collect()
+    }
+
+    fn user_snippet(label: &str, scope: SnippetScope, requires: &[&str]) -> UserSnippet {
+        UserSnippet {
+            label: label.to_string(),
+            body: format!("{}$0", label),
+            requires: requires.iter().map(|it| it.to_string()).collect(),
+            scope,
+        }
+    }
+
     #[test]
     fn completes_snippets_in_expressions() {
         check_snippet_completion(
@@ -70,4 +161,59 @@ mod tests {
             "##,
         );
     }
+
+    #[test]
+    fn completes_user_snippets_in_their_scope() {
+        assert_eq!(user_snippet_labels("fn foo() { <|> }"), vec!["expr"]);
+        assert_eq!(user_snippet_labels("<|>"), vec!["item"]);
+        assert_eq!(user_snippet_labels("fn foo(x: <|>) {}"), vec!["type"]);
+    }
+
+    #[test]
+    fn user_snippets_are_told_apart_from_builtin_ones() {
+        let (analysis, position) = single_file_with_position("fn foo() { <|> }");
+        let config = CompletionConfig {
+            user_snippets: vec![user_snippet("pd", SnippetScope::Expr, &[])],
+            ..CompletionConfig::default()
+        };
+        let completions = completions(&analysis.db, position, &config).unwrap().unwrap();
+        let items: Vec<_> = completions.into();
+        let pd = items
+            .iter()
+            .filter(|it| it.label() == "pd")
+            .map(|it| it.is_user_snippet())
+            .collect::<Vec<_>>();
+        assert_eq!(pd, vec![false, true]);
+    }
+
+    #[test]
+    fn user_snippets_add_missing_imports() {
+        let code = "
+use std::fmt;
+use std::collections::HashMap;
+
+fn foo() { <|> }
+";
+        let (analysis, position) = single_file_with_position(code);
+        let requires = &["std::collections::HashMap", "std::sync::Arc"];
+        let config = CompletionConfig {
+            user_snippets: vec![user_snippet("arc", SnippetScope::Expr, requires)],
+            ..CompletionConfig::default()
+        };
+        let completions = completions(&analysis.db, position, &config).unwrap().unwrap();
+        let items: Vec<_> = completions.into();
+        let item = items.iter().find(|it| it.label() == "arc").unwrap();
+        let edit = item.additional_text_edit().unwrap();
+        let text = analysis.file_text(position.file_id);
+        assert_eq_text!(
+            "
+use std::fmt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn foo() {  }
+",
+            &edit.apply(&text)
+        );
+    }
 }
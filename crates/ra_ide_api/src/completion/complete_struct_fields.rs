@@ -0,0 +1,261 @@
+use ra_syntax::{
+    AstNode, SyntaxNode, TextRange,
+    ast::{self, AttrsOwner, NameOwner},
+    SyntaxKind::*,
+};
+use hir::{Def, Struct, TypeRef, db::HirDatabase};
+
+use crate::Cancelable;
+use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
+
+/// Completes the missing fields of a struct or of a record enum variant in a
+/// literal or in a pattern, and `..Default::default()` at the end of a literal
+/// of a struct which implements `Default`.
+pub(super) fn complete_struct_fields(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+) -> Cancelable<()> {
+    let (path, field_list) = match (ctx.struct_lit_syntax, ctx.struct_pat_syntax) {
+        (Some(lit), _) => (lit.path(), lit.named_field_list().map(|it| it.syntax())),
+        (_, Some(pat)) => (pat.path(), pat.field_pat_list().map(|it| it.syntax())),
+        _ => return Ok(()),
+    };
+    let (module, path) = match (&ctx.module, path.and_then(hir::Path::from_ast)) {
+        (Some(module), Some(path)) => (module, path),
+        _ => return Ok(()),
+    };
+    let def_id = match module.resolve_path(ctx.db, &path)?.take_types() {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let (struct_, variant_data) = match def_id.resolve(ctx.db)? {
+        Def::Struct(it) => {
+            let variant_data = it.variant_data(ctx.db)?;
+            (Some(it), variant_data)
+        }
+        Def::EnumVariant(it) => (None, it.variant_data(ctx.db)?),
+        _ => return Ok(()),
+    };
+    let fields = field_list.map(present_fields).unwrap_or_default();
+    // The field which is being typed, like `fo` in `S { fo<|> }`, is not
+    // there yet.
+    let present = fields
+        .iter()
+        .filter(|(_, range)| !range.contains_inclusive(ctx.offset))
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    for field in variant_data.fields() {
+        let name = field.name().to_string();
+        if present.contains(&name.as_str()) {
+            continue;
+        }
+        let ty = ctx.db.type_for_field(def_id, field.name().clone())?;
+        CompletionItem::new(CompletionKind::Reference, name)
+            .kind(CompletionItemKind::Field)
+            .set_detail(ty.map(|ty| ty.to_string()))
+            .add_to(acc);
+    }
+
+    // The spread must be the last element of the literal.
+    let is_last = fields.iter().all(|(_, range)| range.start() <= ctx.offset);
+    let has_spread = field_list.map_or(false, |it| it.children().any(|it| it.kind() == DOTDOT));
+    if let (Some(struct_), Some(_)) = (struct_, ctx.struct_lit_syntax) {
+        if is_last && !has_spread && implements_default(ctx, &struct_)? {
+            CompletionItem::new(CompletionKind::Magic, "..Default::default()")
+                .kind(CompletionItemKind::Snippet)
+                .add_to(acc);
+        }
+    }
+    Ok(())
+}
+
+/// The names of the fields of a struct literal or pattern, together with
+/// their ranges.
+fn present_fields(field_list: &SyntaxNode) -> Vec<(String, TextRange)> {
+    field_list
+        .children()
+        .filter_map(|it| {
+            let name = if let Some(field) = ast::NamedField::cast(it) {
+                field.name_ref()?.text().to_string()
+            } else if let Some(bind_pat) = ast::BindPat::cast(it) {
+                bind_pat.name()?.text().to_string()
+            } else if it.kind() == IDENT {
+                it.leaf_text()?.to_string()
+            } else {
+                return None;
+            };
+            Some((name, it.range()))
+        })
+        .collect()
+}
+
+/// Looks for `#[derive(Default)]`, as derives are not expanded, and for the
+/// impls of `Default` in the current crate. The trait is recognized by its
+/// name, as `std` is not necessarily loaded.
+fn implements_default(ctx: &CompletionContext, struct_: &Struct) -> Cancelable<bool> {
+    let (_, struct_def) = struct_.source(ctx.db)?;
+    let derives_default = struct_def
+        .attrs()
+        .filter_map(|it| it.as_call())
+        .filter(|(name, _)| name.as_str() == "derive")
+        .any(|(_, tt)| {
+            tt.syntax()
+                .children()
+                .any(|it| it.leaf_text().map_or(false, |it| it.as_str() == "Default"))
+        });
+    if derives_default {
+        return Ok(true);
+    }
+    let krate = match &ctx.module {
+        Some(module) => module.krate(ctx.db)?,
+        None => None,
+    };
+    let krate = match krate {
+        Some(it) => it,
+        None => return Ok(false),
+    };
+    let ty = ctx.db.type_for_def(struct_.def_id())?;
+    let impls = ctx.db.impls_in_crate(krate)?;
+    for impl_block in impls.lookup_impl_blocks(ctx.db, &ty) {
+        let is_default = match impl_block?.target_trait() {
+            Some(TypeRef::Path(path)) => path
+                .segments
+                .last()
+                .map_or(false, |it| it.to_string() == "Default"),
+            _ => false,
+        };
+        if is_default {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_reference_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Reference);
+    }
+
+    fn check_magic_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Magic);
+    }
+
+    #[test]
+    fn completes_missing_fields_of_struct_literal() {
+        check_reference_completion(
+            r"
+            struct S { foo: u32, bar: bool, baz: S }
+            fn foo() {
+                let s = S { bar: true, <|> };
+            }
+            ",
+            r#"foo "u32";baz "S""#,
+        );
+    }
+
+    #[test]
+    fn completes_field_being_typed() {
+        check_reference_completion(
+            r"
+            struct S { foo: u32 }
+            fn foo() {
+                let s = S { fo<|> };
+            }
+            ",
+            r#"foo "u32""#,
+        );
+    }
+
+    #[test]
+    fn completes_missing_fields_of_struct_pattern() {
+        check_reference_completion(
+            r"
+            struct S { foo: u32, bar: bool }
+            fn foo(s: S) {
+                let S { foo, <|> } = s;
+            }
+            ",
+            r#"bar "bool""#,
+        );
+    }
+
+    #[test]
+    fn completes_missing_fields_of_enum_variant() {
+        check_reference_completion(
+            r"
+            enum E { A { foo: u32, bar: bool }, B }
+            fn foo(e: E) {
+                let e = E::A { foo: 92, <|> };
+            }
+            ",
+            r#"bar "bool""#,
+        );
+        check_reference_completion(
+            r"
+            enum E { A { foo: u32, bar: bool }, B }
+            fn foo(e: E) {
+                match e {
+                    E::A { bar, <|> } => (),
+                    _ => (),
+                }
+            }
+            ",
+            r#"foo "u32""#,
+        );
+    }
+
+    #[test]
+    fn completes_default_spread_for_default_impl() {
+        check_magic_completion(
+            r"
+            trait Default { fn default() -> Self; }
+            struct S { foo: u32 }
+            impl Default for S { fn default() -> S { S { foo: 0 } } }
+            fn foo() {
+                let s = S { <|> };
+            }
+            ",
+            r#"..Default::default()"#,
+        );
+    }
+
+    #[test]
+    fn completes_default_spread_for_derived_default() {
+        check_magic_completion(
+            r"
+            #[derive(Debug, Default)]
+            struct S { foo: u32 }
+            fn foo() {
+                let s = S { foo: 92, <|> };
+            }
+            ",
+            r#"..Default::default()"#,
+        );
+    }
+
+    #[test]
+    fn no_default_spread_when_not_last_or_not_default() {
+        check_magic_completion(
+            r"
+            #[derive(Default)]
+            struct S { foo: u32, bar: u32 }
+            fn foo() {
+                let s = S { <|>, bar: 92 };
+            }
+            ",
+            r#""#,
+        );
+        check_magic_completion(
+            r"
+            struct S { foo: u32 }
+            fn foo() {
+                let s = S { <|> };
+            }
+            ",
+            r#""#,
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use ra_syntax::{
+    AstNode, SmolStr, SyntaxNode, TextRange,
+    ast::{self, NameOwner},
+    SyntaxKind::*,
+};
+use rustc_hash::FxHashSet;
+
+use crate::Cancelable;
+use crate::completion::{CompletionContext, Completions, CompletionKind, CompletionItem, CompletionItemKind};
+
+/// Inside `impl Trait for Type { <|> }`, completes the items of the trait which
+/// are not present in the impl yet.
+pub(super) fn complete_trait_impl(acc: &mut Completions, ctx: &CompletionContext) -> Cancelable<()> {
+    if !ctx.is_impl_item {
+        return Ok(());
+    }
+    let impl_block = match &ctx.impl_block {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let trait_ = match impl_block.target_trait_def(ctx.db)? {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let (_, impl_def) = impl_block.source(ctx.db)?;
+    let (_, trait_def) = trait_.source(ctx.db)?;
+
+    let existing: FxHashSet<SmolStr> = impl_def
+        .item_list()
+        .into_iter()
+        .flat_map(|it| it.impl_items())
+        .filter_map(item_name)
+        .collect();
+    let trait_items = trait_def
+        .item_list()
+        .into_iter()
+        .flat_map(|it| it.impl_items());
+    for item in trait_items {
+        let name = match item_name(item) {
+            Some(name) if !existing.contains(&name) => name,
+            _ => continue,
+        };
+        let header = item_header(item.syntax());
+        let (kind, label, snippet) = match item.kind() {
+            ast::ImplItemKind::FnDef(_) => {
+                let snippet = format!("{} {{\n    $0unimplemented!()\n}}", header);
+                (CompletionItemKind::Method, header, snippet)
+            }
+            ast::ImplItemKind::ConstDef(_) => {
+                let snippet = format!("{} = $0;", header);
+                (CompletionItemKind::Const, header, snippet)
+            }
+            ast::ImplItemKind::TypeDef(_) => {
+                // Drop the bounds, they are not allowed in impls.
+                let label = format!("type {}", name);
+                let snippet = format!("{} = $0;", label);
+                (CompletionItemKind::TypeAlias, label, snippet)
+            }
+        };
+        CompletionItem::new(CompletionKind::Magic, label)
+            .kind(kind)
+            .lookup_by(name.to_string())
+            .snippet(snippet)
+            .add_to(acc);
+    }
+    Ok(())
+}
+
+fn item_name(item: &ast::ImplItem) -> Option<SmolStr> {
+    let name = match item.kind() {
+        ast::ImplItemKind::FnDef(it) => it.name(),
+        ast::ImplItemKind::ConstDef(it) => it.name(),
+        ast::ImplItemKind::TypeDef(it) => it.name(),
+    };
+    name.map(|it| it.text().clone())
+}
+
+/// The text of the item without attributes and doc comments in front of it
+/// and without the default body, value or the trailing semicolon.
+fn item_header(node: &SyntaxNode) -> String {
+    let node_start = node.range().start();
+    let start = node
+        .children()
+        .find(|it| match it.kind() {
+            COMMENT | ATTR | WHITESPACE => false,
+            _ => true,
+        })
+        .map_or(node_start, |it| it.range().start());
+    let end = node
+        .children()
+        .find(|it| match it.kind() {
+            BLOCK | EQ | SEMI => true,
+            _ => false,
+        })
+        .map_or(node.range().end(), |it| it.range().start());
+    let range = TextRange::from_to(start - node_start, end - node_start);
+    let text = node.text().to_string();
+    text.as_str()[range].trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{CompletionKind, check_completion};
+
+    fn check_magic_completion(code: &str, expected_completions: &str) {
+        check_completion(code, expected_completions, CompletionKind::Magic);
+    }
+
+    #[test]
+    fn completes_missing_trait_items() {
+        check_magic_completion(
+            r"
+            trait T {
+                /// Docs are not copied.
+                fn foo(&self);
+                fn bar(&self) -> i32 { 92 }
+                const C: i32;
+                type Ty: Clone;
+            }
+            struct S;
+            impl T for S {
+                fn foo(&self) {}
+                <|>
+            }
+            ",
+            r#"
+            bar "fn bar(&self) -> i32" "fn bar(&self) -> i32 {\n    $0unimplemented!()\n}"
+            C "const C: i32" "const C: i32 = $0;"
+            Ty "type Ty" "type Ty = $0;"
+            "#,
+        );
+    }
+
+    #[test]
+    fn no_trait_items_in_inherent_impl() {
+        check_magic_completion(
+            r"
+            struct S;
+            impl S {
+                <|>
+            }
+            ",
+            r#""#,
+        );
+    }
+}
@@ -5,21 +5,31 @@ use ra_syntax::{
     algo::{find_leaf_at_offset, find_covering_node, find_node_at_offset},
     SyntaxKind::*,
 };
-use hir::source_binder;
+use hir::{source_binder, db::HirDatabase, Ty};
+use ra_ide_api_light::{classify_position, PositionKind};
 
-use crate::{db, FilePosition, Cancelable};
+use crate::{
+    db, FileId, FilePosition, Cancelable,
+    format_string::{format_string, is_in_placeholder_arg},
+};
 
 /// `CompletionContext` is created early during completion to figure out, where
 /// exactly is the cursor, syntax-wise.
 #[derive(Debug)]
 pub(super) struct CompletionContext<'a> {
     pub(super) db: &'a db::RootDatabase,
+    pub(super) file_id: FileId,
     pub(super) offset: TextUnit,
     pub(super) leaf: &'a SyntaxNode,
     pub(super) module: Option<hir::Module>,
     pub(super) function: Option<hir::Function>,
     pub(super) function_syntax: Option<&'a ast::FnDef>,
+    /// The impl block we are in, used to resolve `Self`.
+    pub(super) impl_block: Option<hir::ImplBlock>,
     pub(super) use_item_syntax: Option<&'a ast::UseItem>,
+    /// Names imported by the other trees of the enclosing use tree list, like
+    /// `bar` in `use foo::{bar, <|>}`.
+    pub(super) use_tree_siblings: Vec<String>,
     pub(super) is_param: bool,
     /// A single-indent path, like `foo`.
     pub(super) is_trivial_path: bool,
@@ -30,10 +40,36 @@ pub(super) struct CompletionContext<'a> {
     pub(super) can_be_stmt: bool,
     /// Something is typed at the "top" level, in module or impl/trait.
     pub(super) is_new_item: bool,
+    /// The new item is inside a trait body.
+    pub(super) is_trait_item: bool,
+    /// The new item is inside an impl body.
+    pub(super) is_impl_item: bool,
+    /// A path in type position, like `let x: <|>`.
+    pub(super) is_type_position: bool,
+    /// The name in a `mod <|>` at the top level of the file, where the names
+    /// of the sibling files which are not modules yet are completed. The
+    /// declaration may have no `;` or body yet.
+    pub(super) is_mod_decl: bool,
+    /// A plain binding in pattern position, like `let <|>`.
+    pub(super) is_pat_position: bool,
+    /// The expression matched against the pattern, like `x` in `match x { <|> }`.
+    pub(super) pat_scrutinee: Option<&'a ast::Expr>,
+    /// Right after `&` in an expression or a type, where `mut` may follow.
+    pub(super) after_amp: bool,
     /// The receiver if this is a field or method access, i.e. writing something.<|>
     pub(super) dot_receiver: Option<&'a ast::Expr>,
     /// If this is a call (method or function) in particular, i.e. the () are already there.
     pub(super) is_call: bool,
+    /// The struct literal if a field name is being typed, like `S { a: 1, <|> }`.
+    pub(super) struct_lit_syntax: Option<&'a ast::StructLit>,
+    /// The struct pattern if a field name is being typed, like `let S { <|> } = s`.
+    pub(super) struct_pat_syntax: Option<&'a ast::StructPat>,
+    /// The macro call if we are inside a placeholder of its format string,
+    /// like `format!("{<|>}")`.
+    pub(super) format_macro_call: Option<&'a ast::MacroCall>,
+    /// The type of the expression being completed, if the surroundings
+    /// determine it, like in `let x: Option<u32> = <|>`.
+    pub(super) expected_type: Option<Ty>,
 }
 
 impl<'a> CompletionContext<'a> {
@@ -45,45 +81,88 @@ impl<'a> CompletionContext<'a> {
         let module = source_binder::module_from_position(db, position)?;
         let leaf =
             ctry!(find_leaf_at_offset(original_file.syntax(), position.offset).left_biased());
+        // Nothing can be completed in comments and literals, except for the
+        // placeholders of format strings.
+        match classify_position(original_file, position.offset) {
+            PositionKind::Code => (),
+            PositionKind::Literal if format_macro_call(leaf, position.offset).is_some() => (),
+            _ => return Ok(None),
+        }
+        let impl_block = source_binder::impl_block_from_child_node(db, position.file_id, leaf)?;
         let mut ctx = CompletionContext {
             db,
+            file_id: position.file_id,
             leaf,
             offset: position.offset,
             module,
             function: None,
             function_syntax: None,
+            impl_block,
             use_item_syntax: None,
+            use_tree_siblings: Vec::new(),
             is_param: false,
             is_trivial_path: false,
             path_prefix: None,
             after_if: false,
             can_be_stmt: false,
             is_new_item: false,
+            is_trait_item: false,
+            is_impl_item: false,
+            is_type_position: false,
+            is_mod_decl: false,
+            is_pat_position: false,
+            pat_scrutinee: None,
+            after_amp: false,
             dot_receiver: None,
             is_call: false,
+            struct_lit_syntax: None,
+            struct_pat_syntax: None,
+            format_macro_call: None,
+            expected_type: None,
         };
-        ctx.fill(original_file, position.offset);
+        ctx.fill(original_file, position.offset)?;
         Ok(Some(ctx))
     }
 
-    fn fill(&mut self, original_file: &'a SourceFile, offset: TextUnit) {
+    fn fill(&mut self, original_file: &'a SourceFile, offset: TextUnit) -> Cancelable<()> {
+        if self.leaf.kind() == STRING || self.leaf.kind() == RAW_STRING {
+            self.format_macro_call = format_macro_call(self.leaf, offset);
+        }
         // Insert a fake ident to get a valid parse tree. We will use this file
         // to determine context, though the original_file will be used for
         // actual completion.
+        let fake_ident = "intellijRulezz";
         let file = {
-            let edit = AtomTextEdit::insert(offset, "intellijRulezz".to_string());
+            let edit = AtomTextEdit::insert(offset, fake_ident.to_string());
             original_file.reparse(&edit)
         };
 
+        self.function_syntax = self
+            .leaf
+            .ancestors()
+            .take_while(|it| it.kind() != SOURCE_FILE && it.kind() != MODULE)
+            .find_map(ast::FnDef::cast);
+        match (&self.module, self.function_syntax) {
+            (Some(module), Some(fn_def)) => {
+                let function = source_binder::function_from_module(self.db, module, fn_def);
+                self.function = Some(function);
+            }
+            _ => (),
+        }
+        if self.format_macro_call.is_some() {
+            return Ok(());
+        }
+
         // First, let's try to complete a reference to some declaration.
         if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), offset) {
             // Special case, `trait T { fn foo(i_am_a_name_ref) {} }`.
             // See RFC#1685.
             if is_node::<ast::Param>(name_ref.syntax()) {
                 self.is_param = true;
-                return;
+                return Ok(());
             }
             self.classify_name_ref(original_file, name_ref);
+            self.expected_type = self.expected_type_of(original_file, name_ref)?;
         }
 
         // Otherwise, see if this is a declaration. We can use heuristics to
@@ -91,10 +170,101 @@ impl<'a> CompletionContext<'a> {
         if let Some(name) = find_node_at_offset::<ast::Name>(file.syntax(), offset) {
             if is_node::<ast::Param>(name.syntax()) {
                 self.is_param = true;
-                return;
+                return Ok(());
+            }
+            if let Some(module) = name.syntax().parent().and_then(ast::Module::cast) {
+                // Modules nested in inline modules can't be linked to the
+                // existing files yet, so only look at the top level.
+                let parent_kind = module.syntax().parent().map(|it| it.kind());
+                self.is_mod_decl = parent_kind == Some(SOURCE_FILE);
+                return Ok(());
+            }
+            if let Some(bind_pat) = name.syntax().parent().and_then(ast::BindPat::cast) {
+                // A binding in a struct pattern, like `S { <|> }`, is named
+                // after a field.
+                let struct_pat = bind_pat
+                    .syntax()
+                    .parent()
+                    .filter(|it| it.kind() == FIELD_PAT_LIST)
+                    .and_then(|it| it.parent())
+                    .and_then(ast::StructPat::cast);
+                if let Some(struct_pat) = struct_pat {
+                    self.struct_pat_syntax = struct_pat
+                        .path()
+                        .and_then(|path| struct_in_original(original_file, path.syntax()));
+                    return Ok(());
+                }
+                // Only offer `ref` and `mut` if there are no modifiers yet.
+                self.is_pat_position = bind_pat.syntax().first_child() == Some(name.syntax());
+                // The scrutinee of `if let` comes after the fake ident, so
+                // its range has to be shifted back to the original file.
+                let fake_len = TextUnit::of_str(fake_ident);
+                self.pat_scrutinee = scrutinee(bind_pat)
+                    .map(|it| it.syntax().range())
+                    .map(|range| {
+                        if range.start() < offset {
+                            range
+                        } else {
+                            TextRange::offset_len(range.start() - fake_len, range.len())
+                        }
+                    })
+                    .and_then(|range| find_node_with_range(original_file.syntax(), range));
             }
         }
+        Ok(())
+    }
+
+    /// Looks at the declared type in `let x: T = <|>`, the type of the
+    /// parameter in `foo(<|>)` and the return type in `return <|>` or in the
+    /// tail of the function body.
+    fn expected_type_of(
+        &self,
+        original_file: &SourceFile,
+        name_ref: &ast::NameRef,
+    ) -> Cancelable<Option<Ty>> {
+        if !self.is_trivial_path || self.is_type_position || self.use_item_syntax.is_some() {
+            return Ok(None);
+        }
+        let function = ctry!(self.function.as_ref());
+        let expr = ctry!(name_ref.syntax().ancestors().find_map(ast::PathExpr::cast)).syntax();
+        let parent = ctry!(expr.parent());
+        let infer_result = function.infer(self.db)?;
+        let syntax_mapping = function.body_syntax_mapping(self.db)?;
+        // The pattern and the callee come before the fake ident, so they have
+        // the same ranges in the original file.
+        if let Some(let_stmt) = ast::LetStmt::cast(parent) {
+            let pat = ctry!(let_stmt.pat()).syntax().range();
+            let pat = ctry!(find_node_with_range::<ast::Pat>(original_file.syntax(), pat));
+            let pat = ctry!(syntax_mapping.node_pat(pat));
+            return Ok(Some(infer_result[pat].clone()));
+        }
+        if let Some(arg_list) = ast::ArgList::cast(parent) {
+            let call = ctry!(arg_list.syntax().parent().and_then(ast::CallExpr::cast));
+            let idx = arg_list
+                .args()
+                .take_while(|it| it.syntax().range() != expr.range())
+                .count();
+            let callee = ctry!(call.expr()).syntax().range();
+            let callee = ctry!(find_node_with_range::<ast::Expr>(original_file.syntax(), callee));
+            let callee = ctry!(syntax_mapping.node_expr(callee));
+            return match &infer_result[callee] {
+                Ty::FnPtr(sig) => Ok(sig.input().get(idx).cloned()),
+                _ => Ok(None),
+            };
+        }
+        let is_tail = ast::Block::cast(parent).map_or(false, |block| {
+            block.expr().map(|it| it.syntax().range()) == Some(expr.range())
+                && block.syntax().parent().map(|it| it.kind()) == Some(FN_DEF)
+        });
+        if is_tail || ast::ReturnExpr::cast(parent).is_some() {
+            return match self.db.type_for_def(function.def_id())? {
+                Ty::FnPtr(sig) => Ok(Some(sig.output().clone())),
+                _ => Ok(None),
+            };
+        }
+        Ok(None)
     }
+
     fn classify_name_ref(&mut self, original_file: &'a SourceFile, name_ref: &ast::NameRef) {
         let name_range = name_ref.syntax().range();
         let top_node = name_ref
@@ -104,27 +274,22 @@ impl<'a> CompletionContext<'a> {
             .last()
             .unwrap();
 
-        match top_node.parent().map(|it| it.kind()) {
-            Some(SOURCE_FILE) | Some(ITEM_LIST) => {
+        match top_node.parent() {
+            Some(parent) if parent.kind() == SOURCE_FILE || parent.kind() == ITEM_LIST => {
                 self.is_new_item = true;
+                match parent.parent().map(|it| it.kind()) {
+                    Some(TRAIT_DEF) => self.is_trait_item = true,
+                    Some(IMPL_BLOCK) => self.is_impl_item = true,
+                    _ => (),
+                }
                 return;
             }
             _ => (),
         }
 
         self.use_item_syntax = self.leaf.ancestors().find_map(ast::UseItem::cast);
-
-        self.function_syntax = self
-            .leaf
-            .ancestors()
-            .take_while(|it| it.kind() != SOURCE_FILE && it.kind() != MODULE)
-            .find_map(ast::FnDef::cast);
-        match (&self.module, self.function_syntax) {
-            (Some(module), Some(fn_def)) => {
-                let function = source_binder::function_from_module(self.db, module, fn_def);
-                self.function = Some(function);
-            }
-            _ => (),
+        if self.use_item_syntax.is_some() {
+            self.use_tree_siblings = use_tree_siblings(name_ref);
         }
 
         let parent = match name_ref.syntax().parent() {
@@ -143,6 +308,14 @@ impl<'a> CompletionContext<'a> {
             if path.qualifier().is_none() {
                 self.is_trivial_path = true;
 
+                let path_parent = path.syntax().parent();
+                self.is_type_position = path_parent.map(|it| it.kind()) == Some(PATH_TYPE);
+                // `&<|>` is either a reference expression or a reference type.
+                self.after_amp = path_parent
+                    .and_then(|it| it.parent())
+                    .filter(|it| it.kind() == REF_EXPR || it.kind() == REFERENCE_TYPE)
+                    .map_or(false, |it| it.children().all(|child| child.kind() != MUT_KW));
+
                 // Find either enclosing expr statement (thing with `;`) or a
                 // block. If block, check that we are the last expr.
                 self.can_be_stmt = name_ref
@@ -179,6 +352,16 @@ impl<'a> CompletionContext<'a> {
                 .and_then(|it| it.syntax().parent().and_then(ast::CallExpr::cast))
                 .is_some()
         }
+        if let Some(named_field) = ast::NamedField::cast(parent) {
+            // The path comes before the fake ident, so it should have the
+            // same range in the non-modified file.
+            self.struct_lit_syntax = named_field
+                .syntax()
+                .ancestors()
+                .find_map(ast::StructLit::cast)
+                .and_then(|it| it.path())
+                .and_then(|path| struct_in_original(original_file, path.syntax()));
+        }
         if let Some(field_expr) = ast::FieldExpr::cast(parent) {
             // The receiver comes before the point of insertion of the fake
             // ident, so it should have the same range in the non-modified file
@@ -198,6 +381,62 @@ impl<'a> CompletionContext<'a> {
     }
 }
 
+/// If `literal` is the format string of a format macro and `offset` is right
+/// after the `{` of a placeholder or inside its argument name, returns the
+/// macro call.
+fn format_macro_call(literal: &SyntaxNode, offset: TextUnit) -> Option<&ast::MacroCall> {
+    let token_tree = literal.parent().filter(|it| it.kind() == TOKEN_TREE)?;
+    let macro_call = token_tree.parent().and_then(ast::MacroCall::cast)?;
+    if format_string(macro_call) != Some(literal) || !is_in_placeholder_arg(literal, offset) {
+        return None;
+    }
+    Some(macro_call)
+}
+
+/// Names of the plain imports next to the use tree of `name_ref` in the same
+/// list. Trees with nested lists or globs import other items, so they are
+/// skipped.
+fn use_tree_siblings(name_ref: &ast::NameRef) -> Vec<String> {
+    let use_tree = match name_ref.syntax().ancestors().find_map(ast::UseTree::cast) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let use_tree_list = match use_tree.syntax().parent().and_then(ast::UseTreeList::cast) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    use_tree_list
+        .use_trees()
+        .filter(|it| it.syntax().range() != use_tree.syntax().range())
+        .filter(|it| it.use_tree_list().is_none() && !it.has_star())
+        .filter_map(|it| it.path())
+        .filter(|it| it.qualifier().is_none())
+        .filter_map(|it| it.segment()?.name_ref())
+        .map(|it| it.text().to_string())
+        .collect()
+}
+
+/// The expression which the pattern is matched against, for patterns of match
+/// arms and `if let`/`while let` conditions.
+fn scrutinee(pat: &ast::BindPat) -> Option<&ast::Expr> {
+    let parent = pat.syntax().parent()?;
+    if let Some(arm) = ast::MatchArm::cast(parent) {
+        let match_expr = arm.syntax().ancestors().find_map(ast::MatchExpr::cast)?;
+        return match_expr.expr();
+    }
+    ast::Condition::cast(parent)?.expr()
+}
+
+/// Finds the struct literal or pattern with the `path` of the modified file in
+/// the original file.
+fn struct_in_original<'a, N: AstNode>(
+    original_file: &'a SourceFile,
+    path: &SyntaxNode,
+) -> Option<&'a N> {
+    let path = find_node_with_range::<ast::Path>(original_file.syntax(), path.range())?;
+    path.syntax().ancestors().find(|it| it.kind() != PATH).and_then(N::cast)
+}
+
 fn find_node_with_range<N: AstNode>(syntax: &SyntaxNode, range: TextRange) -> Option<&N> {
     let node = find_covering_node(syntax, range);
     node.ancestors().find_map(N::cast)
@@ -1,6 +1,8 @@
 use hir::PerNs;
+use ra_syntax::{TreeArc, ast::AttrsOwner};
+use ra_text_edit::TextEdit;
 
-use crate::completion::CompletionContext;
+use crate::{Cancelable, completion::CompletionContext};
 
 /// `CompletionItem` describes a single completion variant in the editor pop-up.
 /// It is basically a POD with various properties. To construct a
@@ -12,9 +14,14 @@ pub struct CompletionItem {
     completion_kind: CompletionKind,
     label: String,
     detail: Option<String>,
+    documentation: Option<String>,
     lookup: Option<String>,
     snippet: Option<String>,
     kind: Option<CompletionItemKind>,
+    deprecated: bool,
+    triggers_completion: bool,
+    matches_expected_type: bool,
+    additional_text_edit: Option<TextEdit>,
 }
 
 pub enum InsertText {
@@ -49,6 +56,10 @@ pub(crate) enum CompletionKind {
     /// "Secret sauce" completions.
     Magic,
     Snippet,
+    /// The snippets from the user settings.
+    UserSnippet,
+    /// The items which are not in scope yet, and are imported on the fly.
+    Import,
 }
 
 impl CompletionItem {
@@ -58,9 +69,14 @@ impl CompletionItem {
             completion_kind,
             label,
             detail: None,
+            documentation: None,
             lookup: None,
             snippet: None,
             kind: None,
+            deprecated: false,
+            triggers_completion: false,
+            matches_expected_type: false,
+            additional_text_edit: None,
         }
     }
     /// What user sees in pop-up in the UI.
@@ -71,6 +87,11 @@ impl CompletionItem {
     pub fn detail(&self) -> Option<&str> {
         self.detail.as_ref().map(|it| it.as_str())
     }
+    /// Longer, possibly multi-line information, shown when the item is
+    /// selected.
+    pub fn documentation(&self) -> Option<&str> {
+        self.documentation.as_ref().map(|it| it.as_str())
+    }
     /// What string is used for filtering.
     pub fn lookup(&self) -> &str {
         self.lookup
@@ -91,6 +112,36 @@ impl CompletionItem {
     pub fn kind(&self) -> Option<CompletionItemKind> {
         self.kind
     }
+
+    /// Whether the item is one of the snippets from the user settings, rather
+    /// than a built-in one.
+    pub fn is_user_snippet(&self) -> bool {
+        self.completion_kind == CompletionKind::UserSnippet
+    }
+
+    /// Whether the item is marked with `#[deprecated]`, editors usually
+    /// render such items with a strike-through.
+    pub fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    /// Whether the editor should ask for completions again right after this
+    /// item is inserted, like after `foo::` in an import.
+    pub fn triggers_completion(&self) -> bool {
+        self.triggers_completion
+    }
+
+    /// Whether the item produces a value of the type expected at the cursor,
+    /// such items should be shown first.
+    pub fn matches_expected_type(&self) -> bool {
+        self.matches_expected_type
+    }
+
+    /// An edit elsewhere in the file which is applied together with the
+    /// insertion, like a `use` item required by the inserted code.
+    pub fn additional_text_edit(&self) -> Option<&TextEdit> {
+        self.additional_text_edit.as_ref()
+    }
 }
 
 /// A helper to make `CompletionItem`s.
@@ -99,9 +150,14 @@ pub(crate) struct Builder {
     completion_kind: CompletionKind,
     label: String,
     detail: Option<String>,
+    documentation: Option<String>,
     lookup: Option<String>,
     snippet: Option<String>,
     kind: Option<CompletionItemKind>,
+    deprecated: bool,
+    triggers_completion: bool,
+    matches_expected_type: bool,
+    additional_text_edit: Option<TextEdit>,
 }
 
 impl Builder {
@@ -113,9 +169,14 @@ impl Builder {
         CompletionItem {
             label: self.label,
             detail: self.detail,
+            documentation: self.documentation,
             lookup: self.lookup,
             snippet: self.snippet,
             kind: self.kind,
+            deprecated: self.deprecated,
+            triggers_completion: self.triggers_completion,
+            matches_expected_type: self.matches_expected_type,
+            additional_text_edit: self.additional_text_edit,
             completion_kind: self.completion_kind,
         }
     }
@@ -131,6 +192,14 @@ impl Builder {
         self.kind = Some(kind);
         self
     }
+    pub(crate) fn matches_expected_type(mut self) -> Builder {
+        self.matches_expected_type = true;
+        self
+    }
+    pub(crate) fn set_additional_text_edit(mut self, edit: Option<TextEdit>) -> Builder {
+        self.additional_text_edit = edit;
+        self
+    }
     #[allow(unused)]
     pub(crate) fn detail(self, detail: impl Into<String>) -> Builder {
         self.set_detail(Some(detail))
@@ -139,46 +208,74 @@ impl Builder {
         self.detail = detail.map(Into::into);
         self
     }
+    pub(crate) fn documentation(mut self, documentation: impl Into<String>) -> Builder {
+        self.documentation = Some(documentation.into());
+        self
+    }
     pub(super) fn from_resolution(
         mut self,
         ctx: &CompletionContext,
         resolution: &hir::Resolution,
     ) -> Builder {
         let resolved = resolution.def_id.and_then(|d| d.resolve(ctx.db).ok());
-        let kind = match resolved {
-            PerNs {
-                types: Some(hir::Def::Module(..)),
-                ..
-            } => CompletionItemKind::Module,
+        // A name can live in both namespaces, like a tuple struct and its
+        // constructor. Pick the definition which makes sense at the cursor:
+        // the type in type positions and imports, the value elsewhere.
+        // Constructors of structs resolve to `Def::Item`, so for them we
+        // fall back to the struct itself.
+        let def = match resolved {
             PerNs {
-                types: Some(hir::Def::Struct(..)),
-                ..
-            } => CompletionItemKind::Struct,
-            PerNs {
-                types: Some(hir::Def::Enum(..)),
-                ..
-            } => CompletionItemKind::Enum,
-            PerNs {
-                types: Some(hir::Def::Trait(..)),
-                ..
-            } => CompletionItemKind::Trait,
-            PerNs {
-                types: Some(hir::Def::Type(..)),
-                ..
-            } => CompletionItemKind::TypeAlias,
-            PerNs {
-                values: Some(hir::Def::Const(..)),
-                ..
-            } => CompletionItemKind::Const,
+                types: Some(types),
+                values,
+            } => {
+                if ctx.is_type_position || ctx.use_item_syntax.is_some() {
+                    types
+                } else {
+                    match values {
+                        Some(hir::Def::Item) | None => types,
+                        Some(values) => values,
+                    }
+                }
+            }
             PerNs {
-                values: Some(hir::Def::Static(..)),
-                ..
-            } => CompletionItemKind::Static,
+                types: None,
+                values: Some(values),
+            } => values,
             PerNs {
-                values: Some(hir::Def::Function(function)),
-                ..
-            } => return self.from_function(ctx, function),
-            _ => return self,
+                types: None,
+                values: None,
+            } => return self,
+        };
+        self.deprecated = match &def {
+            hir::Def::Module(it) => match it.declaration_source(ctx.db) {
+                Ok(Some((_, node))) => node.has_attr("deprecated"),
+                _ => false,
+            },
+            hir::Def::Struct(it) => has_deprecated_attr(it.source(ctx.db)),
+            hir::Def::Enum(it) => has_deprecated_attr(it.source(ctx.db)),
+            hir::Def::Trait(it) => has_deprecated_attr(it.source(ctx.db)),
+            hir::Def::Const(it) => has_deprecated_attr(it.source(ctx.db)),
+            hir::Def::Static(it) => has_deprecated_attr(it.source(ctx.db)),
+            _ => false,
+        };
+        let kind = match def {
+            hir::Def::Module(..) => {
+                // In imports, a module is usually followed by its items.
+                if ctx.use_item_syntax.is_some() {
+                    self.snippet = Some(format!("{}::$0", self.label));
+                    self.triggers_completion = true;
+                }
+                CompletionItemKind::Module
+            }
+            hir::Def::Struct(..) => CompletionItemKind::Struct,
+            hir::Def::Enum(..) => CompletionItemKind::Enum,
+            hir::Def::EnumVariant(..) => CompletionItemKind::EnumVariant,
+            hir::Def::Trait(..) => CompletionItemKind::Trait,
+            hir::Def::Type(..) => CompletionItemKind::TypeAlias,
+            hir::Def::Const(..) => CompletionItemKind::Const,
+            hir::Def::Static(..) => CompletionItemKind::Static,
+            hir::Def::Function(function) => return self.from_function(ctx, function),
+            hir::Def::Item => return self,
         };
         self.kind = Some(kind);
         self
@@ -189,19 +286,25 @@ impl Builder {
         ctx: &CompletionContext,
         function: hir::Function,
     ) -> Builder {
+        let signature = function.signature(ctx.db);
         // If not an import, add parenthesis automatically.
         if ctx.use_item_syntax.is_none() && !ctx.is_call {
-            if function.signature(ctx.db).params().is_empty() {
+            if signature.params().is_empty() {
                 self.snippet = Some(format!("{}()$0", self.label));
             } else {
                 self.snippet = Some(format!("{}($0)", self.label));
             }
         }
         self.kind = Some(CompletionItemKind::Function);
+        self.deprecated = signature.is_deprecated();
         self
     }
 }
 
+fn has_deprecated_attr<N: AttrsOwner>(source: Cancelable<(hir::HirFileId, TreeArc<N>)>) -> bool {
+    source.map(|(_, node)| node.has_attr("deprecated")).unwrap_or(false)
+}
+
 impl Into<CompletionItem> for Builder {
     fn into(self) -> CompletionItem {
         self.build()
@@ -274,6 +377,15 @@ impl Completions {
                 if let Some(snippet) = &c.snippet {
                     res.push_str(&format!(" {:?}", snippet));
                 }
+                if c.deprecated {
+                    res.push_str(" DEPRECATED");
+                }
+                if c.triggers_completion {
+                    res.push_str(" TRIGGERS_COMPLETION");
+                }
+                if c.matches_expected_type {
+                    res.push_str(" MATCHES_EXPECTED_TYPE");
+                }
                 res.push('\n');
             }
         }
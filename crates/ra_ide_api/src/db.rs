@@ -15,12 +15,14 @@ pub(crate) struct RootDatabase {
 struct IdMaps {
     defs: LocationIntener<hir::DefLoc, hir::DefId>,
     macros: LocationIntener<hir::MacroCallLoc, hir::MacroCallId>,
+    types: hir::TyInterner,
 }
 
 impl fmt::Debug for IdMaps {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("IdMaps")
             .field("n_defs", &self.defs.len())
+            .field("n_types", &self.types.len())
             .finish()
     }
 }
@@ -73,6 +75,12 @@ impl AsRef<LocationIntener<hir::MacroCallLoc, hir::MacroCallId>> for RootDatabas
     }
 }
 
+impl AsRef<hir::TyInterner> for RootDatabase {
+    fn as_ref(&self) -> &hir::TyInterner {
+        &self.id_maps.types
+    }
+}
+
 salsa::query_group! {
     pub(crate) trait LineIndexDatabase: ra_db::FilesDatabase + BaseDatabase {
         fn line_index(file_id: FileId) -> Arc<LineIndex> {
@@ -83,7 +91,7 @@ salsa::query_group! {
 
 fn line_index(db: &impl ra_db::FilesDatabase, file_id: FileId) -> Arc<LineIndex> {
     let text = db.file_text(file_id);
-    Arc::new(LineIndex::new(&*text))
+    Arc::new(LineIndex::from_shared_text(text))
 }
 
 salsa::database_storage! {
@@ -112,9 +120,11 @@ salsa::database_storage! {
             fn expand_macro_invocation() for hir::db::ExpandMacroCallQuery;
             fn module_tree() for hir::db::ModuleTreeQuery;
             fn fn_scopes() for hir::db::FnScopesQuery;
+            fn body_block_scopes() for hir::db::BodyBlockScopesQuery;
             fn file_items() for hir::db::SourceFileItemsQuery;
             fn file_item() for hir::db::FileItemQuery;
             fn input_module_items() for hir::db::InputModuleItemsQuery;
+            fn module_declarations() for hir::db::ModuleDeclarationsQuery;
             fn item_map() for hir::db::ItemMapQuery;
             fn submodules() for hir::db::SubmodulesQuery;
             fn infer() for hir::db::InferQuery;
@@ -123,6 +133,7 @@ salsa::database_storage! {
             fn struct_data() for hir::db::StructDataQuery;
             fn enum_data() for hir::db::EnumDataQuery;
             fn enum_variant_data() for hir::db::EnumVariantDataQuery;
+            fn trait_data() for hir::db::TraitDataQuery;
             fn impls_in_module() for hir::db::ImplsInModuleQuery;
             fn impls_in_crate() for hir::db::ImplsInCrateQuery;
             fn body_hir() for hir::db::BodyHirQuery;
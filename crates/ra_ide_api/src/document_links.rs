@@ -0,0 +1,245 @@
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_syntax::{
+    AstNode, SyntaxNode, TextRange, TextUnit,
+    algo::find_covering_node,
+    ast,
+    SyntaxKind::STRING,
+};
+use hir::source_binder;
+
+use crate::{
+    FileId, FileRange,
+    db::RootDatabase,
+    goto_definition::resolve_file_reference,
+};
+
+/// A range of a file which refers to another file or to a web page.
+#[derive(Debug)]
+pub struct DocumentLink {
+    pub range: TextRange,
+    pub target: LinkTarget,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// A module or an included file. Finding it needs name resolution, so this
+    /// is left to `resolve_document_link`, for the links which are followed.
+    File,
+    Url(String),
+}
+
+/// Finds the names of `mod foo;` declarations, the paths of `include!`-like
+/// macros and the URLs in doc comments. This only looks at the syntax.
+pub(crate) fn document_links(db: &RootDatabase, file_id: FileId) -> Vec<DocumentLink> {
+    let source_file = db.source_file(file_id);
+    let mut res = Vec::new();
+    for node in source_file.syntax().descendants() {
+        if let Some(module) = ast::Module::cast(node) {
+            res.extend(module_name(module).map(|range| DocumentLink {
+                range,
+                target: LinkTarget::File,
+            }));
+        } else if let Some(macro_call) = ast::MacroCall::cast(node) {
+            res.extend(include_path(macro_call).map(|(range, _)| DocumentLink {
+                range,
+                target: LinkTarget::File,
+            }));
+        } else if let Some(comment) = ast::Comment::cast(node) {
+            if comment.flavor().is_doc_comment() {
+                res.extend(url_links(node));
+            }
+        }
+    }
+    res
+}
+
+/// Finds the file of the `LinkTarget::File` link at `frange`.
+pub(crate) fn resolve_document_link(
+    db: &RootDatabase,
+    frange: FileRange,
+) -> Cancelable<Option<FileId>> {
+    let source_file = db.source_file(frange.file_id);
+    let node = find_covering_node(source_file.syntax(), frange.range);
+    for node in node.ancestors() {
+        if let Some(module) = ast::Module::cast(node) {
+            if module_name(module) != Some(frange.range) {
+                return Ok(None);
+            }
+            let module = ctry!(source_binder::module_from_declaration(
+                db,
+                frange.file_id,
+                module
+            )?);
+            let (target, _) = module.definition_source(db)?;
+            return Ok(Some(target));
+        }
+        if let Some(macro_call) = ast::MacroCall::cast(node) {
+            let path = match include_path(macro_call) {
+                Some((range, path)) if range == frange.range => path,
+                _ => return Ok(None),
+            };
+            return Ok(resolve_file_reference(db, frange.file_id, path));
+        }
+    }
+    Ok(None)
+}
+
+/// The range of the name of an out-of-line module declaration.
+fn module_name(module: &ast::Module) -> Option<TextRange> {
+    if module.item_list().is_some() {
+        return None;
+    }
+    Some(module.name()?.syntax().range())
+}
+
+/// The range and the text of the path of an `include!`-like macro.
+fn include_path(macro_call: &ast::MacroCall) -> Option<(TextRange, &str)> {
+    let name_ref = macro_call.path()?.segment()?.name_ref()?;
+    match name_ref.text().as_str() {
+        "include" | "include_str" | "include_bytes" => (),
+        _ => return None,
+    }
+    let string = macro_call
+        .token_tree()?
+        .syntax()
+        .children()
+        .find(|it| it.kind() == STRING)?;
+    let path = string.leaf_text()?.trim_matches('"');
+    Some((string.range(), path))
+}
+
+fn url_links(comment: &SyntaxNode) -> Vec<DocumentLink> {
+    let text = comment.leaf_text().unwrap().as_str();
+    let mut res = Vec::new();
+    let mut pos = 0;
+    while let Some(idx) = find_url_start(&text[pos..]) {
+        let start = pos + idx;
+        let len = url_len(&text[start..]);
+        let url = &text[start..start + len];
+        let offset = comment.range().start() + TextUnit::of_str(&text[..start]);
+        res.push(DocumentLink {
+            range: TextRange::offset_len(offset, TextUnit::of_str(url)),
+            target: LinkTarget::Url(url.to_string()),
+        });
+        pos = start + len;
+    }
+    res
+}
+
+fn find_url_start(text: &str) -> Option<usize> {
+    let http = text.find("http://");
+    let https = text.find("https://");
+    match (http, https) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The URL ends at whitespace or at a delimiter, like in `<https://a.b>`.
+/// Trailing punctuation belongs to the sentence.
+fn url_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || "<>()[]\"'`".contains(c))
+        .unwrap_or(text.len());
+    text[..end].trim_end_matches(|c| ".,;:!?".contains(c)).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::TextRange;
+
+    use crate::{
+        FileId, FileRange,
+        mock_analysis::MockAnalysis,
+        document_links::{DocumentLink, LinkTarget},
+    };
+
+    /// The links of `/lib.rs`, with the files of the `LinkTarget::File` ones.
+    fn links(fixture: &str) -> Vec<(DocumentLink, Option<FileId>)> {
+        let mock = MockAnalysis::with_files(fixture);
+        let file_id = mock.id_of("/lib.rs");
+        let analysis = mock.analysis();
+        analysis
+            .document_links(file_id)
+            .unwrap()
+            .into_iter()
+            .map(|link| {
+                let target = match link.target {
+                    LinkTarget::File => {
+                        let frange = FileRange { file_id, range: link.range };
+                        analysis.resolve_document_link(frange).unwrap()
+                    }
+                    LinkTarget::Url(_) => None,
+                };
+                (link, target)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn links_module_declarations_to_files() {
+        let links = links(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar {}
+            //- /foo.rs
+            fn foo() {}
+            ",
+        );
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].0.range, TextRange::from_to(4.into(), 7.into()));
+        assert_eq!(links[0].1, Some(FileId(2)));
+    }
+
+    #[test]
+    fn links_include_paths() {
+        let links = links(
+            r#"
+            //- /lib.rs
+            const DATA: &str = include_str!("data.txt");
+            //- /data.txt
+            hello
+            "#,
+        );
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].0.range, TextRange::from_to(32.into(), 42.into()));
+        assert_eq!(links[0].1, Some(FileId(2)));
+    }
+
+    #[test]
+    fn file_links_are_only_looked_up_on_resolve() {
+        let links = links(
+            r#"
+            //- /lib.rs
+            mod missing;
+            const DATA: &str = include_str!("missing.txt");
+            "#,
+        );
+        assert_eq!(links.len(), 2);
+        assert!(links.iter().all(|(link, target)| {
+            link.target == LinkTarget::File && target.is_none()
+        }));
+    }
+
+    #[test]
+    fn links_urls_in_doc_comments() {
+        let links = links(
+            "
+            //- /lib.rs
+            /// See <https://example.com/docs>, and http://example.org.
+            // Not a doc comment: https://example.net
+            fn foo() {}
+            ",
+        );
+        let targets = links.iter().map(|(it, _)| &it.target).collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                &LinkTarget::Url("https://example.com/docs".to_string()),
+                &LinkTarget::Url("http://example.org".to_string()),
+            ]
+        );
+        assert_eq!(links[0].0.range, TextRange::from_to(9.into(), 33.into()));
+    }
+}
@@ -0,0 +1,396 @@
+//! The format strings of `format!`, `println!` and the other formatting
+//! macros. The arguments of macro calls are not lowered to hir, so the
+//! interpolations like `{name}` are found here, and the names are resolved
+//! as if they were used at the position of the interpolation.
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_syntax::{
+    AstNode, SmolStr, SyntaxNode, TextRange, TextUnit,
+    SyntaxKind::{COMMA, EQ, IDENT, RAW_STRING, STRING},
+    algo::find_leaf_at_offset,
+    ast,
+};
+use hir::source_binder::{self, NameDefinition};
+
+use crate::{Diagnostic, FileId, Severity, db::RootDatabase};
+
+/// Macros which take a format string, along with the position of the format
+/// string among the arguments.
+const FORMAT_MACROS: &[(&str, usize)] = &[
+    ("format", 0),
+    ("format_args", 0),
+    ("print", 0),
+    ("println", 0),
+    ("eprint", 0),
+    ("eprintln", 0),
+    ("panic", 0),
+    ("write", 1),
+    ("writeln", 1),
+];
+
+/// An interpolation in a format string, like `{}`, `{0}` or `{name:?}`.
+#[derive(Debug)]
+pub(crate) struct FormatSpecifier {
+    /// The range of the whole interpolation, with the braces.
+    pub(crate) range: TextRange,
+    pub(crate) arg: FormatArg,
+}
+
+#[derive(Debug)]
+pub(crate) enum FormatArg {
+    /// `{}`, the next positional argument.
+    Next,
+    /// `{0}`, a positional argument.
+    Index(usize),
+    /// `{name}`, either an explicit `name = value` argument of the macro or a
+    /// value in scope. The range is the range of the name.
+    Named(SmolStr, TextRange),
+}
+
+/// The string literal with the format, like `"{}"` in `write!(f, "{}", x)`.
+pub(crate) fn format_string(macro_call: &ast::MacroCall) -> Option<&SyntaxNode> {
+    let name_ref = macro_call.path()?.segment()?.name_ref()?;
+    let &(_, arg_idx) = FORMAT_MACROS
+        .iter()
+        .find(|(name, _)| *name == name_ref.text().as_str())?;
+    let token_tree = macro_call.token_tree()?;
+    let mut commas = 0;
+    for token in token_tree.syntax().children() {
+        match token.kind() {
+            COMMA => commas += 1,
+            STRING | RAW_STRING if commas == arg_idx => return Some(token),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// The characters of the string literal `literal` after unescaping, each with
+/// its range in the literal. This way, `"\u{e9}"` is a single `é` rather than
+/// a `{e9}` placeholder.
+fn literal_chars(literal: &SyntaxNode) -> Vec<(char, TextRange)> {
+    let text = match literal.leaf_text() {
+        Some(it) => it.as_str(),
+        None => return Vec::new(),
+    };
+    let offset = literal.range().start();
+    let range = |start: usize, end: usize| {
+        TextRange::from_to(
+            offset + TextUnit::from_usize(start),
+            offset + TextUnit::from_usize(end),
+        )
+    };
+    let mut res = Vec::new();
+    if literal.kind() == RAW_STRING {
+        // `r#"..."#` has no escapes.
+        let hashes = text[1..].len() - text[1..].trim_start_matches('#').len();
+        let start = (2 + hashes).min(text.len());
+        let end = text.len().saturating_sub(1 + hashes).max(start);
+        for (idx, c) in text[start..end].char_indices() {
+            res.push((c, range(start + idx, start + idx + c.len_utf8())));
+        }
+        return res;
+    }
+    // Skip the quotes, the closing one might not be typed yet.
+    let start = 1;
+    let end = if text.len() > 1 && text.ends_with('"') {
+        text.len() - 1
+    } else {
+        text.len()
+    };
+    let mut chars = text[start..end].char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let c_start = start + idx;
+        if c != '\\' {
+            res.push((c, range(c_start, c_start + c.len_utf8())));
+            continue;
+        }
+        let (idx, escape) = match chars.next() {
+            Some(it) => it,
+            None => break,
+        };
+        let mut c_end = start + idx + escape.len_utf8();
+        let value = match escape {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            '\\' | '\'' | '"' => Some(escape),
+            'x' => {
+                let mut digits = String::new();
+                while digits.len() < 2 {
+                    match chars.peek() {
+                        Some(&(idx, d)) if d.is_ascii_hexdigit() => {
+                            digits.push(d);
+                            c_end = start + idx + 1;
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                u8::from_str_radix(&digits, 16).ok().map(char::from)
+            }
+            'u' => {
+                let mut digits = String::new();
+                for (idx, d) in chars.by_ref() {
+                    c_end = start + idx + d.len_utf8();
+                    match d {
+                        '}' => break,
+                        '{' | '_' => (),
+                        _ => digits.push(d),
+                    }
+                }
+                u32::from_str_radix(&digits, 16).ok().and_then(std::char::from_u32)
+            }
+            // A line continuation skips the leading whitespace of the next line.
+            '\n' => {
+                while chars.peek().map_or(false, |&(_, d)| d.is_whitespace()) {
+                    chars.next();
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(value) = value {
+            res.push((value, range(c_start, c_end)));
+        }
+    }
+    res
+}
+
+/// The interpolations in the format string `string`. `{{` and `}}` are
+/// escaped braces.
+pub(crate) fn format_specifiers(string: &SyntaxNode) -> Vec<FormatSpecifier> {
+    let chars = literal_chars(string);
+    let mut res = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos].0;
+        match c {
+            '{' | '}' if chars.get(pos + 1).map(|it| it.0) == Some(c) => {
+                pos += 2;
+                continue;
+            }
+            '{' => (),
+            _ => {
+                pos += 1;
+                continue;
+            }
+        }
+        let end = match chars[pos..].iter().position(|it| it.0 == '}') {
+            Some(it) => pos + it,
+            None => break,
+        };
+        let spec = &chars[pos + 1..end];
+        let arg_len = spec.iter().position(|it| it.0 == ':').unwrap_or(spec.len());
+        let arg_chars = &spec[..arg_len];
+        let arg_text = arg_chars.iter().map(|it| it.0).collect::<String>();
+        let arg = if arg_text.is_empty() {
+            Some(FormatArg::Next)
+        } else if arg_text.chars().all(|c| c.is_ascii_digit()) {
+            arg_text.parse().ok().map(FormatArg::Index)
+        } else if is_identifier(&arg_text) {
+            let range = TextRange::from_to(arg_chars[0].1.start(), arg_chars[arg_len - 1].1.end());
+            Some(FormatArg::Named(arg_text.into(), range))
+        } else {
+            None
+        };
+        if let Some(arg) = arg {
+            res.push(FormatSpecifier {
+                range: TextRange::from_to(chars[pos].1.start(), chars[end].1.end()),
+                arg,
+            });
+        }
+        pos = end + 1;
+    }
+    res
+}
+
+/// Whether `offset` is right after the `{` of a placeholder of the format
+/// string `string`, or inside its argument name.
+pub(crate) fn is_in_placeholder_arg(string: &SyntaxNode, offset: TextUnit) -> bool {
+    let prefix = literal_chars(string)
+        .into_iter()
+        .take_while(|(_, range)| range.end() <= offset)
+        .map(|(c, _)| c)
+        .collect::<Vec<_>>();
+    // Skip the part of the argument name which is already typed.
+    let typed = prefix
+        .iter()
+        .rev()
+        .take_while(|&&c| c == '_' || c.is_alphanumeric())
+        .count();
+    let prefix = &prefix[..prefix.len() - typed];
+    // `{{` is an escaped brace rather than a placeholder.
+    let open_braces = prefix.iter().rev().take_while(|&&c| c == '{').count();
+    open_braces % 2 == 1
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map_or(false, |c| c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
+/// The names of the explicit `name = value` arguments of the macro call.
+pub(crate) fn explicit_names(macro_call: &ast::MacroCall) -> Vec<SmolStr> {
+    let token_tree = match macro_call.token_tree() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let tokens = token_tree
+        .syntax()
+        .children()
+        .filter(|it| !it.kind().is_trivia())
+        .collect::<Vec<_>>();
+    tokens
+        .windows(3)
+        .filter(|it| it[0].kind() == COMMA && it[1].kind() == IDENT && it[2].kind() == EQ)
+        .filter_map(|it| it[1].leaf_text().cloned())
+        .collect()
+}
+
+/// The named interpolation at `offset`, together with its macro call, if it
+/// is not one of the explicit arguments of the macro.
+pub(crate) fn named_arg_at_offset(
+    root: &SyntaxNode,
+    offset: TextUnit,
+) -> Option<(&ast::MacroCall, SmolStr, TextRange)> {
+    let string = find_leaf_at_offset(root, offset).find(|it| it.kind() == STRING)?;
+    let macro_call = string.ancestors().find_map(ast::MacroCall::cast)?;
+    if format_string(macro_call) != Some(string) {
+        return None;
+    }
+    let (name, range) = format_specifiers(string)
+        .into_iter()
+        .filter_map(|it| match it.arg {
+            FormatArg::Named(name, range) => Some((name, range)),
+            _ => None,
+        })
+        .find(|(_, range)| range.start() <= offset && offset <= range.end())?;
+    if explicit_names(macro_call).contains(&name) {
+        return None;
+    }
+    Some((macro_call, name, range))
+}
+
+/// Resolves the named interpolation at `range` to a local or a constant.
+pub(crate) fn resolve_named_arg(
+    db: &RootDatabase,
+    file_id: FileId,
+    macro_call: &ast::MacroCall,
+    name: &str,
+    range: TextRange,
+) -> Cancelable<Option<NameDefinition>> {
+    source_binder::resolve_value_at_offset(
+        db,
+        file_id,
+        macro_call.syntax(),
+        range.start(),
+        name,
+    )
+}
+
+/// Reports the named interpolations which refer neither to an explicit
+/// argument nor to a value in scope.
+pub(crate) fn unresolved_named_args(
+    db: &RootDatabase,
+    file_id: FileId,
+) -> Cancelable<Vec<Diagnostic>> {
+    let file = db.source_file(file_id);
+    let mut res = Vec::new();
+    for macro_call in file.syntax().descendants().filter_map(ast::MacroCall::cast) {
+        let string = match format_string(macro_call) {
+            Some(it) => it,
+            None => continue,
+        };
+        let explicit_names = explicit_names(macro_call);
+        for spec in format_specifiers(string) {
+            let (name, range) = match spec.arg {
+                FormatArg::Named(name, range) => (name, range),
+                _ => continue,
+            };
+            if explicit_names.contains(&name)
+                || resolve_named_arg(db, file_id, macro_call, &name, range)?.is_some()
+            {
+                continue;
+            }
+            res.push(Diagnostic {
+                range,
+                message: format!("cannot find value `{}` in this scope", name),
+                severity: Severity::Error,
+                fixes: Vec::new(),
+                related: Vec::new(),
+            });
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::SourceFile;
+
+    use super::*;
+
+    #[test]
+    fn parses_format_specifiers() {
+        let text = r#"fn main() { println!("{{}} {} {0:?} {name:>8} {x.y} }}", a); }"#;
+        let file = SourceFile::parse(text);
+        let macro_call = file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let string = format_string(macro_call).unwrap();
+        let actual = format_specifiers(string)
+            .into_iter()
+            .map(|it| match it.arg {
+                FormatArg::Named(name, range) => {
+                    format!("{} {} {}", &text[it.range], name, &text[range])
+                }
+                arg => format!("{} {:?}", &text[it.range], arg),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual, vec!["{} Next", "{0:?} Index(0)", "{name:>8} name name"]);
+    }
+
+    #[test]
+    fn parses_format_specifiers_after_unescaping() {
+        let text = r#"fn main() { println!("\u{e9} {x} \x7b\x7b {\x79} \"{}\"", z); }"#;
+        let file = SourceFile::parse(text);
+        let macro_call = file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let string = format_string(macro_call).unwrap();
+        let actual = format_specifiers(string)
+            .into_iter()
+            .map(|it| match it.arg {
+                FormatArg::Named(name, range) => {
+                    format!("{} {} {}", &text[it.range], name, &text[range])
+                }
+                arg => format!("{} {:?}", &text[it.range], arg),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual, vec!["{x} x x", r"{\x79} y \x79", "{} Next"]);
+    }
+
+    #[test]
+    fn finds_the_format_string_among_the_arguments() {
+        let text = r##"fn main() { writeln!("{e9}", r#"{}"#, x); }"##;
+        let file = SourceFile::parse(text);
+        let macro_call = file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let string = format_string(macro_call).unwrap();
+        assert_eq!(string.text().to_string(), r##"r#"{}"#"##);
+        let specs = format_specifiers(string);
+        assert_eq!(specs.len(), 1);
+        assert_eq!(&text[specs[0].range], "{}");
+    }
+}
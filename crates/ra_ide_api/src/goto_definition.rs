@@ -1,18 +1,33 @@
-use ra_db::{FileId, Cancelable, SyntaxDatabase};
+use ra_db::{FileId, Cancelable, FilesDatabase, SyntaxDatabase};
 use ra_syntax::{
-    AstNode, ast,
-    algo::find_node_at_offset,
+    AstNode, SyntaxNode, ast,
+    algo::{find_leaf_at_offset, find_node_at_offset},
+    SyntaxKind::{LIFETIME, STRING},
 };
+use relative_path::RelativePath;
+use hir::{Def, source_binder::{self, NameDefinition}};
 
-use crate::{FilePosition, NavigationTarget, db::RootDatabase, RangeInfo};
+use crate::{
+    FilePosition, NavigationTarget, db::RootDatabase, RangeInfo, builtin_type::BuiltinType,
+    format_string,
+};
 
+/// With `follow_reexports`, paths like `foo::Bar` navigate to the definition
+/// of `Bar`, even if `foo` only re-exports it. Otherwise, they navigate to the
+/// `pub use` in `foo`.
 pub(crate) fn goto_definition(
     db: &RootDatabase,
     position: FilePosition,
+    follow_reexports: bool,
 ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
     let file = db.source_file(position.file_id);
     let syntax = file.syntax();
     if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
+        if !follow_reexports {
+            if let Some(nav) = reexport_definition(db, position.file_id, name_ref)? {
+                return Ok(Some(RangeInfo::new(name_ref.syntax().range(), vec![nav])));
+            }
+        }
         let navs = reference_definition(db, position.file_id, name_ref)?;
         return Ok(Some(RangeInfo::new(name_ref.syntax().range(), navs)));
     }
@@ -20,41 +35,83 @@ pub(crate) fn goto_definition(
         let navs = ctry!(name_definition(db, position.file_id, name)?);
         return Ok(Some(RangeInfo::new(name.syntax().range(), navs)));
     }
+    let lifetime = find_leaf_at_offset(syntax, position.offset).find(|it| it.kind() == LIFETIME);
+    if let Some(lifetime) = lifetime {
+        let def = ctry!(source_binder::def_for_node(db, position.file_id, lifetime)?);
+        let nav = ctry!(NavigationTarget::from_name_definition(db, position.file_id, def)?);
+        return Ok(Some(RangeInfo::new(lifetime.range(), vec![nav])));
+    }
+    let named_arg = format_string::named_arg_at_offset(syntax, position.offset);
+    if let Some((macro_call, name, range)) = named_arg {
+        let def = ctry!(format_string::resolve_named_arg(
+            db,
+            position.file_id,
+            macro_call,
+            &name,
+            range
+        )?);
+        let nav = ctry!(NavigationTarget::from_name_definition(db, position.file_id, def)?);
+        return Ok(Some(RangeInfo::new(range, vec![nav])));
+    }
+    let string = find_leaf_at_offset(syntax, position.offset).find(|it| it.kind() == STRING);
+    if let Some(string) = string {
+        let nav = ctry!(file_reference_definition(db, position.file_id, string)?);
+        return Ok(Some(RangeInfo::new(string.range(), vec![nav])));
+    }
     Ok(None)
 }
 
+/// Navigates to where the name at `position` is brought into scope, rather
+/// than to its definition: an imported name goes to its `use` item, and a
+/// module goes to its `mod foo;` declaration instead of to the file.
+pub(crate) fn goto_declaration(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
+    let file = db.source_file(position.file_id);
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+        if let Some(nav) = declaration(db, position.file_id, name_ref)? {
+            return Ok(Some(RangeInfo::new(name_ref.syntax().range(), vec![nav])));
+        }
+    }
+    goto_definition(db, position, true)
+}
+
+fn declaration(
+    db: &RootDatabase,
+    file_id: FileId,
+    name_ref: &ast::NameRef,
+) -> Cancelable<Option<NavigationTarget>> {
+    if let Some(nav) = reexport_definition(db, file_id, name_ref)? {
+        return Ok(Some(nav));
+    }
+    match source_binder::def_for_node(db, file_id, name_ref.syntax())? {
+        Some(NameDefinition::Item(Def::Module(module))) => {
+            let nav = NavigationTarget::from_module_to_decl(db, module)?;
+            Ok(Some(nav))
+        }
+        _ => Ok(None),
+    }
+}
+
 pub(crate) fn reference_definition(
     db: &RootDatabase,
     file_id: FileId,
     name_ref: &ast::NameRef,
 ) -> Cancelable<Vec<NavigationTarget>> {
-    if let Some(fn_descr) =
-        hir::source_binder::function_from_child_node(db, file_id, name_ref.syntax())?
-    {
-        let scope = fn_descr.scopes(db)?;
-        // First try to resolve the symbol locally
-        if let Some(entry) = scope.resolve_local_name(name_ref) {
-            let nav = NavigationTarget::from_scope_entry(file_id, &entry);
-            return Ok(vec![nav]);
-        };
-    }
-    // Then try module name resolution
-    if let Some(module) =
-        hir::source_binder::module_from_child_node(db, file_id, name_ref.syntax())?
-    {
-        if let Some(path) = name_ref
-            .syntax()
-            .ancestors()
-            .find_map(ast::Path::cast)
-            .and_then(hir::Path::from_ast)
-        {
-            let resolved = module.resolve_path(db, &path)?;
-            if let Some(def_id) = resolved.take_types().or(resolved.take_values()) {
-                if let Some(target) = NavigationTarget::from_def(db, def_id.resolve(db)?)? {
-                    return Ok(vec![target]);
-                }
+    if let Some(def) = source_binder::def_for_node(db, file_id, name_ref.syntax())? {
+        if let NameDefinition::Method(function) = &def {
+            let navs = trait_method_implementations(db, function)?;
+            if !navs.is_empty() {
+                return Ok(navs);
             }
         }
+        if let Some(nav) = NavigationTarget::from_name_definition(db, file_id, def)? {
+            return Ok(vec![nav]);
+        }
+    }
+    if let Some(builtin) = BuiltinType::from_name_ref(name_ref) {
+        return Ok(builtin.navigation_target(db)?.into_iter().collect());
     }
     // If that fails try the index based approach.
     let navs = db
@@ -65,24 +122,103 @@ pub(crate) fn reference_definition(
     Ok(navs)
 }
 
+fn reexport_definition(
+    db: &RootDatabase,
+    file_id: FileId,
+    name_ref: &ast::NameRef,
+) -> Cancelable<Option<NavigationTarget>> {
+    match source_binder::def_for_node(db, file_id, name_ref.syntax())? {
+        Some(NameDefinition::Item(_)) => (),
+        _ => return Ok(None),
+    }
+    let import = ctry!(source_binder::import_for_name_ref(db, file_id, name_ref)?);
+    let nav = NavigationTarget::from_import(db, name_ref.text().clone(), &import);
+    Ok(Some(nav))
+}
+
+/// When a method call resolves to a trait method, like for `dyn Trait`
+/// receivers, the implementations are more interesting than the declaration.
+/// They are looked up in the crate of the trait and in the crates which depend
+/// on it, as no other crate can implement the trait. With several
+/// implementations, the client lets the user pick one.
+fn trait_method_implementations(
+    db: &RootDatabase,
+    function: &hir::Function,
+) -> Cancelable<Vec<NavigationTarget>> {
+    if function.containing_trait(db)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let krate = match function.krate(db)? {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let mut res = Vec::new();
+    for krate in krate.transitive_reverse_dependencies(db).iter() {
+        for method in function.implementations(db, krate)? {
+            res.extend(NavigationTarget::from_def(db, Def::Function(method))?);
+        }
+    }
+    Ok(res)
+}
+
 fn name_definition(
     db: &RootDatabase,
     file_id: FileId,
     name: &ast::Name,
 ) -> Cancelable<Option<Vec<NavigationTarget>>> {
-    if let Some(module) = name.syntax().parent().and_then(ast::Module::cast) {
-        if module.has_semi() {
-            if let Some(child_module) =
-                hir::source_binder::module_from_declaration(db, file_id, module)?
-            {
-                let nav = NavigationTarget::from_module(db, child_module)?;
-                return Ok(Some(vec![nav]));
-            }
+    let def = ctry!(source_binder::def_for_node(db, file_id, name.syntax())?);
+    let nav = ctry!(NavigationTarget::from_name_definition(db, file_id, def)?);
+    Ok(Some(vec![nav]))
+}
+
+/// Navigates from the path in `include!("foo.rs")` or in `#[path = "foo.rs"]`
+/// to the file it refers to.
+fn file_reference_definition(
+    db: &RootDatabase,
+    file_id: FileId,
+    string: &SyntaxNode,
+) -> Cancelable<Option<NavigationTarget>> {
+    let owner = ctry!(string.parent().and_then(|tt| tt.parent()));
+    if let Some(macro_call) = ast::MacroCall::cast(owner) {
+        let name_ref = ctry!(macro_call
+            .path()
+            .and_then(|it| it.segment())
+            .and_then(|it| it.name_ref()));
+        match name_ref.text().as_str() {
+            "include" | "include_str" | "include_bytes" => (),
+            _ => return Ok(None),
         }
+        let path = string.leaf_text().unwrap().trim_matches('"');
+        let target = ctry!(resolve_file_reference(db, file_id, path));
+        return Ok(Some(NavigationTarget::from_file(db, target)));
+    }
+    if let Some(attr) = ast::Attr::cast(owner) {
+        let (key, _path) = ctry!(attr.as_key_value());
+        if key != "path" {
+            return Ok(None);
+        }
+        // The path is resolved together with the rest of the module tree.
+        let decl = ctry!(attr.syntax().parent().and_then(ast::Module::cast));
+        let module = ctry!(source_binder::module_from_declaration(db, file_id, decl)?);
+        return NavigationTarget::from_module(db, module).map(Some);
     }
     Ok(None)
 }
 
+/// Finds the file which `path`, as written in `file_id`, refers to. Like for
+/// `include!`, the path is relative to the directory of `file_id`.
+pub(crate) fn resolve_file_reference(
+    db: &RootDatabase,
+    file_id: FileId,
+    path: &str,
+) -> Option<FileId> {
+    let source_root = db.source_root(db.file_source_root(file_id));
+    let file_path = db.file_relative_path(file_id);
+    let dir = file_path.parent().unwrap_or_else(|| RelativePath::new(""));
+    let path = dir.join(path).normalize();
+    source_root.files.get(&path).cloned()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::mock_analysis::analysis_and_position;
@@ -96,6 +232,24 @@ mod tests {
         nav.assert_match(expected);
     }
 
+    fn check_goto_reexport(fixuture: &str, expected: &str) {
+        let (analysis, pos) = analysis_and_position(fixuture);
+
+        let mut navs = analysis.goto_reexport(pos).unwrap().unwrap().info;
+        assert_eq!(navs.len(), 1);
+        let nav = navs.pop().unwrap();
+        nav.assert_match(expected);
+    }
+
+    fn check_goto_declaration(fixuture: &str, expected: &str) {
+        let (analysis, pos) = analysis_and_position(fixuture);
+
+        let mut navs = analysis.goto_declaration(pos).unwrap().unwrap().info;
+        assert_eq!(navs.len(), 1);
+        let nav = navs.pop().unwrap();
+        nav.assert_match(expected);
+    }
+
     #[test]
     fn goto_definition_works_in_items() {
         check_goto(
@@ -108,6 +262,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_definition_works_for_named_format_args() {
+        check_goto(
+            r#"
+            //- /lib.rs
+            fn foo(x: u32) {
+                let name = x;
+                println!("{} {na<|>me:?}", x);
+            }
+            "#,
+            "name NAME FileId(1) [25; 29)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_maps_macro_expansions_back() {
+        check_goto(
+            "
+            //- /lib.rs
+            salsa::query_group! {
+                trait Baz {}
+            }
+            struct S(Baz<|>);
+            ",
+            "Baz TRAIT_DEF FileId(1) [0; 40) [32; 35)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_works_across_crates() {
+        check_goto(
+            "
+            //- /main.rs crate:main deps:other
+            fn main() { other::foo<|>(); }
+            //- /other/lib.rs crate:other
+            pub fn foo() {}
+            ",
+            "foo FN_DEF FileId(2) [0; 15) [7; 10)",
+        );
+    }
+
     #[test]
     fn goto_definition_resolves_correct_name() {
         check_goto(
@@ -126,6 +321,123 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_definition_works_for_fields() {
+        check_goto(
+            "
+            //- /lib.rs
+            struct Foo { spam: u32 }
+            fn bar(foo: &Foo) { foo.spam<|>; }
+            ",
+            "spam NAMED_FIELD_DEF FileId(1) [13; 22) [13; 17)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_works_for_methods() {
+        check_goto(
+            "
+            //- /lib.rs
+            struct Foo;
+            impl Foo { fn frobnicate(&self) {} }
+            fn bar(foo: Foo) { foo.frobnicate<|>(); }
+            ",
+            "frobnicate FN_DEF FileId(1) [23; 46) [26; 36)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_prefers_the_single_trait_impl() {
+        check_goto(
+            "
+            //- /lib.rs
+            trait Shape { fn area(&self) -> u32; }
+            struct Circle;
+            impl Shape for Circle { fn area(&self) -> u32 { 0 } }
+            fn foo(s: &dyn Shape) { s.area<|>(); }
+            ",
+            "area FN_DEF FileId(1) [78; 105) [81; 85)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_lists_all_trait_impls() {
+        let (analysis, pos) = analysis_and_position(
+            "
+            //- /lib.rs
+            trait Shape { fn area(&self) -> u32; }
+            struct Circle;
+            struct Square;
+            impl Shape for Circle { fn area(&self) -> u32 { 0 } }
+            impl Shape for Square { fn area(&self) -> u32 { 1 } }
+            fn foo(s: &dyn Shape) { s.area<|>(); }
+            ",
+        );
+        let navs = analysis.goto_definition(pos).unwrap().unwrap().info;
+        let navs = navs.iter().map(|it| it.debug_render()).collect::<Vec<_>>();
+        assert_eq!(
+            navs,
+            vec![
+                "area FN_DEF FileId(1) [93; 120) [96; 100)",
+                "area FN_DEF FileId(1) [147; 174) [150; 154)",
+            ]
+        );
+    }
+
+    #[test]
+    fn goto_definition_finds_trait_impls_in_dependent_crates() {
+        check_goto(
+            "
+            //- /shapes/lib.rs crate:shapes
+            pub trait Shape { fn area(&self) -> u32; }
+            fn total(s: &dyn Shape) -> u32 { s.area<|>() }
+            //- /main.rs crate:main deps:shapes
+            struct Circle;
+            impl shapes::Shape for Circle { fn area(&self) -> u32 { 0 } }
+            ",
+            "area FN_DEF FileId(2) [47; 74) [50; 54)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_resolves_trait_methods_of_concrete_types() {
+        check_goto(
+            "
+            //- /lib.rs
+            trait Shape { fn area(&self) -> u32; }
+            struct Circle;
+            impl Shape for Circle { fn area(&self) -> u32 { 0 } }
+            fn foo(c: Circle) { c.area<|>(); }
+            ",
+            "area FN_DEF FileId(1) [78; 105) [81; 85)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_falls_back_to_default_trait_methods() {
+        check_goto(
+            "
+            //- /lib.rs
+            trait Shape { fn area(&self) -> u32 { 0 } }
+            struct Circle;
+            impl Shape for Circle {}
+            fn foo(s: &dyn Shape) { s.area<|>(); }
+            ",
+            "area FN_DEF FileId(1) [14; 41) [17; 21)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_works_for_lifetimes() {
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo<'a>(x: &'a<|> u32) {}
+            ",
+            "'a LIFETIME_PARAM FileId(1) [7; 9)",
+        );
+    }
+
     #[test]
     fn goto_definition_works_for_module_declaration() {
         check_goto(
@@ -148,4 +460,116 @@ mod tests {
             "foo SOURCE_FILE FileId(2) [0; 10)",
         );
     }
+
+    #[test]
+    fn goto_definition_works_for_include_paths() {
+        check_goto(
+            r#"
+            //- /lib.rs
+            include!("gen/<|>foo.rs");
+            //- /gen/foo.rs
+            // empty
+            "#,
+            "foo.rs SOURCE_FILE FileId(2) [0; 10)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_works_for_path_attributes() {
+        check_goto(
+            r#"
+            //- /lib.rs
+            #[path = "bar/<|>baz.rs"]
+            mod foo;
+            //- /bar/baz.rs
+            // empty
+            "#,
+            "foo SOURCE_FILE FileId(2) [0; 10)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_follows_reexports() {
+        check_goto(
+            "
+            //- /lib.rs
+            mod foo;
+            fn main() { foo::Bar<|>; }
+            //- /foo.rs
+            pub use self::bar::Bar;
+            mod bar {
+                pub struct Bar;
+            }
+            ",
+            "Bar STRUCT_DEF FileId(2) [38; 53) [49; 52)",
+        );
+    }
+
+    #[test]
+    fn goto_reexport_stops_at_the_use_item() {
+        check_goto_reexport(
+            "
+            //- /lib.rs
+            mod foo;
+            fn main() { foo::Bar<|>; }
+            //- /foo.rs
+            pub use self::bar::Bar;
+            mod bar {
+                pub struct Bar;
+            }
+            ",
+            "Bar USE_ITEM FileId(2) [0; 23) [19; 22)",
+        );
+        check_goto_reexport(
+            "
+            //- /lib.rs
+            mod foo;
+            fn main() { foo::Bar<|>; }
+            //- /foo.rs
+            pub struct Bar;
+            ",
+            "Bar STRUCT_DEF FileId(2) [0; 15) [11; 14)",
+        );
+    }
+
+    #[test]
+    fn goto_declaration_of_a_module_is_its_mod_item() {
+        check_goto_declaration(
+            "
+            //- /lib.rs
+            mod foo;
+            fn main() { foo<|>::bar(); }
+            //- /foo.rs
+            pub fn bar() {}
+            ",
+            "foo MODULE FileId(1) [0; 8) [4; 7)",
+        );
+    }
+
+    #[test]
+    fn goto_declaration_of_an_import_is_its_use_item() {
+        check_goto_declaration(
+            "
+            //- /lib.rs
+            mod foo;
+            use crate::foo::Bar;
+            fn main() { Bar<|>; }
+            //- /foo.rs
+            pub struct Bar;
+            ",
+            "Bar USE_ITEM FileId(1) [9; 29) [25; 28)",
+        );
+    }
+
+    #[test]
+    fn goto_declaration_falls_back_to_the_definition() {
+        check_goto_declaration(
+            "
+            //- /lib.rs
+            struct Foo;
+            fn main() { Foo<|>; }
+            ",
+            "Foo STRUCT_DEF FileId(1) [0; 11) [7; 10)",
+        );
+    }
 }
@@ -1,28 +1,143 @@
-use ra_db::{Cancelable, SyntaxDatabase};
+use ra_db::{Cancelable, FilesDatabase, SyntaxDatabase};
 use ra_syntax::{
     AstNode, SyntaxNode, TreeArc,
-    ast::{self, NameOwner},
+    SyntaxKind::SOURCE_FILE,
+    ast::{self, NameOwner, DocCommentsOwner},
     algo::{find_covering_node, find_node_at_offset, find_leaf_at_offset, visit::{visitor, Visitor}},
 };
+use hir::source_binder::{self, NameDefinition};
 
-use crate::{db::RootDatabase, RangeInfo, FilePosition, FileRange, NavigationTarget};
+use crate::{
+    db::RootDatabase, RangeInfo, FileId, FilePosition, FileRange, NavigationTarget,
+    builtin_type::BuiltinType,
+    format_string, implemented_traits,
+    navigation_target::module_docs,
+};
+
+/// The contents of a hover popup.
+#[derive(Debug, Default)]
+pub struct HoverResult {
+    results: Vec<String>,
+    preview: Option<String>,
+}
+
+impl HoverResult {
+    /// Markdown blocks, like the signature and the docs of the definition, or
+    /// the type of the expression.
+    pub fn results(&self) -> &[String] {
+        &self.results
+    }
+
+    /// The line of the definition with its name, like
+    /// `pub fn frobnicate(timeout: u32) -> bool {`, for peek-style popups.
+    pub fn preview(&self) -> Option<&str> {
+        self.preview.as_ref().map(|it| it.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// All blocks as a single markdown string, with the preview on top.
+    pub fn to_markup(&self) -> String {
+        let preview = self.preview.as_ref().map(|it| format!("```rust\n{}\n```", it));
+        preview
+            .into_iter()
+            .chain(self.results.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("\n\n---\n")
+    }
+}
+
+/// What is shown in the hover popup, in addition to the docs and the types.
+#[derive(Debug, Clone)]
+pub struct HoverConfig {
+    /// The "Implements: ..." section for structs and enums.
+    pub implemented_traits: bool,
+    /// How many of the implemented traits are listed. The rest are only
+    /// counted, which keeps the popup small for types with many impls.
+    pub max_implemented_traits: usize,
+}
+
+impl Default for HoverConfig {
+    fn default() -> HoverConfig {
+        HoverConfig {
+            implemented_traits: true,
+            max_implemented_traits: 10,
+        }
+    }
+}
 
 pub(crate) fn hover(
     db: &RootDatabase,
     position: FilePosition,
-) -> Cancelable<Option<RangeInfo<String>>> {
+    config: &HoverConfig,
+) -> Cancelable<Option<RangeInfo<HoverResult>>> {
     let file = db.source_file(position.file_id);
-    let mut res = Vec::new();
+    let mut res = HoverResult::default();
 
     let mut range = None;
     if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
         let navs = crate::goto_definition::reference_definition(db, position.file_id, name_ref)?;
         for nav in navs {
-            res.extend(doc_text_for(db, nav)?)
+            let preview = definition_preview(db, &nav);
+            if let Some(docs) = doc_text_for(db, nav)? {
+                res.results.push(docs);
+                res.preview = res.preview.or(preview);
+            }
         }
+        if res.is_empty() {
+            if let Some(builtin) = BuiltinType::from_name_ref(name_ref) {
+                res.results.push(builtin.hover_text());
+            }
+        }
+        res.results.extend(trait_info(db, position.file_id, name_ref.syntax())?);
+        res.results.extend(implements_info(db, position.file_id, name_ref.syntax(), config)?);
         if !res.is_empty() {
             range = Some(name_ref.syntax().range())
         }
+    } else if let Some(name) = find_node_at_offset::<ast::Name>(file.syntax(), position.offset) {
+        // On `mod foo;`, show the docs of the declaration together with the
+        // `//!` docs from the file of the module.
+        if let Some(decl) = name.syntax().parent().and_then(ast::Module::cast) {
+            if decl.has_semi() {
+                if let Some(docs) = module_docs(db, position.file_id, decl)? {
+                    res.results.push(format!("```rust\nmod {}\n```\n\n{}", name.text(), docs));
+                }
+            }
+        }
+        if let Some(parent) = name.syntax().parent() {
+            if ast::TraitDef::cast(parent).is_some() {
+                res.results.extend(trait_info(db, position.file_id, name.syntax())?);
+            }
+            if ast::StructDef::cast(parent).is_some() || ast::EnumDef::cast(parent).is_some() {
+                let info = implements_info(db, position.file_id, name.syntax(), config)?;
+                res.results.extend(info);
+            }
+        }
+        if !res.is_empty() {
+            range = Some(name.syntax().range())
+        }
+    }
+    if range.is_none() {
+        let named_arg = format_string::named_arg_at_offset(file.syntax(), position.offset);
+        if let Some((macro_call, name, name_range)) = named_arg {
+            let def = format_string::resolve_named_arg(
+                db,
+                position.file_id,
+                macro_call,
+                &name,
+                name_range,
+            )?;
+            if let Some(NameDefinition::Local(_, entry)) = def {
+                let frange = FileRange {
+                    file_id: position.file_id,
+                    range: entry.ptr().range(),
+                };
+                res.results.extend(type_of(db, frange)?);
+                range = Some(name_range);
+            }
+        }
     }
     if range.is_none() {
         let node = find_leaf_at_offset(file.syntax(), position.offset).find_map(|leaf| {
@@ -34,7 +149,7 @@ pub(crate) fn hover(
             file_id: position.file_id,
             range: node.range(),
         };
-        res.extend(type_of(db, frange)?);
+        res.results.extend(type_of(db, frange)?);
         range = Some(node.range());
     };
 
@@ -42,20 +157,14 @@ pub(crate) fn hover(
     if res.is_empty() {
         return Ok(None);
     }
-    let res = RangeInfo::new(range, res.join("\n\n---\n"));
+    let res = RangeInfo::new(range, res);
     Ok(Some(res))
 }
 
 pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Cancelable<Option<String>> {
     let file = db.source_file(frange.file_id);
     let syntax = file.syntax();
-    let leaf_node = find_covering_node(syntax, frange.range);
-    // if we picked identifier, expand to pattern/expression
-    let node = leaf_node
-        .ancestors()
-        .take_while(|it| it.range() == leaf_node.range())
-        .find(|&it| ast::Expr::cast(it).is_some() || ast::Pat::cast(it).is_some())
-        .unwrap_or(leaf_node);
+    let node = find_covering_node(syntax, frange.range);
     let parent_fn = ctry!(node.ancestors().find_map(ast::FnDef::cast));
     let function = ctry!(hir::source_binder::function_from_source(
         db,
@@ -64,13 +173,78 @@ pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Cancelable<Option
     )?);
     let infer = function.infer(db)?;
     let syntax_mapping = function.body_syntax_mapping(db)?;
-    if let Some(expr) = ast::Expr::cast(node).and_then(|e| syntax_mapping.node_expr(e)) {
-        Ok(Some(infer[expr].to_string()))
-    } else if let Some(pat) = ast::Pat::cast(node).and_then(|p| syntax_mapping.node_pat(p)) {
-        Ok(Some(infer[pat].to_string()))
+    let expr = syntax_mapping.expr_covering_range(frange.range);
+    let pat = syntax_mapping.pat_covering_range(frange.range);
+    let expr_len = expr
+        .and_then(|it| syntax_mapping.expr_syntax(it))
+        .map(|it| it.range().len());
+    let pat_len = pat
+        .and_then(|it| syntax_mapping.pat_syntax(it))
+        .map(|it| it.range().len());
+    // Expressions only end up inside of patterns as literals, so the pattern
+    // wins unless the expression is smaller.
+    match (expr, pat) {
+        (Some(expr), Some(_)) if expr_len < pat_len => Ok(Some(infer[expr].to_string())),
+        (_, Some(pat)) => Ok(Some(infer[pat].to_string())),
+        (Some(expr), None) => Ok(Some(infer[expr].to_string())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// The super-traits of the trait `node` refers to, and whether the trait can
+/// be used as `dyn Trait`.
+fn trait_info(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<String>> {
+    let trait_ = match source_binder::def_for_node(db, file_id, node)? {
+        Some(NameDefinition::Item(hir::Def::Trait(it))) => it,
+        _ => return Ok(None),
+    };
+    let mut lines = Vec::new();
+    let mut super_traits = Vec::new();
+    for super_trait in trait_.super_traits(db)? {
+        if let Some(name) = super_trait.name(db)? {
+            super_traits.push(format!("`{}`", name));
+        }
+    }
+    if !super_traits.is_empty() {
+        lines.push(format!("Super-traits: {}", super_traits.join(", ")));
+    }
+    let violations = trait_.object_safety_violations(db)?;
+    if violations.is_empty() {
+        lines.push("Object safe".to_string());
     } else {
-        Ok(None)
+        lines.push("Not object safe:".to_string());
+        lines.extend(violations.iter().map(|it| format!("- {}", it)));
+    }
+    Ok(Some(lines.join("\n")))
+}
+
+/// The traits implemented by the struct or the enum `node` refers to.
+fn implements_info(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+    config: &HoverConfig,
+) -> Cancelable<Option<String>> {
+    if !config.implemented_traits {
+        return Ok(None);
+    }
+    let traits = ctry!(implemented_traits::implemented_traits(db, file_id, node)?);
+    if traits.is_empty() {
+        return Ok(None);
+    }
+    let mut names = traits
+        .iter()
+        .take(config.max_implemented_traits)
+        .map(|it| format!("`{}`", it.name))
+        .collect::<Vec<_>>();
+    if traits.len() > config.max_implemented_traits {
+        names.push(format!("and {} more", traits.len() - config.max_implemented_traits));
     }
+    Ok(Some(format!("Implements: {}", names.join(", "))))
 }
 
 // FIXME: this should not really use navigation target. Rather, approximatelly
@@ -86,6 +260,24 @@ fn doc_text_for(db: &RootDatabase, nav: NavigationTarget) -> Cancelable<Option<S
     Ok(result)
 }
 
+/// The whole line with the name of the definition. Files of modules don't
+/// have such a line.
+fn definition_preview(db: &RootDatabase, nav: &NavigationTarget) -> Option<String> {
+    if nav.kind() == SOURCE_FILE {
+        return None;
+    }
+    let text = db.file_text(nav.file_id());
+    let offset = nav.focus_range().unwrap_or(nav.full_range()).start().to_usize();
+    let start = text[..offset].rfind('\n').map_or(0, |it| it + 1);
+    let end = text[offset..].find('\n').map_or(text.len(), |it| offset + it);
+    let line = text[start..end].trim();
+    if line.is_empty() {
+        None
+    } else {
+        Some(line.to_string())
+    }
+}
+
 impl NavigationTarget {
     fn node(&self, db: &RootDatabase) -> Option<TreeArc<SyntaxNode>> {
         let source_file = db.source_file(self.file_id());
@@ -108,12 +300,36 @@ impl NavigationTarget {
             }
         }
 
+        // Modules are documented both by the `///` comments on the
+        // declaration and by the `//!` comments inside.
+        fn module_docs(node: &ast::Module) -> Option<String> {
+            let inner = node.item_list().map(|it| it.module_doc_text());
+            let docs = Some(node.doc_comment_text())
+                .into_iter()
+                .chain(inner)
+                .filter(|it| !it.is_empty())
+                .collect::<Vec<_>>();
+            if docs.is_empty() {
+                None
+            } else {
+                Some(docs.join("\n\n"))
+            }
+        }
+
         visitor()
+            .visit(|node: &ast::SourceFile| {
+                let comments = node.module_doc_text();
+                if comments.is_empty() {
+                    None
+                } else {
+                    Some(comments)
+                }
+            })
             .visit(doc_comments::<ast::FnDef>)
             .visit(doc_comments::<ast::StructDef>)
             .visit(doc_comments::<ast::EnumDef>)
             .visit(doc_comments::<ast::TraitDef>)
-            .visit(doc_comments::<ast::Module>)
+            .visit(module_docs)
             .visit(doc_comments::<ast::TypeDef>)
             .visit(doc_comments::<ast::ConstDef>)
             .visit(doc_comments::<ast::StaticDef>)
@@ -175,7 +391,10 @@ impl NavigationTarget {
 #[cfg(test)]
 mod tests {
     use ra_syntax::TextRange;
-    use crate::mock_analysis::{single_file_with_position, single_file_with_range};
+    use super::HoverConfig;
+    use crate::mock_analysis::{
+        single_file_with_position, single_file_with_range, analysis_and_position,
+    };
 
     #[test]
     fn hover_shows_type_of_an_expression() {
@@ -188,23 +407,183 @@ mod tests {
             }
             ",
         );
-        let hover = analysis.hover(position).unwrap().unwrap();
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
         assert_eq!(hover.range, TextRange::from_to(95.into(), 100.into()));
-        assert_eq!(hover.info, "u32");
+        assert_eq!(hover.info.to_markup(), "u32");
+    }
+
+    #[test]
+    fn hover_shows_definition_preview() {
+        let (analysis, position) = single_file_with_position(
+            "
+            /// Returns one.
+            pub fn foo() -> u32 { 1 }
+
+            fn main() {
+                let foo_test = fo<|>o();
+            }
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(hover.range, TextRange::from_to(124.into(), 127.into()));
+        assert_eq!(hover.info.preview(), Some("pub fn foo() -> u32 { 1 }"));
+        assert_eq!(
+            hover.info.results(),
+            ["```rust\nfn foo\n```\n\nReturns one.".to_string()]
+        );
+    }
+
+    #[test]
+    fn hover_shows_description_of_builtin_types() {
+        let (analysis, position) = single_file_with_position(
+            "
+            fn foo(x: u3<|>2) {}
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            ["```rust\nu32\n```\n\nThe 32-bit unsigned integer type.".to_string()]
+        );
+
+        let (analysis, position) = single_file_with_position(
+            "
+            /// Not a primitive.
+            struct u32;
+            fn foo(x: u3<|>2) {}
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            ["```rust\nstruct u32\n```\n\nNot a primitive.".to_string()]
+        );
+    }
+
+    #[test]
+    fn hover_shows_super_traits_and_object_safety() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Base {}
+            trait Foo: Base {
+                fn foo(&self) -> u32;
+                fn new() -> Self where Self: Sized;
+            }
+            fn foo(x: &dyn Fo<|>o) {}
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            [
+                "```rust\ntrait Foo\n```".to_string(),
+                "Super-traits: `Base`\nObject safe".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn hover_shows_object_safety_violations() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Clone: Sized {}
+            trait Fo<|>o: Clone {
+                fn new() -> u32;
+                fn eq(&self, other: &Self) -> bool;
+                fn map<T>(&self, x: T);
+            }
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            [concat!(
+                "Super-traits: `Clone`\n",
+                "Not object safe:\n",
+                "- method `new` has no `self` parameter\n",
+                "- method `eq` references the `Self` type\n",
+                "- method `map` has generic type parameters\n",
+                "- super-trait `Clone` is not object safe",
+            )
+            .to_string()]
+        );
+    }
+
+    #[test]
+    fn hover_shows_implemented_traits() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Frobnicate {}
+            #[derive(Debug, Clone)]
+            enum Foo { A }
+            impl Frobnicate for Foo {}
+            fn foo(x: Fo<|>o) {}
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            [
+                "```rust\nenum Foo\n```".to_string(),
+                "Implements: `Clone`, `Debug`, `Frobnicate`".to_string(),
+            ]
+        );
+
+        let config = HoverConfig {
+            max_implemented_traits: 1,
+            ..HoverConfig::default()
+        };
+        let hover = analysis.hover(position, &config).unwrap().unwrap();
+        assert_eq!(hover.info.results()[1], "Implements: `Clone`, and 2 more");
+
+        let config = HoverConfig {
+            implemented_traits: false,
+            ..HoverConfig::default()
+        };
+        let hover = analysis.hover(position, &config).unwrap().unwrap();
+        assert_eq!(hover.info.results(), ["```rust\nenum Foo\n```".to_string()]);
+    }
+
+    #[test]
+    fn hover_shows_module_docs_on_declaration() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            /// Declared here.
+            mod fo<|>o;
+            //- /foo.rs
+            //! Frobnicates widgets.
+
+            fn frobnicate() {}
+            ",
+        );
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(
+            hover.info.results(),
+            ["```rust\nmod foo\n```\n\nDeclared here.\n\nFrobnicates widgets.".to_string()]
+        );
     }
 
     #[test]
     fn hover_for_local_variable() {
         let (analysis, position) = single_file_with_position("fn func(foo: i32) { fo<|>o; }");
-        let hover = analysis.hover(position).unwrap().unwrap();
-        assert_eq!(hover.info, "i32");
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(hover.info.to_markup(), "i32");
+    }
+
+    #[test]
+    fn hover_for_named_format_arg() {
+        let (analysis, position) =
+            single_file_with_position(r#"fn func(foo: i32) { println!("{fo<|>o}"); }"#);
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(hover.info.to_markup(), "i32");
     }
 
     #[test]
     fn hover_for_local_variable_pat() {
         let (analysis, position) = single_file_with_position("fn func(fo<|>o: i32) {}");
-        let hover = analysis.hover(position).unwrap().unwrap();
-        assert_eq!(hover.info, "i32");
+        let hover = analysis.hover(position, &HoverConfig::default()).unwrap().unwrap();
+        assert_eq!(hover.info.to_markup(), "i32");
     }
 
     #[test]
@@ -223,7 +602,6 @@ mod tests {
         assert_eq!("u32", &type_name);
     }
 
-    // FIXME: improve type_of to make this work
     #[test]
     fn test_type_of_for_expr_1() {
         let (analysis, range) = single_file_with_range(
@@ -235,10 +613,9 @@ mod tests {
         );
 
         let type_name = analysis.type_of(range).unwrap().unwrap();
-        assert_eq!("[unknown]", &type_name);
+        assert_eq!("i32", &type_name);
     }
 
-    // FIXME: improve type_of to make this work
     #[test]
     fn test_type_of_for_expr_2() {
         let (analysis, range) = single_file_with_range(
@@ -251,7 +628,38 @@ mod tests {
         );
 
         let type_name = analysis.type_of(range).unwrap().unwrap();
-        assert_eq!("[unknown]", &type_name);
+        assert_eq!("i32", &type_name);
+    }
+
+    #[test]
+    fn test_type_of_for_partial_selection() {
+        let (analysis, range) = single_file_with_range(
+            "
+            fn main() {
+                let foo: u32 = 1;
+                let bar = <|>foo ==<|> 92;
+            }
+            ",
+        );
+
+        let type_name = analysis.type_of(range).unwrap().unwrap();
+        assert_eq!("bool", &type_name);
+    }
+
+    #[test]
+    fn test_type_of_for_binding() {
+        let (analysis, range) = single_file_with_range(
+            "
+            pub fn foo() -> u32 { 1 }
+
+            fn main() {
+                let <|>foo_test<|> = foo();
+            }
+            ",
+        );
+
+        let type_name = analysis.type_of(range).unwrap().unwrap();
+        assert_eq!("u32", &type_name);
     }
 
 }
@@ -3,21 +3,20 @@ use std::sync::Arc;
 use salsa::Database;
 
 use hir::{
-    self, Problem, source_binder,
+    self, BodyProblem, HirFileId, Problem, Ty, source_binder,
+    db::HirDatabase,
 };
 use ra_db::{FilesDatabase, SourceRoot, SourceRootId, SyntaxDatabase};
-use ra_ide_api_light::{self, assists, LocalEdit, Severity};
-use ra_syntax::{
-    TextRange, AstNode, SourceFile,
-    ast::{self, NameOwner},
-    algo::find_node_at_offset,
-};
+use ra_ide_api_light::{self, assists, insert_use, ImportStyle, LocalEdit, Severity};
+use ra_text_edit::TextEditBuilder;
+use ra_syntax::{ast, AstNode, SourceFile, TextRange, TextUnit, algo::find_node_at_offset};
 
 use crate::{
     AnalysisChange,
     Cancelable,
-    CrateId, db, Diagnostic, FileId, FilePosition, FileRange, FileSystemEdit,
+    CrateId, db, Diagnostic, FileId, FilePosition, FileRange, FileSystemEdit, InferenceStats,
     Query, RootChange, SourceChange, SourceFileEdit,
+    imports::import_candidates,
     symbol_index::{LibrarySymbolsQuery, FileSymbol},
 };
 
@@ -44,14 +43,27 @@ impl db::RootDatabase {
         for (file_id, text) in change.files_changed {
             self.query_mut(ra_db::FileTextQuery).set(file_id, text)
         }
+        if !change.libraries_removed.is_empty() {
+            let mut libraries = Vec::clone(&self.library_roots());
+            for root_id in change.libraries_removed {
+                libraries.retain(|&it| it != root_id);
+                self.clear_library(root_id);
+            }
+            self.query_mut(ra_db::LibraryRootsQuery)
+                .set((), Arc::new(libraries));
+        }
         if !change.libraries_added.is_empty() {
             let mut libraries = Vec::clone(&self.library_roots());
             for library in change.libraries_added {
-                libraries.push(library.root_id);
-                self.query_mut(ra_db::SourceRootQuery)
-                    .set(library.root_id, Default::default());
+                if libraries.contains(&library.root_id) {
+                    self.clear_library(library.root_id);
+                } else {
+                    libraries.push(library.root_id);
+                    self.query_mut(ra_db::SourceRootQuery)
+                        .set(library.root_id, Default::default());
+                }
                 self.query_mut(LibrarySymbolsQuery)
-                    .set_constant(library.root_id, Arc::new(library.symbol_index));
+                    .set(library.root_id, Arc::new(library.symbol_index));
                 self.apply_root_change(library.root_id, library.root_change);
             }
             self.query_mut(ra_db::LibraryRootsQuery)
@@ -65,6 +77,13 @@ impl db::RootDatabase {
 
     fn apply_root_change(&mut self, root_id: SourceRootId, root_change: RootChange) {
         let mut source_root = SourceRoot::clone(&self.source_root(root_id));
+        // A rename is a removal of the old path plus an addition of the new
+        // one, possibly with the same `FileId`, so removals must go first.
+        for remove_file in root_change.removed {
+            self.query_mut(ra_db::FileTextQuery)
+                .set(remove_file.file_id, Default::default());
+            source_root.files.remove(&remove_file.path);
+        }
         for add_file in root_change.added {
             self.query_mut(ra_db::FileTextQuery)
                 .set(add_file.file_id, add_file.text);
@@ -74,13 +93,22 @@ impl db::RootDatabase {
                 .set(add_file.file_id, root_id);
             source_root.files.insert(add_file.path, add_file.file_id);
         }
-        for remove_file in root_change.removed {
+        self.query_mut(ra_db::SourceRootQuery)
+            .set(root_id, Arc::new(source_root));
+    }
+
+    /// Forgets about all files of the library root, so that the root can be
+    /// either removed or repopulated from scratch.
+    fn clear_library(&mut self, root_id: SourceRootId) {
+        let source_root = self.source_root(root_id);
+        for &file_id in source_root.files.values() {
             self.query_mut(ra_db::FileTextQuery)
-                .set(remove_file.file_id, Default::default());
-            source_root.files.remove(&remove_file.path);
+                .set(file_id, Default::default());
         }
         self.query_mut(ra_db::SourceRootQuery)
-            .set(root_id, Arc::new(source_root));
+            .set(root_id, Default::default());
+        self.query_mut(LibrarySymbolsQuery)
+            .set(root_id, Default::default());
     }
 
     #[allow(unused)]
@@ -110,61 +138,25 @@ impl db::RootDatabase {
         };
         Ok(vec![krate.crate_id()])
     }
-    pub(crate) fn find_all_refs(
-        &self,
-        position: FilePosition,
-    ) -> Cancelable<Vec<(FileId, TextRange)>> {
-        let file = self.source_file(position.file_id);
-        // Find the binding associated with the offset
-        let (binding, descr) = match find_binding(self, &file, position)? {
-            None => return Ok(Vec::new()),
-            Some(it) => it,
-        };
-
-        let mut ret = binding
-            .name()
-            .into_iter()
-            .map(|name| (position.file_id, name.syntax().range()))
-            .collect::<Vec<_>>();
-        ret.extend(
-            descr
-                .scopes(self)?
-                .find_all_refs(binding)
-                .into_iter()
-                .map(|ref_desc| (position.file_id, ref_desc.range)),
-        );
 
-        return Ok(ret);
-
-        fn find_binding<'a>(
-            db: &db::RootDatabase,
-            source_file: &'a SourceFile,
-            position: FilePosition,
-        ) -> Cancelable<Option<(&'a ast::BindPat, hir::Function)>> {
-            let syntax = source_file.syntax();
-            if let Some(binding) = find_node_at_offset::<ast::BindPat>(syntax, position.offset) {
-                let descr = ctry!(source_binder::function_from_child_node(
-                    db,
-                    position.file_id,
-                    binding.syntax(),
-                )?);
-                return Ok(Some((binding, descr)));
-            };
-            let name_ref = ctry!(find_node_at_offset::<ast::NameRef>(syntax, position.offset));
-            let descr = ctry!(source_binder::function_from_child_node(
-                db,
-                position.file_id,
-                name_ref.syntax(),
-            )?);
-            let scope = descr.scopes(db)?;
-            let resolved = ctry!(scope.resolve_local_name(name_ref));
-            let resolved = resolved.ptr().resolve(source_file);
-            let binding = ctry!(find_node_at_offset::<ast::BindPat>(
-                syntax,
-                resolved.range().end()
-            ));
-            Ok(Some((binding, descr)))
+    pub(crate) fn infer_all_functions(&self) -> Cancelable<InferenceStats> {
+        let mut stats = InferenceStats::default();
+        for &root in self.local_roots().iter() {
+            let source_root = self.source_root(root);
+            for &file_id in source_root.files.values() {
+                stats.files += 1;
+                let source_file = self.source_file(file_id);
+                for fn_def in source_file.syntax().descendants().filter_map(ast::FnDef::cast) {
+                    let func = match source_binder::function_from_source(self, file_id, fn_def)? {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    func.infer(self)?;
+                    stats.functions += 1;
+                }
+            }
         }
+        Ok(stats)
     }
 
     pub(crate) fn diagnostics(&self, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
@@ -176,29 +168,50 @@ impl db::RootDatabase {
                 range: d.range,
                 message: d.msg,
                 severity: d.severity,
-                fix: d.fix.map(|fix| SourceChange::from_local_edit(file_id, fix)),
+                fixes: d
+                    .fix
+                    .into_iter()
+                    .map(|fix| SourceChange::from_local_edit(file_id, fix))
+                    .collect(),
+                related: Vec::new(),
             })
             .collect::<Vec<_>>();
-        if let Some(m) = source_binder::module_from_file_id(self, file_id)? {
+        // The root module of the file, and the inline modules in it.
+        let mut modules = Vec::new();
+        modules.extend(source_binder::module_from_file_id(self, file_id)?);
+        for module in syntax.syntax().descendants().filter_map(ast::Module::cast) {
+            if let Some(item_list) = module.item_list() {
+                let child = item_list.syntax();
+                modules.extend(source_binder::module_from_child_node(self, file_id, child)?);
+            }
+        }
+        for m in modules {
             for (name_node, problem) in m.problems(self)? {
                 let source_root = self.file_source_root(file_id);
                 let diag = match problem {
-                    Problem::UnresolvedModule { candidate } => {
-                        let create_file = FileSystemEdit::CreateFile {
-                            source_root,
-                            path: candidate.clone(),
-                        };
-                        let fix = SourceChange {
-                            label: "create module".to_string(),
-                            source_file_edits: Vec::new(),
-                            file_system_edits: vec![create_file],
-                            cursor_position: None,
-                        };
+                    Problem::UnresolvedModule { candidates } => {
+                        let fixes = candidates
+                            .iter()
+                            .map(|candidate| {
+                                let create_file = FileSystemEdit::CreateFile {
+                                    source_root,
+                                    path: candidate.clone(),
+                                };
+                                SourceChange {
+                                    label: format!("create module {}", candidate),
+                                    source_file_edits: Vec::new(),
+                                    file_system_edits: vec![create_file],
+                                    cursor_position: None,
+                                    placeholders: Vec::new(),
+                                }
+                            })
+                            .collect();
                         Diagnostic {
                             range: name_node.range(),
                             message: "unresolved module".to_string(),
                             severity: Severity::Error,
-                            fix: Some(fix),
+                            fixes,
+                            related: Vec::new(),
                         }
                     }
                     Problem::NotDirOwner { move_to, candidate } => {
@@ -216,54 +229,319 @@ impl db::RootDatabase {
                             source_file_edits: Vec::new(),
                             file_system_edits: vec![move_file, create_file],
                             cursor_position: None,
+                            placeholders: Vec::new(),
                         };
                         Diagnostic {
                             range: name_node.range(),
                             message: "can't declare module at this location".to_string(),
                             severity: Severity::Error,
-                            fix: Some(fix),
+                            fixes: vec![fix],
+                            related: Vec::new(),
                         }
                     }
+                    Problem::DuplicateDefinition { first } => Diagnostic {
+                        range: name_node.range(),
+                        message: format!("`{}` is defined multiple times", name_node.text()),
+                        severity: Severity::Error,
+                        fixes: Vec::new(),
+                        related: vec![(
+                            FileRange { file_id, range: first },
+                            "first definition".to_string(),
+                        )],
+                    },
                 };
                 res.push(diag)
             }
-        };
+        }
+        for fn_def in syntax.syntax().descendants().filter_map(ast::FnDef::cast) {
+            if let Some(function) = source_binder::function_from_source(self, file_id, fn_def)? {
+                let file_id = HirFileId::from(file_id);
+                res.extend(self.missing_match_arms(file_id, &syntax, &function)?);
+                res.extend(self.body_problems(file_id, &syntax, fn_def, &function)?);
+            }
+        }
+        // The functions which macro calls expand to, like `vec![...]` at the
+        // top level.
+        for function in source_binder::macro_functions(self, file_id)? {
+            let (macro_file, fn_def) = function.source(self)?;
+            let syntax = self.hir_source_file(macro_file);
+            res.extend(self.missing_match_arms(macro_file, &syntax, &function)?);
+            res.extend(self.body_problems(macro_file, &syntax, &fn_def, &function)?);
+        }
+        res.extend(crate::format_string::unresolved_named_args(self, file_id)?);
+        Ok(res)
+    }
+
+    /// The problems of the body of `function`, which is in `file`. The fixes
+    /// are only offered when `file` is not a macro expansion.
+    fn body_problems(
+        &self,
+        file_id: HirFileId,
+        file: &SourceFile,
+        fn_def: &ast::FnDef,
+        function: &hir::Function,
+    ) -> Cancelable<Vec<Diagnostic>> {
+        let syntax_mapping = function.body_syntax_mapping(self)?;
+        let edited_file = editable_file(self, file_id);
+        let mut res = Vec::new();
+        for (expr, problem) in function.body_problems(self)? {
+            let node = match syntax_mapping.expr_syntax(expr) {
+                Some(ptr) => ptr.resolve(file),
+                None => continue,
+            };
+            let range = file_id.original_range(self, node.range()).range;
+            let (message, fixes) = match problem {
+                BodyProblem::BreakOutsideOfLoop => {
+                    ("`break` outside of a loop".to_string(), Vec::new())
+                }
+                BodyProblem::ContinueOutsideOfLoop => {
+                    ("`continue` outside of a loop".to_string(), Vec::new())
+                }
+                BodyProblem::TryInNonTryFn => {
+                    // `?` on an `Option` needs the function to return an
+                    // `Option` too.
+                    let operand = ast::TryExpr::cast(&node)
+                        .and_then(|it| it.expr())
+                        .and_then(|it| syntax_mapping.node_expr(it));
+                    let operand_ty = match operand {
+                        Some(operand) => function.infer(self)?[operand].clone(),
+                        None => Ty::Unknown,
+                    };
+                    let is_option = match operand_ty {
+                        Ty::Adt { name, .. } => name.to_string() == "Option",
+                        _ => false,
+                    };
+                    let fix = if is_option {
+                        ra_ide_api_light::change_return_type_to_option(fn_def)
+                    } else {
+                        ra_ide_api_light::change_return_type_to_result(fn_def)
+                    };
+                    let fixes = fix
+                        .into_iter()
+                        .zip(edited_file)
+                        .map(|(edit, file_id)| SourceChange::from_local_edit(file_id, edit))
+                        .collect();
+                    let message = "the `?` operator can only be used in a function that \
+                                   returns `Result` or `Option`";
+                    (message.to_string(), fixes)
+                }
+            };
+            res.push(Diagnostic {
+                range,
+                message,
+                severity: Severity::Error,
+                fixes,
+                related: Vec::new(),
+            });
+        }
+        Ok(res)
+    }
+
+    fn missing_match_arms(
+        &self,
+        file_id: HirFileId,
+        file: &SourceFile,
+        function: &hir::Function,
+    ) -> Cancelable<Vec<Diagnostic>> {
+        let syntax_mapping = function.body_syntax_mapping(self)?;
+        let edited_file = editable_file(self, file_id);
+        let mut res = Vec::new();
+        for missing_arms in function.missing_match_arms(self)? {
+            let node = match syntax_mapping.expr_syntax(missing_arms.match_expr) {
+                Some(ptr) => ptr.resolve(file),
+                None => continue,
+            };
+            let match_expr = match ast::MatchExpr::cast(&node) {
+                Some(it) => it,
+                None => continue,
+            };
+            // Highlight `match x`, rather than all of the arms.
+            let start = match_expr.syntax().range().start();
+            let end = match_expr
+                .expr()
+                .map_or(start, |it| it.syntax().range().end());
+            let patterns = missing_arms
+                .missing
+                .iter()
+                .map(|it| format!("`{}`", it))
+                .collect::<Vec<_>>();
+            let fixes = ra_ide_api_light::fill_match_arms(match_expr, &missing_arms.missing)
+                .into_iter()
+                .zip(edited_file)
+                .map(|(edit, file_id)| SourceChange::from_local_edit(file_id, edit))
+                .collect();
+            let range = file_id.original_range(self, TextRange::from_to(start, end));
+            res.push(Diagnostic {
+                range: range.range,
+                message: format!("missing arms: {}", patterns.join(", ")),
+                severity: Severity::Error,
+                fixes,
+                related: Vec::new(),
+            });
+        }
         Ok(res)
     }
 
-    pub(crate) fn assists(&self, frange: FileRange) -> Vec<SourceChange> {
+    pub(crate) fn assists(
+        &self,
+        frange: FileRange,
+        import_style: ImportStyle,
+    ) -> Cancelable<Vec<SourceChange>> {
         let file = self.source_file(frange.file_id);
-        assists::assists(&file, frange.range)
+        let mut res = assists::assists(&file, frange.range)
             .into_iter()
             .map(|local_edit| SourceChange::from_local_edit(frange.file_id, local_edit))
-            .collect()
+            .collect::<Vec<_>>();
+        if let Some(edit) = self.fill_match_arms(frange.file_id, &file, frange.range.start())? {
+            res.push(SourceChange::from_local_edit(frange.file_id, edit));
+        }
+        let import_edits =
+            self.import_assists(frange.file_id, &file, frange.range.start(), import_style)?;
+        res.extend(
+            import_edits
+                .into_iter()
+                .map(|edit| SourceChange::from_local_edit(frange.file_id, edit)),
+        );
+        Ok(res)
     }
 
-    pub(crate) fn rename(
+    /// For an unresolved name at `offset`, offers to import the items of the
+    /// crate with this name, or to qualify the name with the module of the
+    /// item. The module is imported then, unless it is the crate root.
+    fn import_assists(
         &self,
-        position: FilePosition,
-        new_name: &str,
-    ) -> Cancelable<Vec<SourceFileEdit>> {
-        let res = self
-            .find_all_refs(position)?
-            .iter()
-            .map(|(file_id, text_range)| SourceFileEdit {
-                file_id: *file_id,
-                edit: {
-                    let mut builder = ra_text_edit::TextEditBuilder::default();
-                    builder.replace(*text_range, new_name.into());
-                    builder.finish()
-                },
-            })
-            .collect::<Vec<_>>();
+        file_id: FileId,
+        file: &SourceFile,
+        offset: TextUnit,
+        import_style: ImportStyle,
+    ) -> Cancelable<Vec<LocalEdit>> {
+        let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), offset) {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let is_plain_path = name_ref
+            .syntax()
+            .parent()
+            .and_then(ast::PathSegment::cast)
+            .and_then(|it| it.syntax().parent())
+            .and_then(ast::Path::cast)
+            .map_or(false, |path| {
+                path.qualifier().is_none()
+                    && !path.syntax().ancestors().any(|it| ast::UseItem::cast(it).is_some())
+            });
+        if !is_plain_path {
+            return Ok(Vec::new());
+        }
+        if source_binder::def_for_node(self, file_id, name_ref.syntax())?.is_some() {
+            return Ok(Vec::new());
+        }
+        let module = source_binder::module_from_child_node(self, file_id, name_ref.syntax())?;
+        let module = match module {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let name = name_ref.text();
+        let candidates = import_candidates(self, &module, |it| it.to_string() == name.as_str())?;
+        let scope = module.scope(self)?;
+        let mut res = Vec::new();
+        for candidate in candidates {
+            if let Some(edit) = insert_use(file, offset, &[candidate.path.clone()], import_style) {
+                res.push(LocalEdit {
+                    label: format!("import `{}`", candidate.path),
+                    edit,
+                    cursor_position: None,
+                    placeholders: Vec::new(),
+                });
+            }
+            // `crate::a::Foo` becomes `a::Foo`, with `crate::a` imported
+            // unless `a` is in scope already.
+            let (qualifier, import) = match candidate.module_path.rfind("::") {
+                Some(idx) => (&candidate.module_path[idx + 2..], Some(&candidate.module_path)),
+                None => (candidate.module_path.as_str(), None),
+            };
+            let in_scope = scope
+                .entries()
+                .filter(|(it, _)| it.to_string() == qualifier)
+                .filter_map(|(_, res)| res.def_id.types)
+                .any(|def_id| match def_id.resolve(self) {
+                    Ok(hir::Def::Module(it)) => it == candidate.module,
+                    _ => false,
+                });
+            let mut edit = TextEditBuilder::default();
+            if let Some(import) = import.filter(|_| !in_scope) {
+                let import_edit = insert_use(file, offset, &[import.clone()], import_style);
+                for atom in import_edit.iter().flat_map(|it| it.as_atoms()) {
+                    edit.replace(atom.delete, atom.insert.clone());
+                }
+            }
+            let qualified = format!("{}::{}", qualifier, name);
+            edit.replace(name_ref.syntax().range(), qualified.clone());
+            res.push(LocalEdit {
+                label: format!("qualify as `{}`", qualified),
+                edit: edit.finish(),
+                cursor_position: None,
+                placeholders: Vec::new(),
+            });
+        }
         Ok(res)
     }
+
+    /// Adds the missing arms to the `match` around `offset`. Unlike the other
+    /// assists, this one needs to know the type of the matched expression.
+    fn fill_match_arms(
+        &self,
+        file_id: FileId,
+        file: &SourceFile,
+        offset: TextUnit,
+    ) -> Cancelable<Option<LocalEdit>> {
+        let match_expr = ctry!(find_node_at_offset::<ast::MatchExpr>(file.syntax(), offset));
+        let function = ctry!(source_binder::function_from_child_node(
+            self,
+            file_id,
+            match_expr.syntax()
+        )?);
+        let syntax_mapping = function.body_syntax_mapping(self)?;
+        let expr = ast::Expr::cast(match_expr.syntax()).and_then(|it| syntax_mapping.node_expr(it));
+        let expr = ctry!(expr);
+        let missing_arms = ctry!(function
+            .missing_match_arms(self)?
+            .into_iter()
+            .find(|it| it.match_expr == expr));
+        Ok(ra_ide_api_light::fill_match_arms(match_expr, &missing_arms.missing))
+    }
+
     pub(crate) fn index_resolve(&self, name_ref: &ast::NameRef) -> Cancelable<Vec<FileSymbol>> {
         let name = name_ref.text();
         let mut query = Query::new(name.to_string());
         query.exact();
         query.limit(4);
-        crate::symbol_index::world_symbols(self, query)
+        let res = crate::symbol_index::world_symbols(self, query)?.value;
+        if !res.is_empty() {
+            return Ok(res);
+        }
+        // Nothing in the workspace, the definition might come from a
+        // dependency, so look into the library roots as well.
+        let mut query = Query::new(name.to_string());
+        query.exact();
+        query.libraries(true);
+        query.limit(4);
+        Ok(crate::symbol_index::world_symbols(self, query)?.value)
+    }
+
+    pub(crate) fn is_library_file(&self, file_id: FileId) -> bool {
+        let source_root = self.file_source_root(file_id);
+        self.library_roots().contains(&source_root)
+    }
+}
+
+/// The file to apply the fixes for `file_id` to, unless it is a macro
+/// expansion, which can't be edited.
+fn editable_file(db: &db::RootDatabase, file_id: HirFileId) -> Option<FileId> {
+    let original_file = file_id.original_file(db);
+    if HirFileId::from(original_file) == file_id {
+        Some(original_file)
+    } else {
+        None
     }
 }
 
@@ -280,6 +558,11 @@ impl SourceChange {
             cursor_position: edit
                 .cursor_position
                 .map(|offset| FilePosition { offset, file_id }),
+            placeholders: edit
+                .placeholders
+                .into_iter()
+                .map(|range| FileRange { range, file_id })
+                .collect(),
         }
     }
 }
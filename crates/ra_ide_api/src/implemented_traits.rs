@@ -0,0 +1,172 @@
+//! The traits implemented by a struct or an enum. Derives are not expanded,
+//! so the traits in `#[derive(...)]` attributes are listed by their name,
+//! next to the trait impls of the crate of the type and of the current crate.
+use ra_db::{Cancelable, SyntaxDatabase};
+use ra_syntax::{
+    AstNode, SyntaxNode,
+    SyntaxKind::{COLONCOLON, IDENT},
+    ast::{self, AttrsOwner},
+    algo::find_leaf_at_offset,
+};
+use hir::{Crate, Def, DefId, source_binder::{self, NameDefinition}};
+use rustc_hash::FxHashSet;
+
+use crate::{FileId, FilePosition, NavigationTarget, db::RootDatabase};
+
+/// A trait implemented by a type.
+#[derive(Debug)]
+pub struct ImplementedTrait {
+    pub name: String,
+    /// The definition of the trait, if it is known. Derived traits are not
+    /// resolved.
+    pub navigation_target: Option<NavigationTarget>,
+    /// Whether the trait comes from a `#[derive(...)]` attribute.
+    pub is_derived: bool,
+}
+
+/// The traits implemented by the struct or the enum at `position`, sorted by
+/// name.
+pub(crate) fn traits_implemented_by(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<ImplementedTrait>> {
+    let file = db.source_file(position.file_id);
+    let node = find_leaf_at_offset(file.syntax(), position.offset).find_map(|leaf| {
+        leaf.ancestors()
+            .find(|it| ast::NameRef::cast(it).is_some() || ast::Name::cast(it).is_some())
+    });
+    let node = match node {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let res = implemented_traits(db, position.file_id, node)?;
+    Ok(res.unwrap_or_default())
+}
+
+/// The traits implemented by the type `node` refers to, or `None` if it isn't
+/// a struct or an enum.
+pub(crate) fn implemented_traits(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<Vec<ImplementedTrait>>> {
+    let (def_id, derived) = match source_binder::def_for_node(db, file_id, node)? {
+        Some(NameDefinition::Item(Def::Struct(it))) => {
+            let (_, node) = it.source(db)?;
+            (it.def_id(), derived_traits(&*node))
+        }
+        Some(NameDefinition::Item(Def::Enum(it))) => {
+            let (_, node) = it.source(db)?;
+            (it.def_id(), derived_traits(&*node))
+        }
+        _ => return Ok(None),
+    };
+    let mut res = derived
+        .into_iter()
+        .map(|name| ImplementedTrait {
+            name,
+            navigation_target: None,
+            is_derived: true,
+        })
+        .collect::<Vec<_>>();
+    let ty = db.type_for_def(def_id)?;
+    let mut seen = FxHashSet::default();
+    for krate in crates_to_search(db, file_id, node, def_id)? {
+        let impls = db.impls_in_crate(krate)?;
+        for impl_block in impls.lookup_impl_blocks(db, &ty) {
+            let trait_ = match impl_block?.target_trait_def(db)? {
+                Some(it) => it,
+                None => continue,
+            };
+            if !seen.insert(trait_.def_id()) {
+                continue;
+            }
+            let name = match trait_.name(db)? {
+                Some(it) => it.to_string(),
+                None => continue,
+            };
+            let navigation_target = NavigationTarget::from_def(db, Def::Trait(trait_))?;
+            res.push(ImplementedTrait {
+                name,
+                navigation_target,
+                is_derived: false,
+            });
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    res.dedup_by(|a, b| a.name == b.name);
+    Ok(Some(res))
+}
+
+/// The names in `#[derive(...)]` attributes. For paths, like
+/// `serde::Serialize`, this is the last segment.
+fn derived_traits(node: &impl AttrsOwner) -> Vec<String> {
+    let mut res = Vec::new();
+    for (name, tt) in node.attrs().filter_map(|it| it.as_call()) {
+        if name.as_str() != "derive" {
+            continue;
+        }
+        let tokens = tt
+            .syntax()
+            .children()
+            .filter(|it| !it.kind().is_trivia())
+            .collect::<Vec<_>>();
+        for (idx, token) in tokens.iter().enumerate() {
+            let is_last_segment = tokens.get(idx + 1).map_or(true, |it| it.kind() != COLONCOLON);
+            if token.kind() == IDENT && is_last_segment {
+                res.extend(token.leaf_text().map(|it| it.to_string()));
+            }
+        }
+    }
+    res
+}
+
+/// Impls of a type can be in its own crate or in any crate depending on it,
+/// so the crate of the type and the current crate are searched.
+fn crates_to_search(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+    def_id: DefId,
+) -> Cancelable<Vec<Crate>> {
+    let mut res = Vec::new();
+    if let Some(krate) = def_id.krate(db)? {
+        res.push(krate);
+    }
+    let current = match source_binder::module_from_child_node(db, file_id, node)? {
+        Some(module) => module.krate(db)?,
+        None => None,
+    };
+    if let Some(krate) = current {
+        if !res.contains(&krate) {
+            res.push(krate);
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file_with_position;
+
+    #[test]
+    fn lists_derived_and_implemented_traits() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Frobnicate {}
+            trait Display {}
+            #[derive(Debug, Clone)]
+            struct Fo<|>o;
+            impl Frobnicate for Foo {}
+            impl Foo {}
+            ",
+        );
+        let traits = analysis.traits_implemented_by(position).unwrap();
+        let names = traits
+            .iter()
+            .map(|it| (it.name.as_str(), it.is_derived))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("Clone", true), ("Debug", true), ("Frobnicate", false)]);
+        assert!(traits[2].navigation_target.is_some());
+    }
+}
@@ -0,0 +1,120 @@
+//! The items which can be imported into a module, for the completions and
+//! assists which add a `use` item with `insert_use`.
+use hir::{Def, Module, Name, Resolution};
+use ra_db::Cancelable;
+use ra_syntax::{TreeArc, ast::VisibilityOwner};
+
+use crate::db::RootDatabase;
+
+/// An item of another module of the crate, which is not in scope.
+#[derive(Debug)]
+pub(crate) struct ImportCandidate {
+    pub(crate) name: Name,
+    /// The path to import, like `crate::collections::HashMap`.
+    pub(crate) path: String,
+    /// The module of the item, and its path, like `crate::collections`.
+    pub(crate) module: Module,
+    pub(crate) module_path: String,
+    pub(crate) resolution: Resolution,
+}
+
+/// The items declared in the other modules of the crate of `module`, which
+/// are visible from `module` and which are not in its scope yet. Only the
+/// items whose names pass `filter` are looked at.
+pub(crate) fn import_candidates(
+    db: &RootDatabase,
+    module: &Module,
+    filter: impl Fn(&Name) -> bool,
+) -> Cancelable<Vec<ImportCandidate>> {
+    let scope = module.scope(db)?;
+    let ancestors = module.path_to_root(db)?;
+    let mut res = Vec::new();
+    let mut queue = vec![module.crate_root(db)?];
+    while let Some(item_module) = queue.pop() {
+        queue.extend(item_module.children(db)?);
+        if item_module == *module {
+            continue;
+        }
+        // The private items of a module are visible in its submodules.
+        let is_ancestor = ancestors.contains(&item_module);
+        let mut module_path = None;
+        for (name, resolution) in item_module.scope(db)?.entries() {
+            // Imports are skipped, the item is offered from the module which
+            // declares it.
+            if resolution.import.is_some() || !filter(name) || scope.get(name).is_some() {
+                continue;
+            }
+            if !is_ancestor && !is_public(db, resolution)? {
+                continue;
+            }
+            if module_path.is_none() {
+                module_path = path_to_module(db, module, &item_module)?;
+            }
+            let module_path = match &module_path {
+                Some(it) => it.clone(),
+                None => break,
+            };
+            res.push(ImportCandidate {
+                name: name.clone(),
+                path: format!("{}::{}", module_path, name),
+                module: item_module.clone(),
+                module_path,
+                resolution: resolution.clone(),
+            });
+        }
+    }
+    res.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(res)
+}
+
+/// The path of `target` as seen from `module`: `crate::a::b` for modules of
+/// the same crate, and a path through the name of the dependency otherwise.
+pub(crate) fn path_to_module(
+    db: &RootDatabase,
+    module: &Module,
+    target: &Module,
+) -> Cancelable<Option<String>> {
+    let target_krate = ctry!(target.krate(db)?);
+    let krate = ctry!(module.krate(db)?);
+    let mut segments = Vec::new();
+    for target in target.path_to_root(db)?.into_iter().rev() {
+        match target.name(db)? {
+            Some(name) => segments.push(name.to_string()),
+            None if target_krate == krate => segments.push("crate".to_string()),
+            None => {
+                let dep = ctry!(krate
+                    .dependencies(db)?
+                    .into_iter()
+                    .find(|it| it.krate == target_krate));
+                segments.push(dep.name.to_string());
+            }
+        }
+    }
+    Ok(Some(segments.join("::")))
+}
+
+fn is_public(db: &RootDatabase, resolution: &Resolution) -> Cancelable<bool> {
+    let def_id = match resolution.def_id.types.or(resolution.def_id.values) {
+        Some(it) => it,
+        None => return Ok(false),
+    };
+    let res = match def_id.resolve(db)? {
+        Def::Module(it) => match it.declaration_source(db)? {
+            Some((_, node)) => node.visibility().is_some(),
+            None => false,
+        },
+        Def::Struct(it) => has_visibility(it.source(db)?.1),
+        Def::Enum(it) => has_visibility(it.source(db)?.1),
+        Def::Function(it) => has_visibility(it.source(db)?.1),
+        Def::Const(it) => has_visibility(it.source(db)?.1),
+        Def::Static(it) => has_visibility(it.source(db)?.1),
+        Def::Trait(it) => has_visibility(it.source(db)?.1),
+        Def::Type(it) => has_visibility(it.source(db)?.1),
+        Def::EnumVariant(_) | Def::Item => false,
+    };
+    Ok(res)
+}
+
+fn has_visibility<N: VisibilityOwner>(node: TreeArc<N>) -> bool {
+    node.visibility().is_some()
+}
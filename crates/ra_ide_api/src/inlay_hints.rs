@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use ra_db::{SyntaxDatabase, Cancelable};
+use ra_syntax::{
+    AstNode, SmolStr, SyntaxNode, TextRange,
+    ast::{self, ArgListOwner},
+    SyntaxKind::WHITESPACE,
+};
+use hir::{source_binder, Def, FnSignature, Ty};
+
+use crate::{FileId, db::RootDatabase};
+
+/// Which kinds of hints should be computed.
+#[derive(Debug, Clone)]
+pub struct InlayHintsConfig {
+    /// Parameter names at call sites, `frobnicate(/*timeout:*/ 10)`.
+    pub parameter_hints: bool,
+    /// Types of the intermediate expressions of multi-line method chains.
+    pub chaining_hints: bool,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> InlayHintsConfig {
+        InlayHintsConfig {
+            parameter_hints: true,
+            chaining_hints: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlayKind {
+    ParameterHint,
+    ChainingHint,
+}
+
+/// A piece of text, like a parameter name, which the editor shows next to the
+/// `range` without it being a part of the source code. Parameter hints go in
+/// front of the argument, chaining hints go after the end of the line.
+#[derive(Debug)]
+pub struct InlayHint {
+    pub range: TextRange,
+    pub kind: InlayKind,
+    pub label: SmolStr,
+}
+
+pub(crate) fn inlay_hints(
+    db: &RootDatabase,
+    file_id: FileId,
+    config: &InlayHintsConfig,
+) -> Cancelable<Vec<InlayHint>> {
+    let file = db.source_file(file_id);
+    let mut res = Vec::new();
+    for node in file.syntax().descendants() {
+        if config.parameter_hints {
+            if let Some(call) = ast::CallExpr::cast(node) {
+                if let Some(signature) = call_signature(db, file_id, call)? {
+                    parameter_hints(&mut res, &signature, call.arg_list(), false);
+                }
+            } else if let Some(call) = ast::MethodCallExpr::cast(node) {
+                if let Some(signature) = method_call_signature(db, file_id, call)? {
+                    parameter_hints(&mut res, &signature, call.arg_list(), true);
+                }
+            }
+        }
+        if config.chaining_hints {
+            if let Some(hint) = chaining_hint(db, file_id, node)? {
+                res.push(hint);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// In a method chain which is split across several lines, shows the type of
+/// the receiver at the end of each line:
+///
+/// ```text
+/// let names = items
+///     .iter()                 // Iter<Item>
+///     .map(|it| it.name())    // Map<...>
+///     .collect::<Vec<_>>();
+/// ```
+fn chaining_hint(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<InlayHint>> {
+    let receiver = ctry!(ast::Expr::cast(node));
+    // Arguments are inside the `ArgList`, so an expression directly in the
+    // method call is always the receiver.
+    ctry!(node.parent().and_then(ast::MethodCallExpr::cast));
+    let ends_line = node.next_sibling().map_or(false, |it| {
+        it.kind() == WHITESPACE && it.leaf_text().map_or(false, |text| text.contains('\n'))
+    });
+    if !ends_line {
+        return Ok(None);
+    }
+    let function = ctry!(source_binder::function_from_child_node(db, file_id, node)?);
+    let infer_result = function.infer(db)?;
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    let expr = ctry!(syntax_mapping.node_expr(receiver));
+    let ty = &infer_result[expr];
+    if let Ty::Unknown = ty {
+        return Ok(None);
+    }
+    Ok(Some(InlayHint {
+        range: node.range(),
+        kind: InlayKind::ChainingHint,
+        label: ty.to_string().into(),
+    }))
+}
+
+fn call_signature(
+    db: &RootDatabase,
+    file_id: FileId,
+    call: &ast::CallExpr,
+) -> Cancelable<Option<Arc<FnSignature>>> {
+    let path = match call.expr().map(|it| it.kind()) {
+        Some(ast::ExprKind::PathExpr(it)) => ctry!(it.path()),
+        _ => return Ok(None),
+    };
+    let path = ctry!(hir::Path::from_ast(path));
+    let module = ctry!(source_binder::module_from_child_node(db, file_id, call.syntax())?);
+    let def_id = ctry!(module.resolve_path(db, &path)?.take_values());
+    match def_id.resolve(db)? {
+        Def::Function(function) => Ok(Some(function.signature(db))),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn method_call_signature(
+    db: &RootDatabase,
+    file_id: FileId,
+    call: &ast::MethodCallExpr,
+) -> Cancelable<Option<Arc<FnSignature>>> {
+    let function = ctry!(source_binder::function_from_child_node(db, file_id, call.syntax())?);
+    let infer_result = function.infer(db)?;
+    let syntax_mapping = function.body_syntax_mapping(db)?;
+    let expr = ctry!(ast::Expr::cast(call.syntax()).and_then(|it| syntax_mapping.node_expr(it)));
+    let def_id = ctry!(infer_result.method_resolution(expr));
+    match def_id.resolve(db)? {
+        Def::Function(function) => Ok(Some(function.signature(db))),
+        _ => Ok(None),
+    }
+}
+
+fn parameter_hints(
+    acc: &mut Vec<InlayHint>,
+    signature: &FnSignature,
+    arg_list: Option<&ast::ArgList>,
+    is_method_call: bool,
+) {
+    let arg_list = match arg_list {
+        Some(it) => it,
+        None => return,
+    };
+    // The receiver of a method call is not in the argument list.
+    let skip = if is_method_call && signature.has_self_param() { 1 } else { 0 };
+    let param_names = signature.param_names().iter().skip(skip);
+    for (name, arg) in param_names.zip(arg_list.args()) {
+        let name = match name {
+            Some(it) => it.to_string(),
+            None => continue,
+        };
+        if name.starts_with('_') || is_obvious_arg(arg, &name) {
+            continue;
+        }
+        acc.push(InlayHint {
+            range: arg.syntax().range(),
+            kind: InlayKind::ParameterHint,
+            label: name.into(),
+        });
+    }
+}
+
+/// Variables and fields named after the parameter, like `frobnicate(timeout)`
+/// or `frobnicate(&self.timeout)`, are clear without a hint. Literals and other
+/// expressions always get one.
+fn is_obvious_arg(arg: &ast::Expr, param_name: &str) -> bool {
+    match arg.kind() {
+        ast::ExprKind::RefExpr(it) => it.expr().map_or(false, |it| is_obvious_arg(it, param_name)),
+        ast::ExprKind::PathExpr(it) => {
+            let name_ref = it.path().and_then(|it| it.segment()).and_then(|it| it.name_ref());
+            name_ref.map_or(false, |it| it.text() == param_name)
+        }
+        ast::ExprKind::FieldExpr(it) => it.name_ref().map_or(false, |it| it.text() == param_name),
+        ast::ExprKind::MethodCallExpr(it) => {
+            it.name_ref().map_or(false, |it| it.text() == param_name)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file;
+    use super::{InlayHintsConfig, InlayKind};
+
+    fn check_hints(code: &str, expected: &[(&str, &str)]) {
+        let (analysis, file_id) = single_file(code);
+        let text = analysis.file_text(file_id);
+        let hints = analysis.inlay_hints(file_id, &InlayHintsConfig::default()).unwrap();
+        let actual: Vec<(&str, &str)> = hints
+            .iter()
+            .map(|hint| (hint.label.as_str(), &text.as_str()[hint.range]))
+            .collect();
+        assert_eq!(actual.as_slice(), expected);
+    }
+
+    #[test]
+    fn parameter_hints_for_calls() {
+        check_hints(
+            r"
+            fn frobnicate(timeout: u32, retries: u32, (a, b): (u32, u32), _unused: u32) {}
+            fn foo(timeout: u32) {
+                frobnicate(timeout, 3, (1, 2), 4);
+            }
+            ",
+            &[("retries", "3")],
+        );
+    }
+
+    #[test]
+    fn parameter_hints_for_method_calls() {
+        check_hints(
+            r"
+            struct S { retries: u32 }
+            impl S {
+                fn frobnicate(&self, timeout: u32, retries: u32) {}
+            }
+            fn foo(s: S) {
+                s.frobnicate(10 + 2, s.retries);
+            }
+            ",
+            &[("timeout", "10 + 2")],
+        );
+    }
+
+    #[test]
+    fn no_parameter_hints_when_disabled() {
+        let (analysis, file_id) = single_file(
+            r"
+            fn frobnicate(timeout: u32) {}
+            fn foo() { frobnicate(10); }
+            ",
+        );
+        let config = InlayHintsConfig {
+            parameter_hints: false,
+            chaining_hints: false,
+        };
+        assert!(analysis.inlay_hints(file_id, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chaining_hints_at_line_ends() {
+        let (analysis, file_id) = single_file(
+            r"
+            struct A; struct B; struct C;
+            impl A { fn b(&self) -> B { B } }
+            impl B { fn c(&self) -> C { C } }
+            fn foo(a: A) {
+                let c = a
+                    .b()
+                    .c();
+                let b = a.b().c();
+            }
+            ",
+        );
+        let config = InlayHintsConfig {
+            parameter_hints: false,
+            chaining_hints: true,
+        };
+        let hints = analysis.inlay_hints(file_id, &config).unwrap();
+        let mut labels: Vec<&str> = hints.iter().map(|it| it.label.as_str()).collect();
+        labels.sort();
+        assert_eq!(labels, ["A", "B"]);
+        assert!(hints.iter().all(|it| it.kind == InlayKind::ChainingHint));
+    }
+}
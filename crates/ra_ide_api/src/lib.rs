@@ -32,8 +32,18 @@ mod hover;
 mod call_info;
 mod syntax_highlighting;
 mod parent_module;
-
-use std::{fmt, sync::Arc};
+mod references;
+mod search_scope;
+mod inlay_hints;
+mod document_links;
+mod module_tree;
+mod builtin_type;
+mod budget;
+mod format_string;
+mod implemented_traits;
+mod imports;
+
+use std::{fmt, sync::Arc, time::Instant};
 
 use ra_syntax::{SourceFile, TreeArc, TextRange, TextUnit};
 use ra_text_edit::TextEdit;
@@ -49,16 +59,27 @@ use crate::{
 };
 
 pub use crate::{
-    completion::{CompletionItem, CompletionItemKind, InsertText},
-    runnables::{Runnable, RunnableKind},
+    completion::{
+        CompletionItem, CompletionItemKind, InsertText, CompletionConfig, UserSnippet,
+        SnippetScope,
+    },
+    runnables::{Runnable, RunnableKind, TestModule, RelatedTest},
+    inlay_hints::{InlayHint, InlayHintsConfig, InlayKind},
+    document_links::{DocumentLink, LinkTarget},
+    module_tree::ModuleTreeNode,
+    syntax_highlighting::{UnsafeKind, UnsafeRange},
+    hover::{HoverResult, HoverConfig},
+    implemented_traits::ImplementedTrait,
     navigation_target::NavigationTarget,
+    budget::Partial,
 };
 pub use ra_ide_api_light::{
-    Fold, FoldKind, HighlightedRange, Severity, StructureNode,
-    LineIndex, LineCol, translate_offset_with_edit,
+    Fold, FoldKind, HighlightedRange, ImportStyle, Severity, StructureNode,
+    LineIndex, LineCol, LineColUtf8, translate_offset_with_edit,
 };
 pub use ra_db::{
-    Cancelable, Canceled, CrateGraph, CrateId, FileId, FilePosition, FileRange, SourceRootId
+    Cancelable, Canceled, CrateGraph, CrateId, Dependency, Edition, FileId, FilePosition, FileRange,
+    SourceRootId
 };
 
 #[derive(Default)]
@@ -67,6 +88,7 @@ pub struct AnalysisChange {
     roots_changed: FxHashMap<SourceRootId, RootChange>,
     files_changed: Vec<(FileId, Arc<String>)>,
     libraries_added: Vec<LibraryData>,
+    libraries_removed: Vec<SourceRootId>,
     crate_graph: Option<CrateGraph>,
 }
 
@@ -104,6 +126,9 @@ impl fmt::Debug for AnalysisChange {
         if !self.libraries_added.is_empty() {
             d.field("libraries_added", &self.libraries_added.len());
         }
+        if !self.libraries_removed.is_empty() {
+            d.field("libraries_removed", &self.libraries_removed);
+        }
         if !self.crate_graph.is_some() {
             d.field("crate_graph", &self.crate_graph);
         }
@@ -161,10 +186,18 @@ impl AnalysisChange {
             .push(file);
     }
 
+    /// Adds a library to the world. If a library with the same root already
+    /// exists, it is replaced.
     pub fn add_library(&mut self, data: LibraryData) {
         self.libraries_added.push(data)
     }
 
+    /// Removes a library root together with all its files and symbols, for
+    /// example, when a dependency is dropped after `cargo update`.
+    pub fn remove_library(&mut self, root_id: SourceRootId) {
+        self.libraries_removed.push(root_id)
+    }
+
     pub fn set_crate_graph(&mut self, graph: CrateGraph) {
         self.crate_graph = Some(graph);
     }
@@ -176,6 +209,9 @@ pub struct SourceChange {
     pub source_file_edits: Vec<SourceFileEdit>,
     pub file_system_edits: Vec<FileSystemEdit>,
     pub cursor_position: Option<FilePosition>,
+    /// The ranges the user is likely to fill in next, like the `$1`, `$2` of
+    /// a snippet. These are ranges in the edited files.
+    pub placeholders: Vec<FileRange>,
 }
 
 #[derive(Debug)]
@@ -184,25 +220,136 @@ pub struct SourceFileEdit {
     pub edit: TextEdit,
 }
 
+/// Why a rename can't be performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// The new name is not an identifier. For keywords, the raw identifier
+    /// is suggested instead.
+    InvalidName {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// There's no local binding or field at the position.
+    NotApplicable,
+    /// The item is defined in a library, which can't be changed.
+    LibraryItem,
+    /// Another item with the new name is already visible at the `range`.
+    Conflict { name: String, range: FileRange },
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::InvalidName {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(f, "`{}` is a keyword, use `{}` instead", name, suggestion),
+            RenameError::InvalidName { name, .. } => {
+                write!(f, "`{}` is not a valid identifier", name)
+            }
+            RenameError::NotApplicable => write!(f, "no symbol to rename at this position"),
+            RenameError::LibraryItem => write!(f, "can't rename an item defined in a library"),
+            RenameError::Conflict { name, .. } => {
+                write!(f, "`{}` is already defined in this scope", name)
+            }
+        }
+    }
+}
+
+/// Whether a reference reads or writes the referenced value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceAccess {
+    Read,
+    Write,
+}
+
+/// The role of a reference in the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// The name of the definition itself, like `foo` in `fn foo() {}`.
+    Definition,
+    /// A definition without a body, like a trait method `fn foo(&self);`.
+    Declaration,
+    /// A path in a `use` item.
+    Import,
+    /// Any other usage.
+    Reference,
+    /// An identifier with the same name in the body of a `macro_rules!`
+    /// definition. Macros are not expanded, so it may or may not refer to
+    /// the definition.
+    MacroText,
+}
+
+/// Which kinds of references should be found.
+#[derive(Debug, Clone)]
+pub struct ReferencesConfig {
+    /// Include the definition and the declarations.
+    pub include_declaration: bool,
+    /// Include the paths in `use` items.
+    pub include_imports: bool,
+    /// Include the textual matches in `macro_rules!` definitions.
+    pub include_macro_text: bool,
+    /// Stop searching the files when the deadline passes, see `Partial`.
+    pub deadline: Option<Instant>,
+    /// Search only the file of the position, like for highlighting the
+    /// references in it.
+    pub current_file_only: bool,
+}
+
+impl Default for ReferencesConfig {
+    fn default() -> ReferencesConfig {
+        ReferencesConfig {
+            include_declaration: true,
+            include_imports: true,
+            include_macro_text: true,
+            deadline: None,
+            current_file_only: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Reference {
+    pub file_id: FileId,
+    pub range: TextRange,
+    pub kind: ReferenceKind,
+    /// `None` for the declaration itself.
+    pub access: Option<ReferenceAccess>,
+    /// The reference is a shorthand field, like `S { foo }`, which has to be
+    /// expanded on rename.
+    pub is_shorthand: bool,
+}
+
 #[derive(Debug)]
 pub enum FileSystemEdit {
     CreateFile {
         source_root: SourceRootId,
         path: RelativePathBuf,
     },
+    CreateFileWithContent {
+        source_root: SourceRootId,
+        path: RelativePathBuf,
+        content: String,
+    },
     MoveFile {
         src: FileId,
         dst_source_root: SourceRootId,
         dst_path: RelativePathBuf,
     },
+    RemoveFile {
+        src: FileId,
+    },
 }
 
 #[derive(Debug)]
 pub struct Diagnostic {
     pub message: String,
     pub range: TextRange,
-    pub fix: Option<SourceChange>,
+    /// Alternative ways to fix the problem, the user is expected to pick one.
+    pub fixes: Vec<SourceChange>,
     pub severity: Severity,
+    /// Other locations which explain the problem, with a message for each.
+    pub related: Vec<(FileRange, String)>,
 }
 
 #[derive(Debug)]
@@ -210,9 +357,10 @@ pub struct Query {
     query: String,
     lowercased: String,
     only_types: bool,
-    libs: bool,
+    libraries: bool,
     exact: bool,
     limit: usize,
+    deadline: Option<Instant>,
 }
 
 impl Query {
@@ -222,9 +370,10 @@ impl Query {
             query,
             lowercased,
             only_types: false,
-            libs: false,
+            libraries: false,
             exact: false,
             limit: usize::max_value(),
+            deadline: None,
         }
     }
 
@@ -232,8 +381,10 @@ impl Query {
         self.only_types = true;
     }
 
-    pub fn libs(&mut self) {
-        self.libs = true;
+    /// Whether the symbols of the libraries are searched as well. By default,
+    /// only the workspace is searched.
+    pub fn libraries(&mut self, libraries: bool) {
+        self.libraries = libraries;
     }
 
     pub fn exact(&mut self) {
@@ -243,6 +394,12 @@ impl Query {
     pub fn limit(&mut self, limit: usize) {
         self.limit = limit
     }
+
+    /// The remaining files are skipped once the deadline passes, see
+    /// `Analysis::symbol_search_partial`.
+    pub fn deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline)
+    }
 }
 
 #[derive(Debug)]
@@ -265,6 +422,13 @@ pub struct CallInfo {
     pub active_parameter: Option<usize>,
 }
 
+/// The amount of work done by `Analysis::infer_all_functions`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InferenceStats {
+    pub files: usize,
+    pub functions: usize,
+}
+
 /// `AnalysisHost` stores the current state of the world.
 #[derive(Debug, Default)]
 pub struct AnalysisHost {
@@ -324,6 +488,32 @@ impl Analysis {
         ra_ide_api_light::matching_brace(file, offset)
     }
 
+    /// Debug information about the state of the analysis, like the number of
+    /// files in the workspace and in the libraries, and of interned types.
+    pub fn status(&self) -> String {
+        let count_files = |roots: &[SourceRootId]| -> usize {
+            roots.iter().map(|&it| self.db.source_root(it).files.len()).sum()
+        };
+        let local_roots = self.db.local_roots();
+        let library_roots = self.db.library_roots();
+        let types: &hir::TyInterner = (*self.db).as_ref();
+        format!(
+            "workspace: {} files in {} roots\nlibraries: {} files in {} roots\n\
+             interned types: {}\n",
+            count_files(&local_roots),
+            local_roots.len(),
+            count_files(&library_roots),
+            library_roots.len(),
+            types.len(),
+        )
+    }
+
+    /// Runs type inference for every function of the local crates. This is
+    /// used to measure the performance of the analysis outside of an editor.
+    pub fn infer_all_functions(&self) -> Cancelable<InferenceStats> {
+        self.db.infer_all_functions()
+    }
+
     /// Returns a syntax tree represented as `String`, for debug purposes.
     // FIXME: use a better name here.
     pub fn syntax_tree(&self, file_id: FileId) -> String {
@@ -341,6 +531,14 @@ impl Analysis {
         )
     }
 
+    /// Returns an edit which sorts the `use` items of the file and removes the
+    /// duplicates, if they are not sorted already.
+    pub fn organize_imports(&self, file_id: FileId) -> Option<SourceChange> {
+        let file = self.db.source_file(file_id);
+        let edit = ra_ide_api_light::organize_imports(&file)?;
+        Some(SourceChange::from_local_edit(file_id, edit))
+    }
+
     /// Returns an edit which should be applied when opening a new line, fixing
     /// up minor stuff like continuing the comment.
     pub fn on_enter(&self, position: FilePosition) -> Option<SourceChange> {
@@ -380,12 +578,23 @@ impl Analysis {
 
     /// Fuzzy searches for a symbol.
     pub fn symbol_search(&self, query: Query) -> Cancelable<Vec<NavigationTarget>> {
+        Ok(self.symbol_search_partial(query)?.value)
+    }
+
+    /// Like `symbol_search`, but if the deadline of the query passes, only the
+    /// files which were searched by then are included. The rest of the files
+    /// are indexed in the background.
+    pub fn symbol_search_partial(
+        &self,
+        query: Query,
+    ) -> Cancelable<Partial<Vec<NavigationTarget>>> {
         self.with_db(|db| {
-            let res = symbol_index::world_symbols(db, query)?
-                .into_iter()
-                .map(NavigationTarget::from_symbol)
-                .collect::<Vec<_>>();
-            Ok(res)
+            let symbols = symbol_index::world_symbols(db, query)?;
+            let mut res = Vec::new();
+            for symbol in symbols.value {
+                res.push(NavigationTarget::from_symbol_with_container(db, symbol)?);
+            }
+            Ok(Partial::new(res, symbols.is_incomplete))
         })?
     }
 
@@ -394,17 +603,67 @@ impl Analysis {
         position: FilePosition,
     ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
         self.db
-            .catch_canceled(|db| goto_definition::goto_definition(db, position))?
+            .catch_canceled(|db| goto_definition::goto_definition(db, position, true))?
+    }
+
+    /// Like `goto_definition`, but a path which resolves through a `use`,
+    /// like `foo::Bar` for `pub use self::bar::Bar;` in `foo`, navigates to
+    /// that `use` instead of to the definition of `Bar`.
+    pub fn goto_reexport(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
+        self.db
+            .catch_canceled(|db| goto_definition::goto_definition(db, position, false))?
+    }
+
+    /// Navigates to the declaration of the name at point: the `use` item of
+    /// an imported name, or the `mod foo;` item of a module. Other names
+    /// navigate to their definition.
+    pub fn goto_declaration(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
+        self.db
+            .catch_canceled(|db| goto_definition::goto_declaration(db, position))?
     }
 
     /// Finds all usages of the reference at point.
-    pub fn find_all_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
-        self.with_db(|db| db.find_all_refs(position))?
+    pub fn find_all_refs(
+        &self,
+        position: FilePosition,
+        config: &ReferencesConfig,
+    ) -> Cancelable<Vec<Reference>> {
+        Ok(self.find_all_refs_partial(position, config)?.value)
+    }
+
+    /// Like `find_all_refs`, but the search stops when the deadline of the
+    /// `config` passes.
+    pub fn find_all_refs_partial(
+        &self,
+        position: FilePosition,
+        config: &ReferencesConfig,
+    ) -> Cancelable<Partial<Vec<Reference>>> {
+        self.with_db(|db| references::find_all_refs(db, position, config))?
     }
 
     /// Returns a short text descrbing element at position.
-    pub fn hover(&self, position: FilePosition) -> Cancelable<Option<RangeInfo<String>>> {
-        self.with_db(|db| hover::hover(db, position))?
+    pub fn hover(
+        &self,
+        position: FilePosition,
+        config: &HoverConfig,
+    ) -> Cancelable<Option<RangeInfo<HoverResult>>> {
+        self.with_db(|db| hover::hover(db, position, config))?
+    }
+
+    /// The traits implemented by the struct or the enum at position, both
+    /// the derived ones and the ones with an impl in the crate of the type or
+    /// in the current crate.
+    pub fn traits_implemented_by(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<ImplementedTrait>> {
+        self.with_db(|db| implemented_traits::traits_implemented_by(db, position))?
     }
 
     /// Computes parameter information for the given call expression.
@@ -418,6 +677,19 @@ impl Analysis {
         self.with_db(|db| parent_module::parent_module(db, position))?
     }
 
+    /// Returns the tree of modules of the given crate. `None` if the crate
+    /// root is not a part of the analysis.
+    pub fn module_tree(&self, crate_id: CrateId) -> Cancelable<Option<ModuleTreeNode>> {
+        self.with_db(|db| module_tree::module_tree(db, crate_id))?
+    }
+
+    /// Checks if the file belongs to a library (a dependency or the standard
+    /// library), as opposed to the local workspace. Library files are not
+    /// supposed to be edited.
+    pub fn is_library_file(&self, file_id: FileId) -> bool {
+        self.db.is_library_file(file_id)
+    }
+
     /// Returns crates this file belongs too.
     pub fn crate_for(&self, file_id: FileId) -> Cancelable<Vec<CrateId>> {
         self.with_db(|db| db.crate_for(file_id))?
@@ -428,30 +700,108 @@ impl Analysis {
         Ok(self.db.crate_graph().crate_root(crate_id))
     }
 
+    /// Returns the edition of the given crate.
+    pub fn crate_edition(&self, crate_id: CrateId) -> Cancelable<Edition> {
+        Ok(self.db.crate_graph().edition(crate_id))
+    }
+
+    /// Returns the direct dependencies of the given crate, with the names
+    /// they are known under in it.
+    pub fn crate_dependencies(&self, crate_id: CrateId) -> Cancelable<Vec<Dependency>> {
+        Ok(self
+            .db
+            .crate_graph()
+            .dependencies(crate_id)
+            .cloned()
+            .collect())
+    }
+
+    /// Returns the given crate and all the crates which depend on it, directly
+    /// or transitively.
+    pub fn transitive_reverse_dependencies(&self, crate_id: CrateId) -> Cancelable<Vec<CrateId>> {
+        Ok(self
+            .db
+            .crate_graph()
+            .transitive_reverse_dependencies(crate_id))
+    }
+
     /// Returns the set of possible targets to run for the current file.
     pub fn runnables(&self, file_id: FileId) -> Cancelable<Vec<Runnable>> {
         self.db
             .catch_canceled(|db| runnables::runnables(db, file_id))?
     }
 
+    /// Returns the tests and benchmarks of the given crate, grouped by
+    /// module. `None` if the crate root is not a part of the analysis.
+    pub fn crate_tests(&self, crate_id: CrateId) -> Cancelable<Option<TestModule>> {
+        self.with_db(|db| runnables::crate_tests(db, crate_id))?
+    }
+
+    /// Finds the tests of the item at point, to jump to them or to run them.
+    /// The tests which refer to the item come first, followed by the tests
+    /// in `#[cfg(test)]` modules which are named after it.
+    pub fn related_tests(&self, position: FilePosition) -> Cancelable<Vec<RelatedTest>> {
+        self.with_db(|db| runnables::related_tests(db, position))?
+    }
+
+    /// Computes the enabled kinds of inlay hints, like parameter names at
+    /// call sites, for the given file.
+    pub fn inlay_hints(
+        &self,
+        file_id: FileId,
+        config: &InlayHintsConfig,
+    ) -> Cancelable<Vec<InlayHint>> {
+        self.db
+            .catch_canceled(|db| inlay_hints::inlay_hints(db, file_id, config))?
+    }
+
+    /// Finds the ranges of the file which refer to other files, like module
+    /// declarations, or to web pages.
+    pub fn document_links(&self, file_id: FileId) -> Cancelable<Vec<DocumentLink>> {
+        self.db
+            .catch_canceled(|db| document_links::document_links(db, file_id))
+    }
+
+    /// Finds the file which the link at `frange` refers to.
+    pub fn resolve_document_link(&self, frange: FileRange) -> Cancelable<Option<FileId>> {
+        self.db
+            .catch_canceled(|db| document_links::resolve_document_link(db, frange))?
+    }
+
     /// Computes syntax highlighting for the given file.
     pub fn highlight(&self, file_id: FileId) -> Cancelable<Vec<HighlightedRange>> {
         self.db
             .catch_canceled(|db| syntax_highlighting::highlight(db, file_id))?
     }
 
+    /// Finds the operations which are allowed only in `unsafe` code, like
+    /// calls of `unsafe fn`s, and the `unsafe` blocks themselves.
+    pub fn unsafe_ranges(&self, file_id: FileId) -> Cancelable<Vec<UnsafeRange>> {
+        self.db
+            .catch_canceled(|db| syntax_highlighting::unsafe_ranges(db, file_id))?
+    }
+
     /// Computes completions at the given position.
-    pub fn completions(&self, position: FilePosition) -> Cancelable<Option<Vec<CompletionItem>>> {
+    pub fn completions(
+        &self,
+        position: FilePosition,
+        config: &CompletionConfig,
+    ) -> Cancelable<Option<Vec<CompletionItem>>> {
         let completions = self
             .db
-            .catch_canceled(|db| completion::completions(db, position))??;
+            .catch_canceled(|db| completion::completions(db, position, config))??;
         Ok(completions.map(|it| it.into()))
     }
 
     /// Computes assists (aks code actons aka intentions) for the given
     /// position.
-    pub fn assists(&self, frange: FileRange) -> Cancelable<Vec<SourceChange>> {
-        Ok(self.db.assists(frange))
+    /// The assists which add imports use `import_style`.
+    pub fn assists(
+        &self,
+        frange: FileRange,
+        import_style: ImportStyle,
+    ) -> Cancelable<Vec<SourceChange>> {
+        self.with_db(|db| db.assists(frange, import_style))?
     }
 
     /// Computes the set of diagnostics for the given file.
@@ -459,19 +809,30 @@ impl Analysis {
         self.with_db(|db| db.diagnostics(file_id))?
     }
 
-    /// Computes the type of the expression at the given position.
+    /// Computes the type of the expression or pattern covering the given
+    /// range. Used to implement "show type of selection".
     pub fn type_of(&self, frange: FileRange) -> Cancelable<Option<String>> {
         self.with_db(|db| hover::type_of(db, frange))?
     }
 
+    /// Returns the range of the identifier which would be renamed by `rename`
+    /// at the position, so that the editor can show it before asking for the
+    /// new name.
+    pub fn prepare_rename(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Result<FileRange, RenameError>> {
+        self.with_db(|db| references::prepare_rename(db, position))?
+    }
+
     /// Returns the edit required to rename reference at the position to the new
-    /// name.
+    /// name, or the reason why the rename is not possible.
     pub fn rename(
         &self,
         position: FilePosition,
         new_name: &str,
-    ) -> Cancelable<Vec<SourceFileEdit>> {
-        self.with_db(|db| db.rename(position, new_name))?
+    ) -> Cancelable<Result<Vec<SourceFileEdit>, RenameError>> {
+        self.with_db(|db| references::rename(db, position, new_name))?
     }
 
     fn with_db<F: FnOnce(&db::RootDatabase) -> T + std::panic::UnwindSafe, T>(
@@ -503,9 +864,9 @@ impl LibraryData {
         root_id: SourceRootId,
         files: Vec<(FileId, RelativePathBuf, Arc<String>)>,
     ) -> LibraryData {
-        let symbol_index = SymbolIndex::for_files(files.par_iter().map(|(file_id, _, text)| {
+        let symbol_index = SymbolIndex::for_files(files.par_iter().map(|(file_id, path, text)| {
             let file = SourceFile::parse(text);
-            (*file_id, file)
+            (*file_id, path.clone(), file)
         }));
         let mut root_change = RootChange::default();
         root_change.added = files
@@ -1,16 +1,29 @@
 use std::sync::Arc;
 
 use relative_path::RelativePathBuf;
+use rustc_hash::FxHashMap;
 use test_utils::{extract_offset, extract_range, parse_fixture, CURSOR_MARKER};
 use ra_db::mock::FileMap;
 
-use crate::{Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, FilePosition, FileRange, SourceRootId};
+use crate::{
+    Analysis, AnalysisChange, AnalysisHost, CrateGraph, Edition, FileId, FilePosition, FileRange,
+    SourceRootId,
+};
 
 /// Mock analysis is used in test to bootstrap an AnalysisHost/Analysis
 /// from a set of in-memory files.
 #[derive(Debug, Default)]
 pub struct MockAnalysis {
     files: Vec<(String, String)>,
+    crates: Vec<MockCrate>,
+}
+
+/// A crate declared in the fixture by its root file.
+#[derive(Debug)]
+struct MockCrate {
+    root: FileId,
+    name: String,
+    deps: Vec<String>,
 }
 
 impl MockAnalysis {
@@ -27,6 +40,10 @@ impl MockAnalysis {
     /// //- /foo.rs
     /// struct Baz;
     /// ```
+    ///
+    /// Crate roots can declare the name of the crate and its dependencies, like
+    /// `//- /main.rs crate:main deps:foo,bar`. If no crates are declared, the
+    /// fixture is a single crate.
     pub fn with_files(fixture: &str) -> MockAnalysis {
         let mut res = MockAnalysis::new();
         for entry in parse_fixture(fixture) {
@@ -55,23 +72,47 @@ impl MockAnalysis {
         (res, position)
     }
 
-    pub fn add_file(&mut self, path: &str, text: &str) -> FileId {
-        let file_id = FileId((self.files.len() + 1) as u32);
-        self.files.push((path.to_string(), text.to_string()));
-        file_id
+    /// `meta` is the path of the file, optionally followed by the crate
+    /// declaration.
+    pub fn add_file(&mut self, meta: &str, text: &str) -> FileId {
+        self.push_file(meta, text.to_string())
     }
-    pub fn add_file_with_position(&mut self, path: &str, text: &str) -> FilePosition {
+    pub fn add_file_with_position(&mut self, meta: &str, text: &str) -> FilePosition {
         let (offset, text) = extract_offset(text);
-        let file_id = FileId((self.files.len() + 1) as u32);
-        self.files.push((path.to_string(), text.to_string()));
+        let file_id = self.push_file(meta, text);
         FilePosition { file_id, offset }
     }
-    pub fn add_file_with_range(&mut self, path: &str, text: &str) -> FileRange {
+    pub fn add_file_with_range(&mut self, meta: &str, text: &str) -> FileRange {
         let (range, text) = extract_range(text);
-        let file_id = FileId((self.files.len() + 1) as u32);
-        self.files.push((path.to_string(), text.to_string()));
+        let file_id = self.push_file(meta, text);
         FileRange { file_id, range }
     }
+    fn push_file(&mut self, meta: &str, text: String) -> FileId {
+        let file_id = FileId((self.files.len() + 1) as u32);
+        let mut components = meta.split_whitespace();
+        let path = components.next().expect("fixture entry without a path");
+        let mut krate = None;
+        for component in components {
+            if component.starts_with("crate:") {
+                krate = Some(MockCrate {
+                    root: file_id,
+                    name: component["crate:".len()..].to_string(),
+                    deps: Vec::new(),
+                });
+            } else if component.starts_with("deps:") {
+                let krate = krate.as_mut().expect("`deps:` without a `crate:`");
+                krate.deps = component["deps:".len()..]
+                    .split(',')
+                    .map(|it| it.to_string())
+                    .collect();
+            } else {
+                panic!("unknown fixture meta: {}", component);
+            }
+        }
+        self.crates.extend(krate);
+        self.files.push((path.to_string(), text));
+        file_id
+    }
     pub fn id_of(&self, path: &str) -> FileId {
         let (idx, _) = self
             .files
@@ -88,15 +129,33 @@ impl MockAnalysis {
         let mut change = AnalysisChange::new();
         change.add_root(source_root, true);
         let mut crate_graph = CrateGraph::default();
+        let declares_crates = !self.crates.is_empty();
         for (path, contents) in self.files.into_iter() {
             assert!(path.starts_with('/'));
             let path = RelativePathBuf::from_path(&path[1..]).unwrap();
             let file_id = file_map.add(path.clone());
-            if path == "/lib.rs" || path == "/main.rs" {
-                crate_graph.add_crate_root(file_id);
+            if !declares_crates && (path == "/lib.rs" || path == "/main.rs") {
+                crate_graph.add_crate_root(file_id, Edition::Edition2018);
             }
             change.add_file(source_root, file_id, path, Arc::new(contents));
         }
+        let crate_ids = self
+            .crates
+            .iter()
+            .map(|krate| {
+                let crate_id = crate_graph.add_crate_root(krate.root, Edition::Edition2018);
+                crate_graph.set_display_name(crate_id, krate.name.as_str().into());
+                (krate.name.as_str(), crate_id)
+            })
+            .collect::<FxHashMap<_, _>>();
+        for krate in self.crates.iter() {
+            for dep in krate.deps.iter() {
+                let to = *crate_ids
+                    .get(dep.as_str())
+                    .unwrap_or_else(|| panic!("unknown crate: {}", dep));
+                crate_graph.add_dep(crate_ids[krate.name.as_str()], dep.as_str().into(), to);
+            }
+        }
         change.set_crate_graph(crate_graph);
         // change.set_file_resolver(Arc::new(file_map));
         host.apply_change(change);
@@ -0,0 +1,98 @@
+use rustc_hash::FxHashSet;
+use ra_db::{Cancelable, CrateId, FilesDatabase};
+use hir::{Module, source_binder};
+
+use crate::{NavigationTarget, db::RootDatabase};
+
+/// A module of a crate, together with its submodules.
+#[derive(Debug)]
+pub struct ModuleTreeNode {
+    pub navigation_target: NavigationTarget,
+    pub children: Vec<ModuleTreeNode>,
+}
+
+/// Builds the tree of modules of the crate, starting at the crate root.
+pub(crate) fn module_tree(
+    db: &RootDatabase,
+    crate_id: CrateId,
+) -> Cancelable<Option<ModuleTreeNode>> {
+    let root_file = db.crate_graph().crate_root(crate_id);
+    let root = ctry!(source_binder::module_from_file_id(db, root_file)?);
+    let mut visited = FxHashSet::default();
+    module_tree_node(db, root, &mut visited).map(Some)
+}
+
+fn module_tree_node(
+    db: &RootDatabase,
+    module: Module,
+    visited: &mut FxHashSet<Module>,
+) -> Cancelable<ModuleTreeNode> {
+    visited.insert(module.clone());
+    let mut children = Vec::new();
+    for child in module.children(db)? {
+        // With `#[path]` attributes, a module may end up among its own
+        // descendants.
+        if visited.contains(&child) {
+            continue;
+        }
+        children.push(module_tree_node(db, child, visited)?);
+    }
+    Ok(ModuleTreeNode {
+        navigation_target: NavigationTarget::from_module(db, module)?,
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::{
+        mock_analysis::analysis_and_position,
+        module_tree::ModuleTreeNode,
+    };
+
+    fn render(node: &ModuleTreeNode, depth: usize, buf: &mut String) {
+        let nav = &node.navigation_target;
+        buf.push_str(&format!(
+            "{}{} {:?} {:?}\n",
+            "  ".repeat(depth),
+            nav.name(),
+            nav.kind(),
+            nav.file_id()
+        ));
+        for child in node.children.iter() {
+            render(child, depth + 1, buf);
+        }
+    }
+
+    #[test]
+    fn builds_module_tree_of_the_crate() {
+        let (analysis, pos) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar {
+                mod baz {}
+            }
+            //- /foo.rs
+            mod spam;
+            //- /foo/spam.rs
+            <|>
+            ",
+        );
+        let crate_id = analysis.crate_for(pos.file_id).unwrap()[0];
+        let tree = analysis.module_tree(crate_id).unwrap().unwrap();
+        let mut actual = String::new();
+        render(&tree, 0, &mut actual);
+        assert_eq_text!(
+            " SOURCE_FILE FileId(1)
+  foo SOURCE_FILE FileId(2)
+    spam SOURCE_FILE FileId(3)
+  bar MODULE FileId(1)
+    baz MODULE FileId(1)
+",
+            &actual
+        );
+    }
+}
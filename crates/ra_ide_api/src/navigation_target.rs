@@ -1,9 +1,10 @@
-use ra_db::{FileId, Cancelable};
+use ra_db::{FileId, Cancelable, FilesDatabase, SyntaxDatabase};
 use ra_syntax::{
-    SyntaxNode, AstNode, SmolStr, TextRange, ast,
-    SyntaxKind::{self, NAME},
+    SyntaxNode, AstNode, SmolStr, TextRange,
+    ast::{self, NameOwner, DocCommentsOwner, StructFlavor},
+    SyntaxKind::{self, NAME, USE_ITEM, ITEM_LIST},
 };
-use hir::{Def, ModuleSource};
+use hir::{Def, HirFileId, ModuleSource, source_binder::NameDefinition};
 
 use crate::{FileSymbol, db::RootDatabase};
 
@@ -19,6 +20,9 @@ pub struct NavigationTarget {
     kind: SyntaxKind,
     full_range: TextRange,
     focus_range: Option<TextRange>,
+    deprecated: bool,
+    container_name: Option<SmolStr>,
+    docs_summary: Option<String>,
 }
 
 impl NavigationTarget {
@@ -46,6 +50,25 @@ impl NavigationTarget {
         self.focus_range
     }
 
+    /// Whether the target is marked with `#[deprecated]`.
+    pub fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    /// The path of the module containing the target, like `mycrate::util::io`.
+    /// For the items of impls and traits, the path ends with the self type or
+    /// the trait, like `mycrate::util::io::Buf`. The symbols of dependencies
+    /// are marked with `(dependency)`. Only computed for workspace symbols.
+    pub fn container_name(&self) -> Option<&SmolStr> {
+        self.container_name.as_ref()
+    }
+
+    /// The first line of the docs of a module, see `module_docs`. Only
+    /// computed for workspace symbols.
+    pub fn docs_summary(&self) -> Option<&str> {
+        self.docs_summary.as_ref().map(|it| it.as_str())
+    }
+
     pub(crate) fn from_symbol(symbol: FileSymbol) -> NavigationTarget {
         NavigationTarget {
             file_id: symbol.file_id,
@@ -53,9 +76,53 @@ impl NavigationTarget {
             kind: symbol.ptr.kind(),
             full_range: symbol.ptr.range(),
             focus_range: None,
+            deprecated: symbol.deprecated,
+            container_name: None,
+            docs_summary: None,
         }
     }
 
+    /// Like `from_symbol`, but also records the path of the module which
+    /// contains the symbol.
+    pub(crate) fn from_symbol_with_container(
+        db: &RootDatabase,
+        symbol: FileSymbol,
+    ) -> Cancelable<NavigationTarget> {
+        let source_file = db.source_file(symbol.file_id);
+        let node = symbol.ptr.resolve(&source_file);
+        // For `mod foo { ... }`, the container is the parent of `foo`.
+        let parent = node.parent().unwrap_or(&*node);
+        let module = hir::source_binder::module_from_child_node(db, symbol.file_id, parent)?;
+        let module_path = match module {
+            Some(module) => container_path(db, module)?,
+            None => None,
+        };
+        let container_name = match (module_path, impl_or_trait_name(&node)) {
+            (Some(path), Some(owner)) => Some(format!("{}::{}", path, owner).into()),
+            (None, Some(owner)) => Some(owner),
+            (path, None) => path,
+        };
+        // Dependencies are searched only on request, so their symbols are
+        // marked to tell them apart from the ones of the workspace.
+        let container_name = if db.is_library_file(symbol.file_id) {
+            let marked = match container_name {
+                Some(it) => format!("{} (dependency)", it),
+                None => "(dependency)".to_string(),
+            };
+            Some(marked.into())
+        } else {
+            container_name
+        };
+        let docs = match ast::Module::cast(&node) {
+            Some(module) => module_docs(db, symbol.file_id, module)?,
+            None => None,
+        };
+        let mut res = NavigationTarget::from_symbol(symbol);
+        res.container_name = container_name;
+        res.docs_summary = docs.and_then(|it| it.lines().next().map(|it| it.to_string()));
+        Ok(res)
+    }
+
     pub(crate) fn from_scope_entry(
         file_id: FileId,
         entry: &hir::ScopeEntryWithSyntax,
@@ -66,6 +133,9 @@ impl NavigationTarget {
             full_range: entry.ptr().range(),
             focus_range: None,
             kind: NAME,
+            deprecated: false,
+            container_name: None,
+            docs_summary: None,
         }
     }
 
@@ -89,40 +159,86 @@ impl NavigationTarget {
         Ok(res)
     }
 
+    /// Navigates to the whole file, which is named after the file itself.
+    pub(crate) fn from_file(db: &RootDatabase, file_id: FileId) -> NavigationTarget {
+        let path = db.file_relative_path(file_id);
+        let name = path.file_name().unwrap_or_default().into();
+        let file = db.source_file(file_id);
+        NavigationTarget::from_syntax(file_id, name, None, file.syntax())
+    }
+
+    /// Navigates to the `mod foo;` or `mod foo { ... }` declaration of the
+    /// module. Crate roots don't have declarations, so for them this is the
+    /// same as `from_module`.
+    pub(crate) fn from_module_to_decl(
+        db: &RootDatabase,
+        module: hir::Module,
+    ) -> Cancelable<NavigationTarget> {
+        match module.declaration_source(db)? {
+            Some((file_id, node)) => Ok(NavigationTarget::from_named(file_id, &*node)),
+            None => NavigationTarget::from_module(db, module),
+        }
+    }
+
+    pub(crate) fn from_fn_def(file_id: FileId, fn_def: &ast::FnDef) -> NavigationTarget {
+        NavigationTarget::from_named(file_id, fn_def)
+    }
+
+    /// Navigates to the `use` item of an import, focusing the imported path.
+    pub(crate) fn from_import(
+        db: &RootDatabase,
+        name: SmolStr,
+        import: &hir::NamedImport,
+    ) -> NavigationTarget {
+        let use_item = import.use_item(db);
+        let full_range = import.file_id.original_range(db, use_item.syntax().range());
+        let focus_range = import.file_id.original_range(db, import.range(db));
+        NavigationTarget {
+            file_id: full_range.file_id,
+            name,
+            kind: USE_ITEM,
+            full_range: full_range.range,
+            focus_range: Some(focus_range.range),
+            deprecated: false,
+            container_name: None,
+            docs_summary: None,
+        }
+    }
+
     // TODO once Def::Item is gone, this should be able to always return a NavigationTarget
     pub(crate) fn from_def(db: &RootDatabase, def: Def) -> Cancelable<Option<NavigationTarget>> {
         let res = match def {
             Def::Struct(s) => {
                 let (file_id, node) = s.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Enum(e) => {
                 let (file_id, node) = e.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::EnumVariant(ev) => {
                 let (file_id, node) = ev.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Function(f) => {
                 let (file_id, node) = f.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Trait(f) => {
                 let (file_id, node) = f.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Type(f) => {
                 let (file_id, node) = f.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Static(f) => {
                 let (file_id, node) = f.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Const(f) => {
                 let (file_id, node) = f.source(db)?;
-                NavigationTarget::from_named(file_id.original_file(db), &*node)
+                NavigationTarget::from_hir_named(db, file_id, &*node)
             }
             Def::Module(m) => NavigationTarget::from_module(db, m)?,
             Def::Item => return Ok(None),
@@ -130,6 +246,50 @@ impl NavigationTarget {
         Ok(Some(res))
     }
 
+    /// Navigates to the definition found by `source_binder::def_for_node` for
+    /// a node in `file_id`.
+    pub(crate) fn from_name_definition(
+        db: &RootDatabase,
+        file_id: FileId,
+        def: NameDefinition,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let res = match def {
+            NameDefinition::Local(_, entry) => NavigationTarget::from_scope_entry(file_id, &entry),
+            NameDefinition::Field(field) => return NavigationTarget::from_field(db, &field),
+            NameDefinition::Method(function) => {
+                return NavigationTarget::from_def(db, Def::Function(function));
+            }
+            NameDefinition::Item(def) => return NavigationTarget::from_def(db, def),
+            NameDefinition::Lifetime(ptr) => {
+                let node = ptr.resolve(&db.source_file(file_id));
+                let name = node
+                    .first_child()
+                    .and_then(|it| it.leaf_text())
+                    .cloned()
+                    .unwrap_or_default();
+                NavigationTarget::from_syntax(file_id, name, None, &node)
+            }
+        };
+        Ok(Some(res))
+    }
+
+    fn from_field(
+        db: &RootDatabase,
+        field: &hir::StructField,
+    ) -> Cancelable<Option<NavigationTarget>> {
+        let (file_id, struct_def) = field.parent_struct().source(db)?;
+        let fields = match struct_def.flavor() {
+            StructFlavor::Named(it) => it,
+            _ => return Ok(None),
+        };
+        let name = field.name().to_string();
+        let res = fields
+            .fields()
+            .find(|it| it.name().map_or(false, |it| it.text().as_str() == name))
+            .map(|it| NavigationTarget::from_hir_named(db, file_id, it));
+        Ok(res)
+    }
+
     #[cfg(test)]
     pub(crate) fn assert_match(&self, expected: &str) {
         let actual = self.debug_render();
@@ -151,6 +311,19 @@ impl NavigationTarget {
         buf
     }
 
+    /// Like `from_named`, but the node may come from a macro expansion, so
+    /// its ranges are mapped back to the macro call.
+    fn from_hir_named(
+        db: &RootDatabase,
+        file_id: HirFileId,
+        node: &impl ast::NameOwner,
+    ) -> NavigationTarget {
+        let mut res = NavigationTarget::from_named(file_id.original_file(db), node);
+        res.full_range = file_id.original_range(db, res.full_range).range;
+        res.focus_range = res.focus_range.map(|it| file_id.original_range(db, it).range);
+        res
+    }
+
     fn from_named(file_id: FileId, node: &impl ast::NameOwner) -> NavigationTarget {
         let name = node.name().map(|it| it.text().clone()).unwrap_or_default();
         let focus_range = node.name().map(|it| it.syntax().range());
@@ -169,7 +342,81 @@ impl NavigationTarget {
             kind: node.kind(),
             full_range: node.range(),
             focus_range,
+            deprecated: node
+                .children()
+                .filter_map(ast::Attr::cast)
+                .any(|attr| attr.name().map_or(false, |it| it == "deprecated")),
+            container_name: None,
+            docs_summary: None,
             // ptr: Some(LocalSyntaxPtr::new(node)),
         }
     }
 }
+
+/// The docs of the module declared by `decl`: the `///` comments on the
+/// declaration, followed by the `//!` comments of the module, which for
+/// `mod foo;` are in the file of `foo`.
+pub(crate) fn module_docs(
+    db: &RootDatabase,
+    file_id: FileId,
+    decl: &ast::Module,
+) -> Cancelable<Option<String>> {
+    let mut docs = vec![decl.doc_comment_text()];
+    match decl.item_list() {
+        Some(item_list) => docs.push(item_list.module_doc_text()),
+        None => {
+            let module = hir::source_binder::module_from_declaration(db, file_id, decl)?;
+            if let Some(module) = module {
+                if let (_, ModuleSource::SourceFile(file)) = module.definition_source(db)? {
+                    docs.push(file.module_doc_text());
+                }
+            }
+        }
+    }
+    docs.retain(|it| !it.is_empty());
+    if docs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(docs.join("\n\n")))
+}
+
+/// The name of the self type of the impl, or of the trait, which contains the
+/// item, without the generic arguments.
+fn impl_or_trait_name(item: &SyntaxNode) -> Option<SmolStr> {
+    let container = item.parent().filter(|it| it.kind() == ITEM_LIST)?.parent()?;
+    if let Some(impl_block) = ast::ImplBlock::cast(container) {
+        let text = impl_block.target_type()?.syntax().text().to_string();
+        let name = text.split('<').next().unwrap_or_default().trim();
+        return Some(name.into());
+    }
+    ast::TraitDef::cast(container)?.name().map(|it| it.text().clone())
+}
+
+/// Renders the path of `module` as `krate::a::b`. The crate is named as its
+/// dependents know it, or by its own display name if nothing depends on it.
+fn container_path(db: &RootDatabase, module: hir::Module) -> Cancelable<Option<SmolStr>> {
+    let mut segments = Vec::new();
+    for module in module.path_to_root(db)?.into_iter().rev() {
+        match module.name(db)? {
+            Some(name) => segments.push(name.to_string()),
+            None => {
+                let crate_graph = db.crate_graph();
+                let krate = match module.krate(db)? {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let crate_id = krate.crate_id();
+                let name = crate_graph
+                    .dependency_name(crate_id)
+                    .or_else(|| crate_graph.display_name(crate_id));
+                if let Some(name) = name {
+                    segments.push(name.to_string());
+                }
+            }
+        }
+    }
+    if segments.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(segments.join("::").into()))
+}
@@ -2,18 +2,16 @@ use ra_db::{Cancelable, FilePosition};
 
 use crate::{NavigationTarget, db::RootDatabase};
 
-/// This returns `Vec` because a module may be included from several places. We
-/// don't handle this case yet though, so the Vec has length at most one.
+/// This returns `Vec` because a module may be included from several places.
+/// Each target points to the `mod` declaration which includes the module.
 pub(crate) fn parent_module(
     db: &RootDatabase,
     position: FilePosition,
 ) -> Cancelable<Vec<NavigationTarget>> {
-    let module = match hir::source_binder::module_from_position(db, position)? {
-        None => return Ok(Vec::new()),
-        Some(it) => it,
-    };
-    let nav = NavigationTarget::from_module(db, module)?;
-    Ok(vec![nav])
+    hir::source_binder::modules_from_position(db, position)?
+        .into_iter()
+        .map(|module| NavigationTarget::from_module_to_decl(db, module))
+        .collect()
 }
 
 #[cfg(test)]
@@ -31,7 +29,7 @@ mod tests {
             ",
         );
         let nav = analysis.parent_module(pos).unwrap().pop().unwrap();
-        nav.assert_match("foo SOURCE_FILE FileId(2) [0; 10)");
+        nav.assert_match("foo MODULE FileId(1) [0; 8) [4; 7)");
     }
 
     #[test]
@@ -47,6 +45,25 @@ mod tests {
             ",
         );
         let nav = analysis.parent_module(pos).unwrap().pop().unwrap();
-        nav.assert_match("baz MODULE FileId(1) [32; 44)");
+        nav.assert_match("baz MODULE FileId(1) [32; 44) [36; 39)");
+    }
+
+    #[test]
+    fn test_resolve_parent_module_included_twice() {
+        let (analysis, pos) = analysis_and_position(
+            "
+            //- /main.rs
+            mod foo;
+            //- /lib.rs
+            mod foo;
+            //- /foo.rs
+            <|>// empty
+            ",
+        );
+        let mut navs = analysis.parent_module(pos).unwrap();
+        navs.sort_by_key(|nav| nav.file_id());
+        assert_eq!(navs.len(), 2);
+        navs[0].assert_match("foo MODULE FileId(1) [0; 8) [4; 7)");
+        navs[1].assert_match("foo MODULE FileId(2) [0; 8) [4; 7)");
     }
 }
@@ -0,0 +1,1129 @@
+use std::time::Instant;
+
+use ra_db::{FileId, Cancelable, SyntaxDatabase, FilesDatabase, BaseDatabase};
+use ra_syntax::{
+    AstNode, Direction, SmolStr, SourceFile, SyntaxNode, TextRange, TextUnit,
+    ast::{self, BinOp, NameOwner, StructFlavor},
+    tokenize,
+    algo::{find_covering_node, find_leaf_at_offset, find_node_at_offset},
+    SyntaxKind::*,
+};
+use ra_text_edit::TextEditBuilder;
+use hir::{Def, source_binder::{self, NameDefinition}, ScopesWithSyntaxMapping};
+use rustc_hash::FxHashSet;
+
+use crate::{
+    db::RootDatabase, FilePosition, FileRange, NavigationTarget, Reference, ReferenceAccess,
+    ReferenceKind, ReferencesConfig, RenameError, SourceFileEdit, Partial,
+    search_scope::SearchScope,
+    budget::{deadline_passed, complete_in_background},
+};
+
+pub(crate) fn find_all_refs(
+    db: &RootDatabase,
+    position: FilePosition,
+    config: &ReferencesConfig,
+) -> Cancelable<Partial<Vec<Reference>>> {
+    let only_file = if config.current_file_only { Some(position.file_id) } else { None };
+    let refs = match find_field_refs(db, position, config.deadline, only_file)? {
+        Some(refs) => refs,
+        None => match find_local_refs(db, position)? {
+            Some(refs) => Partial::new(refs, false),
+            None => find_item_refs(db, position, config.deadline, only_file)?
+                .unwrap_or_else(|| Partial::new(Vec::new(), false)),
+        },
+    };
+    let res = refs
+        .value
+        .into_iter()
+        .filter(|it| only_file.map_or(true, |file_id| it.file_id == file_id))
+        .filter(|it| match it.kind {
+            ReferenceKind::Definition | ReferenceKind::Declaration => config.include_declaration,
+            ReferenceKind::Import => config.include_imports,
+            ReferenceKind::MacroText => config.include_macro_text,
+            ReferenceKind::Reference => true,
+        })
+        .collect();
+    Ok(Partial::new(res, refs.is_incomplete))
+}
+
+/// Calls `f` for each of the `files` until the deadline passes, and returns
+/// whether some of the files were skipped. The skipped files are parsed in
+/// the background. With `only_file`, the other files are not searched at all.
+fn search_files(
+    db: &RootDatabase,
+    mut files: Vec<FileId>,
+    deadline: Option<Instant>,
+    only_file: Option<FileId>,
+    mut f: impl FnMut(FileId) -> Cancelable<()>,
+) -> Cancelable<bool> {
+    if let Some(only_file) = only_file {
+        files.retain(|&it| it == only_file);
+    }
+    for (idx, &file_id) in files.iter().enumerate() {
+        db.check_canceled()?;
+        if deadline_passed(deadline) {
+            let skipped = files[idx..].to_vec();
+            complete_in_background(db, move |db| {
+                for file_id in skipped {
+                    db.check_canceled()?;
+                    db.source_file(file_id);
+                }
+                Ok(())
+            });
+            return Ok(true);
+        }
+        f(file_id)?;
+    }
+    Ok(false)
+}
+
+pub(crate) fn prepare_rename(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Result<FileRange, RenameError>> {
+    let file = db.source_file(position.file_id);
+    let ident =
+        find_leaf_at_offset(file.syntax(), position.offset).find(|leaf| leaf.kind() == IDENT);
+    let range = match ident {
+        Some(it) => it.range(),
+        None => return Ok(Err(RenameError::NotApplicable)),
+    };
+    let refs = find_all_refs(db, position, &ReferencesConfig::default())?.value;
+    // Shorthand pattern references cover the whole binding, like `ref foo`.
+    if !refs
+        .iter()
+        .any(|it| it.file_id == position.file_id && range.is_subrange(&it.range))
+    {
+        return Ok(Err(RenameError::NotApplicable));
+    }
+    if refs.iter().any(|it| db.is_library_file(it.file_id)) {
+        return Ok(Err(RenameError::LibraryItem));
+    }
+    Ok(Ok(FileRange {
+        file_id: position.file_id,
+        range,
+    }))
+}
+
+pub(crate) fn rename(
+    db: &RootDatabase,
+    position: FilePosition,
+    new_name: &str,
+) -> Cancelable<Result<Vec<SourceFileEdit>, RenameError>> {
+    if let Err(err) = check_identifier(new_name) {
+        return Ok(Err(err));
+    }
+    // A rename must see every reference, so there is no deadline.
+    let (refs, conflict, is_field) = match find_field_refs(db, position, None, None)? {
+        Some(refs) => {
+            let refs = refs.value;
+            let conflict = field_conflict(db, &refs[0], new_name);
+            (refs, conflict, true)
+        }
+        None => match find_local_refs(db, position)? {
+            Some(refs) => (refs, local_conflict(db, position, new_name)?, false),
+            // The imports are renamed too, `use a::{Foo as Bar}` keeps `Bar`.
+            None => {
+                let refs = find_item_refs(db, position, None, None)?
+                    .map(|it| it.value)
+                    .unwrap_or_default();
+                (refs, None, false)
+            }
+        },
+    };
+    // The textual matches in macros may be something else entirely, so they
+    // are left as they are.
+    let refs = refs
+        .into_iter()
+        .filter(|it| it.kind != ReferenceKind::MacroText)
+        .collect::<Vec<_>>();
+    if refs.iter().any(|it| db.is_library_file(it.file_id)) {
+        return Ok(Err(RenameError::LibraryItem));
+    }
+    if let Some(range) = conflict {
+        return Ok(Err(RenameError::Conflict {
+            name: new_name.to_string(),
+            range,
+        }));
+    }
+    let res = refs
+        .into_iter()
+        .map(|reference| {
+            let mut builder = TextEditBuilder::default();
+            if !reference.is_shorthand {
+                builder.replace(reference.range, new_name.into());
+            } else if is_field {
+                // `S { foo }` becomes `S { bar: foo }`.
+                builder.insert(reference.range.start(), format!("{}: ", new_name));
+            } else {
+                // `S { foo }` becomes `S { foo: bar }`.
+                let (range, text) = expand_shorthand_binding(db, &reference, new_name);
+                builder.replace(range, text);
+            }
+            SourceFileEdit {
+                file_id: reference.file_id,
+                edit: builder.finish(),
+            }
+        })
+        .collect();
+    Ok(Ok(res))
+}
+
+/// Renames the local binding in a shorthand field, keeping the field name.
+/// The binding might come with modifiers, so `S { ref mut foo }` becomes
+/// `S { foo: ref mut bar }`.
+fn expand_shorthand_binding(
+    db: &RootDatabase,
+    reference: &Reference,
+    new_name: &str,
+) -> (TextRange, String) {
+    let file = db.source_file(reference.file_id);
+    let name = find_covering_node(file.syntax(), reference.range);
+    let old_name = name.text().to_string();
+    let node = match name.ancestors().find_map(ast::BindPat::cast) {
+        Some(bind_pat) => bind_pat.syntax(),
+        None => name,
+    };
+    let mut renamed = node.text().to_string();
+    let name_start = reference.range.start() - node.range().start();
+    let name_range = TextRange::offset_len(name_start, reference.range.len());
+    renamed.replace_range(
+        name_range.start().to_usize()..name_range.end().to_usize(),
+        new_name,
+    );
+    (node.range(), format!("{}: {}", old_name, renamed))
+}
+
+/// Checks that `name` can be used as an identifier, suggesting the raw
+/// identifier for keywords.
+fn check_identifier(name: &str) -> Result<(), RenameError> {
+    let invalid = |suggestion| {
+        Err(RenameError::InvalidName {
+            name: name.to_string(),
+            suggestion,
+        })
+    };
+    let is_raw = name.starts_with("r#");
+    let text = if is_raw { &name[2..] } else { name };
+    match tokenize(text).as_slice() {
+        [token] if token.len == TextUnit::of_str(text) => match token.kind {
+            IDENT => Ok(()),
+            SELF_KW | SUPER_KW | CRATE_KW => invalid(None),
+            kind if kind.is_keyword() && is_raw => Ok(()),
+            kind if kind.is_keyword() => invalid(Some(format!("r#{}", name))),
+            _ => invalid(None),
+        },
+        _ => invalid(None),
+    }
+}
+
+/// Checks that renaming the field doesn't clash with another field of the
+/// same struct.
+fn field_conflict(db: &RootDatabase, decl: &Reference, new_name: &str) -> Option<FileRange> {
+    let file = db.source_file(decl.file_id);
+    let field = find_covering_node(file.syntax(), decl.range)
+        .ancestors()
+        .find_map(ast::NamedFieldDef::cast)?;
+    let other = field
+        .syntax()
+        .parent()?
+        .children()
+        .filter_map(ast::NamedFieldDef::cast)
+        .filter_map(|it| it.name())
+        .find(|it| it.text().as_str() == new_name)?;
+    Some(FileRange {
+        file_id: decl.file_id,
+        range: other.syntax().range(),
+    })
+}
+
+/// Checks that renaming the local binding doesn't change what the names in
+/// the function refer to: neither the usages of the binding may resolve to
+/// some other binding, nor the existing usages of the new name may resolve to
+/// the renamed binding.
+fn local_conflict(
+    db: &RootDatabase,
+    position: FilePosition,
+    new_name: &str,
+) -> Cancelable<Option<FileRange>> {
+    let file = db.source_file(position.file_id);
+    let (binding, descr) = ctry!(find_binding(db, &file, position)?);
+    let old_name = ctry!(binding.name()).text().clone();
+    let fn_def = ctry!(binding.syntax().ancestors().find_map(ast::FnDef::cast));
+    let scopes = descr.scopes(db)?;
+    let binding_range = binding.syntax().range();
+    let conflict = |range| {
+        Ok(Some(FileRange {
+            file_id: position.file_id,
+            range,
+        }))
+    };
+
+    if let Some(other) = visible_binding(&scopes, binding.syntax(), new_name) {
+        return conflict(other);
+    }
+    for name_ref in fn_def.syntax().descendants().filter_map(ast::NameRef::cast) {
+        let refers_to_binding =
+            visible_binding(&scopes, name_ref.syntax(), &old_name) == Some(binding_range);
+        if !refers_to_binding {
+            continue;
+        }
+        if *name_ref.text() == old_name {
+            if let Some(other) = visible_binding(&scopes, name_ref.syntax(), new_name) {
+                return conflict(other);
+            }
+        } else if name_ref.text().as_str() == new_name {
+            return conflict(name_ref.syntax().range());
+        }
+    }
+    Ok(None)
+}
+
+/// The range of the local binding named `name` which is visible at `node`.
+fn visible_binding(
+    scopes: &ScopesWithSyntaxMapping,
+    node: &SyntaxNode,
+    name: &str,
+) -> Option<TextRange> {
+    let mut shadowed = FxHashSet::default();
+    let entry = scopes
+        .scope_chain(node)
+        .flat_map(|scope| scopes.scopes.entries(scope).iter())
+        .filter(|entry| shadowed.insert(entry.name()))
+        .find(|entry| entry.name().to_string() == name)?;
+    let ptr = scopes.syntax_mapping.pat_syntax(entry.pat())?;
+    Some(ptr.range())
+}
+
+fn find_local_refs(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Option<Vec<Reference>>> {
+    let file = db.source_file(position.file_id);
+    // Find the binding associated with the offset
+    let (binding, descr) = ctry!(find_binding(db, &file, position)?);
+
+    // `let S { foo } = s;`
+    let is_shorthand = binding.syntax().parent().map(|it| it.kind()) == Some(FIELD_PAT_LIST);
+    let mut ret = binding
+        .name()
+        .into_iter()
+        .map(|name| Reference {
+            file_id: position.file_id,
+            range: name.syntax().range(),
+            kind: ReferenceKind::Definition,
+            access: None,
+            is_shorthand,
+        })
+        .collect::<Vec<_>>();
+    ret.extend(
+        descr
+            .scopes(db)?
+            .find_all_refs(binding)
+            .into_iter()
+            .filter_map(|ref_desc| {
+                let node = find_covering_node(file.syntax(), ref_desc.range);
+                let name_ref = node.ancestors().find_map(ast::NameRef::cast)?;
+                let parent = name_ref.syntax().parent()?;
+                // Field names in `s.foo` and `S { foo: 92 }` only look like
+                // the binding.
+                if ast::FieldExpr::cast(parent).is_some() {
+                    return None;
+                }
+                let named_field = ast::NamedField::cast(parent);
+                if named_field.map_or(false, |it| it.expr().is_some()) {
+                    return None;
+                }
+                let access = node
+                    .ancestors()
+                    .find_map(ast::PathExpr::cast)
+                    .map_or(ReferenceAccess::Read, |it| expr_access(it.syntax()));
+                Some(Reference {
+                    file_id: position.file_id,
+                    range: ref_desc.range,
+                    kind: ReferenceKind::Reference,
+                    access: Some(access),
+                    // `S { foo }` in a struct literal.
+                    is_shorthand: named_field.is_some(),
+                })
+            }),
+    );
+    Ok(Some(ret))
+}
+
+fn find_binding<'a>(
+    db: &RootDatabase,
+    source_file: &'a SourceFile,
+    position: FilePosition,
+) -> Cancelable<Option<(&'a ast::BindPat, hir::Function)>> {
+    let syntax = source_file.syntax();
+    if let Some(binding) = find_node_at_offset::<ast::BindPat>(syntax, position.offset) {
+        let descr = ctry!(source_binder::function_from_child_node(
+            db,
+            position.file_id,
+            binding.syntax(),
+        )?);
+        return Ok(Some((binding, descr)));
+    };
+    let name_ref = ctry!(find_node_at_offset::<ast::NameRef>(syntax, position.offset));
+    let (descr, entry) =
+        match source_binder::def_for_node(db, position.file_id, name_ref.syntax())? {
+            Some(NameDefinition::Local(function, entry)) => (function, entry),
+            _ => return Ok(None),
+        };
+    let resolved = entry.ptr().resolve(source_file);
+    let binding = ctry!(find_node_at_offset::<ast::BindPat>(
+        syntax,
+        resolved.range().end()
+    ));
+    Ok(Some((binding, descr)))
+}
+
+/// A struct field is identified by the file and the range of its name in the
+/// struct definition.
+type FieldDecl = (FileId, TextRange);
+
+/// Finds the declaration and all the usages of the struct field at the
+/// position, in the files where the field is visible.
+fn find_field_refs(
+    db: &RootDatabase,
+    position: FilePosition,
+    deadline: Option<Instant>,
+    only_file: Option<FileId>,
+) -> Cancelable<Option<Partial<Vec<Reference>>>> {
+    let file = db.source_file(position.file_id);
+    let (name, field) = ctry!(field_at(db, position.file_id, &file, position.offset)?);
+    let decl = ctry!(field_decl(db, &field)?);
+    let mut res = vec![Reference {
+        file_id: decl.0,
+        range: decl.1,
+        kind: ReferenceKind::Definition,
+        access: None,
+        is_shorthand: false,
+    }];
+    let files = SearchScope::for_field(db, &field)?.files(db)?;
+    let is_incomplete = search_files(db, files, deadline, only_file, |file_id| {
+        // Cheap text search first, most of the files don't mention the
+        // field at all.
+        if !db.file_text(file_id).contains(name.as_str()) {
+            return Ok(());
+        }
+        let file = db.source_file(file_id);
+        let idents = file
+            .syntax()
+            .descendants()
+            .filter(|it| it.kind() == IDENT && it.leaf_text() == Some(&name));
+        for ident in idents {
+            let usage = match FieldUsage::classify(ident) {
+                Some(it) => it,
+                None => continue,
+            };
+            // Resolving a usage may run inference for another function.
+            db.check_canceled()?;
+            if usage.resolve(db, file_id)?.as_ref() == Some(&field) {
+                res.push(usage.to_reference(file_id));
+            }
+        }
+        res.extend(macro_text_refs(file_id, &file, &name));
+        Ok(())
+    })?;
+    Ok(Some(Partial::new(res, is_incomplete)))
+}
+
+fn field_at(
+    db: &RootDatabase,
+    file_id: FileId,
+    file: &SourceFile,
+    offset: TextUnit,
+) -> Cancelable<Option<(SmolStr, hir::StructField)>> {
+    let ident = ctry!(find_leaf_at_offset(file.syntax(), offset).find(|it| it.kind() == IDENT));
+    let name = ctry!(ident.leaf_text()).clone();
+    let field = match FieldUsage::classify(ident) {
+        Some(usage) => usage.resolve(db, file_id)?,
+        // The name of the field in the struct definition.
+        None => match source_binder::def_for_node(db, file_id, ctry!(ident.parent()))? {
+            Some(NameDefinition::Field(it)) => Some(it),
+            _ => None,
+        },
+    };
+    Ok(field.map(|it| (name, it)))
+}
+
+/// A place in the code which mentions a struct field by name.
+enum FieldUsage<'a> {
+    /// `s.foo`
+    Access(&'a ast::FieldExpr, &'a ast::NameRef),
+    /// `S { foo: 92 }` or `S { foo }`
+    Literal(&'a ast::NamedField, &'a ast::NameRef),
+    /// `S { foo: _ }`
+    Pattern(&'a ast::StructPat, &'a SyntaxNode),
+    /// `S { foo }` or `S { ref mut foo }`
+    PatternShorthand(&'a ast::StructPat, &'a ast::BindPat),
+}
+
+impl<'a> FieldUsage<'a> {
+    fn classify(ident: &'a SyntaxNode) -> Option<FieldUsage<'a>> {
+        let parent = ident.parent()?;
+        if let Some(name_ref) = ast::NameRef::cast(parent) {
+            let grand_parent = parent.parent()?;
+            if let Some(field_expr) = ast::FieldExpr::cast(grand_parent) {
+                return Some(FieldUsage::Access(field_expr, name_ref));
+            }
+            if let Some(named_field) = ast::NamedField::cast(grand_parent) {
+                return Some(FieldUsage::Literal(named_field, name_ref));
+            }
+            return None;
+        }
+        if parent.kind() == FIELD_PAT_LIST {
+            let next = ident
+                .siblings(Direction::Next)
+                .skip(1)
+                .find(|it| it.kind() != WHITESPACE)?;
+            if next.kind() != COLON {
+                return None;
+            }
+            let struct_pat = parent.parent().and_then(ast::StructPat::cast)?;
+            return Some(FieldUsage::Pattern(struct_pat, ident));
+        }
+        let bind_pat = ast::Name::cast(parent)?
+            .syntax()
+            .parent()
+            .and_then(ast::BindPat::cast)?;
+        let field_pat_list = bind_pat.syntax().parent()?;
+        if field_pat_list.kind() != FIELD_PAT_LIST {
+            return None;
+        }
+        let struct_pat = field_pat_list.parent().and_then(ast::StructPat::cast)?;
+        Some(FieldUsage::PatternShorthand(struct_pat, bind_pat))
+    }
+
+    fn resolve(&self, db: &RootDatabase, file_id: FileId) -> Cancelable<Option<hir::StructField>> {
+        let node = match self {
+            FieldUsage::Access(_, name_ref) | FieldUsage::Literal(_, name_ref) => name_ref.syntax(),
+            FieldUsage::Pattern(_, ident) => *ident,
+            // The name of the shorthand resolves to the binding it introduces.
+            FieldUsage::PatternShorthand(struct_pat, bind_pat) => {
+                let name = ctry!(bind_pat.name());
+                let path = ctry!(struct_pat.path());
+                return source_binder::struct_field(db, file_id, path, name.text());
+            }
+        };
+        match source_binder::def_for_node(db, file_id, node)? {
+            Some(NameDefinition::Field(it)) => Ok(Some(it)),
+            _ => Ok(None),
+        }
+    }
+
+    fn to_reference(&self, file_id: FileId) -> Reference {
+        let (range, access, is_shorthand) = match self {
+            FieldUsage::Access(field_expr, name_ref) => (
+                name_ref.syntax().range(),
+                expr_access(field_expr.syntax()),
+                false,
+            ),
+            FieldUsage::Literal(named_field, name_ref) => (
+                name_ref.syntax().range(),
+                ReferenceAccess::Write,
+                named_field.expr().is_none(),
+            ),
+            FieldUsage::Pattern(_, ident) => (ident.range(), ReferenceAccess::Read, false),
+            FieldUsage::PatternShorthand(_, bind_pat) => {
+                (bind_pat.syntax().range(), ReferenceAccess::Read, true)
+            }
+        };
+        Reference {
+            file_id,
+            range,
+            kind: ReferenceKind::Reference,
+            access: Some(access),
+            is_shorthand,
+        }
+    }
+}
+
+fn field_decl(db: &RootDatabase, field: &hir::StructField) -> Cancelable<Option<FieldDecl>> {
+    let (file_id, struct_def) = field.parent_struct().source(db)?;
+    let fields = match struct_def.flavor() {
+        StructFlavor::Named(fields) => fields,
+        _ => return Ok(None),
+    };
+    let name = field.name().to_string();
+    let field_name = ctry!(fields
+        .fields()
+        .filter_map(|it| it.name())
+        .find(|it| it.text().as_str() == name));
+    let FileRange { file_id, range } = file_id.original_range(db, field_name.syntax().range());
+    Ok(Some((file_id, range)))
+}
+
+/// Finds the definition and all the usages of the item at the position, in
+/// the files where the item is visible. Like struct fields, items are identified by the
+/// file and the range of their names. Modules are not supported yet.
+fn find_item_refs(
+    db: &RootDatabase,
+    position: FilePosition,
+    deadline: Option<Instant>,
+    only_file: Option<FileId>,
+) -> Cancelable<Option<Partial<Vec<Reference>>>> {
+    let file = db.source_file(position.file_id);
+    let ident = ctry!(find_leaf_at_offset(file.syntax(), position.offset)
+        .find(|it| it.kind() == IDENT));
+    let name = ctry!(ident.leaf_text()).clone();
+    let def = ctry!(item_def(db, position.file_id, ident)?);
+    let is_declaration = match &def {
+        Def::Function(function) => function.source(db)?.1.body().is_none(),
+        _ => false,
+    };
+    let scope = SearchScope::for_def(db, &def)?;
+    let decl = ctry!(item_decl(db, def)?);
+    let mut res = vec![Reference {
+        file_id: decl.file_id,
+        range: decl.range,
+        kind: if is_declaration {
+            ReferenceKind::Declaration
+        } else {
+            ReferenceKind::Definition
+        },
+        access: None,
+        is_shorthand: false,
+    }];
+    let files = scope.files(db)?;
+    let is_incomplete = search_files(db, files, deadline, only_file, |file_id| {
+        if !db.file_text(file_id).contains(name.as_str()) {
+            return Ok(());
+        }
+        let file = db.source_file(file_id);
+        let name_refs = file
+            .syntax()
+            .descendants()
+            .filter(|it| it.kind() == IDENT && it.leaf_text() == Some(&name))
+            .filter(|it| it.parent().and_then(ast::NameRef::cast).is_some());
+        for ident in name_refs {
+            db.check_canceled()?;
+            let usage_decl = match item_def(db, file_id, ident)? {
+                Some(def) => item_decl(db, def)?,
+                None => None,
+            };
+            if usage_decl != Some(decl) {
+                continue;
+            }
+            let is_import = ident.ancestors().any(|it| ast::UseItem::cast(it).is_some());
+            res.push(Reference {
+                file_id,
+                range: ident.range(),
+                kind: if is_import {
+                    ReferenceKind::Import
+                } else {
+                    ReferenceKind::Reference
+                },
+                access: None,
+                is_shorthand: false,
+            });
+        }
+        res.extend(macro_text_refs(file_id, &file, &name));
+        Ok(())
+    })?;
+    Ok(Some(Partial::new(res, is_incomplete)))
+}
+
+/// Identifiers in the bodies of `macro_rules!` definitions can't be resolved
+/// without expanding the macros, so the ones with the same name are reported
+/// as textual matches. Metavariables, like `$foo`, are not.
+fn macro_text_refs(file_id: FileId, file: &SourceFile, name: &SmolStr) -> Vec<Reference> {
+    file.syntax()
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter(|it| {
+            it.path()
+                .and_then(|it| it.segment())
+                .and_then(|it| it.name_ref())
+                .map_or(false, |it| it.text().as_str() == "macro_rules")
+        })
+        .filter_map(|it| it.token_tree())
+        .flat_map(|it| it.syntax().descendants())
+        .filter(|it| it.kind() == IDENT && it.leaf_text() == Some(name))
+        .filter(|it| it.siblings(Direction::Prev).nth(1).map(|it| it.kind()) != Some(DOLLAR))
+        .map(|it| Reference {
+            file_id,
+            range: it.range(),
+            kind: ReferenceKind::MacroText,
+            access: None,
+            is_shorthand: false,
+        })
+        .collect()
+}
+
+/// The item which the name or the name reference `ident` refers to. Method
+/// calls refer to the function.
+fn item_def(db: &RootDatabase, file_id: FileId, ident: &SyntaxNode) -> Cancelable<Option<Def>> {
+    match source_binder::def_for_node(db, file_id, ctry!(ident.parent()))? {
+        Some(NameDefinition::Item(Def::Module(_))) => Ok(None),
+        Some(NameDefinition::Item(def)) => Ok(Some(def)),
+        Some(NameDefinition::Method(function)) => Ok(Some(Def::Function(function))),
+        _ => Ok(None),
+    }
+}
+
+/// The name of the item in its definition.
+fn item_decl(db: &RootDatabase, def: Def) -> Cancelable<Option<FileRange>> {
+    let nav = ctry!(NavigationTarget::from_def(db, def)?);
+    let range = ctry!(nav.focus_range());
+    Ok(Some(FileRange {
+        file_id: nav.file_id(),
+        range,
+    }))
+}
+
+/// Whether the expression is the place being assigned to or mutably borrowed.
+pub(crate) fn expr_access(expr: &SyntaxNode) -> ReferenceAccess {
+    let parent = match expr.parent() {
+        Some(it) => it,
+        None => return ReferenceAccess::Read,
+    };
+    if let Some(bin_expr) = ast::BinExpr::cast(parent) {
+        let is_lhs = bin_expr.lhs().map(|it| it.syntax()) == Some(expr);
+        if is_lhs && bin_expr.op().map_or(false, is_assignment) {
+            return ReferenceAccess::Write;
+        }
+    }
+    if let Some(ref_expr) = ast::RefExpr::cast(parent) {
+        if ref_expr.is_mut() {
+            return ReferenceAccess::Write;
+        }
+    }
+    ReferenceAccess::Read
+}
+
+fn is_assignment(op: BinOp) -> bool {
+    match op {
+        BinOp::Assignment
+        | BinOp::AddAssign
+        | BinOp::DivAssign
+        | BinOp::MulAssign
+        | BinOp::RemAssign
+        | BinOp::ShrAssign
+        | BinOp::ShlAssign
+        | BinOp::SubAssign
+        | BinOp::BitOrAssign
+        | BinOp::BitAndAssign
+        | BinOp::BitXorAssign => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{
+        mock_analysis::{analysis_and_position, single_file_with_position},
+        Analysis, FilePosition, ReferenceAccess, ReferenceKind, ReferencesConfig, RenameError,
+    };
+
+    #[test]
+    fn finds_field_accesses_literals_and_patterns() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct S { foo<|>: i32 }
+            fn f(s: &mut S) {
+                let x = s.foo;
+                s.foo = 92;
+                let s2 = S { foo: 1 };
+                let foo = 2;
+                let s3 = S { foo };
+                let S { foo: _ } = s2;
+                let S { foo } = s3;
+            }
+            ",
+        );
+        let refs = analysis
+            .find_all_refs(position, &ReferencesConfig::default())
+            .unwrap();
+        let accesses = refs
+            .iter()
+            .map(|it| (it.access, it.is_shorthand))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accesses,
+            vec![
+                (None, false),
+                (Some(ReferenceAccess::Read), false),
+                (Some(ReferenceAccess::Write), false),
+                (Some(ReferenceAccess::Write), false),
+                (Some(ReferenceAccess::Write), true),
+                (Some(ReferenceAccess::Read), false),
+                (Some(ReferenceAccess::Read), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_field_refs_from_usage_in_other_file() {
+        let (analysis, position) = analysis_and_position(
+            r"
+            //- /lib.rs
+            mod a;
+            struct S { foo: i32, bar: i32 }
+            //- /a.rs
+            use super::S;
+            fn f(s: S) -> i32 { s.foo<|> + s.bar }
+            ",
+        );
+        let refs = analysis
+            .find_all_refs(position, &ReferencesConfig::default())
+            .unwrap();
+        assert_eq!(refs.len(), 2);
+    }
+
+    #[test]
+    fn finds_item_refs_across_files() {
+        let (analysis, position) = analysis_and_position(
+            r"
+            //- /lib.rs
+            mod a;
+            use a::Foo;
+            fn f(foo: Foo) -> Foo<|> { foo }
+            //- /a.rs
+            pub struct Foo;
+            fn g() -> Foo { Foo }
+            ",
+        );
+        let count = |config: &ReferencesConfig, kind: ReferenceKind| {
+            let refs = analysis.find_all_refs(position, config).unwrap();
+            refs.iter().filter(|it| it.kind == kind).count()
+        };
+        let all = ReferencesConfig::default();
+        assert_eq!(count(&all, ReferenceKind::Definition), 1);
+        assert_eq!(count(&all, ReferenceKind::Import), 1);
+        assert_eq!(count(&all, ReferenceKind::Reference), 4);
+
+        let usages_only = ReferencesConfig {
+            include_declaration: false,
+            include_imports: false,
+            include_macro_text: false,
+            deadline: None,
+            current_file_only: false,
+        };
+        let refs = analysis.find_all_refs(position, &usages_only).unwrap();
+        assert_eq!(refs.len(), 4);
+        assert!(refs.iter().all(|it| it.kind == ReferenceKind::Reference));
+    }
+
+    #[test]
+    fn stops_searching_files_when_the_deadline_passes() {
+        let (analysis, position) = analysis_and_position(
+            r"
+            //- /lib.rs
+            mod a;
+            use a::Foo;
+            fn f(foo: Foo) -> Foo<|> { foo }
+            //- /a.rs
+            pub struct Foo;
+            fn g() -> Foo { Foo }
+            ",
+        );
+        let config = ReferencesConfig {
+            deadline: Some(Instant::now()),
+            ..ReferencesConfig::default()
+        };
+        let refs = analysis.find_all_refs_partial(position, &config).unwrap();
+        assert!(refs.is_incomplete);
+        assert_eq!(refs.value.len(), 1);
+        assert_eq!(refs.value[0].kind, ReferenceKind::Definition);
+
+        let refs = analysis
+            .find_all_refs_partial(position, &ReferencesConfig::default())
+            .unwrap();
+        assert!(!refs.is_incomplete);
+        assert_eq!(refs.value.len(), 6);
+    }
+
+    #[test]
+    fn searches_only_the_current_file() {
+        let (analysis, position) = analysis_and_position(
+            r"
+            //- /lib.rs
+            mod a;
+            use a::Foo;
+            fn f(foo: Foo) -> Foo<|> { foo }
+            //- /a.rs
+            pub struct Foo;
+            fn g() -> Foo { Foo }
+            ",
+        );
+        let config = ReferencesConfig {
+            current_file_only: true,
+            ..ReferencesConfig::default()
+        };
+        let refs = analysis.find_all_refs(position, &config).unwrap();
+        assert_eq!(refs.len(), 3);
+        assert!(refs.iter().all(|it| it.file_id == position.file_id));
+    }
+
+    #[test]
+    fn finds_textual_matches_in_macro_definitions() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            fn foo<|>() {}
+            macro_rules! m {
+                ($foo:expr) => { foo($foo); $crate::foo() };
+            }
+            fn bar() { foo(); }
+            ",
+        );
+        let refs = analysis.find_all_refs(position, &ReferencesConfig::default()).unwrap();
+        let kinds = refs.iter().map(|it| it.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ReferenceKind::Definition,
+                ReferenceKind::Reference,
+                ReferenceKind::MacroText,
+                ReferenceKind::MacroText,
+            ]
+        );
+
+        let config = ReferencesConfig {
+            include_macro_text: false,
+            ..ReferencesConfig::default()
+        };
+        let refs = analysis.find_all_refs(position, &config).unwrap();
+        assert!(refs.iter().all(|it| it.kind != ReferenceKind::MacroText));
+
+        assert_eq!(
+            apply_rename(&analysis, position, "baz"),
+            r"
+            fn baz() {}
+            macro_rules! m {
+                ($foo:expr) => { foo($foo); $crate::foo() };
+            }
+            fn bar() { baz(); }
+            ",
+        );
+    }
+
+    #[test]
+    fn finds_trait_method_declaration() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            trait T { fn foo<|>(&self); }
+            fn f(t: &dyn T) { t.foo(); }
+            ",
+        );
+        let refs = analysis
+            .find_all_refs(position, &ReferencesConfig::default())
+            .unwrap();
+        let kinds = refs.iter().map(|it| it.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![ReferenceKind::Declaration, ReferenceKind::Reference]
+        );
+    }
+
+    #[test]
+    fn rename_renames_items_and_imports() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            fn foo<|>() {}
+            mod m {
+                use super::foo;
+                fn g() { foo(); }
+            }
+            fn h() { foo(); }
+            ",
+        );
+        assert_eq!(
+            apply_rename(&analysis, position, "bar"),
+            r"
+            fn bar() {}
+            mod m {
+                use super::bar;
+                fn g() { bar(); }
+            }
+            fn h() { bar(); }
+            ",
+        );
+    }
+
+    #[test]
+    fn rename_expands_shorthand_fields() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct S { foo<|>: i32 }
+            fn f(foo: i32) -> i32 {
+                let S { foo } = S { foo };
+                foo
+            }
+            ",
+        );
+        assert_eq!(
+            apply_rename(&analysis, position, "bar"),
+            r"
+            struct S { bar: i32 }
+            fn f(foo: i32) -> i32 {
+                let S { bar: foo } = S { bar: foo };
+                foo
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn rename_expands_shorthand_bindings() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct S { foo: i32 }
+            fn f(s: S) -> S {
+                let S { ref mut foo } = s;
+                *foo<|> += 1;
+                S { foo: *foo }
+            }
+            fn g(s: S) -> S {
+                let foo = s.foo;
+                S { foo }
+            }
+            ",
+        );
+        assert_eq!(
+            apply_rename(&analysis, position, "bar"),
+            r"
+            struct S { foo: i32 }
+            fn f(s: S) -> S {
+                let S { foo: ref mut bar } = s;
+                *bar += 1;
+                S { foo: *bar }
+            }
+            fn g(s: S) -> S {
+                let foo = s.foo;
+                S { foo }
+            }
+            ",
+        );
+
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct S { foo: i32 }
+            fn g(s: S) -> S {
+                let foo<|> = s.foo;
+                S { foo }
+            }
+            ",
+        );
+        assert_eq!(
+            apply_rename(&analysis, position, "bar"),
+            r"
+            struct S { foo: i32 }
+            fn g(s: S) -> S {
+                let bar = s.foo;
+                S { foo: bar }
+            }
+            ",
+        );
+    }
+
+    fn apply_rename(analysis: &Analysis, position: FilePosition, new_name: &str) -> String {
+        let edits = analysis.rename(position, new_name).unwrap().unwrap();
+        let mut builder = ra_text_edit::TextEditBuilder::default();
+        for edit in edits {
+            for atom in edit.edit.as_atoms() {
+                builder.replace(atom.delete, atom.insert.clone());
+            }
+        }
+        let text = analysis.file_text(position.file_id);
+        builder.finish().apply(&text)
+    }
+
+    #[test]
+    fn prepare_rename_returns_identifier_range() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            struct S { foo: i32 }
+            fn f(s: S) -> i32 {
+                let S { ref foo<|> } = s;
+                *foo
+            }
+            ",
+        );
+        let frange = analysis.prepare_rename(position).unwrap().unwrap();
+        let text = analysis.file_text(position.file_id);
+        assert_eq!(&text.as_str()[frange.range], "foo");
+    }
+
+    #[test]
+    fn prepare_rename_rejects_keywords_and_literals() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            fn f() -> i32 {
+                let x = 92<|>;
+                x
+            }
+            ",
+        );
+        let err = analysis.prepare_rename(position).unwrap().unwrap_err();
+        assert_eq!(err, RenameError::NotApplicable);
+
+        let (analysis, position) = single_file_with_position(
+            r"
+            fn f() -> i32 {
+                le<|>t x = 92;
+                x
+            }
+            ",
+        );
+        let err = analysis.prepare_rename(position).unwrap().unwrap_err();
+        assert_eq!(err, RenameError::NotApplicable);
+    }
+
+    fn rename_error(text: &str, new_name: &str) -> RenameError {
+        let (analysis, position) = single_file_with_position(text);
+        analysis.rename(position, new_name).unwrap().unwrap_err()
+    }
+
+    #[test]
+    fn rename_suggests_raw_identifier_for_keywords() {
+        let err = rename_error("fn f() { let x<|> = 1; }", "type");
+        assert_eq!(
+            err,
+            RenameError::InvalidName {
+                name: "type".to_string(),
+                suggestion: Some("r#type".to_string()),
+            }
+        );
+        let err = rename_error("fn f() { let x<|> = 1; }", "92");
+        assert_eq!(
+            err,
+            RenameError::InvalidName {
+                name: "92".to_string(),
+                suggestion: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rename_detects_conflicting_locals() {
+        let err = rename_error(
+            r"
+            fn f() {
+                let x = 1;
+                let y<|> = 2;
+                x + y
+            }
+            ",
+            "x",
+        );
+        match &err {
+            RenameError::Conflict { name, .. } => assert_eq!(name, "x"),
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn rename_detects_conflicting_fields() {
+        let err = rename_error("struct S { foo<|>: i32, bar: i32 }", "bar");
+        match &err {
+            RenameError::Conflict { name, .. } => assert_eq!(name, "bar"),
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+}
@@ -1,11 +1,16 @@
 use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
 use ra_syntax::{
-    TextRange, SyntaxNode,
-    ast::{self, AstNode, NameOwner, ModuleItemOwner},
+    TextRange, TextUnit, SyntaxNode, SmolStr, TreeArc,
+    ast::{self, AstNode, AttrsOwner, NameOwner, ModuleItemOwner},
+    algo::{find_covering_node, find_node_at_offset},
 };
-use ra_db::{Cancelable, SyntaxDatabase};
+use ra_db::{Cancelable, CrateId, FilesDatabase, SyntaxDatabase};
+use hir::{Module, ModuleSource, source_binder};
 
-use crate::{db::RootDatabase, FileId};
+use crate::{
+    db::RootDatabase, FileId, FilePosition, NavigationTarget, ReferencesConfig, references,
+};
 
 #[derive(Debug)]
 pub struct Runnable {
@@ -21,23 +26,131 @@ pub enum RunnableKind {
     Bin,
 }
 
+/// The tests and benchmarks of a module, together with its submodules which
+/// contain tests. Unlike in `runnables`, the names of the tests include the
+/// path of the module, like `foo::tests::bar`.
+#[derive(Debug)]
+pub struct TestModule {
+    pub navigation_target: NavigationTarget,
+    /// The path of the module from the crate root, empty for the root itself.
+    pub path: String,
+    pub tests: Vec<Runnable>,
+    pub children: Vec<TestModule>,
+}
+
+/// A `#[test]` function related to an item, see `related_tests`.
+#[derive(Debug)]
+pub struct RelatedTest {
+    pub navigation_target: NavigationTarget,
+    /// The path of the test from the crate root, like `foo::tests::bar`.
+    pub path: String,
+}
+
 pub(crate) fn runnables(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<Runnable>> {
     let source_file = db.source_file(file_id);
-    let res = source_file
-        .syntax()
-        .descendants()
-        .filter_map(|i| runnable(db, file_id, i))
-        .collect();
+    let mut res = Vec::new();
+    // Nested modules are visited once per enclosing module, so the answers
+    // of `contains_tests` are shared between them.
+    let mut tests_cache = FxHashMap::default();
+    for node in source_file.syntax().descendants() {
+        res.extend(runnable(db, file_id, node, &mut tests_cache)?);
+    }
     Ok(res)
 }
 
-fn runnable(db: &RootDatabase, file_id: FileId, item: &SyntaxNode) -> Option<Runnable> {
+pub(crate) fn crate_tests(
+    db: &RootDatabase,
+    crate_id: CrateId,
+) -> Cancelable<Option<TestModule>> {
+    let root_file = db.crate_graph().crate_root(crate_id);
+    let root = ctry!(source_binder::module_from_file_id(db, root_file)?);
+    let mut visited = FxHashSet::default();
+    test_module(db, root, String::new(), &mut visited).map(Some)
+}
+
+fn test_module(
+    db: &RootDatabase,
+    module: Module,
+    path: String,
+    visited: &mut FxHashSet<Module>,
+) -> Cancelable<TestModule> {
+    visited.insert(module.clone());
+    let (_, source) = module.definition_source(db)?;
+    let fn_defs: Vec<&ast::FnDef> = match &source {
+        ModuleSource::SourceFile(file) => file.items().filter_map(fn_def_item).collect(),
+        ModuleSource::Module(m) => match m.item_list() {
+            Some(item_list) => item_list.items().filter_map(fn_def_item).collect(),
+            None => Vec::new(),
+        },
+    };
+    let tests = fn_defs
+        .into_iter()
+        .filter_map(runnable_fn)
+        .filter_map(|runnable| {
+            let kind = match runnable.kind {
+                RunnableKind::Test { name } => RunnableKind::Test {
+                    name: qualify(&path, &name),
+                },
+                RunnableKind::Bench { name } => RunnableKind::Bench {
+                    name: qualify(&path, &name),
+                },
+                _ => return None,
+            };
+            Some(Runnable {
+                range: runnable.range,
+                kind,
+            })
+        })
+        .collect();
+    let mut children = Vec::new();
+    for child in module.children(db)? {
+        if visited.contains(&child) {
+            continue;
+        }
+        let name = match child.name(db)? {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let child = test_module(db, child, qualify(&path, &name), visited)?;
+        if !child.tests.is_empty() || !child.children.is_empty() {
+            children.push(child);
+        }
+    }
+    Ok(TestModule {
+        navigation_target: NavigationTarget::from_module(db, module)?,
+        path,
+        tests,
+        children,
+    })
+}
+
+fn fn_def_item(item: &ast::ModuleItem) -> Option<&ast::FnDef> {
+    match item.kind() {
+        ast::ModuleItemKind::FnDef(it) => Some(it),
+        _ => None,
+    }
+}
+
+fn qualify(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}::{}", path, name)
+    }
+}
+
+fn runnable(
+    db: &RootDatabase,
+    file_id: FileId,
+    item: &SyntaxNode,
+    tests_cache: &mut FxHashMap<Module, bool>,
+) -> Cancelable<Option<Runnable>> {
     if let Some(fn_def) = ast::FnDef::cast(item) {
-        runnable_fn(fn_def)
+        Ok(runnable_fn(fn_def))
     } else if let Some(m) = ast::Module::cast(item) {
-        runnable_mod(db, file_id, m)
+        runnable_mod(db, file_id, m, tests_cache)
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -62,33 +175,233 @@ fn runnable_fn(fn_def: &ast::FnDef) -> Option<Runnable> {
     })
 }
 
-fn runnable_mod(db: &RootDatabase, file_id: FileId, module: &ast::Module) -> Option<Runnable> {
-    let has_test_function = module
-        .item_list()?
-        .items()
-        .filter_map(|it| match it.kind() {
-            ast::ModuleItemKind::FnDef(it) => Some(it),
-            _ => None,
-        })
-        .any(|f| f.has_atom_attr("test"));
-    if !has_test_function {
-        return None;
-    }
+/// A module is runnable if it is a `#[cfg(test)]` one, or if it contains
+/// tests, directly or in its submodules. Both inline modules and `mod foo;`
+/// declarations are considered.
+fn runnable_mod(
+    db: &RootDatabase,
+    file_id: FileId,
+    module: &ast::Module,
+    tests_cache: &mut FxHashMap<Module, bool>,
+) -> Cancelable<Option<Runnable>> {
     let range = module.syntax().range();
-    let module =
-        hir::source_binder::module_from_child_node(db, file_id, module.syntax()).ok()??;
-
-    // FIXME: thread cancellation instead of `.ok`ing
-    let path = module
-        .path_to_root(db)
-        .ok()?
-        .into_iter()
-        .rev()
-        .filter_map(|it| it.name(db).ok())
-        .filter_map(|it| it)
-        .join("::");
-    Some(Runnable {
+    let hir_module = if module.has_semi() {
+        source_binder::module_from_declaration(db, file_id, module)?
+    } else {
+        source_binder::module_from_child_node(db, file_id, module.syntax())?
+    };
+    let hir_module = ctry!(hir_module);
+    if !is_cfg_test(module) && !contains_tests(db, &hir_module, tests_cache)? {
+        return Ok(None);
+    }
+    let mut segments = Vec::new();
+    for it in hir_module.path_to_root(db)?.into_iter().rev() {
+        segments.extend(it.name(db)?);
+    }
+    let path = segments.iter().join("::");
+    Ok(Some(Runnable {
         range,
         kind: RunnableKind::TestMod { path },
+    }))
+}
+
+/// Whether the module or one of its submodules has a test. The answers for
+/// all the visited modules are recorded in `cache`.
+fn contains_tests(
+    db: &RootDatabase,
+    module: &Module,
+    cache: &mut FxHashMap<Module, bool>,
+) -> Cancelable<bool> {
+    if let Some(&res) = cache.get(module) {
+        return Ok(res);
+    }
+    let res = contains_tests_uncached(db, module, cache)?;
+    cache.insert(module.clone(), res);
+    Ok(res)
+}
+
+fn contains_tests_uncached(
+    db: &RootDatabase,
+    module: &Module,
+    cache: &mut FxHashMap<Module, bool>,
+) -> Cancelable<bool> {
+    let (_, source) = module.definition_source(db)?;
+    let has_test_function = match &source {
+        ModuleSource::SourceFile(file) => file.items().filter_map(fn_def_item).any(is_test),
+        ModuleSource::Module(m) => m
+            .item_list()
+            .map_or(false, |it| it.items().filter_map(fn_def_item).any(is_test)),
+    };
+    if has_test_function {
+        return Ok(true);
+    }
+    for child in module.children(db)? {
+        if contains_tests(db, &child, cache)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn is_test(fn_def: &ast::FnDef) -> bool {
+    fn_def.has_atom_attr("test")
+}
+
+/// Finds the tests of the item at `position`: the `#[test]` functions which
+/// refer to it, followed by the tests in the `#[cfg(test)]` modules of its
+/// crate which are named after it, like `parses_foo_bar` for `FooBar`.
+pub(crate) fn related_tests(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Vec<RelatedTest>> {
+    let file = db.source_file(position.file_id);
+    let name = match item_name_at_offset(file.syntax(), position.offset) {
+        Some(it) => it,
+        None => return Ok(Vec::new()),
+    };
+    let mut res = Vec::new();
+    let mut seen = FxHashSet::default();
+    let config = ReferencesConfig {
+        include_declaration: false,
+        include_imports: false,
+        include_macro_text: false,
+        deadline: None,
+        current_file_only: false,
+    };
+    for reference in references::find_all_refs(db, position, &config)?.value {
+        let file = db.source_file(reference.file_id);
+        let node = find_covering_node(file.syntax(), reference.range);
+        let test = node
+            .ancestors()
+            .filter_map(ast::FnDef::cast)
+            .find(|it| it.has_atom_attr("test"));
+        if let Some(test) = test {
+            if seen.insert((reference.file_id, test.syntax().range())) {
+                res.push(related_test(db, reference.file_id, test)?);
+            }
+        }
+    }
+
+    let module = match source_binder::module_from_position(db, position)? {
+        Some(it) => it,
+        None => return Ok(res),
+    };
+    let mut visited = FxHashSet::default();
+    let mut tests = Vec::new();
+    collect_cfg_test_fns(db, module.crate_root(db)?, false, &mut visited, &mut tests)?;
+    for (file_id, test) in tests {
+        let test_name = match test.name() {
+            Some(it) => it.text().clone(),
+            None => continue,
+        };
+        if is_named_after(&test_name, &name) && seen.insert((file_id, test.syntax().range())) {
+            res.push(related_test(db, file_id, &test)?);
+        }
+    }
+    Ok(res)
+}
+
+fn item_name_at_offset(root: &SyntaxNode, offset: TextUnit) -> Option<SmolStr> {
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(root, offset) {
+        return Some(name_ref.text().clone());
+    }
+    find_node_at_offset::<ast::Name>(root, offset).map(|it| it.text().clone())
+}
+
+fn related_test(
+    db: &RootDatabase,
+    file_id: FileId,
+    test: &ast::FnDef,
+) -> Cancelable<RelatedTest> {
+    let name = test.name().map(|it| it.text().to_string()).unwrap_or_default();
+    let path = match source_binder::module_from_child_node(db, file_id, test.syntax())? {
+        Some(module) => {
+            let mut names = Vec::new();
+            for module in module.path_to_root(db)?.into_iter().rev() {
+                if let Some(name) = module.name(db)? {
+                    names.push(name.to_string());
+                }
+            }
+            qualify(&names.join("::"), &name)
+        }
+        None => name,
+    };
+    Ok(RelatedTest {
+        navigation_target: NavigationTarget::from_fn_def(file_id, test),
+        path,
     })
 }
+
+/// Collects the `#[test]` functions of `module` and its submodules, if they
+/// are inside of a `#[cfg(test)]` module.
+fn collect_cfg_test_fns(
+    db: &RootDatabase,
+    module: Module,
+    in_cfg_test: bool,
+    visited: &mut FxHashSet<Module>,
+    acc: &mut Vec<(FileId, TreeArc<ast::FnDef>)>,
+) -> Cancelable<()> {
+    visited.insert(module.clone());
+    let in_cfg_test = in_cfg_test
+        || match module.declaration_source(db)? {
+            Some((_, decl)) => is_cfg_test(&decl),
+            None => false,
+        };
+    if in_cfg_test {
+        let (file_id, source) = module.definition_source(db)?;
+        let fn_defs: Vec<&ast::FnDef> = match &source {
+            ModuleSource::SourceFile(file) => file.items().filter_map(fn_def_item).collect(),
+            ModuleSource::Module(m) => match m.item_list() {
+                Some(item_list) => item_list.items().filter_map(fn_def_item).collect(),
+                None => Vec::new(),
+            },
+        };
+        acc.extend(
+            fn_defs
+                .into_iter()
+                .filter(|it| it.has_atom_attr("test"))
+                .map(|it| (file_id, it.to_owned())),
+        );
+    }
+    for child in module.children(db)? {
+        if !visited.contains(&child) {
+            collect_cfg_test_fns(db, child, in_cfg_test, visited, acc)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_cfg_test(module: &ast::Module) -> bool {
+    module
+        .attrs()
+        .filter_map(|it| it.as_call())
+        .any(|(name, args)| name == "cfg" && args.syntax().text() == "(test)")
+}
+
+/// Whether the words of `test_name` contain the words of `item_name`. Both
+/// `snake_case` and `CamelCase` names are split into lowercase words.
+fn is_named_after(test_name: &str, item_name: &str) -> bool {
+    let test_words = words(test_name);
+    let item_words = words(item_name);
+    if item_words.is_empty() {
+        return false;
+    }
+    test_words.windows(item_words.len()).any(|it| it == &item_words[..])
+}
+
+fn words(name: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut word = String::new();
+    for c in name.chars() {
+        if (c == '_' || c.is_uppercase()) && !word.is_empty() {
+            res.push(std::mem::replace(&mut word, String::new()));
+        }
+        if c != '_' {
+            word.extend(c.to_lowercase());
+        }
+    }
+    if !word.is_empty() {
+        res.push(word);
+    }
+    res
+}
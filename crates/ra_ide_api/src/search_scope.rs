@@ -0,0 +1,261 @@
+use rustc_hash::FxHashSet;
+use ra_db::{Cancelable, CrateId, FileId, FilesDatabase};
+use ra_syntax::{
+    AstNode, SyntaxNode, TreeArc,
+    ast::{self, NameOwner},
+    SyntaxKind::*,
+};
+use hir::{Def, HirFileId, Module, source_binder};
+
+use crate::db::RootDatabase;
+
+/// The files which may contain references to a definition. It is computed
+/// from the visibility of the definition: a private item, for example, can be
+/// referred to only from its module and the submodules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SearchScope {
+    File(FileId),
+    Files(Vec<FileId>),
+    Crate(CrateId),
+    /// All local source roots.
+    Workspace,
+}
+
+impl SearchScope {
+    pub(crate) fn for_def(db: &RootDatabase, def: &Def) -> Cancelable<SearchScope> {
+        let (file_id, node) = match def {
+            Def::Struct(it) => syntax_of(it.source(db)?),
+            Def::Enum(it) => syntax_of(it.source(db)?),
+            Def::EnumVariant(it) => syntax_of(it.source(db)?),
+            Def::Function(it) => syntax_of(it.source(db)?),
+            Def::Trait(it) => syntax_of(it.source(db)?),
+            Def::Type(it) => syntax_of(it.source(db)?),
+            Def::Static(it) => syntax_of(it.source(db)?),
+            Def::Const(it) => syntax_of(it.source(db)?),
+            Def::Module(_) | Def::Item => return Ok(SearchScope::Workspace),
+        };
+        // Items expanded from macros don't have a place in the original file.
+        let original_file = file_id.original_file(db);
+        if file_id != HirFileId::from(original_file) {
+            return Ok(SearchScope::Workspace);
+        }
+        SearchScope::for_item(db, original_file, &node)
+    }
+
+    pub(crate) fn for_field(
+        db: &RootDatabase,
+        field: &hir::StructField,
+    ) -> Cancelable<SearchScope> {
+        let (file_id, struct_def) = field.parent_struct().source(db)?;
+        let original_file = file_id.original_file(db);
+        if file_id != HirFileId::from(original_file) {
+            return Ok(SearchScope::Workspace);
+        }
+        let name = field.name().to_string();
+        let field_def = struct_def
+            .syntax()
+            .descendants()
+            .filter_map(ast::NamedFieldDef::cast)
+            .find(|it| it.name().map_or(false, |it| it.text().as_str() == name));
+        let field_def = match field_def {
+            Some(it) => it,
+            None => return Ok(SearchScope::Workspace),
+        };
+        SearchScope::for_visibility(db, original_file, field_def.syntax())
+    }
+
+    fn for_item(db: &RootDatabase, file_id: FileId, node: &SyntaxNode) -> Cancelable<SearchScope> {
+        // Variants are as visible as their enum, the items of a trait as the
+        // trait, and the items of trait impls are as visible as the trait,
+        // which is not known here.
+        if node.kind() == ENUM_VARIANT {
+            if let Some(enum_def) = node.ancestors().find(|it| it.kind() == ENUM_DEF) {
+                return SearchScope::for_visibility(db, file_id, enum_def);
+            }
+        }
+        let container = node
+            .parent()
+            .filter(|it| it.kind() == ITEM_LIST)
+            .and_then(|it| it.parent());
+        if let Some(container) = container {
+            if container.kind() == TRAIT_DEF {
+                return SearchScope::for_visibility(db, file_id, container);
+            }
+            if let Some(impl_block) = ast::ImplBlock::cast(container) {
+                if impl_block.target_trait().is_some() {
+                    return Ok(SearchScope::Workspace);
+                }
+            }
+        }
+        SearchScope::for_visibility(db, file_id, node)
+    }
+
+    fn for_visibility(
+        db: &RootDatabase,
+        file_id: FileId,
+        owner: &SyntaxNode,
+    ) -> Cancelable<SearchScope> {
+        let module = match source_binder::module_from_child_node(db, file_id, owner)? {
+            Some(it) => it,
+            None => return Ok(SearchScope::Workspace),
+        };
+        let visibility = owner
+            .children()
+            .find(|it| it.kind() == VISIBILITY)
+            .map(|it| it.text().to_string().replace(char::is_whitespace, ""));
+        let res = match visibility.as_ref().map(String::as_str) {
+            None | Some("pub(self)") => SearchScope::for_module(db, module)?,
+            Some("pub(super)") => match module.parent(db)? {
+                Some(parent) => SearchScope::for_module(db, parent)?,
+                None => SearchScope::for_module(db, module)?,
+            },
+            Some("pub") => SearchScope::Workspace,
+            // `pub(crate)`, `crate` and `pub(in path)`.
+            Some(_) => match module.krate(db)? {
+                Some(krate) => SearchScope::Crate(krate.crate_id()),
+                None => SearchScope::Workspace,
+            },
+        };
+        Ok(res)
+    }
+
+    /// The files of the module and of its submodules.
+    fn for_module(db: &RootDatabase, module: Module) -> Cancelable<SearchScope> {
+        let mut files = Vec::new();
+        module_files(db, module, &mut FxHashSet::default(), &mut files)?;
+        let res = if files.len() == 1 {
+            SearchScope::File(files[0])
+        } else {
+            SearchScope::Files(files)
+        };
+        Ok(res)
+    }
+
+    pub(crate) fn files(&self, db: &RootDatabase) -> Cancelable<Vec<FileId>> {
+        let res = match self {
+            SearchScope::File(file_id) => vec![*file_id],
+            SearchScope::Files(files) => files.clone(),
+            SearchScope::Crate(crate_id) => {
+                let root_file = db.crate_graph().crate_root(*crate_id);
+                let mut files = Vec::new();
+                if let Some(root) = source_binder::module_from_file_id(db, root_file)? {
+                    module_files(db, root, &mut FxHashSet::default(), &mut files)?;
+                }
+                files
+            }
+            SearchScope::Workspace => {
+                let mut files = Vec::new();
+                for &root in db.local_roots().iter() {
+                    files.extend(db.source_root(root).files.values().cloned());
+                }
+                files
+            }
+        };
+        Ok(res)
+    }
+}
+
+fn syntax_of<N: AstNode>(source: (HirFileId, TreeArc<N>)) -> (HirFileId, TreeArc<SyntaxNode>) {
+    let (file_id, node) = source;
+    (file_id, node.syntax().to_owned())
+}
+
+fn module_files(
+    db: &RootDatabase,
+    module: Module,
+    visited: &mut FxHashSet<Module>,
+    acc: &mut Vec<FileId>,
+) -> Cancelable<()> {
+    if !visited.insert(module.clone()) {
+        return Ok(());
+    }
+    let (file_id, _) = module.definition_source(db)?;
+    if !acc.contains(&file_id) {
+        acc.push(file_id);
+    }
+    for child in module.children(db)? {
+        module_files(db, child, visited, acc)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_db::{FilePosition, SyntaxDatabase};
+    use hir::source_binder::{self, NameDefinition};
+    use ra_syntax::{AstNode, ast, algo::find_node_at_offset};
+
+    use crate::{
+        FileId,
+        mock_analysis::analysis_and_position,
+        search_scope::SearchScope,
+    };
+
+    fn scope_at(fixture: &str) -> SearchScope {
+        let (analysis, position) = analysis_and_position(fixture);
+        let db = &*analysis.db;
+        let FilePosition { file_id, offset } = position;
+        let file = db.source_file(file_id);
+        let name = find_node_at_offset::<ast::Name>(file.syntax(), offset).unwrap();
+        match source_binder::def_for_node(db, file_id, name.syntax()).unwrap() {
+            Some(NameDefinition::Item(def)) => SearchScope::for_def(db, &def).unwrap(),
+            Some(NameDefinition::Field(field)) => SearchScope::for_field(db, &field).unwrap(),
+            def => panic!("unexpected definition: {:?}", def),
+        }
+    }
+
+    #[test]
+    fn private_items_are_searched_in_their_module() {
+        let scope = scope_at(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar;
+            //- /foo.rs
+            struct Foo<|>;
+            mod tests {}
+            //- /bar.rs
+            ",
+        );
+        assert_eq!(scope, SearchScope::File(FileId(2)));
+
+        let scope = scope_at(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar;
+            //- /foo.rs
+            struct Foo { pub(super) spam<|>: u32 }
+            mod tests;
+            //- /foo/tests.rs
+            //- /bar.rs
+            ",
+        );
+        let files = vec![FileId(1), FileId(2), FileId(3), FileId(4)];
+        assert_eq!(scope, SearchScope::Files(files));
+    }
+
+    #[test]
+    fn public_items_are_searched_in_the_crate_or_the_workspace() {
+        let scope = scope_at(
+            "
+            //- /lib.rs
+            mod foo;
+            //- /foo.rs
+            pub(crate) fn foo<|>() {}
+            ",
+        );
+        match scope {
+            SearchScope::Crate(_) => (),
+            _ => panic!("unexpected scope: {:?}", scope),
+        }
+
+        let scope = scope_at(
+            "
+            //- /lib.rs
+            pub enum E { V<|> }
+            ",
+        );
+        assert_eq!(scope, SearchScope::Workspace);
+    }
+}
@@ -16,9 +16,9 @@
 //!
 //! `fst` does not support cheap updating of the index, but it supports unioning
 //! of state machines. So, to account for changing source code, we build an fst
-//! for each library (which is assumed to never change) and an fst for each rust
-//! file in the current workspace, and run a query aginst the union of all
-//! thouse fsts.
+//! for each library (which is rebuilt from scratch if the library changes, for
+//! example, after `cargo update`) and an fst for each rust file in the current
+//! workspace, and run a query aginst the union of all thouse fsts.
 use std::{
     cmp::Ordering,
     hash::{Hash, Hasher},
@@ -30,15 +30,17 @@ use ra_syntax::{
     SyntaxNode, SourceFile, SmolStr, TreeArc, AstNode,
     algo::{visit::{visitor, Visitor}, find_covering_node},
     SyntaxKind::{self, *},
-    ast::{self, NameOwner},
+    ast::{self, NameOwner, AttrsOwner},
 };
-use ra_db::{SourceRootId, FilesDatabase, LocalSyntaxPtr};
+use ra_db::{BaseDatabase, SourceRootId, FilesDatabase, LocalSyntaxPtr};
 use salsa::ParallelDatabase;
 use rayon::prelude::*;
+use relative_path::{RelativePath, RelativePathBuf};
 
 use crate::{
-    Cancelable, FileId, Query,
+    Cancelable, FileId, Query, Partial,
     db::RootDatabase,
+    budget::{deadline_passed, complete_in_background},
 };
 
 salsa::query_group! {
@@ -56,23 +58,31 @@ salsa::query_group! {
 fn file_symbols(db: &impl SymbolsDatabase, file_id: FileId) -> Cancelable<Arc<SymbolIndex>> {
     db.check_canceled()?;
     let source_file = db.source_file(file_id);
-    let mut symbols = source_file
-        .syntax()
-        .descendants()
-        .filter_map(to_symbol)
-        .map(move |(name, ptr)| FileSymbol { name, ptr, file_id })
-        .collect::<Vec<_>>();
+    let file_depth = file_depth(&db.file_relative_path(file_id));
+    let mut symbols = file_symbols_of(&source_file, file_id, file_depth);
 
     for (name, text_range) in hir::source_binder::macro_symbols(db, file_id)? {
         let node = find_covering_node(source_file.syntax(), text_range);
         let ptr = LocalSyntaxPtr::new(node);
-        symbols.push(FileSymbol { file_id, name, ptr })
+        symbols.push(FileSymbol {
+            file_id,
+            name,
+            ptr,
+            deprecated: false,
+            depth: file_depth + module_depth(node),
+        })
     }
 
     Ok(Arc::new(SymbolIndex::new(symbols)))
 }
 
-pub(crate) fn world_symbols(db: &RootDatabase, query: Query) -> Cancelable<Vec<FileSymbol>> {
+/// Searches the symbols of the workspace, and of the libraries if requested.
+/// If the deadline of the query passes, the remaining files are skipped, and
+/// are indexed in the background instead.
+pub(crate) fn world_symbols(
+    db: &RootDatabase,
+    query: Query,
+) -> Cancelable<Partial<Vec<FileSymbol>>> {
     /// Need to wrap Snapshot to provide `Clone` impl for `map_with`
     struct Snap(salsa::Snapshot<RootDatabase>);
     impl Clone for Snap {
@@ -81,27 +91,54 @@ pub(crate) fn world_symbols(db: &RootDatabase, query: Query) -> Cancelable<Vec<F
         }
     }
 
-    let buf: Vec<Arc<SymbolIndex>> = if query.libs {
-        let snap = Snap(db.snapshot());
-        db.library_roots()
-            .par_iter()
-            .map_with(snap, |db, &lib_id| db.0.library_symbols(lib_id))
-            .collect()
-    } else {
-        let mut files = Vec::new();
-        for &root in db.local_roots().iter() {
-            let sr = db.source_root(root);
-            files.extend(sr.files.values().map(|&it| it))
-        }
+    let mut files = Vec::new();
+    for &root in db.local_roots().iter() {
+        let sr = db.source_root(root);
+        files.extend(sr.files.values().map(|&it| it))
+    }
 
+    let snap = Snap(db.snapshot());
+    let deadline = query.deadline;
+    // `None` for the files skipped because of the deadline.
+    let indices: Vec<Option<Cancelable<Arc<SymbolIndex>>>> = files
+        .par_iter()
+        .map_with(snap, |db, &file_id| {
+            if deadline_passed(deadline) {
+                return None;
+            }
+            Some(db.0.file_symbols(file_id))
+        })
+        .collect();
+    let mut buf = Vec::new();
+    let mut skipped = Vec::new();
+    for (&file_id, index) in files.iter().zip(indices) {
+        match index {
+            Some(Ok(index)) => buf.push(index),
+            Some(Err(_)) => (),
+            None => skipped.push(file_id),
+        }
+    }
+    let is_incomplete = !skipped.is_empty();
+    if is_incomplete {
+        complete_in_background(db, move |db| {
+            for file_id in skipped {
+                db.check_canceled()?;
+                db.file_symbols(file_id)?;
+            }
+            Ok(())
+        });
+    }
+    let n_local = buf.len();
+    if query.libraries {
         let snap = Snap(db.snapshot());
-        files
+        let libs: Vec<Arc<SymbolIndex>> = db
+            .library_roots()
             .par_iter()
-            .map_with(snap, |db, &file_id| db.0.file_symbols(file_id))
-            .filter_map(|it| it.ok())
-            .collect()
-    };
-    Ok(query.search(&buf))
+            .map_with(snap, |db, &lib_id| db.0.library_symbols(lib_id))
+            .collect();
+        buf.extend(libs);
+    }
+    Ok(Partial::new(query.search(&buf, n_local), is_incomplete))
 }
 
 #[derive(Default, Debug)]
@@ -141,34 +178,28 @@ impl SymbolIndex {
     }
 
     pub(crate) fn for_files(
-        files: impl ParallelIterator<Item = (FileId, TreeArc<SourceFile>)>,
+        files: impl ParallelIterator<Item = (FileId, RelativePathBuf, TreeArc<SourceFile>)>,
     ) -> SymbolIndex {
         let symbols = files
-            .flat_map(|(file_id, file)| {
-                file.syntax()
-                    .descendants()
-                    .filter_map(to_symbol)
-                    .map(move |(name, ptr)| FileSymbol { name, ptr, file_id })
-                    .collect::<Vec<_>>()
-            })
+            .flat_map(|(file_id, path, file)| file_symbols_of(&file, file_id, file_depth(&path)))
             .collect::<Vec<_>>();
         SymbolIndex::new(symbols)
     }
 }
 
 impl Query {
-    pub(crate) fn search(self, indices: &[Arc<SymbolIndex>]) -> Vec<FileSymbol> {
+    /// Searches `indices`, the first `n_local` of which are the indices of the
+    /// workspace files, and the rest are the indices of the libraries. All the
+    /// hits are ranked, see `SymbolRank`, before they are cut to the limit.
+    pub(crate) fn search(self, indices: &[Arc<SymbolIndex>], n_local: usize) -> Vec<FileSymbol> {
         let mut op = fst::map::OpBuilder::new();
         for file_symbols in indices.iter() {
             let automaton = fst::automaton::Subsequence::new(&self.lowercased);
             op = op.add(file_symbols.map.search(automaton))
         }
         let mut stream = op.union();
-        let mut res = Vec::new();
+        let mut hits = Vec::new();
         while let Some((_, indexed_values)) = stream.next() {
-            if res.len() >= self.limit {
-                break;
-            }
             for indexed_value in indexed_values {
                 let file_symbols = &indices[indexed_value.index];
                 let idx = indexed_value.value as usize;
@@ -180,13 +211,44 @@ impl Query {
                 if self.exact && symbol.name != self.query {
                     continue;
                 }
-                res.push(symbol.clone());
+                let is_library = indexed_value.index >= n_local;
+                hits.push((self.rank(symbol, is_library), symbol));
             }
         }
-        res
+        // The sort is stable, so equally ranked hits stay in the order of
+        // the fst, which is alphabetical.
+        hits.sort_by_key(|&(rank, _)| rank);
+        hits.into_iter()
+            .take(self.limit)
+            .map(|(_, symbol)| symbol.clone())
+            .collect()
+    }
+
+    fn rank(&self, symbol: &FileSymbol, is_library: bool) -> SymbolRank {
+        let wants_type = self.query.starts_with(|c: char| c.is_uppercase());
+        SymbolRank {
+            inexact_case: symbol.name != self.query,
+            is_library,
+            not_a_type: wants_type && !is_type(symbol.ptr.kind()),
+            depth: symbol.depth,
+        }
     }
 }
 
+/// How well a symbol matches a query, smaller is better. The fields are
+/// compared in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SymbolRank {
+    /// The name is not the query itself, with the same case.
+    inexact_case: bool,
+    /// Workspace items go before library items.
+    is_library: bool,
+    /// A capitalized query, like `Hash`, is most likely looking for a type.
+    not_a_type: bool,
+    /// Shorter paths go before deeper ones.
+    depth: u32,
+}
+
 fn is_type(kind: SyntaxKind) -> bool {
     match kind {
         STRUCT_DEF | ENUM_DEF | TRAIT_DEF | TYPE_DEF => true,
@@ -201,13 +263,48 @@ pub(crate) struct FileSymbol {
     pub(crate) file_id: FileId,
     pub(crate) name: SmolStr,
     pub(crate) ptr: LocalSyntaxPtr,
+    pub(crate) deprecated: bool,
+    /// The number of modules the symbol is nested in, counting the modules
+    /// of the file path as well.
+    pub(crate) depth: u32,
+}
+
+fn file_symbols_of(file: &SourceFile, file_id: FileId, file_depth: u32) -> Vec<FileSymbol> {
+    file.syntax()
+        .descendants()
+        .filter_map(|node| {
+            let (name, ptr, deprecated) = to_symbol(node)?;
+            Some(FileSymbol {
+                name,
+                ptr,
+                file_id,
+                deprecated,
+                depth: file_depth + module_depth(node),
+            })
+        })
+        .collect()
+}
+
+/// How deep the module of a file is, going by its path: `foo/bar.rs` and
+/// `foo/bar/mod.rs` are both two modules deep, `lib.rs` is the root.
+fn file_depth(path: &RelativePath) -> u32 {
+    let dirs = path.components().count().saturating_sub(1) as u32;
+    match path.file_name() {
+        Some("lib.rs") | Some("main.rs") | Some("mod.rs") => dirs,
+        _ => dirs + 1,
+    }
+}
+
+/// The number of inline modules around `node`.
+fn module_depth(node: &SyntaxNode) -> u32 {
+    node.ancestors().skip(1).filter(|it| it.kind() == MODULE).count() as u32
 }
 
-fn to_symbol(node: &SyntaxNode) -> Option<(SmolStr, LocalSyntaxPtr)> {
-    fn decl<N: NameOwner>(node: &N) -> Option<(SmolStr, LocalSyntaxPtr)> {
+fn to_symbol(node: &SyntaxNode) -> Option<(SmolStr, LocalSyntaxPtr, bool)> {
+    fn decl<N: NameOwner + AttrsOwner>(node: &N) -> Option<(SmolStr, LocalSyntaxPtr, bool)> {
         let name = node.name()?.text().clone();
         let ptr = LocalSyntaxPtr::new(node.syntax());
-        Some((name, ptr))
+        Some((name, ptr, node.has_attr("deprecated")))
     }
     visitor()
         .visit(decl::<ast::FnDef>)
@@ -1,17 +1,69 @@
-use ra_syntax::{ast, AstNode,};
+use ra_syntax::{
+    AstNode, Direction, SourceFile, SyntaxNode, TextRange, TextUnit,
+    ast::{self, AstToken},
+    SyntaxKind::{MUT_KW, SOURCE_FILE, UNSAFE_KW, WHITESPACE},
+};
 use ra_db::SyntaxDatabase;
+use rustc_hash::FxHashMap;
+use hir::{
+    Def, Mutability, Ty, TypeRef,
+    db::HirDatabase,
+    source_binder::{self, NameDefinition},
+};
 
 use crate::{
-    FileId, Cancelable, HighlightedRange,
+    FileId, Cancelable, HighlightedRange, ReferenceAccess,
     db::RootDatabase,
+    inlay_hints::method_call_signature,
+    format_string::{format_string, format_specifiers},
+    references::expr_access,
 };
 
+/// An operation which is allowed only in `unsafe` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafeKind {
+    /// The `unsafe` keyword of an `unsafe { ... }` block.
+    UnsafeBlock,
+    /// A call of an `unsafe fn`. The range is the callee, or the name of the
+    /// method.
+    UnsafeFnCall,
+    /// A dereference of a raw pointer, like `*ptr`.
+    RawPtrDeref,
+    /// A path to a `static mut`.
+    MutStaticAccess,
+}
+
+#[derive(Debug)]
+pub struct UnsafeRange {
+    pub range: TextRange,
+    pub kind: UnsafeKind,
+}
+
 pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<HighlightedRange>> {
     let source_file = db.source_file(file_id);
     let mut res = ra_ide_api_light::highlight(source_file.syntax());
+    let mutable_usages = highlight_name_refs(db, file_id, &source_file, &mut res)?;
+    res.extend(mutable_usages);
+    // The macro calls among the items of the file are expanded by hir, the
+    // other ones, like the ones in function bodies, are expanded here.
+    for macro_file in source_binder::macro_files(db, file_id)? {
+        let expansion = db.hir_source_file(macro_file);
+        let mapped_ranges = ra_ide_api_light::highlight(expansion.syntax())
+            .into_iter()
+            .filter_map(|r| {
+                let mapped_range = macro_file.exact_original_range(db, r.range)?;
+                let res = HighlightedRange {
+                    range: mapped_range.range,
+                    tag: r.tag,
+                };
+                Some(res)
+            });
+        res.extend(mapped_ranges);
+    }
     for macro_call in source_file
         .syntax()
         .descendants()
+        .filter(|it| it.parent().map(|it| it.kind()) != Some(SOURCE_FILE))
         .filter_map(ast::MacroCall::cast)
     {
         if let Some((off, exp)) = hir::MacroDef::ast_expand(macro_call) {
@@ -28,14 +80,526 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<Hi
             res.extend(mapped_ranges);
         }
     }
+    res.extend(highlight_format_strings(source_file.syntax()));
+    res.extend(highlight_doc_tests(source_file.syntax()));
+    res.extend(unsafe_ranges(db, file_id)?.into_iter().map(|it| HighlightedRange {
+        range: it.range,
+        tag: "unsafe",
+    }));
+    res.extend(highlight_mutation(db, file_id, source_file.syntax())?);
+    Ok(res)
+}
+
+/// Replaces the plain "text" tag of the names which resolve to a definition
+/// with the kind of the definition, like "type" or "variable". Returns the
+/// usages of mutable bindings, which get the "mutable" tag too.
+fn highlight_name_refs(
+    db: &RootDatabase,
+    file_id: FileId,
+    file: &SourceFile,
+    res: &mut [HighlightedRange],
+) -> Cancelable<Vec<HighlightedRange>> {
+    let mut tags = FxHashMap::default();
+    let mut mutable_usages = Vec::new();
+    for name_ref in file.syntax().descendants().filter_map(ast::NameRef::cast) {
+        let def = source_binder::def_for_node(db, file_id, name_ref.syntax())?;
+        if let Some(NameDefinition::Local(_, entry)) = &def {
+            let pat = entry.ptr().resolve(file);
+            if ast::BindPat::cast(&pat).map_or(false, |it| it.is_mut()) {
+                mutable_usages.push(HighlightedRange {
+                    range: name_ref.syntax().range(),
+                    tag: "mutable",
+                });
+            }
+        }
+        if let Some(tag) = def.and_then(|it| definition_tag(&it)) {
+            tags.insert(name_ref.syntax().range(), tag);
+        }
+    }
+    for range in res.iter_mut().filter(|it| it.tag == "text") {
+        if let Some(&tag) = tags.get(&range.range) {
+            range.tag = tag;
+        }
+    }
+    Ok(mutable_usages)
+}
+
+fn definition_tag(def: &NameDefinition) -> Option<&'static str> {
+    let tag = match def {
+        NameDefinition::Local(..) => "variable",
+        NameDefinition::Field(..) => "field",
+        NameDefinition::Method(..) => "function",
+        NameDefinition::Lifetime(..) => "parameter",
+        NameDefinition::Item(def) => match def {
+            Def::Module(..) => "module",
+            Def::Function(..) => "function",
+            Def::Struct(..) | Def::Enum(..) | Def::Trait(..) | Def::Type(..) => "type",
+            Def::EnumVariant(..) | Def::Const(..) | Def::Static(..) => "constant",
+            Def::Item => return None,
+        },
+    };
+    Some(tag)
+}
+
+/// Highlights the interpolations in the format strings of `println!` and
+/// friends, like `{}` and `{name:?}`.
+fn highlight_format_strings(root: &SyntaxNode) -> Vec<HighlightedRange> {
+    root.descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter_map(format_string)
+        .flat_map(format_specifiers)
+        .map(|it| HighlightedRange {
+            range: it.range,
+            tag: "format_specifier",
+        })
+        .collect()
+}
+
+/// Highlights the names of mutable bindings, like `x` in `let mut x = 92;`,
+/// with the "mutable" tag, and the places which are mutated with the
+/// "mutation" tag: the left sides of assignments, the operands of `&mut` and
+/// the receivers of methods which take `&mut self`.
+fn highlight_mutation(
+    db: &RootDatabase,
+    file_id: FileId,
+    root: &SyntaxNode,
+) -> Cancelable<Vec<HighlightedRange>> {
+    let mut res = Vec::new();
+    for node in root.descendants() {
+        if let Some(bind_pat) = ast::BindPat::cast(node) {
+            if let (true, Some(name)) = (bind_pat.is_mut(), bind_pat.name()) {
+                res.push(HighlightedRange {
+                    range: name.syntax().range(),
+                    tag: "mutable",
+                });
+            }
+        } else if ast::Expr::cast(node).is_some() && is_mutated(db, file_id, node)? {
+            res.push(HighlightedRange {
+                range: node.range(),
+                tag: "mutation",
+            });
+        }
+    }
     Ok(res)
 }
 
+fn is_mutated(db: &RootDatabase, file_id: FileId, expr: &SyntaxNode) -> Cancelable<bool> {
+    if expr_access(expr) == ReferenceAccess::Write {
+        return Ok(true);
+    }
+    // Arguments are inside the `ArgList`, so an expression directly in the
+    // method call is always the receiver.
+    let call = match expr.parent().and_then(ast::MethodCallExpr::cast) {
+        Some(it) => it,
+        None => return Ok(false),
+    };
+    let signature = match method_call_signature(db, file_id, call)? {
+        Some(it) => it,
+        None => return Ok(false),
+    };
+    let res = signature.has_self_param()
+        && match signature.params().first() {
+            Some(TypeRef::Reference(_, Mutability::Mut)) => true,
+            _ => false,
+        };
+    Ok(res)
+}
+
+pub(crate) fn unsafe_ranges(db: &RootDatabase, file_id: FileId) -> Cancelable<Vec<UnsafeRange>> {
+    let source_file = db.source_file(file_id);
+    let mut res = Vec::new();
+    for node in source_file.syntax().descendants() {
+        if let Some(range) = unsafe_range(db, file_id, node)? {
+            res.push(range);
+        }
+    }
+    Ok(res)
+}
+
+fn unsafe_range(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Cancelable<Option<UnsafeRange>> {
+    let (range, kind) = if ast::BlockExpr::cast(node).is_some() {
+        let keyword = ctry!(node.children().find(|it| it.kind() == UNSAFE_KW));
+        (keyword.range(), UnsafeKind::UnsafeBlock)
+    } else if let Some(call) = ast::MethodCallExpr::cast(node) {
+        let name_ref = ctry!(call.name_ref());
+        let function = ctry!(source_binder::function_from_child_node(db, file_id, node)?);
+        let infer_result = function.infer(db)?;
+        let syntax_mapping = function.body_syntax_mapping(db)?;
+        let expr = ctry!(ast::Expr::cast(node).and_then(|it| syntax_mapping.node_expr(it)));
+        let def_id = ctry!(infer_result.method_resolution(expr));
+        match def_id.resolve(db)? {
+            Def::Function(it) if it.signature(db).is_unsafe() => (),
+            _ => return Ok(None),
+        }
+        (name_ref.syntax().range(), UnsafeKind::UnsafeFnCall)
+    } else if let Some(prefix_expr) = ast::PrefixExpr::cast(node) {
+        if prefix_expr.op() != Some(ast::PrefixOp::Deref) {
+            return Ok(None);
+        }
+        let operand = ctry!(prefix_expr.expr());
+        let function = ctry!(source_binder::function_from_child_node(db, file_id, node)?);
+        let infer_result = function.infer(db)?;
+        let syntax_mapping = function.body_syntax_mapping(db)?;
+        let expr = ctry!(syntax_mapping.node_expr(operand));
+        match &infer_result[expr] {
+            Ty::RawPtr(..) => (),
+            _ => return Ok(None),
+        }
+        (node.range(), UnsafeKind::RawPtrDeref)
+    } else if let Some(path_expr) = ast::PathExpr::cast(node) {
+        let name_ref = ctry!(path_expr
+            .path()
+            .and_then(|it| it.segment())
+            .and_then(|it| it.name_ref()));
+        // Locals shadow the items, so the path is resolved like a name.
+        let def = match source_binder::def_for_node(db, file_id, name_ref.syntax())? {
+            Some(NameDefinition::Item(it)) => it,
+            _ => return Ok(None),
+        };
+        let kind = match def {
+            Def::Function(it) if it.signature(db).is_unsafe() => {
+                // Only calling the function is unsafe, not taking a pointer
+                // to it.
+                let is_callee = node
+                    .parent()
+                    .and_then(ast::CallExpr::cast)
+                    .and_then(|it| it.expr())
+                    .map_or(false, |it| it.syntax().range() == node.range());
+                if !is_callee {
+                    return Ok(None);
+                }
+                UnsafeKind::UnsafeFnCall
+            }
+            Def::Static(it) => {
+                let (_, static_def) = it.source(db)?;
+                if !static_def.syntax().children().any(|it| it.kind() == MUT_KW) {
+                    return Ok(None);
+                }
+                UnsafeKind::MutStaticAccess
+            }
+            _ => return Ok(None),
+        };
+        (node.range(), kind)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(UnsafeRange { range, kind }))
+}
+
+/// Highlights Rust code in the fenced blocks of doc comments. Like rustdoc
+/// does, a block without its own `fn main` is parsed as a body of one.
+fn highlight_doc_tests(root: &SyntaxNode) -> Vec<HighlightedRange> {
+    let mut res = Vec::new();
+    for node in root.descendants() {
+        if !is_doc_comment(node) {
+            continue;
+        }
+        // Every block of consecutive doc comments is processed once, starting
+        // with its first comment.
+        let is_continuation = node
+            .siblings(Direction::Prev)
+            .skip(1)
+            .find(|it| it.kind() != WHITESPACE)
+            .map_or(false, is_doc_comment);
+        if is_continuation {
+            continue;
+        }
+        let lines = node
+            .siblings(Direction::Next)
+            .filter(|it| it.kind() != WHITESPACE)
+            .take_while(|it| is_doc_comment(it))
+            .filter_map(doc_comment_line)
+            .collect::<Vec<_>>();
+        res.extend(highlight_code_blocks(&lines));
+    }
+    res
+}
+
+fn is_doc_comment(node: &SyntaxNode) -> bool {
+    ast::Comment::cast(node).map_or(false, |it| it.is_doc_comment())
+}
+
+/// The text of the doc comment without the `///` and the space after it,
+/// together with its offset in the file.
+fn doc_comment_line(node: &SyntaxNode) -> Option<(TextUnit, &str)> {
+    let comment = ast::Comment::cast(node)?;
+    let text = &comment.text()[comment.prefix().len()..];
+    let text = if text.starts_with(' ') { &text[1..] } else { text };
+    let offset = node.range().end() - TextUnit::of_str(text);
+    Some((offset, text))
+}
+
+fn highlight_code_blocks(lines: &[(TextUnit, &str)]) -> Vec<HighlightedRange> {
+    let mut res = Vec::new();
+    let mut in_code_block = false;
+    let mut code = String::new();
+    // For each line of `code`, its range there and its offset in the file.
+    let mut line_offsets = Vec::new();
+    for &(offset, line) in lines {
+        if line.trim_start().starts_with("```") {
+            if in_code_block {
+                res.extend(highlight_code(&code, &line_offsets));
+                code.clear();
+                line_offsets.clear();
+                in_code_block = false;
+            } else {
+                in_code_block = is_rust_fence(&line.trim_start()[3..]);
+            }
+            continue;
+        }
+        if !in_code_block {
+            continue;
+        }
+        // Lines starting with `# ` are hidden from the docs, but are still a
+        // part of the test.
+        let (offset, line) = match line.trim_start() {
+            "#" => (offset + TextUnit::of_str(line), ""),
+            it if it.starts_with("# ") => {
+                let hidden = TextUnit::of_str(line) - TextUnit::of_str(it) + TextUnit::of_char('#');
+                (offset + hidden, &line[hidden.to_usize()..])
+            }
+            _ => (offset, line),
+        };
+        let start = TextUnit::of_str(&code);
+        code.push_str(line);
+        code.push('\n');
+        line_offsets.push((TextRange::offset_len(start, TextUnit::of_str(line)), offset));
+    }
+    res
+}
+
+/// Whether the info string of the fence, like `rust,no_run`, says that the
+/// block contains Rust code. An empty one does too.
+fn is_rust_fence(info: &str) -> bool {
+    const RUST_TAGS: &[&str] = &[
+        "",
+        "rust",
+        "ignore",
+        "should_panic",
+        "no_run",
+        "compile_fail",
+        "allow_fail",
+        "test_harness",
+        "edition2015",
+        "edition2018",
+    ];
+    info.split(|c| c == ',' || c == ' ' || c == '\t')
+        .all(|tag| RUST_TAGS.contains(&tag.trim()))
+}
+
+fn highlight_code(code: &str, line_offsets: &[(TextRange, TextUnit)]) -> Vec<HighlightedRange> {
+    let prefix = if code.contains("fn main") { "" } else { "fn main() {\n" };
+    let text = format!("{}{}{}", prefix, code, if prefix.is_empty() { "" } else { "}" });
+    let prefix_len = TextUnit::of_str(prefix);
+    let file = SourceFile::parse(&text);
+    ra_ide_api_light::highlight(file.syntax())
+        .into_iter()
+        .filter_map(|r| {
+            if r.range.start() < prefix_len {
+                return None;
+            }
+            let range = TextRange::offset_len(r.range.start() - prefix_len, r.range.len());
+            // Ranges spanning several lines can't be mapped back as a whole.
+            let &(line_range, offset) = line_offsets
+                .iter()
+                .find(|(line_range, _)| range.is_subrange(line_range))?;
+            let start = offset + (range.start() - line_range.start());
+            Some(HighlightedRange {
+                range: TextRange::offset_len(start, range.len()),
+                tag: r.tag,
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::mock_analysis::single_file;
     use test_utils::assert_eq_dbg;
 
+    #[test]
+    fn finds_unsafe_operations() {
+        let (analysis, file_id) = single_file(
+            r"
+            static mut COUNTER: u32 = 0;
+            static LIMIT: u32 = 10;
+            unsafe fn frobnicate(ptr: *const u32) -> u32 { *ptr }
+            struct S;
+            impl S { unsafe fn get(&self) -> u32 { 0 } fn safe(&self) {} }
+            fn foo(s: S) {
+                let x = 92u32;
+                let ptr = &x as *const u32;
+                let f = frobnicate;
+                unsafe {
+                    COUNTER += LIMIT;
+                    frobnicate(ptr);
+                    s.get();
+                    s.safe();
+                    *ptr;
+                    *&x;
+                }
+            }
+            ",
+        );
+        let text = analysis.file_text(file_id);
+        let actual = analysis
+            .unsafe_ranges(file_id)
+            .unwrap()
+            .into_iter()
+            .map(|it| format!("{:?} {}", it.kind, &text[it.range]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                "RawPtrDeref *ptr",
+                "UnsafeBlock unsafe",
+                "MutStaticAccess COUNTER",
+                "UnsafeFnCall frobnicate",
+                "UnsafeFnCall get",
+                "RawPtrDeref *ptr",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_macro_calls_among_items() {
+        let (analysis, file_id) = single_file(
+            r#"
+            vec![1, "a"];
+            fn foo() { vec![2]; }
+            "#,
+        );
+        let text = analysis.file_text(file_id);
+        let actual = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.tag == "literal" || it.tag == "string")
+            .map(|it| format!("{} {}", it.tag, &text[it.range]))
+            .collect::<Vec<_>>();
+        // The tokens of the calls are highlighted, and so are the ones of the
+        // expansions.
+        assert_eq!(
+            actual,
+            vec![
+                "literal 1",
+                "string \"a\"",
+                "literal 2",
+                "literal 1",
+                "string \"a\"",
+                "literal 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_mutable_bindings_and_mutations() {
+        let (analysis, file_id) = single_file(
+            r"
+            struct S { foo: u32 }
+            impl S {
+                fn inc(&mut self) {}
+                fn get(&self) -> u32 { 0 }
+            }
+            fn foo(mut s: S, t: S) {
+                let mut x = 92;
+                let ref mut y = 0;
+                x += 1;
+                s.foo = t.get();
+                s.inc();
+                let r = &mut x;
+            }
+            ",
+        );
+        let text = analysis.file_text(file_id);
+        let actual = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.tag == "mutable" || it.tag == "mutation")
+            .map(|it| format!("{} {}", it.tag, &text[it.range]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                "mutable x",
+                "mutable s",
+                "mutable s",
+                "mutable x",
+                "mutable s",
+                "mutable x",
+                "mutation x",
+                "mutation s.foo",
+                "mutation s",
+                "mutation x",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_names_by_the_kind_of_definition() {
+        let (analysis, file_id) = single_file(
+            r"
+            mod m { pub const C: () = (); }
+            struct S { foo: () }
+            impl S { fn get(&self) -> () { self.foo } }
+            fn foo(s: S) {
+                let x = m::C;
+                (x, s.get(), s.foo, unknown);
+            }
+            ",
+        );
+        let tags = ["variable", "field", "type", "module", "constant", "text"];
+        let text = analysis.file_text(file_id);
+        let actual = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| tags.contains(&it.tag))
+            .map(|it| format!("{} {}", it.tag, &text[it.range]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                "type S",
+                "field foo",
+                "type S",
+                "module m",
+                "constant C",
+                "variable x",
+                "variable s",
+                "variable s",
+                "field foo",
+                "text unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_format_specifiers() {
+        let (analysis, file_id) = single_file(
+            r#"
+            fn main() {
+                let x = 92;
+                println!("{} {x:?} {{}}", x);
+            }
+            "#,
+        );
+        let text = analysis.file_text(file_id);
+        let actual = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.tag == "format_specifier")
+            .map(|it| text[it.range].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(actual, vec!["{}", "{x:?}"]);
+    }
+
     #[test]
     fn highlights_code_inside_macros() {
         let (analysis, file_id) = single_file(
@@ -68,6 +632,34 @@ mod tests {
         )
     }
 
+    #[test]
+    fn highlights_code_inside_doc_tests() {
+        let (analysis, file_id) = single_file(
+            "
+            /// Returns the answer.
+            ///
+            /// ```
+            /// let x = 92;
+            /// ```
+            fn foo() {}
+            ",
+        );
+        let highlights = analysis.highlight(file_id).unwrap();
+        assert_eq_dbg(
+            r#"[HighlightedRange { range: [13; 36), tag: "comment" },
+                HighlightedRange { range: [49; 52), tag: "comment" },
+                HighlightedRange { range: [65; 72), tag: "comment" },
+                HighlightedRange { range: [85; 100), tag: "comment" },
+                HighlightedRange { range: [113; 120), tag: "comment" },
+                HighlightedRange { range: [133; 135), tag: "keyword" },
+                HighlightedRange { range: [136; 139), tag: "function" },
+                HighlightedRange { range: [89; 92), tag: "keyword" },
+                HighlightedRange { range: [93; 94), tag: "function" },
+                HighlightedRange { range: [97; 99), tag: "literal" }]"#,
+            &highlights,
+        )
+    }
+
     // FIXME: this test is not really necessary: artifact of the inital hacky
     // macros implementation.
     #[test]
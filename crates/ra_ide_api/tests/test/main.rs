@@ -1,11 +1,18 @@
 mod runnables;
 
-use ra_syntax::TextRange;
+use std::{sync::Arc, time::Instant};
+
+use ra_syntax::{
+    SyntaxKind::{FN_DEF, STRUCT_DEF},
+    TextRange,
+};
+use relative_path::RelativePathBuf;
 use test_utils::{assert_eq_dbg, assert_eq_text};
 
 use ra_ide_api::{
     mock_analysis::{single_file, single_file_with_position, MockAnalysis},
-    AnalysisChange, CrateGraph, FileId, Query
+    AnalysisChange, AnalysisHost, CrateGraph, Edition, FileId, FileRange, ImportStyle, LibraryData,
+    Query, Reference, ReferencesConfig, SourceRootId,
 };
 
 #[test]
@@ -16,16 +23,58 @@ fn test_unresolved_module_diagnostic() {
         r#"[Diagnostic {
             message: "unresolved module",
             range: [4; 7),
-            fix: Some(SourceChange {
-                label: "create module",
+            fixes: [SourceChange {
+                label: "create module foo.rs",
                 source_file_edits: [],
                 file_system_edits: [CreateFile { source_root: SourceRootId(0), path: "foo.rs" }],
-                cursor_position: None }),
-                severity: Error }]"#,
+                cursor_position: None,
+                placeholders: [] }, SourceChange {
+                label: "create module foo/mod.rs",
+                source_file_edits: [],
+                file_system_edits: [CreateFile { source_root: SourceRootId(0), path: "foo/mod.rs" }],
+                cursor_position: None,
+                placeholders: [] }],
+                severity: Error,
+                related: [] }]"#,
         &diagnostics,
     );
 }
 
+#[test]
+fn test_unresolved_module_diagnostic_in_non_dir_owner() {
+    let mock = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        mod bar;
+        //- /bar.rs
+        mod foo;
+        ",
+    );
+    let file_id = mock.id_of("/bar.rs");
+    let diagnostics = mock.analysis().diagnostics(file_id).unwrap();
+    assert_eq_dbg(
+        r#"[Diagnostic {
+            message: "unresolved module",
+            range: [4; 7),
+            fixes: [SourceChange {
+                label: "create module bar/foo.rs",
+                source_file_edits: [],
+                file_system_edits: [CreateFile { source_root: SourceRootId(0), path: "bar/foo.rs" }],
+                cursor_position: None,
+                placeholders: [] }],
+                severity: Error,
+                related: [] }]"#,
+        &diagnostics,
+    );
+}
+
+#[test]
+fn test_no_unresolved_module_diagnostic_inside_function() {
+    let (analysis, file_id) = single_file("fn main() { mod tests; mod foo {} }");
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_eq_dbg(r#"[]"#, &diagnostics);
+}
+
 // FIXME: move this test to hir
 #[test]
 fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
@@ -34,6 +83,297 @@ fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
     assert_eq_dbg(r#"[]"#, &diagnostics);
 }
 
+#[test]
+fn test_duplicate_definition_diagnostic() {
+    let (analysis, file_id) = single_file("mod foo { fn bar() {} fn bar() {} }");
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_eq_dbg(
+        r#"[Diagnostic {
+            message: "`bar` is defined multiple times",
+            range: [25; 28),
+            fixes: [],
+            severity: Error,
+            related: [(FileRange { file_id: FileId(1), range: [13; 16) }, "first definition")] }]"#,
+        &diagnostics,
+    );
+}
+
+#[test]
+fn test_missing_match_arms_diagnostic() {
+    let text = "
+enum E { A, B(u32), C { x: u32 } }
+fn f(e: E, b: bool) {
+    match e { E::A => (), _ if b => () }
+    match e { E::A => (), _ => () }
+    match b { true => () }
+    if let E::A = e {}
+}
+";
+    let (analysis, file_id) = single_file(text);
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let actual = diagnostics
+        .iter()
+        .map(|it| (it.message.as_str(), &text[it.range], it.fixes[0].label.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            ("missing arms: `E::B(..)`, `E::C { .. }`", "match e", "fill match arms"),
+            ("missing arms: `false`", "match b", "fill match arms"),
+        ]
+    );
+}
+
+#[test]
+fn test_missing_match_arms_use_the_enum_path_in_scope() {
+    let text = "
+mod m {
+    pub enum E { A, B }
+    pub enum F { A, #[cfg(test)] B }
+}
+fn f(e: m::E, f: m::F) {
+    match e { m::E::A => () }
+    match f { m::F::A => () }
+}
+";
+    let (analysis, file_id) = single_file(text);
+    let messages = analysis
+        .diagnostics(file_id)
+        .unwrap()
+        .into_iter()
+        .map(|it| it.message)
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["missing arms: `crate::m::E::B`".to_string()]);
+}
+
+#[test]
+fn test_fill_match_arms_assist() {
+    let (analysis, position) = single_file_with_position(
+        "
+enum E { A, B(u32) }
+fn f(e: E) {
+    match e<|> {
+        E::A => (),
+    }
+}
+",
+    );
+    let frange = FileRange {
+        file_id: position.file_id,
+        range: TextRange::offset_len(position.offset, 0.into()),
+    };
+    let change = analysis
+        .assists(frange, ImportStyle::Plain)
+        .unwrap()
+        .into_iter()
+        .find(|it| it.label == "fill match arms")
+        .unwrap();
+    let text = analysis.file_text(position.file_id);
+    assert_eq_text!(
+        "
+enum E { A, B(u32) }
+fn f(e: E) {
+    match e {
+        E::A => (),
+        E::B(..) => {},
+    }
+}
+",
+        &change.source_file_edits[0].edit.apply(&text)
+    );
+}
+
+#[test]
+fn test_import_and_qualify_path_assists() {
+    let (analysis, position) = single_file_with_position(
+        "
+mod collections {
+    pub struct HashMap;
+}
+fn f() { let m = HashMap<|>; }
+",
+    );
+    let frange = FileRange {
+        file_id: position.file_id,
+        range: TextRange::offset_len(position.offset, 0.into()),
+    };
+    let text = analysis.file_text(position.file_id);
+    let actual = analysis
+        .assists(frange, ImportStyle::Plain)
+        .unwrap()
+        .into_iter()
+        .filter(|it| it.label.starts_with("import") || it.label.starts_with("qualify"))
+        .map(|it| (it.label, it.source_file_edits[0].edit.apply(&text)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            (
+                "import `crate::collections::HashMap`".to_string(),
+                "
+use crate::collections::HashMap;
+
+mod collections {
+    pub struct HashMap;
+}
+fn f() { let m = HashMap; }
+"
+                .to_string(),
+            ),
+            (
+                "qualify as `collections::HashMap`".to_string(),
+                "
+mod collections {
+    pub struct HashMap;
+}
+fn f() { let m = collections::HashMap; }
+"
+                .to_string(),
+            ),
+        ]
+    );
+
+    // `collections` is not in scope of `app`, so it is imported.
+    let (analysis, position) = single_file_with_position(
+        "
+mod collections {
+    pub struct HashMap;
+}
+mod app {
+    fn f() { let m = HashMap<|>; }
+}
+",
+    );
+    let frange = FileRange {
+        file_id: position.file_id,
+        range: TextRange::offset_len(position.offset, 0.into()),
+    };
+    let text = analysis.file_text(position.file_id);
+    let qualify = analysis
+        .assists(frange, ImportStyle::Plain)
+        .unwrap()
+        .into_iter()
+        .find(|it| it.label == "qualify as `collections::HashMap`")
+        .unwrap();
+    let actual = qualify.source_file_edits[0].edit.apply(&text);
+    assert!(actual.contains("use crate::collections;"));
+    assert!(actual.contains("let m = collections::HashMap;"));
+}
+
+#[test]
+fn test_body_problem_diagnostics() {
+    let text = "
+enum Option<T> { Some(T), None }
+fn f(x: Option<u32>) -> u32 {
+    break;
+    loop { let g = || continue; break; }
+    x?
+}
+fn g(x: Option<u32>) -> Option<u32> {
+    let y = x?;
+    while y > 0 { continue }
+    None
+}
+enum Result<T, E> { Ok(T), Err(E) }
+fn h(y: Result<u32, ()>) -> u32 {
+    y?
+}
+";
+    let (analysis, file_id) = single_file(text);
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let actual = diagnostics
+        .iter()
+        .map(|it| {
+            let fix = it.fixes.first().map(|it| it.label.as_str());
+            (it.message.as_str(), &text[it.range], fix)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            ("`break` outside of a loop", "break", None),
+            ("`continue` outside of a loop", "continue", None),
+            (
+                "the `?` operator can only be used in a function that returns `Result` or `Option`",
+                "x?",
+                Some("change return type to `Option`"),
+            ),
+            (
+                "the `?` operator can only be used in a function that returns `Result` or `Option`",
+                "y?",
+                Some("change return type to `Result`"),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_diagnostics_in_macro_expansions_point_to_the_call() {
+    let text = "vec![1, break];";
+    let (analysis, file_id) = single_file(text);
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let actual = diagnostics
+        .iter()
+        .map(|it| (it.message.as_str(), &text[it.range], it.fixes.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(actual, vec![("`break` outside of a loop", "break", 0)]);
+}
+
+#[test]
+fn test_unresolved_format_arg_diagnostic() {
+    let text = r#"
+const LIMIT: u32 = 10;
+fn main() {
+    let x = 92;
+    println!("{x} {y} {LIMIT} {z}", z = 1);
+    println!("caf\u{e9} {x}");
+    let y = 0;
+}
+"#;
+    let (analysis, file_id) = single_file(text);
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let actual = diagnostics
+        .iter()
+        .map(|it| (it.message.as_str(), &text[it.range]))
+        .collect::<Vec<_>>();
+    assert_eq!(actual, vec![("cannot find value `y` in this scope", "y")]);
+}
+
+#[test]
+fn world_symbols_survive_file_renames() {
+    fn symbols(host: &AnalysisHost, name: &str) -> Vec<String> {
+        host.analysis()
+            .symbol_search(Query::new(name.to_string()))
+            .unwrap()
+            .into_iter()
+            .map(|it| it.name().to_string())
+            .collect()
+    }
+
+    let mock = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        mod foo;
+        //- /foo.rs
+        struct Foo;
+    ",
+    );
+    let file_id = mock.id_of("/foo.rs");
+    let mut host = mock.analysis_host();
+    assert_eq!(symbols(&host, "Foo"), vec!["Foo".to_string()]);
+
+    let mut change = AnalysisChange::new();
+    change.remove_file(SourceRootId(0), file_id, RelativePathBuf::from("foo.rs"));
+    change.add_file(
+        SourceRootId(0),
+        file_id,
+        RelativePathBuf::from("bar.rs"),
+        Arc::new("struct Foo;".to_string()),
+    );
+    host.apply_change(change);
+    assert_eq!(symbols(&host, "Foo"), vec!["Foo".to_string()]);
+}
+
 #[test]
 fn test_resolve_crate_root() {
     let mock = MockAnalysis::with_files(
@@ -50,7 +390,7 @@ fn test_resolve_crate_root() {
     assert!(host.analysis().crate_for(mod_file).unwrap().is_empty());
 
     let mut crate_graph = CrateGraph::default();
-    let crate_id = crate_graph.add_crate_root(root_file);
+    let crate_id = crate_graph.add_crate_root(root_file, Edition::Edition2018);
     let mut change = AnalysisChange::new();
     change.set_crate_graph(crate_graph);
     host.apply_change(change);
@@ -58,9 +398,52 @@ fn test_resolve_crate_root() {
     assert_eq!(host.analysis().crate_for(mod_file).unwrap(), vec![crate_id]);
 }
 
-fn get_all_refs(text: &str) -> Vec<(FileId, TextRange)> {
+#[test]
+fn test_crate_metadata() {
+    let mock = MockAnalysis::with_files(
+        "
+        //- /main.rs
+        extern crate foo;
+        //- /foo.rs
+        pub fn foo() {}
+        //- /bar.rs
+        pub fn bar() {}
+    ",
+    );
+    let main_file = mock.id_of("/main.rs");
+    let foo_file = mock.id_of("/foo.rs");
+    let bar_file = mock.id_of("/bar.rs");
+    let mut host = mock.analysis_host();
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_file, Edition::Edition2018);
+    let foo_crate = crate_graph.add_crate_root(foo_file, Edition::Edition2015);
+    let bar_crate = crate_graph.add_crate_root(bar_file, Edition::Edition2015);
+    crate_graph.add_dep(main_crate, "foo".into(), foo_crate);
+    crate_graph.add_dep(foo_crate, "bar".into(), bar_crate);
+    let mut change = AnalysisChange::new();
+    change.set_crate_graph(crate_graph);
+    host.apply_change(change);
+
+    let analysis = host.analysis();
+    assert_eq!(analysis.crate_root(foo_crate).unwrap(), foo_file);
+    assert_eq!(analysis.crate_edition(main_crate).unwrap(), Edition::Edition2018);
+    assert_eq!(analysis.crate_edition(foo_crate).unwrap(), Edition::Edition2015);
+    let deps = analysis.crate_dependencies(main_crate).unwrap();
+    assert_eq!(deps.len(), 1);
+    assert_eq!(deps[0].name.as_str(), "foo");
+    assert_eq!(deps[0].crate_id(), foo_crate);
+    assert_eq!(
+        analysis.transitive_reverse_dependencies(bar_crate).unwrap(),
+        vec![bar_crate, foo_crate, main_crate]
+    );
+}
+
+fn get_all_refs(text: &str) -> Vec<Reference> {
     let (analysis, position) = single_file_with_position(text);
-    analysis.find_all_refs(position).unwrap()
+    analysis
+        .find_all_refs(position, &ReferencesConfig::default())
+        .unwrap()
 }
 
 #[test]
@@ -181,7 +564,7 @@ fn test_rename_for_mut_param() {
 
 fn test_rename(text: &str, new_name: &str, expected: &str) {
     let (analysis, position) = single_file_with_position(text);
-    let edits = analysis.rename(position, new_name).unwrap();
+    let edits = analysis.rename(position, new_name).unwrap().unwrap();
     let mut text_edit_bulder = ra_text_edit::TextEditBuilder::default();
     let mut file_id: Option<FileId> = None;
     for edit in edits {
@@ -211,3 +594,317 @@ pub trait HirDatabase: SyntaxDatabase {}
     assert_eq!(s.name(), "HirDatabase");
     assert_eq!(s.full_range(), TextRange::from_to(33.into(), 44.into()));
 }
+
+#[test]
+fn world_symbols_have_container_names() {
+    let (analysis, _) = single_file(
+        "
+mod util {
+    mod io {
+        struct Error;
+    }
+}
+struct Error;
+    ",
+    );
+
+    let symbols = analysis.symbol_search(Query::new("Error".into())).unwrap();
+    let mut containers = symbols
+        .iter()
+        .map(|it| it.container_name().map(|it| it.to_string()))
+        .collect::<Vec<_>>();
+    containers.sort();
+    assert_eq!(containers, vec![None, Some("util::io".to_string())]);
+
+    let symbols = analysis.symbol_search(Query::new("io".into())).unwrap();
+    let io = symbols.iter().find(|it| it.name() == "io").unwrap();
+    assert_eq!(io.container_name().map(|it| it.as_str()), Some("util"));
+}
+
+#[test]
+fn world_symbols_are_prefixed_with_the_crate_name() {
+    let analysis = MockAnalysis::with_files(
+        "
+        //- /main.rs crate:app deps:util
+        struct Config;
+        //- /lib.rs crate:util
+        mod io {
+            struct Buf;
+        }
+        ",
+    )
+    .analysis();
+
+    let container_of = |name: &str| {
+        let symbols = analysis.symbol_search(Query::new(name.into())).unwrap();
+        let symbol = symbols.iter().find(|it| it.name() == name).unwrap();
+        symbol.container_name().map(|it| it.to_string())
+    };
+    assert_eq!(container_of("Config"), Some("app".to_string()));
+    assert_eq!(container_of("Buf"), Some("util::io".to_string()));
+}
+
+#[test]
+fn world_symbols_have_module_docs() {
+    let analysis = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        mod util;
+        /// Reads and writes.
+        mod io {
+            //! Synchronously.
+        }
+        mod empty {}
+        //- /util.rs
+        //! Frobnicates widgets.
+        ",
+    )
+    .analysis();
+
+    let docs_of = |name: &str| {
+        let symbols = analysis.symbol_search(Query::new(name.into())).unwrap();
+        let symbol = symbols.iter().find(|it| it.name() == name).unwrap();
+        symbol.docs_summary().map(|it| it.to_string())
+    };
+    assert_eq!(docs_of("util"), Some("Frobnicates widgets.".to_string()));
+    assert_eq!(docs_of("io"), Some("Reads and writes.".to_string()));
+    assert_eq!(docs_of("empty"), None);
+}
+
+#[test]
+fn world_symbols_include_items_of_impls_and_traits() {
+    let (analysis, _) = single_file(
+        "
+mod util {
+    struct Buf<T>;
+    impl<T> Buf<T> {
+        fn push(&mut self) {}
+        const CAP: usize = 0;
+    }
+    trait Flush {
+        fn flush(&mut self);
+    }
+}
+    ",
+    );
+
+    let container_of = |name: &str| {
+        let symbols = analysis.symbol_search(Query::new(name.into())).unwrap();
+        let symbol = symbols.iter().find(|it| it.name() == name).unwrap();
+        symbol.container_name().map(|it| it.to_string())
+    };
+    assert_eq!(container_of("push"), Some("util::Buf".to_string()));
+    assert_eq!(container_of("CAP"), Some("util::Buf".to_string()));
+    assert_eq!(container_of("flush"), Some("util::Flush".to_string()));
+}
+
+#[test]
+fn library_symbols_are_updated_when_library_changes() {
+    fn lib_symbols(host: &AnalysisHost, name: &str) -> Vec<String> {
+        let mut query = Query::new(name.to_string());
+        query.libraries(true);
+        host.analysis()
+            .symbol_search(query)
+            .unwrap()
+            .into_iter()
+            .map(|it| it.name().to_string())
+            .collect()
+    }
+    fn lib(text: &str) -> LibraryData {
+        let files = vec![(
+            FileId(100),
+            RelativePathBuf::from("lib.rs"),
+            Arc::new(text.to_string()),
+        )];
+        LibraryData::prepare(SourceRootId(1), files)
+    }
+
+    let mut host = MockAnalysis::new().analysis_host();
+
+    let mut change = AnalysisChange::new();
+    change.add_library(lib("struct OldName;"));
+    host.apply_change(change);
+    assert_eq!(lib_symbols(&host, "OldName"), vec!["OldName".to_string()]);
+
+    let mut change = AnalysisChange::new();
+    change.add_library(lib("struct NewName;"));
+    host.apply_change(change);
+    assert!(lib_symbols(&host, "OldName").is_empty());
+    assert_eq!(lib_symbols(&host, "NewName"), vec!["NewName".to_string()]);
+
+    let mut change = AnalysisChange::new();
+    change.remove_library(SourceRootId(1));
+    host.apply_change(change);
+    assert!(lib_symbols(&host, "NewName").is_empty());
+}
+
+#[test]
+fn world_symbols_are_ranked() {
+    let mock = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        mod deep;
+        mod shallow;
+        fn HashMap() {}
+        fn hash_map() {}
+        //- /deep.rs
+        mod inner {
+            struct HashMap;
+        }
+        //- /shallow.rs
+        struct HashMap;
+    ",
+    );
+    let lib_rs = mock.id_of("/lib.rs");
+    let deep_rs = mock.id_of("/deep.rs");
+    let shallow_rs = mock.id_of("/shallow.rs");
+    let library_rs = FileId(100);
+    let mut host = mock.analysis_host();
+    let mut change = AnalysisChange::new();
+    change.add_library(LibraryData::prepare(
+        SourceRootId(1),
+        vec![(
+            library_rs,
+            RelativePathBuf::from("lib.rs"),
+            Arc::new("struct HashMap;".to_string()),
+        )],
+    ));
+    host.apply_change(change);
+
+    let symbols = |limit: usize| {
+        let mut query = Query::new("HashMap".to_string());
+        query.libraries(true);
+        query.limit(limit);
+        host.analysis()
+            .symbol_search(query)
+            .unwrap()
+            .into_iter()
+            .map(|it| (it.name().to_string(), it.file_id(), it.kind()))
+            .collect::<Vec<_>>()
+    };
+    let hash_map = |file_id, kind| ("HashMap".to_string(), file_id, kind);
+    assert_eq!(
+        symbols(10),
+        vec![
+            hash_map(shallow_rs, STRUCT_DEF),
+            hash_map(deep_rs, STRUCT_DEF),
+            hash_map(lib_rs, FN_DEF),
+            hash_map(library_rs, STRUCT_DEF),
+            ("hash_map".to_string(), lib_rs, FN_DEF),
+        ]
+    );
+    assert_eq!(symbols(1), vec![hash_map(shallow_rs, STRUCT_DEF)]);
+}
+
+#[test]
+fn world_symbols_search_libraries_only_on_request() {
+    fn symbols(host: &AnalysisHost, libraries: bool) -> Vec<String> {
+        let mut query = Query::new("Name".to_string());
+        query.libraries(libraries);
+        let mut res = host
+            .analysis()
+            .symbol_search(query)
+            .unwrap()
+            .into_iter()
+            .map(|it| it.name().to_string())
+            .collect::<Vec<_>>();
+        res.sort();
+        res
+    }
+
+    let mut host = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        struct LocalName;
+        ",
+    )
+    .analysis_host();
+    let mut change = AnalysisChange::new();
+    let files = vec![(
+        FileId(100),
+        RelativePathBuf::from("lib.rs"),
+        Arc::new("struct LibraryName;".to_string()),
+    )];
+    change.add_library(LibraryData::prepare(SourceRootId(1), files));
+    host.apply_change(change);
+
+    assert_eq!(symbols(&host, false), vec!["LocalName".to_string()]);
+    assert_eq!(
+        symbols(&host, true),
+        vec!["LibraryName".to_string(), "LocalName".to_string()]
+    );
+}
+
+#[test]
+fn world_symbols_of_libraries_are_marked() {
+    let mut host = MockAnalysis::with_files(
+        "
+        //- /lib.rs
+        struct LocalName;
+        ",
+    )
+    .analysis_host();
+    let mut change = AnalysisChange::new();
+    let files = vec![(
+        FileId(100),
+        RelativePathBuf::from("lib.rs"),
+        Arc::new("struct LibraryName;".to_string()),
+    )];
+    change.add_library(LibraryData::prepare(SourceRootId(1), files));
+    host.apply_change(change);
+
+    let mut query = Query::new("Name".to_string());
+    query.libraries(true);
+    let mut res = host
+        .analysis()
+        .symbol_search(query)
+        .unwrap()
+        .into_iter()
+        .map(|it| (it.name().to_string(), it.container_name().map(|it| it.to_string())))
+        .collect::<Vec<_>>();
+    res.sort();
+    assert_eq!(res[0], ("LibraryName".to_string(), Some("(dependency)".to_string())));
+    assert_eq!(res[1].0, "LocalName");
+    assert!(!res[1].1.as_ref().map_or(false, |it| it.contains("(dependency)")));
+}
+
+#[test]
+fn world_symbols_return_partial_results_after_the_deadline() {
+    let (analysis, _) = single_file("struct Foo;");
+    let mut query = Query::new("Foo".to_string());
+    query.deadline(Instant::now());
+    let symbols = analysis.symbol_search_partial(query).unwrap();
+    assert!(symbols.is_incomplete);
+    assert!(symbols.value.is_empty());
+
+    let symbols = analysis.symbol_search_partial(Query::new("Foo".to_string())).unwrap();
+    assert!(!symbols.is_incomplete);
+    assert_eq!(symbols.value.len(), 1);
+}
+
+#[test]
+fn goto_definition_of_builtin_type_jumps_to_its_std_docs() {
+    let (mock, position) = MockAnalysis::with_files_and_position(
+        "
+        //- /lib.rs
+        fn foo(x: u3<|>2) {}
+        ",
+    );
+    let mut host = mock.analysis_host();
+    let navs = host.analysis().goto_definition(position).unwrap().unwrap().info;
+    assert!(navs.is_empty());
+
+    let mut change = AnalysisChange::new();
+    let files = vec![(
+        FileId(100),
+        RelativePathBuf::from("lib.rs"),
+        Arc::new("#[doc(primitive = \"u32\")]\nmod prim_u32 {}\nmod prim_u8 {}".to_string()),
+    )];
+    change.add_library(LibraryData::prepare(SourceRootId(1), files));
+    host.apply_change(change);
+
+    let navs = host.analysis().goto_definition(position).unwrap().unwrap().info;
+    assert_eq!(navs.len(), 1);
+    assert_eq!(navs[0].file_id(), FileId(100));
+    assert_eq!(navs[0].name().as_str(), "prim_u32");
+}
@@ -1,4 +1,4 @@
-use test_utils::assert_eq_dbg;
+use test_utils::{assert_eq_dbg, assert_eq_text};
 
 use ra_ide_api::mock_analysis::analysis_and_position;
 
@@ -93,6 +93,70 @@ fn test_runnables_multiple_depth_module() {
     )
 }
 
+#[test]
+fn test_runnables_module_inside_function() {
+    let (analysis, pos) = analysis_and_position(
+        r#"
+        //- /lib.rs
+        <|> //empty
+        fn main() {
+            mod tests {
+                #[test]
+                fn test_foo1() {}
+            }
+        }
+        "#,
+    );
+    let runnables = analysis.runnables(pos.file_id).unwrap();
+    assert_eq_dbg(
+        r#"[Runnable { range: [1; 86), kind: Bin },
+                Runnable { range: [25; 84), kind: TestMod { path: "tests" } },
+                Runnable { range: [45; 78), kind: Test { name: "test_foo1" } }]"#,
+        &runnables,
+    )
+}
+
+#[test]
+fn test_runnables_nested_and_cfg_test_modules() {
+    let (analysis, pos) = analysis_and_position(
+        r#"
+        //- /lib.rs
+        <|>mod outer {
+            mod inner {
+                #[test]
+                fn test_foo() {}
+            }
+        }
+        #[cfg(test)]
+        mod helpers {
+            fn helper() {}
+        }
+        mod foo;
+        mod no_tests {}
+        //- /foo.rs
+        mod tests {
+            #[test]
+            fn test_bar() {}
+        }
+        "#,
+    );
+    let runnables = analysis.runnables(pos.file_id).unwrap();
+    let kinds = runnables
+        .iter()
+        .map(|it| format!("{:?}", it.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            r#"TestMod { path: "outer" }"#,
+            r#"TestMod { path: "outer::inner" }"#,
+            r#"Test { name: "test_foo" }"#,
+            r#"TestMod { path: "helpers" }"#,
+            r#"TestMod { path: "foo" }"#,
+        ]
+    );
+}
+
 #[test]
 fn test_runnables_no_test_function_in_module() {
     let (analysis, pos) = analysis_and_position(
@@ -107,3 +171,76 @@ fn test_runnables_no_test_function_in_module() {
     let runnables = analysis.runnables(pos.file_id).unwrap();
     assert_eq_dbg(r#"[]"#, &runnables)
 }
+
+#[test]
+fn test_crate_tests() {
+    let (analysis, pos) = analysis_and_position(
+        r#"
+        //- /lib.rs
+        <|>mod foo;
+        mod no_tests {
+            fn helper() {}
+        }
+        fn main() {}
+        #[test]
+        fn test_root() {}
+        //- /foo.rs
+        #[bench]
+        fn bench_foo() {}
+        mod tests {
+            #[test]
+            fn test_foo() {}
+        }
+        "#,
+    );
+    let crate_id = analysis.crate_for(pos.file_id).unwrap()[0];
+    let root = analysis.crate_tests(crate_id).unwrap().unwrap();
+    let mut actual = String::new();
+    let mut stack = vec![&root];
+    while let Some(module) = stack.pop() {
+        let tests = module.tests.iter().map(|it| &it.kind).collect::<Vec<_>>();
+        actual.push_str(&format!("{:?} {:?}\n", module.path, tests));
+        stack.extend(module.children.iter().rev());
+    }
+    assert_eq_text!(
+        r#""" [Test { name: "test_root" }]
+"foo" [Bench { name: "foo::bench_foo" }]
+"foo::tests" [Test { name: "foo::tests::test_foo" }]
+"#,
+        &actual
+    );
+}
+
+#[test]
+fn test_related_tests() {
+    let (analysis, pos) = analysis_and_position(
+        r#"
+        //- /lib.rs
+        pub fn parse_he<|>ader() {}
+        fn other() {}
+        #[cfg(test)]
+        mod tests;
+        //- /tests.rs
+        #[test]
+        fn reads_the_header() { super::parse_header(); }
+        #[test]
+        fn parse_header_rejects_garbage() {}
+        #[test]
+        fn parses_headers() {}
+        #[test]
+        fn unrelated() { super::other(); }
+        "#,
+    );
+    let tests = analysis.related_tests(pos).unwrap();
+    let actual = tests
+        .iter()
+        .map(|it| (it.path.as_str(), it.navigation_target.name().as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            ("tests::reads_the_header", "reads_the_header"),
+            ("tests::parse_header_rejects_garbage", "parse_header_rejects_garbage"),
+        ]
+    );
+}
@@ -18,7 +18,7 @@ use ra_syntax::{
 };
 use itertools::Itertools;
 
-use crate::formatting::leading_indent;
+use crate::{PositionKind, classify_position, formatting::leading_indent};
 
 pub use self::{
     flip_comma::flip_comma,
@@ -32,6 +32,10 @@ pub use self::{
 
 /// Return all the assists applicable at the given position.
 pub fn assists(file: &SourceFile, range: TextRange) -> Vec<LocalEdit> {
+    // Assists edit code, so there are none in comments and literals.
+    if classify_position(file, range.start()) != PositionKind::Code {
+        return Vec::new();
+    }
     let ctx = AssistCtx::new(file, range);
     [
         flip_comma,
@@ -52,6 +56,10 @@ pub struct LocalEdit {
     pub label: String,
     pub edit: TextEdit,
     pub cursor_position: Option<TextUnit>,
+    /// The ranges the user is likely to fill in next, like the `$1`, `$2` of
+    /// a snippet, in the order of visiting. Like the `cursor_position`, these
+    /// are ranges in the edited text.
+    pub placeholders: Vec<TextRange>,
 }
 
 fn non_trivia_sibling(node: &SyntaxNode, direction: Direction) -> Option<&SyntaxNode> {
@@ -107,6 +115,7 @@ pub enum Assist {
 struct AssistBuilder {
     edit: TextEditBuilder,
     cursor_position: Option<TextUnit>,
+    placeholders: Vec<TextRange>,
 }
 
 impl<'a> AssistCtx<'a> {
@@ -146,6 +155,7 @@ impl<'a> AssistCtx<'a> {
             label: label.into(),
             edit: edit.edit.finish(),
             cursor_position: edit.cursor_position,
+            placeholders: edit.placeholders,
         }))
     }
 
@@ -169,7 +179,7 @@ impl AssistBuilder {
         if let Some(indent) = leading_indent(node) {
             replace_with = reindent(&replace_with, indent)
         }
-        self.replace(node.range(), replace_with)
+        self.edit.replace_with_diff(node.range(), &node.text().to_string(), &replace_with)
     }
     #[allow(unused)]
     fn delete(&mut self, range: TextRange) {
@@ -181,6 +191,9 @@ impl AssistBuilder {
     fn set_cursor(&mut self, offset: TextUnit) {
         self.cursor_position = Some(offset)
     }
+    fn add_placeholder(&mut self, range: TextRange) {
+        self.placeholders.push(range)
+    }
 }
 
 fn reindent(text: &str, indent: &str) -> String {
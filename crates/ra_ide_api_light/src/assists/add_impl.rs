@@ -1,7 +1,7 @@
 use join_to_string::join;
 use ra_syntax::{
     ast::{self, AstNode, AstToken, NameOwner, TypeParamsOwner},
-    TextUnit,
+    TextRange, TextUnit,
 };
 
 use crate::assists::{AssistCtx, Assist};
@@ -33,7 +33,10 @@ pub fn add_impl(ctx: AssistCtx) -> Option<Assist> {
                 .to_buf(&mut buf);
         }
         buf.push_str(" {\n");
-        edit.set_cursor(start_offset + TextUnit::of_str(&buf));
+        // The body is a tab stop, so that a snippet leaves the cursor there.
+        let body_offset = start_offset + TextUnit::of_str(&buf);
+        edit.set_cursor(body_offset);
+        edit.add_placeholder(TextRange::offset_len(body_offset, 0.into()));
         buf.push_str("\n}");
         edit.insert(start_offset, buf);
     })
@@ -41,6 +44,8 @@ pub fn add_impl(ctx: AssistCtx) -> Option<Assist> {
 
 #[cfg(test)]
 mod tests {
+    use ra_syntax::SourceFile;
+
     use super::*;
     use crate::assists::check_assist;
 
@@ -63,4 +68,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_impl_puts_a_tab_stop_in_the_body() {
+        let file = SourceFile::parse("struct Foo {}\n");
+        let range = TextRange::offset_len(1.into(), 0.into());
+        let edit = AssistCtx::new(&file, range).apply(add_impl).unwrap();
+        let body_offset = TextUnit::of_str("struct Foo {}\n\nimpl Foo {\n");
+        assert_eq!(edit.placeholders, vec![TextRange::offset_len(body_offset, 0.into())]);
+    }
+
 }
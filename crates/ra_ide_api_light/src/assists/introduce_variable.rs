@@ -1,7 +1,7 @@
 use ra_syntax::{
     ast::{self, AstNode},
     SyntaxKind::WHITESPACE,
-    SyntaxNode, TextUnit,
+    SyntaxNode, TextRange, TextUnit,
 };
 
 use crate::assists::{AssistCtx, Assist};
@@ -33,7 +33,9 @@ pub fn introduce_variable<'a>(ctx: AssistCtx) -> Option<Assist> {
             edit.replace(expr.syntax().range(), "var_name".to_string());
             edit.insert(anchor_stmt.range().start(), buf);
         }
-        edit.set_cursor(anchor_stmt.range().start() + TextUnit::of_str("let "));
+        let name_start = anchor_stmt.range().start() + TextUnit::of_str("let ");
+        edit.set_cursor(name_start);
+        edit.add_placeholder(TextRange::offset_len(name_start, TextUnit::of_str("var_name")));
     })
 }
 
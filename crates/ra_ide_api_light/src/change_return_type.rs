@@ -0,0 +1,208 @@
+use ra_syntax::{
+    AstNode,
+    SyntaxKind::{R_CURLY, RETURN_EXPR},
+    ast,
+};
+
+use crate::{LocalEdit, TextEditBuilder, formatting::leading_indent};
+
+const ERROR_TYPE: &str = "Box<dyn std::error::Error>";
+
+/// Changes the return type `T` of `fn_def` to `Result<T, Box<dyn Error>>`,
+/// and wraps the tail expression of the body in `Ok`, so that the `?`
+/// operator can be used. Functions with a `return` are left alone, as each
+/// returned value would have to be wrapped too.
+pub fn change_return_type_to_result(fn_def: &ast::FnDef) -> Option<LocalEdit> {
+    change_return_type(fn_def, TryType::Result)
+}
+
+/// Like `change_return_type_to_result`, but for `?` applied to an `Option`:
+/// the return type becomes `Option<T>` and the tail is wrapped in `Some`.
+pub fn change_return_type_to_option(fn_def: &ast::FnDef) -> Option<LocalEdit> {
+    change_return_type(fn_def, TryType::Option)
+}
+
+#[derive(Clone, Copy)]
+enum TryType {
+    Result,
+    Option,
+}
+
+impl TryType {
+    fn wrap_type(self, ty: &str) -> String {
+        match self {
+            TryType::Result => format!("Result<{}, {}>", ty, ERROR_TYPE),
+            TryType::Option => format!("Option<{}>", ty),
+        }
+    }
+
+    fn wrap_expr(self, expr: &str) -> String {
+        match self {
+            TryType::Result => format!("Ok({})", expr),
+            TryType::Option => format!("Some({})", expr),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TryType::Result => "Result",
+            TryType::Option => "Option",
+        }
+    }
+}
+
+fn change_return_type(fn_def: &ast::FnDef, try_type: TryType) -> Option<LocalEdit> {
+    let body = fn_def.body()?;
+    if body.syntax().descendants().any(|it| it.kind() == RETURN_EXPR) {
+        return None;
+    }
+    let mut edit = TextEditBuilder::default();
+    match fn_def.ret_type().and_then(|it| it.type_ref()) {
+        Some(type_ref) => {
+            let new_type = try_type.wrap_type(&type_ref.syntax().text().to_string());
+            edit.replace(type_ref.syntax().range(), new_type);
+        }
+        None => {
+            let param_list = fn_def.param_list()?;
+            let new_type = format!(" -> {}", try_type.wrap_type("()"));
+            edit.insert(param_list.syntax().range().end(), new_type);
+        }
+    }
+    match body.expr() {
+        Some(tail) => {
+            let new_tail = try_type.wrap_expr(&tail.syntax().text().to_string());
+            edit.replace(tail.syntax().range(), new_tail);
+        }
+        None => {
+            // Without a tail expression, the function returns `()`.
+            if fn_def.ret_type().is_some() {
+                return None;
+            }
+            let r_curly = body.syntax().last_child().filter(|it| it.kind() == R_CURLY)?;
+            let anchor = r_curly.prev_sibling().filter(|it| !it.kind().is_trivia())?;
+            let unit = try_type.wrap_expr("()");
+            let text = match leading_indent(r_curly) {
+                Some(indent) => format!("\n{}    {}", indent, unit),
+                None => format!(" {}", unit),
+            };
+            edit.insert(anchor.range().end(), text);
+        }
+    }
+    Some(LocalEdit {
+        label: format!("change return type to `{}`", try_type.name()),
+        edit: edit.finish(),
+        cursor_position: None,
+        placeholders: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::SourceFile;
+
+    use crate::test_utils::assert_eq_text;
+
+    use super::*;
+
+    fn check_change_return_type(before: &str, after: &str) {
+        check(change_return_type_to_result, before, after)
+    }
+
+    fn check_change_return_type_to_option(before: &str, after: &str) {
+        check(change_return_type_to_option, before, after)
+    }
+
+    fn check(f: fn(&ast::FnDef) -> Option<LocalEdit>, before: &str, after: &str) {
+        let file = SourceFile::parse(before);
+        let fn_def = file
+            .syntax()
+            .descendants()
+            .find_map(ast::FnDef::cast)
+            .unwrap();
+        let actual = match f(fn_def) {
+            Some(edit) => edit.edit.apply(before),
+            None => before.to_string(),
+        };
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn changes_unit_return_type() {
+        check_change_return_type(
+            r"
+fn main() {
+    let x = foo()?;
+}
+",
+            r"
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let x = foo()?;
+    Ok(())
+}
+",
+        );
+    }
+
+    #[test]
+    fn wraps_the_tail_expression() {
+        check_change_return_type(
+            r"
+fn foo() -> u32 {
+    bar()? + 1
+}
+",
+            r"
+fn foo() -> Result<u32, Box<dyn std::error::Error>> {
+    Ok(bar()? + 1)
+}
+",
+        );
+    }
+
+    #[test]
+    fn ignores_functions_with_returns() {
+        check_change_return_type(
+            r"
+fn foo() -> u32 {
+    if bar()? { return 0 }
+    1
+}
+",
+            r"
+fn foo() -> u32 {
+    if bar()? { return 0 }
+    1
+}
+",
+        );
+    }
+
+    #[test]
+    fn changes_return_type_to_option() {
+        check_change_return_type_to_option(
+            r"
+fn foo(x: Option<u32>) -> u32 {
+    x? + 1
+}
+",
+            r"
+fn foo(x: Option<u32>) -> Option<u32> {
+    Some(x? + 1)
+}
+",
+        );
+        check_change_return_type_to_option(
+            r"
+fn main() {
+    let x = foo()?;
+}
+",
+            r"
+fn main() -> Option<()> {
+    let x = foo()?;
+    Some(())
+}
+",
+        );
+    }
+}
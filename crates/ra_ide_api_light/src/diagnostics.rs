@@ -61,6 +61,7 @@ fn check_unnecessary_braces_in_use_statement(
                 label: "Remove unnecessary braces".to_string(),
                 edit,
                 cursor_position: None,
+                placeholders: Vec::new(),
             }),
         });
     }
@@ -114,6 +115,7 @@ fn check_struct_shorthand_initialization(
                         label: "use struct shorthand initialization".to_string(),
                         edit,
                         cursor_position: None,
+                        placeholders: Vec::new(),
                     }),
                 });
             }
@@ -0,0 +1,144 @@
+use ra_syntax::{
+    AstNode, TextRange, TextUnit,
+    SyntaxKind::{R_CURLY, MATCH_ARM},
+    ast,
+};
+
+use crate::{LocalEdit, TextEditBuilder, formatting::leading_indent};
+
+/// Adds an arm with an empty body for each of the `patterns` at the end of
+/// `match_expr`. The patterns are not computed here, as this needs type
+/// information: the callers usually pass the missing variants of the matched
+/// enum. The bodies of the new arms are the placeholders of the edit.
+pub fn fill_match_arms(match_expr: &ast::MatchExpr, patterns: &[String]) -> Option<LocalEdit> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let arm_list = match_expr.match_arm_list()?;
+    let r_curly = arm_list.syntax().last_child().filter(|it| it.kind() == R_CURLY)?;
+    // The arms are added after the last arm, or after the `{`.
+    let anchor = arm_list
+        .syntax()
+        .children()
+        .filter(|it| !it.kind().is_trivia() && it != &r_curly)
+        .last()?;
+    let mut buf = String::new();
+    // The offsets of the `{}` bodies in `buf`.
+    let mut bodies = Vec::new();
+    if anchor.kind() == MATCH_ARM {
+        buf.push(',');
+    }
+    match leading_indent(r_curly) {
+        Some(indent) => {
+            let arm_indent = arm_list
+                .arms()
+                .last()
+                .and_then(|it| leading_indent(it.syntax()))
+                .map(|it| it.to_string())
+                .unwrap_or_else(|| format!("{}    ", indent));
+            for pattern in patterns {
+                buf.push_str(&format!("\n{}{} => ", arm_indent, pattern));
+                bodies.push(TextUnit::of_str(&buf));
+                buf.push_str("{},");
+            }
+        }
+        None => {
+            for pattern in patterns {
+                buf.push_str(&format!(" {} => ", pattern));
+                bodies.push(TextUnit::of_str(&buf));
+                buf.push_str("{},");
+            }
+            if !r_curly.prev_sibling().map_or(false, |it| it.kind().is_trivia()) {
+                buf.push(' ');
+            }
+        }
+    }
+    // The text before the insertion doesn't change, so the offsets in the
+    // edited text are the offsets in `buf` shifted by the insertion point.
+    let offset = anchor.range().end();
+    let placeholders = bodies
+        .into_iter()
+        .map(|it| TextRange::offset_len(offset + it, TextUnit::of_str("{}")))
+        .collect();
+    let mut edit = TextEditBuilder::default();
+    edit.insert(offset, buf);
+    Some(LocalEdit {
+        label: "fill match arms".to_string(),
+        edit: edit.finish(),
+        cursor_position: None,
+        placeholders,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::SourceFile;
+
+    use crate::test_utils::assert_eq_text;
+
+    use super::*;
+
+    fn check_fill_match_arms(before: &str, patterns: &[&str], after: &str) {
+        let file = SourceFile::parse(before);
+        let match_expr = file
+            .syntax()
+            .descendants()
+            .find_map(ast::MatchExpr::cast)
+            .unwrap();
+        let patterns = patterns.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+        let edit = fill_match_arms(match_expr, &patterns).unwrap();
+        let actual = edit.edit.apply(before);
+        assert_eq_text!(after, &actual);
+        // Every new arm has its body as a placeholder.
+        assert_eq!(edit.placeholders.len(), patterns.len());
+        for range in edit.placeholders {
+            assert_eq!(&actual[range.start().to_usize()..range.end().to_usize()], "{}");
+        }
+    }
+
+    #[test]
+    fn adds_arms_after_the_last_arm() {
+        check_fill_match_arms(
+            r"
+fn main() {
+    match x {
+        A::B => 92
+    }
+}
+",
+            &["A::C", "A::D(..)"],
+            r"
+fn main() {
+    match x {
+        A::B => 92,
+        A::C => {},
+        A::D(..) => {},
+    }
+}
+",
+        );
+    }
+
+    #[test]
+    fn adds_arms_to_an_empty_match() {
+        check_fill_match_arms(
+            r"
+fn main() {
+    match x {
+    }
+    match y {}
+}
+",
+            &["true", "false"],
+            r"
+fn main() {
+    match x {
+        true => {},
+        false => {},
+    }
+    match y {}
+}
+",
+        );
+    }
+}
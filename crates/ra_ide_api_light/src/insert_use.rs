@@ -0,0 +1,500 @@
+use std::{mem, ptr};
+
+use ra_syntax::{
+    AstNode, SourceFile, SyntaxNode, TextRange, TextUnit,
+    SyntaxKind::*,
+    algo::find_covering_node,
+    ast,
+};
+use ra_text_edit::{TextEdit, TextEditBuilder};
+
+/// How `insert_use` imports a path whose parent module is imported from
+/// already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportStyle {
+    /// Adds a separate item, `use std::fmt::Debug;` next to
+    /// `use std::fmt::Display;`.
+    Plain,
+    /// Merges the path into the existing import, which becomes
+    /// `use std::fmt::{Debug, Display};`.
+    Nested,
+}
+
+impl Default for ImportStyle {
+    fn default() -> ImportStyle {
+        ImportStyle::Plain
+    }
+}
+
+/// Imports the `paths` into the module which contains the `offset`. The paths
+/// which are imported already are skipped.
+///
+/// A new `use` item is added at the end of the closest group of adjacent `use`
+/// items: the group which shares the longest prefix with the path, or else
+/// the first group of the same kind of crate, like `std` or the current crate.
+/// Without `use` items, it is added in front of the first item, after the
+/// inner attributes and the doc comments of the module. With
+/// `ImportStyle::Nested`, a path is merged into an import of its parent module
+/// instead, if there is one.
+pub fn insert_use(
+    file: &SourceFile,
+    offset: TextUnit,
+    paths: &[String],
+    style: ImportStyle,
+) -> Option<TextEdit> {
+    let container = find_covering_node(file.syntax(), TextRange::offset_len(offset, 0.into()))
+        .ancestors()
+        .find(|it| {
+            it.kind() == SOURCE_FILE
+                || (it.kind() == ITEM_LIST && it.parent().map(|it| it.kind()) == Some(MODULE))
+        })?;
+    let use_items = container
+        .children()
+        .filter_map(ast::UseItem::cast)
+        .collect::<Vec<_>>();
+    let mut imported = Vec::new();
+    for use_item in use_items.iter() {
+        if let Some(tree) = use_item.use_tree() {
+            imported_paths("", tree, &mut imported);
+        }
+    }
+    let mut missing: Vec<String> = Vec::new();
+    for path in paths {
+        let path = normalize(path);
+        if !imported.contains(&path) && !missing.contains(&path) {
+            missing.push(path);
+        }
+    }
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut merges: Vec<(&ast::UseTree, Vec<String>)> = Vec::new();
+    let mut plain = Vec::new();
+    for path in missing {
+        let target = match style {
+            ImportStyle::Nested => merge_target(&use_items, &path),
+            ImportStyle::Plain => None,
+        };
+        match target {
+            Some((tree, name)) => match merges.iter_mut().find(|(it, _)| ptr::eq(*it, tree)) {
+                Some((_, names)) => names.push(name),
+                None => merges.push((tree, vec![name])),
+            },
+            None => plain.push(path),
+        }
+    }
+
+    let mut builder = TextEditBuilder::default();
+    for (tree, names) in merges {
+        merge_into(tree, names, &mut builder);
+    }
+    if !plain.is_empty() {
+        add_use_items(container, &plain, &mut builder);
+    }
+    Some(builder.finish())
+}
+
+fn normalize(text: &str) -> String {
+    text.replace(char::is_whitespace, "")
+}
+
+/// The paths imported by `tree`, without the renamed ones and the globs.
+fn imported_paths(prefix: &str, tree: &ast::UseTree, acc: &mut Vec<String>) {
+    let prefix = match tree.path().map(|it| normalize(&it.syntax().text().to_string())) {
+        Some(ref path) if path == "self" => prefix.to_string(),
+        Some(path) => join(prefix, &path),
+        None => prefix.to_string(),
+    };
+    match tree.use_tree_list() {
+        Some(list) => {
+            for child in list.use_trees() {
+                imported_paths(&prefix, child, acc);
+            }
+        }
+        None => {
+            if !is_renamed_or_glob(tree) {
+                acc.push(prefix);
+            }
+        }
+    }
+}
+
+fn is_renamed_or_glob(tree: &ast::UseTree) -> bool {
+    tree.syntax().children().any(|it| it.kind() == ALIAS || it.kind() == STAR)
+}
+
+fn join(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}::{}", prefix, path)
+    }
+}
+
+/// The import of the parent module of `path` which it can be merged into,
+/// like `use std::fmt::{Display};` or `use std::fmt::Display;` for
+/// `std::fmt::Debug`, together with the last segment of `path`.
+fn merge_target<'a>(
+    use_items: &[&'a ast::UseItem],
+    path: &str,
+) -> Option<(&'a ast::UseTree, String)> {
+    let idx = path.rfind("::")?;
+    let (parent, name) = (&path[..idx], &path[idx + 2..]);
+    let target = use_items.iter().filter_map(|it| it.use_tree()).find(|tree| {
+        let tree_path = match tree.path() {
+            Some(it) => normalize(&it.syntax().text().to_string()),
+            None => return false,
+        };
+        if tree.use_tree_list().is_some() {
+            return tree_path == parent;
+        }
+        !is_renamed_or_glob(tree)
+            && tree_path.len() > parent.len() + 2
+            && tree_path.starts_with(parent)
+            && tree_path[parent.len()..].starts_with("::")
+            && !tree_path[parent.len() + 2..].contains("::")
+    })?;
+    Some((target, name.to_string()))
+}
+
+fn merge_into(tree: &ast::UseTree, mut names: Vec<String>, builder: &mut TextEditBuilder) {
+    names.sort_by(|a, b| (a != "self", a).cmp(&(b != "self", b)));
+    let list = match tree.use_tree_list() {
+        Some(it) => it,
+        None => {
+            // `use foo::bar;` becomes `use foo::{bar, baz};`.
+            let path = normalize(&tree.syntax().text().to_string());
+            let idx = path.rfind("::").unwrap();
+            names.push(path[idx + 2..].to_string());
+            names.sort_by(|a, b| (a != "self", a).cmp(&(b != "self", b)));
+            let text = format!("{}::{{{}}}", &path[..idx], names.join(", "));
+            builder.replace(tree.syntax().range(), text);
+            return;
+        }
+    };
+    let existing = list.use_trees().collect::<Vec<_>>();
+    let last = match existing.last() {
+        Some(it) => it,
+        None => {
+            let l_curly = list.syntax().first_child().filter(|it| it.kind() == L_CURLY);
+            if let Some(l_curly) = l_curly {
+                builder.insert(l_curly.range().end(), names.join(", "));
+            }
+            return;
+        }
+    };
+    for name in names {
+        let before = existing.iter().find(|it| {
+            let text = normalize(&it.syntax().text().to_string());
+            text != "self" && (name == "self" || text > name)
+        });
+        match before {
+            Some(tree) => builder.insert(tree.syntax().range().start(), format!("{}, ", name)),
+            None => builder.insert(last.syntax().range().end(), format!(", {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CrateKind {
+    Std,
+    Local,
+    External,
+}
+
+fn crate_kind(path: &str) -> CrateKind {
+    let first = path.trim_start_matches("::").split("::").next().unwrap_or("");
+    match first {
+        "std" | "core" | "alloc" => CrateKind::Std,
+        "crate" | "self" | "super" => CrateKind::Local,
+        _ => CrateKind::External,
+    }
+}
+
+fn add_use_items(container: &SyntaxNode, paths: &[String], builder: &mut TextEditBuilder) {
+    let groups = use_groups(container);
+    if groups.is_empty() {
+        add_first_use_items(container, paths, builder);
+        return;
+    }
+    for path in paths {
+        let group = closest_group(&groups, path);
+        let last_use = group[group.len() - 1];
+        let text = format!("\n{}use {};", indent_of(last_use.syntax()), path);
+        builder.insert(last_use.syntax().range().end(), text);
+    }
+}
+
+/// Groups of `use` items which are separated by blank lines or other items.
+fn use_groups(container: &SyntaxNode) -> Vec<Vec<&ast::UseItem>> {
+    let mut res = Vec::new();
+    let mut group = Vec::new();
+    for child in container.children() {
+        match ast::UseItem::cast(child) {
+            Some(use_item) => group.push(use_item),
+            None if child.kind() == WHITESPACE && newlines(child) <= 1 => (),
+            None => {
+                if !group.is_empty() {
+                    res.push(mem::replace(&mut group, Vec::new()));
+                }
+            }
+        }
+    }
+    if !group.is_empty() {
+        res.push(group);
+    }
+    res
+}
+
+fn closest_group<'a, 'b>(
+    groups: &'a [Vec<&'b ast::UseItem>],
+    path: &str,
+) -> &'a [&'b ast::UseItem] {
+    let segments = path.split("::").collect::<Vec<_>>();
+    let common_prefix = |group: &[&ast::UseItem]| {
+        let mut imported = Vec::new();
+        for tree in group.iter().filter_map(|it| it.use_tree()) {
+            imported_paths("", tree, &mut imported);
+        }
+        imported
+            .iter()
+            .map(|it| {
+                it.split("::")
+                    .zip(segments.iter())
+                    .take_while(|(a, b)| a == *b)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
+    };
+    let mut best: Option<(&[&ast::UseItem], usize)> = None;
+    for group in groups {
+        let len = common_prefix(&group[..]);
+        if len > 0 && best.map_or(true, |(_, best_len)| len > best_len) {
+            best = Some((&group[..], len));
+        }
+    }
+    if let Some((group, _)) = best {
+        return group;
+    }
+    let kind = crate_kind(path);
+    let same_kind = groups.iter().find(|group| {
+        group[0]
+            .use_tree()
+            .and_then(|it| it.path())
+            .map_or(false, |it| crate_kind(&it.syntax().text().to_string()) == kind)
+    });
+    match same_kind {
+        Some(group) => &group[..],
+        None => &groups[groups.len() - 1][..],
+    }
+}
+
+/// Adds the first `use` items of the module in front of its first item. The
+/// inner attributes and doc comments at the start of the module stay at the
+/// start, and so do the comments which are separated from the first item by
+/// a blank line, like license headers.
+fn add_first_use_items(
+    container: &SyntaxNode,
+    paths: &[String],
+    builder: &mut TextEditBuilder,
+) {
+    let first_item = container.children().find(|it| match it.kind() {
+        L_CURLY | WHITESPACE | ATTR => false,
+        COMMENT => {
+            let text = it.leaf_text().map_or("", |it| it.as_str());
+            let is_header = it
+                .next_sibling()
+                .map_or(true, |next| next.kind() == WHITESPACE && newlines(next) > 1);
+            !(text.starts_with("//!") || text.starts_with("/*!") || is_header)
+        }
+        _ => true,
+    });
+    let first_item = match first_item {
+        Some(it) => it,
+        None => {
+            // Only comments and attributes, add the items after them.
+            let text = paths.iter().map(|it| format!("use {};\n", it)).collect::<String>();
+            let last = container
+                .children()
+                .filter(|it| it.kind() != WHITESPACE && it.kind() != R_CURLY)
+                .last();
+            match last {
+                Some(last) if last.kind() != L_CURLY => {
+                    builder.insert(last.range().end(), format!("\n\n{}", text.trim_end()))
+                }
+                Some(l_curly) => {
+                    builder.insert(l_curly.range().end(), format!(" {}", text.trim_end()))
+                }
+                None => builder.insert(container.range().start(), text),
+            }
+            return;
+        }
+    };
+    let indent = indent_of(first_item);
+    let text = paths.iter().map(|it| format!("use {};\n{}", it, indent)).collect::<String>();
+    builder.insert(first_item.range().start(), format!("{}\n{}", text, indent));
+}
+
+fn newlines(ws: &SyntaxNode) -> usize {
+    ws.leaf_text().map_or(0, |it| it.matches('\n').count())
+}
+
+fn indent_of(node: &SyntaxNode) -> &str {
+    let ws = match node.prev_sibling().filter(|it| it.kind() == WHITESPACE) {
+        Some(it) => it.leaf_text().unwrap().as_str(),
+        None => return "",
+    };
+    match ws.rfind('\n') {
+        Some(idx) => &ws[idx + 1..],
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::assert_eq_text;
+
+    use super::*;
+
+    fn check_insert_use(style: ImportStyle, paths: &[&str], before: &str, after: &str) {
+        let file = SourceFile::parse(before);
+        let paths = paths.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+        let offset = TextUnit::of_str(before);
+        let actual = match insert_use(&file, offset, &paths, style) {
+            Some(edit) => edit.apply(before),
+            None => before.to_string(),
+        };
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn adds_use_items_to_the_closest_group() {
+        check_insert_use(
+            ImportStyle::Plain,
+            &["std::fmt::Debug", "crate::db::Database", "itertools::Itertools"],
+            "
+use std::fmt::Display;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use crate::syntax::Node;
+
+fn main() {}
+",
+            "
+use std::fmt::Display;
+use std::sync::Arc;
+use std::fmt::Debug;
+
+use rustc_hash::FxHashMap;
+use itertools::Itertools;
+
+use crate::syntax::Node;
+use crate::db::Database;
+
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn skips_imported_paths() {
+        check_insert_use(
+            ImportStyle::Plain,
+            &["std::fmt::Debug", "std::sync::Arc"],
+            "
+use std::{fmt::{Debug, Display}, sync::Arc};
+",
+            "
+use std::{fmt::{Debug, Display}, sync::Arc};
+",
+        );
+    }
+
+    #[test]
+    fn merges_into_nested_imports() {
+        check_insert_use(
+            ImportStyle::Nested,
+            &["std::fmt::Debug", "std::sync::Arc", "std::io"],
+            "
+use std::fmt::{Display, Write};
+use std::sync::Arc;
+use std::fs;
+
+fn main() {}
+",
+            "
+use std::fmt::{Debug, Display, Write};
+use std::sync::Arc;
+use std::{fs, io};
+
+fn main() {}
+",
+        );
+        check_insert_use(
+            ImportStyle::Nested,
+            &["std::sync::Mutex"],
+            "
+use std::sync::Arc;
+",
+            "
+use std::sync::{Arc, Mutex};
+",
+        );
+    }
+
+    #[test]
+    fn adds_the_first_use_items_after_inner_attributes() {
+        check_insert_use(
+            ImportStyle::Plain,
+            &["std::sync::Arc"],
+            "//! Docs of the crate.
+#![allow(dead_code)]
+
+// Copyright notice.
+
+/// Docs of `main`.
+fn main() {}
+",
+            "//! Docs of the crate.
+#![allow(dead_code)]
+
+// Copyright notice.
+
+use std::sync::Arc;
+
+/// Docs of `main`.
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn adds_use_items_to_inline_modules() {
+        let before = "
+mod foo {
+    use std::fmt;
+
+    fn bar() {}
+}
+";
+        let file = SourceFile::parse(before);
+        let offset = TextUnit::of_str(before) - TextUnit::of_str("{}\n}\n");
+        let paths = vec!["std::sync::Arc".to_string()];
+        let edit = insert_use(&file, offset, &paths, ImportStyle::Plain).unwrap();
+        assert_eq_text!(
+            "
+mod foo {
+    use std::fmt;
+    use std::sync::Arc;
+
+    fn bar() {}
+}
+",
+            &edit.apply(before)
+        );
+    }
+}
@@ -21,6 +21,7 @@ pub fn join_lines(file: &SourceFile, range: TextRange) -> LocalEdit {
                     label: "join lines".to_string(),
                     edit: TextEditBuilder::default().finish(),
                     cursor_position: None,
+                    placeholders: Vec::new(),
                 };
             }
             Some(pos) => pos,
@@ -54,6 +55,7 @@ pub fn join_lines(file: &SourceFile, range: TextRange) -> LocalEdit {
         label: "join lines".to_string(),
         edit: edit.finish(),
         cursor_position: None,
+        placeholders: Vec::new(),
     }
 }
 
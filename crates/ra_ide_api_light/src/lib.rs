@@ -8,10 +8,15 @@ mod extend_selection;
 mod folding_ranges;
 mod line_index;
 mod line_index_utils;
+mod position_kind;
 mod structure;
 #[cfg(test)]
 mod test_utils;
 mod join_lines;
+mod organize_imports;
+mod insert_use;
+mod fill_match_arms;
+mod change_return_type;
 mod typing;
 mod diagnostics;
 pub(crate) mod formatting;
@@ -20,11 +25,16 @@ pub use self::{
     assists::LocalEdit,
     extend_selection::extend_selection,
     folding_ranges::{folding_ranges, Fold, FoldKind},
-    line_index::{LineCol, LineIndex},
+    line_index::{LineCol, LineColUtf8, LineIndex},
     line_index_utils::translate_offset_with_edit,
+    position_kind::{classify_position, PositionKind},
     structure::{file_structure, StructureNode},
     diagnostics::diagnostics,
     join_lines::join_lines,
+    organize_imports::organize_imports,
+    insert_use::{insert_use, ImportStyle},
+    fill_match_arms::fill_match_arms,
+    change_return_type::{change_return_type_to_option, change_return_type_to_result},
     typing::{on_enter, on_dot_typed, on_eq_typed},
 
 };
@@ -58,6 +68,9 @@ pub struct Diagnostic {
 }
 
 pub fn matching_brace(file: &SourceFile, offset: TextUnit) -> Option<TextUnit> {
+    if classify_position(file, offset) != PositionKind::Code {
+        return None;
+    }
     const BRACES: &[SyntaxKind] = &[
         L_CURLY, R_CURLY, L_BRACK, R_BRACK, L_PAREN, R_PAREN, L_ANGLE, R_ANGLE,
     ];
@@ -172,6 +185,15 @@ fn main() {}
         }
 
         do_check("struct Foo { a: i32, }<|>", "struct Foo <|>{ a: i32, }");
+        do_check("fn main() { foo(1<|>) }", "fn main() { foo<|>(1) }");
+    }
+
+    #[test]
+    fn test_assists_right_after_a_number() {
+        let (offset, text) = extract_offset("fn main() { (1<|>, 2); }");
+        let file = SourceFile::parse(&text);
+        let assists = assists::assists(&file, TextRange::offset_len(offset, 0.into()));
+        assert!(assists.iter().any(|it| it.label == "flip comma"));
     }
 
 }
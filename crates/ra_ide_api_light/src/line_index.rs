@@ -1,19 +1,35 @@
+use std::sync::{Arc, Mutex};
+
 use crate::TextUnit;
 use rustc_hash::FxHashMap;
 use superslice::Ext;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct LineIndex {
     pub(crate) newlines: Vec<TextUnit>,
-    pub(crate) utf16_lines: FxHashMap<u32, Vec<Utf16Char>>,
+    /// The text is kept to compute `utf16_lines` on first use: clients which
+    /// use UTF-8 positions never need them.
+    text: Arc<String>,
+    utf16_lines: Mutex<Option<Arc<Utf16Lines>>>,
 }
 
+/// The characters which take more than one UTF-16 code unit, by line.
+pub(crate) type Utf16Lines = FxHashMap<u32, Vec<Utf16Char>>;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct LineCol {
     pub line: u32,
     pub col_utf16: u32,
 }
 
+/// Like `LineCol`, but the column is in bytes. This is what clients which
+/// opt into the UTF-8 position encoding use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LineColUtf8 {
+    pub line: u32,
+    pub col_utf8: u32,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub(crate) struct Utf16Char {
     pub(crate) start: TextUnit,
@@ -26,54 +42,51 @@ impl Utf16Char {
     }
 }
 
-impl LineIndex {
-    pub fn new(text: &str) -> LineIndex {
-        let mut utf16_lines = FxHashMap::default();
-        let mut utf16_chars = Vec::new();
+impl Clone for LineIndex {
+    fn clone(&self) -> LineIndex {
+        LineIndex {
+            newlines: self.newlines.clone(),
+            text: Arc::clone(&self.text),
+            utf16_lines: Mutex::new(self.utf16_lines.lock().unwrap().clone()),
+        }
+    }
+}
 
-        let mut newlines = vec![0.into()];
-        let mut curr_row = 0.into();
-        let mut curr_col = 0.into();
-        let mut line = 0;
-        for c in text.chars() {
-            curr_row += TextUnit::of_char(c);
-            if c == '\n' {
-                newlines.push(curr_row);
+impl PartialEq for LineIndex {
+    fn eq(&self, other: &LineIndex) -> bool {
+        // Everything else is computed from the text.
+        self.text == other.text
+    }
+}
 
-                // Save any utf-16 characters seen in the previous line
-                if utf16_chars.len() > 0 {
-                    utf16_lines.insert(line, utf16_chars);
-                    utf16_chars = Vec::new();
-                }
+impl Eq for LineIndex {}
 
-                // Prepare for processing the next line
-                curr_col = 0.into();
-                line += 1;
-                continue;
-            }
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        LineIndex::from_shared_text(Arc::new(text.to_string()))
+    }
 
-            let char_len = TextUnit::of_char(c);
-            if char_len.to_usize() > 1 {
-                utf16_chars.push(Utf16Char {
-                    start: curr_col,
-                    end: curr_col + char_len,
-                });
+    /// Like `new`, but shares the text instead of copying it.
+    pub fn from_shared_text(text: Arc<String>) -> LineIndex {
+        let mut newlines = vec![0.into()];
+        for (i, b) in text.bytes().enumerate() {
+            if b == b'\n' {
+                newlines.push(TextUnit::from_usize(i + 1));
             }
-
-            curr_col += char_len;
-        }
-
-        // Save any utf-16 characters seen in the last line
-        if utf16_chars.len() > 0 {
-            utf16_lines.insert(line, utf16_chars);
         }
-
         LineIndex {
             newlines,
-            utf16_lines,
+            text,
+            utf16_lines: Mutex::new(None),
         }
     }
 
+    pub(crate) fn utf16_lines(&self) -> Arc<Utf16Lines> {
+        let text = &self.text;
+        let mut utf16_lines = self.utf16_lines.lock().unwrap();
+        Arc::clone(utf16_lines.get_or_insert_with(|| Arc::new(compute_utf16_lines(text))))
+    }
+
     pub fn line_col(&self, offset: TextUnit) -> LineCol {
         let line = self.newlines.upper_bound(&offset) - 1;
         let line_start_offset = self.newlines[line];
@@ -91,8 +104,22 @@ impl LineIndex {
         self.newlines[line_col.line as usize] + col
     }
 
+    /// Unlike `line_col`, doesn't need to look at the UTF-16 characters.
+    pub fn line_col_utf8(&self, offset: TextUnit) -> LineColUtf8 {
+        let line = self.newlines.upper_bound(&offset) - 1;
+        let col = offset - self.newlines[line];
+        LineColUtf8 {
+            line: line as u32,
+            col_utf8: col.into(),
+        }
+    }
+
+    pub fn offset_utf8(&self, line_col: LineColUtf8) -> TextUnit {
+        self.newlines[line_col.line as usize] + TextUnit::from(line_col.col_utf8)
+    }
+
     fn utf8_to_utf16_col(&self, line: u32, mut col: TextUnit) -> usize {
-        if let Some(utf16_chars) = self.utf16_lines.get(&line) {
+        if let Some(utf16_chars) = self.utf16_lines().get(&line) {
             let mut correction = TextUnit::from_usize(0);
             for c in utf16_chars {
                 if col >= c.end {
@@ -112,7 +139,7 @@ impl LineIndex {
 
     fn utf16_to_utf8_col(&self, line: u32, col: u32) -> TextUnit {
         let mut col: TextUnit = col.into();
-        if let Some(utf16_chars) = self.utf16_lines.get(&line) {
+        if let Some(utf16_chars) = self.utf16_lines().get(&line) {
             for c in utf16_chars {
                 if col >= c.start {
                     col += c.len() - TextUnit::from_usize(1);
@@ -128,6 +155,45 @@ impl LineIndex {
     }
 }
 
+fn compute_utf16_lines(text: &str) -> Utf16Lines {
+    let mut utf16_lines = FxHashMap::default();
+    let mut utf16_chars = Vec::new();
+
+    let mut curr_col = 0.into();
+    let mut line = 0;
+    for c in text.chars() {
+        if c == '\n' {
+            // Save any utf-16 characters seen in the previous line
+            if utf16_chars.len() > 0 {
+                utf16_lines.insert(line, utf16_chars);
+                utf16_chars = Vec::new();
+            }
+
+            // Prepare for processing the next line
+            curr_col = 0.into();
+            line += 1;
+            continue;
+        }
+
+        let char_len = TextUnit::of_char(c);
+        if char_len.to_usize() > 1 {
+            utf16_chars.push(Utf16Char {
+                start: curr_col,
+                end: curr_col + char_len,
+            });
+        }
+
+        curr_col += char_len;
+    }
+
+    // Save any utf-16 characters seen in the last line
+    if utf16_chars.len() > 0 {
+        utf16_lines.insert(line, utf16_chars);
+    }
+
+    utf16_lines
+}
+
 #[cfg(test)]
 /// Simple reference implementation to use in proptests
 pub fn to_line_col(text: &str, offset: TextUnit) -> LineCol {
@@ -320,7 +386,7 @@ mod test_utf8_utf16_conv {
 const C: char = 'x';
 ",
         );
-        assert_eq!(col_index.utf16_lines.len(), 0);
+        assert_eq!(col_index.utf16_lines().len(), 0);
     }
 
     #[test]
@@ -331,10 +397,10 @@ const C: char = 'メ';
 ",
         );
 
-        assert_eq!(col_index.utf16_lines.len(), 1);
-        assert_eq!(col_index.utf16_lines[&1].len(), 1);
+        assert_eq!(col_index.utf16_lines().len(), 1);
+        assert_eq!(col_index.utf16_lines()[&1].len(), 1);
         assert_eq!(
-            col_index.utf16_lines[&1][0],
+            col_index.utf16_lines()[&1][0],
             Utf16Char {
                 start: 17.into(),
                 end: 20.into()
@@ -362,17 +428,17 @@ const C: char = \"メ メ\";
 ",
         );
 
-        assert_eq!(col_index.utf16_lines.len(), 1);
-        assert_eq!(col_index.utf16_lines[&1].len(), 2);
+        assert_eq!(col_index.utf16_lines().len(), 1);
+        assert_eq!(col_index.utf16_lines()[&1].len(), 2);
         assert_eq!(
-            col_index.utf16_lines[&1][0],
+            col_index.utf16_lines()[&1][0],
             Utf16Char {
                 start: 17.into(),
                 end: 20.into()
             }
         );
         assert_eq!(
-            col_index.utf16_lines[&1][1],
+            col_index.utf16_lines()[&1][1],
             Utf16Char {
                 start: 21.into(),
                 end: 24.into()
@@ -396,4 +462,29 @@ const C: char = \"メ メ\";
         assert_eq!(col_index.utf16_to_utf8_col(2, 15), TextUnit::from_usize(15));
     }
 
+    #[test]
+    fn test_utf8_line_col() {
+        let col_index = LineIndex::new("\nconst C: &str = \"𝕊\";\n");
+        let line_col = LineColUtf8 {
+            line: 1,
+            col_utf8: 22,
+        };
+        assert_eq!(col_index.line_col_utf8(23.into()), line_col);
+        assert_eq!(col_index.offset_utf8(line_col), TextUnit::from(23));
+    }
+
+    #[test]
+    fn test_utf8_positions_dont_compute_utf16_lines() {
+        let col_index = LineIndex::new("\nconst C: &str = \"𝕊\";\n");
+        col_index.line_col_utf8(23.into());
+        col_index.offset_utf8(LineColUtf8 {
+            line: 1,
+            col_utf8: 22,
+        });
+        assert!(col_index.utf16_lines.lock().unwrap().is_none());
+
+        col_index.line_col(23.into());
+        assert!(col_index.utf16_lines.lock().unwrap().is_some());
+    }
+
 }
@@ -1,6 +1,8 @@
+use std::sync::Arc;
+
 use ra_text_edit::{AtomTextEdit, TextEdit};
 use ra_syntax::{TextUnit, TextRange};
-use crate::{LineIndex, LineCol, line_index::Utf16Char};
+use crate::{LineIndex, LineCol, line_index::{Utf16Char, Utf16Lines}};
 
 #[derive(Debug, Clone)]
 enum Step {
@@ -11,14 +13,16 @@ enum Step {
 #[derive(Debug)]
 struct LineIndexStepIter<'a> {
     line_index: &'a LineIndex,
+    utf16_lines: Arc<Utf16Lines>,
     next_newline_idx: usize,
-    utf16_chars: Option<(TextUnit, std::slice::Iter<'a, Utf16Char>)>,
+    utf16_chars: Option<(TextUnit, std::vec::IntoIter<Utf16Char>)>,
 }
 
 impl<'a> LineIndexStepIter<'a> {
     fn from(line_index: &LineIndex) -> LineIndexStepIter {
         let mut x = LineIndexStepIter {
             line_index,
+            utf16_lines: line_index.utf16_lines(),
             next_newline_idx: 0,
             utf16_chars: None,
         };
@@ -43,10 +47,9 @@ impl<'a> Iterator for LineIndexStepIter<'a> {
             .or_else(|| {
                 let next_newline = *self.line_index.newlines.get(self.next_newline_idx)?;
                 self.utf16_chars = self
-                    .line_index
                     .utf16_lines
                     .get(&(self.next_newline_idx as u32))
-                    .map(|x| (next_newline, x.iter()));
+                    .map(|x| (next_newline, x.clone().into_iter()));
                 self.next_newline_idx += 1;
                 Some(Step::Newline(next_newline))
             })
@@ -0,0 +1,152 @@
+use std::mem;
+
+use ra_syntax::{
+    SourceFile, SyntaxNode, TextRange, AstNode,
+    SyntaxKind::{SOURCE_FILE, ITEM_LIST, USE_ITEM, WHITESPACE, COMMENT},
+};
+
+use crate::{LocalEdit, TextEditBuilder};
+
+/// Sorts the groups of adjacent `use` items of every module and removes the
+/// duplicate items. Groups are separated by blank lines, comments or other
+/// items, and are sorted independently, as the grouping is usually
+/// intentional.
+pub fn organize_imports(file: &SourceFile) -> Option<LocalEdit> {
+    let mut edit = TextEditBuilder::default();
+    let mut changed = false;
+    let containers = file
+        .syntax()
+        .descendants()
+        .filter(|it| it.kind() == SOURCE_FILE || it.kind() == ITEM_LIST);
+    for container in containers {
+        for group in use_groups(container) {
+            let texts = group.iter().map(|it| it.text().to_string()).collect::<Vec<_>>();
+            let mut sorted = texts.clone();
+            sorted.sort();
+            sorted.dedup();
+            if sorted == texts {
+                continue;
+            }
+            // Keep the whitespace which separates the first two items, so
+            // that the indentation is preserved.
+            let separator = group[0]
+                .next_sibling()
+                .filter(|it| it.kind() == WHITESPACE)
+                .map(|it| it.text().to_string())
+                .unwrap_or_else(|| "\n".to_string());
+            let range = TextRange::from_to(
+                group[0].range().start(),
+                group[group.len() - 1].range().end(),
+            );
+            let old_text = file.syntax().text().slice(range).to_string();
+            edit.replace_with_diff(range, &old_text, &sorted.join(&separator));
+            changed = true;
+        }
+    }
+    if !changed {
+        return None;
+    }
+    Some(LocalEdit {
+        label: "organize imports".to_string(),
+        edit: edit.finish(),
+        cursor_position: None,
+        placeholders: Vec::new(),
+    })
+}
+
+fn use_groups(container: &SyntaxNode) -> Vec<Vec<&SyntaxNode>> {
+    let mut res = Vec::new();
+    let mut group = Vec::new();
+    for child in container.children() {
+        match child.kind() {
+            // Comments are attached to the following item, and moving them
+            // around would be confusing.
+            USE_ITEM if !child.children().any(|it| it.kind() == COMMENT) => group.push(child),
+            WHITESPACE if child.text().to_string().matches('\n').count() <= 1 => (),
+            _ => {
+                if !group.is_empty() {
+                    res.push(mem::replace(&mut group, Vec::new()));
+                }
+            }
+        }
+    }
+    if !group.is_empty() {
+        res.push(group);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::assert_eq_text;
+
+    use super::*;
+
+    fn check_organize_imports(before: &str, after: &str) {
+        let file = SourceFile::parse(before);
+        let actual = match organize_imports(&file) {
+            Some(edit) => edit.edit.apply(before),
+            None => before.to_string(),
+        };
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn sorts_and_dedups_use_items() {
+        check_organize_imports(
+            r"
+use std::sync::Arc;
+use crate::foo;
+use std::sync::Arc;
+
+use b;
+use a;
+
+fn main() {}
+",
+            r"
+use crate::foo;
+use std::sync::Arc;
+
+use a;
+use b;
+
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn sorts_use_items_in_inline_modules() {
+        check_organize_imports(
+            r"
+mod foo {
+    use super::b;
+    use super::a;
+}
+",
+            r"
+mod foo {
+    use super::a;
+    use super::b;
+}
+",
+        );
+    }
+
+    #[test]
+    fn keeps_sorted_use_items() {
+        check_organize_imports(
+            r"
+use a;
+// comment
+use b;
+",
+            r"
+use a;
+// comment
+use b;
+",
+        );
+    }
+}
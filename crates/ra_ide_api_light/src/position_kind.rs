@@ -0,0 +1,96 @@
+use ra_syntax::{
+    AstNode, SourceFile, TextUnit,
+    SyntaxKind::*,
+    algo::find_leaf_at_offset,
+    ast::{self, AstToken},
+};
+
+/// What the cursor is in, syntactically. Most features are about code and
+/// should keep quiet in comments and literals, while some, like continuing
+/// doc comments on enter, are only about the comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionKind {
+    Code,
+    Comment,
+    DocComment,
+    /// A string, char or number literal.
+    Literal,
+    /// A string literal in an attribute, like in `#[path = "foo.rs"]`.
+    AttrLiteral,
+}
+
+pub fn classify_position(file: &SourceFile, offset: TextUnit) -> PositionKind {
+    let leaf = match find_leaf_at_offset(file.syntax(), offset).left_biased() {
+        Some(it) => it,
+        None => return PositionKind::Code,
+    };
+    let range = leaf.range();
+    let is_strictly_inside = range.start() < offset && offset < range.end();
+    match leaf.kind() {
+        COMMENT => {
+            let comment = ast::Comment::cast(leaf).unwrap();
+            // A line comment extends up to the end of the line, and an
+            // unterminated block comment up to the end of the file.
+            let text = comment.text();
+            let is_open = comment.flavor() != ast::CommentFlavor::Multiline
+                || !(text.len() >= 4 && text.ends_with("*/"));
+            if !(is_strictly_inside || (is_open && offset == range.end())) {
+                PositionKind::Code
+            } else if comment.is_doc_comment() {
+                PositionKind::DocComment
+            } else {
+                PositionKind::Comment
+            }
+        }
+        // A number followed by a dot, like `1.`, is a float literal rather
+        // than a field access, so right after it is in the literal as well.
+        INT_NUMBER | FLOAT_NUMBER
+            if is_strictly_inside
+                || (offset == range.end()
+                    && leaf.leaf_text().map_or(false, |it| it.ends_with('.'))) =>
+        {
+            PositionKind::Literal
+        }
+        STRING | RAW_STRING | BYTE_STRING | RAW_BYTE_STRING | CHAR | BYTE
+            if is_strictly_inside =>
+        {
+            if leaf.ancestors().any(|it| it.kind() == ATTR) {
+                PositionKind::AttrLiteral
+            } else {
+                PositionKind::Literal
+            }
+        }
+        _ => PositionKind::Code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::extract_offset;
+
+    use super::*;
+
+    fn check(code: &str, expected: PositionKind) {
+        let (offset, code) = extract_offset(code);
+        let file = SourceFile::parse(&code);
+        assert_eq!(classify_position(&file, offset), expected);
+    }
+
+    #[test]
+    fn test_classify_position() {
+        check("fn foo() { <|> }", PositionKind::Code);
+        check("fn foo() {}<|> // comment", PositionKind::Code);
+        check("fn foo() {} // comm<|>ent", PositionKind::Comment);
+        check("fn foo() {} // comment<|>", PositionKind::Comment);
+        check("fn foo() {} /* comment */<|>", PositionKind::Code);
+        check("fn foo() {} /* comment<|>", PositionKind::Comment);
+        check("/// doc <|>\nfn foo() {}", PositionKind::DocComment);
+        check(r#"fn foo() { "str<|>ing" }"#, PositionKind::Literal);
+        check(r#"fn foo() { "string"<|> }"#, PositionKind::Code);
+        check("fn foo() { 1.<|> }", PositionKind::Literal);
+        check("fn foo() { 12<|>3 }", PositionKind::Literal);
+        check("fn foo() { foo(1<|>) }", PositionKind::Code);
+        check("fn foo() { (1<|>, 2) }", PositionKind::Code);
+        check(r#"#[path = "foo<|>.rs"] mod foo;"#, PositionKind::AttrLiteral);
+    }
+}
@@ -2,7 +2,7 @@ use crate::TextRange;
 
 use ra_syntax::{
     algo::visit::{visitor, Visitor},
-    ast::{self, NameOwner},
+    ast::{self, NameOwner, AttrsOwner},
     AstNode, SourceFile, SyntaxKind, SyntaxNode, WalkEvent,
 };
 
@@ -13,6 +13,7 @@ pub struct StructureNode {
     pub navigation_range: TextRange,
     pub node_range: TextRange,
     pub kind: SyntaxKind,
+    pub deprecated: bool,
 }
 
 pub fn file_structure(file: &SourceFile) -> Vec<StructureNode> {
@@ -39,7 +40,7 @@ pub fn file_structure(file: &SourceFile) -> Vec<StructureNode> {
 }
 
 fn structure_node(node: &SyntaxNode) -> Option<StructureNode> {
-    fn decl<N: NameOwner>(node: &N) -> Option<StructureNode> {
+    fn decl<N: NameOwner + AttrsOwner>(node: &N) -> Option<StructureNode> {
         let name = node.name()?;
         Some(StructureNode {
             parent: None,
@@ -47,6 +48,7 @@ fn structure_node(node: &SyntaxNode) -> Option<StructureNode> {
             navigation_range: name.syntax().range(),
             node_range: node.syntax().range(),
             kind: node.syntax().kind(),
+            deprecated: node.has_attr("deprecated"),
         })
     }
 
@@ -78,6 +80,7 @@ fn structure_node(node: &SyntaxNode) -> Option<StructureNode> {
                 navigation_range: target_type.syntax().range(),
                 node_range: im.syntax().range(),
                 kind: im.syntax().kind(),
+                deprecated: false,
             };
             Some(node)
         })
@@ -109,20 +112,36 @@ const C: i32 = 92;
 impl E {}
 
 impl fmt::Debug for E {}
+
+#[deprecated]
+fn obsolete() {}
 "#,
         );
         let structure = file_structure(&file);
         assert_eq_dbg(
-            r#"[StructureNode { parent: None, label: "Foo", navigation_range: [8; 11), node_range: [1; 26), kind: STRUCT_DEF },
-                StructureNode { parent: Some(0), label: "x", navigation_range: [18; 19), node_range: [18; 24), kind: NAMED_FIELD_DEF },
-                StructureNode { parent: None, label: "m", navigation_range: [32; 33), node_range: [28; 53), kind: MODULE },
-                StructureNode { parent: Some(2), label: "bar", navigation_range: [43; 46), node_range: [40; 51), kind: FN_DEF },
-                StructureNode { parent: None, label: "E", navigation_range: [60; 61), node_range: [55; 75), kind: ENUM_DEF },
-                StructureNode { parent: None, label: "T", navigation_range: [81; 82), node_range: [76; 88), kind: TYPE_DEF },
-                StructureNode { parent: None, label: "S", navigation_range: [96; 97), node_range: [89; 108), kind: STATIC_DEF },
-                StructureNode { parent: None, label: "C", navigation_range: [115; 116), node_range: [109; 127), kind: CONST_DEF },
-                StructureNode { parent: None, label: "impl E", navigation_range: [134; 135), node_range: [129; 138), kind: IMPL_BLOCK },
-                StructureNode { parent: None, label: "impl fmt::Debug for E", navigation_range: [160; 161), node_range: [140; 164), kind: IMPL_BLOCK }]"#,
+            r#"[StructureNode { parent: None, label: "Foo", navigation_range: [8; 11), node_range: [1; 26), kind: STRUCT_DEF, deprecated: false },
+                StructureNode { parent: Some(0), label: "x", navigation_range: [18; 19), node_range: [18; 24), kind: NAMED_FIELD_DEF, deprecated: false },
+                StructureNode { parent: None, label: "m", navigation_range: [32; 33), node_range: [28; 53), kind: MODULE, deprecated: false },
+                StructureNode { parent: Some(2), label: "bar", navigation_range: [43; 46), node_range: [40; 51), kind: FN_DEF, deprecated: false },
+                StructureNode { parent: None, label: "E", navigation_range: [60; 61), node_range: [55; 75), kind: ENUM_DEF, deprecated: false },
+                StructureNode { parent: None, label: "T", navigation_range: [81; 82), node_range: [76; 88), kind: TYPE_DEF, deprecated: false },
+                StructureNode { parent: None, label: "S", navigation_range: [96; 97), node_range: [89; 108), kind: STATIC_DEF, deprecated: false },
+                StructureNode { parent: None, label: "C", navigation_range: [115; 116), node_range: [109; 127), kind: CONST_DEF, deprecated: false },
+                StructureNode { parent: None, label: "impl E", navigation_range: [134; 135), node_range: [129; 138), kind: IMPL_BLOCK, deprecated: false },
+                StructureNode { parent: None, label: "impl fmt::Debug for E", navigation_range: [160; 161), node_range: [140; 164), kind: IMPL_BLOCK, deprecated: false },
+                StructureNode { parent: None, label: "obsolete", navigation_range: [183; 191), node_range: [166; 196), kind: FN_DEF, deprecated: true }]"#,
+            &structure,
+        )
+    }
+
+    #[test]
+    fn test_file_structure_nests_impl_items() {
+        let file = SourceFile::parse("impl S {\n    fn foo() {}\n    const C: u32 = 0;\n}\n");
+        let structure = file_structure(&file);
+        assert_eq_dbg(
+            r#"[StructureNode { parent: None, label: "impl S", navigation_range: [5; 6), node_range: [0; 48), kind: IMPL_BLOCK, deprecated: false },
+                StructureNode { parent: Some(0), label: "foo", navigation_range: [16; 19), node_range: [13; 24), kind: FN_DEF, deprecated: false },
+                StructureNode { parent: Some(0), label: "C", navigation_range: [35; 36), node_range: [29; 46), kind: CONST_DEF, deprecated: false }]"#,
             &structure,
         )
     }
@@ -5,34 +5,87 @@ use ra_syntax::{
     ast::{self, AstToken},
 };
 
-use crate::{LocalEdit, TextEditBuilder, formatting::leading_indent};
+use crate::{
+    LocalEdit, TextEditBuilder, PositionKind, classify_position,
+    formatting::leading_indent,
+};
 
+/// Continues the comment the cursor is in on the next line, or, inside an
+/// empty block, puts the closing brace on its own line.
 pub fn on_enter(file: &SourceFile, offset: TextUnit) -> Option<LocalEdit> {
+    match classify_position(file, offset) {
+        PositionKind::Comment | PositionKind::DocComment => on_enter_in_comment(file, offset),
+        PositionKind::Code => on_enter_in_empty_block(file, offset),
+        _ => None,
+    }
+}
+
+fn on_enter_in_comment(file: &SourceFile, offset: TextUnit) -> Option<LocalEdit> {
     let comment = find_leaf_at_offset(file.syntax(), offset)
         .left_biased()
         .and_then(ast::Comment::cast)?;
 
-    if let ast::CommentFlavor::Multiline = comment.flavor() {
-        return None;
-    }
+    let indent = node_indent(file, comment.syntax())?;
+    let (inserted, after_cursor) = if let ast::CommentFlavor::Multiline = comment.flavor() {
+        let text = comment.text();
+        let is_terminated = text.len() >= 4 && text.ends_with("*/");
+        let after_cursor = if is_terminated {
+            String::new()
+        } else {
+            format!("\n{} */", indent)
+        };
+        (format!("\n{} * ", indent), after_cursor)
+    } else {
+        let prefix = comment.prefix();
+        if offset < comment.syntax().range().start() + TextUnit::of_str(prefix) + TextUnit::from(1)
+        {
+            return None;
+        }
+        (format!("\n{}{} ", indent, prefix), String::new())
+    };
+    let cursor_position = offset + TextUnit::of_str(&inserted);
+    let mut edit = TextEditBuilder::default();
+    edit.insert(offset, inserted + &after_cursor);
+    Some(LocalEdit {
+        label: "on enter".to_string(),
+        edit: edit.finish(),
+        cursor_position: Some(cursor_position),
+        placeholders: Vec::new(),
+    })
+}
 
-    let prefix = comment.prefix();
-    if offset < comment.syntax().range().start() + TextUnit::of_str(prefix) + TextUnit::from(1) {
+/// `{<|>}` becomes a block with an indented empty line for the cursor.
+fn on_enter_in_empty_block(file: &SourceFile, offset: TextUnit) -> Option<LocalEdit> {
+    let l_curly = find_leaf_at_offset(file.syntax(), offset)
+        .left_biased()
+        .filter(|it| it.kind() == L_CURLY)?;
+    let r_curly = l_curly.next_sibling()?;
+    if r_curly.kind() != R_CURLY || r_curly.range().start() != offset {
         return None;
     }
-
-    let indent = node_indent(file, comment.syntax())?;
-    let inserted = format!("\n{}{} ", indent, prefix);
+    let indent = line_indent(file, offset);
+    let inserted = format!("\n{}    ", indent);
     let cursor_position = offset + TextUnit::of_str(&inserted);
     let mut edit = TextEditBuilder::default();
-    edit.insert(offset, inserted);
+    edit.insert(offset, format!("{}\n{}", inserted, indent));
     Some(LocalEdit {
         label: "on enter".to_string(),
         edit: edit.finish(),
         cursor_position: Some(cursor_position),
+        placeholders: Vec::new(),
     })
 }
 
+/// The whitespace at the start of the line containing `offset`.
+fn line_indent(file: &SourceFile, offset: TextUnit) -> String {
+    let text = file.syntax().text().to_string();
+    let line_start = text[..offset.to_usize()].rfind('\n').map_or(0, |it| it + 1);
+    text[line_start..]
+        .chars()
+        .take_while(|&c| c == ' ' || c == '\t')
+        .collect()
+}
+
 fn node_indent<'a>(file: &'a SourceFile, node: &SyntaxNode) -> Option<&'a str> {
     let ws = match find_leaf_at_offset(file.syntax(), node.range().start()) {
         LeafAtOffset::Between(l, r) => {
@@ -82,6 +135,7 @@ pub fn on_eq_typed(file: &SourceFile, eq_offset: TextUnit) -> Option<LocalEdit>
         label: "add semicolon".to_string(),
         edit: edit.finish(),
         cursor_position: None,
+        placeholders: Vec::new(),
     })
 }
 
@@ -121,6 +175,7 @@ pub fn on_dot_typed(file: &SourceFile, dot_offset: TextUnit) -> Option<LocalEdit
         cursor_position: Some(
             dot_offset + target_indent_len - current_indent_len + TextUnit::of_char('.'),
         ),
+        placeholders: Vec::new(),
     };
     Some(res)
 }
@@ -340,5 +395,46 @@ impl S {
 ",
         );
         do_check_noop(r"<|>//! docz");
+        do_check(
+            r"
+/* Some<|> docs */
+fn foo() {}
+",
+            r"
+/* Some
+ * <|> docs */
+fn foo() {}
+",
+        );
+        do_check(
+            r"
+fn foo() {
+    /* Some docs<|>
+}
+",
+            r"
+fn foo() {
+    /* Some docs
+     * <|>
+     */
+}
+",
+        );
+        do_check(
+            r"
+impl S {
+    fn foo() {<|>}
+}
+",
+            r"
+impl S {
+    fn foo() {
+        <|>
+    }
+}
+",
+        );
+        do_check_noop(r"fn foo() {<|> 92 }");
+        do_check_noop(r#"fn foo() { "str<|>ing" }"#);
     }
 }
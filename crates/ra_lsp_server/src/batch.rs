@@ -0,0 +1,81 @@
+//! Loading of a cargo workspace outside of an editor, for command line tools
+//! like `ra_cli analysis-stats`.
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use failure::format_err;
+use ra_ide_api::{AnalysisHost, FileId, LibraryData};
+
+use crate::{
+    caps::PositionEncoding,
+    config::ServerConfig,
+    project_model::ProjectWorkspace,
+    server_world::ServerWorldState,
+    Result,
+};
+
+/// A cargo workspace loaded by `load_cargo`.
+#[derive(Debug)]
+pub struct BatchWorkspace {
+    pub host: AnalysisHost,
+    /// The files of the workspace itself, as opposed to the files of its
+    /// dependencies, with their absolute paths.
+    pub local_files: Vec<(FileId, PathBuf)>,
+}
+
+/// Loads the cargo workspace at `path`, together with its sysroot, and blocks
+/// until all of its files are read and the libraries are indexed.
+pub fn load_cargo(path: &Path) -> Result<BatchWorkspace> {
+    let root = path.canonicalize()?;
+    let ws = ProjectWorkspace::discover(&root)?;
+    if let Some(e) = &ws.sysroot_error {
+        log::warn!("{}", e);
+    }
+    let mut state = ServerWorldState::new(
+        root,
+        vec![ws],
+        false,
+        false,
+        PositionEncoding::Utf16,
+        ServerConfig::default(),
+    );
+    let task_receiver = state.vfs.read().task_receiver().clone();
+    while state.roots_to_scan > 0 {
+        let task = task_receiver.recv()?;
+        state.vfs.write().handle_task(task);
+        for (root, files) in state.process_changes() {
+            state.add_lib(LibraryData::prepare(root, files));
+        }
+    }
+
+    let ServerWorldState {
+        root,
+        analysis_host,
+        vfs,
+        excluded_files,
+        ..
+    } = state;
+    let vfs = Arc::try_unwrap(vfs)
+        .map_err(|_| format_err!("vfs is still in use"))?
+        .into_inner();
+    let mut local_files = Vec::new();
+    for vfs_root in vfs.roots() {
+        if !vfs.root2path(vfs_root).starts_with(&root) {
+            continue;
+        }
+        for (file, _path, _text) in vfs.root_files(vfs_root) {
+            let file_id = FileId(file.0.into());
+            if !excluded_files.contains(&file_id) {
+                local_files.push((file_id, vfs.file2path(file)));
+            }
+        }
+    }
+    local_files.sort_by(|(_, a), (_, b)| a.cmp(b));
+    vfs.shutdown().map_err(|_| format_err!("vfs died"))?;
+    Ok(BatchWorkspace {
+        host: analysis_host,
+        local_files,
+    })
+}
@@ -2,8 +2,9 @@ use languageserver_types::{
     CodeActionProviderCapability, CodeLensOptions, CompletionOptions, DocumentOnTypeFormattingOptions,
     ExecuteCommandOptions, FoldingRangeProviderCapability, RenameOptions, RenameProviderCapability,
     ServerCapabilities, SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
-    TextDocumentSyncOptions,
+    TextDocumentSyncOptions, InitializeResult, SaveOptions,
 };
+use serde_json::Value;
 
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
@@ -12,14 +13,18 @@ pub fn server_capabilities() -> ServerCapabilities {
                 open_close: Some(true),
                 change: Some(TextDocumentSyncKind::Full),
                 will_save: None,
-                will_save_wait_until: None,
-                save: None,
+                will_save_wait_until: Some(true),
+                save: Some(SaveOptions { include_text: Some(false) }),
             },
         )),
         hover_provider: Some(true),
         completion_provider: Some(CompletionOptions {
             resolve_provider: None,
-            trigger_characters: Some(vec![":".to_string(), ".".to_string()]),
+            trigger_characters: Some(vec![
+                ":".to_string(),
+                ".".to_string(),
+                "<".to_string(),
+            ]),
         }),
         signature_help_provider: Some(SignatureHelpOptions {
             trigger_characters: Some(vec!["(".to_string(), ",".to_string(), ")".to_string()]),
@@ -52,3 +57,67 @@ pub fn server_capabilities() -> ServerCapabilities {
         workspace: None,
     }
 }
+
+/// The unit of the `character` field of LSP positions. The protocol says
+/// UTF-16 code units, but clients which store text as UTF-8 may opt into byte
+/// offsets, which are cheaper to convert to on both sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf16,
+    Utf8,
+}
+
+/// Picks the position encoding from the raw `initialize` params. Clients
+/// list the encodings they support in `capabilities.offsetEncoding`, like
+/// `["utf-8", "utf-16"]`.
+pub fn negotiate_position_encoding(params: &Value) -> PositionEncoding {
+    let supports_utf8 = params
+        .get("capabilities")
+        .and_then(|it| it.get("offsetEncoding"))
+        .and_then(|it| it.as_array())
+        .map_or(false, |encodings| encodings.iter().any(|it| it == "utf-8"));
+    if supports_utf8 {
+        PositionEncoding::Utf8
+    } else {
+        PositionEncoding::Utf16
+    }
+}
+
+/// The response to `initialize`, which also tells the client the chosen
+/// position encoding and the capabilities `ServerCapabilities` lacks.
+pub fn initialize_result(encoding: PositionEncoding) -> Value {
+    let mut res = serde_json::to_value(InitializeResult {
+        capabilities: server_capabilities(),
+    })
+    .unwrap();
+    res["capabilities"]["documentLinkProvider"] = serde_json::json!({ "resolveProvider": true });
+    res["capabilities"]["declarationProvider"] = true.into();
+    res["capabilities"]["selectionRangeProvider"] = true.into();
+    if encoding == PositionEncoding::Utf8 {
+        res["offsetEncoding"] = "utf-8".into();
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn utf8_is_used_only_when_the_client_offers_it() {
+        let utf8 = json!({ "capabilities": { "offsetEncoding": ["utf-8", "utf-16"] } });
+        assert_eq!(negotiate_position_encoding(&utf8), PositionEncoding::Utf8);
+        let utf16 = json!({ "capabilities": { "offsetEncoding": ["utf-16"] } });
+        assert_eq!(negotiate_position_encoding(&utf16), PositionEncoding::Utf16);
+        let missing = json!({ "capabilities": {} });
+        assert_eq!(negotiate_position_encoding(&missing), PositionEncoding::Utf16);
+    }
+
+    #[test]
+    fn initialize_result_reports_utf8() {
+        assert_eq!(initialize_result(PositionEncoding::Utf8)["offsetEncoding"], "utf-8");
+        assert!(initialize_result(PositionEncoding::Utf16).get("offsetEncoding").is_none());
+    }
+}
@@ -13,38 +13,45 @@ pub(crate) fn runnable_args(
 ) -> Result<Vec<String>> {
     let spec = CargoTargetSpec::for_file(world, file_id)?;
     let mut res = Vec::new();
-    match kind {
-        RunnableKind::Test { name } => {
-            res.push("test".to_string());
-            if let Some(spec) = spec {
-                spec.push_to(&mut res);
-            }
-            res.push("--".to_string());
-            res.push(name.to_string());
-            res.push("--nocapture".to_string());
-        }
-        RunnableKind::TestMod { path } => {
-            res.push("test".to_string());
-            if let Some(spec) = spec {
-                spec.push_to(&mut res);
-            }
-            res.push("--".to_string());
-            res.push(path.to_string());
-            res.push("--nocapture".to_string());
-        }
-        RunnableKind::Bench { name } => {
-            res.push("bench".to_string());
-            if let Some(spec) = spec {
-                spec.push_to(&mut res);
-            }
-            res.push("--".to_string());
-            res.push(name.to_string());
-            res.push("--nocapture".to_string());
-        }
-        RunnableKind::Bin => {
-            res.push("run".to_string());
-            if let Some(spec) = spec {
-                spec.push_to(&mut res);
+    let subcommand = match kind {
+        RunnableKind::Test { .. } | RunnableKind::TestMod { .. } => "test",
+        RunnableKind::Bench { .. } => "bench",
+        RunnableKind::Bin => "run",
+    };
+    res.push(subcommand.to_string());
+    if let Some(spec) = spec {
+        spec.push_to(&mut res);
+    }
+    res.extend(world.config.runnables.cargo_args.iter().cloned());
+    let filter = match kind {
+        RunnableKind::Test { name } | RunnableKind::Bench { name } => Some(name),
+        RunnableKind::TestMod { path } => Some(path),
+        RunnableKind::Bin => None,
+    };
+    if let Some(filter) = filter {
+        res.push("--".to_string());
+        res.push(filter.to_string());
+        res.push("--nocapture".to_string());
+    }
+    Ok(res)
+}
+
+/// Returns the packages which need to be checked after a change to `file_id`:
+/// the package of the file itself, followed by the packages which depend on it,
+/// directly or transitively.
+pub(crate) fn dependent_packages(world: &ServerWorld, file_id: FileId) -> Result<Vec<String>> {
+    let mut res = Vec::new();
+    for crate_id in world.analysis().crate_for(file_id)? {
+        for crate_id in world.analysis().transitive_reverse_dependencies(crate_id)? {
+            let root = world.analysis().crate_root(crate_id)?;
+            let path = world.vfs.read().file2path(ra_vfs::VfsFile(root.0.into()));
+            for ws in world.workspaces.iter() {
+                if let Some(tgt) = ws.cargo.target_by_root(&path) {
+                    let package = tgt.package(&ws.cargo).name(&ws.cargo).to_string();
+                    if !res.contains(&package) {
+                        res.push(package);
+                    }
+                }
             }
         }
     }
@@ -55,6 +62,7 @@ pub struct CargoTargetSpec {
     pub package: String,
     pub target: String,
     pub target_kind: TargetKind,
+    pub required_features: Vec<String>,
 }
 
 impl CargoTargetSpec {
@@ -74,6 +82,7 @@ impl CargoTargetSpec {
                 package: tgt.package(&ws.cargo).name(&ws.cargo).to_string(),
                 target: tgt.name(&ws.cargo).to_string(),
                 target_kind: tgt.kind(&ws.cargo),
+                required_features: tgt.required_features(&ws.cargo).to_vec(),
             };
             Some(res)
         });
@@ -105,5 +114,9 @@ impl CargoTargetSpec {
             }
             TargetKind::Other => (),
         }
+        if !self.required_features.is_empty() {
+            buf.push("--features".to_string());
+            buf.push(self.required_features.join(" "));
+        }
     }
 }
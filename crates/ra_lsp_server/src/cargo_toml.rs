@@ -0,0 +1,307 @@
+//! Light support for `Cargo.toml` documents: the paths of `path`
+//! dependencies and of the workspace members are links to the manifests
+//! they point to, and the members can be completed. The manifest is not
+//! parsed as TOML, it is scanned line by line, which is good enough for the
+//! strings we are interested in.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ra_syntax::{TextRange, TextUnit};
+
+/// How deep the directories of the workspace are searched for packages, when
+/// completing the members.
+const MEMBER_SEARCH_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ManifestPathKind {
+    /// `path = "../foo"` of a dependency or of a patch.
+    Dependency,
+    /// An entry of `members` or `exclude` in the `[workspace]` table.
+    Member,
+}
+
+/// A string in the manifest which is a path relative to it.
+#[derive(Debug)]
+pub(crate) struct ManifestPath {
+    pub(crate) kind: ManifestPathKind,
+    /// The range of the contents of the string, without the quotes.
+    pub(crate) range: TextRange,
+    pub(crate) value: String,
+}
+
+impl ManifestPath {
+    /// The manifest of the package the path points to, if it exists. Globs,
+    /// like `crates/*`, don't point to a single package.
+    pub(crate) fn target(&self, manifest: &Path) -> Option<PathBuf> {
+        if self.value.contains(|c: char| c == '*' || c == '?' || c == '[') {
+            return None;
+        }
+        let target = manifest.parent()?.join(&self.value).join("Cargo.toml");
+        if target.is_file() {
+            Some(target)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) fn is_manifest(path: &Path) -> bool {
+    path.file_name().map_or(false, |it| it == "Cargo.toml")
+}
+
+/// Finds the paths in the manifest `text`.
+pub(crate) fn manifest_paths(text: &str) -> Vec<ManifestPath> {
+    let mut res = Vec::new();
+    let mut table = String::new();
+    let mut in_members = false;
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let code = strip_comment(line.trim_end_matches('\r'));
+        let trimmed = code.trim();
+        if trimmed.starts_with('[') && !in_members {
+            table = trimmed.trim_matches(|c: char| c == '[' || c == ']').trim().to_string();
+        } else if table == "workspace" {
+            let key = trimmed.split('=').next().unwrap_or("").trim();
+            if key == "members" || key == "exclude" {
+                in_members = true;
+            }
+            if in_members {
+                let strings_start = code.find('[').map_or(0, |it| it + 1);
+                for (range, value) in strings(code, strings_start) {
+                    res.push(ManifestPath {
+                        kind: ManifestPathKind::Member,
+                        range: offset_range(range, line_start),
+                        value,
+                    });
+                }
+                if code.contains(']') {
+                    in_members = false;
+                }
+            }
+        } else if is_dependency_table(&table) {
+            for start in path_value_starts(code) {
+                if let Some((range, value)) = strings(code, start).into_iter().next() {
+                    res.push(ManifestPath {
+                        kind: ManifestPathKind::Dependency,
+                        range: offset_range(range, line_start),
+                        value,
+                    });
+                }
+            }
+        }
+        line_start += line.len() + 1;
+    }
+    res
+}
+
+/// The path at `offset`. The end of the path counts too, so that a path which
+/// is being typed is found.
+pub(crate) fn manifest_path_at(text: &str, offset: TextUnit) -> Option<ManifestPath> {
+    manifest_paths(text)
+        .into_iter()
+        .find(|it| it.range.start() <= offset && offset <= it.range.end())
+}
+
+/// The directories below the manifest which contain a package and are not
+/// listed in `members` yet, relative to the manifest, like `crates/foo`.
+pub(crate) fn member_candidates(manifest: &Path, text: &str) -> Vec<String> {
+    let root = match manifest.parent() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let listed = manifest_paths(text)
+        .into_iter()
+        .filter(|it| it.kind == ManifestPathKind::Member)
+        .map(|it| it.value)
+        .collect::<Vec<_>>();
+    let mut res = Vec::new();
+    collect_packages(root, root, MEMBER_SEARCH_DEPTH, &mut res);
+    res.retain(|it| !listed.contains(it));
+    res.sort();
+    res
+}
+
+fn collect_packages(root: &Path, dir: &Path, depth: usize, acc: &mut Vec<String>) {
+    if depth == 0 {
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(it) => it,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|it| it.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !path.is_dir() || name.starts_with('.') || name == "target" {
+            continue;
+        }
+        if path.join("Cargo.toml").is_file() {
+            if let Ok(relative) = path.strip_prefix(root) {
+                let components = relative
+                    .components()
+                    .map(|it| it.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+                acc.push(components.join("/"));
+            }
+        }
+        collect_packages(root, &path, depth - 1, acc);
+    }
+}
+
+/// Dependency tables, like `[dependencies]`, `[dev-dependencies.foo]` or
+/// `[target.'cfg(unix)'.dependencies]`, and the patches, which can point to
+/// local packages too.
+fn is_dependency_table(table: &str) -> bool {
+    table.split('.').any(|segment| match segment.trim() {
+        "dependencies" | "dev-dependencies" | "build-dependencies" => true,
+        _ => false,
+    }) || table.starts_with("patch.")
+        || table == "replace"
+}
+
+/// The offsets right after `path =`, both for `path = "../foo"` lines and
+/// for inline tables, like `foo = { path = "../foo" }`.
+fn path_value_starts(line: &str) -> Vec<usize> {
+    let mut res = Vec::new();
+    let mut pos = 0;
+    while let Some(idx) = line[pos..].find("path") {
+        let start = pos + idx;
+        let end = start + "path".len();
+        pos = end;
+        let is_key_start = line[..start]
+            .chars()
+            .next_back()
+            .map_or(true, |c| c.is_whitespace() || c == '{' || c == ',');
+        let rest = line[end..].trim_start();
+        if is_key_start && rest.starts_with('=') {
+            res.push(line.len() - rest.len() + 1);
+        }
+    }
+    res
+}
+
+/// The string literals of `line` after `start`, with the ranges of their
+/// contents. An unterminated string extends to the end of the line.
+fn strings(line: &str, start: usize) -> Vec<(TextRange, String)> {
+    let mut res = Vec::new();
+    let mut chars = line[start..].char_indices().map(|(idx, c)| (start + idx, c));
+    while let Some((idx, c)) = chars.next() {
+        if c != '"' && c != '\'' {
+            continue;
+        }
+        let content_start = idx + 1;
+        let mut content_end = line.len();
+        let mut escaped = false;
+        for (idx, it) in chars.by_ref() {
+            if it == c && !escaped {
+                content_end = idx;
+                break;
+            }
+            escaped = c == '"' && it == '\\' && !escaped;
+        }
+        let range = TextRange::from_to(
+            TextUnit::from_usize(content_start),
+            TextUnit::from_usize(content_end),
+        );
+        res.push((range, line[content_start..content_end].to_string()));
+    }
+    res
+}
+
+/// The line without its `#` comment. `#`s in strings don't start comments.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..idx],
+            None => (),
+        }
+    }
+    line
+}
+
+fn offset_range(range: TextRange, offset: usize) -> TextRange {
+    let offset = TextUnit::from_usize(offset);
+    TextRange::from_to(range.start() + offset, range.end() + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(text: &str) -> Vec<(ManifestPathKind, &str, String)> {
+        manifest_paths(text)
+            .into_iter()
+            .map(|it| (it.kind, &text[it.range], it.value))
+            .collect()
+    }
+
+    #[test]
+    fn finds_members_and_path_dependencies() {
+        let text = r#"
+[workspace]
+members = [
+    "crates/*", # "commented"
+    'foo',
+]
+exclude = ["bar"]
+
+[dependencies]
+baz = { version = "1.0", path = "../baz" }
+
+[target.'cfg(unix)'.dev-dependencies.qux]
+path = "qux"
+
+[package]
+path = "not/a/dependency"
+"#;
+        assert_eq!(
+            paths(text),
+            vec![
+                (ManifestPathKind::Member, "crates/*", "crates/*".to_string()),
+                (ManifestPathKind::Member, "foo", "foo".to_string()),
+                (ManifestPathKind::Member, "bar", "bar".to_string()),
+                (ManifestPathKind::Dependency, "../baz", "../baz".to_string()),
+                (ManifestPathKind::Dependency, "qux", "qux".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_path_being_typed() {
+        let text = "[dependencies]\nfoo = { path = \"../fo";
+        let path = manifest_path_at(text, TextUnit::of_str(text)).unwrap();
+        assert_eq!(path.value, "../fo");
+    }
+
+    #[test]
+    fn finds_strings() {
+        let line = r#"a = ["b", 'c\', "d\"e", "f"#;
+        let actual = strings(line, 0).into_iter().map(|(_, it)| it).collect::<Vec<_>>();
+        assert_eq!(actual, vec!["b", "c\\", "d\\\"e", "f"]);
+        let actual = strings(line, line.find(',').unwrap()).len();
+        assert_eq!(actual, 3);
+    }
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(strip_comment("a = 1 # comment"), "a = 1 ");
+        assert_eq!(strip_comment(r##"a = "#1" # comment"##), r##"a = "#1" "##);
+        assert_eq!(strip_comment("a = '#'"), "a = '#'");
+        assert_eq!(strip_comment("# comment"), "");
+    }
+
+    #[test]
+    fn finds_path_values() {
+        assert_eq!(path_value_starts(r#"foo = { path = "../foo" }"#), vec![14]);
+        assert_eq!(path_value_starts(r#"path="foo""#), vec![5]);
+        let line = r#"foo = { xpath = "x", subpath_b = 1 }"#;
+        assert_eq!(path_value_starts(line), Vec::<usize>::new());
+    }
+}
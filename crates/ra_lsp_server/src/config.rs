@@ -0,0 +1,228 @@
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+/// Client-provided settings. They are passed as `initializationOptions` and
+/// can be updated later via `workspace/didChangeConfiguration`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerConfig {
+    // Whether the client supports our custom highlighting publishing decorations.
+    // This is different to the highlightingOn setting, which is whether the user
+    // wants our custom highlighting to be used.
+    pub publish_decorations: bool,
+    /// Extra env vars and cargo arguments for the runnables.
+    pub runnables: RunnableConfig,
+    pub inlay_hints: InlayHintsOptions,
+    pub hover: HoverOptions,
+    /// The cargo subcommand used by the "cargo check" runnable, like `clippy`.
+    pub cargo_check_command: String,
+    /// Whether completions may insert snippets with placeholders.
+    pub completion_snippets: bool,
+    /// Snippets defined by the user, offered among the completions.
+    pub snippets: Vec<SnippetOptions>,
+    /// How completions add the imports they need.
+    pub import_style: ImportStyleOptions,
+    /// Files which should not be analyzed, as globs relative to the workspace
+    /// root, like `target/**` or `**/generated/*.rs`.
+    pub exclude_globs: Vec<String>,
+    /// What is done when a document is saved.
+    pub on_save: OnSaveOptions,
+    /// Whether workspace symbol search looks into the dependencies too. It
+    /// can also be requested for a single query with `*`.
+    pub symbol_search_libraries: bool,
+    /// How long workspace symbol search may take, in milliseconds. When the
+    /// budget is exceeded, the symbols found so far are returned, and the
+    /// client can't tell that some are missing, so there is no limit by
+    /// default.
+    pub request_budget_ms: Option<u64>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            publish_decorations: false,
+            runnables: RunnableConfig::default(),
+            inlay_hints: InlayHintsOptions::default(),
+            hover: HoverOptions::default(),
+            cargo_check_command: "check".to_string(),
+            completion_snippets: true,
+            snippets: Vec::new(),
+            import_style: ImportStyleOptions::default(),
+            exclude_globs: Vec::new(),
+            on_save: OnSaveOptions::default(),
+            symbol_search_libraries: false,
+            request_budget_ms: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Checks the path of a file, relative to the workspace root, against the
+    /// exclude globs.
+    pub(crate) fn is_excluded(&self, path: &str) -> bool {
+        self.exclude_globs.iter().any(|glob| glob_match(glob, path))
+    }
+}
+
+/// User-provided settings which apply to every runnable.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnableConfig {
+    /// Extra environment variables, like `RUST_LOG`.
+    #[serde(default)]
+    pub env: FxHashMap<String, String>,
+    /// Extra arguments for cargo, like `--no-default-features` or `--target`.
+    #[serde(default)]
+    pub cargo_args: Vec<String>,
+}
+
+/// Which kinds of inlay hints are shown.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InlayHintsOptions {
+    pub parameter_hints: bool,
+    pub chaining_hints: bool,
+}
+
+impl Default for InlayHintsOptions {
+    fn default() -> InlayHintsOptions {
+        InlayHintsOptions {
+            parameter_hints: true,
+            chaining_hints: true,
+        }
+    }
+}
+
+/// What is shown in hover popups.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HoverOptions {
+    /// The traits implemented by structs and enums.
+    pub implemented_traits: bool,
+    pub max_implemented_traits: usize,
+}
+
+impl Default for HoverOptions {
+    fn default() -> HoverOptions {
+        HoverOptions {
+            implemented_traits: true,
+            max_implemented_traits: 10,
+        }
+    }
+}
+
+/// The actions which are run when a document is saved. Diagnostics are
+/// always recomputed.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OnSaveOptions {
+    /// Asks the client to run the "cargo check" runnable of the document.
+    pub cargo_check: bool,
+    /// Sorts the `use` items of the document.
+    pub organize_imports: bool,
+    /// Formats the document with rustfmt.
+    pub rustfmt: bool,
+}
+
+/// A user-defined snippet completion.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetOptions {
+    pub label: String,
+    pub body: String,
+    /// Paths which are imported when the snippet is accepted, like
+    /// `std::sync::Arc`.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub scope: SnippetScopeOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnippetScopeOptions {
+    Expr,
+    Item,
+    Type,
+}
+
+impl Default for SnippetScopeOptions {
+    fn default() -> SnippetScopeOptions {
+        SnippetScopeOptions::Expr
+    }
+}
+
+/// Whether a new import of `foo::bar` is merged into `use foo::baz;`, making
+/// it `use foo::{bar, baz};`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStyleOptions {
+    Plain,
+    Nested,
+}
+
+impl Default for ImportStyleOptions {
+    fn default() -> ImportStyleOptions {
+        ImportStyleOptions::Plain
+    }
+}
+
+/// A minimal glob matcher: `?` matches a single character, `*` matches any
+/// sequence of characters except `/`, and `**/` matches any number of whole
+/// path segments.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    glob_match_bytes(pattern.as_bytes(), path.as_bytes())
+}
+
+fn glob_match_bytes(pattern: &[u8], path: &[u8]) -> bool {
+    if pattern.starts_with(b"**") {
+        let rest = &pattern[2..];
+        let rest_after_slash = if rest.starts_with(b"/") { &rest[1..] } else { rest };
+        return (0..=path.len()).any(|i| {
+            let at_segment_start = i == 0 || path[i - 1] == b'/';
+            glob_match_bytes(rest, &path[i..])
+                || (at_segment_start && glob_match_bytes(rest_after_slash, &path[i..]))
+        });
+    }
+    if pattern.starts_with(b"*") {
+        return (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| glob_match_bytes(&pattern[1..], &path[i..]));
+    }
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(b'?'), Some(&c)) if c != b'/' => glob_match_bytes(&pattern[1..], &path[1..]),
+        (Some(p), Some(c)) if p == c && *p != b'?' => glob_match_bytes(&pattern[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn star_matches_within_a_segment() {
+        assert!(glob_match("*.rs", "lib.rs"));
+        assert!(glob_match("src/*", "src/lib.rs"));
+        assert!(!glob_match("*.rs", "src/lib.rs"));
+        assert!(!glob_match("*.rs", "lib.rst"));
+    }
+
+    #[test]
+    fn question_mark_matches_a_single_char() {
+        assert!(glob_match("src/?.rs", "src/a.rs"));
+        assert!(!glob_match("src/?.rs", "src/ab.rs"));
+        assert!(!glob_match("src?a.rs", "src/a.rs"));
+    }
+
+    #[test]
+    fn double_star_matches_whole_segments() {
+        assert!(glob_match("**/gen.rs", "gen.rs"));
+        assert!(glob_match("**/gen.rs", "a/b/gen.rs"));
+        assert!(!glob_match("**/gen.rs", "a/xgen.rs"));
+        assert!(glob_match("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_match("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(!glob_match("src/**/*.rs", "tests/a.rs"));
+    }
+}
@@ -1,18 +1,20 @@
+use std::sync::Arc;
+
 use languageserver_types::{
-    self, CreateFile, DocumentChangeOperation, DocumentChanges, InsertTextFormat, Location, LocationLink,
-    Position, Range, RenameFile, ResourceOp, SymbolKind, TextDocumentEdit, TextDocumentIdentifier,
-    TextDocumentItem, TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
-    WorkspaceEdit,
+    self, Command, CreateFile, DeleteFile, DocumentChangeOperation, DocumentChanges, Documentation,
+    InsertTextFormat, Location, LocationLink, Position, Range, RenameFile, ResourceOp, SymbolKind,
+    TextDocumentEdit, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Url,
+    VersionedTextDocumentIdentifier, WorkspaceEdit,
 };
 use ra_ide_api::{
     CompletionItem, CompletionItemKind, FileId, FilePosition, FileRange, FileSystemEdit,
-    InsertText, NavigationTarget, SourceChange, SourceFileEdit, RangeInfo,
-    LineCol, LineIndex, translate_offset_with_edit
+    InlayKind, InsertText, NavigationTarget, SourceChange, SourceFileEdit, RangeInfo,
+    LineCol, LineColUtf8, LineIndex, translate_offset_with_edit
 };
 use ra_syntax::{SyntaxKind, TextRange, TextUnit};
-use ra_text_edit::{AtomTextEdit, TextEdit};
+use ra_text_edit::{AtomTextEdit, TextEdit, TextEditBuilder};
 
-use crate::{req, server_world::ServerWorld, Result};
+use crate::{caps::PositionEncoding, req, server_world::ServerWorld, Result};
 
 pub trait Conv {
     type Output;
@@ -74,6 +76,17 @@ impl Conv for CompletionItemKind {
     }
 }
 
+impl Conv for InlayKind {
+    type Output = req::InlayKind;
+
+    fn conv(self) -> <Self as Conv>::Output {
+        match self {
+            InlayKind::ParameterHint => req::InlayKind::ParameterHint,
+            InlayKind::ChainingHint => req::InlayKind::ChainingHint,
+        }
+    }
+}
+
 impl Conv for CompletionItem {
     type Output = ::languageserver_types::CompletionItem;
 
@@ -81,10 +94,25 @@ impl Conv for CompletionItem {
         let mut res = ::languageserver_types::CompletionItem {
             label: self.label().to_string(),
             detail: self.detail().map(|it| it.to_string()),
+            documentation: self.documentation().map(|it| Documentation::String(it.to_string())),
             filter_text: Some(self.lookup().to_string()),
             kind: self.kind().map(|it| it.conv()),
+            deprecated: Some(self.deprecated()),
+            // The client sorts by the label otherwise, `0` goes before letters.
+            sort_text: if self.matches_expected_type() {
+                Some(format!("0{}", self.label()))
+            } else {
+                None
+            },
             ..Default::default()
         };
+        if self.triggers_completion() {
+            res.command = Some(Command {
+                title: "triggerSuggest".to_string(),
+                command: "editor.action.triggerSuggest".to_string(),
+                arguments: None,
+            });
+        }
         match self.insert_text() {
             InsertText::PlainText { text } => {
                 res.insert_text = Some(text);
@@ -99,34 +127,57 @@ impl Conv for CompletionItem {
     }
 }
 
+/// The line index of a file, together with the position encoding negotiated
+/// with the client.
+pub struct LineMap {
+    pub index: Arc<LineIndex>,
+    pub encoding: PositionEncoding,
+}
+
 impl ConvWith for Position {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = TextUnit;
 
-    fn conv_with(self, line_index: &LineIndex) -> TextUnit {
-        let line_col = LineCol {
-            line: self.line as u32,
-            col_utf16: self.character as u32,
-        };
-        line_index.offset(line_col)
+    fn conv_with(self, line_map: &LineMap) -> TextUnit {
+        let line = self.line as u32;
+        let col = self.character as u32;
+        match line_map.encoding {
+            PositionEncoding::Utf16 => line_map.index.offset(LineCol {
+                line,
+                col_utf16: col,
+            }),
+            PositionEncoding::Utf8 => line_map.index.offset_utf8(LineColUtf8 {
+                line,
+                col_utf8: col,
+            }),
+        }
     }
 }
 
 impl ConvWith for TextUnit {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = Position;
 
-    fn conv_with(self, line_index: &LineIndex) -> Position {
-        let line_col = line_index.line_col(self);
-        Position::new(u64::from(line_col.line), u64::from(line_col.col_utf16))
+    fn conv_with(self, line_map: &LineMap) -> Position {
+        let (line, col) = match line_map.encoding {
+            PositionEncoding::Utf16 => {
+                let line_col = line_map.index.line_col(self);
+                (line_col.line, line_col.col_utf16)
+            }
+            PositionEncoding::Utf8 => {
+                let line_col = line_map.index.line_col_utf8(self);
+                (line_col.line, line_col.col_utf8)
+            }
+        };
+        Position::new(u64::from(line), u64::from(col))
     }
 }
 
 impl ConvWith for TextRange {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = Range;
 
-    fn conv_with(self, line_index: &LineIndex) -> Range {
+    fn conv_with(self, line_index: &LineMap) -> Range {
         Range::new(
             self.start().conv_with(line_index),
             self.end().conv_with(line_index),
@@ -135,10 +186,10 @@ impl ConvWith for TextRange {
 }
 
 impl ConvWith for Range {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = TextRange;
 
-    fn conv_with(self, line_index: &LineIndex) -> TextRange {
+    fn conv_with(self, line_index: &LineMap) -> TextRange {
         TextRange::from_to(
             self.start.conv_with(line_index),
             self.end.conv_with(line_index),
@@ -147,10 +198,10 @@ impl ConvWith for Range {
 }
 
 impl ConvWith for TextEdit {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = Vec<languageserver_types::TextEdit>;
 
-    fn conv_with(self, line_index: &LineIndex) -> Vec<languageserver_types::TextEdit> {
+    fn conv_with(self, line_index: &LineMap) -> Vec<languageserver_types::TextEdit> {
         self.as_atoms()
             .into_iter()
             .map_conv_with(line_index)
@@ -159,10 +210,10 @@ impl ConvWith for TextEdit {
 }
 
 impl<'a> ConvWith for &'a AtomTextEdit {
-    type Ctx = LineIndex;
+    type Ctx = LineMap;
     type Output = languageserver_types::TextEdit;
 
-    fn conv_with(self, line_index: &LineIndex) -> languageserver_types::TextEdit {
+    fn conv_with(self, line_index: &LineMap) -> languageserver_types::TextEdit {
         languageserver_types::TextEdit {
             range: self.delete.conv_with(line_index),
             new_text: self.insert.clone(),
@@ -223,7 +274,7 @@ impl<'a> TryConvWith for &'a TextDocumentPositionParams {
     type Output = FilePosition;
     fn try_conv_with(self, world: &ServerWorld) -> Result<FilePosition> {
         let file_id = self.text_document.try_conv_with(world)?;
-        let line_index = world.analysis().file_line_index(file_id);
+        let line_index = world.line_map(file_id);
         let offset = self.position.conv_with(&line_index);
         Ok(FilePosition { file_id, offset })
     }
@@ -234,7 +285,7 @@ impl<'a> TryConvWith for (&'a TextDocumentIdentifier, Range) {
     type Output = FileRange;
     fn try_conv_with(self, world: &ServerWorld) -> Result<FileRange> {
         let file_id = self.0.try_conv_with(world)?;
-        let line_index = world.analysis().file_line_index(file_id);
+        let line_index = world.line_map(file_id);
         let range = self.1.conv_with(&line_index);
         Ok(FileRange { file_id, range })
     }
@@ -259,27 +310,79 @@ impl TryConvWith for SourceChange {
         let cursor_position = match self.cursor_position {
             None => None,
             Some(pos) => {
-                let line_index = world.analysis().file_line_index(pos.file_id);
+                let line_index = world.line_map(pos.file_id);
                 let edit = self
                     .source_file_edits
                     .iter()
                     .find(|it| it.file_id == pos.file_id)
                     .map(|it| &it.edit);
-                let line_col = match edit {
-                    Some(edit) => translate_offset_with_edit(&*line_index, pos.offset, edit),
-                    None => line_index.line_col(pos.offset),
+                let position = match (edit, line_index.encoding) {
+                    (Some(edit), PositionEncoding::Utf16) => {
+                        let line_col =
+                            translate_offset_with_edit(&line_index.index, pos.offset, edit);
+                        Position::new(u64::from(line_col.line), u64::from(line_col.col_utf16))
+                    }
+                    (Some(edit), PositionEncoding::Utf8) => {
+                        let text = edit.apply(&world.analysis().file_text(pos.file_id));
+                        let line_col = LineIndex::new(&text).line_col_utf8(pos.offset);
+                        Position::new(u64::from(line_col.line), u64::from(line_col.col_utf8))
+                    }
+                    (None, _) => pos.offset.conv_with(&line_index),
                 };
-                let position =
-                    Position::new(u64::from(line_col.line), u64::from(line_col.col_utf16));
                 Some(TextDocumentPositionParams {
                     text_document: TextDocumentIdentifier::new(pos.file_id.try_conv_with(world)?),
                     position,
                 })
             }
         };
+        let snippet_edit = if world.supports_snippet_text_edits && !self.placeholders.is_empty() {
+            snippet_file_edit(
+                world,
+                &self.source_file_edits,
+                self.cursor_position,
+                &self.placeholders,
+            )
+        } else {
+            None
+        };
+        let snippet_edit = match snippet_edit {
+            Some(it) => Some(it.try_conv_with(world)?),
+            None => None,
+        };
         let mut document_changes: Vec<DocumentChangeOperation> = Vec::new();
-        for resource_op in self.file_system_edits.try_conv_with(world)? {
-            document_changes.push(DocumentChangeOperation::Op(resource_op));
+        if let Some(edit) =
+            self.file_system_edits.iter().find(|it| !world.supports_file_system_edit(it))
+        {
+            failure::bail!("client doesn't support the resource operation of {:?}", edit);
+        }
+        for file_system_edit in self.file_system_edits {
+            // Resource operations can't carry text, so initial contents of a
+            // new file are inserted by a separate edit right after creation.
+            let initial_text = match &file_system_edit {
+                FileSystemEdit::CreateFileWithContent {
+                    source_root,
+                    path,
+                    content,
+                } => {
+                    let uri = world.path_to_uri(*source_root, path)?;
+                    Some((uri, content.clone()))
+                }
+                _ => None,
+            };
+            document_changes.push(DocumentChangeOperation::Op(
+                file_system_edit.try_conv_with(world)?,
+            ));
+            if let Some((uri, text)) = initial_text {
+                let text_document = VersionedTextDocumentIdentifier { uri, version: None };
+                let edit = languageserver_types::TextEdit {
+                    range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                    new_text: text,
+                };
+                document_changes.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document,
+                    edits: vec![edit],
+                }));
+            }
         }
         for text_document_edit in self.source_file_edits.try_conv_with(world)? {
             document_changes.push(DocumentChangeOperation::Edit(text_document_edit));
@@ -292,19 +395,130 @@ impl TryConvWith for SourceChange {
             label: self.label,
             workspace_edit,
             cursor_position,
+            snippet_edit,
         })
     }
 }
 
+/// The edit of the file with the placeholders as a single snippet, or `None`
+/// if the placeholders are in several files.
+fn snippet_file_edit(
+    world: &ServerWorld,
+    source_file_edits: &[SourceFileEdit],
+    cursor_position: Option<FilePosition>,
+    placeholders: &[FileRange],
+) -> Option<SourceFileEdit> {
+    let file_id = placeholders[0].file_id;
+    if placeholders.iter().any(|it| it.file_id != file_id) {
+        return None;
+    }
+    let file_edit = source_file_edits.iter().find(|it| it.file_id == file_id)?;
+    let text = world.analysis().file_text(file_id);
+    let cursor = cursor_position
+        .filter(|it| it.file_id == file_id)
+        .map(|it| it.offset);
+    let placeholders = placeholders.iter().map(|it| it.range).collect::<Vec<_>>();
+    let edit = snippet_edit(&text, &file_edit.edit, cursor, &placeholders)?;
+    Some(SourceFileEdit { file_id, edit })
+}
+
+/// Rewrites `edit` of `text` into a single snippet, where the placeholders
+/// become `${1:text}`, `${2:text}` and so on, and the cursor becomes `$0`.
+/// The placeholders and the cursor are offsets in the edited text. Returns
+/// `None` if some of them are outside of the changed text.
+fn snippet_edit(
+    text: &str,
+    edit: &TextEdit,
+    cursor: Option<TextUnit>,
+    placeholders: &[TextRange],
+) -> Option<TextEdit> {
+    let atoms = edit.as_atoms();
+    let (first, last) = (atoms.first()?, atoms.last()?);
+    // Clients can expand only one snippet at a time, so all the atoms are
+    // merged into one, which also covers the unchanged text between them.
+    let delete = TextRange::from_to(first.delete.start(), last.delete.end());
+    let mut new_text = String::new();
+    let mut prev = delete.start();
+    for atom in atoms {
+        new_text.push_str(&text[prev.to_usize()..atom.delete.start().to_usize()]);
+        new_text.push_str(&atom.insert);
+        prev = atom.delete.end();
+    }
+    // Nothing before the first atom changes, so it starts at the same offset
+    // in the edited file.
+    let new_range = TextRange::offset_len(delete.start(), TextUnit::of_str(&new_text));
+
+    let mut tab_stops = placeholders
+        .iter()
+        .enumerate()
+        .map(|(idx, &range)| (range, idx + 1))
+        .collect::<Vec<_>>();
+    // Within a placeholder, the cursor is just where the typing starts.
+    if let Some(cursor) = cursor {
+        if !tab_stops
+            .iter()
+            .any(|(range, _)| range.contains_inclusive(cursor))
+        {
+            tab_stops.push((TextRange::offset_len(cursor, 0.into()), 0));
+        }
+    }
+    if !tab_stops.iter().all(|(range, _)| range.is_subrange(&new_range)) {
+        return None;
+    }
+    let mut tab_stops = tab_stops
+        .into_iter()
+        .map(|(range, idx)| (range - new_range.start(), idx))
+        .collect::<Vec<_>>();
+
+    let mut builder = TextEditBuilder::default();
+    builder.replace(delete, snippet_text(&new_text, &mut tab_stops));
+    Some(builder.finish())
+}
+
+/// Escapes `text` for a snippet, inserting the tab stops, which must not
+/// overlap.
+fn snippet_text(text: &str, tab_stops: &mut [(TextRange, usize)]) -> String {
+    fn escape(text: &str, buf: &mut String) {
+        for c in text.chars() {
+            if c == '\\' || c == '$' || c == '}' {
+                buf.push('\\');
+            }
+            buf.push(c);
+        }
+    }
+
+    tab_stops.sort_by_key(|(range, _)| range.start());
+    let mut buf = String::new();
+    let mut prev = 0;
+    for &(range, idx) in tab_stops.iter() {
+        escape(&text[prev..range.start().to_usize()], &mut buf);
+        if range.start() == range.end() {
+            buf.push_str(&format!("${}", idx));
+        } else {
+            buf.push_str(&format!("${{{}:", idx));
+            escape(&text[range.start().to_usize()..range.end().to_usize()], &mut buf);
+            buf.push('}');
+        }
+        prev = range.end().to_usize();
+    }
+    escape(&text[prev..], &mut buf);
+    buf
+}
+
 impl TryConvWith for SourceFileEdit {
     type Ctx = ServerWorld;
     type Output = TextDocumentEdit;
     fn try_conv_with(self, world: &ServerWorld) -> Result<TextDocumentEdit> {
+        // Library sources are available for navigation, but are read-only.
+        if world.analysis().is_library_file(self.file_id) {
+            let uri = self.file_id.try_conv_with(world)?;
+            failure::bail!("can't edit library file: {}", uri);
+        }
         let text_document = VersionedTextDocumentIdentifier {
             uri: self.file_id.try_conv_with(world)?,
-            version: None,
+            version: world.doc_versions.get(&self.file_id).cloned(),
         };
-        let line_index = world.analysis().file_line_index(self.file_id);
+        let line_index = world.line_map(self.file_id);
         let edits = self
             .edit
             .as_atoms()
@@ -323,7 +537,8 @@ impl TryConvWith for FileSystemEdit {
     type Output = ResourceOp;
     fn try_conv_with(self, world: &ServerWorld) -> Result<ResourceOp> {
         let res = match self {
-            FileSystemEdit::CreateFile { source_root, path } => {
+            FileSystemEdit::CreateFile { source_root, path }
+            | FileSystemEdit::CreateFileWithContent { source_root, path, .. } => {
                 let uri = world.path_to_uri(source_root, &path)?.to_string();
                 ResourceOp::Create(CreateFile { uri, options: None })
             }
@@ -340,6 +555,10 @@ impl TryConvWith for FileSystemEdit {
                     options: None,
                 })
             }
+            FileSystemEdit::RemoveFile { src } => {
+                let uri = world.file_id_to_uri(src)?.to_string();
+                ResourceOp::Delete(DeleteFile { uri, options: None })
+            }
         };
         Ok(res)
     }
@@ -349,7 +568,7 @@ impl TryConvWith for &NavigationTarget {
     type Ctx = ServerWorld;
     type Output = Location;
     fn try_conv_with(self, world: &ServerWorld) -> Result<Location> {
-        let line_index = world.analysis().file_line_index(self.file_id());
+        let line_index = world.line_map(self.file_id());
         let range = self.focus_range().unwrap_or(self.full_range());
         to_location(self.file_id(), range, &world, &line_index)
     }
@@ -359,10 +578,10 @@ pub fn to_location_link(
     target: &RangeInfo<NavigationTarget>,
     world: &ServerWorld,
     // line index for original range file
-    line_index: &LineIndex,
+    line_index: &LineMap,
 ) -> Result<LocationLink> {
     let url = target.info.file_id().try_conv_with(world)?;
-    let tgt_line_index = world.analysis().file_line_index(target.info.file_id());
+    let tgt_line_index = world.line_map(target.info.file_id());
 
     let res = LocationLink {
         origin_selection_range: Some(target.range.conv_with(line_index)),
@@ -380,7 +599,7 @@ pub fn to_location(
     file_id: FileId,
     range: TextRange,
     world: &ServerWorld,
-    line_index: &LineIndex,
+    line_index: &LineMap,
 ) -> Result<Location> {
     let url = file_id.try_conv_with(world)?;
     let loc = Location::new(url, range.conv_with(line_index));
@@ -421,3 +640,114 @@ where
         self.iter.next().map(|item| item.conv_with(self.ctx))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use languageserver_types::ResourceOperationKind;
+
+    use crate::{config::ServerConfig, server_world::ServerWorldState};
+
+    fn range(start: u32, end: u32) -> TextRange {
+        TextRange::from_to(start.into(), end.into())
+    }
+
+    #[test]
+    fn positions_use_the_negotiated_encoding() {
+        let index = Arc::new(LineIndex::new("fn main() {\n    \"𝕊\"; 92\n}"));
+        let offset = TextUnit::from(24);
+        for &(encoding, col) in &[(PositionEncoding::Utf8, 12), (PositionEncoding::Utf16, 10)] {
+            let line_map = LineMap {
+                index: Arc::clone(&index),
+                encoding,
+            };
+            let position = offset.conv_with(&line_map);
+            assert_eq!(position, Position::new(1, col));
+            assert_eq!(position.conv_with(&line_map), offset);
+        }
+    }
+
+    #[test]
+    fn snippet_text_escapes_and_inserts_tab_stops() {
+        let mut tab_stops = vec![(range(9, 12), 2), (range(13, 13), 0), (range(4, 7), 1)];
+        assert_eq!(snippet_text("foo(bar, baz)", &mut tab_stops), "foo(${1:bar}, ${2:baz})$0");
+        let mut tab_stops = vec![(range(0, 2), 1)];
+        assert_eq!(snippet_text("a}$b\\", &mut tab_stops), r"${1:a\}}\$b\\");
+    }
+
+    #[test]
+    fn snippet_edit_shifts_the_tab_stops_to_the_edit() {
+        let text = "aaa bbb";
+        let mut builder = TextEditBuilder::default();
+        builder.insert(4.into(), "new ".to_string());
+        let edit = snippet_edit(text, &builder.finish(), None, &[range(4, 7)]).unwrap();
+        assert_eq!(edit.apply(text), "aaa ${1:new} bbb");
+    }
+
+    #[test]
+    fn snippet_edit_merges_the_atoms() {
+        let text = "aaa bbb ccc";
+        let mut builder = TextEditBuilder::default();
+        builder.replace(range(0, 3), "xxxxx".to_string());
+        builder.insert(8.into(), "yy ".to_string());
+        let edit = builder.finish();
+        let snippet = snippet_edit(text, &edit, Some(13.into()), &[range(6, 9)]).unwrap();
+        assert_eq!(snippet.as_atoms().len(), 1);
+        assert_eq!(snippet.apply(text), "xxxxx ${1:bbb} yy $0ccc");
+
+        // Within a placeholder, the cursor is dropped.
+        let snippet = snippet_edit(text, &edit, Some(7.into()), &[range(6, 9)]).unwrap();
+        assert_eq!(snippet.apply(text), "xxxxx ${1:bbb} yy ccc");
+
+        // `ccc` is not changed, so it can't be a placeholder.
+        assert!(snippet_edit(text, &edit, None, &[range(13, 16)]).is_none());
+    }
+
+    #[test]
+    fn remove_file_becomes_delete_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo.rs");
+        std::fs::write(&path, "").unwrap();
+        let state = ServerWorldState::new(
+            dir.path().to_path_buf(),
+            Vec::new(),
+            vec![ResourceOperationKind::Delete],
+            false,
+            PositionEncoding::Utf16,
+            ServerConfig::default(),
+        );
+        let file = state.vfs.write().load(&path).unwrap();
+        let file_id = FileId(file.0.into());
+        let mut world = state.snapshot();
+
+        let op = FileSystemEdit::RemoveFile { src: file_id }.try_conv_with(&world).unwrap();
+        let uri = Url::from_file_path(&path).unwrap().to_string();
+        match op {
+            ResourceOp::Delete(delete) => {
+                assert_eq!(delete.uri, uri);
+                assert!(delete.options.is_none());
+            }
+            _ => panic!("expected a delete operation, got {:?}", op),
+        }
+
+        // A client which can only create files doesn't get the edit.
+        world.resource_operations = Arc::new(vec![ResourceOperationKind::Create]);
+        let change = SourceChange {
+            label: "remove foo.rs".to_string(),
+            source_file_edits: Vec::new(),
+            file_system_edits: vec![FileSystemEdit::RemoveFile { src: file_id }],
+            cursor_position: None,
+            placeholders: Vec::new(),
+        };
+        assert!(change.try_conv_with(&world).is_err());
+
+        drop(world);
+        let vfs = Arc::try_unwrap(state.vfs).expect("the snapshot is dropped");
+        let mut vfs = vfs.into_inner();
+        // Let the initial scan of the root finish, so the worker has nothing
+        // left to send when it is stopped.
+        let task = vfs.task_receiver().recv().unwrap();
+        vfs.handle_task(task);
+        vfs.shutdown().unwrap();
+    }
+}
@@ -1,5 +1,8 @@
+mod batch;
 mod caps;
 mod cargo_target_spec;
+mod cargo_toml;
+mod config;
 mod conv;
 mod main_loop;
 mod project_model;
@@ -7,4 +10,13 @@ pub mod req;
 mod server_world;
 
 pub type Result<T> = ::std::result::Result<T, ::failure::Error>;
-pub use crate::{caps::server_capabilities, main_loop::main_loop, main_loop::LspError};
+pub use crate::{
+    batch::{load_cargo, BatchWorkspace},
+    caps::{initialize_result, negotiate_position_encoding, server_capabilities, PositionEncoding},
+    config::{
+        HoverOptions, InlayHintsOptions, OnSaveOptions, RunnableConfig, ServerConfig,
+        SnippetOptions, SnippetScopeOptions,
+    },
+    main_loop::main_loop,
+    main_loop::LspError,
+};
@@ -1,8 +1,11 @@
+use std::cell::Cell;
+
 use serde::Deserialize;
 use flexi_logger::{Duplicate, Logger};
-use gen_lsp_server::{run_server, stdio_transport};
-
-use ra_lsp_server::Result;
+use gen_lsp_server::{
+    run_server_with_init, stdio_transport, tcp_connect_transport, tcp_listen_transport,
+};
+use ra_lsp_server::{PositionEncoding, Result, ServerConfig};
 
 fn main() -> Result<()> {
     ::std::env::set_var("RUST_BACKTRACE", "short");
@@ -24,20 +27,58 @@ fn main() -> Result<()> {
     }
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct InitializationOptions {
-    // Whether the client supports our custom highlighting publishing decorations.
-    // This is different to the highlightingOn setting, which is whether the user
-    // wants our custom highlighting to be used.
-    publish_decorations: Option<bool>,
+/// How the server talks to the editor.
+enum TransportKind {
+    Stdio,
+    /// `--socket <port>`: the editor listens, and the server connects.
+    Connect(u16),
+    /// `--port <port>`: the server listens, and the editor connects.
+    Listen(u16),
+}
+
+fn parse_args() -> Result<TransportKind> {
+    let mut res = TransportKind::Stdio;
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.find('=') {
+            Some(idx) => (arg[..idx].to_string(), Some(arg[idx + 1..].to_string())),
+            None => (arg.clone(), None),
+        };
+        match flag.as_str() {
+            "--stdio" => res = TransportKind::Stdio,
+            "--socket" | "--port" => {
+                let port = match value.or_else(|| args.next()) {
+                    Some(it) => it,
+                    None => failure::bail!("{} requires a port", flag),
+                };
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| failure::format_err!("invalid port: {}", port))?;
+                res = if flag == "--socket" {
+                    TransportKind::Connect(port)
+                } else {
+                    TransportKind::Listen(port)
+                };
+            }
+            _ => failure::bail!("unknown argument: {}", arg),
+        }
+    }
+    Ok(res)
 }
 
 fn main_inner() -> Result<()> {
-    let (receiver, sender, threads) = stdio_transport();
+    let (receiver, sender, threads) = match parse_args()? {
+        TransportKind::Stdio => stdio_transport(),
+        TransportKind::Connect(port) => tcp_connect_transport(("127.0.0.1", port))?,
+        TransportKind::Listen(port) => tcp_listen_transport(("127.0.0.1", port))?,
+    };
     let cwd = ::std::env::current_dir()?;
-    run_server(
-        ra_lsp_server::server_capabilities(),
+    let position_encoding = Cell::new(PositionEncoding::Utf16);
+    run_server_with_init(
+        |params| {
+            position_encoding.set(ra_lsp_server::negotiate_position_encoding(params));
+            Ok(ra_lsp_server::initialize_result(position_encoding.get()))
+        },
         receiver,
         sender,
         |params, r, s| {
@@ -45,12 +86,35 @@ fn main_inner() -> Result<()> {
                 .root_uri
                 .and_then(|it| it.to_file_path().ok())
                 .unwrap_or(cwd);
-            let supports_decorations = params
+            let resource_operations = params
+                .capabilities
+                .workspace
+                .and_then(|it| it.workspace_edit)
+                .and_then(|it| it.resource_operations)
+                .unwrap_or_default();
+            // The snippet edits are an extension, which clients announce
+            // among the experimental capabilities.
+            let supports_snippet_text_edits = params
+                .capabilities
+                .experimental
+                .as_ref()
+                .and_then(|it| it.get("snippetTextEdit"))
+                .and_then(|it| it.as_bool())
+                .unwrap_or(false);
+            let config = params
                 .initialization_options
-                .and_then(|v| InitializationOptions::deserialize(v).ok())
-                .and_then(|it| it.publish_decorations)
-                == Some(true);
-            ra_lsp_server::main_loop(false, root, supports_decorations, r, s)
+                .and_then(|v| ServerConfig::deserialize(v).ok())
+                .unwrap_or_default();
+            ra_lsp_server::main_loop(
+                false,
+                root,
+                resource_operations,
+                supports_snippet_text_edits,
+                position_encoding.get(),
+                config,
+                r,
+                s,
+            )
         },
     )?;
     log::info!("shutting down IO...");
@@ -1,26 +1,48 @@
 mod handlers;
+mod request_queue;
 mod subscriptions;
 
-use std::{fmt, path::PathBuf, sync::Arc};
+use std::{
+    collections::VecDeque,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use crossbeam_channel::{select, unbounded, Receiver, RecvError, Sender};
+use crossbeam_channel::{select, unbounded, Receiver, RecvError, RecvTimeoutError, Sender};
 use failure::{bail, format_err};
 use failure_derive::Fail;
 use gen_lsp_server::{
     handle_shutdown, ErrorCode, RawMessage, RawNotification, RawRequest, RawResponse,
 };
-use languageserver_types::NumberOrString;
+use languageserver_types::{
+    Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, ResourceOperationKind, Url,
+};
+use parking_lot::{Mutex, RwLock};
 use ra_ide_api::{Canceled, FileId, LibraryData};
-use ra_vfs::VfsTask;
+use ra_vfs::{Vfs, VfsFile, VfsTask};
 use rayon;
-use rustc_hash::FxHashSet;
-use serde::{de::DeserializeOwned, Serialize};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thread_worker::Worker;
 use threadpool::ThreadPool;
 
 use crate::{
-    main_loop::subscriptions::Subscriptions,
-    project_model::workspace_loader,
-    req,
+    caps::PositionEncoding,
+    cargo_toml,
+    config::ServerConfig,
+    main_loop::{
+        request_queue::{self, Job, Priority, RequestQueue},
+        subscriptions::Subscriptions,
+    },
+    project_model::{workspace_loader, ProjectWorkspace, WorkspaceLoadError},
+    req::{self, Request},
     server_world::{ServerWorld, ServerWorldState},
     Result,
 };
@@ -45,14 +67,27 @@ impl LspError {
 enum Task {
     Respond(RawResponse),
     Notify(RawNotification),
+    /// The current tests of an open file. The client is notified only if they
+    /// differ from the last known ones.
+    Tests(FileId, req::TestsChangedParams),
 }
 
 const THREADPOOL_SIZE: usize = 8;
 
+/// How long to wait for the running requests on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check whether a vfs which is still used by some tasks can be
+/// shut down.
+const VFS_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 pub fn main_loop(
     internal_mode: bool,
     ws_root: PathBuf,
-    supports_decorations: bool,
+    resource_operations: Vec<ResourceOperationKind>,
+    supports_snippet_text_edits: bool,
+    position_encoding: PositionEncoding,
+    config: ServerConfig,
     msg_receiver: &Receiver<RawMessage>,
     msg_sender: &Sender<RawMessage>,
 ) -> Result<()> {
@@ -61,27 +96,34 @@ pub fn main_loop(
     let (ws_worker, ws_watcher) = workspace_loader();
 
     ws_worker.send(ws_root.clone()).unwrap();
-    // FIXME: support dynamic workspace loading.
+    let mut load_error_manifest = None;
     let workspaces = match ws_worker.recv().unwrap() {
-        Ok(ws) => vec![ws],
+        Ok(workspaces) => {
+            load_error_manifest = report_sysroot_errors(&workspaces, msg_sender);
+            workspaces
+        }
         Err(e) => {
             log::error!("loading workspace failed: {}", e);
+            load_error_manifest = report_workspace_load_error(&e, msg_sender);
             Vec::new()
         }
     };
-    ws_worker.shutdown();
-    ws_watcher
-        .shutdown()
-        .map_err(|_| format_err!("ws watcher died"))?;
-    let mut state = ServerWorldState::new(ws_root.clone(), workspaces);
+    let mut state = ServerWorldState::new(
+        ws_root.clone(),
+        workspaces,
+        resource_operations,
+        supports_snippet_text_edits,
+        position_encoding,
+        config,
+    );
 
     log::info!("server initialized, serving requests");
 
     let mut pending_requests = FxHashSet::default();
     let mut subs = Subscriptions::new();
+    let mut retired_vfs = Vec::new();
     let main_res = main_loop_inner(
         internal_mode,
-        supports_decorations,
         &pool,
         msg_sender,
         msg_receiver,
@@ -90,31 +132,89 @@ pub fn main_loop(
         &mut state,
         &mut pending_requests,
         &mut subs,
+        &mut retired_vfs,
+        &ws_worker,
+        load_error_manifest,
     );
 
+    // The loader stops once its input is closed.
+    ws_worker.shutdown();
+    let ws_res = ws_watcher
+        .shutdown()
+        .map_err(|_| format_err!("ws watcher died"));
+
     log::info!("waiting for tasks to finish...");
-    task_receiver
-        .into_iter()
-        .for_each(|task| on_task(task, msg_sender, &mut pending_requests));
-    log::info!("...tasks have finished");
-    log::info!("joining threadpool...");
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    loop {
+        let now = Instant::now();
+        let timeout = if now < deadline { deadline - now } else { Duration::from_secs(0) };
+        match task_receiver.recv_timeout(timeout) {
+            Ok(task) => on_task(
+                task,
+                msg_sender,
+                &mut pending_requests,
+                &mut FxHashMap::default(),
+            ),
+            Err(RecvTimeoutError::Disconnected) => {
+                log::info!("...tasks have finished");
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                log::warn!("...some tasks are still running, not waiting for them");
+                break;
+            }
+        }
+    }
     drop(pool);
-    log::info!("...threadpool has finished");
 
-    let vfs = Arc::try_unwrap(state.vfs).expect("all snapshots should be dead");
-    let vfs_res = vfs.into_inner().shutdown();
+    retired_vfs.push(state.vfs);
+    loop {
+        shutdown_retired_vfs(&mut retired_vfs);
+        if retired_vfs.is_empty() || Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(VFS_POLL_INTERVAL);
+    }
+    if !retired_vfs.is_empty() {
+        // A task dropping the last snapshot would trip the drop bomb of the
+        // vfs, so keep a reference until the tasks are done with it.
+        log::warn!("vfs is still in use, shutting it down in the background");
+        thread::spawn(move || {
+            while !retired_vfs.is_empty() {
+                thread::sleep(VFS_POLL_INTERVAL);
+                shutdown_retired_vfs(&mut retired_vfs);
+            }
+        });
+    }
 
     main_res?;
-    vfs_res.map_err(|_| format_err!("fs watcher died"))?;
+    ws_res?;
 
     Ok(())
 }
 
+/// Shuts down the vfs instances which were replaced by a workspace reload
+/// and are no longer used by any snapshots.
+fn shutdown_retired_vfs(retired_vfs: &mut Vec<Arc<RwLock<Vfs>>>) {
+    for vfs in mem::replace(retired_vfs, Vec::new()) {
+        match Arc::try_unwrap(vfs) {
+            Ok(vfs) => {
+                if vfs.into_inner().shutdown().is_err() {
+                    log::error!("fs watcher died");
+                }
+            }
+            Err(vfs) => retired_vfs.push(vfs),
+        }
+    }
+}
+
 enum Event {
     Msg(RawMessage),
     Task(Task),
     Vfs(VfsTask),
     Lib(LibraryData),
+    /// The reloaded workspaces, and the id of the request which asked for them.
+    Workspace(u64, std::result::Result<Vec<ProjectWorkspace>, WorkspaceLoadError>),
 }
 
 impl fmt::Debug for Event {
@@ -150,13 +250,13 @@ impl fmt::Debug for Event {
             Event::Task(it) => fmt::Debug::fmt(it, f),
             Event::Vfs(it) => fmt::Debug::fmt(it, f),
             Event::Lib(it) => fmt::Debug::fmt(it, f),
+            Event::Workspace(id, _) => f.debug_tuple("Workspace").field(id).finish(),
         }
     }
 }
 
 fn main_loop_inner(
     internal_mode: bool,
-    supports_decorations: bool,
     pool: &ThreadPool,
     msg_sender: &Sender<RawMessage>,
     msg_receiver: &Receiver<RawMessage>,
@@ -165,13 +265,25 @@ fn main_loop_inner(
     state: &mut ServerWorldState,
     pending_requests: &mut FxHashSet<u64>,
     subs: &mut Subscriptions,
+    retired_vfs: &mut Vec<Arc<RwLock<Vfs>>>,
+    ws_worker: &Worker<PathBuf, std::result::Result<Vec<ProjectWorkspace>, WorkspaceLoadError>>,
+    // The `Cargo.toml` with the diagnostic about the failed workspace load.
+    mut load_error_manifest: Option<PathBuf>,
 ) -> Result<()> {
     // We try not to index more than THREADPOOL_SIZE - 3 libraries at the same
     // time to always have a thread ready to react to input.
     let mut in_flight_libraries = 0;
     let mut pending_libraries = Vec::new();
 
-    let (libdata_sender, libdata_receiver) = unbounded();
+    let (mut libdata_sender, mut libdata_receiver) = unbounded();
+    // The ids of the reload requests, in the order the loader answers them.
+    let mut pending_reloads = VecDeque::new();
+    let request_queue = Arc::new(Mutex::new(RequestQueue::default()));
+    let mut pending_edits = PendingEdits {
+        apply_source_changes: state.supports_snippet_text_edits,
+        ..PendingEdits::default()
+    };
+    let mut known_tests = FxHashMap::default();
     loop {
         log::trace!("selecting");
         let event = select! {
@@ -184,13 +296,17 @@ fn main_loop_inner(
                 Ok(task) => Event::Vfs(task),
                 Err(RecvError) => bail!("vfs died"),
             },
-            recv(libdata_receiver) -> data => Event::Lib(data.unwrap())
+            recv(libdata_receiver) -> data => Event::Lib(data.unwrap()),
+            recv(ws_worker.out) -> ws => {
+                let id = pending_reloads.pop_front().unwrap();
+                Event::Workspace(id, ws.unwrap())
+            }
         };
         log::info!("loop_turn = {:?}", event);
         let start = std::time::Instant::now();
         let mut state_changed = false;
         match event {
-            Event::Task(task) => on_task(task, msg_sender, pending_requests),
+            Event::Task(task) => on_task(task, msg_sender, pending_requests, &mut known_tests),
             Event::Vfs(task) => {
                 state.vfs.write().handle_task(task);
                 state_changed = true;
@@ -200,13 +316,89 @@ fn main_loop_inner(
                 state.add_lib(lib);
                 in_flight_libraries -= 1;
             }
+            Event::Workspace(id, ws) => {
+                let resp = match ws {
+                    Ok(workspaces) => {
+                        if let Some(cargo_toml) = load_error_manifest.take() {
+                            clear_manifest_diagnostics(&cargo_toml, msg_sender);
+                        }
+                        load_error_manifest = report_sysroot_errors(&workspaces, msg_sender);
+                        reload_workspace(state, subs, workspaces, retired_vfs);
+                        // Libraries of the old workspace are not needed anymore.
+                        let (sender, receiver) = unbounded();
+                        libdata_sender = sender;
+                        libdata_receiver = receiver;
+                        in_flight_libraries = 0;
+                        pending_libraries.clear();
+                        // File ids are not stable across reloads.
+                        known_tests.clear();
+                        state_changed = true;
+                        RawResponse::ok::<req::ReloadWorkspace>(id, &())
+                    }
+                    Err(e) => {
+                        if let Some(cargo_toml) = load_error_manifest.take() {
+                            clear_manifest_diagnostics(&cargo_toml, msg_sender);
+                        }
+                        load_error_manifest = report_workspace_load_error(&e, msg_sender);
+                        RawResponse::err(
+                            id,
+                            ErrorCode::InternalError as i32,
+                            format!("failed to reload workspace: {}", e),
+                        )
+                    }
+                };
+                on_task(
+                    Task::Respond(resp),
+                    msg_sender,
+                    pending_requests,
+                    &mut known_tests,
+                );
+            }
             Event::Msg(msg) => match msg {
                 RawMessage::Request(req) => {
                     let req = match handle_shutdown(req, msg_sender) {
                         Some(req) => req,
-                        None => return Ok(()),
+                        None => {
+                            // Nobody is waiting for the results anymore.
+                            request_queue.lock().clear();
+                            return Ok(());
+                        }
                     };
-                    match on_request(state, pending_requests, pool, &task_sender, req)? {
+                    let req = match req.cast::<req::ReloadWorkspace>() {
+                        Ok((id, ())) => {
+                            pending_requests.insert(id);
+                            pending_reloads.push_back(id);
+                            ws_worker.send(state.root.clone()).unwrap();
+                            continue;
+                        }
+                        Err(req) => req,
+                    };
+                    let req = match req.cast::<req::ExecuteCommand>() {
+                        Ok((id, params)) => {
+                            let resp = match source_change_of_command(params) {
+                                Ok(change) => {
+                                    pending_edits.send(change, msg_sender);
+                                    RawResponse::ok::<req::ExecuteCommand>(id, &None)
+                                }
+                                Err(e) => RawResponse::err(
+                                    id,
+                                    ErrorCode::InvalidParams as i32,
+                                    e.to_string(),
+                                ),
+                            };
+                            msg_sender.send(RawMessage::Response(resp)).unwrap();
+                            continue;
+                        }
+                        Err(req) => req,
+                    };
+                    match on_request(
+                        state,
+                        pending_requests,
+                        pool,
+                        &request_queue,
+                        &task_sender,
+                        req,
+                    )? {
                         None => (),
                         Some(req) => {
                             log::error!("unknown request: {:?}", req);
@@ -220,10 +412,23 @@ fn main_loop_inner(
                     }
                 }
                 RawMessage::Notification(not) => {
-                    on_notification(msg_sender, state, pending_requests, subs, not)?;
+                    match not.cast::<req::DidSaveTextDocument>() {
+                        // The diagnostics of all open files are recomputed
+                        // below, as the files depending on the saved one may
+                        // be affected too.
+                        Ok(params) => on_did_save_on_threadpool(
+                            pool,
+                            state.snapshot(),
+                            task_sender.clone(),
+                            params,
+                        ),
+                        Err(not) => {
+                            on_notification(msg_sender, state, pending_requests, subs, not)?
+                        }
+                    }
                     state_changed = true;
                 }
-                RawMessage::Response(resp) => log::error!("unexpected response: {:?}", resp),
+                RawMessage::Response(resp) => pending_edits.on_response(resp, msg_sender),
             },
         };
 
@@ -237,7 +442,8 @@ fn main_loop_inner(
                 log::info!("indexing {:?} ... ", root);
                 let data = LibraryData::prepare(root, files);
                 log::info!("indexed {:?} {:?}", start.elapsed(), root);
-                sender.send(data).unwrap();
+                // The receiver is gone if the workspace was reloaded meanwhile.
+                let _ = sender.send(data);
             });
         }
 
@@ -249,16 +455,207 @@ fn main_loop_inner(
             update_file_notifications_on_threadpool(
                 pool,
                 state.snapshot(),
-                supports_decorations,
                 task_sender.clone(),
                 subs.subscriptions(),
             )
         }
+        if !retired_vfs.is_empty() {
+            shutdown_retired_vfs(retired_vfs);
+        }
         log::info!("loop_turn = {:?}", start.elapsed());
     }
 }
 
-fn on_task(task: Task, msg_sender: &Sender<RawMessage>, pending_requests: &mut FxHashSet<u64>) {
+/// Code actions run the `apply_code_action` command, which asks the server to
+/// apply their source change, so that the server learns whether it applied.
+fn source_change_of_command(params: req::ExecuteCommandParams) -> Result<req::SourceChange> {
+    if params.command != "apply_code_action" {
+        bail!("unknown command: {}", params.command);
+    }
+    let arg = params
+        .arguments
+        .into_iter()
+        .next()
+        .ok_or_else(|| format_err!("missing source change"))?;
+    let change = serde_json::from_value(arg)?;
+    Ok(change)
+}
+
+/// The id of a new request to the client. All the requests the server sends
+/// take their ids from this one counter, so that each response matches only
+/// the request it is for.
+fn next_request_id() -> u64 {
+    static NEXT_REQUEST_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst) as u64
+}
+
+/// Edit requests sent to the client, by the labels of their source changes.
+#[derive(Default)]
+struct PendingEdits {
+    /// Whether the client applies whole source changes, with their snippets
+    /// and cursors, on `m/applySourceChange`. Only the clients which support
+    /// snippet text edits do, the others get a `workspace/applyEdit`.
+    apply_source_changes: bool,
+    labels: FxHashMap<u64, String>,
+}
+
+impl PendingEdits {
+    fn send(&mut self, change: req::SourceChange, msg_sender: &Sender<RawMessage>) {
+        let id = next_request_id();
+        self.labels.insert(id, change.label.clone());
+        let request = if self.apply_source_changes {
+            RawRequest::new::<req::ApplySourceChange>(id, &change)
+        } else {
+            let params = req::ApplyWorkspaceEditParams {
+                edit: change.workspace_edit,
+            };
+            RawRequest::new::<req::ApplyWorkspaceEdit>(id, &params)
+        };
+        msg_sender.send(RawMessage::Request(request)).unwrap();
+    }
+
+    /// Tells the user about the edits which the client has rejected, for
+    /// example because the document has changed since the edit was computed.
+    fn on_response(&mut self, resp: RawResponse, msg_sender: &Sender<RawMessage>) {
+        let label = match self.labels.remove(&resp.id) {
+            Some(it) => it,
+            None => {
+                log::error!("unexpected response: {:?}", resp);
+                return;
+            }
+        };
+        let message = match (resp.error, resp.result) {
+            (Some(error), _) => format!("failed to apply `{}`: {}", label, error.message),
+            (None, result) => {
+                let response: Option<req::ApplyWorkspaceEditResponse> =
+                    result.and_then(|it| serde_json::from_value(it).ok());
+                let applied = response.map_or(false, |it| it.applied);
+                if applied {
+                    return;
+                }
+                format!("`{}` was not applied, the document may have been changed", label)
+            }
+        };
+        log::warn!("{}", message);
+        let params = req::ShowMessageParams {
+            typ: req::MessageType::Error,
+            message,
+        };
+        let not = RawNotification::new::<req::ShowMessage>(&params);
+        msg_sender.send(RawMessage::Notification(not)).unwrap();
+    }
+}
+
+/// Tells the user why the workspace could not be loaded, with a message and
+/// with a diagnostic on the `Cargo.toml`, if the error is about one. Returns
+/// the path of that `Cargo.toml`, so that the diagnostic can be cleared later.
+fn report_workspace_load_error(
+    error: &WorkspaceLoadError,
+    msg_sender: &Sender<RawMessage>,
+) -> Option<PathBuf> {
+    let params = req::ShowMessageParams {
+        typ: req::MessageType::Error,
+        message: error.to_string(),
+    };
+    let not = RawNotification::new::<req::ShowMessage>(&params);
+    msg_sender.send(RawMessage::Notification(not)).unwrap();
+
+    let cargo_toml = error.cargo_toml()?;
+    let uri = Url::from_file_path(cargo_toml).ok()?;
+    let diagnostic = Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        severity: Some(DiagnosticSeverity::Error),
+        code: None,
+        source: Some("rust-analyzer".to_string()),
+        message: error.to_string(),
+        related_information: None,
+    };
+    let params = req::PublishDiagnosticsParams {
+        uri,
+        diagnostics: vec![diagnostic],
+    };
+    let not = RawNotification::new::<req::PublishDiagnostics>(&params);
+    msg_sender.send(RawMessage::Notification(not)).unwrap();
+    Some(cargo_toml.to_path_buf())
+}
+
+/// Reports the workspaces which are loaded without their standard library,
+/// see `report_workspace_load_error`.
+fn report_sysroot_errors(
+    workspaces: &[ProjectWorkspace],
+    msg_sender: &Sender<RawMessage>,
+) -> Option<PathBuf> {
+    let mut res = None;
+    for error in workspaces.iter().filter_map(|ws| ws.sysroot_error.as_ref()) {
+        res = report_workspace_load_error(error, msg_sender).or(res);
+    }
+    res
+}
+
+fn clear_manifest_diagnostics(cargo_toml: &Path, msg_sender: &Sender<RawMessage>) {
+    let uri = match Url::from_file_path(cargo_toml) {
+        Ok(it) => it,
+        Err(()) => return,
+    };
+    let params = req::PublishDiagnosticsParams {
+        uri,
+        diagnostics: Vec::new(),
+    };
+    let not = RawNotification::new::<req::PublishDiagnostics>(&params);
+    msg_sender.send(RawMessage::Notification(not)).unwrap();
+}
+
+/// Replaces the world state with the one built from the reloaded workspace,
+/// carrying over the open files.
+fn reload_workspace(
+    state: &mut ServerWorldState,
+    subs: &mut Subscriptions,
+    workspaces: Vec<ProjectWorkspace>,
+    retired_vfs: &mut Vec<Arc<RwLock<Vfs>>>,
+) {
+    let mut new_state = ServerWorldState::new(
+        state.root.clone(),
+        workspaces,
+        state.resource_operations.to_vec(),
+        state.supports_snippet_text_edits,
+        state.position_encoding,
+        ServerConfig::clone(&state.config),
+    );
+    let mut new_subs = Subscriptions::new();
+    let mut doc_versions = FxHashMap::default();
+    for file_id in subs.subscriptions() {
+        let (path, text) = {
+            let old_vfs = state.vfs.read();
+            let file = VfsFile(file_id.0.into());
+            (old_vfs.file2path(file), old_vfs.file2text(file).to_string())
+        };
+        let is_detached = new_state.vfs.read().is_detached(&path);
+        let new_file_id = if is_detached {
+            new_state.add_detached_file(&path, text)
+        } else {
+            let file = new_state.vfs.write().add_file_overlay(&path, text);
+            file.map(|it| FileId(it.0.into()))
+        };
+        if let Some(new_file_id) = new_file_id {
+            new_subs.add_sub(new_file_id);
+            if let Some(&version) = state.doc_versions.get(&file_id) {
+                doc_versions.insert(new_file_id, version);
+            }
+        }
+    }
+    new_state.doc_versions = Arc::new(doc_versions);
+    new_state.manifests = Arc::clone(&state.manifests);
+    let old_state = mem::replace(state, new_state);
+    *subs = new_subs;
+    retired_vfs.push(old_state.vfs);
+}
+
+fn on_task(
+    task: Task,
+    msg_sender: &Sender<RawMessage>,
+    pending_requests: &mut FxHashSet<u64>,
+    known_tests: &mut FxHashMap<FileId, Vec<String>>,
+) {
     match task {
         Task::Respond(response) => {
             if pending_requests.remove(&response.id) {
@@ -268,6 +665,13 @@ fn on_task(task: Task, msg_sender: &Sender<RawMessage>, pending_requests: &mut F
         Task::Notify(n) => {
             msg_sender.send(RawMessage::Notification(n)).unwrap();
         }
+        Task::Tests(file_id, params) => {
+            let old = known_tests.insert(file_id, params.tests.clone());
+            if old.unwrap_or_default() != params.tests {
+                let not = RawNotification::new::<req::TestsChanged>(&params);
+                msg_sender.send(RawMessage::Notification(not)).unwrap();
+            }
+        }
     }
 }
 
@@ -275,29 +679,52 @@ fn on_request(
     world: &mut ServerWorldState,
     pending_requests: &mut FxHashSet<u64>,
     pool: &ThreadPool,
+    request_queue: &Arc<Mutex<RequestQueue>>,
     sender: &Sender<Task>,
     req: RawRequest,
 ) -> Result<Option<RawRequest>> {
+    // The handlers only know about Rust files, so they would fail to find a
+    // `Cargo.toml` among them.
+    if is_unsupported_manifest_request(&req) {
+        let inserted = pending_requests.insert(req.id);
+        assert!(inserted, "duplicate request: {}", req.id);
+        let resp = RawResponse {
+            id: req.id,
+            result: Some(serde_json::Value::Null),
+            error: None,
+        };
+        sender.send(Task::Respond(resp)).unwrap();
+        return Ok(None);
+    }
     let mut pool_dispatcher = PoolDispatcher {
         req: Some(req),
         res: None,
         pool,
+        request_queue,
         world,
         sender,
     };
     let req = pool_dispatcher
+        .on::<req::AnalyzerStatus>(handlers::handle_analyzer_status)?
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
         .on::<req::ExtendSelection>(handlers::handle_extend_selection)?
+        .on::<req::SelectionRangeRequest>(handlers::handle_selection_range)?
         .on::<req::FindMatchingBrace>(handlers::handle_find_matching_brace)?
         .on::<req::JoinLines>(handlers::handle_join_lines)?
         .on::<req::OnEnter>(handlers::handle_on_enter)?
+        .on::<req::TypeOf>(handlers::handle_type_of)?
         .on::<req::OnTypeFormatting>(handlers::handle_on_type_formatting)?
         .on::<req::DocumentSymbolRequest>(handlers::handle_document_symbol)?
         .on::<req::WorkspaceSymbol>(handlers::handle_workspace_symbol)?
         .on::<req::GotoDefinition>(handlers::handle_goto_definition)?
+        .on::<req::GotoDeclaration>(handlers::handle_goto_declaration)?
         .on::<req::ParentModule>(handlers::handle_parent_module)?
+        .on::<req::ModuleTree>(handlers::handle_module_tree)?
         .on::<req::Runnables>(handlers::handle_runnables)?
+        .on::<req::DiscoverTests>(handlers::handle_discover_tests)?
+        .on::<req::RelatedTests>(handlers::handle_related_tests)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
+        .on::<req::InlayHints>(handlers::handle_inlay_hints)?
         .on::<req::Completion>(handlers::handle_completion)?
         .on::<req::CodeActionRequest>(handlers::handle_code_action)?
         .on::<req::CodeLensRequest>(handlers::handle_code_lens)?
@@ -308,7 +735,10 @@ fn on_request(
         .on::<req::Rename>(handlers::handle_rename)?
         .on::<req::References>(handlers::handle_references)?
         .on::<req::Formatting>(handlers::handle_formatting)?
+        .on::<req::WillSaveWaitUntil>(handlers::handle_will_save_wait_until)?
         .on::<req::DocumentHighlightRequest>(handlers::handle_document_highlight)?
+        .on::<req::DocumentLinkRequest>(handlers::handle_document_link)?
+        .on::<req::DocumentLinkResolve>(handlers::handle_document_link_resolve)?
         .finish();
     match req {
         Ok(id) => {
@@ -320,6 +750,27 @@ fn on_request(
     }
 }
 
+/// Whether the request is about a `Cargo.toml`, and is not one of the few
+/// requests which support manifests. The others get an empty response.
+fn is_unsupported_manifest_request(request: &RawRequest) -> bool {
+    const MANIFEST_REQUESTS: &[&str] = &[
+        req::GotoDefinition::METHOD,
+        req::Completion::METHOD,
+        req::DocumentLinkRequest::METHOD,
+    ];
+    if MANIFEST_REQUESTS.contains(&request.method.as_str()) {
+        return false;
+    }
+    let uri = request
+        .params
+        .get("textDocument")
+        .and_then(|it| it.get("uri"))
+        .and_then(|it| it.as_str())
+        .and_then(|it| Url::parse(it).ok());
+    uri.and_then(|it| it.to_file_path().ok())
+        .map_or(false, |it| cargo_toml::is_manifest(&it))
+}
+
 fn on_notification(
     msg_sender: &Sender<RawMessage>,
     state: &mut ServerWorldState,
@@ -350,15 +801,25 @@ fn on_notification(
     let not = match not.cast::<req::DidOpenTextDocument>() {
         Ok(params) => {
             let uri = params.text_document.uri;
+            let version = params.text_document.version;
             let path = uri
                 .to_file_path()
                 .map_err(|()| format_err!("invalid uri: {}", uri))?;
-            if let Some(file_id) = state
-                .vfs
-                .write()
-                .add_file_overlay(&path, params.text_document.text)
-            {
-                subs.add_sub(FileId(file_id.0.into()));
+            let text = params.text_document.text;
+            if cargo_toml::is_manifest(&path) {
+                Arc::make_mut(&mut state.manifests).insert(path, text);
+                return Ok(());
+            }
+            let is_detached = state.vfs.read().is_detached(&path);
+            let file_id = if is_detached {
+                state.add_detached_file(&path, text)
+            } else {
+                let file_id = state.vfs.write().add_file_overlay(&path, text);
+                file_id.map(|it| FileId(it.0.into()))
+            };
+            if let Some(file_id) = file_id {
+                subs.add_sub(file_id);
+                Arc::make_mut(&mut state.doc_versions).insert(file_id, version);
             }
             return Ok(());
         }
@@ -375,7 +836,20 @@ fn on_notification(
                 .pop()
                 .ok_or_else(|| format_err!("empty changes"))?
                 .text;
-            state.vfs.write().change_file_overlay(path.as_path(), text);
+            if cargo_toml::is_manifest(&path) {
+                Arc::make_mut(&mut state.manifests).insert(path, text);
+                return Ok(());
+            }
+            let mut vfs = state.vfs.write();
+            vfs.change_file_overlay(path.as_path(), text);
+            if let Some(file_id) = vfs.path2file(&path) {
+                let file_id = FileId(file_id.0.into());
+                let doc_versions = Arc::make_mut(&mut state.doc_versions);
+                match params.text_document.version {
+                    Some(version) => doc_versions.insert(file_id, version),
+                    None => doc_versions.remove(&file_id),
+                };
+            }
             return Ok(());
         }
         Err(not) => not,
@@ -386,8 +860,16 @@ fn on_notification(
             let path = uri
                 .to_file_path()
                 .map_err(|()| format_err!("invalid uri: {}", uri))?;
+            // The diagnostics of a manifest are about the workspace, they
+            // stay when it's closed.
+            if cargo_toml::is_manifest(&path) {
+                Arc::make_mut(&mut state.manifests).remove(&path);
+                return Ok(());
+            }
             if let Some(file_id) = state.vfs.write().remove_file_overlay(path.as_path()) {
-                subs.remove_sub(FileId(file_id.0.into()));
+                let file_id = FileId(file_id.0.into());
+                subs.remove_sub(file_id);
+                Arc::make_mut(&mut state.doc_versions).remove(&file_id);
             }
             let params = req::PublishDiagnosticsParams {
                 uri,
@@ -399,6 +881,28 @@ fn on_notification(
         }
         Err(not) => not,
     };
+    let not = match not.cast::<req::DidChangeWatchedFiles>() {
+        Ok(params) => {
+            let mut vfs = state.vfs.write();
+            for change in params.changes {
+                if let Ok(path) = change.uri.to_file_path() {
+                    vfs.notify_changed(&path);
+                }
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
+    let not = match not.cast::<req::DidChangeConfiguration>() {
+        Ok(params) => {
+            match ServerConfig::deserialize(params.settings) {
+                Ok(config) => state.update_config(config),
+                Err(e) => log::error!("invalid configuration: {}", e),
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
     log::error!("unhandled notification: {:?}", not);
     Ok(())
 }
@@ -407,6 +911,7 @@ struct PoolDispatcher<'a> {
     req: Option<RawRequest>,
     res: Option<u64>,
     pool: &'a ThreadPool,
+    request_queue: &'a Arc<Mutex<RequestQueue>>,
     world: &'a ServerWorldState,
     sender: &'a Sender<Task>,
 }
@@ -425,12 +930,18 @@ impl<'a> PoolDispatcher<'a> {
             None => return Ok(self),
             Some(req) => req,
         };
+        if req.method != R::METHOD {
+            self.req = Some(req);
+            return Ok(self);
+        }
+        let priority = Priority::of(&req.method);
+        let key = request_queue::dedup_key(&req);
         match req.cast::<R>() {
             Ok((id, params)) => {
                 let world = self.world.snapshot();
                 let sender = self.sender.clone();
-                self.pool.execute(move || {
-                    let resp = match f(world, params) {
+                let job = Job::new(id, key, move || {
+                    let resp = match catch_panics(|| f(world, params)) {
                         Ok(resp) => RawResponse::ok::<R>(id, &resp),
                         Err(e) => match e.downcast::<LspError>() {
                             Ok(lsp_error) => {
@@ -456,6 +967,23 @@ impl<'a> PoolDispatcher<'a> {
                     let task = Task::Respond(resp);
                     sender.send(task).unwrap();
                 });
+                let shed = self.request_queue.lock().push(priority, job);
+                for id in shed {
+                    let resp = RawResponse::err(
+                        id,
+                        ErrorCode::RequestCanceled as i32,
+                        "superseded by a newer request".to_string(),
+                    );
+                    self.sender.send(Task::Respond(resp)).unwrap();
+                }
+                let request_queue = Arc::clone(self.request_queue);
+                self.pool.execute(move || {
+                    // Not necessarily the job we've just pushed, see `RequestQueue`.
+                    let job = request_queue.lock().pop();
+                    if let Some(job) = job {
+                        job.run();
+                    }
+                });
                 self.res = Some(id);
             }
             Err(req) => self.req = Some(req),
@@ -475,29 +1003,41 @@ impl<'a> PoolDispatcher<'a> {
 fn update_file_notifications_on_threadpool(
     pool: &ThreadPool,
     world: ServerWorld,
-    publish_decorations: bool,
     sender: Sender<Task>,
     subscriptions: Vec<FileId>,
 ) {
     pool.execute(move || {
+        let publish_decorations = world.config.publish_decorations;
         for file_id in subscriptions {
-            match handlers::publish_diagnostics(&world, file_id) {
+            match catch_panics(|| handlers::publish_diagnostics(&world, file_id)) {
                 Err(e) => {
-                    if !is_canceled(&e) {
-                        log::error!("failed to compute diagnostics: {:?}", e);
+                    // Everything else would be canceled as well.
+                    if is_canceled(&e) {
+                        return;
                     }
+                    log::error!("failed to compute diagnostics: {:?}", e);
                 }
                 Ok(params) => {
                     let not = RawNotification::new::<req::PublishDiagnostics>(&params);
                     sender.send(Task::Notify(not)).unwrap();
                 }
             }
+            match catch_panics(|| handlers::tests_of_file(&world, file_id)) {
+                Err(e) => {
+                    if is_canceled(&e) {
+                        return;
+                    }
+                    log::error!("failed to compute tests: {:?}", e);
+                }
+                Ok(params) => sender.send(Task::Tests(file_id, params)).unwrap(),
+            }
             if publish_decorations {
-                match handlers::publish_decorations(&world, file_id) {
+                match catch_panics(|| handlers::publish_decorations(&world, file_id)) {
                     Err(e) => {
-                        if !is_canceled(&e) {
-                            log::error!("failed to compute decorations: {:?}", e);
+                        if is_canceled(&e) {
+                            return;
                         }
+                        log::error!("failed to compute decorations: {:?}", e);
                     }
                     Ok(params) => {
                         let not = RawNotification::new::<req::PublishDecorations>(&params);
@@ -509,6 +1049,25 @@ fn update_file_notifications_on_threadpool(
     });
 }
 
+/// Asks the client to run `cargo check` if it's enabled in the config. The
+/// save actions which edit the document run before the save, see
+/// `handle_will_save_wait_until`.
+fn on_did_save_on_threadpool(
+    pool: &ThreadPool,
+    world: ServerWorld,
+    sender: Sender<Task>,
+    params: req::DidSaveTextDocumentParams,
+) {
+    pool.execute(move || match catch_panics(|| handlers::on_save(&world, params)) {
+        Err(e) => log::error!("failed to run save actions: {:?}", e),
+        Ok(None) => (),
+        Ok(Some(params)) => {
+            let not = RawNotification::new::<req::OnSave>(&params);
+            sender.send(Task::Notify(not)).unwrap();
+        }
+    });
+}
+
 fn feedback(intrnal_mode: bool, msg: &str, sender: &Sender<RawMessage>) {
     if !intrnal_mode {
         return;
@@ -520,3 +1079,137 @@ fn feedback(intrnal_mode: bool, msg: &str, sender: &Sender<RawMessage>) {
 fn is_canceled(e: &failure::Error) -> bool {
     e.downcast_ref::<Canceled>().is_some()
 }
+
+/// Runs a task on a snapshot, turning panics into errors, so that the client
+/// gets a response even if the handler is buggy. Salsa panics with `Canceled`
+/// when the snapshot is used after an edit, these become ordinary
+/// cancellation errors.
+fn catch_panics<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => match payload.downcast::<Canceled>() {
+            Ok(canceled) => Err((*canceled).into()),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|it| it.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(format_err!("handler panicked: {}", message))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use languageserver_types::WorkspaceEdit;
+    use serde_json::json;
+
+    use super::*;
+
+    fn source_change(label: &str) -> req::SourceChange {
+        req::SourceChange {
+            label: label.to_string(),
+            workspace_edit: WorkspaceEdit {
+                changes: None,
+                document_changes: None,
+            },
+            cursor_position: None,
+            snippet_edit: None,
+        }
+    }
+
+    fn sent_request_id(receiver: &Receiver<RawMessage>) -> u64 {
+        match receiver.try_recv().unwrap() {
+            RawMessage::Request(req) => {
+                assert_eq!(req.method, "workspace/applyEdit");
+                req.id
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    fn applied(id: u64, applied: bool) -> RawResponse {
+        RawResponse {
+            id,
+            result: Some(json!({ "applied": applied })),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn pending_edits_match_responses_by_id() {
+        let (sender, receiver) = unbounded();
+        let mut pending_edits = PendingEdits::default();
+        pending_edits.send(source_change("first"), &sender);
+        pending_edits.send(source_change("second"), &sender);
+        let first = sent_request_id(&receiver);
+        let second = sent_request_id(&receiver);
+        assert_ne!(first, second);
+
+        // Responses to other requests are not mistaken for the edits.
+        pending_edits.on_response(applied(second + 1000, false), &sender);
+        assert!(receiver.try_recv().is_err());
+
+        pending_edits.on_response(applied(second, false), &sender);
+        match receiver.try_recv().unwrap() {
+            RawMessage::Notification(not) => {
+                let message = not.params["message"].as_str().unwrap().to_string();
+                assert!(message.contains("`second`"), "{}", message);
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+        pending_edits.on_response(applied(first, true), &sender);
+        assert!(receiver.try_recv().is_err());
+        assert!(pending_edits.labels.is_empty());
+
+        // A request id is never reused.
+        pending_edits.send(source_change("third"), &sender);
+        let third = sent_request_id(&receiver);
+        assert!(third != first && third != second);
+    }
+
+    #[test]
+    fn snippet_clients_apply_whole_source_changes() {
+        let (sender, receiver) = unbounded();
+        let mut pending_edits = PendingEdits {
+            apply_source_changes: true,
+            ..PendingEdits::default()
+        };
+        pending_edits.send(source_change("snippet"), &sender);
+        let id = match receiver.try_recv().unwrap() {
+            RawMessage::Request(req) => {
+                assert_eq!(req.method, "m/applySourceChange");
+                assert_eq!(req.params["label"], "snippet");
+                req.id
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        };
+
+        // The response is checked like the one of `workspace/applyEdit`.
+        pending_edits.on_response(applied(id, false), &sender);
+        match receiver.try_recv().unwrap() {
+            RawMessage::Notification(not) => assert_eq!(not.method, "window/showMessage"),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn retired_vfs_is_shut_down_once_unused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut vfs, _) = Vfs::new(vec![dir.path().to_path_buf()]);
+        let task = vfs.task_receiver().recv().unwrap();
+        vfs.handle_task(task);
+        let vfs = Arc::new(RwLock::new(vfs));
+        let snapshot = Arc::clone(&vfs);
+
+        let mut retired_vfs = vec![vfs];
+        shutdown_retired_vfs(&mut retired_vfs);
+        assert_eq!(retired_vfs.len(), 1);
+
+        drop(snapshot);
+        shutdown_retired_vfs(&mut retired_vfs);
+        assert!(retired_vfs.is_empty());
+    }
+}
@@ -1,30 +1,40 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use gen_lsp_server::ErrorCode;
 use languageserver_types::{
-    CodeActionResponse, CodeLens, Command, Diagnostic, DiagnosticSeverity,
-    DocumentFormattingParams, DocumentHighlight, DocumentSymbol, Documentation, FoldingRange,
-    FoldingRangeKind, FoldingRangeParams, Hover, HoverContents, Location, MarkupContent,
-    MarkupKind, ParameterInformation, ParameterLabel, Position, PrepareRenameResponse, Range,
-    RenameParams, SignatureInformation, SymbolInformation, TextDocumentIdentifier, TextEdit,
-    WorkspaceEdit,
+    CodeActionResponse, CodeLens, Command, Diagnostic, DiagnosticRelatedInformation,
+    DiagnosticSeverity, DocumentFormattingParams, DocumentHighlight, DocumentHighlightKind,
+    DocumentSymbol, Documentation, FoldingRange, FoldingRangeKind, FoldingRangeParams, Hover,
+    HoverContents, InsertTextFormat, Location, MarkupContent, MarkupKind, ParameterInformation,
+    ParameterLabel, Position, PrepareRenameResponse, Range, RenameParams, SignatureInformation,
+    SymbolInformation, TextDocumentIdentifier, TextEdit, Url, WorkspaceEdit,
 };
 use ra_ide_api::{
-    FileId, FilePosition, FileRange, FoldKind, Query, RangeInfo, RunnableKind, Severity,
+    CompletionConfig, CompletionItemKind, FileId, FilePosition, FileRange, FoldKind, ImportStyle,
+    HoverConfig, InlayHintsConfig, LineIndex, LinkTarget, ModuleTreeNode, Query, RangeInfo,
+    ReferenceAccess, ReferencesConfig, Runnable, RunnableKind, Severity, SnippetScope, TestModule,
+    UserSnippet,
 };
-use ra_syntax::{AstNode, TextUnit};
+use ra_syntax::{AstNode, TextRange, TextUnit};
+use ra_text_edit::TextEditBuilder;
 use rustc_hash::FxHashMap;
 use serde_json::to_value;
 use std::io::Write;
 
 use crate::{
-    cargo_target_spec::{runnable_args, CargoTargetSpec},
-    conv::{to_location, to_location_link, Conv, ConvWith, MapConvWith, TryConvWith},
+    cargo_target_spec::{dependent_packages, runnable_args, CargoTargetSpec},
+    cargo_toml,
+    config::{ImportStyleOptions, SnippetScopeOptions},
+    conv::{to_location, to_location_link, Conv, ConvWith, LineMap, MapConvWith, TryConvWith},
     req::{self, Decoration},
     server_world::ServerWorld,
     LspError, Result,
 };
 
+pub fn handle_analyzer_status(world: ServerWorld, _: ()) -> Result<String> {
+    Ok(world.analysis().status())
+}
+
 pub fn handle_syntax_tree(world: ServerWorld, params: req::SyntaxTreeParams) -> Result<String> {
     let id = params.text_document.try_conv_with(&world)?;
     let res = world.analysis().syntax_tree(id);
@@ -36,7 +46,7 @@ pub fn handle_extend_selection(
     params: req::ExtendSelectionParams,
 ) -> Result<req::ExtendSelectionResult> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let selections = params
         .selections
         .into_iter()
@@ -48,13 +58,52 @@ pub fn handle_extend_selection(
     Ok(req::ExtendSelectionResult { selections })
 }
 
+pub fn handle_selection_range(
+    world: ServerWorld,
+    params: req::SelectionRangeParams,
+) -> Result<Vec<req::SelectionRange>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.line_map(file_id);
+    let res = params
+        .positions
+        .into_iter()
+        .map_conv_with(&line_index)
+        .map(|offset| {
+            // Extend the selection until it covers the whole file, then link
+            // the ranges starting from the outermost one.
+            let mut ranges = Vec::new();
+            let mut range = TextRange::from_to(offset, offset);
+            loop {
+                let extended = world.analysis().extend_selection(FileRange { file_id, range });
+                if extended == range || !range.is_subrange(&extended) {
+                    break;
+                }
+                ranges.push(extended);
+                range = extended;
+            }
+            if ranges.is_empty() {
+                ranges.push(range);
+            }
+            let mut res: Option<req::SelectionRange> = None;
+            for range in ranges.into_iter().rev() {
+                res = Some(req::SelectionRange {
+                    range: range.conv_with(&line_index),
+                    parent: res.map(Box::new),
+                });
+            }
+            res.unwrap()
+        })
+        .collect();
+    Ok(res)
+}
+
 pub fn handle_find_matching_brace(
     world: ServerWorld,
     params: req::FindMatchingBraceParams,
 ) -> Result<Vec<Position>> {
     let file_id = params.text_document.try_conv_with(&world)?;
     let file = world.analysis().file_syntax(file_id);
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let res = params
         .offsets
         .into_iter()
@@ -89,12 +138,18 @@ pub fn handle_on_enter(
     }
 }
 
+pub fn handle_type_of(world: ServerWorld, params: req::TypeOfParams) -> Result<Option<String>> {
+    let frange = (&params.text_document, params.range).try_conv_with(&world)?;
+    let res = world.analysis().type_of(frange)?;
+    Ok(res)
+}
+
 pub fn handle_on_type_formatting(
     world: ServerWorld,
     params: req::DocumentOnTypeFormattingParams,
 ) -> Result<Option<Vec<TextEdit>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let position = FilePosition {
         file_id,
         /// in `ra_ide_api`, the `on_type` invariant is that
@@ -124,7 +179,7 @@ pub fn handle_document_symbol(
     params: req::DocumentSymbolParams,
 ) -> Result<Option<req::DocumentSymbolResponse>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
 
     let mut parents: Vec<(DocumentSymbol, Option<usize>)> = Vec::new();
 
@@ -133,7 +188,7 @@ pub fn handle_document_symbol(
             name: symbol.label,
             detail: Some("".to_string()),
             kind: symbol.kind.conv(),
-            deprecated: None,
+            deprecated: Some(symbol.deprecated),
             range: symbol.node_range.conv_with(&line_index),
             selection_range: symbol.navigation_range.conv_with(&line_index),
             children: None,
@@ -162,41 +217,63 @@ pub fn handle_workspace_symbol(
     params: req::WorkspaceSymbolParams,
 ) -> Result<Option<Vec<SymbolInformation>>> {
     let all_symbols = params.query.contains('#');
-    let libs = params.query.contains('*');
+    // Dependencies are searched only on request, as they are usually much
+    // bigger than the workspace.
+    let libraries = params.query.contains('*') || world.config.symbol_search_libraries;
+    let text: String = params
+        .query
+        .chars()
+        .filter(|&c| c != '#' && c != '*')
+        .collect();
+    // Both queries share the budget.
+    let deadline = world.request_deadline();
     let query = {
-        let query: String = params
-            .query
-            .chars()
-            .filter(|&c| c != '#' && c != '*')
-            .collect();
-        let mut q = Query::new(query);
+        let mut q = Query::new(text.clone());
         if !all_symbols {
             q.only_types();
         }
-        if libs {
-            q.libs();
-        }
+        q.libraries(libraries);
         q.limit(128);
+        if let Some(deadline) = deadline {
+            q.deadline(deadline);
+        }
         q
     };
     let mut res = exec_query(&world, query)?;
     if res.is_empty() && !all_symbols {
-        let mut query = Query::new(params.query);
+        let mut query = Query::new(text);
+        query.libraries(libraries);
         query.limit(128);
+        if let Some(deadline) = deadline {
+            query.deadline(deadline);
+        }
         res = exec_query(&world, query)?;
     }
 
     return Ok(Some(res));
 
     fn exec_query(world: &ServerWorld, query: Query) -> Result<Vec<SymbolInformation>> {
+        let symbols = world.analysis().symbol_search_partial(query)?;
+        // The protocol can't mark the response as incomplete, the client
+        // gets the rest on the next query, once the index is warm.
+        if symbols.is_incomplete {
+            log::info!("workspace symbol search exceeded the time budget");
+        }
         let mut res = Vec::new();
-        for nav in world.analysis().symbol_search(query)? {
+        for nav in symbols.value {
+            // The picker shows the container next to the name, so that's where
+            // the summary of a module's docs goes too.
+            let container_name = match (nav.container_name(), nav.docs_summary()) {
+                (Some(container), Some(docs)) => Some(format!("{} - {}", container, docs)),
+                (Some(container), None) => Some(container.to_string()),
+                (None, docs) => docs.map(|it| it.to_string()),
+            };
             let info = SymbolInformation {
                 name: nav.name().to_string(),
                 kind: nav.kind().conv(),
                 location: nav.try_conv_with(world)?,
-                container_name: None,
-                deprecated: None,
+                container_name,
+                deprecated: Some(nav.deprecated()),
             };
             res.push(info);
         }
@@ -208,8 +285,18 @@ pub fn handle_goto_definition(
     world: ServerWorld,
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<req::GotoDefinitionResponse>> {
+    if let Some((path, text)) = world.manifest(&params.text_document.uri) {
+        let line_index = manifest_line_map(&world, &text);
+        let offset = params.position.conv_with(&line_index);
+        let target = cargo_toml::manifest_path_at(&text, offset).and_then(|it| it.target(&path));
+        let location = match target.and_then(|it| Url::from_file_path(it).ok()) {
+            Some(uri) => Location::new(uri, Range::default()),
+            None => return Ok(None),
+        };
+        return Ok(Some(req::GotoDefinitionResponse::Scalar(location)));
+    }
     let position = params.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(position.file_id);
+    let line_index = world.line_map(position.file_id);
     let nav_info = match world.analysis().goto_definition(position)? {
         None => return Ok(None),
         Some(it) => it,
@@ -224,6 +311,26 @@ pub fn handle_goto_definition(
     Ok(Some(req::GotoDefinitionResponse::Link(res)))
 }
 
+pub fn handle_goto_declaration(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::GotoDefinitionResponse>> {
+    let position = params.try_conv_with(&world)?;
+    let line_index = world.line_map(position.file_id);
+    let nav_info = match world.analysis().goto_declaration(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let nav_range = nav_info.range;
+    let res = nav_info
+        .info
+        .into_iter()
+        .map(|nav| RangeInfo::new(nav_range, nav))
+        .map(|nav| to_location_link(&nav, &world, &line_index))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(req::GotoDefinitionResponse::Link(res)))
+}
+
 pub fn handle_parent_module(
     world: ServerWorld,
     params: req::TextDocumentPositionParams,
@@ -237,12 +344,48 @@ pub fn handle_parent_module(
         .collect::<Result<Vec<_>>>()
 }
 
+pub fn handle_module_tree(
+    world: ServerWorld,
+    params: req::ModuleTreeParams,
+) -> Result<Option<req::ModuleTreeNode>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let crate_id = match world.analysis().crate_for(file_id)?.first() {
+        Some(&crate_id) => crate_id,
+        None => return Ok(None),
+    };
+    match world.analysis().module_tree(crate_id)? {
+        Some(tree) => Ok(Some(to_lsp_module_tree(&world, tree)?)),
+        None => Ok(None),
+    }
+}
+
+fn to_lsp_module_tree(world: &ServerWorld, node: ModuleTreeNode) -> Result<req::ModuleTreeNode> {
+    let nav = &node.navigation_target;
+    let line_index = world.line_map(nav.file_id());
+    let name = if nav.name().is_empty() {
+        "crate".to_string()
+    } else {
+        nav.name().to_string()
+    };
+    let children = node
+        .children
+        .into_iter()
+        .map(|child| to_lsp_module_tree(world, child))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(req::ModuleTreeNode {
+        name,
+        uri: nav.file_id().try_conv_with(world)?,
+        range: nav.full_range().conv_with(&line_index),
+        children,
+    })
+}
+
 pub fn handle_runnables(
     world: ServerWorld,
     params: req::RunnablesParams,
 ) -> Result<Vec<req::Runnable>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let offset = params.position.map(|it| it.conv_with(&line_index));
     let mut res = Vec::new();
     for runnable in world.analysis().runnables(file_id)? {
@@ -252,47 +395,153 @@ pub fn handle_runnables(
             }
         }
 
-        let args = runnable_args(&world, file_id, &runnable.kind)?;
-
-        let r = req::Runnable {
-            range: runnable.range.conv_with(&line_index),
-            label: match &runnable.kind {
-                RunnableKind::Test { name } => format!("test {}", name),
-                RunnableKind::TestMod { path } => format!("test-mod {}", path),
-                RunnableKind::Bench { name } => format!("bench {}", name),
-                RunnableKind::Bin => "run binary".to_string(),
-            },
-            bin: "cargo".to_string(),
-            args,
-            env: {
-                let mut m = FxHashMap::default();
-                m.insert("RUST_BACKTRACE".to_string(), "short".to_string());
-                m
-            },
-        };
-        res.push(r);
+        res.push(to_lsp_runnable(&world, file_id, runnable)?);
     }
-    let mut check_args = vec!["check".to_string()];
+    // Always add `cargo check`.
+    res.push(cargo_check_runnable(&world, file_id)?);
+    return Ok(res);
+}
+
+/// The `cargo check` of the package of the file and of the packages which
+/// depend on it.
+fn cargo_check_runnable(world: &ServerWorld, file_id: FileId) -> Result<req::Runnable> {
+    let check_command = &world.config.cargo_check_command;
+    let mut check_args = vec![check_command.clone()];
     let label;
-    match CargoTargetSpec::for_file(&world, file_id)? {
+    match CargoTargetSpec::for_file(world, file_id)? {
         Some(spec) => {
-            label = format!("cargo check -p {}", spec.package);
-            spec.push_to(&mut check_args);
+            let packages = dependent_packages(world, file_id)?;
+            if packages.len() > 1 {
+                // The change may break the packages which depend on this one,
+                // so check them too, but not the whole workspace.
+                let flags = packages
+                    .iter()
+                    .map(|it| format!("-p {}", it))
+                    .collect::<Vec<_>>();
+                label = format!("cargo {} {}", check_command, flags.join(" "));
+                for package in packages {
+                    check_args.push("--package".to_string());
+                    check_args.push(package);
+                }
+            } else {
+                label = format!("cargo {} -p {}", check_command, spec.package);
+                spec.push_to(&mut check_args);
+            }
         }
         None => {
-            label = "cargo check --all".to_string();
+            label = format!("cargo {} --all", check_command);
             check_args.push("--all".to_string())
         }
     }
-    // Always add `cargo check`.
-    res.push(req::Runnable {
+    check_args.extend(world.config.runnables.cargo_args.iter().cloned());
+    Ok(req::Runnable {
         range: Default::default(),
         label,
         bin: "cargo".to_string(),
         args: check_args,
-        env: FxHashMap::default(),
-    });
-    return Ok(res);
+        env: world.config.runnables.env.clone(),
+    })
+}
+
+fn to_lsp_runnable(
+    world: &ServerWorld,
+    file_id: FileId,
+    runnable: Runnable,
+) -> Result<req::Runnable> {
+    let line_index = world.line_map(file_id);
+    let args = runnable_args(world, file_id, &runnable.kind)?;
+    Ok(req::Runnable {
+        range: runnable.range.conv_with(&line_index),
+        label: runnable_label(&runnable.kind),
+        bin: "cargo".to_string(),
+        args,
+        env: {
+            let mut m = FxHashMap::default();
+            m.insert("RUST_BACKTRACE".to_string(), "short".to_string());
+            m.extend(world.config.runnables.env.clone());
+            m
+        },
+    })
+}
+
+fn runnable_label(kind: &RunnableKind) -> String {
+    match kind {
+        RunnableKind::Test { name } => format!("test {}", name),
+        RunnableKind::TestMod { path } => format!("test-mod {}", path),
+        RunnableKind::Bench { name } => format!("bench {}", name),
+        RunnableKind::Bin => "run binary".to_string(),
+    }
+}
+
+pub fn handle_discover_tests(world: ServerWorld, _params: ()) -> Result<Vec<req::TestCrate>> {
+    let mut res = Vec::new();
+    for ws in world.workspaces.iter() {
+        for pkg in ws.cargo.packages().filter(|it| it.is_member(&ws.cargo)) {
+            for tgt in pkg.targets(&ws.cargo) {
+                let file = match world.vfs.read().path2file(tgt.root(&ws.cargo)) {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let file_id = FileId(file.0.into());
+                for crate_id in world.analysis().crate_for(file_id)? {
+                    if world.analysis().crate_root(crate_id)? != file_id {
+                        continue;
+                    }
+                    if let Some(root) = world.analysis().crate_tests(crate_id)? {
+                        res.push(req::TestCrate {
+                            package: pkg.name(&ws.cargo).to_string(),
+                            target: tgt.name(&ws.cargo).to_string(),
+                            root: to_lsp_test_module(&world, root)?,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+pub fn handle_related_tests(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Vec<req::RelatedTest>> {
+    let position = params.try_conv_with(&world)?;
+    let mut res = Vec::new();
+    for test in world.analysis().related_tests(position)? {
+        let nav = test.navigation_target;
+        let line_index = world.line_map(nav.file_id());
+        let runnable = Runnable {
+            range: nav.full_range(),
+            kind: RunnableKind::Test { name: test.path },
+        };
+        res.push(req::RelatedTest {
+            location: to_location(nav.file_id(), nav.full_range(), &world, &line_index)?,
+            runnable: to_lsp_runnable(&world, nav.file_id(), runnable)?,
+        });
+    }
+    Ok(res)
+}
+
+fn to_lsp_test_module(world: &ServerWorld, module: TestModule) -> Result<req::TestModule> {
+    let file_id = module.navigation_target.file_id();
+    let line_index = world.line_map(file_id);
+    let tests = module
+        .tests
+        .into_iter()
+        .map(|it| to_lsp_runnable(world, file_id, it))
+        .collect::<Result<Vec<_>>>()?;
+    let children = module
+        .children
+        .into_iter()
+        .map(|child| to_lsp_test_module(world, child))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(req::TestModule {
+        path: module.path,
+        uri: file_id.try_conv_with(world)?,
+        range: module.navigation_target.full_range().conv_with(&line_index),
+        tests,
+        children,
+    })
 }
 
 pub fn handle_decorations(
@@ -303,53 +552,168 @@ pub fn handle_decorations(
     highlight(&world, file_id)
 }
 
+pub fn handle_inlay_hints(
+    world: ServerWorld,
+    params: req::InlayHintsParams,
+) -> Result<Vec<req::InlayHint>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.line_map(file_id);
+    let config = InlayHintsConfig {
+        parameter_hints: world.config.inlay_hints.parameter_hints,
+        chaining_hints: world.config.inlay_hints.chaining_hints,
+    };
+    let res = world
+        .analysis()
+        .inlay_hints(file_id, &config)?
+        .into_iter()
+        .map(|hint| req::InlayHint {
+            range: hint.range.conv_with(&line_index),
+            kind: hint.kind.conv(),
+            label: hint.label.to_string(),
+        })
+        .collect();
+    Ok(res)
+}
+
 pub fn handle_completion(
     world: ServerWorld,
     params: req::CompletionParams,
 ) -> Result<Option<req::CompletionResponse>> {
+    if let Some((path, text)) = world.manifest(&params.text_document.uri) {
+        return Ok(manifest_completion(&world, &path, &text, params.position));
+    }
     let position = {
         let file_id = params.text_document.try_conv_with(&world)?;
-        let line_index = world.analysis().file_line_index(file_id);
+        let line_index = world.line_map(file_id);
         let offset = params.position.conv_with(&line_index);
         FilePosition { file_id, offset }
     };
+    let trigger_character = params
+        .context
+        .and_then(|ctx| ctx.trigger_character)
+        .unwrap_or_default();
     let completion_triggered_after_single_colon = {
         let mut res = false;
-        if let Some(ctx) = params.context {
-            if ctx.trigger_character.unwrap_or_default() == ":" {
-                let source_file = world.analysis().file_syntax(position.file_id);
-                let syntax = source_file.syntax();
-                let text = syntax.text();
-                if let Some(next_char) = text.char_at(position.offset) {
-                    let diff = TextUnit::of_char(next_char) + TextUnit::of_char(':');
-                    let prev_char = position.offset - diff;
-                    if text.char_at(prev_char) != Some(':') {
-                        res = true;
-                    }
+        if trigger_character == ":" {
+            let source_file = world.analysis().file_syntax(position.file_id);
+            let syntax = source_file.syntax();
+            let text = syntax.text();
+            if let Some(next_char) = text.char_at(position.offset) {
+                let diff = TextUnit::of_char(next_char) + TextUnit::of_char(':');
+                let prev_char = position.offset - diff;
+                if text.char_at(prev_char) != Some(':') {
+                    res = true;
                 }
             }
         }
         res
     };
-    if completion_triggered_after_single_colon {
+    // `<` starts generic arguments only right after a path, like in `Vec<` or
+    // in `iter::<`. Otherwise, it's most likely a comparison.
+    let completion_triggered_after_less_than = trigger_character == "<" && {
+        let text = world.analysis().file_text(position.file_id);
+        // The offset is after the `<`, unless the client sent a bogus one.
+        let prev_char = position
+            .offset
+            .to_usize()
+            .checked_sub('<'.len_utf8())
+            .and_then(|lt_offset| text.get(..lt_offset))
+            .and_then(|it| it.chars().next_back());
+        prev_char.map_or(true, |c| !(c.is_alphanumeric() || c == '_' || c == ':'))
+    };
+    if completion_triggered_after_single_colon || completion_triggered_after_less_than {
         return Ok(None);
     }
 
-    let items = match world.analysis().completions(position)? {
+    let config = CompletionConfig {
+        user_snippets: world
+            .config
+            .snippets
+            .iter()
+            .map(|it| UserSnippet {
+                label: it.label.clone(),
+                body: it.body.clone(),
+                requires: it.requires.clone(),
+                scope: match it.scope {
+                    SnippetScopeOptions::Expr => SnippetScope::Expr,
+                    SnippetScopeOptions::Item => SnippetScope::Item,
+                    SnippetScopeOptions::Type => SnippetScope::Type,
+                },
+            })
+            .collect(),
+        import_style: import_style(&world),
+    };
+    let items = match world.analysis().completions(position, &config)? {
         None => return Ok(None),
         Some(items) => items,
     };
-    let items = items.into_iter().map(|item| item.conv()).collect();
+    let line_index = world.line_map(position.file_id);
+    let snippets = world.config.completion_snippets;
+    // The user asked for their own snippets, so these are kept, just without
+    // the placeholders, when the built-in ones are turned off.
+    let items = items
+        .into_iter()
+        .filter(|item| {
+            snippets || item.kind() != Some(CompletionItemKind::Snippet) || item.is_user_snippet()
+        })
+        .map(|item| {
+            let additional_text_edits =
+                item.additional_text_edit().map(|it| it.clone().conv_with(&line_index));
+            let mut res = item.conv();
+            res.additional_text_edits = additional_text_edits;
+            if let (false, Some(InsertTextFormat::Snippet)) = (snippets, res.insert_text_format) {
+                res.insert_text = res.insert_text.map(|it| snippet_to_plain_text(&it));
+                res.insert_text_format = Some(InsertTextFormat::PlainText);
+            }
+            res
+        })
+        .collect();
 
     Ok(Some(req::CompletionResponse::Array(items)))
 }
 
+/// Drops tab stops and keeps the default text of placeholders, so that
+/// `foo(${1:x})$0` becomes `foo(x)`.
+fn snippet_to_plain_text(snippet: &str) -> String {
+    let mut res = String::with_capacity(snippet.len());
+    // The number of placeholders around the current char: only their closing
+    // braces are dropped, the other braces are text.
+    let mut depth = 0;
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => res.extend(chars.next()),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                // Skip the tab stop number and the colon.
+                while let Some(c) = chars.next() {
+                    if c == ':' {
+                        depth += 1;
+                        break;
+                    }
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            '$' => {
+                while chars.peek().map_or(false, |it| it.is_ascii_digit()) {
+                    chars.next();
+                }
+            }
+            '}' if depth > 0 => depth -= 1,
+            c => res.push(c),
+        }
+    }
+    res
+}
+
 pub fn handle_folding_range(
     world: ServerWorld,
     params: FoldingRangeParams,
 ) -> Result<Option<Vec<FoldingRange>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
 
     let res = Some(
         world
@@ -417,16 +781,20 @@ pub fn handle_hover(
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<Hover>> {
     let position = params.try_conv_with(&world)?;
-    let info = match world.analysis().hover(position)? {
+    let config = HoverConfig {
+        implemented_traits: world.config.hover.implemented_traits,
+        max_implemented_traits: world.config.hover.max_implemented_traits,
+    };
+    let info = match world.analysis().hover(position, &config)? {
         None => return Ok(None),
         Some(info) => info,
     };
-    let line_index = world.analysis.file_line_index(position.file_id);
+    let line_index = world.line_map(position.file_id);
     let range = info.range.conv_with(&line_index);
     let res = Hover {
         contents: HoverContents::Markup(MarkupContent {
             kind: MarkupKind::Markdown,
-            value: info.info,
+            value: info.info.to_markup(),
         }),
         range: Some(range),
     };
@@ -439,24 +807,21 @@ pub fn handle_prepare_rename(
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<PrepareRenameResponse>> {
     let position = params.try_conv_with(&world)?;
-
-    // We support renaming references like handle_rename does.
-    // In the future we may want to reject the renaming of things like keywords here too.
-    let refs = world.analysis().find_all_refs(position)?;
-    let r = match refs.first() {
-        Some(r) => r,
-        None => return Ok(None),
-    };
-    let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
-    let loc = to_location(r.0, r.1, &world, &line_index)?;
-
-    Ok(Some(PrepareRenameResponse::Range(loc.range)))
+    let frange = world
+        .analysis()
+        .prepare_rename(position)?
+        .map_err(|err| LspError::new(ErrorCode::InvalidParams as i32, err.to_string()))?;
+    let line_index = world.line_map(frange.file_id);
+    let text = world.analysis().file_text(frange.file_id);
+    Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+        range: frange.range.conv_with(&line_index),
+        placeholder: text.as_str()[frange.range].to_string(),
+    }))
 }
 
 pub fn handle_rename(world: ServerWorld, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let offset = params.position.conv_with(&line_index);
 
     if params.new_name.is_empty() {
@@ -469,15 +834,17 @@ pub fn handle_rename(world: ServerWorld, params: RenameParams) -> Result<Option<
 
     let renames = world
         .analysis()
-        .rename(FilePosition { file_id, offset }, &*params.new_name)?;
+        .rename(FilePosition { file_id, offset }, &*params.new_name)?
+        .map_err(|err| LspError::new(ErrorCode::InvalidParams as i32, err.to_string()))?;
     if renames.is_empty() {
         return Ok(None);
     }
 
     let mut changes = HashMap::new();
     for edit in renames {
+        let line_index = world.line_map(edit.file_id);
         changes
-            .entry(file_id.try_conv_with(&world)?)
+            .entry(edit.file_id.try_conv_with(&world)?)
             .or_insert_with(Vec::new)
             .extend(edit.edit.conv_with(&line_index));
     }
@@ -495,16 +862,30 @@ pub fn handle_references(
     params: req::ReferenceParams,
 ) -> Result<Option<Vec<Location>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let offset = params.position.conv_with(&line_index);
 
+    // The protocol has no way to exclude imports or to flag the textual
+    // matches in macros. It can't mark the response as incomplete either, so
+    // there is no deadline: a truncated list of references is misleading.
+    let config = ReferencesConfig {
+        include_declaration: params.context.include_declaration,
+        include_imports: true,
+        include_macro_text: true,
+        deadline: None,
+        current_file_only: false,
+    };
     let refs = world
         .analysis()
-        .find_all_refs(FilePosition { file_id, offset })?;
+        .find_all_refs_partial(FilePosition { file_id, offset }, &config)?;
 
     Ok(Some(
-        refs.into_iter()
-            .filter_map(|r| to_location(r.0, r.1, &world, &line_index).ok())
+        refs.value
+            .into_iter()
+            .filter_map(|r| {
+                let line_index = world.line_map(r.file_id);
+                to_location(r.file_id, r.range, &world, &line_index).ok()
+            })
             .collect(),
     ))
 }
@@ -516,16 +897,61 @@ pub fn handle_formatting(
     let file_id = params.text_document.try_conv_with(&world)?;
     let file = world.analysis().file_text(file_id);
 
-    let file_line_index = world.analysis().file_line_index(file_id);
-    let end_position = TextUnit::of_str(&file).conv_with(&file_line_index);
+    let file_line_index = world.line_map(file_id);
+    // Only the changed parts are replaced, to keep the cursors and the folds.
+    let mut edit = TextEditBuilder::default();
+    let range = TextRange::offset_len(0.into(), TextUnit::of_str(&file));
+    edit.replace_with_diff(range, &file, &rustfmt(&file)?);
+    Ok(Some(edit.finish().conv_with(&file_line_index)))
+}
 
+/// Runs the save actions which edit the document, before it is saved, so that
+/// the saved document is the edited one. Organizing imports and formatting
+/// are combined into a single edit.
+pub fn handle_will_save_wait_until(
+    world: ServerWorld,
+    params: req::WillSaveTextDocumentParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    // Auto saves happen while typing, editing the document then would get in
+    // the way.
+    if let req::TextDocumentSaveReason::AfterDelay = params.reason {
+        return Ok(None);
+    }
+    let options = &world.config.on_save;
+    if !options.organize_imports && !options.rustfmt {
+        return Ok(None);
+    }
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let text = world.analysis().file_text(file_id);
+    let mut new_text = text.to_string();
+    if options.organize_imports {
+        if let Some(change) = world.analysis().organize_imports(file_id) {
+            for edit in change.source_file_edits {
+                new_text = edit.edit.apply(&new_text);
+            }
+        }
+    }
+    if options.rustfmt {
+        new_text = rustfmt(&new_text)?;
+    }
+    if new_text == *text {
+        return Ok(None);
+    }
+    let line_index = world.line_map(file_id);
+    let mut edit = TextEditBuilder::default();
+    let range = TextRange::offset_len(0.into(), TextUnit::of_str(&text));
+    edit.replace_with_diff(range, &text, &new_text);
+    Ok(Some(edit.finish().conv_with(&line_index)))
+}
+
+fn rustfmt(text: &str) -> Result<String> {
     use std::process;
     let mut rustfmt = process::Command::new("rustfmt")
         .stdin(process::Stdio::piped())
         .stdout(process::Stdio::piped())
         .spawn()?;
 
-    rustfmt.stdin.as_mut().unwrap().write_all(file.as_bytes())?;
+    rustfmt.stdin.as_mut().unwrap().write_all(text.as_bytes())?;
 
     let output = rustfmt.wait_with_output()?;
     let captured_stdout = String::from_utf8(output.stdout)?;
@@ -536,11 +962,15 @@ pub fn handle_formatting(
             captured_stdout,
         );
     }
+    Ok(captured_stdout)
+}
 
-    Ok(Some(vec![TextEdit {
-        range: Range::new(Position::new(0, 0), end_position),
-        new_text: captured_stdout,
-    }]))
+/// How the completions and assists which need an import add it.
+fn import_style(world: &ServerWorld) -> ImportStyle {
+    match world.config.import_style {
+        ImportStyleOptions::Plain => ImportStyle::Plain,
+        ImportStyleOptions::Nested => ImportStyle::Nested,
+    }
 }
 
 pub fn handle_code_action(
@@ -548,20 +978,20 @@ pub fn handle_code_action(
     params: req::CodeActionParams,
 ) -> Result<Option<CodeActionResponse>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let range = params.range.conv_with(&line_index);
 
     let assists = world
         .analysis()
-        .assists(FileRange { file_id, range })?
+        .assists(FileRange { file_id, range }, import_style(&world))?
         .into_iter();
     let fixes = world
         .analysis()
         .diagnostics(file_id)?
         .into_iter()
-        .filter_map(|d| Some((d.range, d.fix?)))
-        .filter(|(diag_range, _fix)| diag_range.intersection(&range).is_some())
-        .map(|(_range, fix)| fix);
+        .filter(|d| d.range.intersection(&range).is_some())
+        .flat_map(|d| d.fixes)
+        .filter(|fix| fix.file_system_edits.iter().all(|it| world.supports_file_system_edit(it)));
 
     let mut res = Vec::new();
     for source_edit in assists.chain(fixes) {
@@ -569,7 +999,7 @@ pub fn handle_code_action(
         let edit = source_edit.try_conv_with(&world)?;
         let cmd = Command {
             title,
-            command: "ra-lsp.applySourceChange".to_string(),
+            command: "apply_code_action".to_string(),
             arguments: Some(vec![to_value(edit).unwrap()]),
         };
         res.push(cmd);
@@ -583,7 +1013,7 @@ pub fn handle_code_lens(
     params: req::CodeLensParams,
 ) -> Result<Option<Vec<CodeLens>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
 
     let mut lenses: Vec<CodeLens> = Default::default();
 
@@ -629,39 +1059,169 @@ pub fn handle_document_highlight(
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<Vec<DocumentHighlight>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
 
-    let refs = world
-        .analysis()
-        .find_all_refs(params.try_conv_with(&world)?)?;
+    let config = ReferencesConfig {
+        current_file_only: true,
+        ..ReferencesConfig::default()
+    };
+    let refs = world.analysis().find_all_refs(params.try_conv_with(&world)?, &config)?;
 
     Ok(Some(
         refs.into_iter()
             .map(|r| DocumentHighlight {
-                range: r.1.conv_with(&line_index),
-                kind: None,
+                range: r.range.conv_with(&line_index),
+                kind: r.access.map(|access| match access {
+                    ReferenceAccess::Read => DocumentHighlightKind::Read,
+                    ReferenceAccess::Write => DocumentHighlightKind::Write,
+                }),
             })
             .collect(),
     ))
 }
 
+pub fn handle_document_link(
+    world: ServerWorld,
+    params: req::DocumentLinkParams,
+) -> Result<Option<Vec<req::DocumentLink>>> {
+    if let Some((path, text)) = world.manifest(&params.text_document.uri) {
+        return Ok(Some(manifest_links(&world, &path, &text)));
+    }
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.line_map(file_id);
+    let res = world
+        .analysis()
+        .document_links(file_id)?
+        .into_iter()
+        .map(|link| {
+            // URLs are ready as is, files are only looked up on resolve.
+            let (target, data) = match link.target {
+                LinkTarget::Url(url) => (Some(url), None),
+                LinkTarget::File => {
+                    let data = req::DocumentLinkData {
+                        text_document: params.text_document.clone(),
+                    };
+                    (None, Some(data))
+                }
+            };
+            req::DocumentLink {
+                range: link.range.conv_with(&line_index),
+                target,
+                data,
+            }
+        })
+        .collect();
+    Ok(Some(res))
+}
+
+/// Links from the `path` dependencies and the workspace members of a
+/// `Cargo.toml` to the manifests of the packages.
+fn manifest_links(world: &ServerWorld, path: &Path, text: &str) -> Vec<req::DocumentLink> {
+    let line_index = manifest_line_map(world, text);
+    cargo_toml::manifest_paths(text)
+        .into_iter()
+        .filter_map(|it| {
+            let target = Url::from_file_path(it.target(path)?).ok()?;
+            Some(req::DocumentLink {
+                range: it.range.conv_with(&line_index),
+                target: Some(target.to_string()),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+/// Completes the packages below the manifest, in the `members` of the
+/// `[workspace]` table.
+fn manifest_completion(
+    world: &ServerWorld,
+    path: &Path,
+    text: &str,
+    position: Position,
+) -> Option<req::CompletionResponse> {
+    let line_index = manifest_line_map(world, text);
+    let offset = position.conv_with(&line_index);
+    let member = cargo_toml::manifest_path_at(text, offset)
+        .filter(|it| it.kind == cargo_toml::ManifestPathKind::Member)?;
+    let range = member.range.conv_with(&line_index);
+    let items = cargo_toml::member_candidates(path, text)
+        .into_iter()
+        .map(|candidate| languageserver_types::CompletionItem {
+            label: candidate.clone(),
+            kind: Some(languageserver_types::CompletionItemKind::Folder),
+            text_edit: Some(TextEdit::new(range, candidate)),
+            ..Default::default()
+        })
+        .collect();
+    Some(req::CompletionResponse::Array(items))
+}
+
+/// Manifests are not a part of the analysis, so their line index is built on
+/// demand.
+fn manifest_line_map(world: &ServerWorld, text: &str) -> LineMap {
+    LineMap {
+        index: Arc::new(LineIndex::new(text)),
+        encoding: world.position_encoding,
+    }
+}
+
+pub fn handle_document_link_resolve(
+    world: ServerWorld,
+    link: req::DocumentLink,
+) -> Result<req::DocumentLink> {
+    let data = match &link.data {
+        Some(it) => it,
+        None => return Ok(link),
+    };
+    let file_id = data.text_document.try_conv_with(&world)?;
+    let line_index = world.line_map(file_id);
+    let range = link.range.conv_with(&line_index);
+    let target = match world.analysis().resolve_document_link(FileRange { file_id, range })? {
+        Some(it) => it,
+        None => {
+            return Err(LspError::new(
+                ErrorCode::InvalidParams as i32,
+                "the target of the link was not found".to_string(),
+            )
+            .into());
+        }
+    };
+    Ok(req::DocumentLink {
+        range: link.range,
+        target: Some(target.try_conv_with(&world)?.to_string()),
+        data: None,
+    })
+}
+
 pub fn publish_diagnostics(
     world: &ServerWorld,
     file_id: FileId,
 ) -> Result<req::PublishDiagnosticsParams> {
     let uri = world.file_id_to_uri(file_id)?;
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let diagnostics = world
         .analysis()
         .diagnostics(file_id)?
         .into_iter()
-        .map(|d| Diagnostic {
-            range: d.range.conv_with(&line_index),
-            severity: Some(to_diagnostic_severity(d.severity)),
-            code: None,
-            source: Some("rust-analyzer".to_string()),
-            message: d.message,
-            related_information: None,
+        .map(|d| {
+            let related = d
+                .related
+                .into_iter()
+                .filter_map(|(frange, message)| {
+                    let line_index = world.line_map(frange.file_id);
+                    let location =
+                        to_location(frange.file_id, frange.range, world, &line_index).ok()?;
+                    Some(DiagnosticRelatedInformation { location, message })
+                })
+                .collect::<Vec<_>>();
+            Diagnostic {
+                range: d.range.conv_with(&line_index),
+                severity: Some(to_diagnostic_severity(d.severity)),
+                code: None,
+                source: Some("rust-analyzer".to_string()),
+                message: d.message,
+                related_information: if related.is_empty() { None } else { Some(related) },
+            }
         })
         .collect();
     Ok(req::PublishDiagnosticsParams { uri, diagnostics })
@@ -678,8 +1238,41 @@ pub fn publish_decorations(
     })
 }
 
+/// Runs the save actions which come after the save: for now, asking the
+/// client to run `cargo check`.
+pub fn on_save(
+    world: &ServerWorld,
+    params: req::DidSaveTextDocumentParams,
+) -> Result<Option<req::OnSaveParams>> {
+    if !world.config.on_save.cargo_check {
+        return Ok(None);
+    }
+    let file_id = params.text_document.try_conv_with(world)?;
+    Ok(Some(req::OnSaveParams {
+        text_document: params.text_document,
+        cargo_check: Some(cargo_check_runnable(world, file_id)?),
+    }))
+}
+
+/// Lists the labels of the tests and benchmarks of the file, to notice when
+/// they change.
+pub fn tests_of_file(world: &ServerWorld, file_id: FileId) -> Result<req::TestsChangedParams> {
+    let uri = world.file_id_to_uri(file_id)?;
+    let tests = world
+        .analysis()
+        .runnables(file_id)?
+        .into_iter()
+        .filter(|it| match it.kind {
+            RunnableKind::Test { .. } | RunnableKind::Bench { .. } => true,
+            _ => false,
+        })
+        .map(|it| runnable_label(&it.kind))
+        .collect();
+    Ok(req::TestsChangedParams { uri, tests })
+}
+
 fn highlight(world: &ServerWorld, file_id: FileId) -> Result<Vec<Decoration>> {
-    let line_index = world.analysis().file_line_index(file_id);
+    let line_index = world.line_map(file_id);
     let res = world
         .analysis()
         .highlight(file_id)?
@@ -700,3 +1293,24 @@ fn to_diagnostic_severity(severity: Severity) -> DiagnosticSeverity {
         WeakWarning => DiagnosticSeverity::Hint,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::snippet_to_plain_text;
+
+    #[test]
+    fn snippets_to_plain_text() {
+        assert_eq!(snippet_to_plain_text("foo(${1:x})$0"), "foo(x)");
+        assert_eq!(snippet_to_plain_text("foo(${1}, $2)"), "foo(, )");
+        assert_eq!(snippet_to_plain_text("${1:Vec<${2:T}>}::new()"), "Vec<T>::new()");
+        assert_eq!(
+            snippet_to_plain_text("fn main() {\n    $0\n}"),
+            "fn main() {\n    \n}"
+        );
+        assert_eq!(
+            snippet_to_plain_text("struct ${1:Foo} { ${2:x}: u32 }"),
+            "struct Foo { x: u32 }"
+        );
+        assert_eq!(snippet_to_plain_text("\\$1 ${1:a\\}b}"), "$1 a}b");
+    }
+}
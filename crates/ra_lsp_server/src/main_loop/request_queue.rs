@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+
+use gen_lsp_server::RawRequest;
+
+use crate::req::{self, Request};
+
+/// Requests which are queued but not yet running. If there are more, the
+/// oldest background ones are dropped.
+const MAX_QUEUED_REQUESTS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// The user is waiting for the result while typing.
+    Latency,
+    /// Potentially slow requests, the result of which can wait a bit.
+    Background,
+}
+
+impl Priority {
+    pub fn of(method: &str) -> Priority {
+        const LATENCY_SENSITIVE: &[&str] = &[
+            req::Completion::METHOD,
+            req::OnEnter::METHOD,
+            req::OnTypeFormatting::METHOD,
+            req::ExtendSelection::METHOD,
+            req::SelectionRangeRequest::METHOD,
+            req::FindMatchingBrace::METHOD,
+            req::JoinLines::METHOD,
+            req::SignatureHelpRequest::METHOD,
+            req::HoverRequest::METHOD,
+            req::DocumentHighlightRequest::METHOD,
+        ];
+        if LATENCY_SENSITIVE.contains(&method) {
+            Priority::Latency
+        } else {
+            Priority::Background
+        }
+    }
+}
+
+pub struct Job {
+    id: u64,
+    /// Requests with the same key differ only in their ids, so only the most
+    /// recent one is worth running.
+    key: Option<String>,
+    run: Box<dyn FnBox + Send>,
+}
+
+impl Job {
+    pub fn new(id: u64, key: Option<String>, run: impl FnOnce() + Send + 'static) -> Job {
+        Job {
+            id,
+            key,
+            run: Box::new(run),
+        }
+    }
+
+    pub fn run(self) {
+        self.run.call_box()
+    }
+}
+
+/// `Box<FnOnce()>` can't be called directly.
+trait FnBox {
+    fn call_box(self: Box<Self>);
+}
+
+impl<F: FnOnce()> FnBox for F {
+    fn call_box(self: Box<F>) {
+        (*self)()
+    }
+}
+
+/// Requests waiting for a free thread in the pool. Each queued request is
+/// paired with a pool task, which runs the most urgent job at the time the
+/// task starts, and not necessarily the one it was spawned for.
+#[derive(Default)]
+pub struct RequestQueue {
+    latency: VecDeque<Job>,
+    background: VecDeque<Job>,
+}
+
+impl RequestQueue {
+    /// Adds a job to the queue and returns the ids of the requests which were
+    /// dropped in favor of it.
+    pub fn push(&mut self, priority: Priority, job: Job) -> Vec<u64> {
+        let mut shed = Vec::new();
+        if let Some(key) = &job.key {
+            for queue in [&mut self.latency, &mut self.background].iter_mut() {
+                queue.retain(|it| {
+                    let stale = it.key.as_ref() == Some(key);
+                    if stale {
+                        shed.push(it.id);
+                    }
+                    !stale
+                });
+            }
+        }
+        match priority {
+            Priority::Latency => self.latency.push_back(job),
+            Priority::Background => self.background.push_back(job),
+        }
+        while self.latency.len() + self.background.len() > MAX_QUEUED_REQUESTS {
+            let oldest = match self.background.pop_front() {
+                Some(it) => it,
+                None => self.latency.pop_front().unwrap(),
+            };
+            shed.push(oldest.id);
+        }
+        shed
+    }
+
+    pub fn clear(&mut self) {
+        self.latency.clear();
+        self.background.clear();
+    }
+
+    pub fn pop(&mut self) -> Option<Job> {
+        self.latency
+            .pop_front()
+            .or_else(|| self.background.pop_front())
+    }
+}
+
+/// The method, the document and the position of a request. Requests without
+/// a position, like `workspace/symbol`, are never considered duplicates.
+pub fn dedup_key(req: &RawRequest) -> Option<String> {
+    let uri = req.params.get("textDocument")?.get("uri")?;
+    let position = req.params.get("position")?;
+    Some(format!("{} {} {}", req.method, uri, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: u64, key: Option<&str>) -> Job {
+        Job::new(id, key.map(|it| it.to_string()), || ())
+    }
+
+    fn pop_all(queue: &mut RequestQueue) -> Vec<u64> {
+        let mut res = Vec::new();
+        while let Some(job) = queue.pop() {
+            res.push(job.id);
+        }
+        res
+    }
+
+    #[test]
+    fn latency_jobs_run_before_background_ones() {
+        let mut queue = RequestQueue::default();
+        queue.push(Priority::Background, job(1, None));
+        queue.push(Priority::Latency, job(2, None));
+        queue.push(Priority::Background, job(3, None));
+        queue.push(Priority::Latency, job(4, None));
+        assert_eq!(pop_all(&mut queue), vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn newer_duplicate_cancels_the_queued_one() {
+        let mut queue = RequestQueue::default();
+        assert!(queue.push(Priority::Background, job(1, Some("a"))).is_empty());
+        assert!(queue.push(Priority::Latency, job(2, Some("b"))).is_empty());
+        assert!(queue.push(Priority::Background, job(3, None)).is_empty());
+        assert_eq!(queue.push(Priority::Latency, job(4, Some("a"))), vec![1]);
+        assert_eq!(queue.push(Priority::Latency, job(5, Some("b"))), vec![2]);
+        assert_eq!(pop_all(&mut queue), vec![4, 5, 3]);
+    }
+
+    #[test]
+    fn overflow_cancels_the_oldest_background_jobs_first() {
+        let mut queue = RequestQueue::default();
+        queue.push(Priority::Latency, job(0, None));
+        for id in 1..MAX_QUEUED_REQUESTS as u64 {
+            assert!(queue.push(Priority::Background, job(id, None)).is_empty());
+        }
+        let next = MAX_QUEUED_REQUESTS as u64;
+        assert_eq!(queue.push(Priority::Latency, job(next, None)), vec![1]);
+        assert_eq!(queue.push(Priority::Background, job(next + 1, None)), vec![2]);
+
+        let ids = pop_all(&mut queue);
+        assert_eq!(ids.len(), MAX_QUEUED_REQUESTS);
+        assert_eq!(&ids[..3], &[0, next, 3]);
+        assert_eq!(ids.last(), Some(&(next + 1)));
+    }
+
+    #[test]
+    fn cleared_queue_runs_nothing() {
+        let mut queue = RequestQueue::default();
+        queue.push(Priority::Latency, job(1, None));
+        queue.push(Priority::Background, job(2, None));
+        queue.clear();
+        assert!(queue.pop().is_none());
+    }
+}
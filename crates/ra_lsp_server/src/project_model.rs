@@ -1,13 +1,14 @@
 mod cargo_workspace;
 mod sysroot;
 
-use std::path::{Path, PathBuf};
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
 
-use failure::bail;
 use thread_worker::{WorkerHandle, Worker};
 
-use crate::Result;
-
 pub use crate::project_model::{
     cargo_workspace::{CargoWorkspace, Package, Target, TargetKind},
     sysroot::Sysroot,
@@ -17,33 +18,106 @@ pub use crate::project_model::{
 pub struct ProjectWorkspace {
     pub(crate) cargo: CargoWorkspace,
     pub(crate) sysroot: Sysroot,
+    /// Why the standard library could not be loaded. The workspace is still
+    /// analyzed, with an empty sysroot.
+    pub(crate) sysroot_error: Option<WorkspaceLoadError>,
+}
+
+/// Why a workspace could not be loaded. The server keeps running without the
+/// workspace, and analyzes the open files as separate crates. A missing
+/// standard library is not fatal, the workspace is loaded without it.
+#[derive(Debug, Clone)]
+pub enum WorkspaceLoadError {
+    /// There is no `Cargo.toml` in the directory or in its parents.
+    NoCargoToml { path: PathBuf },
+    /// `cargo metadata` failed, usually because the manifest is invalid.
+    CargoMetadata { cargo_toml: PathBuf, message: String },
+    /// The standard library could not be found.
+    Sysroot { cargo_toml: PathBuf, message: String },
+}
+
+impl WorkspaceLoadError {
+    /// The manifest the error is about, if there is one.
+    pub fn cargo_toml(&self) -> Option<&Path> {
+        match self {
+            WorkspaceLoadError::NoCargoToml { .. } => None,
+            WorkspaceLoadError::CargoMetadata { cargo_toml, .. }
+            | WorkspaceLoadError::Sysroot { cargo_toml, .. } => Some(cargo_toml),
+        }
+    }
 }
 
+impl fmt::Display for WorkspaceLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkspaceLoadError::NoCargoToml { path } => write!(
+                f,
+                "can't find Cargo.toml at {} or in its parent directories, \
+                 the open files are analyzed on their own",
+                path.display()
+            ),
+            WorkspaceLoadError::CargoMetadata { cargo_toml, message } => write!(
+                f,
+                "failed to load {}: {}\n\
+                 fix the manifest and reload the workspace",
+                cargo_toml.display(),
+                message
+            ),
+            WorkspaceLoadError::Sysroot { message, .. } => write!(
+                f,
+                "failed to load the standard library: {}\n\
+                 make sure that `rustc` is in PATH, \
+                 the workspace is analyzed without it",
+                message
+            ),
+        }
+    }
+}
+
+impl Error for WorkspaceLoadError {}
+
 impl ProjectWorkspace {
-    pub fn discover(path: &Path) -> Result<ProjectWorkspace> {
+    pub fn discover(path: &Path) -> Result<ProjectWorkspace, WorkspaceLoadError> {
         let cargo_toml = find_cargo_toml(path)?;
-        let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml)?;
-        let sysroot = Sysroot::discover(&cargo_toml)?;
-        let res = ProjectWorkspace { cargo, sysroot };
+        let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml).map_err(|e| {
+            WorkspaceLoadError::CargoMetadata {
+                cargo_toml: cargo_toml.clone(),
+                message: e.to_string(),
+            }
+        })?;
+        let (sysroot, sysroot_error) = match Sysroot::discover(&cargo_toml) {
+            Ok(it) => (it, None),
+            Err(e) => {
+                let error = WorkspaceLoadError::Sysroot {
+                    cargo_toml: cargo_toml.clone(),
+                    message: e.to_string(),
+                };
+                (Sysroot::default(), Some(error))
+            }
+        };
+        let res = ProjectWorkspace { cargo, sysroot, sysroot_error };
         Ok(res)
     }
 }
 
-pub fn workspace_loader() -> (Worker<PathBuf, Result<ProjectWorkspace>>, WorkerHandle) {
-    thread_worker::spawn::<PathBuf, Result<ProjectWorkspace>, _>(
+/// Loads the workspaces of a root, on startup and whenever the client asks
+/// for a reload.
+pub fn workspace_loader(
+) -> (Worker<PathBuf, Result<Vec<ProjectWorkspace>, WorkspaceLoadError>>, WorkerHandle) {
+    thread_worker::spawn::<PathBuf, Result<Vec<ProjectWorkspace>, WorkspaceLoadError>, _>(
         "workspace loader",
         1,
         |input_receiver, output_sender| {
             input_receiver
                 .into_iter()
-                .map(|path| ProjectWorkspace::discover(path.as_path()))
+                .map(|path| ProjectWorkspace::discover(path.as_path()).map(|ws| vec![ws]))
                 .try_for_each(|it| output_sender.send(it))
                 .unwrap()
         },
     )
 }
 
-fn find_cargo_toml(path: &Path) -> Result<PathBuf> {
+fn find_cargo_toml(path: &Path) -> Result<PathBuf, WorkspaceLoadError> {
     if path.ends_with("Cargo.toml") {
         return Ok(path.to_path_buf());
     }
@@ -55,5 +129,7 @@ fn find_cargo_toml(path: &Path) -> Result<PathBuf> {
         }
         curr = path.parent();
     }
-    bail!("can't find Cargo.toml at {}", path.display())
+    Err(WorkspaceLoadError::NoCargoToml {
+        path: path.to_path_buf(),
+    })
 }
@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use cargo_metadata::{metadata_run, CargoOpt};
+use ra_ide_api::Edition;
 use ra_syntax::SmolStr;
 use ra_arena::{Arena, RawId, impl_arena_id};
 use rustc_hash::FxHashMap;
@@ -33,6 +34,7 @@ impl_arena_id!(Target);
 struct PackageData {
     name: SmolStr,
     manifest: PathBuf,
+    edition: Edition,
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
@@ -41,6 +43,9 @@ struct PackageData {
 #[derive(Debug, Clone)]
 pub struct PackageDependency {
     pub pkg: Package,
+    /// The name the dependency is visible under in the code of the dependent
+    /// package. Cargo reports the alias here for renamed dependencies
+    /// (`bar = { package = "foo" }`).
     pub name: SmolStr,
 }
 
@@ -50,6 +55,7 @@ struct TargetData {
     name: SmolStr,
     root: PathBuf,
     kind: TargetKind,
+    required_features: Vec<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -88,6 +94,9 @@ impl Package {
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.packages[self].targets.iter().cloned()
     }
+    pub fn edition(self, ws: &CargoWorkspace) -> Edition {
+        ws.packages[self].edition
+    }
     #[allow(unused)]
     pub fn is_member(self, ws: &CargoWorkspace) -> bool {
         ws.packages[self].is_member
@@ -113,6 +122,10 @@ impl Target {
     pub fn kind(self, ws: &CargoWorkspace) -> TargetKind {
         ws.targets[self].kind
     }
+    /// Features listed in `required-features` of the target's manifest entry.
+    pub fn required_features(self, ws: &CargoWorkspace) -> &[String] {
+        ws.targets[self].required_features.as_slice()
+    }
 }
 
 impl CargoWorkspace {
@@ -130,6 +143,7 @@ impl CargoWorkspace {
             let pkg = packages.alloc(PackageData {
                 name: meta_pkg.name.into(),
                 manifest: meta_pkg.manifest_path.clone(),
+                edition: Edition::from_string(&meta_pkg.edition),
                 targets: Vec::new(),
                 is_member,
                 dependencies: Vec::new(),
@@ -142,6 +156,7 @@ impl CargoWorkspace {
                     name: meta_tgt.name.into(),
                     root: meta_tgt.src_path.clone(),
                     kind: TargetKind::new(meta_tgt.kind.as_slice()),
+                    required_features: meta_tgt.required_features,
                 });
                 pkg_data.targets.push(tgt);
             }
@@ -8,7 +8,7 @@ use ra_arena::{Arena, RawId, impl_arena_id};
 
 use crate::Result;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Sysroot {
     crates: Arena<SysrootCrate, SysrootCrateData>,
 }
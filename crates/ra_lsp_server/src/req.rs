@@ -4,11 +4,13 @@ use serde::{Deserialize, Serialize};
 use url_serde;
 
 pub use languageserver_types::{
-    notification::*, request::*, ApplyWorkspaceEditParams, CodeActionParams, CodeLens, CodeLensParams,
-    CompletionParams, CompletionResponse, DocumentOnTypeFormattingParams, DocumentSymbolParams,
-    DocumentSymbolResponse, ExecuteCommandParams, Hover, InitializeResult,
-    PublishDiagnosticsParams, ReferenceParams, SignatureHelp, TextDocumentEdit,
-    TextDocumentPositionParams, TextEdit, WorkspaceEdit, WorkspaceSymbolParams,
+    notification::*, request::*, ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse,
+    CodeActionParams, CodeLens, CodeLensParams, CompletionParams, CompletionResponse,
+    DidSaveTextDocumentParams, DocumentOnTypeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
+    ExecuteCommandParams, Hover, InitializeResult, MessageType, PublishDiagnosticsParams,
+    ReferenceParams, ShowMessageParams, SignatureHelp, TextDocumentEdit,
+    TextDocumentPositionParams, TextDocumentSaveReason, TextEdit, WillSaveTextDocumentParams,
+    WorkspaceEdit, WorkspaceSymbolParams,
 };
 
 pub enum SyntaxTree {}
@@ -46,6 +48,43 @@ pub struct ExtendSelectionResult {
     pub selections: Vec<Range>,
 }
 
+/// `textDocument/selectionRange` from the proposed LSP 3.15 API, which
+/// `languageserver-types` doesn't have yet.
+pub enum SelectionRangeRequest {}
+
+impl Request for SelectionRangeRequest {
+    type Params = SelectionRangeParams;
+    type Result = Vec<SelectionRange>;
+    const METHOD: &'static str = "textDocument/selectionRange";
+}
+
+/// `textDocument/declaration` from LSP 3.14, which `languageserver-types`
+/// doesn't have yet.
+pub enum GotoDeclaration {}
+
+impl Request for GotoDeclaration {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<GotoDefinitionResponse>;
+    const METHOD: &'static str = "textDocument/declaration";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub positions: Vec<Position>,
+}
+
+/// A range and all the ranges enclosing it, from the innermost to the whole
+/// file.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRange {
+    pub range: Range,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Box<SelectionRange>>,
+}
+
 pub enum FindMatchingBrace {}
 
 impl Request for FindMatchingBrace {
@@ -114,6 +153,21 @@ pub struct JoinLinesParams {
     pub range: Range,
 }
 
+pub enum TypeOf {}
+
+impl Request for TypeOf {
+    type Params = TypeOfParams;
+    type Result = Option<String>;
+    const METHOD: &'static str = "m/typeOf";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeOfParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
 pub enum OnEnter {}
 
 impl Request for OnEnter {
@@ -147,12 +201,220 @@ pub struct Runnable {
     pub env: FxHashMap<String, String>,
 }
 
+/// Lists the tests of all crates of the workspace, for test explorers.
+pub enum DiscoverTests {}
+
+impl Request for DiscoverTests {
+    type Params = ();
+    type Result = Vec<TestCrate>;
+    const METHOD: &'static str = "m/discoverTests";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCrate {
+    pub package: String,
+    pub target: String,
+    pub root: TestModule,
+}
+
+/// A module with tests. Modules without tests in them or in their submodules
+/// are omitted, except for the crate root.
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
+pub struct TestModule {
+    pub path: String,
+    #[serde(with = "url_serde")]
+    pub uri: Url,
+    pub range: Range,
+    pub tests: Vec<Runnable>,
+    pub children: Vec<TestModule>,
+}
+
+/// Finds the tests of the item at the position, to jump to them or to run
+/// them.
+pub enum RelatedTests {}
+
+impl Request for RelatedTests {
+    type Params = TextDocumentPositionParams;
+    type Result = Vec<RelatedTest>;
+    const METHOD: &'static str = "m/relatedTests";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedTest {
+    pub location: Location,
+    pub runnable: Runnable,
+}
+
+/// Sent when the set of tests of an open document changes.
+pub enum TestsChanged {}
+
+impl Notification for TestsChanged {
+    type Params = TestsChangedParams;
+    const METHOD: &'static str = "m/testsChanged";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TestsChangedParams {
+    #[serde(with = "url_serde")]
+    pub uri: Url,
+    /// The labels of the tests of the document.
+    pub tests: Vec<String>,
+}
+
+/// Sent after a document was saved, when the client should run `cargo check`.
+pub enum OnSave {}
+
+impl Notification for OnSave {
+    type Params = OnSaveParams;
+    const METHOD: &'static str = "m/onSave";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OnSaveParams {
+    pub text_document: TextDocumentIdentifier,
+    pub cargo_check: Option<Runnable>,
+}
+
+/// Reloads the project model, for example, after `Cargo.toml` was edited.
+pub enum ReloadWorkspace {}
+
+impl Request for ReloadWorkspace {
+    type Params = ();
+    type Result = ();
+    const METHOD: &'static str = "m/reloadWorkspace";
+}
+
+pub enum AnalyzerStatus {}
+
+impl Request for AnalyzerStatus {
+    type Params = ();
+    type Result = String;
+    const METHOD: &'static str = "m/analyzerStatus";
+}
+
+pub enum InlayHints {}
+
+impl Request for InlayHints {
+    type Params = InlayHintsParams;
+    type Result = Vec<InlayHint>;
+    const METHOD: &'static str = "m/inlayHints";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintsParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum InlayKind {
+    ParameterHint,
+    ChainingHint,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    pub range: Range,
+    pub kind: InlayKind,
+    pub label: String,
+}
+
+pub enum ModuleTree {}
+
+impl Request for ModuleTree {
+    type Params = ModuleTreeParams;
+    type Result = Option<ModuleTreeNode>;
+    const METHOD: &'static str = "m/moduleTree";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleTreeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// A module of the crate of the document, with its submodules. The crate
+/// root is named `crate`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleTreeNode {
+    pub name: String,
+    #[serde(with = "url_serde")]
+    pub uri: Url,
+    pub range: Range,
+    pub children: Vec<ModuleTreeNode>,
+}
+
+/// `textDocument/documentLink`, with the `data` field which lets us resolve
+/// the targets of the links lazily.
+pub enum DocumentLinkRequest {}
+
+impl Request for DocumentLinkRequest {
+    type Params = DocumentLinkParams;
+    type Result = Option<Vec<DocumentLink>>;
+    const METHOD: &'static str = "textDocument/documentLink";
+}
+
+pub enum DocumentLinkResolve {}
+
+impl Request for DocumentLinkResolve {
+    type Params = DocumentLink;
+    type Result = DocumentLink;
+    const METHOD: &'static str = "documentLink/resolve";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLinkParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLink {
+    pub range: Range,
+    /// The URI of the target, `None` until the link is resolved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<DocumentLinkData>,
+}
+
+/// Where an unresolved link comes from.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLinkData {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Asks the client to apply a whole source change, with its snippet edit and
+/// cursor position. Sent instead of `workspace/applyEdit` to the clients which
+/// support snippet text edits.
+pub enum ApplySourceChange {}
+
+impl Request for ApplySourceChange {
+    type Params = SourceChange;
+    type Result = ApplyWorkspaceEditResponse;
+    const METHOD: &'static str = "m/applySourceChange";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct SourceChange {
     pub label: String,
     pub workspace_edit: WorkspaceEdit,
     pub cursor_position: Option<TextDocumentPositionParams>,
+    /// The edit of the document with the placeholders as a single snippet,
+    /// with `${1:placeholder}` and `$0` for the final cursor. `workspace_edit`
+    /// makes the same change in plain text, for when it can't be expanded.
+    pub snippet_edit: Option<TextDocumentEdit>,
 }
 
 pub enum InternalFeedback {}
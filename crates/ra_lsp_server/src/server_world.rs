@@ -1,20 +1,26 @@
 use std::{
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use languageserver_types::Url;
+use languageserver_types::{ResourceOperationKind, Url};
 use ra_ide_api::{
-    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, LibraryData,
-    SourceRootId
+    Analysis, AnalysisChange, AnalysisHost, CrateGraph, Edition, FileId, FileSystemEdit,
+    LibraryData, SourceRootId
 };
 use ra_vfs::{Vfs, VfsChange, VfsFile, VfsRoot};
-use rustc_hash::FxHashMap;
-use relative_path::RelativePathBuf;
+use rustc_hash::{FxHashMap, FxHashSet};
+use relative_path::{RelativePath, RelativePathBuf};
 use parking_lot::RwLock;
 use failure::format_err;
 
 use crate::{
+    caps::PositionEncoding,
+    cargo_toml,
+    config::ServerConfig,
+    conv::LineMap,
     project_model::{ProjectWorkspace, TargetKind},
     Result,
 };
@@ -26,20 +32,62 @@ pub struct ServerWorldState {
     pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis_host: AnalysisHost,
     pub vfs: Arc<RwLock<Vfs>>,
+    /// The file operations, like creating or renaming a file, which the
+    /// client can apply as a part of a `WorkspaceEdit`.
+    pub resource_operations: Arc<Vec<ResourceOperationKind>>,
+    /// Whether the client can expand the snippet edits of a `SourceChange`.
+    pub supports_snippet_text_edits: bool,
+    /// The unit of columns in the positions exchanged with the client.
+    pub position_encoding: PositionEncoding,
+    pub config: Arc<ServerConfig>,
+    /// Local files which match `config.exclude_globs`. The vfs still tracks
+    /// them, but they are not added to the analysis.
+    pub excluded_files: Arc<FxHashSet<FileId>>,
+    /// Versions of the documents open in the client. Edits sent to the client
+    /// carry them, so that stale edits are rejected.
+    pub doc_versions: Arc<FxHashMap<FileId, u64>>,
+    /// The crates of the workspaces, and of the detached files opened so far.
+    pub crate_graph: CrateGraph,
+    /// The texts of the `Cargo.toml`s open in the client. The vfs only tracks
+    /// Rust files, so they are kept here.
+    pub manifests: Arc<FxHashMap<PathBuf, String>>,
+    /// The URLs of the files of the library roots, filled when a library is
+    /// scanned and kept up to date as the watcher adds and removes files.
+    pub library_uris: Arc<FxHashMap<FileId, Url>>,
 }
 
 pub struct ServerWorld {
     pub workspaces: Arc<Vec<ProjectWorkspace>>,
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
+    pub resource_operations: Arc<Vec<ResourceOperationKind>>,
+    pub supports_snippet_text_edits: bool,
+    pub position_encoding: PositionEncoding,
+    pub config: Arc<ServerConfig>,
+    pub excluded_files: Arc<FxHashSet<FileId>>,
+    pub doc_versions: Arc<FxHashMap<FileId, u64>>,
+    pub manifests: Arc<FxHashMap<PathBuf, String>>,
+    pub library_uris: Arc<FxHashMap<FileId, Url>>,
 }
 
 impl ServerWorldState {
-    pub fn new(root: PathBuf, workspaces: Vec<ProjectWorkspace>) -> ServerWorldState {
+    pub fn new(
+        root: PathBuf,
+        workspaces: Vec<ProjectWorkspace>,
+        resource_operations: Vec<ResourceOperationKind>,
+        supports_snippet_text_edits: bool,
+        position_encoding: PositionEncoding,
+        config: ServerConfig,
+    ) -> ServerWorldState {
         let mut change = AnalysisChange::new();
 
         let mut roots = Vec::new();
-        roots.push(root.clone());
+        // Without a workspace, the files of the root belong to no crate. They
+        // are left out of the vfs, so that the open ones become detached
+        // files, each analyzed as a crate of its own.
+        if !workspaces.is_empty() {
+            roots.push(root.clone());
+        }
         for ws in workspaces.iter() {
             for pkg in ws.cargo.packages() {
                 roots.push(pkg.root(&ws.cargo).to_path_buf());
@@ -64,7 +112,9 @@ impl ServerWorldState {
             for krate in ws.sysroot.crates() {
                 if let Some(file_id) = vfs.load(krate.root(&ws.sysroot)) {
                     let file_id = FileId(file_id.0.into());
-                    sysroot_crates.insert(krate, crate_graph.add_crate_root(file_id));
+                    let crate_id = crate_graph.add_crate_root(file_id, Edition::Edition2015);
+                    crate_graph.set_display_name(crate_id, krate.name(&ws.sysroot).clone());
+                    sysroot_crates.insert(krate, crate_id);
                 }
             }
             for from in ws.sysroot.crates() {
@@ -92,9 +142,14 @@ impl ServerWorldState {
                     let root = tgt.root(&ws.cargo);
                     if let Some(file_id) = vfs.load(root) {
                         let file_id = FileId(file_id.0.into());
-                        let crate_id = crate_graph.add_crate_root(file_id);
+                        let edition = pkg.edition(&ws.cargo);
+                        let crate_id = crate_graph.add_crate_root(file_id, edition);
+                        // The crate is visible under its lib name, which may
+                        // differ from the name of the package.
+                        let name = tgt.name(&ws.cargo).replace('-', "_");
+                        crate_graph.set_display_name(crate_id, name.as_str().into());
                         if tgt.kind(&ws.cargo) == TargetKind::Lib {
-                            lib_tgt = Some(crate_id);
+                            lib_tgt = Some((crate_id, name));
                             pkg_to_lib_crate.insert(pkg, crate_id);
                         }
                         pkg_crates
@@ -106,9 +161,9 @@ impl ServerWorldState {
 
                 // Set deps to the std and to the lib target of the current package
                 for &from in pkg_crates.get(&pkg).into_iter().flatten() {
-                    if let Some(to) = lib_tgt {
-                        if to != from {
-                            crate_graph.add_dep(from, pkg.name(&ws.cargo).into(), to);
+                    if let Some((to, name)) = &lib_tgt {
+                        if *to != from {
+                            crate_graph.add_dep(from, name.as_str().into(), *to);
                         }
                     }
                     if let Some(std) = libstd {
@@ -129,7 +184,7 @@ impl ServerWorldState {
                 }
             }
         }
-        change.set_crate_graph(crate_graph);
+        change.set_crate_graph(crate_graph.clone());
 
         let mut analysis_host = AnalysisHost::default();
         analysis_host.apply_change(change);
@@ -139,6 +194,15 @@ impl ServerWorldState {
             workspaces: Arc::new(workspaces),
             analysis_host,
             vfs: Arc::new(RwLock::new(vfs)),
+            resource_operations: Arc::new(resource_operations),
+            supports_snippet_text_edits,
+            position_encoding,
+            config: Arc::new(config),
+            excluded_files: Arc::new(FxHashSet::default()),
+            doc_versions: Arc::new(FxHashMap::default()),
+            crate_graph,
+            manifests: Arc::new(FxHashMap::default()),
+            library_uris: Arc::new(FxHashMap::default()),
         }
     }
 
@@ -160,6 +224,11 @@ impl ServerWorldState {
                     if root_path.starts_with(&self.root) {
                         self.roots_to_scan -= 1;
                         for (file, path, text) in files {
+                            if is_excluded(&self.config, &self.root, &root_path, &path) {
+                                Arc::make_mut(&mut self.excluded_files)
+                                    .insert(FileId(file.0.into()));
+                                continue;
+                            }
                             change.add_file(
                                 SourceRootId(root.0.into()),
                                 FileId(file.0.into()),
@@ -171,7 +240,13 @@ impl ServerWorldState {
                         let files = files
                             .into_iter()
                             .map(|(vfsfile, path, text)| (FileId(vfsfile.0.into()), path, text))
-                            .collect();
+                            .collect::<Vec<_>>();
+                        let library_uris = Arc::make_mut(&mut self.library_uris);
+                        for (file_id, path, _) in files.iter() {
+                            if let Ok(uri) = Url::from_file_path(path.to_path(&root_path)) {
+                                library_uris.insert(*file_id, uri);
+                            }
+                        }
                         libs.push((SourceRootId(root.0.into()), files));
                     }
                 }
@@ -181,6 +256,17 @@ impl ServerWorldState {
                     path,
                     text,
                 } => {
+                    let root_path = self.vfs.read().root2path(root);
+                    if !root_path.starts_with(&self.root) {
+                        if let Ok(uri) = Url::from_file_path(path.to_path(&root_path)) {
+                            let file_id = FileId(file.0.into());
+                            Arc::make_mut(&mut self.library_uris).insert(file_id, uri);
+                        }
+                    }
+                    if is_excluded(&self.config, &self.root, &root_path, &path) {
+                        Arc::make_mut(&mut self.excluded_files).insert(FileId(file.0.into()));
+                        continue;
+                    }
                     change.add_file(
                         SourceRootId(root.0.into()),
                         FileId(file.0.into()),
@@ -189,10 +275,22 @@ impl ServerWorldState {
                     );
                 }
                 VfsChange::RemoveFile { root, file, path } => {
-                    change.remove_file(SourceRootId(root.0.into()), FileId(file.0.into()), path)
+                    let file_id = FileId(file.0.into());
+                    if self.library_uris.contains_key(&file_id) {
+                        Arc::make_mut(&mut self.library_uris).remove(&file_id);
+                    }
+                    if self.excluded_files.contains(&file_id) {
+                        Arc::make_mut(&mut self.excluded_files).remove(&file_id);
+                        continue;
+                    }
+                    change.remove_file(SourceRootId(root.0.into()), file_id, path)
                 }
                 VfsChange::ChangeFile { file, text } => {
-                    change.change_file(FileId(file.0.into()), text);
+                    let file_id = FileId(file.0.into());
+                    if self.excluded_files.contains(&file_id) {
+                        continue;
+                    }
+                    change.change_file(file_id, text);
                 }
             }
         }
@@ -200,6 +298,25 @@ impl ServerWorldState {
         libs
     }
 
+    /// Adds a file which is outside of the workspace and of the libraries,
+    /// like a scratch file open in the editor, as a crate of its own, so that
+    /// the features which don't need the rest of the project still work. The
+    /// crate has no dependencies, and it stays around when the file is closed.
+    pub fn add_detached_file(&mut self, path: &Path, text: String) -> Option<FileId> {
+        let text = Arc::new(text);
+        let (root, file, rel_path) =
+            self.vfs.write().add_detached_file(path, Arc::clone(&text))?;
+        let root_id = SourceRootId(root.0.into());
+        let file_id = FileId(file.0.into());
+        self.crate_graph.add_crate_root(file_id, Edition::Edition2018);
+        let mut change = AnalysisChange::new();
+        change.add_root(root_id, true);
+        change.add_file(root_id, file_id, rel_path, text);
+        change.set_crate_graph(self.crate_graph.clone());
+        self.analysis_host.apply_change(change);
+        Some(file_id)
+    }
+
     pub fn add_lib(&mut self, data: LibraryData) {
         self.roots_to_scan -= 1;
         let mut change = AnalysisChange::new();
@@ -207,11 +324,56 @@ impl ServerWorldState {
         self.analysis_host.apply_change(change);
     }
 
+    /// Replaces the config. Only the state which depends on the changed
+    /// settings is recomputed: for now, that's the set of excluded files.
+    pub fn update_config(&mut self, config: ServerConfig) {
+        let excludes_changed = config.exclude_globs != self.config.exclude_globs;
+        self.config = Arc::new(config);
+        if !excludes_changed {
+            return;
+        }
+        let mut change = AnalysisChange::new();
+        let mut excluded_files = FxHashSet::default();
+        {
+            let vfs = self.vfs.read();
+            for root in vfs.roots() {
+                let root_path = vfs.root2path(root);
+                if !root_path.starts_with(&self.root) {
+                    continue;
+                }
+                let root_id = SourceRootId(root.0.into());
+                for (file, path, text) in vfs.root_files(root) {
+                    let file_id = FileId(file.0.into());
+                    let was_excluded = self.excluded_files.contains(&file_id);
+                    let excluded = is_excluded(&self.config, &self.root, &root_path, &path);
+                    if excluded {
+                        excluded_files.insert(file_id);
+                    }
+                    match (was_excluded, excluded) {
+                        (false, true) => change.remove_file(root_id, file_id, path),
+                        (true, false) => change.add_file(root_id, file_id, path, text),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        self.excluded_files = Arc::new(excluded_files);
+        self.analysis_host.apply_change(change);
+    }
+
     pub fn snapshot(&self) -> ServerWorld {
         ServerWorld {
             workspaces: Arc::clone(&self.workspaces),
             analysis: self.analysis_host.analysis(),
             vfs: Arc::clone(&self.vfs),
+            resource_operations: Arc::clone(&self.resource_operations),
+            supports_snippet_text_edits: self.supports_snippet_text_edits,
+            position_encoding: self.position_encoding,
+            config: Arc::clone(&self.config),
+            excluded_files: Arc::clone(&self.excluded_files),
+            doc_versions: Arc::clone(&self.doc_versions),
+            manifests: Arc::clone(&self.manifests),
+            library_uris: Arc::clone(&self.library_uris),
         }
     }
 }
@@ -221,6 +383,47 @@ impl ServerWorld {
         &self.analysis
     }
 
+    /// Whether the client can apply `edit` as a part of a `WorkspaceEdit`.
+    pub fn supports_file_system_edit(&self, edit: &FileSystemEdit) -> bool {
+        let kind = match edit {
+            FileSystemEdit::CreateFile { .. } | FileSystemEdit::CreateFileWithContent { .. } => {
+                ResourceOperationKind::Create
+            }
+            FileSystemEdit::MoveFile { .. } => ResourceOperationKind::Rename,
+            FileSystemEdit::RemoveFile { .. } => ResourceOperationKind::Delete,
+        };
+        self.resource_operations.contains(&kind)
+    }
+
+    /// The deadline of an expensive request which starts now, according to
+    /// the `request_budget_ms` setting.
+    pub fn request_deadline(&self) -> Option<Instant> {
+        self.config
+            .request_budget_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms))
+    }
+
+    pub fn line_map(&self, file_id: FileId) -> LineMap {
+        LineMap {
+            index: self.analysis.file_line_index(file_id),
+            encoding: self.position_encoding,
+        }
+    }
+
+    /// The path and the text of the `Cargo.toml` at `uri`, or `None` if `uri`
+    /// is not a manifest. Manifests which are not open are read from disk.
+    pub fn manifest(&self, uri: &Url) -> Option<(PathBuf, String)> {
+        let path = uri.to_file_path().ok()?;
+        if !cargo_toml::is_manifest(&path) {
+            return None;
+        }
+        let text = match self.manifests.get(&path) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(&path).ok()?,
+        };
+        Some((path, text))
+    }
+
     pub fn uri_to_file_id(&self, uri: &Url) -> Result<FileId> {
         let path = uri
             .to_file_path()
@@ -230,10 +433,17 @@ impl ServerWorld {
             .read()
             .path2file(&path)
             .ok_or_else(|| format_err!("unknown file: {}", path.display()))?;
-        Ok(FileId(file.0.into()))
+        let file_id = FileId(file.0.into());
+        if self.excluded_files.contains(&file_id) {
+            return Err(format_err!("excluded file: {}", path.display()));
+        }
+        Ok(file_id)
     }
 
     pub fn file_id_to_uri(&self, id: FileId) -> Result<Url> {
+        if let Some(uri) = self.library_uris.get(&id) {
+            return Ok(uri.clone());
+        }
         let path = self.vfs.read().file2path(VfsFile(id.0.into()));
         let url = Url::from_file_path(&path)
             .map_err(|_| format_err!("can't convert path to url: {}", path.display()))?;
@@ -248,3 +458,25 @@ impl ServerWorld {
         Ok(url)
     }
 }
+
+/// Checks a file of a local root against the exclude globs, which are relative
+/// to the workspace root.
+fn is_excluded(
+    config: &ServerConfig,
+    ws_root: &Path,
+    root_path: &Path,
+    path: &RelativePath,
+) -> bool {
+    if config.exclude_globs.is_empty() {
+        return false;
+    }
+    let path = path.to_path(root_path);
+    let path = match path.strip_prefix(ws_root) {
+        Ok(it) => it,
+        Err(_) => return false,
+    };
+    match RelativePathBuf::from_path(path) {
+        Ok(path) => config.is_excluded(path.as_str()),
+        Err(_) => false,
+    }
+}
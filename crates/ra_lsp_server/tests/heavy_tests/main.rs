@@ -2,18 +2,28 @@ mod support;
 
 use std::{
     collections::HashMap,
+    fs,
     time::Instant,
 };
 
 use languageserver_types::{
-    CodeActionContext, DocumentFormattingParams, FormattingOptions, Position, Range,
+    CodeActionContext, DidChangeConfigurationParams, DocumentFormattingParams, FoldingRangeParams,
+    FormattingOptions, Position, Range, TextDocumentSaveReason, WillSaveTextDocumentParams,
 };
-use ra_lsp_server::req::{
-    CodeActionParams, CodeActionRequest, Formatting, Runnables, RunnablesParams, CompletionParams, Completion,
+use ra_lsp_server::{
+    req::{
+        CodeActionParams, CodeActionRequest, Formatting, Runnables, RunnablesParams,
+        CompletionParams, Completion, DidChangeConfiguration, DiscoverTests, DidSaveTextDocument,
+        DocumentLinkParams, DocumentLinkRequest, FoldingRangeRequest, GotoDefinition, HoverRequest,
+        OnSave, ReloadWorkspace, SelectionRangeParams, SelectionRangeRequest, ShowMessage,
+        TextDocumentPositionParams, TypeOf, TypeOfParams, WillSaveWaitUntil, WorkspaceSymbol,
+        WorkspaceSymbolParams,
+    },
+    OnSaveOptions, ServerConfig,
 };
-use serde_json::json;
+use serde_json::{json, Value};
 
-use crate::support::project;
+use crate::support::{project, project_in_dir, project_with_config};
 
 const LOG: &'static str = "";
 
@@ -93,6 +103,28 @@ fn foo() {
     );
 }
 
+#[test]
+fn test_files_without_cargo_toml_are_analyzed_on_their_own() {
+    let server = project(
+        r"
+//- main.rs
+struct Foo;
+fn main() { let _x = Foo; }
+",
+    );
+    let message = server.wait_for_notification::<ShowMessage>();
+    let message = message["message"].as_str().unwrap();
+    assert!(message.starts_with("can't find Cargo.toml"), "{}", message);
+    server.wait_for_feedback("workspace loaded");
+    let res = server.send_request::<GotoDefinition>(TextDocumentPositionParams {
+        text_document: server.doc_id("main.rs"),
+        position: Position::new(1, 22),
+    });
+    let target = res[0]["targetUri"].as_str().unwrap();
+    assert!(target.ends_with("/main.rs"), "{}", target);
+    assert_eq!(res[0]["targetRange"]["start"], json!({ "line": 0, "character": 0 }));
+}
+
 #[test]
 fn test_runnables_project() {
     let server = project(
@@ -153,6 +185,531 @@ fn test_eggs() {}
     );
 }
 
+#[test]
+fn test_runnables_use_config_and_required_features() {
+    let mut config = ServerConfig::default();
+    config
+        .runnables
+        .env
+        .insert("RUST_LOG".to_string(), "debug".to_string());
+    config.runnables.cargo_args = vec!["--no-default-features".to_string()];
+    let server = project_with_config(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+[features]
+slow = []
+
+[[test]]
+name = "spam"
+required-features = ["slow"]
+
+//- src/lib.rs
+pub fn foo() {}
+
+//- tests/spam.rs
+#[test]
+fn test_eggs() {}
+"#,
+        config,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("tests/spam.rs"),
+            position: None,
+        },
+        json!([
+          {
+            "args": [
+              "test", "--package", "foo", "--test", "spam", "--features", "slow",
+              "--no-default-features", "--", "test_eggs", "--nocapture"
+            ],
+            "bin": "cargo",
+            "env": { "RUST_BACKTRACE": "short", "RUST_LOG": "debug" },
+            "label": "test test_eggs",
+            "range": {
+              "end": { "character": 17, "line": 1 },
+              "start": { "character": 0, "line": 0 }
+            }
+          },
+          {
+            "args": [
+              "check", "--package", "foo", "--test", "spam", "--features", "slow",
+              "--no-default-features"
+            ],
+            "bin": "cargo",
+            "env": { "RUST_LOG": "debug" },
+            "label": "cargo check -p foo",
+            "range": {
+              "end": { "character": 0, "line": 0 },
+              "start": { "character": 0, "line": 0 }
+            }
+          }
+        ])
+    );
+}
+
+#[test]
+fn test_reload_workspace_picks_up_manifest_changes() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {}
+
+//- bar/Cargo.toml
+[package]
+name = "bar"
+version = "0.0.0"
+
+//- bar/src/lib.rs
+pub fn bar() {}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let check_label = || {
+        let res = server.send_request::<Runnables>(RunnablesParams {
+            text_document: server.doc_id("bar/src/lib.rs"),
+            position: None,
+        });
+        let runnables = res.as_array().unwrap();
+        runnables.last().unwrap()["label"].as_str().unwrap().to_string()
+    };
+    assert_eq!(check_label(), "cargo check --all");
+
+    let manifest = server.doc_id("Cargo.toml").uri.to_file_path().unwrap();
+    let text = fs::read_to_string(&manifest).unwrap();
+    fs::write(&manifest, text + "\n[dependencies]\nbar = { path = \"bar\" }\n").unwrap();
+    server.request::<ReloadWorkspace>((), json!(null));
+
+    // `bar` is a member of the reloaded workspace, and `foo` depends on it.
+    let label = check_label();
+    assert!(label.contains("-p bar") && label.contains("-p foo"), "{}", label);
+}
+
+#[test]
+fn test_discover_tests() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {}
+
+mod tests {
+    #[test]
+    fn test_eggs() {}
+}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<DiscoverTests>(
+        (),
+        json!([
+          {
+            "package": "foo",
+            "target": "foo",
+            "root": {
+              "path": "",
+              "uri": "[..]/src/lib.rs",
+              "range": {
+                "start": { "character": 0, "line": 0 },
+                "end": { "character": 0, "line": 6 }
+              },
+              "tests": [],
+              "children": [
+                {
+                  "path": "tests",
+                  "uri": "[..]/src/lib.rs",
+                  "range": {
+                    "start": { "character": 0, "line": 2 },
+                    "end": { "character": 1, "line": 5 }
+                  },
+                  "tests": [
+                    {
+                      "args": [ "test", "--package", "foo", "--lib", "--", "tests::test_eggs", "--nocapture" ],
+                      "bin": "cargo",
+                      "env": { "RUST_BACKTRACE": "short" },
+                      "label": "test tests::test_eggs",
+                      "range": {
+                        "start": { "character": 4, "line": 3 },
+                        "end": { "character": 21, "line": 4 }
+                      }
+                    }
+                  ],
+                  "children": []
+                }
+              ]
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_check_runnable_includes_dependent_packages() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[workspace]
+members = ["foo", "bar"]
+
+//- foo/Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- foo/src/lib.rs
+pub fn foo() {}
+
+//- bar/Cargo.toml
+[package]
+name = "bar"
+version = "0.0.0"
+
+[dependencies]
+foo = { path = "../foo" }
+
+//- bar/src/lib.rs
+pub fn bar() { foo::foo() }
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("foo/src/lib.rs"),
+            position: None,
+        },
+        json!([
+          {
+            "args": [
+              "check",
+              "--package",
+              "foo",
+              "--package",
+              "bar"
+            ],
+            "bin": "cargo",
+            "env": {},
+            "label": "cargo check -p foo -p bar",
+            "range": {
+              "end": {
+                "character": 0,
+                "line": 0
+              },
+              "start": {
+                "character": 0,
+                "line": 0
+              }
+            }
+          }
+        ])
+    );
+}
+
+#[test]
+fn test_manifest_links_to_path_dependencies() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[workspace]
+members = ["foo", "bar"]
+
+//- foo/Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- foo/src/lib.rs
+pub fn foo() {}
+
+//- bar/Cargo.toml
+[package]
+name = "bar"
+version = "0.0.0"
+
+[dependencies]
+foo = { path = "../foo" }
+
+//- bar/src/lib.rs
+pub fn bar() { foo::foo() }
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<DocumentLinkRequest>(
+        DocumentLinkParams {
+            text_document: server.doc_id("bar/Cargo.toml"),
+        },
+        json!([
+          {
+            "range": {
+              "end": {
+                "character": 22,
+                "line": 5
+              },
+              "start": {
+                "character": 16,
+                "line": 5
+              }
+            },
+            "target": "file:///[..]foo/Cargo.toml"
+          }
+        ])
+    );
+}
+
+#[test]
+fn test_goto_definition_into_library() {
+    let server = project_in_dir(
+        r#"
+//- ws/Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+[dependencies]
+bar = { path = "../bar" }
+
+//- ws/src/lib.rs
+pub fn foo() { bar::bar() }
+
+//- bar/Cargo.toml
+[package]
+name = "bar"
+version = "0.0.0"
+
+//- bar/src/lib.rs
+pub fn bar() {}
+"#,
+        "ws",
+        ServerConfig::default(),
+    );
+    server.wait_for_feedback("workspace loaded");
+    let res = server.send_request::<GotoDefinition>(TextDocumentPositionParams {
+        text_document: server.doc_id("ws/src/lib.rs"),
+        position: Position::new(0, 21),
+    });
+    let target = res[0]["targetUri"].as_str().unwrap();
+    assert!(target.starts_with("file:///"), "{}", target);
+    assert!(target.ends_with("/bar/src/lib.rs"), "{}", target);
+}
+
+#[test]
+fn test_manifest_ignores_other_requests() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<HoverRequest>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("Cargo.toml"),
+            position: Position::new(1, 2),
+        },
+        json!(null),
+    );
+    server.request::<FoldingRangeRequest>(
+        FoldingRangeParams {
+            text_document: server.doc_id("Cargo.toml"),
+        },
+        json!(null),
+    );
+}
+
+#[test]
+fn test_selection_range() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn main() {
+    foo(bar + 2);
+}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let res = server.send_request::<SelectionRangeRequest>(SelectionRangeParams {
+        text_document: server.doc_id("src/lib.rs"),
+        positions: vec![Position::new(1, 9)],
+    });
+    let mut selection = &res[0];
+    assert_eq!(
+        selection["range"],
+        json!({ "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 11 } })
+    );
+    let mut depth = 0;
+    while !selection["parent"].is_null() {
+        let parent = &selection["parent"];
+        let (start, end) = (&selection["range"]["start"], &selection["range"]["end"]);
+        let (parent_start, parent_end) = (&parent["range"]["start"], &parent["range"]["end"]);
+        assert!(position_key(parent_start) <= position_key(start));
+        assert!(position_key(end) <= position_key(parent_end));
+        selection = parent;
+        depth += 1;
+    }
+    assert!(depth > 2);
+    assert_eq!(selection["range"]["start"], json!({ "line": 0, "character": 0 }));
+}
+
+fn position_key(position: &Value) -> (u64, u64) {
+    (position["line"].as_u64().unwrap(), position["character"].as_u64().unwrap())
+}
+
+#[test]
+fn test_type_of_selection() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn foo() -> u32 { 92 }
+fn main() {
+    let x = foo();
+}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<TypeOf>(
+        TypeOfParams {
+            text_document: server.doc_id("src/lib.rs"),
+            range: Range::new(Position::new(2, 12), Position::new(2, 17)),
+        },
+        json!("u32"),
+    );
+    // A part of an expression gets the type of the smallest expression
+    // covering it.
+    server.request::<TypeOf>(
+        TypeOfParams {
+            text_document: server.doc_id("src/lib.rs"),
+            range: Range::new(Position::new(2, 13), Position::new(2, 17)),
+        },
+        json!("u32"),
+    );
+}
+
+#[test]
+fn test_save_actions_edit_the_document_before_save() {
+    let config = ServerConfig {
+        on_save: OnSaveOptions {
+            organize_imports: true,
+            ..OnSaveOptions::default()
+        },
+        ..ServerConfig::default()
+    };
+    let text = "use std::sync::Arc;\nuse std::collections::HashMap;\n\nfn main() {}\n";
+    let server = project_with_config(
+        &format!(
+            "
+//- Cargo.toml
+[package]
+name = \"foo\"
+version = \"0.0.0\"
+
+//- src/lib.rs
+{}",
+            text
+        ),
+        config,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let edits = server.send_request::<WillSaveWaitUntil>(WillSaveTextDocumentParams {
+        text_document: server.doc_id("src/lib.rs"),
+        reason: TextDocumentSaveReason::Manual,
+    });
+    assert_eq!(
+        apply_edits(text, &edits),
+        "use std::collections::HashMap;\nuse std::sync::Arc;\n\nfn main() {}\n"
+    );
+
+    // Auto saves don't edit the document.
+    server.request::<WillSaveWaitUntil>(
+        WillSaveTextDocumentParams {
+            text_document: server.doc_id("src/lib.rs"),
+            reason: TextDocumentSaveReason::AfterDelay,
+        },
+        json!(null),
+    );
+}
+
+#[test]
+fn test_save_runs_cargo_check() {
+    let config = ServerConfig {
+        on_save: OnSaveOptions {
+            cargo_check: true,
+            ..OnSaveOptions::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = project_with_config(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {}
+"#,
+        config,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let params = serde_json::from_value(json!({ "textDocument": server.doc_id("src/lib.rs") }));
+    server.notification::<DidSaveTextDocument>(params.unwrap());
+    let on_save = server.wait_for_notification::<OnSave>();
+    assert_eq!(on_save["cargoCheck"]["label"], "cargo check -p foo");
+}
+
+/// Applies the LSP text edits in `edits`, which don't overlap, to `text`. The
+/// text is ASCII, so the columns are byte offsets.
+fn apply_edits(text: &str, edits: &Value) -> String {
+    let offset = |position: &Value| {
+        let line = position["line"].as_u64().unwrap() as usize;
+        let column = position["character"].as_u64().unwrap() as usize;
+        text.split('\n').take(line).map(|it| it.len() + 1).sum::<usize>() + column
+    };
+    let mut edits = edits
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|it| {
+            let range = &it["range"];
+            let new_text = it["newText"].as_str().unwrap();
+            (offset(&range["start"]), offset(&range["end"]), new_text)
+        })
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut res = text.to_string();
+    for (start, end, new_text) in edits.into_iter().rev() {
+        res.replace_range(start..end, new_text);
+    }
+    res
+}
+
 #[test]
 fn test_format_document() {
     let server = project(
@@ -246,7 +803,7 @@ fn main() {}
                   "label": "create module"
                 }
               ],
-              "command": "ra-lsp.applySourceChange",
+              "command": "apply_code_action",
               "title": "create module"
             }
         ]),
@@ -261,3 +818,39 @@ fn main() {}
         json!([]),
     );
 }
+
+#[test]
+fn test_exclude_globs_are_updated_at_runtime() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+mod generated;
+
+//- src/generated.rs
+pub fn generated_fn() {}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    let find_symbol = || {
+        let res = server.send_request::<WorkspaceSymbol>(WorkspaceSymbolParams {
+            query: "generated_fn".to_string(),
+        });
+        format!("{}", res).contains("generated_fn")
+    };
+    assert!(find_symbol());
+
+    server.notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: json!({ "excludeGlobs": ["src/generated.rs"] }),
+    });
+    assert!(!find_symbol());
+
+    server.notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: json!({}),
+    });
+    assert!(find_symbol());
+}
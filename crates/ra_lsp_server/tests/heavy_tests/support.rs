@@ -10,9 +10,10 @@ use crossbeam_channel::{after, select, Receiver};
 use flexi_logger::Logger;
 use gen_lsp_server::{RawMessage, RawNotification, RawRequest};
 use languageserver_types::{
-    notification::DidOpenTextDocument,
+    notification::{DidOpenTextDocument, Notification},
     request::{Request, Shutdown},
-    DidOpenTextDocumentParams, TextDocumentIdentifier, TextDocumentItem, Url,
+    DidOpenTextDocumentParams, ResourceOperationKind, TextDocumentIdentifier, TextDocumentItem,
+    Url,
 };
 use serde::Serialize;
 use serde_json::{to_string_pretty, Value};
@@ -21,10 +22,24 @@ use thread_worker::{WorkerHandle, Worker};
 use test_utils::{parse_fixture, find_mismatch};
 
 use ra_lsp_server::{
-    main_loop, req,
+    main_loop, req, PositionEncoding, ServerConfig,
 };
 
 pub fn project(fixture: &str) -> Server {
+    let config = ServerConfig {
+        publish_decorations: true,
+        ..ServerConfig::default()
+    };
+    project_with_config(fixture, config)
+}
+
+pub fn project_with_config(fixture: &str, config: ServerConfig) -> Server {
+    project_in_dir(fixture, "", config)
+}
+
+/// Like `project_with_config`, but the workspace is the `ws_dir` of the
+/// fixture. The files outside of it are written, but not opened.
+pub fn project_in_dir(fixture: &str, ws_dir: &str, config: ServerConfig) -> Server {
     static INIT: Once = Once::new();
     INIT.call_once(|| Logger::with_env_or_str(crate::LOG).start().unwrap());
 
@@ -37,7 +52,9 @@ pub fn project(fixture: &str) -> Server {
         fs::write(path.as_path(), entry.text.as_bytes()).unwrap();
         paths.push((path, entry.text));
     }
-    Server::new(tmp_dir, paths)
+    let ws_root = tmp_dir.path().join(ws_dir);
+    paths.retain(|(path, _)| path.starts_with(&ws_root));
+    Server::new(tmp_dir, ws_root, paths, config)
 }
 
 pub struct Server {
@@ -49,13 +66,32 @@ pub struct Server {
 }
 
 impl Server {
-    fn new(dir: TempDir, files: Vec<(PathBuf, String)>) -> Server {
-        let path = dir.path().to_path_buf();
+    fn new(
+        dir: TempDir,
+        path: PathBuf,
+        files: Vec<(PathBuf, String)>,
+        config: ServerConfig,
+    ) -> Server {
         let (worker, watcher) = thread_worker::spawn::<RawMessage, RawMessage, _>(
             "test server",
             128,
             move |mut msg_receiver, mut msg_sender| {
-                main_loop(true, path, true, &mut msg_receiver, &mut msg_sender).unwrap()
+                let resource_operations = vec![
+                    ResourceOperationKind::Create,
+                    ResourceOperationKind::Rename,
+                    ResourceOperationKind::Delete,
+                ];
+                main_loop(
+                    true,
+                    path,
+                    resource_operations,
+                    true,
+                    PositionEncoding::Utf16,
+                    config,
+                    &mut msg_receiver,
+                    &mut msg_sender,
+                )
+                .unwrap()
             },
         );
         let res = Server {
@@ -139,6 +175,22 @@ impl Server {
         }
         panic!("no response");
     }
+    pub fn notification<N>(&self, params: N::Params)
+    where
+        N: Notification,
+        N::Params: Serialize,
+    {
+        self.send_notification(RawNotification::new::<N>(&params))
+    }
+    /// Waits for a notification of type `N`, and returns its parameters.
+    pub fn wait_for_notification<N: Notification>(&self) -> Value {
+        loop {
+            match self.recv().expect("no notification") {
+                RawMessage::Notification(n) if n.method == N::METHOD => return n.params,
+                _ => (),
+            }
+        }
+    }
     pub fn wait_for_feedback(&self, feedback: &str) {
         self.wait_for_feedback_n(feedback, 1)
     }
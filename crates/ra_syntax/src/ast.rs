@@ -106,6 +106,10 @@ pub trait AttrsOwner: AstNode {
     fn attrs(&self) -> AstChildren<Attr> {
         children(self)
     }
+
+    fn has_attr(&self, name: &str) -> bool {
+        self.attrs().any(|attr| attr.name().map_or(false, |it| it == name))
+    }
 }
 
 pub trait DocCommentsOwner: AstNode {
@@ -139,6 +143,13 @@ impl FnDef {
 }
 
 impl Attr {
+    /// Name of the attribute: `foo` for `#[foo]`, `#[foo(...)]` and `#[foo = "..."]`.
+    pub fn name(&self) -> Option<SmolStr> {
+        self.as_atom()
+            .or_else(|| self.as_call().map(|(name, _)| name))
+            .or_else(|| self.as_key_value().map(|(name, _)| name))
+    }
+
     pub fn as_atom(&self) -> Option<SmolStr> {
         let tt = self.value()?;
         let (_bra, attr, _ket) = tt.syntax().children().collect_tuple()?;
@@ -149,6 +160,22 @@ impl Attr {
         }
     }
 
+    /// Destructures `#[key = "value"]` attribute into a `(key, value)` pair.
+    pub fn as_key_value(&self) -> Option<(SmolStr, SmolStr)> {
+        let tt = self.value()?;
+        let (_bra, key, eq, value, _ket) = tt
+            .syntax()
+            .children()
+            .filter(|it| it.kind() != WHITESPACE)
+            .collect_tuple()?;
+        if key.kind() != IDENT || eq.kind() != EQ || value.kind() != STRING {
+            return None;
+        }
+        let value = value.leaf_text()?;
+        let value = value.trim_start_matches('"').trim_end_matches('"');
+        Some((key.leaf_text().unwrap().clone(), value.into()))
+    }
+
     pub fn as_call(&self) -> Option<(SmolStr, &TokenTree)> {
         let tt = self.value()?;
         let (_bra, attr, args, _ket) = tt.syntax().children().collect_tuple()?;
@@ -275,6 +302,49 @@ impl Module {
     }
 }
 
+impl SourceFile {
+    /// The `//!` comments at the start of the file, which document the module
+    /// the file defines.
+    pub fn module_doc_text(&self) -> std::string::String {
+        inner_doc_text(self.syntax())
+    }
+}
+
+impl ItemList {
+    /// The `//!` comments right after the opening brace, like in
+    /// `mod foo { //! docs }`.
+    pub fn module_doc_text(&self) -> std::string::String {
+        inner_doc_text(self.syntax())
+    }
+}
+
+fn inner_doc_text(node: &SyntaxNode) -> std::string::String {
+    node.descendants()
+        .filter(|it| it.first_child().is_none())
+        .skip_while(|it| it.kind() == L_CURLY)
+        .take_while(|it| it.kind() == WHITESPACE || it.kind() == COMMENT)
+        .filter_map(Comment::cast)
+        .filter(|comment| comment.flavor() == CommentFlavor::ModuleDoc)
+        .map(|comment| {
+            comment
+                .text()
+                .as_str()
+                .trim()
+                .trim_start_matches("//!")
+                .trim_start()
+                .to_owned()
+        })
+        .join("\n")
+}
+
+impl ExternCrateItem {
+    /// The name of the crate, `foo` in `extern crate foo as bar;`. This is a
+    /// `Name` and not a `NameRef` only because of how the item is parsed.
+    pub fn crate_name(&self) -> Option<&Name> {
+        child_opt(self)
+    }
+}
+
 impl LetStmt {
     pub fn has_semi(&self) -> bool {
         match self.syntax().last_child() {
@@ -296,6 +366,42 @@ impl IfExpr {
     }
 }
 
+impl IndexExpr {
+    pub fn base(&self) -> Option<&Expr> {
+        children(self).nth(0)
+    }
+    pub fn index(&self) -> Option<&Expr> {
+        children(self).nth(1)
+    }
+}
+
+impl ArrayExpr {
+    /// Whether this is a repeat expression like `[0; 16]`, in which case the
+    /// two `exprs` are the initializer and the length.
+    pub fn is_repeat(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == SEMI)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind<'a> {
+    Name(&'a NameRef),
+    /// The number of a tuple field, like `0` in `t.0`
+    Index(&'a SyntaxNode),
+}
+
+impl FieldExpr {
+    pub fn field_access(&self) -> Option<FieldKind> {
+        if let Some(name_ref) = self.name_ref() {
+            return Some(FieldKind::Name(name_ref));
+        }
+        self.syntax()
+            .children()
+            .find(|n| n.kind() == INT_NUMBER)
+            .map(FieldKind::Index)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PathSegmentKind<'a> {
     Name(&'a NameRef),
@@ -431,6 +537,81 @@ impl RefExpr {
     }
 }
 
+impl BindPat {
+    pub fn is_ref(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == REF_KW)
+    }
+
+    /// True for `mut x`, but not for `ref mut x`, which binds a mutable
+    /// reference to an immutable variable.
+    pub fn is_mut(&self) -> bool {
+        !self.is_ref() && self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LiteralFlavor {
+    String,
+    ByteString,
+    Char,
+    Byte,
+    /// An integer literal, with its type suffix if any: `u8` in `92u8`
+    IntNumber { suffix: Option<SmolStr> },
+    /// A float literal, with its type suffix if any: `f32` in `1.0f32`
+    FloatNumber { suffix: Option<SmolStr> },
+    Bool,
+}
+
+impl Literal {
+    pub fn token(&self) -> &SyntaxNode {
+        self.syntax()
+            .first_child()
+            .expect("literal should have a token")
+    }
+
+    /// The kind of the literal, or `None` if the token is not a literal one,
+    /// which can happen in broken code.
+    pub fn flavor(&self) -> Option<LiteralFlavor> {
+        const INT_SUFFIXES: [&str; 12] = [
+            "isize", "i128", "i64", "i32", "i16", "i8", "usize", "u128", "u64", "u32", "u16", "u8",
+        ];
+        const FLOAT_SUFFIXES: [&str; 2] = ["f64", "f32"];
+
+        let token = self.token();
+        let text = token.leaf_text().map(|it| it.as_str()).unwrap_or("");
+        let suffix = |suffixes: &[&str]| {
+            suffixes
+                .iter()
+                .find(|&&it| text.ends_with(it))
+                .map(|&it| SmolStr::new(it))
+        };
+        let flavor = match token.kind() {
+            INT_NUMBER => {
+                // `1f32` is a float, but `0x1f32` is a hexadecimal integer
+                let is_hex = text.starts_with("0x");
+                match suffix(&FLOAT_SUFFIXES) {
+                    Some(suffix) if !is_hex => LiteralFlavor::FloatNumber {
+                        suffix: Some(suffix),
+                    },
+                    _ => LiteralFlavor::IntNumber {
+                        suffix: suffix(&INT_SUFFIXES),
+                    },
+                }
+            }
+            FLOAT_NUMBER => LiteralFlavor::FloatNumber {
+                suffix: suffix(&FLOAT_SUFFIXES),
+            },
+            STRING | RAW_STRING => LiteralFlavor::String,
+            BYTE_STRING | RAW_BYTE_STRING => LiteralFlavor::ByteString,
+            CHAR => LiteralFlavor::Char,
+            BYTE => LiteralFlavor::Byte,
+            TRUE_KW | FALSE_KW => LiteralFlavor::Bool,
+            _ => return None,
+        };
+        Some(flavor)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PrefixOp {
     /// The `*` operator for dereferencing
@@ -603,6 +784,37 @@ impl SelfParam {
     }
 }
 
+impl WherePred {
+    /// Trait bounds of the predicate, `Clone` and `Copy` in `T: Clone + Copy`.
+    /// Lifetime bounds are not included.
+    pub fn bounds(&self) -> impl Iterator<Item = &TypeRef> {
+        children(self).skip(1)
+    }
+}
+
+impl ImplTraitType {
+    /// Trait bounds of the type, `Iterator` in `impl Iterator + 'a`. Lifetime
+    /// bounds are not included.
+    pub fn bounds(&self) -> impl Iterator<Item = &TypeRef> {
+        children(self)
+    }
+}
+
+impl DynTraitType {
+    /// Trait bounds of the type, `Display` in `dyn Display + Send`.
+    pub fn bounds(&self) -> impl Iterator<Item = &TypeRef> {
+        children(self)
+    }
+}
+
+impl TraitDef {
+    /// Super-traits of the trait, `Clone` and `Debug` in
+    /// `trait Foo: Clone + Debug {}`. Lifetime bounds are not included.
+    pub fn super_traits(&self) -> impl Iterator<Item = &TypeRef> {
+        children(self)
+    }
+}
+
 #[test]
 fn test_doc_comment_of_items() {
     let file = SourceFile::parse(
@@ -615,3 +827,56 @@ fn test_doc_comment_of_items() {
     let module = file.syntax().descendants().find_map(Module::cast).unwrap();
     assert_eq!("doc", module.doc_comment_text());
 }
+
+#[test]
+fn test_module_doc_text() {
+    let file = SourceFile::parse(
+        r#"
+        //! Frobnicates widgets.
+        //! Carefully.
+
+        /// Not a module doc.
+        fn foo() {}
+
+        mod bar {
+            //! Bars.
+            fn baz() {}
+        }
+        "#,
+    );
+    assert_eq!("Frobnicates widgets.\nCarefully.", file.module_doc_text());
+    let item_list = file
+        .syntax()
+        .descendants()
+        .find_map(Module::cast)
+        .and_then(|it| it.item_list())
+        .unwrap();
+    assert_eq!("Bars.", item_list.module_doc_text());
+}
+
+#[test]
+fn test_key_value_attr() {
+    let file = SourceFile::parse(
+        r#"
+        #[path = "foo/bar.rs"]
+        mod foo;
+        "#,
+    );
+    let module = file.syntax().descendants().find_map(Module::cast).unwrap();
+    let attr = module.attrs().next().unwrap();
+    let (key, value) = attr.as_key_value().unwrap();
+    assert_eq!(key, "path");
+    assert_eq!(value, "foo/bar.rs");
+}
+
+#[test]
+fn test_where_pred_bounds() {
+    let file = SourceFile::parse("fn foo<T>() where T: Clone + Copy + 'static {}");
+    let pred = file.syntax().descendants().find_map(WherePred::cast).unwrap();
+    assert_eq!(pred.type_ref().unwrap().syntax().text(), "T");
+    let bounds = pred
+        .bounds()
+        .map(|it| it.syntax().text().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(bounds, vec!["Clone", "Copy"]);
+}
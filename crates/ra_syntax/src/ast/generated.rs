@@ -17,6 +17,31 @@ use crate::{
     ast::{self, AstNode},
 };
 
+// Alias
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Alias {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for Alias {
+    type Repr = rowan::SyntaxNode<RaTypes>;
+}
+
+impl AstNode for Alias {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            ALIAS => Some(Alias::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+    fn to_owned(&self) -> TreeArc<Alias> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl ast::NameOwner for Alias {}
+impl Alias {}
+
 // ArgList
 #[derive(Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -67,7 +92,11 @@ impl AstNode for ArrayExpr {
 }
 
 
-impl ArrayExpr {}
+impl ArrayExpr {
+    pub fn exprs(&self) -> impl Iterator<Item = &Expr> {
+        super::children(self)
+    }
+}
 
 // ArrayType
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -200,6 +229,7 @@ impl AstNode for Block {
 }
 
 
+impl ast::ModuleItemOwner for Block {}
 impl Block {
     pub fn statements(&self) -> impl Iterator<Item = &Stmt> {
         super::children(self)
@@ -486,7 +516,11 @@ impl ast::NameOwner for ConstDef {}
 impl ast::TypeParamsOwner for ConstDef {}
 impl ast::AttrsOwner for ConstDef {}
 impl ast::DocCommentsOwner for ConstDef {}
-impl ConstDef {}
+impl ConstDef {
+    pub fn type_ref(&self) -> Option<&TypeRef> {
+        super::child_opt(self)
+    }
+}
 
 // ContinueExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -592,6 +626,7 @@ impl AstNode for EnumVariant {
 
 
 impl ast::NameOwner for EnumVariant {}
+impl ast::AttrsOwner for EnumVariant {}
 impl EnumVariant {
     pub fn expr(&self) -> Option<&Expr> {
         super::child_opt(self)
@@ -791,7 +826,11 @@ impl AstNode for ExternCrateItem {
 }
 
 
-impl ExternCrateItem {}
+impl ExternCrateItem {
+    pub fn alias(&self) -> Option<&Alias> {
+        super::child_opt(self)
+    }
+}
 
 // FieldExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2138,7 +2177,11 @@ impl AstNode for PathPat {
 }
 
 
-impl PathPat {}
+impl PathPat {
+    pub fn path(&self) -> Option<&Path> {
+        super::child_opt(self)
+    }
+}
 
 // PathSegment
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2707,7 +2750,11 @@ impl ast::NameOwner for StaticDef {}
 impl ast::TypeParamsOwner for StaticDef {}
 impl ast::AttrsOwner for StaticDef {}
 impl ast::DocCommentsOwner for StaticDef {}
-impl StaticDef {}
+impl StaticDef {
+    pub fn type_ref(&self) -> Option<&TypeRef> {
+        super::child_opt(self)
+    }
+}
 
 // Stmt
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2861,7 +2908,15 @@ impl AstNode for StructPat {
 }
 
 
-impl StructPat {}
+impl StructPat {
+    pub fn field_pat_list(&self) -> Option<&FieldPatList> {
+        super::child_opt(self)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        super::child_opt(self)
+    }
+}
 
 // TokenTree
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2913,7 +2968,11 @@ impl ast::VisibilityOwner for TraitDef {}
 impl ast::NameOwner for TraitDef {}
 impl ast::AttrsOwner for TraitDef {}
 impl ast::DocCommentsOwner for TraitDef {}
-impl TraitDef {}
+impl TraitDef {
+    pub fn item_list(&self) -> Option<&ItemList> {
+        super::child_opt(self)
+    }
+}
 
 // TryExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2965,7 +3024,11 @@ impl AstNode for TupleExpr {
 }
 
 
-impl TupleExpr {}
+impl TupleExpr {
+    pub fn exprs(&self) -> impl Iterator<Item = &Expr> {
+        super::children(self)
+    }
+}
 
 // TuplePat
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2989,7 +3052,11 @@ impl AstNode for TuplePat {
 }
 
 
-impl TuplePat {}
+impl TuplePat {
+    pub fn args(&self) -> impl Iterator<Item = &Pat> {
+        super::children(self)
+    }
+}
 
 // TupleStructPat
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -3078,7 +3145,11 @@ impl ast::NameOwner for TypeDef {}
 impl ast::TypeParamsOwner for TypeDef {}
 impl ast::AttrsOwner for TypeDef {}
 impl ast::DocCommentsOwner for TypeDef {}
-impl TypeDef {}
+impl TypeDef {
+    pub fn type_ref(&self) -> Option<&TypeRef> {
+        super::child_opt(self)
+    }
+}
 
 // TypeParam
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -3344,7 +3415,39 @@ impl AstNode for WhereClause {
 }
 
 
-impl WhereClause {}
+impl WhereClause {
+    pub fn predicates(&self) -> impl Iterator<Item = &WherePred> {
+        super::children(self)
+    }
+}
+
+// WherePred
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct WherePred {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for WherePred {
+    type Repr = rowan::SyntaxNode<RaTypes>;
+}
+
+impl AstNode for WherePred {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            WHERE_PRED => Some(WherePred::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+    fn to_owned(&self) -> TreeArc<WherePred> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl WherePred {
+    pub fn type_ref(&self) -> Option<&TypeRef> {
+        super::child_opt(self)
+    }
+}
 
 // WhileExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
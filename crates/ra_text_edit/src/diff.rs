@@ -0,0 +1,249 @@
+use text_unit::{TextRange, TextUnit};
+
+use crate::{TextEdit, TextEditBuilder};
+
+/// Diffs with more changed lines than this are not minimized, as the diff
+/// itself would take too much time and memory.
+const MAX_CHANGED_LINES: usize = 1000;
+
+impl TextEdit {
+    /// Computes a small edit which turns `old` into `new`, so that editors
+    /// can keep the cursors, folds and undo history of the unchanged parts.
+    ///
+    /// The lines are diffed first, and then each group of changed lines is
+    /// shrunk to the characters which actually differ.
+    pub fn diff(old: &str, new: &str) -> TextEdit {
+        let mut builder = TextEditBuilder::default();
+        let range = TextRange::offset_len(0.into(), TextUnit::of_str(old));
+        builder.replace_with_diff(range, old, new);
+        builder.finish()
+    }
+}
+
+impl TextEditBuilder {
+    /// Like `replace`, but only the parts of `old`, the current text of the
+    /// `range`, which differ from `new` are replaced. See `TextEdit::diff`.
+    pub fn replace_with_diff(&mut self, range: TextRange, old: &str, new: &str) {
+        assert_eq!(range.len(), TextUnit::of_str(old));
+        let start = u32::from(range.start()) as usize;
+        let old_lines = lines_with_ends(old);
+        let new_lines = lines_with_ends(new);
+        let old_offsets = line_offsets(&old_lines);
+        let new_offsets = line_offsets(&new_lines);
+
+        let prefix = old_lines
+            .iter()
+            .zip(new_lines.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old_lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+        let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+        for hunk in diff_lines(old_middle, new_middle) {
+            let old_start = old_offsets[prefix + hunk.old_start];
+            let old_end = old_offsets[prefix + hunk.old_end];
+            let new_start = new_offsets[prefix + hunk.new_start];
+            let new_end = new_offsets[prefix + hunk.new_end];
+            push_refined(
+                self,
+                start + old_start,
+                &old[old_start..old_end],
+                &new[new_start..new_end],
+            );
+        }
+    }
+}
+
+/// The lines `old[old_start..old_end]` are replaced with
+/// `new[new_start..new_end]`.
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    old_start: usize,
+    old_end: usize,
+    new_start: usize,
+    new_end: usize,
+}
+
+fn lines_with_ends(text: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    for (idx, _) in text.match_indices('\n') {
+        res.push(&text[start..idx + 1]);
+        start = idx + 1;
+    }
+    if start < text.len() {
+        res.push(&text[start..]);
+    }
+    res
+}
+
+/// Byte offsets of the starts of the lines, and of the end of the text.
+fn line_offsets(lines: &[&str]) -> Vec<usize> {
+    let mut res = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    res.push(offset);
+    for line in lines {
+        offset += line.len();
+        res.push(offset);
+    }
+    res
+}
+
+/// Myers' diff algorithm, "An O(ND) Difference Algorithm and Its Variations".
+/// Falls back to replacing everything if there are too many changes.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let everything = Hunk {
+        old_start: 0,
+        old_end: old.len(),
+        new_start: 0,
+        new_end: new.len(),
+    };
+    if old.is_empty() || new.is_empty() {
+        if old.is_empty() && new.is_empty() {
+            return Vec::new();
+        }
+        return vec![everything];
+    }
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = n + m;
+    let offset = max;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    // `trace[d]` is the part of `v` which is used on the step `d`, as it was
+    // before that step.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let get = |v: &[isize], k: isize| v[(k + offset) as usize];
+    'search: for d in 0..=max {
+        if d as usize > MAX_CHANGED_LINES {
+            return vec![everything];
+        }
+        let lo = (offset - d) as usize;
+        let hi = (offset + d) as usize;
+        trace.push(v[lo..=hi].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && get(&v, k - 1) < get(&v, k + 1)) {
+                get(&v, k + 1)
+            } else {
+                get(&v, k - 1) + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back through the trace, collecting the pairs of equal lines.
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, snapshot) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let prev = |k: isize| snapshot[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && prev(k - 1) < prev(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { prev(prev_k) };
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    matches.reverse();
+
+    let mut res = Vec::new();
+    let (mut old_start, mut new_start) = (0, 0);
+    for (x, y) in matches.into_iter().chain(Some((old.len(), new.len()))) {
+        if x > old_start || y > new_start {
+            res.push(Hunk { old_start, old_end: x, new_start, new_end: y });
+        }
+        old_start = x + 1;
+        new_start = y + 1;
+    }
+    res
+}
+
+/// Replaces `old`, which starts at `start`, with `new`, leaving out their
+/// common prefix and suffix.
+fn push_refined(builder: &mut TextEditBuilder, start: usize, old: &str, new: &str) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .take_while(|((_, a), b)| a == b)
+        .last()
+        .map_or(0, |((idx, c), _)| idx + c.len_utf8());
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix: usize = old
+        .chars()
+        .rev()
+        .zip(new.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.is_empty() && new.is_empty() {
+        return;
+    }
+    let start = TextUnit::from_usize(start + prefix);
+    let range = TextRange::offset_len(start, TextUnit::of_str(old));
+    builder.replace(range, new.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_utils::arb_text;
+
+    fn check_diff(old: &str, new: &str, expected_atoms: &[(&str, &str)]) {
+        let edit = TextEdit::diff(old, new);
+        assert_eq!(edit.apply(old), new);
+        let actual_atoms = edit
+            .as_atoms()
+            .iter()
+            .map(|atom| {
+                let start = u32::from(atom.delete.start()) as usize;
+                let end = u32::from(atom.delete.end()) as usize;
+                (&old[start..end], atom.insert.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual_atoms, expected_atoms);
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        check_diff("a\nb\nc\n", "a\nb\nc\n", &[]);
+        check_diff("a\nb\nc\n", "a\nc\n", &[("b\n", "")]);
+        check_diff("fn foo(){}\nfn bar() {}\n", "fn foo() {}\nfn bar() {}\n", &[("", " ")]);
+        check_diff(
+            "use b;\nuse a;\nfn main() {}\n",
+            "use a;\nuse b;\nfn main() {}\n",
+            &[("use b;\n", ""), ("", "use b;\n")],
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn diff_turns_old_text_into_new_text(old in arb_text(), new in arb_text()) {
+            prop_assert_eq!(TextEdit::diff(&old, &new).apply(&old), new);
+        }
+    }
+}
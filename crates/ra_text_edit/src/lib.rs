@@ -1,4 +1,5 @@
 mod text_edit;
+mod diff;
 pub mod test_utils;
 
 pub use crate::text_edit::{TextEdit, TextEditBuilder};
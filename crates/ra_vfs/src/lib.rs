@@ -40,6 +40,8 @@ pub use crate::io::TaskResult as VfsTask;
 struct RootFilter {
     root: PathBuf,
     file_filter: fn(&Path) -> bool,
+    /// Set for the roots of detached files, which contain only that file.
+    only_file: Option<PathBuf>,
 }
 
 impl RootFilter {
@@ -47,6 +49,14 @@ impl RootFilter {
         RootFilter {
             root,
             file_filter: has_rs_extension,
+            only_file: None,
+        }
+    }
+    fn detached(file: PathBuf) -> RootFilter {
+        RootFilter {
+            root: file.parent().unwrap().to_path_buf(),
+            file_filter: has_rs_extension,
+            only_file: Some(file),
         }
     }
     /// Check if this root can contain `path`. NB: even if this returns
@@ -55,8 +65,26 @@ impl RootFilter {
         if !(self.file_filter)(path) {
             return None;
         }
+        if let Some(file) = &self.only_file {
+            if file != path {
+                return None;
+            }
+        }
         let path = path.strip_prefix(&self.root).ok()?;
-        RelativePathBuf::from_path(path).ok()
+        let path = RelativePathBuf::from_path(path).ok()?;
+        let mut dirs = path.as_str().match_indices('/').map(|(idx, _)| &path.as_str()[..idx]);
+        if dirs.any(is_ignored_dir) {
+            return None;
+        }
+        Some(path)
+    }
+    /// Check if `path` is in the directory of this root, even if it's not
+    /// a file of the root.
+    fn covers(&self, path: &Path) -> bool {
+        match &self.only_file {
+            Some(file) => file == path,
+            None => path.starts_with(&self.root),
+        }
     }
 }
 
@@ -64,6 +92,14 @@ fn has_rs_extension(p: &Path) -> bool {
     p.extension() == Some(OsStr::new("rs"))
 }
 
+/// Build artifacts and VCS metadata are never loaded, even if they happen to
+/// contain `.rs` files. `dir` is relative to the root, with `/` separators.
+/// Only the `target` right in the root is skipped, a module can be called
+/// `target` too.
+fn is_ignored_dir(dir: &str) -> bool {
+    dir == "target" || dir == ".git" || dir.ends_with("/.git")
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct VfsRoot(pub RawId);
 impl_arena_id!(VfsRoot);
@@ -82,6 +118,9 @@ pub struct Vfs {
     roots: Arena<VfsRoot, RootFilter>,
     files: Arena<VfsFile, VfsFileData>,
     root2files: FxHashMap<VfsRoot, FxHashSet<VfsFile>>,
+    /// Files with unsaved contents in the editor. Their contents on disk are
+    /// ignored until the overlay is removed.
+    overlays: FxHashSet<VfsFile>,
     pending_changes: Vec<VfsChange>,
     worker: io::Worker,
     worker_handle: WorkerHandle,
@@ -101,6 +140,7 @@ impl Vfs {
             roots: Arena::default(),
             files: Arena::default(),
             root2files: FxHashMap::default(),
+            overlays: FxHashSet::default(),
             worker,
             worker_handle,
             pending_changes: Vec::new(),
@@ -116,11 +156,25 @@ impl Vfs {
                 .filter(|it| it.starts_with(path))
                 .map(|it| it.clone())
                 .collect::<Vec<_>>();
+            let root_path = path.clone();
             let filter = move |entry: &DirEntry| {
-                if entry.file_type().is_file() {
+                if entry.depth() == 0 {
+                    // The root itself.
+                    true
+                } else if entry.file_type().is_file() {
                     has_rs_extension(entry.path())
+                } else if entry.path_is_symlink() {
+                    // Symlinks may point outside of the root, or even to its
+                    // parent, so they are not followed.
+                    false
                 } else {
-                    nested.iter().all(|it| it != entry.path())
+                    let relative = entry
+                        .path()
+                        .strip_prefix(&root_path)
+                        .ok()
+                        .and_then(|it| RelativePathBuf::from_path(it).ok());
+                    let is_ignored = relative.map_or(false, |it| is_ignored_dir(it.as_str()));
+                    !is_ignored && nested.iter().all(|it| it != entry.path())
                 }
             };
             let task = io::Task {
@@ -134,6 +188,21 @@ impl Vfs {
         (res, roots)
     }
 
+    pub fn roots(&self) -> Vec<VfsRoot> {
+        self.roots.iter().map(|(id, _)| id).collect()
+    }
+
+    /// Current files of the `root`, in the same form as in `VfsChange::AddRoot`.
+    pub fn root_files(&self, root: VfsRoot) -> Vec<(VfsFile, RelativePathBuf, Arc<String>)> {
+        self.root2files[&root]
+            .iter()
+            .map(|&file| {
+                let data = &self.files[file];
+                (file, data.path.clone(), Arc::clone(&data.text))
+            })
+            .collect()
+    }
+
     pub fn root2path(&self, root: VfsRoot) -> PathBuf {
         self.roots[root].root.clone()
     }
@@ -151,6 +220,10 @@ impl Vfs {
         rel_path.to_path(root_path)
     }
 
+    pub fn file2text(&self, file: VfsFile) -> Arc<String> {
+        Arc::clone(&self.files[file].text)
+    }
+
     pub fn file_for_path(&self, path: &Path) -> Option<VfsFile> {
         if let Some((_root, _path, Some(file))) = self.find_root(path) {
             return Some(file);
@@ -193,6 +266,9 @@ impl Vfs {
             .collect::<FxHashMap<_, _>>();
         for (path, text) in task.files {
             if let Some(&file) = exising.get(&path) {
+                if !self.overlays.contains(&file) {
+                    self.change_file(file, Arc::new(text));
+                }
                 let text = Arc::clone(&self.files[file].text);
                 files.push((file, path, text));
                 continue;
@@ -229,9 +305,88 @@ impl Vfs {
             };
             self.pending_changes.push(change);
         }
+        if let Some(file) = res {
+            self.overlays.insert(file);
+        }
         res
     }
 
+    /// Whether the file has unsaved contents in the editor.
+    pub fn has_overlay(&self, file: VfsFile) -> bool {
+        self.overlays.contains(&file)
+    }
+
+    /// Handles a file watcher event: the file at `path` was created, changed
+    /// or deleted on disk. Files with overlays are skipped, the editor
+    /// contents win until the overlay is removed.
+    pub fn notify_changed(&mut self, path: &Path) {
+        let (root, rel_path, file) = match self.find_root(path) {
+            Some(it) => it,
+            None => return,
+        };
+        if let Some(file) = file {
+            if self.overlays.contains(&file) {
+                return;
+            }
+        }
+        let change = match (file, fs::read_to_string(path)) {
+            (Some(file), Ok(text)) => {
+                if *self.files[file].text == text {
+                    return;
+                }
+                let text = Arc::new(text);
+                self.change_file(file, Arc::clone(&text));
+                VfsChange::ChangeFile { file, text }
+            }
+            (Some(file), Err(_)) => {
+                self.remove_file(file);
+                VfsChange::RemoveFile {
+                    root,
+                    file,
+                    path: rel_path,
+                }
+            }
+            (None, Ok(text)) => {
+                let text = Arc::new(text);
+                let file = self.add_file(root, rel_path.clone(), Arc::clone(&text));
+                VfsChange::AddFile {
+                    file,
+                    text,
+                    root,
+                    path: rel_path,
+                }
+            }
+            (None, Err(_)) => return,
+        };
+        self.pending_changes.push(change);
+    }
+
+    /// Whether `path` is a Rust file outside of the directories of all the
+    /// roots, see `add_detached_file`.
+    pub fn is_detached(&self, path: &Path) -> bool {
+        has_rs_extension(path) && !self.roots.iter().any(|(_, it)| it.covers(path))
+    }
+
+    /// Adds a Rust file which is outside of all the roots, like a scratch file
+    /// open in the editor, as a root of its own. The file is not watched, so
+    /// it should be kept in sync with the overlay methods. Returns `None` if
+    /// the file is not detached.
+    pub fn add_detached_file(
+        &mut self,
+        path: &Path,
+        text: Arc<String>,
+    ) -> Option<(VfsRoot, VfsFile, RelativePathBuf)> {
+        if !self.is_detached(path) {
+            return None;
+        }
+        let name = RelativePathBuf::from_path(path.file_name()?).ok()?;
+        let root = self.roots.alloc(RootFilter::detached(path.to_path_buf()));
+        self.root2files.insert(root, Default::default());
+        let file = self.add_file(root, name.clone(), text);
+        self.overlays.insert(file);
+        Some((root, file, name))
+    }
+
     pub fn change_file_overlay(&mut self, path: &Path, new_text: String) {
         if let Some((_root, _path, file)) = self.find_root(path) {
             let file = file.expect("can't change a file which wasn't added");
@@ -242,11 +397,13 @@ impl Vfs {
         }
     }
 
+    /// Removes the overlay, the file is re-read from disk.
     pub fn remove_file_overlay(&mut self, path: &Path) -> Option<VfsFile> {
         let mut res = None;
         if let Some((root, path, file)) = self.find_root(path) {
             let file = file.expect("can't remove a file which wasn't added");
             res = Some(file);
+            self.overlays.remove(&file);
             let full_path = path.to_path(&self.roots[root].root);
             let change = if let Ok(text) = fs::read_to_string(&full_path) {
                 let text = Arc::new(text);
@@ -1,6 +1,7 @@
 use std::{
     fs,
     collections::HashSet,
+    sync::Arc,
 };
 
 use tempfile::tempdir;
@@ -99,3 +100,121 @@ fn test_vfs_works() -> std::io::Result<()> {
     vfs.shutdown().unwrap();
     Ok(())
 }
+
+#[test]
+fn test_vfs_skips_ignored_dirs() -> std::io::Result<()> {
+    let files = [
+        ("src/lib.rs", "lib"),
+        ("src/target/mod.rs", "module"),
+        ("target/debug/build/out.rs", "generated"),
+        (".git/hooks/hook.rs", "hook"),
+    ];
+
+    let dir = tempdir()?;
+    for (path, text) in files.iter() {
+        let file_path = dir.path().join(path);
+        fs::create_dir_all(file_path.parent().unwrap())?;
+        fs::write(file_path, text)?
+    }
+
+    let (mut vfs, _) = Vfs::new(vec![dir.path().to_path_buf()]);
+    let task = vfs.task_receiver().recv().unwrap();
+    vfs.handle_task(task);
+    let mut files = vfs
+        .commit_changes()
+        .into_iter()
+        .flat_map(|change| match change {
+            VfsChange::AddRoot { files, .. } => files,
+            _ => panic!("unexpected change"),
+        })
+        .map(|(_id, path, _text)| format!("{}", path.display()))
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, vec!["src/lib.rs".to_string(), "src/target/mod.rs".to_string()]);
+
+    let generated = dir.path().join("target/debug/build/out.rs");
+    assert!(vfs.add_file_overlay(&generated, "changed".to_string()).is_none());
+    assert!(vfs.commit_changes().is_empty());
+
+    vfs.shutdown().unwrap();
+    Ok(())
+}
+
+#[test]
+fn test_vfs_adds_detached_files() -> std::io::Result<()> {
+    let dir = tempdir()?;
+    let ws_root = dir.path().join("ws");
+    fs::create_dir_all(&ws_root)?;
+    let (mut vfs, _) = Vfs::new(vec![ws_root.clone()]);
+    let task = vfs.task_receiver().recv().unwrap();
+    vfs.handle_task(task);
+    vfs.commit_changes();
+
+    let text = Arc::new("fn main() {}".to_string());
+    assert!(vfs.add_detached_file(&ws_root.join("target/out.rs"), Arc::clone(&text)).is_none());
+    assert!(vfs.add_detached_file(&dir.path().join("notes.txt"), Arc::clone(&text)).is_none());
+
+    let scratch = dir.path().join("scratch.rs");
+    let (root, file, path) = vfs.add_detached_file(&scratch, Arc::clone(&text)).unwrap();
+    assert_eq!(path, "scratch.rs");
+    assert_eq!(vfs.path2file(&scratch), Some(file));
+    assert_eq!(vfs.file2path(file), scratch);
+    assert_eq!(vfs.root_files(root).len(), 1);
+    assert!(!vfs.is_detached(&scratch));
+    assert!(vfs.path2file(&dir.path().join("other.rs")).is_none());
+    assert!(vfs.is_detached(&dir.path().join("other.rs")));
+
+    vfs.change_file_overlay(&scratch, "fn foo() {}".to_string());
+    match vfs.commit_changes().pop().unwrap() {
+        VfsChange::ChangeFile { text, .. } => assert_eq!(&*text, "fn foo() {}"),
+        _ => panic!("unexpected change"),
+    }
+
+    vfs.shutdown().unwrap();
+    Ok(())
+}
+
+#[test]
+fn test_vfs_ignores_disk_changes_of_files_with_overlays() -> std::io::Result<()> {
+    let dir = tempdir()?;
+    let file_path = dir.path().join("lib.rs");
+    fs::write(&file_path, "disk")?;
+
+    let (mut vfs, _) = Vfs::new(vec![dir.path().to_path_buf()]);
+    let task = vfs.task_receiver().recv().unwrap();
+    vfs.handle_task(task);
+    vfs.commit_changes();
+
+    let file = vfs.add_file_overlay(&file_path, "editor".to_string()).unwrap();
+    assert!(vfs.has_overlay(file));
+    vfs.commit_changes();
+
+    fs::write(&file_path, "disk, changed")?;
+    vfs.notify_changed(&file_path);
+    assert!(vfs.commit_changes().is_empty());
+    assert_eq!(&*vfs.file2text(file), "editor");
+
+    vfs.remove_file_overlay(&file_path);
+    assert!(!vfs.has_overlay(file));
+    match vfs.commit_changes().pop().unwrap() {
+        VfsChange::ChangeFile { text, .. } => assert_eq!(&*text, "disk, changed"),
+        _ => panic!("unexpected change"),
+    }
+
+    fs::write(&file_path, "disk, changed again")?;
+    vfs.notify_changed(&file_path);
+    match vfs.commit_changes().pop().unwrap() {
+        VfsChange::ChangeFile { text, .. } => assert_eq!(&*text, "disk, changed again"),
+        _ => panic!("unexpected change"),
+    }
+
+    fs::remove_file(&file_path)?;
+    vfs.notify_changed(&file_path);
+    match vfs.commit_changes().pop().unwrap() {
+        VfsChange::RemoveFile { path, .. } => assert_eq!(path, "lib.rs"),
+        _ => panic!("unexpected change"),
+    }
+
+    vfs.shutdown().unwrap();
+    Ok(())
+}
@@ -0,0 +1,14 @@
+import * as vscode from 'vscode';
+
+import { Server } from '../server';
+
+export async function handle() {
+    const status = await Server.client.sendRequest<string>(
+        'm/analyzerStatus',
+        null
+    );
+    const document = await vscode.workspace.openTextDocument({
+        content: status
+    });
+    await vscode.window.showTextDocument(document, vscode.ViewColumn.Two, true);
+}
@@ -7,9 +7,24 @@ export interface SourceChange {
     label: string;
     workspaceEdit: lc.WorkspaceEdit;
     cursorPosition?: lc.TextDocumentPositionParams;
+    snippetEdit?: lc.TextDocumentEdit;
 }
 
-export async function handle(change: SourceChange) {
+// The server sends `m/applySourceChange` to apply the source changes of the
+// code actions, and shows an error if they are not applied.
+export async function handleRequest(
+    change: SourceChange
+): Promise<{ applied: boolean }> {
+    return { applied: await handle(change) };
+}
+
+export async function handle(change: SourceChange): Promise<boolean> {
+    if (
+        change.snippetEdit &&
+        (await applySnippetChange(change, change.snippetEdit))
+    ) {
+        return true;
+    }
     const wsEdit = Server.client.protocol2CodeConverter.asWorkspaceEdit(
         change.workspaceEdit
     );
@@ -26,7 +41,9 @@ export async function handle(change: SourceChange) {
     }
     const toOpen = created || moved;
     const toReveal = change.cursorPosition;
-    await vscode.workspace.applyEdit(wsEdit);
+    if (!(await vscode.workspace.applyEdit(wsEdit))) {
+        return false;
+    }
     if (toOpen) {
         const toOpenUri = vscode.Uri.parse(toOpen);
         const doc = await vscode.workspace.openTextDocument(toOpenUri);
@@ -39,12 +56,55 @@ export async function handle(change: SourceChange) {
             toReveal.position
         );
         const editor = vscode.window.activeTextEditor;
-        if (!editor || editor.document.uri.toString() !== uri.toString()) {
-            return;
+        if (
+            editor &&
+            editor.document.uri.toString() === uri.toString() &&
+            editor.selection.isEmpty
+        ) {
+            editor.selection = new vscode.Selection(position, position);
         }
-        if (!editor.selection.isEmpty) {
-            return;
+    }
+    return true;
+}
+
+// The server merges the edits of the document with the placeholders into a
+// single snippet edit, which is expanded in the editor, so that the
+// placeholders can be visited with tab. The workspace edit has the same change
+// in plain text, which is applied instead if this returns false.
+async function applySnippetChange(
+    change: SourceChange,
+    snippetEdit: lc.TextDocumentEdit
+): Promise<boolean> {
+    const editor = vscode.window.activeTextEditor;
+    const documentChanges = change.workspaceEdit.documentChanges;
+    const uri = snippetEdit.textDocument.uri;
+    if (
+        !editor ||
+        editor.document.uri.toString() !== uri ||
+        !documentChanges ||
+        snippetEdit.edits.length !== 1
+    ) {
+        return false;
+    }
+    const edit = snippetEdit.edits[0];
+    const inserted = await editor.insertSnippet(
+        new vscode.SnippetString(edit.newText),
+        Server.client.protocol2CodeConverter.asRange(edit.range)
+    );
+    if (!inserted) {
+        return false;
+    }
+    const otherChanges = [];
+    for (const docChange of documentChanges) {
+        if (
+            !lc.TextDocumentEdit.is(docChange) ||
+            docChange.textDocument.uri !== uri
+        ) {
+            otherChanges.push(docChange);
         }
-        editor.selection = new vscode.Selection(position, position);
     }
+    const wsEdit = Server.client.protocol2CodeConverter.asWorkspaceEdit({
+        documentChanges: otherChanges
+    });
+    return vscode.workspace.applyEdit(wsEdit);
 }
@@ -1,19 +1,25 @@
+import * as analyzerStatus from './analyzer_status';
 import * as applySourceChange from './apply_source_change';
 import * as extendSelection from './extend_selection';
 import * as joinLines from './join_lines';
 import * as matchingBrace from './matching_brace';
 import * as onEnter from './on_enter';
 import * as parentModule from './parent_module';
+import * as reloadWorkspace from './reload_workspace';
 import * as runnables from './runnables';
 import * as syntaxTree from './syntaxTree';
+import * as typeOf from './type_of';
 
 export {
+    analyzerStatus,
     applySourceChange,
     extendSelection,
     joinLines,
     matchingBrace,
     parentModule,
+    reloadWorkspace,
     runnables,
     syntaxTree,
+    typeOf,
     onEnter
 };
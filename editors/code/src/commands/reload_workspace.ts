@@ -0,0 +1,8 @@
+import * as vscode from 'vscode';
+
+import { Server } from '../server';
+
+export async function handle() {
+    await Server.client.sendRequest<null>('m/reloadWorkspace', null);
+    vscode.window.showInformationMessage('Rust workspace reloaded');
+}
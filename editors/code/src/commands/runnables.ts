@@ -7,7 +7,7 @@ interface RunnablesParams {
     position?: lc.Position;
 }
 
-interface Runnable {
+export interface Runnable {
     range: lc.Range;
     label: string;
     bin: string;
@@ -34,7 +34,7 @@ interface CargoTaskDefinition extends vscode.TaskDefinition {
     env?: { [key: string]: string };
 }
 
-function createTask(spec: Runnable): vscode.Task {
+export function createTask(spec: Runnable): vscode.Task {
     const TASK_SOURCE = 'Rust';
     const definition: CargoTaskDefinition = {
         type: 'cargo',
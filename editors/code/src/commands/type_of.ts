@@ -0,0 +1,29 @@
+import * as vscode from 'vscode';
+
+import { Range, TextDocumentIdentifier } from 'vscode-languageclient';
+import { Server } from '../server';
+
+interface TypeOfParams {
+    textDocument: TextDocumentIdentifier;
+    range: Range;
+}
+
+export async function handle() {
+    const editor = vscode.window.activeTextEditor;
+    if (editor == null || editor.document.languageId !== 'rust') {
+        return;
+    }
+    const request: TypeOfParams = {
+        range: Server.client.code2ProtocolConverter.asRange(editor.selection),
+        textDocument: { uri: editor.document.uri.toString() }
+    };
+    const ty = await Server.client.sendRequest<string | null>(
+        'm/typeOf',
+        request
+    );
+    if (ty == null) {
+        vscode.window.showInformationMessage('Type is unknown');
+        return;
+    }
+    vscode.window.showInformationMessage(ty);
+}
@@ -4,7 +4,23 @@ import { Server } from './server';
 
 export class Config {
     public highlightingOn = true;
+    public parameterHintsOn = true;
+    public chainingHintsOn = true;
+    public hoverImplementedTraits = true;
+    public hoverMaxImplementedTraits = 10;
     public raLspServerPath = 'ra_lsp_server';
+    public runnableEnv: { [key: string]: string } = {};
+    public runnableCargoArgs: string[] = [];
+    public cargoCheckCommand = 'check';
+    public completionSnippets = true;
+    public snippets: object[] = [];
+    public importStyle = 'plain';
+    public excludeGlobs: string[] = [];
+    public cargoCheckOnSave = false;
+    public organizeImportsOnSave = false;
+    public rustfmtOnSave = false;
+    public symbolSearchLibraries = false;
+    public requestBudgetMs: number | null = null;
 
     constructor() {
         vscode.workspace.onDidChangeConfiguration(_ =>
@@ -23,8 +39,126 @@ export class Config {
             Server.highlighter.removeHighlights();
         }
 
+        if (config.has('parameterHintsOn')) {
+            this.parameterHintsOn = config.get('parameterHintsOn') as boolean;
+        }
+
+        if (config.has('chainingHintsOn')) {
+            this.chainingHintsOn = config.get('chainingHintsOn') as boolean;
+        }
+
+        if (config.has('hoverImplementedTraits')) {
+            this.hoverImplementedTraits = config.get(
+                'hoverImplementedTraits'
+            ) as boolean;
+        }
+
+        if (config.has('hoverMaxImplementedTraits')) {
+            this.hoverMaxImplementedTraits = config.get(
+                'hoverMaxImplementedTraits'
+            ) as number;
+        }
+
         if (config.has('raLspServerPath')) {
             this.raLspServerPath = config.get('raLspServerPath') as string;
         }
+
+        if (config.has('runnableEnv')) {
+            this.runnableEnv = config.get('runnableEnv') as {
+                [key: string]: string;
+            };
+        }
+
+        if (config.has('runnableCargoArgs')) {
+            this.runnableCargoArgs = config.get(
+                'runnableCargoArgs'
+            ) as string[];
+        }
+
+        if (config.has('cargoCheckCommand')) {
+            this.cargoCheckCommand = config.get('cargoCheckCommand') as string;
+        }
+
+        if (config.has('completionSnippets')) {
+            this.completionSnippets = config.get(
+                'completionSnippets'
+            ) as boolean;
+        }
+
+        if (config.has('snippets')) {
+            this.snippets = config.get('snippets') as object[];
+        }
+
+        if (config.has('importStyle')) {
+            this.importStyle = config.get('importStyle') as string;
+        }
+
+        if (config.has('excludeGlobs')) {
+            this.excludeGlobs = config.get('excludeGlobs') as string[];
+        }
+
+        if (config.has('cargoCheckOnSave')) {
+            this.cargoCheckOnSave = config.get('cargoCheckOnSave') as boolean;
+        }
+
+        if (config.has('organizeImportsOnSave')) {
+            this.organizeImportsOnSave = config.get(
+                'organizeImportsOnSave'
+            ) as boolean;
+        }
+
+        if (config.has('rustfmtOnSave')) {
+            this.rustfmtOnSave = config.get('rustfmtOnSave') as boolean;
+        }
+
+        if (config.has('symbolSearchLibraries')) {
+            this.symbolSearchLibraries = config.get(
+                'symbolSearchLibraries'
+            ) as boolean;
+        }
+
+        if (config.has('requestBudgetMs')) {
+            this.requestBudgetMs = config.get('requestBudgetMs') as
+                | number
+                | null;
+        }
+
+        if (Server && Server.client) {
+            Server.client.sendNotification(
+                'workspace/didChangeConfiguration',
+                { settings: this.serverSettings() }
+            );
+        }
+    }
+
+    // Settings which are sent to the server, both on startup and on change.
+    public serverSettings() {
+        return {
+            publishDecorations: true,
+            runnables: {
+                env: this.runnableEnv,
+                cargoArgs: this.runnableCargoArgs
+            },
+            inlayHints: {
+                parameterHints: this.parameterHintsOn,
+                chainingHints: this.chainingHintsOn
+            },
+            hover: {
+                implementedTraits: this.hoverImplementedTraits,
+                maxImplementedTraits: this.hoverMaxImplementedTraits
+            },
+            cargoCheckCommand: this.cargoCheckCommand,
+            completionSnippets: this.completionSnippets,
+            snippets: this.snippets,
+            importStyle: this.importStyle,
+            excludeGlobs: this.excludeGlobs,
+            onSave: {
+                cargoCheck: this.cargoCheckOnSave,
+                organizeImports: this.organizeImportsOnSave,
+                rustfmt: this.rustfmtOnSave
+            },
+            symbolSearchLibraries: this.symbolSearchLibraries,
+            requestBudgetMs: this.requestBudgetMs
+        };
     }
 }
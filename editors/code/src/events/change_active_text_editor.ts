@@ -2,9 +2,11 @@ import { TextEditor } from 'vscode';
 import { TextDocumentIdentifier } from 'vscode-languageclient';
 
 import { Decoration } from '../highlighting';
+import { updateHints } from '../inlay_hints';
 import { Server } from '../server';
 
 export async function handle(editor: TextEditor | undefined) {
+    updateHints(editor);
     if (
         !Server.config.highlightingOn ||
         !editor ||
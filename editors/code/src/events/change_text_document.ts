@@ -4,6 +4,7 @@ import {
     syntaxTreeUri,
     TextDocumentContentProvider
 } from '../commands/syntaxTree';
+import { updateHints } from '../inlay_hints';
 
 export function createHandler(
     textDocumentContentProvider: TextDocumentContentProvider
@@ -15,6 +16,10 @@ export function createHandler(
         }
         afterLs(() => {
             textDocumentContentProvider.eventEmitter.fire(syntaxTreeUri);
+            const editor = vscode.window.activeTextEditor;
+            if (editor && editor.document === doc) {
+                updateHints(editor);
+            }
         });
     };
 }
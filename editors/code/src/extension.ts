@@ -51,6 +51,9 @@ export function activate(context: vscode.ExtensionContext) {
     registerCommand('ra-lsp.joinLines', commands.joinLines.handle);
     registerCommand('ra-lsp.parentModule', commands.parentModule.handle);
     registerCommand('ra-lsp.run', commands.runnables.handle);
+    registerCommand('ra-lsp.typeOf', commands.typeOf.handle);
+    registerCommand('ra-lsp.reloadWorkspace', commands.reloadWorkspace.handle);
+    registerCommand('ra-lsp.analyzerStatus', commands.analyzerStatus.handle);
     registerCommand(
         'ra-lsp.applySourceChange',
         commands.applySourceChange.handle
@@ -63,7 +66,15 @@ export function activate(context: vscode.ExtensionContext) {
     // Notifications are events triggered by the language server
     const allNotifications: Iterable<
         [string, lc.GenericNotificationHandler]
-    > = [['m/publishDecorations', notifications.publishDecorations.handle]];
+    > = [
+        ['m/publishDecorations', notifications.publishDecorations.handle],
+        ['m/onSave', notifications.onSave.handle]
+    ];
+
+    // Requests are sent by the language server, which waits for the results
+    const allRequests: Iterable<
+        [string, lc.GenericRequestHandler<any, any>]
+    > = [['m/applySourceChange', commands.applySourceChange.handleRequest]];
 
     // The events below are plain old javascript events, triggered and handled by vscode
     vscode.window.onDidChangeActiveTextEditor(
@@ -85,7 +96,7 @@ export function activate(context: vscode.ExtensionContext) {
     );
 
     // Start the language server, finally!
-    Server.start(allNotifications);
+    Server.start(allNotifications, allRequests);
 }
 
 export function deactivate(): Thenable<void> {
@@ -15,6 +15,10 @@ export class Highlighter {
     > {
         const decor = (color: string) =>
             vscode.window.createTextEditorDecorationType({ color });
+        const underline = () =>
+            vscode.window.createTextEditorDecorationType({
+                textDecoration: 'underline'
+            });
 
         const decorations: Iterable<
             [string, vscode.TextEditorDecorationType]
@@ -27,9 +31,18 @@ export class Highlighter {
             ['parameter', decor('#94BFF3')],
             ['builtin', decor('#DD6718')],
             ['text', decor('#DCDCCC')],
+            ['variable', decor('#DCDCCC')],
+            ['field', decor('#DCDCCC')],
+            ['type', decor('#7CB8BB')],
+            ['module', decor('#DCDCCC')],
+            ['constant', decor('#DFAF8F')],
             ['attribute', decor('#BFEBBF')],
             ['literal', decor('#DFAF8F')],
-            ['macro', decor('#DFAF8F')]
+            ['macro', decor('#DFAF8F')],
+            ['unsafe', decor('#CC9393')],
+            ['mutable', underline()],
+            ['mutation', underline()],
+            ['format_specifier', decor('#DCA3A3')]
         ];
 
         return new Map<string, vscode.TextEditorDecorationType>(decorations);
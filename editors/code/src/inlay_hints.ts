@@ -0,0 +1,63 @@
+import * as vscode from 'vscode';
+import * as lc from 'vscode-languageclient';
+
+import { Server } from './server';
+
+interface InlayHintsParams {
+    textDocument: lc.TextDocumentIdentifier;
+}
+
+interface InlayHint {
+    range: lc.Range;
+    kind: 'parameterHint' | 'chainingHint';
+    label: string;
+}
+
+const hintColor = new vscode.ThemeColor('editorCodeLens.foreground');
+
+const parameterHintDecorationType = vscode.window.createTextEditorDecorationType(
+    {
+        before: { color: hintColor, fontStyle: 'italic' }
+    }
+);
+
+const chainingHintDecorationType = vscode.window.createTextEditorDecorationType(
+    {
+        after: { color: hintColor, fontStyle: 'italic', margin: '0 0 0 1em' }
+    }
+);
+
+export async function updateHints(editor: vscode.TextEditor | undefined) {
+    if (!editor || editor.document.languageId !== 'rust') {
+        return;
+    }
+    const { parameterHintsOn, chainingHintsOn } = Server.config;
+    if (!parameterHintsOn && !chainingHintsOn) {
+        editor.setDecorations(parameterHintDecorationType, []);
+        editor.setDecorations(chainingHintDecorationType, []);
+        return;
+    }
+    const params: InlayHintsParams = {
+        textDocument: { uri: editor.document.uri.toString() }
+    };
+    const hints = await Server.client.sendRequest<InlayHint[]>(
+        'm/inlayHints',
+        params
+    );
+    const asRange = (range: lc.Range) =>
+        Server.client.protocol2CodeConverter.asRange(range);
+    const parameterHints = hints
+        .filter(hint => parameterHintsOn && hint.kind === 'parameterHint')
+        .map(hint => ({
+            range: asRange(hint.range),
+            renderOptions: { before: { contentText: `${hint.label}: ` } }
+        }));
+    const chainingHints = hints
+        .filter(hint => chainingHintsOn && hint.kind === 'chainingHint')
+        .map(hint => ({
+            range: asRange(hint.range),
+            renderOptions: { after: { contentText: hint.label } }
+        }));
+    editor.setDecorations(parameterHintDecorationType, parameterHints);
+    editor.setDecorations(chainingHintDecorationType, chainingHints);
+}
@@ -1,3 +1,4 @@
+import * as onSave from './on_save';
 import * as publishDecorations from './publish_decorations';
 
-export { publishDecorations };
+export { onSave, publishDecorations };
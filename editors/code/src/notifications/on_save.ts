@@ -0,0 +1,22 @@
+import * as vscode from 'vscode';
+import * as lc from 'vscode-languageclient';
+
+import { createTask, Runnable } from '../commands/runnables';
+
+export interface OnSaveParams {
+    textDocument: lc.TextDocumentIdentifier;
+    cargoCheck?: Runnable;
+}
+
+export function handle(params: OnSaveParams) {
+    if (!params.cargoCheck) {
+        return;
+    }
+    const task = createTask(params.cargoCheck);
+    task.group = vscode.TaskGroup.Build;
+    task.presentationOptions = {
+        reveal: vscode.TaskRevealKind.Silent,
+        panel: vscode.TaskPanelKind.Dedicated
+    };
+    return vscode.tasks.executeTask(task);
+}
@@ -1,3 +1,4 @@
+import * as vscode from 'vscode';
 import * as lc from 'vscode-languageclient';
 
 import { Config } from './config';
@@ -9,7 +10,8 @@ export class Server {
     public static client: lc.LanguageClient;
 
     public static start(
-        notificationHandlers: Iterable<[string, lc.GenericNotificationHandler]>
+        notificationHandlers: Iterable<[string, lc.GenericNotificationHandler]>,
+        requestHandlers: Iterable<[string, lc.GenericRequestHandler<any, any>]>
     ) {
         const run: lc.Executable = {
             command: this.config.raLspServerPath,
@@ -20,10 +22,16 @@ export class Server {
             debug: run
         };
         const clientOptions: lc.LanguageClientOptions = {
-            documentSelector: [{ scheme: 'file', language: 'rust' }],
-            initializationOptions: {
-                publishDecorations: true
-            }
+            documentSelector: [
+                { scheme: 'file', language: 'rust' },
+                // Only the links, goto and member completion are provided.
+                { scheme: 'file', pattern: '**/Cargo.toml' }
+            ],
+            synchronize: {
+                // Changes made outside of the editor, like `git checkout`.
+                fileEvents: vscode.workspace.createFileSystemWatcher('**/*.rs')
+            },
+            initializationOptions: this.config.serverSettings()
         };
 
         Server.client = new lc.LanguageClient(
@@ -56,11 +64,27 @@ export class Server {
                 }
             }
         };
+        // Source changes are expanded as snippets by `applySnippetChange`.
+        Server.client.registerFeature(new SnippetTextEditFeature());
         Server.client.onReady().then(() => {
             for (const [type, handler] of notificationHandlers) {
                 Server.client.onNotification(type, handler);
             }
+            for (const [type, handler] of requestHandlers) {
+                Server.client.onRequest(type, handler);
+            }
         });
         Server.client.start();
     }
 }
+
+// Tells the server that the edits of its source changes can be snippets.
+class SnippetTextEditFeature implements lc.StaticFeature {
+    public fillClientCapabilities(capabilities: lc.ClientCapabilities) {
+        const experimental: any = capabilities.experimental || {};
+        experimental.snippetTextEdit = true;
+        capabilities.experimental = experimental;
+    }
+
+    public initialize() {}
+}